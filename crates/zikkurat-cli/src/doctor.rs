@@ -0,0 +1,323 @@
+//! 🩺 Doctor - самодиагностика всего стека
+//!
+//! `ziggurat-unified doctor` проверяет модели, размерность эмбеддингов,
+//! доступность хранилищ, архетипы, GPU и прогоняет короткий end-to-end цикл
+
+use anyhow::Result;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Instant;
+
+use crate::demiurge::ArchetypeLoader;
+use crate::priests::device::select_device;
+use crate::priests::embeddings::{Embedder, EmbeddingEngine};
+use crate::priests::remote_embeddings::{EmbeddingBackend, RemoteEmbedder};
+use crate::totems::episodic::persistence::PersistenceManager;
+use crate::totems::episodic::DialogueManager;
+use crate::totems::semantic::persistence::SemanticPersistenceManager;
+use crate::{load_pipeline, remote_embedder_config, resolve_path, user_data_dir, Args};
+
+const GREEN: &str = "\x1b[32m";
+const YELLOW: &str = "\x1b[33m";
+const RED: &str = "\x1b[31m";
+const RESET: &str = "\x1b[0m";
+
+/// Результат отдельной проверки
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CheckStatus {
+    Pass,
+    Warn,
+    Fail,
+}
+
+impl CheckStatus {
+    fn label(&self) -> String {
+        match self {
+            CheckStatus::Pass => format!("{}PASS{}", GREEN, RESET),
+            CheckStatus::Warn => format!("{}WARN{}", YELLOW, RESET),
+            CheckStatus::Fail => format!("{}FAIL{}", RED, RESET),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CheckResult {
+    pub name: &'static str,
+    pub status: CheckStatus,
+    pub detail: String,
+}
+
+/// Отчёт диагностики
+#[derive(Debug, Default)]
+pub struct DoctorReport {
+    pub checks: Vec<CheckResult>,
+}
+
+impl DoctorReport {
+    fn push(&mut self, name: &'static str, status: CheckStatus, detail: impl Into<String>) {
+        let check = CheckResult {
+            name,
+            status,
+            detail: detail.into(),
+        };
+        println!("  [{}] {:<22} {}", check.status.label(), check.name, check.detail);
+        self.checks.push(check);
+    }
+
+    fn record(&mut self, name: &'static str, result: Result<String>) {
+        match result {
+            Ok(detail) => self.push(name, CheckStatus::Pass, detail),
+            Err(e) => self.push(name, CheckStatus::Fail, format!("{:#}", e)),
+        }
+    }
+
+    fn count(&self, status: CheckStatus) -> usize {
+        self.checks.iter().filter(|c| c.status == status).count()
+    }
+
+    pub fn has_failures(&self) -> bool {
+        self.count(CheckStatus::Fail) > 0
+    }
+
+    fn print_summary(&self) {
+        let color = if self.has_failures() { RED } else { GREEN };
+        println!(
+            "\n{}🩺 {} passed, {} warnings, {} failed{}",
+            color,
+            self.count(CheckStatus::Pass),
+            self.count(CheckStatus::Warn),
+            self.count(CheckStatus::Fail),
+            RESET
+        );
+    }
+}
+
+/// Запускает все проверки и печатает цветной отчёт
+pub fn run_doctor(args: &Args, skip_generation: bool) -> Result<DoctorReport> {
+    println!("🩺 ZIGGURAT DOCTOR - checking the stack\n");
+    let mut report = DoctorReport::default();
+
+    check_model_files(args, &mut report);
+    check_gpu(args, &mut report);
+
+    let embedder = check_embedder(args, &mut report);
+    if let Some(embedder) = &embedder {
+        check_embedding_dim(embedder.as_ref(), &mut report);
+    }
+
+    report.record("persistence", check_persistence());
+    report.record("archetypes", check_archetypes());
+
+    match &embedder {
+        Some(embedder) => report.record("memory round trip", check_memory_round_trip(embedder.clone())),
+        None => report.push("memory round trip", CheckStatus::Warn, "skipped: no embedder"),
+    }
+
+    if skip_generation {
+        report.push("generation", CheckStatus::Warn, "skipped (--skip-generation)");
+    } else {
+        report.record("generation", check_generation(args));
+    }
+
+    report.print_summary();
+    Ok(report)
+}
+
+fn check_model_files(args: &Args, report: &mut DoctorReport) {
+    let local = resolve_path("models/mistral-7b-instruct");
+    let required = ["tokenizer.json", "model.safetensors.index.json", "config.json"];
+    let missing: Vec<_> = required
+        .iter()
+        .filter(|f| !local.join(f).exists())
+        .collect();
+
+    if missing.is_empty() {
+        report.push("llm files", CheckStatus::Pass, local.display().to_string());
+    } else {
+        let model_id = args
+            .model_id
+            .clone()
+            .unwrap_or_else(|| "mistralai/Mistral-7B-Instruct-v0.2".to_string());
+        report.push(
+            "llm files",
+            CheckStatus::Warn,
+            format!("local model missing {:?}, will download {} from HF Hub", missing, model_id),
+        );
+    }
+
+    if args.embedding_backend != EmbeddingBackend::Local {
+        report.push("embedding files", CheckStatus::Pass, format!("not needed: {} backend", args.embedding_backend.as_str()));
+        return;
+    }
+    let embedding_path = resolve_path(&args.embedding_path);
+    let missing: Vec<_> = ["config.json", "tokenizer.json", "model.safetensors"]
+        .iter()
+        .filter(|f| !embedding_path.join(f).exists())
+        .collect();
+    if missing.is_empty() {
+        report.push("embedding files", CheckStatus::Pass, embedding_path.display().to_string());
+    } else {
+        report.push(
+            "embedding files",
+            CheckStatus::Fail,
+            format!("{} is missing {:?}", embedding_path.display(), missing),
+        );
+    }
+}
+
+fn check_gpu(args: &Args, report: &mut DoctorReport) {
+    let cuda = candle_core::utils::cuda_is_available();
+    let metal = candle_core::utils::metal_is_available();
+
+    match select_device(args.cpu) {
+        Ok(device) if device.is_cpu() && !args.cpu => report.push(
+            "gpu",
+            CheckStatus::Warn,
+            format!("no GPU selected (cuda: {}, metal: {}), running on CPU", cuda, metal),
+        ),
+        Ok(device) => report.push("gpu", CheckStatus::Pass, format!("{:?}", device)),
+        Err(e) => report.push("gpu", CheckStatus::Fail, format!("{:#}", e)),
+    }
+}
+
+fn check_embedder(args: &Args, report: &mut DoctorReport) -> Option<Arc<dyn Embedder>> {
+    let path = resolve_path(&args.embedding_path);
+    let start = Instant::now();
+    let engine: Result<Arc<dyn Embedder>> = match args.embedding_backend {
+        EmbeddingBackend::Local => EmbeddingEngine::new(&path.to_string_lossy(), candle_core::Device::Cpu)
+            .map(|engine| Arc::new(engine) as Arc<dyn Embedder>),
+        _ => RemoteEmbedder::connect(remote_embedder_config(args)).map(|engine| Arc::new(engine) as Arc<dyn Embedder>),
+    };
+    match engine {
+        Ok(engine) => {
+            report.push(
+                "embedding engine",
+                CheckStatus::Pass,
+                format!("loaded in {:.1}s", start.elapsed().as_secs_f32()),
+            );
+            Some(engine)
+        }
+        Err(e) => {
+            report.push("embedding engine", CheckStatus::Fail, format!("{:#}", e));
+            None
+        }
+    }
+}
+
+/// Сверяет фактическую размерность с сохранённой в metadata.json
+fn check_embedding_dim(embedder: &dyn Embedder, report: &mut DoctorReport) {
+    let probe = match embedder.embed("doctor probe") {
+        Ok(v) => v,
+        Err(e) => {
+            report.push("embedding dim", CheckStatus::Fail, format!("embed failed: {:#}", e));
+            return;
+        }
+    };
+
+    if probe.len() != embedder.embedding_dim() {
+        report.push(
+            "embedding dim",
+            CheckStatus::Fail,
+            format!("engine reports {} but produced {}", embedder.embedding_dim(), probe.len()),
+        );
+        return;
+    }
+
+    let stored = PersistenceManager::new(Some(&user_data_dir()), false)
+        .and_then(|p| p.get_stats());
+    match stored {
+        Ok(meta) if meta.total_turns > 0 && meta.embedding_dim != probe.len() => report.push(
+            "embedding dim",
+            CheckStatus::Fail,
+            format!(
+                "stored memory uses dim {}, engine produces {}",
+                meta.embedding_dim,
+                probe.len()
+            ),
+        ),
+        Ok(_) => report.push("embedding dim", CheckStatus::Pass, format!("{}", probe.len())),
+        Err(e) => report.push("embedding dim", CheckStatus::Warn, format!("metadata unreadable: {:#}", e)),
+    }
+}
+
+fn check_persistence() -> Result<String> {
+    let persistence = PersistenceManager::new(Some(&user_data_dir()), false)?;
+    let probe = persistence.memory_dir().join(".doctor_probe");
+    std::fs::write(&probe, b"ok")?;
+    let read_back = std::fs::read(&probe)?;
+    std::fs::remove_file(&probe)?;
+    anyhow::ensure!(read_back == b"ok", "probe file content mismatch");
+
+    let sessions = persistence.load_sessions()?.map(|s| s.len()).unwrap_or(0);
+
+    let semantic = SemanticPersistenceManager::new(Some(&user_data_dir().join("semantic")))?;
+    let concepts = crate::utils::block_on(semantic.load())?.map(|c| c.len()).unwrap_or(0);
+
+    Ok(format!(
+        "{} writable, {} sessions, {} concepts",
+        persistence.memory_dir().display(),
+        sessions,
+        concepts
+    ))
+}
+
+fn check_archetypes() -> Result<String> {
+    let ids = ArchetypeLoader::list_ids()?;
+    anyhow::ensure!(!ids.is_empty(), "no archetypes found in config/archetypes");
+
+    let mut broken = Vec::new();
+    for id in &ids {
+        if let Err(e) = ArchetypeLoader::load(id) {
+            broken.push(format!("{}: {}", id, e));
+        }
+    }
+    anyhow::ensure!(broken.is_empty(), "failed to parse: {}", broken.join("; "));
+
+    Ok(format!("{} parsed ({})", ids.len(), ids.join(", ")))
+}
+
+/// Сохраняет и загружает обмен репликами во временном каталоге
+fn check_memory_round_trip(embedder: Arc<dyn Embedder>) -> Result<String> {
+    let tmp = std::env::temp_dir().join(format!("ziggurat-doctor-{}", std::process::id()));
+    let result = memory_round_trip_in(&tmp, embedder);
+    let _ = std::fs::remove_dir_all(&tmp);
+    result
+}
+
+fn memory_round_trip_in(dir: &Path, embedder: Arc<dyn Embedder>) -> Result<String> {
+    let persistence = PersistenceManager::new(Some(dir), false)?;
+
+    let mut manager = DialogueManager::new(embedder.clone(), "doctor".to_string());
+    manager.add_exchange_blocking(
+        "My favourite colour is teal".to_string(),
+        "Noted, teal it is.".to_string(),
+    )?;
+    persistence.save_with_embeddings_blocking(&manager, embedder.embedding_dim())?;
+
+    let (mut loaded, _) = persistence
+        .load_with_embeddings_blocking(embedder, "doctor".to_string())?
+        .ok_or_else(|| anyhow::anyhow!("saved memory was not found on reload"))?;
+    let hits = loaded.find_similar_dialogues_blocking("what colour do I like?", 1)?;
+    anyhow::ensure!(!hits.is_empty(), "reloaded memory returned no matches");
+
+    Ok("save → load → search ok".to_string())
+}
+
+fn check_generation(args: &Args) -> Result<String> {
+    let device = select_device(args.cpu)?;
+    let start = Instant::now();
+    let mut pipeline = load_pipeline(args, &device)?;
+    let load_secs = start.elapsed().as_secs_f32();
+
+    let start = Instant::now();
+    let prompt = pipeline.chat_template.instruction("Say OK.");
+    let output = pipeline.run(&prompt, 8, args.seed)?;
+    anyhow::ensure!(!output.trim().is_empty(), "model produced empty output");
+
+    Ok(format!(
+        "load {:.1}s, 8 tokens in {:.1}s: {:?}",
+        load_secs,
+        start.elapsed().as_secs_f32(),
+        output.trim()
+    ))
+}
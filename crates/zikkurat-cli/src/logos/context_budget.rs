@@ -0,0 +1,182 @@
+//! Token budget for the context sections of the prompt
+//!
+//! Memory sections used to be bounded in characters (`MAX_DIALOGUE_LENGTH` per
+//! memory, 512 chars per turn), which says little about how many tokens they
+//! take. Here every section is measured with the model's tokenizer, and while
+//! the context is over budget the section with the lowest priority loses its
+//! last lines - the least relevant ones, providers put the best match first.
+//! A section that would shrink below a useful size is dropped whole.
+
+use anyhow::Result;
+
+use crate::totems::context::Section;
+use crate::truncate_text;
+
+/// Sections are joined with a blank line
+const SECTION_SEPARATOR_TOKENS: usize = 2;
+/// A section cut shorter than this is dropped instead
+const MIN_SECTION_CHARS: usize = 80;
+
+/// Tokens available to the context sections
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ContextBudget {
+    pub max_tokens: usize,
+}
+
+/// Tokens of one section before and after fitting
+#[derive(Debug, Clone, PartialEq)]
+pub struct SectionUsage {
+    pub name: String,
+    pub priority: i32,
+    pub before: usize,
+    pub after: usize,
+}
+
+/// What fitting did to the sections, for `--explain`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BudgetReport {
+    pub max_tokens: usize,
+    pub sections: Vec<SectionUsage>,
+}
+
+impl ContextBudget {
+    pub fn new(max_tokens: usize) -> Self {
+        Self { max_tokens }
+    }
+
+    /// Trims `sections` in place until their total fits the budget, lowest
+    /// priority first (the later registered one on a tie)
+    pub fn fit(&self, sections: &mut [Section], count_tokens: impl Fn(&str) -> Result<usize>) -> Result<BudgetReport> {
+        let measure = |section: &Section| -> Result<usize> {
+            if section.body.trim().is_empty() {
+                return Ok(0);
+            }
+            Ok(count_tokens(&section.render())? + SECTION_SEPARATOR_TOKENS)
+        };
+        let mut tokens = sections.iter().map(measure).collect::<Result<Vec<_>>>()?;
+        let mut report = BudgetReport {
+            max_tokens: self.max_tokens,
+            sections: sections
+                .iter()
+                .zip(&tokens)
+                .map(|(s, &t)| SectionUsage { name: s.name.clone(), priority: s.priority, before: t, after: t })
+                .collect(),
+        };
+
+        loop {
+            let total: usize = tokens.iter().sum();
+            if total <= self.max_tokens {
+                break;
+            }
+            let Some(i) = (0..sections.len())
+                .filter(|&i| tokens[i] > 0)
+                .min_by_key(|&i| (sections[i].priority, std::cmp::Reverse(i)))
+            else {
+                break;
+            };
+            let excess = total - self.max_tokens;
+            let section = &mut sections[i];
+            if excess >= tokens[i] {
+                section.body.clear();
+            } else {
+                trim_section(section, excess, &count_tokens)?;
+            }
+            let after = measure(section)?;
+            // no progress means the section cannot be cut any further
+            if after >= tokens[i] {
+                section.body.clear();
+                tokens[i] = 0;
+            } else {
+                tokens[i] = after;
+            }
+            report.sections[i].after = tokens[i];
+        }
+        Ok(report)
+    }
+}
+
+/// Drops trailing lines of the section worth at least `excess` tokens; a single
+/// remaining line is cut in proportion
+fn trim_section(section: &mut Section, excess: usize, count_tokens: &impl Fn(&str) -> Result<usize>) -> Result<()> {
+    let mut lines: Vec<&str> = section.body.lines().collect();
+    let mut removed = 0;
+    while lines.len() > 1 && removed < excess {
+        let line = lines.pop().unwrap_or_default();
+        // +1 for the newline joining the lines
+        removed += count_tokens(line)? + 1;
+    }
+    // a sub-header left without its items goes too
+    while lines.len() > 1 && lines.last().is_some_and(|l| l.trim().is_empty() || l.trim_end().ends_with(':')) {
+        lines.pop();
+    }
+    let mut body = lines.join("\n");
+
+    if removed < excess {
+        let tokens = count_tokens(&body)?.max(1);
+        let chars = body.chars().count();
+        let keep = chars * tokens.saturating_sub(excess - removed) / tokens;
+        body = if keep < MIN_SECTION_CHARS { String::new() } else { truncate_text(&body, keep) };
+    }
+    section.body = body;
+    Ok(())
+}
+
+impl BudgetReport {
+    pub fn tokens(&self) -> usize {
+        self.sections.iter().map(|s| s.after).sum()
+    }
+
+    /// Report for `--explain`
+    pub fn explain(&self) -> String {
+        let mut report = format!("CONTEXT: {} of {} tokens", self.tokens(), self.max_tokens);
+        for s in self.sections.iter().filter(|s| s.before > 0) {
+            let change = match s.after {
+                after if after == s.before => String::new(),
+                0 => " (dropped)".to_string(),
+                _ => format!(" (cut from {})", s.before),
+            };
+            report.push_str(&format!("\n  {} [{}]: {}{}", s.name, s.priority, s.after, change));
+        }
+        report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn section(name: &str, priority: i32, body: &str) -> Section {
+        let mut section = Section::new(format!("{}:", name.to_uppercase()), body);
+        section.name = name.to_string();
+        section.priority = priority;
+        section
+    }
+
+    #[test]
+    fn test_fit_by_priority() -> Result<()> {
+        // one token per word
+        let count = |s: &str| Ok(s.split_whitespace().count());
+        let memories = "user likes green tea in the morning\nuser went hiking last summer\nuser has a cat";
+        let mut sections = vec![
+            section("persona", 60, "the persona remembers the user's name"),
+            section("episodic", 30, memories),
+            section("style", 10, "short answers"),
+        ];
+        let full = ContextBudget::new(1000).fit(&mut sections, count)?;
+        // title + body + separator
+        assert_eq!(full.tokens(), 9 + 19 + 5);
+
+        // style goes first, then episodic loses its last lines
+        let report = ContextBudget::new(20).fit(&mut sections, count)?;
+        assert_eq!(report.tokens(), 19);
+        assert!(sections[2].body.is_empty());
+        assert_eq!(sections[1].body, "user likes green tea in the morning");
+        assert_eq!(sections[0].body, "the persona remembers the user's name");
+        assert!(report.explain().contains("style [10]: 0 (dropped)"));
+
+        let report = ContextBudget::new(3).fit(&mut sections, count)?;
+        assert_eq!(report.tokens(), 0);
+        assert!(sections.iter().all(|s| s.body.is_empty()));
+        Ok(())
+    }
+}
@@ -0,0 +1,234 @@
+//! Paced delivery of answers
+//!
+//! Chat frontends (Telegram, Discord) should not dump one wall of text: the
+//! answer is split into messages at paragraph/sentence boundaries, each one
+//! preceded by a typing indicator whose duration follows the message length.
+//! Frontends implement [`DeliverySink`]; the terminal uses [`ConsoleSink`] (`--pace`).
+
+use anyhow::Result;
+use std::io::Write;
+use std::time::Duration;
+
+/// How an answer is paced
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pacing {
+    /// Simulated typing speed
+    pub chars_per_second: f32,
+    pub min_typing: Duration,
+    pub max_typing: Duration,
+    /// Preferred message size; longer answers are split
+    pub chunk_chars: usize,
+    /// Reading pause between messages
+    pub pause: Duration,
+}
+
+impl Default for Pacing {
+    fn default() -> Self {
+        Self {
+            chars_per_second: 30.0,
+            min_typing: Duration::from_millis(500),
+            max_typing: Duration::from_secs(4),
+            chunk_chars: 600,
+            pause: Duration::from_millis(700),
+        }
+    }
+}
+
+/// One message of a paced answer
+#[derive(Debug, Clone, PartialEq)]
+pub struct Chunk {
+    pub text: String,
+    /// How long the typing indicator is shown before the message
+    pub typing: Duration,
+}
+
+/// Where paced messages go (bot API, terminal)
+pub trait DeliverySink {
+    /// Shows a typing indicator for `duration` (blocking)
+    fn typing(&mut self, duration: Duration) -> Result<()>;
+
+    fn send(&mut self, text: &str) -> Result<()>;
+
+    fn pause(&mut self, duration: Duration) -> Result<()> {
+        std::thread::sleep(duration);
+        Ok(())
+    }
+}
+
+impl Pacing {
+    pub fn typing_for(&self, text: &str) -> Duration {
+        let secs = text.chars().count() as f32 / self.chars_per_second.max(1.0);
+        Duration::from_secs_f32(secs).clamp(self.min_typing, self.max_typing)
+    }
+
+    /// Splits an answer into messages with their typing durations
+    pub fn plan(&self, text: &str) -> Vec<Chunk> {
+        let limit = self.chunk_chars.max(1);
+        let mut chunks: Vec<String> = Vec::new();
+        let mut current = String::new();
+
+        for block in blocks(text) {
+            let pieces = if block.chars().count() > limit {
+                split_long(&block, limit)
+            } else {
+                vec![block]
+            };
+            for piece in pieces {
+                if !current.is_empty() && current.chars().count() + 2 + piece.chars().count() > limit {
+                    chunks.push(std::mem::take(&mut current));
+                }
+                if !current.is_empty() {
+                    current.push_str("\n\n");
+                }
+                current.push_str(&piece);
+            }
+        }
+        if !current.is_empty() {
+            chunks.push(current);
+        }
+
+        chunks
+            .into_iter()
+            .map(|text| Chunk {
+                typing: self.typing_for(&text),
+                text,
+            })
+            .collect()
+    }
+
+    /// Delivers an answer message by message
+    pub fn deliver(&self, text: &str, sink: &mut dyn DeliverySink) -> Result<()> {
+        for (i, chunk) in self.plan(text).iter().enumerate() {
+            if i > 0 {
+                sink.pause(self.pause)?;
+            }
+            sink.typing(chunk.typing)?;
+            sink.send(&chunk.text)?;
+        }
+        Ok(())
+    }
+}
+
+/// Paragraphs; a fenced code block stays in one piece
+fn blocks(text: &str) -> Vec<String> {
+    let mut blocks = Vec::new();
+    let mut current = String::new();
+    let mut in_fence = false;
+
+    for line in text.lines() {
+        if line.trim_start().starts_with("```") {
+            in_fence = !in_fence;
+        }
+        if line.trim().is_empty() && !in_fence {
+            if !current.trim().is_empty() {
+                blocks.push(current.trim_end().to_string());
+            }
+            current.clear();
+            continue;
+        }
+        if !current.is_empty() {
+            current.push('\n');
+        }
+        current.push_str(line);
+    }
+    if !current.trim().is_empty() {
+        blocks.push(current.trim_end().to_string());
+    }
+    blocks
+}
+
+/// Splits an oversized block at sentence ends, then at spaces, then anywhere
+fn split_long(block: &str, limit: usize) -> Vec<String> {
+    let mut pieces = Vec::new();
+    let mut rest = block.trim();
+
+    while rest.chars().count() > limit {
+        let head: String = rest.chars().take(limit).collect();
+        let cut = head
+            .rfind(['.', '!', '?', '\n'])
+            .map(|i| i + 1)
+            .filter(|&i| i > head.len() / 3)
+            .or_else(|| head.rfind(' ').filter(|&i| i > 0))
+            .unwrap_or(head.len());
+        pieces.push(rest[..cut].trim_end().to_string());
+        rest = rest[cut..].trim_start();
+    }
+    if !rest.is_empty() {
+        pieces.push(rest.to_string());
+    }
+    pieces
+}
+
+/// Terminal stand-in for a chat: "печатает…" then the message
+pub struct ConsoleSink;
+
+impl DeliverySink for ConsoleSink {
+    fn typing(&mut self, duration: Duration) -> Result<()> {
+        print!("   ✍️  печатает…");
+        std::io::stdout().flush()?;
+        std::thread::sleep(duration);
+        print!("\r\x1b[2K");
+        std::io::stdout().flush()?;
+        Ok(())
+    }
+
+    fn send(&mut self, text: &str) -> Result<()> {
+        println!("{}\n", text);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Default)]
+    struct Recorder {
+        events: Vec<String>,
+    }
+
+    impl DeliverySink for Recorder {
+        fn typing(&mut self, duration: Duration) -> Result<()> {
+            self.events.push(format!("typing {}ms", duration.as_millis()));
+            Ok(())
+        }
+
+        fn send(&mut self, text: &str) -> Result<()> {
+            self.events.push(text.to_string());
+            Ok(())
+        }
+
+        fn pause(&mut self, _duration: Duration) -> Result<()> {
+            self.events.push("pause".to_string());
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_plan_and_deliver() {
+        let pacing = Pacing {
+            chunk_chars: 30,
+            ..Default::default()
+        };
+
+        let text = "Short intro.\n\nSecond paragraph is here. It has two sentences.\n\n```\nfn main() {}\n\nx();\n```";
+        let plan = pacing.plan(text);
+        let texts: Vec<&str> = plan.iter().map(|c| c.text.as_str()).collect();
+        assert_eq!(
+            texts,
+            [
+                "Short intro.",
+                "Second paragraph is here.",
+                "It has two sentences.",
+                "```\nfn main() {}\n\nx();\n```",
+            ]
+        );
+        assert!(plan.iter().all(|c| c.typing >= pacing.min_typing && c.typing <= pacing.max_typing));
+
+        // one long answer: typing capped, a single message for short text
+        assert_eq!(pacing.typing_for(&"a".repeat(10_000)), pacing.max_typing);
+        let mut sink = Recorder::default();
+        pacing.deliver("Hi!", &mut sink).unwrap();
+        assert_eq!(sink.events, ["typing 500ms", "Hi!"]);
+    }
+}
@@ -0,0 +1,147 @@
+//! Live events of a turn
+//!
+//! A frontend that renders live typing and memory activity (the `--serve`
+//! WebSocket) subscribes with [`subscribe`]; `process_query`, the generation
+//! loop and the extraction jobs report what happens through [`emit`]. Without a
+//! subscriber nothing is built: emitters check [`is_active`] first. Turns of other
+//! clients run under [`silence`], so they never reach the subscriber.
+
+use serde::Serialize;
+use std::cell::Cell;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+/// What happened during a turn
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum TurnEvent {
+    /// Answer text generated since the previous token event
+    Token { text: String },
+    /// A memory source put entries into the prompt
+    MemoryHit { source: String, items: Vec<String> },
+    /// A concept was learned from the turn or confirmed by it
+    ConceptExtracted {
+        /// `semantic` or `persona`
+        store: String,
+        text: String,
+        category: String,
+        confidence: f32,
+        /// The concept was already known, the turn changed it
+        updated: bool,
+    },
+    /// The persona's state after the turn
+    PersonaEvolved {
+        persona: String,
+        interactions: u64,
+        /// Relationship with the current user, 0.0 - 1.0
+        affection: Option<f32>,
+        trust: Option<f32>,
+    },
+}
+
+type Listener = Box<dyn FnMut(&TurnEvent) + Send>;
+
+static LISTENER: Mutex<Option<Listener>> = Mutex::new(None);
+static ACTIVE: AtomicBool = AtomicBool::new(false);
+
+thread_local! {
+    static SILENCED: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Keeps the listener registered; dropping it unsubscribes
+#[cfg_attr(not(feature = "server"), allow(dead_code))]
+#[must_use = "the listener is removed when the subscription is dropped"]
+pub struct Subscription(());
+
+impl Drop for Subscription {
+    fn drop(&mut self) {
+        ACTIVE.store(false, Ordering::Release);
+        *LISTENER.lock().unwrap_or_else(|e| e.into_inner()) = None;
+    }
+}
+
+/// Sends every event to `listener` until the subscription is dropped. There is one
+/// listener per process (the server lets one WebSocket client in at a time), a new
+/// one replaces it
+#[cfg_attr(not(feature = "server"), allow(dead_code))]
+pub fn subscribe(listener: impl FnMut(&TurnEvent) + Send + 'static) -> Subscription {
+    *LISTENER.lock().unwrap_or_else(|e| e.into_inner()) = Some(Box::new(listener));
+    ACTIVE.store(true, Ordering::Release);
+    Subscription(())
+}
+
+/// Keeps this thread's events from the listener; dropping it lets them through again
+#[cfg_attr(not(feature = "server"), allow(dead_code))]
+#[must_use = "events are let through again when the guard is dropped"]
+pub struct Silence(bool);
+
+impl Drop for Silence {
+    fn drop(&mut self) {
+        SILENCED.set(self.0);
+    }
+}
+
+/// Events emitted on this thread until the guard is dropped are not reported: a turn
+/// of an HTTP client must not reach the WebSocket client. Background work started
+/// by such a turn checks [`is_silenced`] and silences itself too
+#[cfg_attr(not(feature = "server"), allow(dead_code))]
+pub fn silence() -> Silence {
+    Silence(SILENCED.replace(true))
+}
+
+pub fn is_silenced() -> bool {
+    SILENCED.get()
+}
+
+/// Someone listens to this thread: worth building events
+pub fn is_active() -> bool {
+    ACTIVE.load(Ordering::Acquire) && !is_silenced()
+}
+
+pub fn emit(event: TurnEvent) {
+    if !is_active() {
+        return;
+    }
+    if let Some(listener) = LISTENER.lock().unwrap_or_else(|e| e.into_inner()).as_mut() {
+        listener(&event);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_events_reach_subscriber_only() {
+        let token = || TurnEvent::Token { text: "При".to_string() };
+        emit(token());
+
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let subscription = subscribe({
+            let seen = Arc::clone(&seen);
+            move |event| seen.lock().unwrap().push(serde_json::to_value(event).unwrap())
+        });
+        assert!(is_active());
+        emit(token());
+        emit(TurnEvent::MemoryHit {
+            source: "episodic".to_string(),
+            items: vec!["- чай без сахара".to_string()],
+        });
+        {
+            let _silence = silence();
+            assert!(!is_active() && is_silenced());
+            emit(token());
+        }
+        assert!(is_active());
+        drop(subscription);
+        assert!(!is_active());
+        emit(token());
+
+        let seen = seen.lock().unwrap();
+        assert_eq!(seen.len(), 2);
+        assert_eq!(seen[0], serde_json::json!({ "type": "token", "text": "При" }));
+        assert_eq!(seen[1]["type"], "memory_hit");
+        assert_eq!(seen[1]["items"][0], "- чай без сахара");
+    }
+}
@@ -0,0 +1,120 @@
+//! KNOWLEDGE section budget
+//!
+//! Semantic search always returns `semantic_top_k` concepts, even when most of
+//! them barely match the query. Before the section goes into the prompt, concepts
+//! below a relevance floor are dropped and the rest are taken by relevance until
+//! the token cap is reached. What was dropped and why is kept for `--explain`.
+
+use anyhow::Result;
+
+/// Relevance floor and token cap for the KNOWLEDGE section
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct KnowledgeBudget {
+    /// Concepts less similar to the query than this are not injected
+    pub min_similarity: f32,
+    /// Token cap for the whole section (0 = no cap)
+    pub max_tokens: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DropReason {
+    BelowFloor,
+    OverBudget,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DroppedItem {
+    pub line: String,
+    pub similarity: f32,
+    pub reason: DropReason,
+}
+
+/// Lines that made it into the section and the ones that did not
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct KnowledgeSelection {
+    pub lines: Vec<String>,
+    pub tokens: usize,
+    pub dropped: Vec<DroppedItem>,
+}
+
+impl KnowledgeBudget {
+    /// Picks lines from `(similarity, line)` items, most relevant first. A line
+    /// over the remaining budget is skipped, a shorter one after it may still fit
+    pub fn select(
+        &self,
+        mut items: Vec<(f32, String)>,
+        count_tokens: impl Fn(&str) -> Result<usize>,
+    ) -> Result<KnowledgeSelection> {
+        items.sort_by(|a, b| b.0.total_cmp(&a.0));
+        let mut selection = KnowledgeSelection::default();
+        for (similarity, line) in items {
+            if similarity < self.min_similarity {
+                selection.dropped.push(DroppedItem { line, similarity, reason: DropReason::BelowFloor });
+                continue;
+            }
+            // +1 for the newline joining the lines
+            let tokens = count_tokens(&line)? + 1;
+            if self.max_tokens > 0 && selection.tokens + tokens > self.max_tokens {
+                selection.dropped.push(DroppedItem { line, similarity, reason: DropReason::OverBudget });
+                continue;
+            }
+            selection.tokens += tokens;
+            selection.lines.push(line);
+        }
+        Ok(selection)
+    }
+}
+
+impl KnowledgeSelection {
+    pub fn text(&self) -> String {
+        self.lines.join("\n")
+    }
+
+    /// Report for `--explain`
+    pub fn explain(&self, budget: &KnowledgeBudget) -> String {
+        let cap = match budget.max_tokens {
+            0 => "no cap".to_string(),
+            n => format!("cap {}", n),
+        };
+        let mut report = format!(
+            "KNOWLEDGE: {} concepts, {} tokens ({}), floor {:.2}",
+            self.lines.len(),
+            self.tokens,
+            cap,
+            budget.min_similarity
+        );
+        for item in &self.dropped {
+            let reason = match item.reason {
+                DropReason::BelowFloor => "below relevance floor",
+                DropReason::OverBudget => "over token cap",
+            };
+            report.push_str(&format!("\n  dropped ({}, {:.2}): {}", reason, item.similarity, item.line));
+        }
+        report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_floor_and_token_cap() {
+        let budget = KnowledgeBudget { min_similarity: 0.3, max_tokens: 8 };
+        // one token per word
+        let count = |s: &str| Ok(s.split_whitespace().count());
+        let items = vec![
+            (0.9, "user loves rust".to_string()),
+            (0.1, "user has a cat".to_string()),
+            (0.7, "user works remotely from a small town".to_string()),
+            (0.5, "user drinks tea".to_string()),
+        ];
+
+        let selection = budget.select(items, count).unwrap();
+        assert_eq!(selection.text(), "user loves rust\nuser drinks tea");
+        assert_eq!(selection.tokens, 8);
+        let reasons: Vec<DropReason> = selection.dropped.iter().map(|d| d.reason).collect();
+        assert_eq!(reasons, [DropReason::OverBudget, DropReason::BelowFloor]);
+        assert!(selection.explain(&budget).contains("dropped (below relevance floor, 0.10): user has a cat"));
+    }
+}
@@ -0,0 +1,103 @@
+//! Response length control
+//!
+//! Detects explicit requests for brevity ("коротко", "tl;dr") or depth
+//! ("подробно", "explain in detail") and turns them into a token budget
+//! and a style directive for the prompt.
+
+/// Desired response length expressed by the user
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LengthIntent {
+    Brief,
+    Default,
+    Detailed,
+}
+
+const BRIEF_MARKERS: &[&str] = &[
+    "коротко",
+    "кратко",
+    "вкратце",
+    "в двух словах",
+    "в одном предложении",
+    "одним предложением",
+    "без подробностей",
+    "tl;dr",
+    "tldr",
+    "briefly",
+    "in short",
+    "in one sentence",
+    "in a sentence",
+    "short answer",
+    "keep it short",
+];
+
+const DETAILED_MARKERS: &[&str] = &[
+    "подробно",
+    "подробнее",
+    "детально",
+    "развёрнуто",
+    "развернуто",
+    "во всех деталях",
+    "объясни подробно",
+    "in detail",
+    "in depth",
+    "detailed",
+    "elaborate",
+    "step by step",
+    "пошагово",
+];
+
+/// Token budget for a brief answer
+const BRIEF_MAX_TOKENS: usize = 128;
+/// Upper bound for a detailed answer in interactive mode
+const DETAILED_MAX_TOKENS: usize = 1024;
+
+impl LengthIntent {
+    /// Определяет желаемую длину ответа по тексту запроса
+    pub fn detect(text: &str) -> Self {
+        let lower = text.to_lowercase();
+        let brief = BRIEF_MARKERS.iter().any(|m| lower.contains(m));
+        let detailed = DETAILED_MARKERS.iter().any(|m| lower.contains(m));
+
+        match (brief, detailed) {
+            (true, false) => LengthIntent::Brief,
+            (false, true) => LengthIntent::Detailed,
+            // both or none - no explicit preference
+            _ => LengthIntent::Default,
+        }
+    }
+
+    /// Корректирует бюджет токенов, посчитанный по чертам персоны
+    pub fn max_tokens(&self, trait_based: usize, sample_len: usize) -> usize {
+        match self {
+            LengthIntent::Brief => trait_based.min(BRIEF_MAX_TOKENS),
+            LengthIntent::Default => trait_based,
+            LengthIntent::Detailed => trait_based.max(sample_len.min(DETAILED_MAX_TOKENS)),
+        }
+    }
+
+    /// Директива стиля для промпта
+    pub fn directive(&self) -> Option<&'static str> {
+        match self {
+            LengthIntent::Brief => Some("Отвечать кратко: 1-3 предложения, без вступлений"),
+            LengthIntent::Default => None,
+            LengthIntent::Detailed => Some("Отвечать развёрнуто и подробно, с примерами и пояснениями"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect_length_intent() {
+        assert_eq!(LengthIntent::detect("Коротко: что такое borrow checker?"), LengthIntent::Brief);
+        assert_eq!(LengthIntent::detect("explain lifetimes in one sentence"), LengthIntent::Brief);
+        assert_eq!(LengthIntent::detect("Расскажи подробно про async"), LengthIntent::Detailed);
+        assert_eq!(LengthIntent::detect("Что такое trait?"), LengthIntent::Default);
+
+        assert_eq!(LengthIntent::Brief.max_tokens(512, 2048), 128);
+        assert_eq!(LengthIntent::Detailed.max_tokens(512, 2048), 1024);
+        assert_eq!(LengthIntent::Default.max_tokens(256, 2048), 256);
+    }
+}
@@ -0,0 +1,16 @@
+pub mod context_budget;
+pub mod delivery;
+pub mod events;
+pub mod knowledge;
+pub mod length;
+pub mod providers;
+#[cfg(feature = "monitoring")]
+pub mod telemetry;
+pub mod warm_start;
+
+// Moved to zikkurat-core and zikkurat-inference; re-exported under the old paths
+pub use zikkurat_core::logos::{metrics, profiling};
+pub use zikkurat_inference::lora as inference;
+pub use zikkurat_inference::{
+    chat_template, context_window, echo, loop_guard, pipeline, prefix_cache, prompt_tokens, watchdog,
+};
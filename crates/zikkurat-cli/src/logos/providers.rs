@@ -0,0 +1,467 @@
+//! Built-in prompt context providers
+//!
+//! The memory sources the prompt builder used to assemble inline, as
+//! [`ContextProvider`]s. Their priorities keep the old cut order when the prompt
+//! does not fit the window: style memory goes first, then third-party sections,
+//! document excerpts, past dialogues, the current conversation, KNOWLEDGE and the
+//! group conversation note; the persona's view of the user goes last.
+
+use anyhow::Result;
+use std::cell::RefCell;
+use std::sync::{Arc, Mutex};
+
+use crate::demiurge::narrative::format_relationship_summary;
+use crate::demiurge::Persona;
+use crate::logos::knowledge::KnowledgeBudget;
+use crate::logos::metrics;
+use crate::totems::context::{ContextProvider, Section};
+use crate::totems::documents::DocumentStore;
+use crate::totems::episodic::style::format_style_memory;
+use crate::totems::episodic::summaries::{SummaryLevel, SummaryStore, CHUNK_TURNS};
+use crate::totems::episodic::DialogueManager;
+use crate::totems::semantic::perspective::{ConfidencePhrasing, HEDGE_NOTE};
+use crate::totems::semantic::{SemanticMemoryManager, TagFilter};
+use crate::utils::relative_time::humanize;
+use crate::{confidence_phrasing, truncate_text, user_utc_offset, Args, UnifiedPipeline, MAX_DIALOGUE_LENGTH, RESUME_CONTEXT_TURNS};
+
+pub const STYLE_PRIORITY: i32 = 10;
+pub const DOCUMENT_PRIORITY: i32 = 25;
+pub const EPISODIC_PRIORITY: i32 = 30;
+pub const SUMMARY_PRIORITY: i32 = 35;
+pub const CONVERSATION_PRIORITY: i32 = 40;
+pub const BOOKMARK_PRIORITY: i32 = 45;
+pub const KNOWLEDGE_PRIORITY: i32 = 50;
+pub const GROUP_PRIORITY: i32 = 55;
+pub const PERSONA_PRIORITY: i32 = 60;
+
+/// Episodic providers share one dialogue manager within a query
+pub type SharedDialogue<'a, 'b> = &'a RefCell<&'b mut DialogueManager>;
+
+/// Default `--memory-gate-threshold`: e5 puts unrelated texts around 0.75
+pub const DEFAULT_MEMORY_GATE_THRESHOLD: f32 = 0.8;
+/// Default `--documents-min-similarity`, on the same scale as the memory gate
+pub const DEFAULT_DOCUMENT_MIN_SIMILARITY: f32 = 0.8;
+
+/// Whether past conversations reach the prompt. A cheap vector search finds the stored
+/// turn closest to the query on every query; memory goes in only when it is close enough
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MemoryGate {
+    /// Similarity of the closest stored turn, 0 for empty memory
+    pub score: f32,
+    pub threshold: f32,
+}
+
+impl MemoryGate {
+    /// The persona's own threshold, if its archetype sets one, wins over the flag
+    pub fn new(score: f32, persona_threshold: Option<f32>, threshold: f32) -> Self {
+        Self { score, threshold: persona_threshold.unwrap_or(threshold) }
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.score >= self.threshold
+    }
+}
+
+/// The user asks for something they bookmarked ("show me the bookmarked explanation about lifetimes")
+pub fn asks_for_bookmark(query: &str) -> bool {
+    const MARKERS: &[&str] = &["bookmark", "закладк", "закладоч"];
+    let query = query.to_lowercase();
+    MARKERS.iter().any(|m| query.contains(m))
+}
+
+/// Section of user facts; hedged ones get a note on how to treat them
+fn hedged_section(title: &str, body: String) -> Section {
+    let hedged = ConfidencePhrasing::is_hedged(&body);
+    let section = Section::new(title, body);
+    if hedged {
+        section.with_footer(HEDGE_NOTE)
+    } else {
+        section
+    }
+}
+
+/// Last turns of the current session: when the query is close to something said
+/// before, or to pick up the thread of a resumed session
+pub struct ConversationProvider<'a, 'b> {
+    pub dialogue: SharedDialogue<'a, 'b>,
+    pub gate: MemoryGate,
+}
+
+impl ContextProvider for ConversationProvider<'_, '_> {
+    fn name(&self) -> &str {
+        "conversation"
+    }
+
+    fn priority(&self) -> i32 {
+        CONVERSATION_PRIORITY
+    }
+
+    fn provide(&mut self, _query: &str, _budget: usize) -> Result<Option<Section>> {
+        let dm = self.dialogue.borrow();
+        let context = if self.gate.is_open() {
+            dm.get_current_context(5)
+        } else if dm.was_resumed() {
+            dm.get_current_context(RESUME_CONTEXT_TURNS)
+        } else {
+            return Ok(None);
+        };
+        Ok(Some(Section::new("Current conversation:", context)))
+    }
+}
+
+/// Similar dialogues from past sessions, only when the memory gate is open
+pub struct EpisodicProvider<'a, 'b> {
+    pub dialogue: SharedDialogue<'a, 'b>,
+    pub top_k: usize,
+    pub gate: MemoryGate,
+}
+
+impl ContextProvider for EpisodicProvider<'_, '_> {
+    fn name(&self) -> &str {
+        "episodic"
+    }
+
+    fn priority(&self) -> i32 {
+        EPISODIC_PRIORITY
+    }
+
+    fn provide(&mut self, query: &str, _budget: usize) -> Result<Option<Section>> {
+        if !self.gate.is_open() {
+            return Ok(None);
+        }
+        let similar = self.dialogue.borrow_mut().find_similar_dialogues_blocking(query, self.top_k)?;
+        let memories: Vec<String> = similar.iter().map(|s| truncate_text(s, MAX_DIALOGUE_LENGTH)).collect();
+        let section = Section::new(
+            "═══════════════════════════════════════════════════════════════\n\
+             PREVIOUS CONVERSATION MEMORY (CRITICAL - YOU MUST USE THIS!)\n\
+             ═══════════════════════════════════════════════════════════════",
+            memories.join("\n\n"),
+        )
+        .with_footer(
+            "═══════════════════════════════════════════════════════════════\n\
+             INSTRUCTIONS:\n\
+             1. If user asks about preferences, past conversations, or remembers something - ANSWER directly using this memory\n\
+             2. If user asks \"what did I say about X\" - find it in this memory and repeat\n\
+             3. If memory contains the answer, say it clearly: \"You said [specific thing]\"\n\
+             4. Do NOT say \"I don't know\" if the answer is in this memory!\n\
+             ═══════════════════════════════════════════════════════════════",
+        );
+        Ok(Some(section))
+    }
+}
+
+/// Stored summaries of earlier parts of this conversation and of past sessions, when the
+/// memory gate is open: recent sessions one by one, older ones as weekly digests
+pub struct SummaryProvider<'a, 'b> {
+    pub dialogue: SharedDialogue<'a, 'b>,
+    pub store: &'a Mutex<SummaryStore>,
+    pub args: &'a Args,
+    pub gate: MemoryGate,
+}
+
+/// Past sessions and weeks summarized per query
+const SUMMARY_SECTION_LIMIT: usize = 3;
+
+impl ContextProvider for SummaryProvider<'_, '_> {
+    fn name(&self) -> &str {
+        "summaries"
+    }
+
+    fn priority(&self) -> i32 {
+        SUMMARY_PRIORITY
+    }
+
+    fn provide(&mut self, _query: &str, _budget: usize) -> Result<Option<Section>> {
+        if !self.gate.is_open() {
+            return Ok(None);
+        }
+        let dm = self.dialogue.borrow();
+        let current = dm.current_session().id;
+        let store = self.store.lock().unwrap();
+        let mut lines: Vec<String> = store
+            .session(&current)
+            .map(|s| s.chunks.as_slice())
+            .unwrap_or_default()
+            .iter()
+            .enumerate()
+            .map(|(i, chunk)| format!("[this conversation, turns {}-{}] {}", i * CHUNK_TURNS + 1, (i + 1) * CHUNK_TURNS, chunk))
+            .collect();
+        let now = chrono::Utc::now();
+        let offset = user_utc_offset(self.args);
+        for (at, level, text) in store.earlier(&current, now, SUMMARY_SECTION_LIMIT) {
+            let kind = if level == SummaryLevel::Week { "week" } else { "conversation" };
+            lines.push(format!("[{}, {}] {}", kind, humanize(at, now, offset), text));
+        }
+        if lines.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(Section::new("CONVERSATION SUMMARIES:", lines.join("\n"))))
+    }
+}
+
+/// Bookmarked exchanges matching the query, in full, when the user asks for a bookmark
+/// Who else takes part in a group conversation (`/persona group`) and, in a debate,
+/// what they already answered this turn
+pub struct GroupProvider<'a> {
+    pub note: &'a str,
+}
+
+impl ContextProvider for GroupProvider<'_> {
+    fn name(&self) -> &str {
+        "group"
+    }
+
+    fn priority(&self) -> i32 {
+        GROUP_PRIORITY
+    }
+
+    fn provide(&mut self, _query: &str, _budget: usize) -> Result<Option<Section>> {
+        Ok(Some(Section::new("GROUP CONVERSATION:", self.note)))
+    }
+}
+
+pub struct BookmarkProvider<'a, 'b> {
+    pub dialogue: SharedDialogue<'a, 'b>,
+}
+
+/// Bookmarks quoted per query
+const BOOKMARK_SECTION_LIMIT: usize = 2;
+/// Characters of a bookmarked answer quoted in the prompt
+const BOOKMARK_ANSWER_CHARS: usize = 800;
+
+impl ContextProvider for BookmarkProvider<'_, '_> {
+    fn name(&self) -> &str {
+        "bookmarks"
+    }
+
+    fn priority(&self) -> i32 {
+        BOOKMARK_PRIORITY
+    }
+
+    fn provide(&mut self, query: &str, _budget: usize) -> Result<Option<Section>> {
+        if !asks_for_bookmark(query) {
+            return Ok(None);
+        }
+        let hits = self.dialogue.borrow_mut().recall_bookmarks_blocking(query, BOOKMARK_SECTION_LIMIT)?;
+        if hits.is_empty() {
+            return Ok(None);
+        }
+        let body: Vec<String> = hits
+            .iter()
+            .map(|(_, b)| {
+                let note = if b.note.is_empty() { String::new() } else { format!(" ({})", b.note) };
+                format!(
+                    "[{}]{}\nUser: {}\nAssistant: {}",
+                    b.timestamp.format("%Y-%m-%d"),
+                    note,
+                    b.user,
+                    truncate_text(&b.assistant, BOOKMARK_ANSWER_CHARS)
+                )
+            })
+            .collect();
+        Ok(Some(
+            Section::new("BOOKMARKED EXCHANGES (the user asks to see these again):", body.join("\n\n"))
+                .with_footer("Repeat or build on the bookmarked answer the user refers to."),
+        ))
+    }
+}
+
+/// Excerpts of the user's files (`/docs ingest`) close enough to the query
+pub struct DocumentProvider<'a> {
+    pub store: &'a Mutex<DocumentStore>,
+    pub top_k: usize,
+    pub min_similarity: f32,
+}
+
+impl ContextProvider for DocumentProvider<'_> {
+    fn name(&self) -> &str {
+        "documents"
+    }
+
+    fn priority(&self) -> i32 {
+        DOCUMENT_PRIORITY
+    }
+
+    fn provide(&mut self, query: &str, _budget: usize) -> Result<Option<Section>> {
+        let hits = self.store.lock().unwrap().search_blocking(query, self.top_k)?;
+        let excerpts: Vec<String> = hits
+            .iter()
+            .filter(|hit| hit.similarity >= self.min_similarity)
+            .map(|hit| format!("[{}, part {}] {}", hit.title, hit.chunk + 1, hit.text.replace("\n\n", "\n")))
+            .collect();
+        metrics::record_lookup("documents", !excerpts.is_empty());
+        if excerpts.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(
+            Section::new("DOCUMENTS (excerpts from files the user added):", excerpts.join("\n\n"))
+                .with_footer("When you use an excerpt, name the document it comes from."),
+        ))
+    }
+}
+
+/// How the persona itself explained the topic in past sessions
+pub struct StyleProvider<'a, 'b> {
+    pub dialogue: SharedDialogue<'a, 'b>,
+    pub top_k: usize,
+}
+
+impl ContextProvider for StyleProvider<'_, '_> {
+    fn name(&self) -> &str {
+        "style"
+    }
+
+    fn priority(&self) -> i32 {
+        STYLE_PRIORITY
+    }
+
+    fn provide(&mut self, query: &str, _budget: usize) -> Result<Option<Section>> {
+        let phrasings = self.dialogue.borrow_mut().find_own_phrasings_blocking(query, self.top_k)?;
+        Ok(Some(Section::new(
+            "STYLE MEMORY (how you explained related topics before - keep the same terms and analogies, do not copy):",
+            format_style_memory(&phrasings),
+        )))
+    }
+}
+
+/// Concepts relevant to the query, within the KNOWLEDGE budget
+pub struct SemanticProvider<'a> {
+    pub semantic: &'a Arc<Mutex<SemanticMemoryManager>>,
+    pub pipeline: &'a Arc<Mutex<UnifiedPipeline>>,
+    pub args: &'a Args,
+}
+
+impl ContextProvider for SemanticProvider<'_> {
+    fn name(&self) -> &str {
+        "knowledge"
+    }
+
+    fn priority(&self) -> i32 {
+        KNOWLEDGE_PRIORITY
+    }
+
+    fn provide(&mut self, query: &str, _budget: usize) -> Result<Option<Section>> {
+        let args = self.args;
+        let mut sm = self.semantic.lock().unwrap();
+        let tag_filter = TagFilter::excluding(&args.exclude_tags);
+        let results = sm.search_with_tags_blocking(query, args.semantic_top_k, None, &tag_filter);
+        if results.is_empty() {
+            metrics::record_lookup("semantic", false);
+            return Ok(None);
+        }
+
+        let now = chrono::Utc::now();
+        let offset = user_utc_offset(args);
+        let phrasing = confidence_phrasing(args);
+        let mut line_ids = Vec::new();
+        let items: Vec<(f32, String)> = results
+            .iter()
+            .map(|(sim, concept)| {
+                let tags: String = concept.tags.iter().map(|t| format!(" #{}", t)).collect();
+                let text = phrasing.phrase(&concept.text, concept.confidence);
+                let when = humanize(concept.updated_at, now, offset);
+                let line = format!(
+                    "[{} {:.2}{}, {}] {}",
+                    concept.category,
+                    sim,
+                    tags,
+                    when,
+                    truncate_text(&text, 200)
+                );
+                line_ids.push((line.clone(), concept.id));
+                (*sim, line)
+            })
+            .collect();
+        let found = results.len();
+        let budget = KnowledgeBudget {
+            min_similarity: args.semantic_min_similarity,
+            max_tokens: args.knowledge_max_tokens,
+        };
+        let pipeline = self.pipeline.lock().unwrap();
+        let selection = budget.select(items, |line| pipeline.count_tokens(line))?;
+        drop(pipeline);
+        // injected concepts count as used: the retention limit evicts the least recently used
+        let used: Vec<_> = line_ids
+            .into_iter()
+            .filter(|(line, _)| selection.lines.contains(line))
+            .map(|(_, id)| id)
+            .collect();
+        sm.mark_used(&used);
+        drop(sm);
+        metrics::record_lookup("semantic", !selection.lines.is_empty());
+        if !args.quiet {
+            eprintln!(
+                "📚 Found {} relevant concepts, {} injected",
+                found,
+                selection.lines.len()
+            );
+        }
+        if args.explain {
+            eprintln!("🔎 {}", selection.explain(&budget));
+        }
+        Ok(Some(hedged_section("KNOWLEDGE:", selection.text())))
+    }
+}
+
+/// The persona's relationship with the user
+pub struct RelationshipProvider<'a> {
+    pub persona: &'a Persona,
+}
+
+impl ContextProvider for RelationshipProvider<'_> {
+    fn name(&self) -> &str {
+        "relationship"
+    }
+
+    fn priority(&self) -> i32 {
+        PERSONA_PRIORITY
+    }
+
+    fn provide(&mut self, _query: &str, _budget: usize) -> Result<Option<Section>> {
+        let summary = format_relationship_summary(&self.persona.narrative.narrative, self.persona.user.as_str());
+        Ok(Some(Section::new("RELATIONSHIP:", summary)))
+    }
+}
+
+/// Preferences and facts the persona knows about the user
+pub struct ProfileProvider<'a> {
+    pub persona: &'a Persona,
+    pub phrasing: ConfidencePhrasing,
+}
+
+impl ContextProvider for ProfileProvider<'_> {
+    fn name(&self) -> &str {
+        "profile"
+    }
+
+    fn priority(&self) -> i32 {
+        PERSONA_PRIORITY
+    }
+
+    fn provide(&mut self, _query: &str, _budget: usize) -> Result<Option<Section>> {
+        let summary = self.persona.get_user_knowledge_summary(&self.phrasing);
+        Ok(Some(hedged_section("USER PROFILE (use when relevant):", summary)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_memory_gate() {
+        let gate = MemoryGate::new(0.85, None, DEFAULT_MEMORY_GATE_THRESHOLD);
+        assert!(gate.is_open());
+        assert!(!MemoryGate::new(0.7, None, DEFAULT_MEMORY_GATE_THRESHOLD).is_open());
+        // the archetype's threshold overrides the flag
+        assert!(!MemoryGate::new(0.85, Some(0.9), DEFAULT_MEMORY_GATE_THRESHOLD).is_open());
+        assert!(MemoryGate::new(0.6, Some(0.5), DEFAULT_MEMORY_GATE_THRESHOLD).is_open());
+    }
+
+    #[test]
+    fn test_asks_for_bookmark() {
+        assert!(asks_for_bookmark("show me the bookmarked explanation about lifetimes"));
+        assert!(asks_for_bookmark("Покажи закладку про lifetimes"));
+        assert!(!asks_for_bookmark("What is a lifetime?"));
+    }
+}
@@ -0,0 +1,111 @@
+//! Trace export
+//!
+//! `--tracing` writes a chrome trace file; `--otlp-endpoint` (feature `otlp`)
+//! sends the same spans to an OpenTelemetry collector, so every query shows up
+//! in Jaeger as one trace: retrieval → prompt build → generation → extraction →
+//! persistence. Both sinks can be active at once.
+
+use anyhow::Result;
+use tracing_subscriber::prelude::*;
+
+/// Service name the traces are reported under
+#[cfg(feature = "otlp")]
+const SERVICE_NAME: &str = "ziggurat-mind";
+
+/// Keeps the exporters alive; flushes pending spans on drop
+#[derive(Default)]
+pub struct TelemetryGuard {
+    chrome: Option<tracing_chrome::FlushGuard>,
+    #[cfg(feature = "otlp")]
+    provider: Option<opentelemetry_sdk::trace::SdkTracerProvider>,
+}
+
+impl Drop for TelemetryGuard {
+    fn drop(&mut self) {
+        #[cfg(feature = "otlp")]
+        if let Some(provider) = self.provider.take() {
+            if let Err(e) = provider.shutdown() {
+                eprintln!("⚠️  Failed to flush OTLP traces: {}", e);
+            }
+        }
+        drop(self.chrome.take());
+    }
+}
+
+/// Installs the global subscriber with the requested sinks; without any it does nothing
+pub fn init(chrome: bool, otlp_endpoint: Option<&str>) -> Result<TelemetryGuard> {
+    let mut guard = TelemetryGuard::default();
+    if !chrome && otlp_endpoint.is_none() {
+        return Ok(guard);
+    }
+
+    let chrome_layer = chrome.then(|| {
+        let (layer, flush) = tracing_chrome::ChromeLayerBuilder::new().build();
+        guard.chrome = Some(flush);
+        layer
+    });
+
+    #[cfg(feature = "otlp")]
+    let otlp_layer = match otlp_endpoint {
+        Some(endpoint) => {
+            use opentelemetry::trace::TracerProvider;
+
+            let provider = otlp_provider(endpoint)?;
+            let layer = tracing_opentelemetry::layer().with_tracer(provider.tracer(SERVICE_NAME));
+            guard.provider = Some(provider);
+            Some(layer)
+        }
+        None => None,
+    };
+    #[cfg(not(feature = "otlp"))]
+    let otlp_layer: Option<tracing_subscriber::layer::Identity> = {
+        if otlp_endpoint.is_some() {
+            eprintln!("⚠️  --otlp-endpoint requires the `otlp` feature, ignoring");
+        }
+        None
+    };
+
+    tracing_subscriber::registry().with(chrome_layer).with(otlp_layer).try_init()?;
+    Ok(guard)
+}
+
+/// Batch exporter to `endpoint` over OTLP/HTTP; a bare `http://host:4318` gets `/v1/traces`
+#[cfg(feature = "otlp")]
+fn otlp_provider(endpoint: &str) -> Result<opentelemetry_sdk::trace::SdkTracerProvider> {
+    use opentelemetry_otlp::WithExportConfig;
+
+    let endpoint = traces_url(endpoint);
+    let exporter = opentelemetry_otlp::SpanExporter::builder()
+        .with_http()
+        .with_endpoint(endpoint.clone())
+        .with_timeout(std::time::Duration::from_secs(5))
+        .build()?;
+    println!("🛰️  OTLP traces: {}", endpoint);
+    Ok(opentelemetry_sdk::trace::SdkTracerProvider::builder()
+        .with_batch_exporter(exporter)
+        .with_resource(opentelemetry_sdk::Resource::builder().with_service_name(SERVICE_NAME).build())
+        .build())
+}
+
+/// Collector address → URL of its traces endpoint
+#[cfg(any(feature = "otlp", test))]
+pub fn traces_url(endpoint: &str) -> String {
+    let endpoint = endpoint.trim_end_matches('/');
+    if endpoint.ends_with("/v1/traces") {
+        endpoint.to_string()
+    } else {
+        format!("{}/v1/traces", endpoint)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_traces_url() {
+        assert_eq!(traces_url("http://localhost:4318"), "http://localhost:4318/v1/traces");
+        assert_eq!(traces_url("http://localhost:4318/"), "http://localhost:4318/v1/traces");
+        assert_eq!(traces_url("http://collector/v1/traces"), "http://collector/v1/traces");
+    }
+}
@@ -0,0 +1,123 @@
+//! Warm-start bundle
+//!
+//! Weights are mmapped and load quickly from the page cache, but the first
+//! turns after a restart are still slow: every prompt paragraph goes through
+//! the tokenizer again, the compose check of the prompt cache runs again, and
+//! the embedder recomputes vectors for the same memory texts. `--warm-start`
+//! keeps that state between processes.
+//!
+//! Shutdown is two-phase: [`WarmStart::capture`] copies the caches while the
+//! model is still alive, then [`WarmStart::save`] writes them to a temp file
+//! and renames it over the bundle, so a kill mid-write leaves the old bundle.
+//! A bundle saved for another model, revision or embedder is ignored.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+use crate::logos::prompt_tokens::{PromptTokenCache, PromptTokenSnapshot};
+use crate::priests::embeddings::Embedder;
+
+/// Bumped when the layout changes; older bundles are ignored
+const FORMAT_VERSION: u32 = 1;
+
+/// Caches that survive a restart
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WarmStart {
+    version: u32,
+    /// Model, revision and embedder the caches belong to
+    fingerprint: String,
+    saved_at: chrono::DateTime<chrono::Utc>,
+    /// Token ids of prompt paragraphs and the tokenizer's compose check
+    pub prompt_tokens: PromptTokenSnapshot,
+    /// Embeddings of recently seen texts
+    pub embeddings: Vec<(String, Vec<f32>)>,
+}
+
+impl WarmStart {
+    /// Phase one: copy the caches, cheap enough to run while the model is locked
+    pub fn capture(fingerprint: &str, prompt_tokens: &PromptTokenCache, embedder: &dyn Embedder) -> Self {
+        Self {
+            version: FORMAT_VERSION,
+            fingerprint: fingerprint.to_string(),
+            saved_at: chrono::Utc::now(),
+            prompt_tokens: prompt_tokens.snapshot(),
+            embeddings: embedder.cached_embeddings(),
+        }
+    }
+
+    /// Phase two: write the bundle atomically
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+            std::fs::create_dir_all(dir)?;
+        }
+        let tmp = path.with_extension("tmp");
+        std::fs::write(&tmp, bincode::serialize(self)?)?;
+        std::fs::rename(&tmp, path).with_context(|| format!("Failed to write {}", path.display()))?;
+        Ok(())
+    }
+
+    /// Bundle at `path` if it exists and was saved for `fingerprint`
+    pub fn load(path: &Path, fingerprint: &str) -> Result<Option<Self>> {
+        if !path.exists() {
+            return Ok(None);
+        }
+        let bundle: Self = bincode::deserialize(&std::fs::read(path)?)
+            .with_context(|| format!("Corrupted warm-start bundle {}", path.display()))?;
+        if bundle.version != FORMAT_VERSION || bundle.fingerprint != fingerprint {
+            return Ok(None);
+        }
+        Ok(Some(bundle))
+    }
+
+    /// Fills the caches of a freshly loaded model and embedder
+    pub fn restore(self, prompt_tokens: &PromptTokenCache, embedder: &dyn Embedder) {
+        prompt_tokens.restore(self.prompt_tokens);
+        embedder.warm_cache(self.embeddings);
+    }
+
+    pub fn saved_at(&self) -> chrono::DateTime<chrono::Utc> {
+        self.saved_at
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct NoCache;
+
+    impl Embedder for NoCache {
+        fn embed(&self, _text: &str) -> Result<Vec<f32>> {
+            Ok(vec![0.0; 4])
+        }
+
+        fn embedding_dim(&self) -> usize {
+            4
+        }
+    }
+
+    #[test]
+    fn test_warm_start_roundtrip() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("ziggurat-warm-start-{}", uuid::Uuid::new_v4()));
+        let path = dir.join("warm/bundle.bin");
+        let cache = PromptTokenCache::default();
+        cache.restore(PromptTokenSnapshot {
+            entries: vec![(7, vec![1, 2, 3])],
+            composes: Some(true),
+        });
+
+        WarmStart::capture("mistral@main", &cache, &NoCache).save(&path)?;
+        assert!(!path.with_extension("tmp").exists());
+        assert!(WarmStart::load(&path, "nemo@main")?.is_none());
+        assert!(WarmStart::load(&dir.join("missing.bin"), "mistral@main")?.is_none());
+
+        let bundle = WarmStart::load(&path, "mistral@main")?.expect("bundle for the same model");
+        let restored = PromptTokenCache::default();
+        bundle.restore(&restored, &NoCache);
+        assert_eq!(restored.snapshot(), cache.snapshot());
+
+        std::fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+}
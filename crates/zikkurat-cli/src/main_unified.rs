@@ -0,0 +1,5602 @@
+//! ZIGGURAT MIND - Unified Entry Point
+//!
+//! Unified architecture: Embedding Engine + Dialogue Memory + Mistral 7B
+//! Memory flow: Query → Embed → Search → Context → Generate → Save
+
+mod logos;
+mod doctor;
+mod memory_archive;
+mod repl;
+mod scenario;
+#[cfg(feature = "server")]
+mod server;
+#[cfg(feature = "server")]
+mod websocket;
+
+use zikkurat_core::{demiurge, priests, totems, utils};
+
+use anyhow::{Context, Error as E, Result};
+use candle_core::{DType, Device};
+use candle_transformers::models::mistral::{Config, Model as Mistral};
+use clap::{Parser, Subcommand};
+use regex::Regex;
+use std::io::Write;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use tokenizers::Tokenizer;
+
+use crate::logos::chat_template::ChatTemplate;
+use crate::logos::context_budget::ContextBudget;
+use crate::logos::context_window::ContextWindow;
+use crate::logos::delivery::{ConsoleSink, Pacing};
+use crate::logos::events::{self, TurnEvent};
+use crate::logos::echo::EchoModel;
+use crate::logos::inference::{var_builder_with_adapter, LoraAdapter};
+use crate::logos::length::LengthIntent;
+use crate::logos::metrics::{self, ExtractionResult};
+use crate::logos::profiling::{self, Stage};
+use crate::logos::warm_start::WarmStart;
+use crate::logos::watchdog::Watchdog;
+use crate::logos::loop_guard::{LoopGuard, DEFAULT_LOOP_NGRAM};
+use crate::logos::pipeline::{Backend, UnifiedPipeline};
+use crate::logos::prefix_cache::{PrefixCache, DEFAULT_MIN_PREFIX_TOKENS};
+use crate::priests::device::select_device;
+use crate::priests::dummy_embeddings::DummyEmbeddingEngine;
+use crate::priests::embeddings::{Embedder, EmbeddingConfig, EmbeddingEngine, EmbeddingScheduler, SchedulerConfig};
+use crate::priests::remote_embeddings::{EmbeddingBackend, RemoteEmbedder, RemoteEmbedderConfig};
+use crate::totems::consent::ConsentMode;
+use crate::totems::episodic::archive::SessionArchive;
+use crate::totems::episodic::analytics::{ActivityFilter, AnalyticsFormat};
+use crate::totems::episodic::export::{export_dataset, DatasetFilter, DatasetFormat, Rating};
+use crate::totems::episodic::persistence::{EmbeddingsIntegrityError, LoadScope, PersistenceManager};
+use crate::totems::episodic::segmentation::SessionBreak;
+use crate::totems::episodic::timeline;
+use crate::totems::episodic::transcript::{write_transcript, SanitizeRules};
+use crate::totems::grounding::{flag_note, self_check, Verdict};
+use crate::totems::jobs::{JobPriority, JobQueue, JobQueueConfig, Submitted};
+use crate::totems::language::Language;
+use crate::totems::maintenance::{MaintenanceScheduler, MaintenanceTask, SystemConfig, MAINTENANCE_STATE_FILE};
+use crate::totems::memory_export::{MemoryExportRequest, MemoryScope};
+use crate::totems::quota::{QuotaConfig, QuotaLimits, QuotaTracker};
+use crate::totems::retrieval::recall_cache::DEFAULT_REUSE_THRESHOLD;
+use crate::totems::retrieval::RetrievalConfig;
+use crate::totems::episodic::summaries::{run_summary_tasks, SummaryStore};
+use crate::totems::episodic::{DeferredSession, DialogueManager};
+use crate::totems::user::UserId;
+use crate::totems::semantic::{is_self_disclosure, utterance, FactsFile, SemanticMemoryManager, SensitiveAction, SensitiveKind};
+use crate::totems::semantic::concept::{Concept, TagFilter};
+use crate::totems::semantic::{AuditEntry, TurnRef};
+use crate::totems::semantic::SemanticDiff;
+use crate::totems::semantic::graph_query::{GraphEdge, GraphQuery, DEFAULT_HOPS, MAX_PATH_HOPS};
+use crate::totems::semantic::correction::{self, CorrectionRequest};
+use crate::totems::semantic::perspective::ConfidencePhrasing;
+use crate::totems::semantic::persistence::SemanticPersistenceManager;
+use crate::totems::significance::{SignificanceFilter, DEFAULT_ACK_MAX_CHARS};
+use crate::utils::relative_time::parse_utc_offset;
+use crate::utils::download::{DownloadConfig, ModelDownloader};
+use crate::utils::llm_json;
+use crate::utils::background::Pending;
+use crate::utils::crypto;
+use crate::utils::data_dir::{self, DataDirSource};
+use crate::utils::lock::MemoryLock;
+use crate::totems::context::{self, dedupe_sections, CommandProvider, ContextRegistry, Section};
+use crate::logos::providers::{
+    BookmarkProvider, ConversationProvider, DocumentProvider, EpisodicProvider, GroupProvider, MemoryGate,
+    ProfileProvider, RelationshipProvider, SemanticProvider, StyleProvider, SummaryProvider, DEFAULT_DOCUMENT_MIN_SIMILARITY,
+    DEFAULT_MEMORY_GATE_THRESHOLD,
+};
+use crate::totems::documents::{DocumentFormat, DocumentStore, Ingested};
+use crate::demiurge::{AdapterConfig, AddressStyle, Persona, ArchetypeLoader, ArchetypeWatcher};
+use crate::demiurge::{Archetype, EnsembleMode, PersonaEnsemble};
+use crate::demiurge::context::PersonaSessionContext;
+use crate::demiurge::topics::find_forbidden;
+use crate::demiurge::persona::extract_concepts_into;
+
+const DEFAULT_SAMPLE_LEN: usize = 2048;
+
+// Global verbose flag for debug output
+static VERBOSE: AtomicBool = AtomicBool::new(false);
+
+// Per-user request and token quotas, set once in main
+static QUOTA: std::sync::OnceLock<QuotaTracker> = std::sync::OnceLock::new();
+
+// Background extraction and maintenance with --background-jobs
+static JOBS: std::sync::OnceLock<JobQueue> = std::sync::OnceLock::new();
+
+// Process settings from --system-config, loaded once in main
+static SYSTEM: std::sync::OnceLock<SystemConfig> = std::sync::OnceLock::new();
+
+// Scheduled decay, merging, session cleanup and compaction in interactive and --serve modes
+static MAINTENANCE: std::sync::OnceLock<Arc<MaintenanceScheduler>> = std::sync::OnceLock::new();
+
+// Per-chunk, per-session and weekly summaries with --progressive-summaries
+static SUMMARIES: std::sync::OnceLock<Arc<std::sync::Mutex<SummaryStore>>> = std::sync::OnceLock::new();
+// Who else is in the group conversation (/persona group), for the persona answering now
+static GROUP_NOTE: std::sync::Mutex<Option<String>> = std::sync::Mutex::new(None);
+// Files added with /docs ingest
+static DOCUMENTS: std::sync::OnceLock<Arc<std::sync::Mutex<DocumentStore>>> = std::sync::OnceLock::new();
+// A summary pass is waiting in the job queue; the next turn does not plan another one
+static SUMMARY_PASS_QUEUED: AtomicBool = AtomicBool::new(false);
+
+// Memory directory chosen at startup (--data-dir, platform data dir or legacy memory_data)
+static DATA_DIR: std::sync::OnceLock<std::path::PathBuf> = std::sync::OnceLock::new();
+
+// Code-specific embedding model from --code-embedding-path, with the path as its name
+static CODE_EMBEDDER: std::sync::OnceLock<(String, Arc<dyn Embedder>)> = std::sync::OnceLock::new();
+
+// Whose memory is open: --user at startup, /user switches it
+static USER: std::sync::RwLock<Option<UserId>> = std::sync::RwLock::new(None);
+
+macro_rules! debug_log {
+    ($($arg:tt)*) => {
+        if VERBOSE.load(Ordering::Relaxed) {
+            eprintln!($($arg)*);
+        }
+    };
+}
+
+fn regex_fallback_extract(text: &str) -> totems::semantic::ExtractionResult {
+    let mut results = totems::semantic::ExtractionResult::new();
+
+    debug_log!("DEBUG [regex_fallback]: processing '{}'", text);
+
+    let patterns = vec![
+        (r#"(?i)я\s+люблю\s+(.+)"#, "preferences", 0.8),
+        (r#"(?i)я\s+не\s+люблю\s+(.+)"#, "preferences", 0.8),
+        (r#"(?i)мне\s+нравится\s+(.+)"#, "preferences", 0.8),
+        (r#"(?i)мне\s+не\s+нравится\s+(.+)"#, "preferences", 0.8),
+        (r#"(?i)я\s+предпочитаю\s+(.+)"#, "preferences", 0.8),
+        (r#"(?i)я\s+ненавижу\s+(.+)"#, "preferences", 0.8),
+        (r#"(?i)мой\s+(?:любимый|любимая|любимое)\s+(.+)"#, "preferences", 0.8),
+        (r#"(?i)i\s+love\s+(.+)"#, "preferences", 0.8),
+        (r#"(?i)i\s+don't\s+love\s+(.+)"#, "preferences", 0.8),
+        (r#"(?i)i\s+like\s+(.+)"#, "preferences", 0.8),
+        (r#"(?i)i\s+prefer\s+(.+)"#, "preferences", 0.8),
+        (r#"(?i)i\s+hate\s+(.+)"#, "preferences", 0.8),
+        (r#"(?i)my\s+favorite\s+(.+)"#, "preferences", 0.8),
+        (r#"(?i)я\s+хочу\s+(.+)"#, "goals", 0.7),
+        (r#"(?i)я\s+мечтаю\s+(.+)"#, "goals", 0.7),
+        (r#"(?i)я\s+планирую\s+(.+)"#, "goals", 0.7),
+        (r#"(?i)моя\s+цель\s+(.+)"#, "goals", 0.8),
+        (r#"(?i)моя\s+мечта\s+(.+)"#, "goals", 0.8),
+        (r#"(?i)i\s+want\s+(.+)"#, "goals", 0.7),
+        (r#"(?i)i\s+dream\s+(.+)"#, "goals", 0.7),
+        (r#"(?i)i\s+plan\s+(.+)"#, "goals", 0.7),
+        (r#"(?i)my\s+goal\s+(.+)"#, "goals", 0.8),
+        (r#"(?i)my\s+dream\s+(.+)"#, "goals", 0.8),
+        (r#"(?i)я\s+работаю\s+(.+)"#, "facts", 0.7),
+        (r#"(?i)я\s+знаю\s+(.+)"#, "skills", 0.7),
+        (r#"(?i)i\s+work\s+(.+)"#, "facts", 0.7),
+        (r#"(?i)i\s+know\s+(.+)"#, "skills", 0.7),
+    ];
+
+    for (pattern, category, confidence) in &patterns {
+        if pattern.contains("хочу") {
+            debug_log!("DEBUG [regex_fallback]: TESTING 'хочу' pattern: '{}' on '{}'", pattern, text);
+        }
+        if let Ok(re) = Regex::new(pattern) {
+            if let Some(cap) = re.captures(text) {
+                if let Some(m) = cap.get(1) {
+                    let extracted = m.as_str().trim().to_string();
+                    if !extracted.is_empty() && extracted.len() > 2 {
+                        debug_log!("DEBUG [regex_fallback]: MATCHED '{}' -> '{}'", pattern, extracted);
+                        results.push((format!("I {}", extract_english_pattern(pattern, &extracted)), category.to_string(), *confidence, totems::semantic::suggest_tags(&extracted)));
+                    } else if pattern.contains("хочу") {
+                        debug_log!("DEBUG [regex_fallback]: MATCHED '{}' but extracted empty or too short", pattern);
+                    }
+                } else if pattern.contains("хочу") {
+                    debug_log!("DEBUG [regex_fallback]: MATCHED '{}' but no capture group 1", pattern);
+                }
+            } else if pattern.contains("хочу") {
+                debug_log!("DEBUG [regex_fallback]: NO MATCH '{}' on '{}'", pattern, text);
+            }
+        } else if pattern.contains("хочу") {
+            debug_log!("DEBUG [regex_fallback]: REGEX ERROR for '{}'", pattern);
+        }
+    }
+
+    debug_log!("DEBUG [regex_fallback]: found {} results", results.len());
+    for (i, (text, cat, conf, tags)) in results.iter().enumerate() {
+        debug_log!("DEBUG [regex_fallback]: result {}: '{}' ({}, {:.2}, {:?})", i, text, cat, conf, tags);
+    }
+    results
+}
+
+fn extract_english_pattern(pattern: &str, russian: &str) -> String {
+    if pattern.contains("люблю") || pattern.contains("love") {
+        format!("love {}", russian)
+    } else if pattern.contains("нравится") || pattern.contains("like") {
+        format!("like {}", russian)
+    } else if pattern.contains("предпочитаю") || pattern.contains("prefer") {
+        format!("prefer {}", russian)
+    } else if pattern.contains("ненавижу") || pattern.contains("hate") {
+        format!("hate {}", russian)
+    } else if pattern.contains("хочу") || pattern.contains("want") {
+        format!("want to {}", russian)
+    } else if pattern.contains("мечта") || pattern.contains("dream") {
+        format!("dream of {}", russian)
+    } else if pattern.contains("планиру") || pattern.contains("plan") {
+        format!("plan to {}", russian)
+    } else if pattern.contains("цель") || pattern.contains("goal") {
+        format!("goal: {}", russian)
+    } else {
+        russian.to_string()
+    }
+}
+
+struct ConceptExtractorImpl {
+    pipeline: std::sync::Arc<std::sync::Mutex<UnifiedPipeline>>,
+}
+
+impl ConceptExtractorImpl {
+    fn new(pipeline: std::sync::Arc<std::sync::Mutex<UnifiedPipeline>>) -> Self {
+        Self { pipeline }
+    }
+}
+
+impl totems::semantic::ConceptExtractor for ConceptExtractorImpl {
+    fn extract(
+        &mut self,
+        user_query: &str,
+        _assistant_response: &str,
+        _session_id: &str,
+    ) -> Result<totems::semantic::ExtractionResult> {
+        let prompt = format!(
+            r#"<s>[INST] You are a knowledge extraction assistant. Extract ONLY explicit self-disclosed facts, preferences, rules, or skills that the USER directly states about themselves.
+
+CRITICAL RULES FOR RUSSIAN:
+- "я люблю X" = "I love X" (POSITIVE - extract!)
+- "я не люблю X" = "I don't love X" (NEGATIVE - extract!)
+- "нет, я люблю X" = "I love X" (CORRECTION - still POSITIVE, extract!)
+- "нет я люблю X" = "I love X" (CORRECTION - still POSITIVE, extract!)
+- "я предпочитаю X" = "I prefer X" (POSITIVE)
+- "мне нравится X" = "I like X" (POSITIVE)
+
+KEY PATTERNS TO DETECT:
+- "люблю" = love (POSITIVE)
+- "нравится" = like (POSITIVE)
+- "предпочитаю" = prefer (POSITIVE)
+- "не люблю" = don't love (NEGATIVE)
+- "не нравится" = don't like (NEGATIVE)
+
+Examples:
+- "я люблю пиццу" → {{"text":"I love pizza","category":"preferences","confidence":0.9,"tags":["food"]}}
+- "я не люблю суши" → {{"text":"I don't love sushi","category":"preferences","confidence":0.9,"tags":["food"]}}
+- "нет я люблю суши" → {{"text":"I love sushi","category":"preferences","confidence":0.9,"tags":["food"]}}
+- "предпочитаю кофе" → {{"text":"I prefer coffee","category":"preferences","confidence":0.9,"tags":["food"]}}
+- "у меня аллергия на орехи" → {{"text":"I am allergic to nuts","category":"facts","confidence":0.9,"tags":["health","food"]}}
+
+Tags are short lowercase topics such as work, family, health, food, hobby, tech.
+
+If no explicit self-disclosure found, return empty array [].
+
+User message:
+{user_query}
+
+Output format: [{{"text":"...","category":"...","confidence":0.8,"tags":["..."]}}]
+NO markdown, NO explanations, NO text before or after. Only JSON.
+[/INST]</s>"#,
+            user_query = user_query
+        );
+
+        let response = {
+            let mut pipeline = self.pipeline.lock().unwrap();
+            pipeline.clear_cache();
+            let prompt = pipeline.chat_template.adapt(&prompt);
+            pipeline.run(&prompt, 200, 0)?
+        };
+
+        let concepts: Vec<serde_json::Value> = match llm_json::parse_list(&response) {
+            Ok(c) => c,
+            Err(e) => {
+                debug_log!("DEBUG: {}, using regex fallback on user query", e);
+                return Ok(regex_fallback_extract(user_query));
+            }
+        };
+
+        let mut results = Vec::new();
+        for value in concepts {
+            let text = match value.get("text").and_then(|v| v.as_str()) {
+                Some(t) => t.to_string(),
+                None => continue,
+            };
+
+            let category = value
+                .get("category")
+                .and_then(|v| v.as_str())
+                .map(|s| s.to_string())
+                .unwrap_or_else(|| "general".to_string());
+
+            let confidence: f32 = value
+                .get("confidence")
+                .and_then(|v| v.as_f64())
+                .unwrap_or(0.5) as f32;
+
+            let tags: Vec<String> = value
+                .get("tags")
+                .and_then(|v| v.as_array())
+                .map(|arr| {
+                    arr.iter()
+                        .filter_map(|t| t.as_str())
+                        .map(|t| t.to_string())
+                        .collect()
+                })
+                .unwrap_or_default();
+
+            results.push((text, category, confidence, tags));
+        }
+
+        Ok(results)
+    }
+}
+
+struct ContextAnalyzerImpl {
+    pipeline: std::sync::Arc<std::sync::Mutex<UnifiedPipeline>>,
+}
+
+impl ContextAnalyzerImpl {
+    fn new(pipeline: std::sync::Arc<std::sync::Mutex<UnifiedPipeline>>) -> Self {
+        Self { pipeline }
+    }
+}
+
+impl totems::episodic::LlmPipeline for ContextAnalyzerImpl {
+    fn generate(&self, prompt: &str, max_tokens: usize) -> Result<String> {
+        let mut pipeline = self.pipeline.lock().unwrap();
+        pipeline.clear_cache();
+        // memory prompts of zikkurat-core are written for Mistral
+        let prompt = pipeline.chat_template.adapt(prompt);
+        pipeline.run(&prompt, max_tokens, 0)
+    }
+}
+
+#[derive(Subcommand, Debug, Clone)]
+enum Command {
+    /// Check model files, memory storage, archetypes and GPU, then run a tiny end-to-end test
+    Doctor {
+        /// Skip loading the LLM and the test generation
+        #[arg(long)]
+        skip_generation: bool,
+    },
+    /// Run YAML behavior scenarios (messages, persona switches, time jumps, memory expectations)
+    /// against the echo model and dummy embeddings
+    Scenario {
+        /// Scenario files or directories with *.yaml
+        #[arg(required = true)]
+        files: Vec<std::path::PathBuf>,
+    },
+    /// Check archetype files (IDs or paths to .json) for errors and likely mistakes
+    ValidateArchetype {
+        /// Archetypes to check (default: all in config/archetypes)
+        archetypes: Vec<String>,
+    },
+    /// Move the memory directory (episodic, semantic, archive) to a new place and check it loads there
+    MigrateData {
+        /// New location (default: the platform data dir, e.g. ~/.local/share/zikkurat-mind)
+        target: Option<std::path::PathBuf>,
+    },
+    /// Encrypt stored sessions, embeddings, summaries, the session archive, semantic memory with
+    /// its audit log, the knowledge graph and persona contexts with the memory key
+    /// (ZIGGURAT_MEMORY_KEY or the OS keyring), or decrypt them back
+    EncryptMemory {
+        /// Write the stores back as plaintext
+        #[arg(long)]
+        decrypt: bool,
+    },
+    /// Resume the conversation saved by --checkpoint, answer one prompt and checkpoint again
+    Continue {
+        /// Next message (read from stdin if omitted)
+        prompt: Option<String>,
+    },
+    /// Convert stored sessions into a fine-tuning JSONL dataset (PII is redacted)
+    ExportDataset {
+        /// sharegpt (one line per session) or alpaca (one line per turn)
+        #[arg(long, default_value = "sharegpt")]
+        format: DatasetFormat,
+        /// Only sessions of this persona
+        #[arg(long)]
+        persona: Option<String>,
+        /// Only turns rated with /good (turns rated /bad are always skipped)
+        #[arg(long)]
+        only_good: bool,
+        /// First day to include (YYYY-MM-DD)
+        #[arg(long)]
+        since: Option<chrono::NaiveDate>,
+        /// Last day to include (YYYY-MM-DD)
+        #[arg(long)]
+        until: Option<chrono::NaiveDate>,
+        /// Output file (stdout if omitted)
+        #[arg(long, short)]
+        output: Option<std::path::PathBuf>,
+    },
+    /// Export interaction analytics (per day/hour, heat map, sentiment, topics) from saved sessions
+    ExportAnalytics {
+        /// csv (one table) or json
+        #[arg(long, default_value = "json")]
+        format: AnalyticsFormat,
+        /// Only sessions of this persona
+        #[arg(long)]
+        persona: Option<String>,
+        /// First day to include (YYYY-MM-DD)
+        #[arg(long)]
+        since: Option<chrono::NaiveDate>,
+        /// Last day to include (YYYY-MM-DD)
+        #[arg(long)]
+        until: Option<chrono::NaiveDate>,
+        /// Output file (stdout if omitted)
+        #[arg(long, short)]
+        output: Option<std::path::PathBuf>,
+    },
+    /// Write one session as a Markdown transcript, e.g. to attach to a bug report
+    ExportSession {
+        /// Session ID or its prefix
+        id: String,
+        /// Redact identifying info and mask strong language
+        #[arg(long)]
+        sanitized: bool,
+        /// JSON rule set for --sanitized (default: PII and built-in profanity list)
+        #[arg(long, requires = "sanitized")]
+        rules: Option<std::path::PathBuf>,
+        /// Output file (stdout if omitted)
+        #[arg(long, short)]
+        output: Option<std::path::PathBuf>,
+    },
+    /// Export memory selectively: only semantic knowledge, one persona, a date range, without embeddings
+    ExportMemory {
+        /// all, episodic or semantic
+        #[arg(long, default_value = "all")]
+        scope: MemoryScope,
+        /// Only sessions of this persona and concepts learned in them
+        #[arg(long)]
+        persona: Option<String>,
+        /// First day to include (YYYY-MM-DD)
+        #[arg(long)]
+        since: Option<chrono::NaiveDate>,
+        /// Last day to include (YYYY-MM-DD)
+        #[arg(long)]
+        until: Option<chrono::NaiveDate>,
+        /// Drop turn embeddings (they are recomputed on load)
+        #[arg(long)]
+        no_embeddings: bool,
+        /// Output file (stdout if omitted)
+        #[arg(long, short)]
+        output: Option<std::path::PathBuf>,
+    },
+    /// Pack the whole memory (sessions with vectors, archived sessions, concepts, knowledge graph,
+    /// persona narratives) into a portable .zmx archive
+    Export {
+        /// Archive file, e.g. memory.zmx
+        #[arg(long, short)]
+        output: std::path::PathBuf,
+    },
+    /// Add what is missing from a .zmx archive to the memory; turns are re-embedded
+    /// if the archive was written with another embedding model
+    Import {
+        /// Archive file written by `export`
+        input: std::path::PathBuf,
+    },
+}
+
+#[derive(Parser, Debug, Clone)]
+#[command(author, version, about, long_about = None)]
+struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// Run on CPU rather than on GPU.
+    #[arg(long)]
+    cpu: bool,
+
+    /// Enable CUDA kernels.
+    #[arg(long)]
+    use_flash_attn: bool,
+
+    /// Prompt to process
+    #[arg(long)]
+    prompt: Option<String>,
+
+    /// Temperature for generation (0 = deterministic)
+    #[arg(long, default_value_t = 0.7)]
+    temperature: f64,
+
+    /// Nucleus sampling probability cutoff.
+    #[arg(long)]
+    top_p: Option<f64>,
+
+    /// Only sample among the top K samples.
+    #[arg(long)]
+    top_k: Option<usize>,
+
+    /// The seed to use when generating random samples.
+    #[arg(long, default_value_t = 299792458)]
+    seed: u64,
+
+    /// The length of the sample to generate (in tokens).
+    #[arg(long, short = 'n', default_value_t = DEFAULT_SAMPLE_LEN)]
+    sample_len: usize,
+
+    /// Embedding model path
+    #[arg(long, default_value = "models/embeddings")]
+    embedding_path: String,
+
+    /// Where embeddings come from: local (the model at --embedding-path), openai (any
+    /// OpenAI-compatible /v1/embeddings) or ollama. The API key is read from
+    /// ZIKKURAT_EMBEDDING_API_KEY, or OPENAI_API_KEY for openai
+    #[arg(long, default_value = "local")]
+    embedding_backend: EmbeddingBackend,
+
+    /// Embedding server URL (default: https://api.openai.com for openai,
+    /// http://localhost:11434 for ollama)
+    #[arg(long)]
+    embedding_url: Option<String>,
+
+    /// Embedding model on the server (default: text-embedding-3-small for openai,
+    /// nomic-embed-text for ollama)
+    #[arg(long)]
+    embedding_model: Option<String>,
+
+    /// Separate embedding model for code blocks in turns; by default code is
+    /// embedded with the main model into its own index
+    #[arg(long)]
+    code_embedding_path: Option<String>,
+
+    /// Texts embedded together in one model pass: turns, concepts and searches from all
+    /// threads share a queue (1 = embed each text on the calling thread)
+    #[arg(long, default_value_t = 16)]
+    embedding_batch_size: usize,
+
+    /// How long the first text of a batch waits for others, in milliseconds
+    #[arg(long, default_value_t = 2)]
+    embedding_flush_ms: u64,
+
+    /// Enable episodic memory
+    #[arg(long)]
+    enable_memory: bool,
+
+    /// Load only the active persona's sessions from the last N days at startup;
+    /// older and other personas' sessions load on demand (0 = all of the persona's sessions)
+    #[arg(long, default_value_t = 30)]
+    memory_window_days: i64,
+
+    /// What reaches long-term memory: always, or on-request (only turns the user asked
+    /// to remember with "remember this" / "запомни" or /remember)
+    #[arg(long, default_value = "always")]
+    memory_consent: ConsentMode,
+
+    /// Acknowledgments up to this many characters ("ok", "спасибо", "lol") stay in the
+    /// session but are not indexed or extracted (0 = keep everything)
+    #[arg(long, default_value_t = DEFAULT_ACK_MAX_CHARS)]
+    ack_max_chars: usize,
+
+    /// Extra acknowledgment words, comma-separated (e.g. "merci,danke")
+    #[arg(long, value_delimiter = ',')]
+    ack_words: Vec<String>,
+
+    /// Continue a past session (id or its prefix from /sessions search) instead of starting a new one
+    #[arg(long)]
+    resume_session: Option<String>,
+
+    /// Enable semantic memory (facts, rules, preferences)
+    #[arg(long)]
+    enable_semantic: bool,
+
+    /// Disable memory context after first exchange (workaround for Candle compatibility)
+    #[arg(long)]
+    disable_memory_context: bool,
+
+    /// Share memory_data owned by another instance: read it, never write to it
+    #[arg(long)]
+    read_only: bool,
+
+    /// Memory directory (default: the platform data dir, e.g. ~/.local/share/zikkurat-mind;
+    /// a legacy memory_data next to the checkout is used until `migrate-data` moves it)
+    #[arg(long)]
+    data_dir: Option<std::path::PathBuf>,
+
+    /// Whose memory to open: every user has their own episodic and semantic memory
+    /// (users/<id>/ in the data dir); /user switches in the REPL
+    #[arg(long, default_value_t = UserId::default())]
+    user: UserId,
+
+    /// Run without models: a rule-based echo model and dummy embeddings, memory is read-only
+    #[arg(long)]
+    smoke_test: bool,
+
+    /// Deterministic CI profile (`ci` feature): the smoke-test models, writable memory in a fresh
+    /// temp dir unless --data-dir is given, and a clock standing at 2024-01-01
+    #[cfg_attr(feature = "ci", arg(long))]
+    #[cfg_attr(not(feature = "ci"), arg(skip))]
+    ci: bool,
+
+    /// Keep prompt token ids and embedder caches in this file between runs for a faster first answer
+    #[arg(long)]
+    warm_start: Option<String>,
+
+    /// After each turn, print what it changed in memory: episodic entry, concepts, graph links
+    #[arg(long)]
+    memory_diff: bool,
+
+    /// Run concept extraction and maintenance in a background queue; generation always goes first
+    #[arg(long)]
+    background_jobs: bool,
+
+    /// Background jobs waiting at most; when full, low-priority ones are dropped first
+    #[arg(long, default_value_t = 32)]
+    job_queue_capacity: usize,
+
+    /// Keep summaries of every 10 turns, of each session and of each week in summaries.json,
+    /// updated after answers (in the background with --background-jobs); recall and the
+    /// greeting after a break use them instead of summarizing again
+    #[arg(long)]
+    progressive_summaries: bool,
+
+    /// JSON quotas: {"default": {...}, "users": {"id": {...}}} with requests_per_hour and tokens_per_day
+    #[arg(long, default_value = "config/quota.json")]
+    quota_config: String,
+
+    /// Requests per user per hour, overrides the default in --quota-config
+    #[arg(long)]
+    requests_per_hour: Option<u32>,
+
+    /// Prompt + answer tokens per user per day, overrides the default in --quota-config
+    #[arg(long)]
+    tokens_per_day: Option<u64>,
+
+    /// JSON list of shell-command context providers (name, command, title, priority, timeout_ms)
+    #[arg(long, default_value = "config/context_providers.json")]
+    context_providers: String,
+
+    /// JSON process settings: {"maintenance": {...}} with the decay, merge, session cleanup
+    /// and compaction intervals in hours (0 turns a task off), {"retrieval": {...}} with the
+    /// memory recall weights and thresholds
+    #[arg(long, default_value = "config/system.json")]
+    system_config: String,
+
+    /// Quiet mode - suppress debug output
+    #[arg(long, short = 'q')]
+    quiet: bool,
+
+    /// Abort a generation running longer than this many seconds (0 = no limit)
+    #[arg(long, default_value_t = 300)]
+    generation_timeout: u64,
+
+    /// Phrase length in tokens for repetition loop detection: a loop first gets a stronger
+    /// penalty, then generation stops at a sentence boundary (0 = off)
+    #[arg(long, default_value_t = DEFAULT_LOOP_NGRAM)]
+    loop_ngram: usize,
+
+    /// Keep the KV cache of a prompt prefix shared between turns if it is at least this many
+    /// tokens, so only the rest of the prompt is prefilled (0 = off)
+    #[arg(long, default_value_t = DEFAULT_MIN_PREFIX_TOKENS)]
+    prefix_cache_min_tokens: usize,
+
+    /// Deliver answers like a chat bot: typing indicator, then message by message
+    #[arg(long)]
+    pace: bool,
+
+    /// Simulated typing speed for --pace, characters per second
+    #[arg(long, default_value_t = 30.0)]
+    typing_speed: f32,
+
+    /// Enable verbose/debug output
+    #[arg(long, short = 'v')]
+    verbose: bool,
+
+    /// Number of similar dialogues to retrieve
+    #[arg(long, default_value_t = 5)]
+    memory_top_k: usize,
+
+    /// Past conversations reach the prompt only when the closest stored turn is at least this
+    /// similar to the query; an archetype's `memory_gate_threshold` overrides it
+    #[arg(long, default_value_t = DEFAULT_MEMORY_GATE_THRESHOLD)]
+    memory_gate_threshold: f32,
+
+    /// A follow-up this similar to the last full recall reuses its candidates (1.0 = off)
+    #[arg(long, default_value_t = DEFAULT_REUSE_THRESHOLD)]
+    recall_cache_threshold: f32,
+
+    /// Persona's past answers on the same topic shown as STYLE MEMORY (0 = off)
+    #[arg(long, default_value_t = 2)]
+    style_top_k: usize,
+
+    /// Excerpts of files added with /docs ingest put into the prompt per query (0 = off)
+    #[arg(long, default_value_t = 3)]
+    documents_top_k: usize,
+
+    /// Document excerpts less similar to the query than this stay out of the prompt
+    #[arg(long, default_value_t = DEFAULT_DOCUMENT_MIN_SIMILARITY)]
+    documents_min_similarity: f32,
+
+    /// Number of semantic concepts to retrieve
+    #[arg(long, default_value_t = 10)]
+    semantic_top_k: usize,
+
+    /// Concepts less similar to the query than this are not injected into KNOWLEDGE
+    #[arg(long, default_value_t = 0.3)]
+    semantic_min_similarity: f32,
+
+    /// A fact repeated in several prompt sections (USER PROFILE and KNOWLEDGE) is kept only in the
+    /// highest-priority one; items with embeddings at least this similar count as repeats
+    /// (0 = compare normalized text only)
+    #[arg(long, default_value_t = 0.92)]
+    context_dedupe_similarity: f32,
+
+    /// Before showing an answer that cites memory, ask the model to check it against the
+    /// retrieved entries and fix or flag claims they do not support (one extra short generation)
+    #[arg(long)]
+    self_check: bool,
+
+    /// Token cap for the KNOWLEDGE section of the prompt (0 = no cap)
+    #[arg(long, default_value_t = 300)]
+    knowledge_max_tokens: usize,
+
+    /// Token cap for all context sections together: memories, knowledge, profile, persona
+    /// (0 = whatever the context window leaves after the system prompt and the answer)
+    #[arg(long, default_value_t = 0)]
+    context_max_tokens: usize,
+
+    /// Concepts at least this confident are stated in the prompt as facts
+    #[arg(long, default_value_t = 0.85)]
+    certain_confidence: f32,
+
+    /// Concepts below this confidence are marked as unconfirmed guesses ("Maybe")
+    #[arg(long, default_value_t = 0.6)]
+    tentative_confidence: f32,
+
+    /// Explain prompt assembly: which concepts were injected or dropped and why
+    #[arg(long)]
+    explain: bool,
+
+    /// User's UTC offset for "yesterday" / "3 weeks ago" next to memories (e.g. +03:00; default: system time zone)
+    #[arg(long, value_parser = parse_utc_offset)]
+    timezone: Option<chrono::FixedOffset>,
+
+    /// Persona name for the session
+    #[arg(long, default_value = "assistant")]
+    persona: String,
+
+    /// Archetype to use (girlfriend, programmer, devops, scientist, philosopher)
+    #[arg(long, default_value = "programmer")]
+    archetype: String,
+
+    /// Re-read the persona's archetype file after it is edited; changes apply before the next message
+    #[arg(long)]
+    watch_archetype: bool,
+
+    /// Model ID to use
+    #[arg(long)]
+    model_id: Option<String>,
+
+    /// Context window cap in tokens for long-context models like Mistral-Nemo (0 = the model's full window).
+    /// The KV cache grows with every position, 128k on a 12B model needs ~20 GB
+    #[arg(long, default_value_t = 32768)]
+    max_context: usize,
+
+    /// Prompt format of the model: mistral, llama3, chatml or gemma.
+    /// Detected from the model's tokenizer_config.json and vocabulary when not set
+    #[arg(long)]
+    chat_template: Option<ChatTemplate>,
+
+    /// Model revision
+    #[arg(long, default_value = "main")]
+    revision: String,
+
+    /// HF Hub mirror to download models from instead of huggingface.co (also HF_ENDPOINT)
+    #[arg(long)]
+    hf_mirror: Option<String>,
+
+    /// Retries after a network error while downloading a model (partial files are resumed)
+    #[arg(long, default_value_t = 5)]
+    download_retries: usize,
+
+    /// Interactive mode - keep running for multiple queries
+    #[arg(long)]
+    interactive: bool,
+
+    /// Maximum number of sessions to keep in memory
+    #[arg(long, default_value_t = 50)]
+    max_sessions: usize,
+
+    /// Apply temporal decay to semantic concepts
+    #[arg(long)]
+    apply_decay: bool,
+
+    /// Show decay statistics
+    #[arg(long)]
+    decay_stats: bool,
+
+    /// Show knowledge graph statistics
+    #[arg(long)]
+    graph_stats: bool,
+
+    /// Extract relations from text
+    #[arg(long)]
+    extract_relations: bool,
+
+    /// Find related concepts
+    #[arg(long)]
+    find_related: Option<String>,
+
+    /// Write a chrome trace (trace-<timestamp>.json) with per-stage spans
+    #[arg(long)]
+    tracing: bool,
+
+    /// Send a trace per query to this OpenTelemetry collector over OTLP/HTTP, e.g. http://localhost:4318 (feature `otlp`)
+    #[arg(long)]
+    otlp_endpoint: Option<String>,
+
+    /// Serve Prometheus metrics on GET /metrics at this address (e.g. 127.0.0.1:9090)
+    #[arg(long, value_name = "ADDR")]
+    metrics_addr: Option<String>,
+
+    /// Run as an HTTP API service instead of the REPL: chat, sessions and memory search over REST (feature `server`)
+    #[arg(long, conflicts_with_all = ["interactive", "checkpoint"])]
+    serve: bool,
+
+    /// Port of the --serve API
+    #[arg(long, default_value_t = 8080)]
+    port: u16,
+
+    /// Address the --serve API listens on; 0.0.0.0 opens it to the network
+    #[arg(long, default_value = "127.0.0.1")]
+    host: String,
+
+    /// Facts file ingested into semantic memory and re-synced on change
+    /// (default: facts.md / facts.yaml in the working directory)
+    #[arg(long, value_name = "PATH")]
+    facts_file: Option<String>,
+
+    /// Concept tags never injected into the prompt (e.g. health,family)
+    #[arg(long, value_delimiter = ',')]
+    exclude_tags: Vec<String>,
+
+    /// Answer one prompt, save a resumable checkpoint and exit (for machines that can't keep the model resident).
+    /// Resume with `continue`. Implies --enable-memory
+    #[arg(long)]
+    checkpoint: bool,
+
+    /// Re-generate every recorded turn of a saved session (id or id prefix) and
+    /// check that the outputs match. Exits with code 1 on any mismatch
+    #[arg(long, value_name = "SESSION_ID")]
+    replay: Option<String>,
+}
+
+const MAX_DIALOGUE_LENGTH: usize = 100;
+
+fn get_memory_mb() -> u64 {
+    #[cfg(target_os = "linux")]
+    {
+        if let Ok(status) = std::fs::read_to_string("/proc/self/status") {
+            for line in status.lines() {
+                if line.starts_with("VmRSS:") {
+                    if let Some(val) = line.split_whitespace().nth(1) {
+                        return val.parse::<u64>().unwrap_or(0) / 1024;
+                    }
+                }
+            }
+        }
+    }
+    0
+}
+
+fn get_gpu_memory_mb() -> Option<u64> {
+    std::process::Command::new("nvidia-smi")
+        .args(&["--query-gpu=memory.used", "--format=csv,noheader,nounits"])
+        .output()
+        .ok()
+        .and_then(|output| {
+            let stdout = String::from_utf8_lossy(&output.stdout);
+            stdout.trim().parse::<u64>().ok()
+        })
+}
+
+fn log_memory_usage(_label: &str) {
+        // Debug memory info - uncomment if needed for debugging
+        // let mem_mb = get_memory_mb();
+        // if mem_mb > 0 {
+        //     eprintln!("DEBUG [{}]: RAM: {} MB", _label, mem_mb);
+        // }
+        // if let Some(gpu_mb) = get_gpu_memory_mb() {
+        //     eprintln!("DEBUG [{}]: VRAM: {} MB", _label, gpu_mb);
+        // }
+    }
+
+fn truncate_text(text: &str, max_chars: usize) -> String {
+    let char_count = text.chars().count();
+    if char_count <= max_chars {
+        return text.to_string();
+    }
+
+    let char_pos = text.char_indices().nth(max_chars);
+    match char_pos {
+        Some((byte_pos, _)) => {
+            let truncated = &text[..byte_pos];
+            if let Some(last_newline) = truncated.rfind('\n') {
+                truncated[..last_newline].to_string()
+            } else if let Some(last_space) = truncated.rfind(' ') {
+                truncated[..last_space].to_string() + "..."
+            } else {
+                truncated.to_string() + "..."
+            }
+        }
+        None => text.to_string(),
+    }
+}
+
+/// Текст запроса к модели; в шаблон чата модели его оборачивает вызывающий
+fn build_prompt_with_context(
+    user_input: &str,
+    sections: &[Section],
+    enable_memory: bool,
+    persona: Option<&Persona>,
+    address: AddressStyle,
+    length_intent: LengthIntent,
+    refusal: Option<&str>,
+) -> String {
+    let mut prompt_parts = Vec::new();
+
+    // Without a persona there is no STYLE CONSTRAINTS block - attach the length request to the query
+    let user_input = match (persona, length_intent.directive()) {
+        (None, Some(directive)) => format!("{}\n({})", user_input, directive),
+        _ => user_input.to_string(),
+    };
+
+    // Add Persona system prompt if available
+    if let Some(p) = persona {
+        prompt_parts.push(p.format_system_prompt());
+    }
+
+    // Context sections from the providers, in registration order
+    let context_parts: Vec<String> = sections
+        .iter()
+        .filter(|s| !s.body.is_empty())
+        .map(|s| s.render())
+        .collect();
+
+    if !context_parts.is_empty() {
+        prompt_parts.push(context_parts.join("\n\n"));
+    }
+
+    // Add directives as constraints
+    if let Some(p) = persona {
+        // Build directive constraints based on communication style
+        let mut constraints = Vec::new();
+
+        // Honorifics constraint: tracked across the session, see /address
+        constraints.push(address.constraint());
+
+        // Style-based constraints
+        match p.communication.style.as_str() {
+            "technical" => constraints.push("Давать технически точные ответы с примерами кода"),
+            "warm" => constraints.push("Отвечать тепло и поддерживающе"),
+            "academic" => constraints.push("Отвечать академично, с данными и фактами"),
+            "socratic" => constraints.push("Использовать вопросы для направления мысли"),
+            _ => {}
+        }
+
+        // Forbidden topic of the archetype: decline instead of answering
+        if let Some(refusal) = refusal {
+            constraints.push(refusal);
+        }
+
+        // Explicit length request from the user overrides trait-based verbosity
+        if let Some(directive) = length_intent.directive() {
+            constraints.push(directive);
+        }
+
+        // Trait-based constraints
+        let traits = p.get_all_traits();
+        if traits.get("pedagogical").unwrap_or(&0.5) > &0.7 && length_intent != LengthIntent::Brief {
+            constraints.push("Объяснять подробно и понятно");
+        }
+        if traits.get("humor").unwrap_or(&0.5) > &0.7 {
+            constraints.push("Добавлять юмор в ответы");
+        }
+        if traits.get("empathy").unwrap_or(&0.5) > &0.8 {
+            constraints.push("Проявлять эмпатию и понимание");
+        }
+
+        if !constraints.is_empty() {
+            prompt_parts.push(format!("STYLE CONSTRAINTS:\n{}", constraints.join("\n")));
+        }
+    }
+
+    let combined_context = prompt_parts.join("\n\n");
+
+    if !enable_memory && persona.is_none() {
+        user_input
+    } else if combined_context.is_empty() {
+        // No context - just be a friendly assistant
+        format!(
+            "You are {}, {}.\n\
+             \n\
+             {}\
+             \n\
+             User: {}\n\
+             \n\
+             Respond naturally and helpfully.",
+            persona.map(|p| p.name.as_str()).unwrap_or("a helpful assistant"),
+            persona.map(|p| p.description.as_str()).unwrap_or("friendly and supportive"),
+            persona.map(|p| p.communication.greeting.as_str()).unwrap_or(""),
+            user_input
+        )
+    } else {
+        // Has context - user asked about past
+        format!(
+            "You are {}, {}.\n\
+             \n\
+             IMPORTANT: The user is asking about their own preferences or past statements from earlier conversations.\n\
+             \n\
+             PAST MEMORY:\n{}\n\
+             \n\
+             {}\
+             \n\
+             YOUR TASK: Read the memory above and give a CONFIDENT, DIRECT answer.\n\
+             - The user is asking about THEIR preferences, not yours\n\
+             - Look for \"USER SAID: ...\" to find what they previously stated\n\
+             - Answer clearly: \"Your favorite car is Lamborghini\" or \"You said your favorite food is sushi\"\n\
+             - Do NOT say \"I don't know\" if the memory contains relevant information\n\
+             - Be specific and confident\n\
+             \n\
+             User's question: {}\n\
+             \n\
+             Your confident answer:",
+            persona.map(|p| p.name.as_str()).unwrap_or("a helpful assistant"),
+            persona.map(|p| p.description.as_str()).unwrap_or("friendly and supportive"),
+            combined_context,
+            persona.map(|p| p.communication.greeting.as_str()).unwrap_or(""),
+            user_input
+        )
+    }
+}
+
+/// Drops retrieved memories on the persona's forbidden topics; the current conversation stays as is
+fn keep_forbidden_topics_out(sections: &mut [Section], persona: &Persona, args: &Args) {
+    for section in sections.iter_mut().filter(|s| s.name != "conversation") {
+        let mut topics = Vec::new();
+        let removed = section.retain_lines(|line| match find_forbidden(&persona.forbidden_topics, line) {
+            Some(topic) => {
+                topics.push(topic.topic.as_str());
+                false
+            }
+            None => true,
+        });
+        if removed == 0 {
+            continue;
+        }
+        topics.sort_unstable();
+        topics.dedup();
+        tracing::info!(persona = %persona.archetype_id, section = %section.name, removed, "forbidden topic in memories");
+        if !args.quiet {
+            eprintln!(
+                "🚫 {} {} lines on forbidden topics ({}) kept out of the prompt",
+                removed,
+                section.name,
+                topics.join(", ")
+            );
+        }
+    }
+}
+
+/// Builds the prompt with context sections fitted to `budget` tokens of the prompt.
+/// What the sections may take is the budget minus the prompt without them (system
+/// prompt, constraints, query), capped by `--context-max-tokens`; the section with
+/// the lowest priority is cut first
+fn fit_prompt_to_window(
+    pipeline: &UnifiedPipeline,
+    budget: usize,
+    mut sections: Vec<Section>,
+    args: &Args,
+    build: impl Fn(&[Section]) -> String,
+) -> Result<String> {
+    let fixed = pipeline.encode_prompt(&build(&[]))?.len();
+    let mut context = budget.saturating_sub(fixed);
+    if args.context_max_tokens > 0 {
+        context = context.min(args.context_max_tokens);
+    }
+    let count = |text: &str| pipeline.count_tokens(text);
+    let report = ContextBudget::new(context).fit(&mut sections, count)?;
+    debug_log!("DEBUG: prompt without context {} tokens, {}", fixed, report.explain());
+    if args.explain {
+        eprintln!("🔎 {}", report.explain());
+    }
+
+    // Sections measured apart tokenize a little differently than the whole prompt
+    let mut context = report.tokens();
+    loop {
+        let prompt = build(&sections);
+        let tokens = pipeline.encode_prompt(&prompt)?.len();
+        // Nothing left to cut: `run` reports the prompt as too long
+        if tokens <= budget || context == 0 {
+            return Ok(prompt);
+        }
+        context = context.saturating_sub(tokens - budget);
+        debug_log!("DEBUG: prompt {} tokens > budget {}, context cut to {}", tokens, budget, context);
+        ContextBudget::new(context).fit(&mut sections, count)?;
+    }
+}
+
+/// Token budget for phrasing a knowledge-graph answer
+const GRAPH_ANSWER_MAX_TOKENS: usize = 96;
+
+/// Instruction that only rephrases facts found in the knowledge graph,
+/// without the chat template
+fn build_graph_phrasing_prompt(
+    user_input: &str,
+    answer: &totems::semantic::GraphAnswer,
+    persona: Option<&Persona>,
+) -> String {
+    format!(
+        "You are {}. Answer the user's question using ONLY the facts below, \
+         in one or two natural sentences and in the user's language. Do not add other items.\n\
+         \n\
+         FACTS FROM MEMORY:\n- {}\n\
+         \n\
+         Draft answer: {}\n\
+         \n\
+         User's question: {}",
+        persona.map(|p| p.name.as_str()).unwrap_or("a helpful assistant"),
+        answer.facts().join("\n- "),
+        answer.compose(),
+        user_input
+    )
+}
+
+/// Полный ход: контекст из памяти, генерация, сохранение, извлечение фактов.
+/// Возвращает ответ, показанный пользователю
+fn process_query(
+    prompt: &str,
+    pipeline_arc: &std::sync::Arc<std::sync::Mutex<UnifiedPipeline>>,
+    dialogue_manager: &mut Option<DialogueManager>,
+    semantic_manager: &mut Option<std::sync::Arc<std::sync::Mutex<SemanticMemoryManager>>>,
+    persistence_manager: &std::sync::Arc<totems::episodic::persistence::PersistenceManager>,
+    embedder: &Arc<dyn crate::priests::embeddings::Embedder>,
+    args: &Args,
+    persona: &mut Option<Persona>,
+) -> Result<String> {
+    log_memory_usage("process_query start");
+    profiling::begin_response();
+    // Root of the query's trace; stage timers and the spans below nest under it
+    let _query_span = tracing::info_span!(
+        "query",
+        prompt_chars = prompt.chars().count(),
+        persona = persona.as_ref().map_or("none", |p| p.archetype_id.as_str()),
+    )
+    .entered();
+
+    // Over quota: the persona says when to come back, the model is not called
+    let user = quota_user();
+    if let Some(Err(exceeded)) = QUOTA.get().map(|quota| quota.admit(user.as_str(), chrono::Utc::now())) {
+        debug_log!("DEBUG: Quota for {}: {}", user, exceeded);
+        println!("\n📝 You: {}", prompt);
+        println!("\n🤖 {}:", persona.as_ref().map_or("Assistant", |p| p.name.as_str()));
+        let reply = exceeded.reply(Language::detect(prompt));
+        println!("{}", reply);
+        return Ok(reply);
+    }
+
+    // Background jobs wait until the query is done
+    let _interactive = JOBS.get().map(JobQueue::interactive);
+    if JOBS.get().is_some() {
+        confirm_background_sensitive(semantic_manager, persona, args.interactive);
+    }
+
+    // Persona switched with memory isolation has no semantic link - keep its prompt clean
+    let semantic_enabled = args.enable_semantic
+        && persona.as_ref().is_none_or(|p| p.semantic_manager.is_some());
+
+    let correction_source = dialogue_manager
+        .as_ref()
+        .map_or_else(|| "manual".to_string(), |dm| dm.current_session().id.to_string());
+
+    // *actually ...* in the message rewrites the matching concept right away
+    if let (Some(request), Some(sm)) = (correction::parse_inline(prompt), semantic_manager.as_ref()) {
+        if semantic_enabled {
+            apply_user_correction(sm, &request, &correction_source);
+        }
+    }
+
+    // Conflicting facts would reach KNOWLEDGE together - ask which one is current first
+    if let Some(sm) = semantic_manager.as_ref().filter(|_| semantic_enabled && args.interactive) {
+        ask_memory_conflicts(sm, prompt, &correction_source, args);
+    }
+
+    // Вы/ты is tracked per user with hysteresis instead of re-detected per message
+    let address = match persona.as_mut() {
+        Some(p) => p.observe_address(prompt),
+        None => AddressStyle::detect(prompt).unwrap_or(AddressStyle::Informal),
+    };
+
+    // Get sampling parameters from Persona traits
+    let (temperature, max_tokens) = if let Some(ref p) = *persona {
+        let traits = p.get_all_traits();
+
+        // Temperature: analytical = lower temp, creative = higher
+        let temp = traits.get("analytical").copied().unwrap_or(0.5);
+        let temperature = if temp > 0.8 {
+            0.3  // Analytical - precise
+        } else if temp > 0.6 {
+            0.5  // Balanced
+        } else {
+            0.7  // Creative
+        };
+
+        // Max tokens: verbose = longer, concise = shorter
+        let verbose = traits.get("verbose").copied().unwrap_or(0.5);
+        let max_tokens = if verbose > 0.7 {
+            (args.sample_len as f32 * 0.5) as usize
+        } else {
+            (args.sample_len as f32 * 0.25) as usize
+        };
+
+        (Some(temperature), max_tokens.min(512)) // Cap at 512 tokens for interactive mode
+    } else {
+        // For interactive mode without persona, limit to 512 tokens
+        let max_tokens = if args.interactive {
+            (args.sample_len as f32 * 0.25) as usize
+        } else {
+            args.sample_len
+        };
+        (None, max_tokens.min(512))
+    };
+
+    // User asked for a short or detailed answer explicitly
+    let length_intent = LengthIntent::detect(prompt);
+    let max_tokens = length_intent.max_tokens(max_tokens, args.sample_len);
+    let window = pipeline_arc.lock().unwrap().window;
+    let max_tokens = window.answer_budget(max_tokens);
+    if length_intent != LengthIntent::Default {
+        debug_log!("DEBUG: length intent {:?}, max_tokens={}", length_intent, max_tokens);
+    }
+
+    let retrieval_timer = profiling::time(Stage::Retrieval);
+    let sections = {
+        // Sections render in registration order; plugins from --context-providers come last
+        let dialogue = dialogue_manager
+            .as_mut()
+            .filter(|_| !args.disable_memory_context)
+            .map(std::cell::RefCell::new);
+        // One cheap vector search decides whether past conversations are relevant at all
+        let score = match dialogue {
+            Some(ref dialogue) => dialogue.borrow_mut().recall_score_blocking(prompt)?,
+            None => 0.0,
+        };
+        if let Some(ref dialogue) = dialogue {
+            dialogue.borrow_mut().set_retrieval_config(retrieval_config(persona.as_ref()));
+        }
+        let persona_threshold = persona.as_ref().and_then(|p| p.memory_gate_threshold);
+        let gate = MemoryGate::new(score, persona_threshold, args.memory_gate_threshold);
+        debug_log!("DEBUG: memory gate: closest turn {:.2}, threshold {:.2}", gate.score, gate.threshold);
+        metrics::record_lookup("gate", gate.is_open());
+        let group_note = GROUP_NOTE.lock().unwrap().clone();
+        let mut registry = ContextRegistry::new();
+        if let Some(ref dialogue) = dialogue {
+            registry.register(ConversationProvider { dialogue, gate });
+        }
+        if let Some(semantic) = semantic_manager.as_ref().filter(|_| semantic_enabled) {
+            registry.register(SemanticProvider { semantic, pipeline: pipeline_arc, args });
+        }
+        if let Some(ref dialogue) = dialogue {
+            registry.register(EpisodicProvider { dialogue, top_k: args.memory_top_k, gate });
+            if let Some(store) = SUMMARIES.get() {
+                registry.register(SummaryProvider { dialogue, store, args, gate });
+            }
+            registry.register(BookmarkProvider { dialogue });
+        }
+        if let Some(store) = DOCUMENTS.get().filter(|_| args.documents_top_k > 0) {
+            registry.register(DocumentProvider {
+                store,
+                top_k: args.documents_top_k,
+                min_similarity: args.documents_min_similarity,
+            });
+        }
+        if let Some(ref note) = group_note {
+            registry.register(GroupProvider { note });
+        }
+        if let Some(p) = persona.as_ref() {
+            registry.register(RelationshipProvider { persona: p });
+            registry.register(ProfileProvider { persona: p, phrasing: confidence_phrasing(args) });
+            // The persona's own earlier explanations of the topic keep terms and analogies consistent
+            if let Some(dialogue) = dialogue.as_ref().filter(|_| args.style_top_k > 0) {
+                registry.register(StyleProvider { dialogue, top_k: args.style_top_k });
+            }
+        }
+        let mut context_budget = window.prompt_budget(max_tokens);
+        if args.context_max_tokens > 0 {
+            context_budget = context_budget.min(args.context_max_tokens);
+        }
+        let mut sections = registry.collect(prompt, context_budget);
+        let removed = dedupe_sections(&mut sections, |text| embedder.embed(text).ok(), args.context_dedupe_similarity);
+        if removed > 0 {
+            debug_log!("DEBUG: dropped {} context lines repeated in a higher-priority section", removed);
+        }
+        if let Some(p) = persona.as_ref().filter(|p| !p.forbidden_topics.is_empty()) {
+            keep_forbidden_topics_out(&mut sections, p, args);
+        }
+        sections
+    };
+    drop(retrieval_timer);
+    if events::is_active() {
+        for section in sections.iter().filter(|s| MEMORY_SECTIONS.contains(&s.name.as_str())) {
+            let items: Vec<String> = section.items().map(str::to_string).collect();
+            if !items.is_empty() {
+                events::emit(TurnEvent::MemoryHit { source: section.name.clone(), items });
+            }
+        }
+    }
+
+    // Entries the answer may cite, kept for --self-check before the sections go into the prompt
+    let memory_entries: Vec<String> = if args.self_check {
+        sections
+            .iter()
+            .filter(|s| MEMORY_SECTIONS.contains(&s.name.as_str()))
+            .flat_map(|s| s.items().map(str::to_string))
+            .collect()
+    } else {
+        Vec::new()
+    };
+
+    // A query on a topic the persona stays away from gets a refusal directive instead of memories
+    let forbidden = persona.as_ref().and_then(|p| {
+        let topic = find_forbidden(&p.forbidden_topics, prompt)?;
+        tracing::info!(persona = %p.archetype_id, topic = %topic.topic, "forbidden topic in query");
+        if !args.quiet {
+            eprintln!("🚫 Forbidden topic for {}: {} - the persona declines", p.name, topic.topic);
+        }
+        Some(topic.directive())
+    });
+
+    // Direct-lookup fast path: relational questions ("what foods do I like?") are answered
+    // from the knowledge graph, the LLM only phrases the facts it found
+    let graph_answer = if semantic_enabled && forbidden.is_none() {
+        totems::semantic::RelationalQuery::parse(prompt).and_then(|query| {
+            semantic_manager
+                .as_ref()
+                .and_then(|sm| sm.lock().unwrap().answer_relational(&query))
+        })
+    } else {
+        None
+    };
+
+    let prompt_build_span = tracing::info_span!("prompt_build", sections = sections.len()).entered();
+    let template = pipeline_arc.lock().unwrap().chat_template;
+    let (enhanced_prompt, max_tokens) = match &graph_answer {
+        Some(answer) => {
+            debug_log!("DEBUG: graph fast path, {} facts: {:?}", answer.items.len(), answer.facts());
+            (
+                template.instruction(&build_graph_phrasing_prompt(prompt, answer, persona.as_ref())),
+                max_tokens.min(GRAPH_ANSWER_MAX_TOKENS),
+            )
+        }
+        None => {
+            let pipeline = pipeline_arc.lock().unwrap();
+            let enhanced_prompt = fit_prompt_to_window(
+                &pipeline,
+                window.prompt_budget(max_tokens),
+                sections,
+                args,
+                |sections| {
+                    template.instruction(&build_prompt_with_context(
+                        prompt,
+                        sections,
+                        args.enable_memory || args.enable_semantic,
+                        persona.as_ref(),
+                        address,
+                        length_intent,
+                        forbidden.as_deref(),
+                    ))
+                },
+            )?;
+            (enhanced_prompt, max_tokens)
+        }
+    };
+    drop(prompt_build_span);
+
+    if !args.quiet {
+        debug_log!("DEBUG: Enhanced prompt length: {}", enhanced_prompt.len());
+    }
+
+    println!("\n📝 You: {}", prompt);
+    if let Some(ref answer) = graph_answer {
+        println!("🕸️  From knowledge graph: {} facts", answer.items.len());
+    }
+
+    // Show which persona is responding
+    if let Some(ref p) = *persona {
+        println!("\n🤖 {}:", p.name);
+    } else {
+        println!("\n🤖 Assistant:");
+    }
+
+    // Apply trait-based sampling parameters
+    {
+        let mut pipeline = pipeline_arc.lock().unwrap();
+        reload_stalled_model(&mut pipeline, args)?;
+        if let Some(temp) = temperature {
+            // Temporarily modify temperature for this generation
+            pipeline.set_temperature(temp);
+        }
+    }
+
+    // Per-turn seed: replaying a session reproduces every turn independently
+    let turn_seed = args.seed.wrapping_add(
+        dialogue_manager
+            .as_ref()
+            .map_or(0, |dm| dm.current_session().turn_count() as u64),
+    );
+
+    let (response, generated_tokens, sampling_record) = {
+        let _generation_span = tracing::info_span!("generation", max_tokens, seed = turn_seed).entered();
+        let mut pipeline = pipeline_arc.lock().unwrap();
+        let response = if events::is_active() {
+            pipeline.run_streaming(&enhanced_prompt, max_tokens, turn_seed, &mut |text| {
+                events::emit(TurnEvent::Token { text: text.to_string() })
+            })?
+        } else {
+            pipeline.run(&enhanced_prompt, max_tokens, turn_seed)?
+        };
+        let record = pipeline.sampling_record(turn_seed, max_tokens, &enhanced_prompt);
+        (response, pipeline.last_generated_tokens, record)
+    };
+    if let Some(quota) = QUOTA.get() {
+        let tokens = sampling_record.prompt_tokens + sampling_record.generated_tokens;
+        quota.record_tokens(user.as_str(), tokens as u64, chrono::Utc::now());
+    }
+
+    // Reset temperature if we changed it
+    {
+        let mut pipeline = pipeline_arc.lock().unwrap();
+        pipeline.set_temperature(args.temperature);
+    }
+
+    // Graph answers and refusals are built from the entries themselves, nothing to check
+    let response = if args.self_check && graph_answer.is_none() && forbidden.is_none() {
+        check_against_memory(response, &memory_entries, pipeline_arc, args)
+    } else {
+        response
+    };
+
+    if args.pace {
+        let pacing = Pacing {
+            chars_per_second: args.typing_speed,
+            ..Default::default()
+        };
+        pacing.deliver(&response, &mut ConsoleSink)?;
+    } else {
+        println!("{}", response);
+    }
+
+    if let Some(ref mut dm) = *dialogue_manager {
+        let _io_timer = profiling::time(Stage::Io);
+        dm.add_exchange_with_sampling_blocking(
+            prompt.to_string(),
+            response.clone(),
+            Some(sampling_record),
+        )?;
+
+        let session_breaks = dm.take_session_breaks();
+        summarize_closed_segments(dm, &session_breaks, args);
+        if args.interactive && !args.quiet {
+            for session_break in session_breaks {
+                eprintln!("✂️  New session: {}", session_break.reason);
+            }
+            let stats = dm.stats();
+            eprintln!("💾 Memory: {} turns in current session", stats.current_session_turns);
+        }
+        if args.memory_diff {
+            let session = dm.current_session();
+            let id = session.id.to_string();
+            let line = format!("+ turn {} in session {}", session.turn_count(), &id[..8]);
+            print_memory_diff("episodic", &[line]);
+        }
+
+        if let Err(e) = persistence_manager.save_with_embeddings_blocking(dm, embedder.embedding_dim()) {
+            eprintln!("WARNING: Failed to save memory: {}", e);
+        }
+        update_summaries(dm, pipeline_arc, args);
+    }
+    // The turn just stored: evidence of the concepts extracted from it
+    let origin = match *dialogue_manager {
+        Some(ref dm) => TurnRef::new(dm.current_session().id.to_string(), dm.current_session().turn_count()),
+        None => TurnRef::session("unknown"),
+    };
+
+    // In on-request consent mode only what the user asked to remember reaches long-term memory
+    if args.memory_consent.allows(prompt) {
+        if significance_filter(args).is_ack(prompt) {
+            debug_log!("DEBUG: Acknowledgment, skipping indexing and extraction");
+        } else if user_discloses(prompt, pipeline_arc) {
+            extract_long_term_memory(prompt, &response, &origin, semantic_enabled, semantic_manager, persona, args);
+        }
+    } else if !args.quiet {
+        eprintln!("🫥 Kept in working memory only (say \"remember this\" or /remember to keep it)");
+    }
+
+    // Apply Persona evolution based on interaction
+    if let Some(ref mut p) = *persona {
+        // Create interaction record
+        let interaction = crate::demiurge::Interaction {
+            user_sentiment: 0.5,  // Would need sentiment analysis to determine
+            successful_help: true,  // Assuming response was generated
+            emotional_depth: if prompt.contains("?") || prompt.len() > 100 { 0.5 } else { 0.3 },
+            topics: vec!["general".to_string()],
+            user_gave_feedback: false,
+            feedback_positive: false,
+            is_deep_conversation: prompt.len() > 200,
+            is_code_related: prompt.contains("code") || prompt.contains("function") || prompt.contains("bug"),
+            is_emotional_support: prompt.contains("sad") || prompt.contains("help") || prompt.contains("помоги"),
+        };
+
+        p.apply_interaction(interaction);
+        if events::is_active() {
+            let arc = p.narrative.narrative.relationship_arcs.get(p.user.as_str());
+            events::emit(TurnEvent::PersonaEvolved {
+                persona: p.name.clone(),
+                interactions: p.evolution.interactions_count,
+                affection: arc.map(|a| a.affection),
+                trust: arc.map(|a| a.trust),
+            });
+        }
+
+        // Save narrative periodically (every 10 interactions)
+        if p.evolution.interactions_count % 10 == 0 && !args.read_only {
+            if let Err(e) = p.save_narrative() {
+                eprintln!("WARNING: Failed to save narrative: {}", e);
+            } else {
+                eprintln!("💾 Persona narrative saved");
+            }
+            if let Err(e) = p.save_evolution() {
+                eprintln!("WARNING: Failed to save persona state: {}", e);
+            }
+        }
+    }
+
+    if let Some(ref dm) = *dialogue_manager {
+        let stats = dm.stats();
+        metrics::set_store_size("episodic", stats.total_turns);
+        metrics::set_store_size("sessions", stats.total_sessions);
+    }
+    if let Some(ref sm) = *semantic_manager {
+        metrics::set_store_size("semantic", sm.lock().unwrap().count());
+    }
+    if let Some(store) = DOCUMENTS.get() {
+        metrics::set_store_size("documents", store.lock().unwrap().chunk_count());
+    }
+
+    if let Some(timings) = profiling::end_response(generated_tokens) {
+        debug_log!(
+            "DEBUG: response took {:.0} ms (forward {:.0} ms, retrieval {:.0} ms, io {:.0} ms)",
+            timings.total.as_secs_f64() * 1000.0,
+            timings.get(Stage::Forward).as_secs_f64() * 1000.0,
+            timings.get(Stage::Retrieval).as_secs_f64() * 1000.0,
+            timings.get(Stage::Io).as_secs_f64() * 1000.0
+        );
+    }
+
+    log_memory_usage("process_query end");
+    Ok(response)
+}
+
+/// Секции с воспоминаниями и фактами, на которые может сослаться ответ
+const MEMORY_SECTIONS: &[&str] = &["episodic", "summaries", "bookmarks", "documents", "knowledge", "profile"];
+
+/// Самопроверка `--self-check`: ответ, расходящийся с найденными пунктами памяти,
+/// исправляется или помечается до показа. Ошибка проверки оставляет ответ как есть
+fn check_against_memory(
+    response: String,
+    entries: &[String],
+    pipeline_arc: &Arc<std::sync::Mutex<UnifiedPipeline>>,
+    args: &Args,
+) -> String {
+    let llm = ContextAnalyzerImpl::new(pipeline_arc.clone());
+    let verdict = {
+        let _span = tracing::info_span!("self_check", entries = entries.len()).entered();
+        self_check(&response, entries, &llm)
+    };
+    match verdict {
+        Ok(Verdict::Consistent) => response,
+        Ok(Verdict::Corrected(fixed)) => {
+            tracing::info!("self-check corrected a memory claim");
+            if !args.quiet {
+                eprintln!("🔎 Self-check: the answer contradicted memory and was corrected");
+            }
+            debug_log!("DEBUG: self-check replaced answer: {}", response);
+            fixed
+        }
+        Ok(Verdict::Flagged) => {
+            tracing::info!("self-check flagged a memory claim");
+            let note = flag_note(Language::detect(&response));
+            format!("{}\n\n{}", response, note)
+        }
+        Err(e) => {
+            debug_log!("DEBUG: self-check failed: {}", e);
+            response
+        }
+    }
+}
+
+/// Фильтр подтверждений из `--ack-max-chars` и `--ack-words`
+fn significance_filter(args: &Args) -> SignificanceFilter {
+    SignificanceFilter::new(args.ack_max_chars, args.ack_words.clone())
+}
+
+/// Стоит ли извлекать факты из реплики: только утверждения о себе, вопросы и
+/// гипотезы пропускаются. Неуверенные случаи решает LLM
+fn user_discloses(prompt: &str, pipeline_arc: &Arc<std::sync::Mutex<UnifiedPipeline>>) -> bool {
+    let llm = ContextAnalyzerImpl::new(pipeline_arc.clone());
+    let discloses = is_self_disclosure(prompt, Some(&llm)).unwrap_or_else(|e| {
+        debug_log!("DEBUG: Utterance classification failed: {}", e);
+        utterance::mentions_self(prompt)
+    });
+    if !discloses {
+        debug_log!("DEBUG: Not a self-disclosure, skipping extraction");
+    }
+    discloses
+}
+
+/// Экстракция концептов из хода в семантическую память и память персоны.
+/// С `--background-jobs` она уходит в фоновую очередь, а чувствительные концепты
+/// подтверждаются перед следующим ответом
+fn extract_long_term_memory(
+    prompt: &str,
+    response: &str,
+    origin: &TurnRef,
+    semantic_enabled: bool,
+    semantic_manager: &Option<std::sync::Arc<std::sync::Mutex<SemanticMemoryManager>>>,
+    persona: &mut Option<Persona>,
+    args: &Args,
+) {
+    if let Some(jobs) = JOBS.get() {
+        let semantic = semantic_manager.clone().filter(|_| semantic_enabled);
+        let persona_target = persona.as_ref().and_then(Persona::extraction_target);
+        let (prompt, response, origin) = (prompt.to_string(), response.to_string(), origin.clone());
+        let (quiet, show_diff) = (args.quiet, args.memory_diff);
+        let silenced = events::is_silenced();
+        let submitted = jobs.submit("extraction", JobPriority::High, move || {
+            let _silence = silenced.then(events::silence);
+            if let Some(sm) = semantic {
+                with_memory_diff(&sm, "semantic", show_diff, || {
+                    extract_semantic_concepts(&sm, &prompt, &response, &origin, quiet)
+                });
+            }
+            if let Some((sm, persona_session)) = persona_target {
+                with_memory_diff(&sm, "persona", show_diff, || {
+                    extract_concepts_into(&sm, &persona_session, &prompt, &response)
+                });
+            }
+            Ok(())
+        });
+        report_submitted("extraction", &submitted, args);
+        return;
+    }
+
+    if semantic_enabled {
+        if let Some(ref sm) = *semantic_manager {
+            with_memory_diff(sm, "semantic", args.memory_diff, || {
+                let _extraction_timer = profiling::time(Stage::Extraction);
+                extract_semantic_concepts(sm, prompt, response, origin, args.quiet);
+            });
+            confirm_sensitive_concepts(sm, args.interactive);
+        }
+    }
+
+    // Extract and store concepts in Persona semantic memory
+    if let Some((sm, persona_session)) = persona.as_ref().and_then(Persona::extraction_target) {
+        with_memory_diff(&sm, "persona", args.memory_diff, || {
+            let _extraction_timer = profiling::time(Stage::Extraction);
+            extract_concepts_into(&sm, &persona_session, prompt, response);
+        });
+        confirm_sensitive_concepts(&sm, args.interactive);
+    }
+}
+
+/// Runs `update` and, with `--memory-diff`, prints what it changed in `sm`;
+/// a live frontend gets the learned concepts as events
+fn with_memory_diff(sm: &std::sync::Mutex<SemanticMemoryManager>, label: &str, show: bool, update: impl FnOnce()) {
+    let before = (show || events::is_active()).then(|| sm.lock().unwrap().snapshot());
+    update();
+    if let Some(before) = before {
+        let diff = SemanticDiff::between(&before, &sm.lock().unwrap().snapshot());
+        if show {
+            print_memory_diff(label, &diff.lines());
+        }
+        let learned = diff.added.iter().map(|c| (c, false));
+        for (concept, updated) in learned.chain(diff.updated.iter().map(|(_, c)| (c, true))) {
+            events::emit(TurnEvent::ConceptExtracted {
+                store: label.to_string(),
+                text: concept.text.clone(),
+                category: concept.category.to_string(),
+                confidence: concept.confidence,
+                updated,
+            });
+        }
+    }
+}
+
+fn print_memory_diff(label: &str, lines: &[String]) {
+    if lines.is_empty() {
+        return;
+    }
+    println!("🧾 Memory ({}):", label);
+    for line in lines {
+        println!("   {}", line);
+    }
+}
+
+/// Концепты хода в семантическую память
+fn extract_semantic_concepts(
+    semantic_manager: &std::sync::Mutex<SemanticMemoryManager>,
+    prompt: &str,
+    response: &str,
+    origin: &TurnRef,
+    quiet: bool,
+) {
+    let mut sm = semantic_manager.lock().unwrap();
+    match sm.extract_from_dialogue_blocking(prompt, response, origin) {
+        Ok(0) => metrics::record_extraction(ExtractionResult::Empty),
+        Ok(_) => metrics::record_extraction(ExtractionResult::Ok),
+        Err(e) => {
+            metrics::record_extraction(ExtractionResult::Error);
+            if !quiet {
+                debug_log!("DEBUG: Failed to extract concepts: {}", e);
+            }
+        }
+    }
+    if !quiet {
+        debug_log!("DEBUG: Semantic memory now has {} concepts", sm.count());
+    }
+}
+
+/// Сообщает, если очередь переполнена и задача отброшена или вытеснила другую
+fn report_submitted(name: &str, submitted: &Submitted, args: &Args) {
+    match submitted {
+        Submitted::Queued => {}
+        Submitted::Displaced(other) if !args.quiet => {
+            eprintln!("🧵 Job queue is full: '{}' dropped to make room for '{}'", other, name)
+        }
+        Submitted::Shed if !args.quiet => eprintln!("🧵 Job queue is full: '{}' skipped", name),
+        _ => {}
+    }
+}
+
+/// Сводки `--progressive-summaries`, которых не хватает после хода: фоновой задачей,
+/// а без `--background-jobs` - сразу
+fn update_summaries(dm: &DialogueManager, pipeline_arc: &Arc<std::sync::Mutex<UnifiedPipeline>>, args: &Args) {
+    let Some(store) = SUMMARIES.get() else {
+        return;
+    };
+    if SUMMARY_PASS_QUEUED.load(Ordering::SeqCst) {
+        return;
+    }
+    let current = dm.current_session();
+    let sessions = std::iter::once(current).chain(dm.session_history().values());
+    let tasks = store.lock().unwrap().plan(sessions, Some(current.id), chrono::Utc::now());
+    if tasks.is_empty() {
+        return;
+    }
+    let (store, llm) = (store.clone(), ContextAnalyzerImpl::new(pipeline_arc.clone()));
+    let summarize = move || -> Result<()> {
+        let done = run_summary_tasks(&store, &tasks, &llm);
+        SUMMARY_PASS_QUEUED.store(false, Ordering::SeqCst);
+        debug_log!("DEBUG: {} summaries updated", done?);
+        Ok(())
+    };
+    match JOBS.get() {
+        Some(jobs) => {
+            SUMMARY_PASS_QUEUED.store(true, Ordering::SeqCst);
+            let submitted = jobs.submit("summaries", JobPriority::Normal, summarize);
+            if submitted == Submitted::Shed {
+                SUMMARY_PASS_QUEUED.store(false, Ordering::SeqCst);
+            }
+            report_submitted("summaries", &submitted, args);
+        }
+        None => {
+            if let Err(e) = summarize() {
+                eprintln!("WARNING: Failed to update summaries: {}", e);
+            }
+        }
+    }
+}
+
+/// Разборы сессий, закрытых автоматически, для ленты: фоновой задачей, а без
+/// `--background-jobs` - сразу. Готовые разборы менеджер забирает на следующем ходу
+fn summarize_closed_segments(dm: &mut DialogueManager, session_breaks: &[SessionBreak], args: &Args) {
+    for session_break in session_breaks {
+        let Some(summarize) = dm.segment_summary_job(session_break) else {
+            continue;
+        };
+        match JOBS.get() {
+            Some(jobs) => {
+                let submitted = jobs.submit("session summary", JobPriority::Normal, summarize);
+                report_submitted("session summary", &submitted, args);
+            }
+            None => {
+                if let Err(e) = summarize() {
+                    eprintln!("WARNING: Failed to summarize the closed session: {}", e);
+                }
+            }
+        }
+    }
+    dm.apply_segment_summaries();
+}
+
+/// Сводка сессии для контекста при выходе: проход суммаризации считает текущую
+/// сессию законченной. `None` без `--progressive-summaries` - тогда сводку
+/// составит сохранение контекста
+fn final_session_summary(dm: &DialogueManager, pipeline_arc: &Arc<std::sync::Mutex<UnifiedPipeline>>) -> Option<String> {
+    let store = SUMMARIES.get()?;
+    let current = dm.current_session();
+    let tasks = store.lock().unwrap().plan([current], None, chrono::Utc::now());
+    if let Err(e) = run_summary_tasks(store, &tasks, &ContextAnalyzerImpl::new(pipeline_arc.clone())) {
+        eprintln!("WARNING: Failed to update summaries: {}", e);
+    }
+    let store = store.lock().unwrap();
+    store.current_session_summary(&current.id, current.durable_turns().count()).map(str::to_string)
+}
+
+/// Сводка прошлого разговора по длине перерыва: вчерашний - последний кусок,
+/// недельной давности - сессия, старше - дайджест недели
+fn catch_up_from_summaries(context: &mut PersonaSessionContext) {
+    let Some(store) = SUMMARIES.get() else {
+        return;
+    };
+    let Ok(last_session) = uuid::Uuid::parse_str(&context.previous_session_id) else {
+        return;
+    };
+    let now = chrono::Utc::now();
+    let last = chrono::DateTime::from_timestamp(context.last_interaction_date as i64, 0).unwrap_or(now);
+    let store = store.lock().unwrap();
+    if let Some((level, summary)) = store.catch_up(&last_session, now - last) {
+        println!("   Last time ({}): {}", level.as_str(), summary);
+        context.summary = summary.to_string();
+    }
+}
+
+/// Сохраняет разговор с текущим пользователем: контекст сессии персоны и эпизодическую
+/// память. При выходе и перед `/user`, который открывает память другого пользователя
+fn save_conversation(
+    persona: &Option<Persona>,
+    dialogue_manager: &mut Option<DialogueManager>,
+    semantic_manager: &Option<Arc<std::sync::Mutex<SemanticMemoryManager>>>,
+    persistence_manager: &PersistenceManager,
+    embedder: &Arc<dyn Embedder>,
+    pipeline_arc: &Arc<std::sync::Mutex<UnifiedPipeline>>,
+) {
+    println!("💾 Saving session context...");
+    drain_background_jobs();
+    if let Some(ref mut dm) = dialogue_manager {
+        dm.apply_segment_summaries();
+    }
+    let summary = dialogue_manager.as_ref().and_then(|dm| final_session_summary(dm, pipeline_arc));
+
+    if let Some(ref p) = persona {
+        if let Some(ref mut dm) = dialogue_manager {
+            let context_analyzer = ContextAnalyzerImpl::new(pipeline_arc.clone());
+            if let Ok(Some(context)) = p.save_session_context_with_summary(dm, &context_analyzer, summary) {
+                println!("💾 Context saved for next session");
+                if !context.summary.is_empty() {
+                    println!("   Topics: {}", context.key_topics.join(", "));
+                }
+            }
+        }
+    }
+
+    if let Some(ref dm) = dialogue_manager {
+        if let Err(e) = persistence_manager.save_with_embeddings_blocking(dm, embedder.embedding_dim()) {
+            eprintln!("WARNING: Failed to save memory on exit: {}", e);
+        } else {
+            println!("💾 Episodic memory saved");
+        }
+    }
+    if let Some(ref sm) = semantic_manager {
+        let sm = sm.lock().unwrap();
+        let count = sm.count();
+        if count > 0 {
+            println!("📚 Semantic memory: {} concepts saved", count);
+        }
+    }
+}
+
+/// `/user` без аргумента: чья память открыта и у кого она есть
+fn show_users() {
+    let current = current_user();
+    println!("\n👤 User: {}", current);
+    for user in UserId::list(&data_dir()) {
+        let marker = if user == current { "*" } else { " " };
+        println!("   {} {}", marker, user);
+    }
+    println!("   /user <id> switches to another user's memory");
+}
+
+/// Открывает память `user`: хранилище эпизодов, сводки и фоновую загрузку, как при
+/// старте. Если хранилище не открылось, пользователь остаётся прежним
+fn switch_user(
+    user: UserId,
+    args: &Args,
+    embedder: &Arc<dyn Embedder>,
+) -> Result<(Arc<PersistenceManager>, Pending<LoadedMemory>)> {
+    let previous = current_user();
+    set_current_user(user);
+    let persistence = match open_persistence_manager(args) {
+        Ok(persistence) => Arc::new(persistence),
+        Err(e) => {
+            set_current_user(previous);
+            return Err(e);
+        }
+    };
+    if let Some(store) = SUMMARIES.get() {
+        if let Some(opened) = open_summary_store(&persistence, args) {
+            *store.lock().unwrap() = opened;
+        }
+    }
+
+    // --resume-session относится к прежнему пользователю
+    let mut args = args.clone();
+    args.resume_session = None;
+    let (loader, embedder) = (persistence.clone(), embedder.clone());
+    let loading = Pending::spawn("memory", move || hydrate_memory(&args, None, &loader, &embedder))?;
+    Ok((persistence, loading))
+}
+
+/// Приветствие по сохранённому контексту прошлой сессии с этим пользователем
+fn greet_from_saved_context(p: &mut Persona) -> Result<()> {
+    if let Some(mut context) = p.load_session_context()? {
+        println!("💭 Found saved session context!");
+        catch_up_from_summaries(&mut context);
+
+        if !context.summary.is_empty() {
+            let greeting = p.generate_contextual_greeting(&context);
+            println!("\n🤖 {}:", p.name);
+            println!("{}", greeting);
+        }
+    } else if p.has_saved_context() {
+        println!("💭 Found expired session context (will be cleared)");
+    }
+    Ok(())
+}
+
+/// Сколько ждать фоновые задачи при выходе
+const JOB_DRAIN_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(120);
+
+/// Дожидается фоновых задач, прежде чем память сохранится при выходе
+fn drain_background_jobs() {
+    let Some(jobs) = JOBS.get() else {
+        return;
+    };
+    let pending = jobs.stats().pending.len();
+    if pending > 0 {
+        println!("🧵 Finishing {} background jobs...", pending);
+    }
+    if !jobs.drain(JOB_DRAIN_TIMEOUT) {
+        eprintln!(
+            "WARNING: Background jobs did not finish in {}s, the rest is dropped",
+            JOB_DRAIN_TIMEOUT.as_secs()
+        );
+    }
+}
+
+/// Чувствительные концепты, извлечённые фоновыми задачами с прошлого хода
+fn confirm_background_sensitive(
+    semantic_manager: &Option<Arc<std::sync::Mutex<SemanticMemoryManager>>>,
+    persona: &Option<Persona>,
+    interactive: bool,
+) {
+    let persona_sm = persona.as_ref().and_then(|p| p.semantic_manager.as_ref());
+    for sm in semantic_manager.iter().chain(persona_sm) {
+        confirm_sensitive_concepts(sm, interactive);
+    }
+}
+
+/// Каталог памяти этого запуска (см. [`utils::data_dir`])
+fn data_dir() -> std::path::PathBuf {
+    DATA_DIR
+        .get()
+        .cloned()
+        .unwrap_or_else(|| resolve_path(data_dir::LEGACY_DIR))
+}
+
+/// Пользователь, чья память открыта
+fn current_user() -> UserId {
+    USER.read().unwrap().clone().unwrap_or_default()
+}
+
+fn set_current_user(user: UserId) {
+    *USER.write().unwrap() = Some(user);
+}
+
+thread_local! {
+    /// Чья квота расходуется ходами этого потока, если не текущего пользователя
+    static QUOTA_USER: std::cell::RefCell<Option<UserId>> = const { std::cell::RefCell::new(None) };
+}
+
+/// Выполняет `f`, засчитывая запросы и токены в квоту `user`: у клиентов `--serve`
+/// квоты свои, хотя память у них общая
+#[cfg(feature = "server")]
+fn with_quota_user<T>(user: UserId, f: impl FnOnce() -> T) -> T {
+    let previous = QUOTA_USER.replace(Some(user));
+    let result = f();
+    QUOTA_USER.set(previous);
+    result
+}
+
+/// Чья квота расходуется ходом
+fn quota_user() -> UserId {
+    QUOTA_USER.with_borrow(Clone::clone).unwrap_or_else(current_user)
+}
+
+/// Каталог памяти текущего пользователя в [`data_dir`]
+fn user_data_dir() -> std::path::PathBuf {
+    current_user().data_dir(&data_dir())
+}
+
+fn resolve_path(path: &str) -> std::path::PathBuf {
+    let path = std::path::Path::new(path);
+    if path.is_absolute() {
+        return path.to_path_buf();
+    }
+
+    let exe_path = std::env::current_exe().unwrap_or(std::path::PathBuf::from("."));
+    let mut current = exe_path.as_path();
+
+    while let Some(parent) = current.parent() {
+        if parent.join("Cargo.toml").exists() {
+            return parent.join(path);
+        }
+        current = parent;
+    }
+
+    std::env::current_dir()
+        .unwrap_or(std::path::PathBuf::from("."))
+        .join(path)
+}
+
+/// Обслуживание, которое расписание оставило потоку с эпизодической памятью (REPL или
+/// сервер): уборка старых сессий и перезапись файлов памяти. Заодно расписанию
+/// передаётся текущая семантическая память для фоновых работ
+fn run_pending_maintenance(
+    dialogue_manager: &mut Option<DialogueManager>,
+    semantic_manager: &Option<Arc<std::sync::Mutex<SemanticMemoryManager>>>,
+    persistence_manager: &PersistenceManager,
+    embedder: &Arc<dyn Embedder>,
+    args: &Args,
+) {
+    let Some(scheduler) = MAINTENANCE.get() else {
+        return;
+    };
+    scheduler.set_semantic(semantic_manager.clone());
+    for task in scheduler.take_pending() {
+        let _span = tracing::info_span!("maintenance", task = task.as_str()).entered();
+        let result = match (task, dialogue_manager.as_mut()) {
+            (MaintenanceTask::SessionCleanup, Some(dm)) => {
+                let max_age = scheduler.config().session_max_age_days;
+                let archived = dm.evict_older_than(dm.clock().now() - chrono::Duration::days(max_age as i64));
+                if archived > 0 && !args.quiet {
+                    println!("🧹 Archived {} sessions older than {} days", archived, max_age);
+                }
+                if archived > 0 {
+                    persistence_manager.save_with_embeddings_blocking(dm, embedder.embedding_dim())
+                } else {
+                    Ok(())
+                }
+            }
+            (MaintenanceTask::Compaction, dm) => compact_memory(dm.as_deref(), semantic_manager, persistence_manager, embedder),
+            _ => Ok(()),
+        };
+        if let Err(e) = result {
+            eprintln!("WARNING: Maintenance {} failed: {}", task.as_str(), e);
+        }
+    }
+}
+
+/// Переписывает файлы памяти из текущего состояния: векторы архивированных и
+/// удалённых сессий, удалённые концепты и их связи уходят с диска
+fn compact_memory(
+    dialogue_manager: Option<&DialogueManager>,
+    semantic_manager: &Option<Arc<std::sync::Mutex<SemanticMemoryManager>>>,
+    persistence_manager: &PersistenceManager,
+    embedder: &Arc<dyn Embedder>,
+) -> Result<()> {
+    if let Some(dm) = dialogue_manager {
+        persistence_manager.save_with_embeddings_blocking(dm, embedder.embedding_dim())?;
+    }
+    if let Some(ref sm) = semantic_manager {
+        let sm = sm.lock().unwrap();
+        sm.save_blocking()?;
+        sm.save_graph_blocking()?;
+    }
+    debug_log!("DEBUG: Memory files compacted");
+    Ok(())
+}
+
+/// Сколько типичных концептов показывать на кластер
+const CLUSTER_SAMPLE: usize = 3;
+
+/// Applies a user correction to semantic memory and saves it
+fn apply_user_correction(
+    semantic_manager: &std::sync::Arc<std::sync::Mutex<SemanticMemoryManager>>,
+    request: &CorrectionRequest,
+    source: &str,
+) {
+    let mut sm = semantic_manager.lock().unwrap();
+    match sm.apply_correction_blocking(request, source) {
+        Ok(correction) => {
+            match correction.replaced {
+                Some(old) => println!("✏️  Corrected: «{}» → «{}»", old, correction.concept.text),
+                None => println!("✏️  Remembered: «{}»", correction.concept.text),
+            }
+            if let Err(e) = sm.save_blocking() {
+                eprintln!("WARNING: Failed to save semantic memory: {}", e);
+            }
+        }
+        Err(e) => println!("❌ {}", e),
+    }
+}
+
+/// Сколько конфликтов памяти спрашивать за один запрос
+const MAX_CONFLICT_QUESTIONS: usize = 2;
+
+/// Спрашивает, какой из противоречащих найденных концептов актуален; ответ записывается
+/// исправлением, «both» оставляет оба и больше эту пару не предлагает
+fn ask_memory_conflicts(
+    semantic_manager: &std::sync::Arc<std::sync::Mutex<SemanticMemoryManager>>,
+    prompt: &str,
+    source: &str,
+    args: &Args,
+) {
+    let tag_filter = TagFilter::excluding(&args.exclude_tags);
+    let conflicts = semantic_manager
+        .lock()
+        .unwrap()
+        .retrieved_conflicts_blocking(prompt, args.semantic_top_k, &tag_filter);
+
+    for conflict in conflicts.iter().take(MAX_CONFLICT_QUESTIONS) {
+        println!("\n⚖️  Memory has conflicting facts:");
+        for (i, concept) in [&conflict.first, &conflict.second].into_iter().enumerate() {
+            println!("   {}) {} ({})", i + 1, concept.text, concept.updated_at.format("%Y-%m-%d"));
+        }
+        print!("   Which one is current? [1/2/both] ");
+        let _ = std::io::stdout().flush();
+        let mut answer = String::new();
+        if std::io::stdin().read_line(&mut answer).is_err() {
+            continue;
+        }
+        let keep = match answer.trim() {
+            "1" => Some(&conflict.first),
+            "2" => Some(&conflict.second),
+            _ => None,
+        };
+
+        let mut sm = semantic_manager.lock().unwrap();
+        match sm.resolve_conflict_blocking(conflict, keep, source) {
+            Ok(Some(correction)) => {
+                println!("   ✏️  Keeping «{}»", correction.concept.text);
+                if let Err(e) = sm.save_blocking() {
+                    eprintln!("WARNING: Failed to save semantic memory: {}", e);
+                }
+            }
+            Ok(None) => println!("   Keeping both"),
+            Err(e) => eprintln!("WARNING: Failed to resolve memory conflict: {}", e),
+        }
+    }
+}
+
+fn handle_semantic_command(
+    command: &repl::Command,
+    semantic_manager: &Option<Arc<std::sync::Mutex<SemanticMemoryManager>>>,
+) {
+    let Some(sm) = semantic_manager else {
+        println!("Semantic memory is not loaded.");
+        return;
+    };
+    let subcmd = command.subcommand.unwrap_or("help");
+
+    match subcmd {
+        "tag" | "untag" => {
+            let (Some(id), Some(tag)) = (command.arg(0), command.arg(1)) else {
+                println!("Usage: /semantic {} <id> <tag>", subcmd);
+                return;
+            };
+            let mut sm = sm.lock().unwrap();
+            let result = sm.resolve_id(id).and_then(|id| {
+                if subcmd == "tag" {
+                    sm.add_tag(&id, tag)
+                } else {
+                    sm.remove_tag(&id, tag)
+                }
+            });
+            match result {
+                Ok(true) => {
+                    if let Err(e) = sm.save_blocking() {
+                        eprintln!("WARNING: Failed to save semantic memory: {}", e);
+                    }
+                    println!("🏷️  {} #{}", if subcmd == "tag" { "Tagged" } else { "Untagged" }, tag);
+                }
+                Ok(false) => println!("Nothing to change"),
+                Err(e) => println!("❌ {}", e),
+            }
+        }
+        "tags" => {
+            let sm = sm.lock().unwrap();
+            let counts = sm.tag_counts();
+            if counts.is_empty() {
+                println!("No tags yet.");
+            }
+            for (tag, count) in counts {
+                println!("   #{:<15} {}", tag, count);
+            }
+        }
+        "list" | "ls" => {
+            let sm = sm.lock().unwrap();
+            let mut concepts = match command.arg(0) {
+                Some(tag) => sm.get_concepts_by_tag(tag),
+                None => sm.get_concepts_with_decay(usize::MAX).into_iter().map(|(_, c)| c).collect(),
+            };
+            concepts.sort_by(|a, b| b.confidence.partial_cmp(&a.confidence).unwrap_or(std::cmp::Ordering::Equal));
+            for concept in concepts.iter().take(30) {
+                let tags: String = concept.tags.iter().map(|t| format!(" #{}", t)).collect();
+                println!(
+                    "   {} [{} {:.2}] {}{}",
+                    &concept.id.to_string()[..8],
+                    concept.category,
+                    concept.confidence,
+                    truncate_text(&concept.text, 80),
+                    tags
+                );
+            }
+            if concepts.len() > 30 {
+                println!("   ... and {} more", concepts.len() - 30);
+            }
+        }
+        "get" | "show" => {
+            let Some(id) = command.arg(0) else {
+                println!("Usage: /semantic get <id>");
+                return;
+            };
+            let sm = sm.lock().unwrap();
+            let concept = match sm.resolve_id(id) {
+                Ok(id) => sm.get_concept(&id).cloned(),
+                Err(e) => {
+                    println!("❌ {}", e);
+                    return;
+                }
+            };
+            if let Some(concept) = concept {
+                print_concept(&concept);
+            }
+        }
+        "history" => {
+            let Some(arg) = command.arg(0) else {
+                println!("Usage: /semantic history <id>");
+                return;
+            };
+            let sm = sm.lock().unwrap();
+            // a removed concept is only in the audit log: its full id is needed
+            let id = match sm.resolve_id(arg) {
+                Ok(id) => id,
+                Err(e) => match uuid::Uuid::parse_str(arg) {
+                    Ok(id) => id,
+                    Err(_) => {
+                        println!("❌ {}", e);
+                        return;
+                    }
+                },
+            };
+            match sm.history(&id) {
+                Ok(history) => print_concept_history(sm.get_concept(&id), &history),
+                Err(e) => println!("❌ {}", e),
+            }
+        }
+        "clusters" => {
+            let k = match command.arg(0).map(|k| k.parse::<usize>()) {
+                Some(Ok(k)) if k > 0 => Some(k),
+                Some(_) => {
+                    println!("Usage: /semantic clusters [k]");
+                    return;
+                }
+                None => None,
+            };
+            let sm = sm.lock().unwrap();
+            let clusters = sm.clusters(k);
+            if clusters.is_empty() {
+                println!("No concepts to cluster.");
+                return;
+            }
+            let total: usize = clusters.iter().map(|c| c.members.len()).sum();
+            println!("\n🧩 Concept clusters (k={}, {} concepts):", clusters.len(), total);
+            for (i, cluster) in clusters.iter().enumerate() {
+                println!(
+                    "\n   {}. {} - {} concepts, cohesion {:.2}",
+                    i + 1,
+                    cluster.label,
+                    cluster.members.len(),
+                    cluster.cohesion
+                );
+                for id in cluster.members.iter().take(CLUSTER_SAMPLE) {
+                    if let Some(concept) = sm.get_concept(id) {
+                        println!("      {} {}", &id.to_string()[..8], truncate_text(&concept.text, 70));
+                    }
+                }
+                if cluster.members.len() > CLUSTER_SAMPLE {
+                    println!("      ... and {} more", cluster.members.len() - CLUSTER_SAMPLE);
+                }
+            }
+        }
+        "graph" => {
+            let Some(arg) = command.arg(0) else {
+                println!("Usage: /semantic graph <concept> [hops] [predicate...]");
+                return;
+            };
+            let (hops, predicates) = match command.arg(1).map(str::parse::<usize>) {
+                Some(Ok(hops)) => (hops, &command.args[2..]),
+                _ => (DEFAULT_HOPS, command.args.get(1..).unwrap_or_default()),
+            };
+            let sm = sm.lock().unwrap();
+            let Some(id) = resolve_concept(&sm, arg) else {
+                println!("❌ Concept not found: {}", arg);
+                return;
+            };
+            let subgraph = sm.query_graph(&GraphQuery::neighborhood(id, hops).with_predicates(predicates));
+            let name = concept_label(&sm, &id);
+            if subgraph.is_empty() {
+                println!("No relations around {}.", name);
+                return;
+            }
+            println!(
+                "\n🕸️  {}: {} concepts, {} relations within {} hops",
+                name,
+                subgraph.nodes.len(),
+                subgraph.edges.len(),
+                hops
+            );
+            print_graph_edges(&sm, &subgraph.edges);
+        }
+        "path" => {
+            let (Some(from), Some(to)) = (command.arg(0), command.arg(1)) else {
+                println!("Usage: /semantic path <from> <to> [predicate...]");
+                return;
+            };
+            let sm = sm.lock().unwrap();
+            let Some(from_id) = resolve_concept(&sm, from) else {
+                println!("❌ Concept not found: {}", from);
+                return;
+            };
+            let Some(to_id) = resolve_concept(&sm, to) else {
+                println!("❌ Concept not found: {}", to);
+                return;
+            };
+            let path = sm.query_graph(&GraphQuery::path(from_id, to_id).with_predicates(&command.args[2..]));
+            let (from, to) = (concept_label(&sm, &from_id), concept_label(&sm, &to_id));
+            if path.is_empty() {
+                println!("No path from {} to {} within {} hops.", from, to, MAX_PATH_HOPS);
+                return;
+            }
+            println!("\n🧭 {} → {} ({} hops):", from, to, path.edges.len());
+            print_graph_edges(&sm, &path.edges);
+        }
+        _ => {
+            print!("{}", repl::command_help(command.spec));
+            println!("   CLI: --graph-stats, --extract-relations, --find-related <text>, --exclude-tags <tags>");
+        }
+    }
+}
+
+/// Концепт по тексту, ID или его префиксу; иначе самый короткий, где текст встречается
+fn resolve_concept(sm: &SemanticMemoryManager, arg: &str) -> Option<uuid::Uuid> {
+    let needle = arg.to_lowercase();
+    sm.find_by_content(arg).or_else(|| sm.resolve_id(arg).ok()).or_else(|| {
+        sm.get_concepts_with_decay(usize::MAX)
+            .into_iter()
+            .map(|(_, c)| c)
+            .filter(|c| c.text.to_lowercase().contains(&needle))
+            .min_by_key(|c| c.text.chars().count())
+            .map(|c| c.id)
+    })
+}
+
+fn concept_label(sm: &SemanticMemoryManager, id: &uuid::Uuid) -> String {
+    sm.get_concept(id)
+        .map_or_else(|| id.to_string()[..8].to_string(), |c| truncate_text(&c.text, 40))
+}
+
+/// `subject -predicate-> object (уверенность)` по строке на связь
+fn print_graph_edges(sm: &SemanticMemoryManager, edges: &[GraphEdge]) {
+    for edge in edges {
+        println!(
+            "   {} -{}-> {} ({:.2})",
+            concept_label(sm, &edge.subject),
+            edge.predicate,
+            concept_label(sm, &edge.object),
+            edge.confidence
+        );
+    }
+}
+
+/// Концепт целиком: канонический текст и реплика, из которой он извлечён, каждый со своим языком
+fn print_concept(concept: &Concept) {
+    let lang = |lang: Option<Language>| lang.map(|l| format!(" ({})", l)).unwrap_or_default();
+    println!("\n🧠 {} [{} {:.2}]", concept.id, concept.category, concept.confidence);
+    println!("   Text{}: {}", lang(concept.text_language()), concept.text);
+    match &concept.utterance {
+        Some(utterance) => println!("   Said{}: {}", lang(concept.language), utterance),
+        None => println!("   Said: -"),
+    }
+    for previous in &concept.previous_texts {
+        println!("   Was: {}", previous);
+    }
+    if !concept.tags.is_empty() {
+        let tags: Vec<String> = concept.tags.iter().map(|t| format!("#{}", t)).collect();
+        println!("   Tags: {}", tags.join(" "));
+    }
+    println!(
+        "   Source: {} ({}), v{}, used {}x, updated {}",
+        concept.source,
+        concept.knowledge_source,
+        concept.version,
+        concept.usage_count,
+        concept.updated_at.format("%Y-%m-%d %H:%M")
+    );
+}
+
+/// Свидетельства концепта и журнал его изменений
+fn print_concept_history(concept: Option<&Concept>, history: &[AuditEntry]) {
+    match concept {
+        Some(concept) => println!("\n🧾 {} [{:.2}] {}", &concept.id.to_string()[..8], concept.confidence, concept.text),
+        None if history.is_empty() => {
+            println!("No history for this concept.");
+            return;
+        }
+        None => println!("\n🧾 Removed concept: {}", history[history.len() - 1].text),
+    }
+    if let Some(concept) = concept.filter(|c| !c.evidence.is_empty()) {
+        println!("   Evidence:");
+        for evidence in &concept.evidence {
+            let turn = evidence.turn.map(|t| format!(", turn {}", t)).unwrap_or_default();
+            let session: String = evidence.session_id.chars().take(8).collect();
+            println!(
+                "   - {} session {}{}: \"{}\"",
+                evidence.timestamp.format("%Y-%m-%d %H:%M"),
+                session,
+                turn,
+                truncate_text(&evidence.raw_text, 100)
+            );
+        }
+    }
+    if history.is_empty() {
+        println!("   No recorded changes (created before the audit log).");
+        return;
+    }
+    println!("   Changes:");
+    for entry in history {
+        println!("   {}", entry.describe());
+    }
+}
+
+/// Персона архетипа для текущего пользователя: с её нарративом, эволюцией и сидами памяти
+fn load_persona(archetype: Archetype, semantic_manager: Option<&Arc<std::sync::Mutex<SemanticMemoryManager>>>) -> Persona {
+    let mut p = Persona::from_archetype(std::sync::Arc::new(archetype));
+    p.set_user(current_user());
+    if let Err(e) = p.load_narrative() {
+        eprintln!("WARNING: Failed to load narrative: {}", e);
+    }
+    if let Err(e) = p.load_evolution() {
+        eprintln!("WARNING: Failed to load persona state: {}", e);
+    }
+    if let Some(sm) = semantic_manager {
+        p.set_semantic_manager(sm.clone());
+    }
+    apply_persona_seeds(&mut p);
+    p
+}
+
+/// `/persona group NAME... [--debate]` собирает группу вокруг текущей персоны,
+/// `/persona group off` распускает, без имён - состав группы или смена режима
+fn handle_group_command(
+    command: &repl::Command,
+    ensemble: &mut Option<PersonaEnsemble>,
+    persona: &mut Option<Persona>,
+    semantic_manager: &Option<Arc<std::sync::Mutex<SemanticMemoryManager>>>,
+) {
+    let Some(host) = persona.as_mut() else {
+        println!("No persona loaded.");
+        return;
+    };
+    let mode = if command.has_flag("--debate") {
+        Some(EnsembleMode::Debate)
+    } else if command.has_flag("--route") {
+        Some(EnsembleMode::Route)
+    } else {
+        None
+    };
+    let names = command.positional();
+
+    match names.first().copied() {
+        Some("off") => match ensemble.take() {
+            Some(_) => println!("👥 Group conversation ended, {} answers alone", host.name),
+            None => println!("No group conversation."),
+        },
+        None => {
+            let Some(group) = ensemble.as_mut() else {
+                println!("No group conversation. Start one with /persona group <name...> [--debate]");
+                return;
+            };
+            if let Some(mode) = mode {
+                group.mode = mode;
+            }
+            println!("\n👥 Group conversation ({}):", group.mode.as_str());
+            for i in 0..group.len() {
+                let p = group.member(host, i);
+                println!("   {} ({}){}", p.name, p.archetype_id, if i == 0 { " - host" } else { "" });
+            }
+        }
+        Some(_) => {
+            let mut guests = Vec::new();
+            for name in names.iter().filter(|n| **n != host.archetype_id) {
+                match ArchetypeLoader::load(name) {
+                    Ok(archetype) => guests.push(load_persona(archetype, semantic_manager.as_ref())),
+                    Err(e) => {
+                        println!("❌ Failed to load archetype '{}': {}", name, e);
+                        println!("Available: {:?}", ArchetypeLoader::list_ids().unwrap_or_default());
+                        return;
+                    }
+                }
+            }
+            let mode = mode.unwrap_or_default();
+            match PersonaEnsemble::new(host, guests, mode) {
+                Ok(mut group) => {
+                    group.record_start(host);
+                    println!(
+                        "👥 Group conversation: {} ({})",
+                        group.names(host).join(", "),
+                        match mode {
+                            EnsembleMode::Route => "the best fit answers each message",
+                            EnsembleMode::Debate => "everyone answers in turn",
+                        }
+                    );
+                    if group.guests().iter().any(|g| g.adapter.is_some() && g.adapter != host.adapter) {
+                        println!("   LoRA adapters of the guests are not loaded: everyone speaks through {}'s model", host.name);
+                    }
+                    *ensemble = Some(group);
+                }
+                Err(e) => println!("❌ {}", e),
+            }
+        }
+    }
+}
+
+/// Ход группового разговора: отвечает подходящая персона или, в дебатах, все по очереди.
+/// Каждый отвечает своей персоной, так что эволюция и нарратив у всех свои
+#[allow(clippy::too_many_arguments)]
+fn group_turn(
+    input: &str,
+    group: &mut PersonaEnsemble,
+    pipeline_arc: &Arc<std::sync::Mutex<UnifiedPipeline>>,
+    dialogue_manager: &mut Option<DialogueManager>,
+    semantic_manager: &mut Option<Arc<std::sync::Mutex<SemanticMemoryManager>>>,
+    persistence_manager: &Arc<PersistenceManager>,
+    embedder: &Arc<dyn Embedder>,
+    args: &Args,
+    persona: &mut Option<Persona>,
+) -> Result<()> {
+    let Some(host) = persona.as_ref() else {
+        anyhow::bail!("No persona loaded");
+    };
+    let mut replies: Vec<(usize, String)> = Vec::new();
+    for speaker in group.speakers(host, input) {
+        let Some(host) = persona.as_mut() else { break };
+        *GROUP_NOTE.lock().unwrap() = Some(group.group_note(host, speaker, &replies));
+        group.swap_speaker(host, speaker);
+        pipeline_arc.lock().unwrap().clear_cache();
+        let reply = process_query(
+            input,
+            pipeline_arc,
+            dialogue_manager,
+            semantic_manager,
+            persistence_manager,
+            embedder,
+            args,
+            persona,
+        );
+        if let Some(speaker_persona) = persona.as_mut() {
+            group.swap_speaker(speaker_persona, speaker);
+        }
+        *GROUP_NOTE.lock().unwrap() = None;
+        replies.push((speaker, reply?));
+    }
+    Ok(())
+}
+
+/// Применяет сиды памяти архетипа (только отсутствующие) и сообщает о добавленных
+fn apply_persona_seeds(persona: &mut Persona) {
+    match persona.apply_memory_seeds() {
+        Ok((0, 0)) => {}
+        Ok((concepts, narrative)) => println!(
+            "🌱 Archetype seeds applied: {} concepts, {} narrative entries",
+            concepts, narrative
+        ),
+        Err(e) => eprintln!("WARNING: Failed to apply archetype seeds: {}", e),
+    }
+}
+
+/// `validate-archetype`: отчёт по каждому архетипу; `false`, если хоть один не загрузится
+fn validate_archetypes_command(archetypes: &[String]) -> Result<bool> {
+    let ids = if archetypes.is_empty() { ArchetypeLoader::list_ids()? } else { archetypes.to_vec() };
+    let mut valid = true;
+    for id in &ids {
+        let report = ArchetypeLoader::validate(id)?;
+        let mark = if !report.is_valid() {
+            "❌"
+        } else if report.warnings.is_empty() {
+            "✅"
+        } else {
+            "⚠️ "
+        };
+        println!("{} {}", mark, id);
+        for error in &report.errors {
+            println!("   error: {}", error);
+        }
+        for warning in &report.warnings {
+            println!("   warning: {}", warning);
+        }
+        valid &= report.is_valid();
+    }
+    Ok(valid)
+}
+
+/// `--watch-archetype`: правки файла архетипа применяются к персоне до следующего сообщения.
+/// После `/persona switch` следится файл новой персоны
+fn reload_edited_archetype(watcher: &mut Option<ArchetypeWatcher>, persona: &mut Option<Persona>, args: &Args) {
+    let Some(p) = persona.as_mut() else {
+        return;
+    };
+    if watcher.as_ref().is_none_or(|w| w.archetype_id() != p.archetype_id) {
+        // --archetype может быть путём к общему файлу, тогда следится он
+        *watcher = [args.archetype.as_str(), p.archetype_id.as_str()]
+            .into_iter()
+            .filter_map(|spec| ArchetypeLoader::watch(spec).ok())
+            .find(|w| w.archetype_id() == p.archetype_id);
+        return;
+    }
+    let Some(w) = watcher.as_mut() else {
+        return;
+    };
+    match w.poll() {
+        Some(Ok(archetype)) => {
+            let adapter = p.adapter.clone();
+            match p.reload_archetype(archetype) {
+                Ok(()) => {
+                    println!("🔄 Archetype reloaded from {}", w.path().display());
+                    if p.adapter != adapter {
+                        println!("   The LoRA adapter changes after a restart or /persona switch");
+                    }
+                }
+                Err(e) => println!("❌ Archetype not reloaded: {}", e),
+            }
+        }
+        Some(Err(e)) => println!("❌ Archetype not reloaded, keeping the previous version: {}", e),
+        None => {}
+    }
+}
+
+/// Загружает файл фактов в семантическую память и сохраняет её, если что-то изменилось
+fn sync_facts_file(semantic_manager: &Arc<std::sync::Mutex<SemanticMemoryManager>>, facts: &mut FactsFile) {
+    let entries = match facts.read() {
+        Ok(entries) => entries,
+        Err(e) => {
+            eprintln!("WARNING: {:#}", e);
+            return;
+        }
+    };
+    let mut sm = semantic_manager.lock().unwrap();
+    match sm.sync_facts_blocking(&facts.source(), entries) {
+        Ok(sync) if sync.added + sync.removed > 0 => {
+            println!(
+                "📌 Facts synced from {}: +{} -{} ({} unchanged)",
+                facts.path().display(),
+                sync.added,
+                sync.removed,
+                sync.unchanged
+            );
+            if let Err(e) = sm.save_blocking() {
+                eprintln!("WARNING: Failed to save semantic memory: {}", e);
+            }
+        }
+        Ok(_) => {}
+        Err(e) => eprintln!("WARNING: Failed to sync facts from {}: {}", facts.path().display(), e),
+    }
+}
+
+/// Читает ответ да/нет из stdin, пустой ввод - значение по умолчанию
+fn ask_yes_no(question: &str, default: bool) -> bool {
+    print!("{} [{}] ", question, if default { "Y/n" } else { "y/N" });
+    let _ = std::io::stdout().flush();
+
+    let mut answer = String::new();
+    if std::io::stdin().read_line(&mut answer).is_err() {
+        return default;
+    }
+    match answer.trim().to_lowercase().as_str() {
+        "" => default,
+        "y" | "yes" | "д" | "да" => true,
+        _ => false,
+    }
+}
+
+/// Спрашивает, сохранять ли отложенные чувствительные концепты. Без интерактивного
+/// режима спросить некого - концепты отбрасываются, но решение не запоминается
+fn confirm_sensitive_concepts(
+    semantic_manager: &Arc<std::sync::Mutex<SemanticMemoryManager>>,
+    interactive: bool,
+) {
+    let pending = semantic_manager.lock().unwrap().take_pending_sensitive();
+    if !interactive {
+        if !pending.is_empty() {
+            debug_log!("DEBUG: dropped {} sensitive concepts (no one to ask)", pending.len());
+        }
+        return;
+    }
+
+    for concept in pending {
+        print!(
+            "🔒 Запомнить ({}): «{}»? [y/n/always/never] ",
+            concept.kind.as_str(),
+            concept.text
+        );
+        let _ = std::io::stdout().flush();
+        let mut answer = String::new();
+        if std::io::stdin().read_line(&mut answer).is_err() {
+            continue;
+        }
+
+        let mut sm = semantic_manager.lock().unwrap();
+        let store = match answer.trim().to_lowercase().as_str() {
+            "y" | "yes" | "д" | "да" => true,
+            "always" | "всегда" => {
+                sm.sensitive_policy_mut().set_action(concept.kind, SensitiveAction::Store);
+                true
+            }
+            "never" | "никогда" => {
+                sm.sensitive_policy_mut().set_action(concept.kind, SensitiveAction::Skip);
+                false
+            }
+            _ => false,
+        };
+        match sm.resolve_sensitive_blocking(concept, store) {
+            Ok(Some(_)) => println!("   💾 Saved"),
+            Ok(None) => println!("   🚫 Not stored"),
+            Err(e) => eprintln!("WARNING: Failed to store concept: {}", e),
+        }
+    }
+}
+
+/// `/sensitive [CATEGORY ask|store|skip]` - политика для чувствительных концептов
+fn handle_sensitive_command(
+    command: &repl::Command,
+    semantic_manager: &Option<Arc<std::sync::Mutex<SemanticMemoryManager>>>,
+) {
+    let Some(sm) = semantic_manager else {
+        println!("Semantic memory is disabled. Use --enable-semantic to enable.");
+        return;
+    };
+    let mut sm = sm.lock().unwrap();
+
+    match command.positional().as_slice() {
+        [] => {
+            println!("\n🔒 Sensitive concepts:");
+            for kind in SensitiveKind::ALL {
+                println!("   {:<13} {:?}", kind.as_str(), sm.sensitive_policy().action(kind));
+            }
+            println!("\n   /sensitive CATEGORY ask|store|skip");
+        }
+        [kind, action] => {
+            let parsed = kind
+                .parse::<SensitiveKind>()
+                .and_then(|kind| Ok((kind, action.parse::<SensitiveAction>()?)));
+            match parsed {
+                Ok((kind, action)) => {
+                    sm.sensitive_policy_mut().set_action(kind, action);
+                    match sm.sensitive_policy().save() {
+                        Ok(()) => println!("✅ {}: {:?}", kind.as_str(), action),
+                        Err(e) => eprintln!("WARNING: Failed to save policy: {}", e),
+                    }
+                }
+                Err(e) => println!("❌ {}", e),
+            }
+        }
+        _ => println!("Usage: /sensitive [CATEGORY ask|store|skip]"),
+    }
+}
+
+fn handle_persona_command(
+    command: &repl::Command,
+    persona: &mut Option<Persona>,
+    dialogue_manager: &mut Option<DialogueManager>,
+    semantic_manager: &Option<Arc<std::sync::Mutex<SemanticMemoryManager>>>,
+    persistence_manager: &PersistenceManager,
+    pipeline_arc: &Arc<std::sync::Mutex<UnifiedPipeline>>,
+    args: &Args,
+) {
+    match command.subcommand.unwrap_or("show") {
+        "show" => {
+            if let Some(ref p) = *persona {
+                println!("\n🎭 Current Persona:");
+                println!("   Name: {}", p.name);
+                println!("   Archetype: {}", p.archetype_id);
+                println!("   Description: {}", p.description);
+                println!("   Style: {}", p.communication.style);
+                println!("   Greeting: {}", p.communication.greeting);
+            } else {
+                println!("No persona loaded.");
+            }
+        }
+        "traits" => {
+            if let Some(ref p) = *persona {
+                println!("\n📊 Persona Traits:");
+                for (trait_name, value) in p.get_all_traits() {
+                    let bar_len = (value * 20.0) as usize;
+                    let bar = "█".repeat(bar_len) + &"░".repeat(20 - bar_len);
+                    println!("   {:<15} [{}] {:.2}", trait_name, bar, value);
+                }
+            } else {
+                println!("No persona loaded.");
+            }
+        }
+        "evolution" => {
+            if let Some(ref p) = *persona {
+                println!("\n📈 Persona Evolution:");
+                println!("   Interactions: {}", p.evolution.interactions_count);
+                println!("   Successful helps: {}", p.evolution.successful_helps);
+                println!("   Relationship score: {:.2}", p.evolution.relationship_score);
+                println!("   Unlocked traits: {:?}", p.evolution.unlocked_traits);
+                if !p.evolution.adjustments.is_empty() {
+                    println!("   Manual adjustments:");
+                    for a in p.evolution.adjustments.iter().rev().take(10) {
+                        println!("      {} {:.2} → {:.2} ({})", a.trait_name, a.from, a.to, a.source);
+                    }
+                }
+            } else {
+                println!("No persona loaded.");
+            }
+        }
+        "set" => {
+            let Some(p) = persona.as_mut() else {
+                println!("No persona loaded.");
+                return;
+            };
+            match (command.arg(0), command.arg(1).and_then(|v| v.parse::<f32>().ok())) {
+                (Some(name), Some(value)) => match p.set_trait(name, value) {
+                    Ok(a) => println!("🎛️  {}: {:.2} → {:.2} (saved)", a.trait_name, a.from, a.to),
+                    Err(e) => println!("❌ {}", e),
+                },
+                _ => println!("Usage: /persona set <trait> <0..1>"),
+            }
+        }
+        "switch" => {
+            if let Some(archetype_name) = command.positional().first() {
+                match ArchetypeLoader::load(archetype_name) {
+                    Ok(archetype) => {
+                        let has_turns = dialogue_manager
+                            .as_ref()
+                            .is_some_and(|dm| dm.current_session().turn_count() > 0);
+
+                        let fresh_session = if command.has_flag("--fresh") {
+                            true
+                        } else if command.has_flag("--keep-session") || !has_turns {
+                            false
+                        } else {
+                            ask_yes_no("   Start a fresh session for the new persona?", true)
+                        };
+                        let carry_semantic = if command.has_flag("--isolate") {
+                            false
+                        } else if command.has_flag("--carry") || semantic_manager.is_none() {
+                            true
+                        } else {
+                            ask_yes_no("   Carry over what you know about the user (semantic memory)?", true)
+                        };
+
+                        let mut p = load_persona(archetype, semantic_manager.as_ref().filter(|_| carry_semantic));
+                        if let Err(e) = switch_adapter(pipeline_arc, args, p.adapter.as_ref()) {
+                            eprintln!("WARNING: Failed to apply adapter, keeping the current model: {:#}", e);
+                        }
+
+                        if let Some(ref mut dm) = dialogue_manager {
+                            let scope = LoadScope::persona(p.archetype_id.clone()).recent(args.memory_window_days);
+                            load_deferred_sessions(dm, persistence_manager, |_, d| {
+                                scope.includes(&d.persona_name, d.updated_at)
+                            });
+                            if fresh_session {
+                                dm.start_new_session(p.archetype_id.clone());
+                            }
+                        }
+
+                        let from = persona
+                            .as_ref()
+                            .map_or_else(|| "none".to_string(), |old| old.archetype_id.clone());
+                        let to = p.archetype_id.clone();
+                        if let Some(ref mut old) = persona {
+                            old.record_switch(&from, &to, fresh_session, carry_semantic);
+                        }
+                        p.record_switch(&from, &to, fresh_session, carry_semantic);
+
+                        println!("🎭 Switched to persona: {} ({})", p.name, p.archetype_id);
+                        println!(
+                            "   Session: {}, semantic memory: {}",
+                            if fresh_session { "fresh" } else { "continued" },
+                            if carry_semantic { "shared" } else { "isolated" }
+                        );
+                        *persona = Some(p);
+                    }
+                    Err(e) => {
+                        eprintln!("Failed to load archetype '{}': {}", archetype_name, e);
+                        eprintln!("Available: {:?}", ArchetypeLoader::list_ids().unwrap_or_default());
+                    }
+                }
+            } else {
+                println!("Usage: /persona switch <archetype>");
+                println!("Available: {:?}", ArchetypeLoader::list_ids().unwrap_or_default());
+            }
+        }
+        "list" => {
+            println!("\n📋 Available Archetypes:");
+            match ArchetypeLoader::list_ids() {
+                Ok(ids) => {
+                    for id in ids {
+                        println!("   - {}", id);
+                    }
+                }
+                Err(e) => eprintln!("Error listing archetypes: {}", e),
+            }
+        }
+        _ => print!("{}", repl::command_help(command.spec)),
+    }
+}
+
+/// `/context` - контекст сессии, который будет восстановлен при следующем запуске
+fn show_session_context(persona: &mut Option<Persona>) {
+    if let Some(ref mut p) = persona {
+        match p.load_session_context() {
+            Ok(Some(context)) => {
+                println!("\n💭 Session Context:");
+                println!("   Version: {}", context.version);
+                println!("   Last interaction: <timestamp>");
+
+                if !context.summary.is_empty() {
+                    println!("   Summary: {}", context.summary);
+                }
+
+                if !context.key_topics.is_empty() {
+                    println!("   Topics: {}", context.key_topics.join(", "));
+                }
+
+                println!("   Emotional state: {:.1}", context.emotional_state);
+
+                if !context.last_topic.is_empty() {
+                    println!("   Last topic: {}", context.last_topic);
+                }
+
+                if !context.pending_questions.is_empty() {
+                    println!("   Pending questions:");
+                    for q in &context.pending_questions {
+                        println!("     - {}", q);
+                    }
+                }
+
+                println!("\n   💡 This context will be restored in the next session.");
+            }
+            Ok(None) => {
+                println!("\n💭 No saved context found.");
+                println!("   Start a conversation to create context for the next session.");
+            }
+            Err(e) => {
+                println!("\n❌ Error loading context: {}", e);
+            }
+        }
+    } else {
+        println!("\n💭 No persona loaded.");
+    }
+}
+
+/// `/address [formal|informal|auto]` - показывает или закрепляет обращение на Вы/ты
+fn handle_address_command(command: &repl::Command, persona: &mut Option<Persona>) {
+    let Some(p) = persona.as_mut() else {
+        println!("No persona loaded.");
+        return;
+    };
+
+    match command.arg(0) {
+        None | Some("show") => {
+            println!("\n🎩 Address: {}", p.address_style());
+            match (p.address.pinned, p.address.observed) {
+                (Some(_), _) => println!("   Pinned with /address (use '/address auto' to follow the user)"),
+                (None, Some(_)) => println!("   Detected from your messages"),
+                (None, None) => println!("   Persona default, no signal from your messages yet"),
+            }
+            return;
+        }
+        Some("auto") => {
+            p.address.pin(None);
+            println!("🎩 Address follows your messages: {}", p.address_style());
+        }
+        Some(value) => match AddressStyle::parse(value) {
+            Some(style) => {
+                p.address.pin(Some(style));
+                println!("🎩 Address pinned: {}", style);
+            }
+            None => {
+                println!("Usage: /address [formal|informal|auto]");
+                return;
+            }
+        },
+    }
+
+    if let Err(e) = p.save_address() {
+        eprintln!("WARNING: Failed to save address style: {}", e);
+    }
+}
+
+/// Сколько сессий показывает `/sessions search`
+const SESSION_SEARCH_LIMIT: usize = 10;
+
+/// Подгружает отложенные сессии (старые и других персон) и сообщает, сколько загружено
+fn load_deferred_sessions(
+    dm: &mut DialogueManager,
+    persistence_manager: &PersistenceManager,
+    filter: impl Fn(&uuid::Uuid, &DeferredSession) -> bool,
+) {
+    match persistence_manager.load_deferred_blocking(dm, filter) {
+        Ok(0) => {}
+        Ok(count) => println!("📂 Loaded {} more sessions from disk", count),
+        Err(e) => eprintln!("WARNING: Failed to load deferred sessions: {}", e),
+    }
+}
+
+/// Часовой пояс пользователя: `--timezone` или системный
+fn user_utc_offset(args: &Args) -> chrono::FixedOffset {
+    args.timezone.unwrap_or_else(|| *chrono::Local::now().offset())
+}
+
+/// Пороги уверенности концептов из `--certain-confidence` и `--tentative-confidence`
+fn confidence_phrasing(args: &Args) -> ConfidencePhrasing {
+    ConfidencePhrasing {
+        certain: args.certain_confidence,
+        tentative: args.tentative_confidence,
+    }
+}
+
+/// Ходов продолженной сессии, которые показываются при возобновлении и идут в промпт
+const RESUME_CONTEXT_TURNS: usize = 5;
+
+/// Делает прошлую сессию текущей (подгружая её с диска при необходимости) и показывает последние ходы
+fn resume_past_session(dm: &mut DialogueManager, persistence_manager: &PersistenceManager, id: &str) -> Result<()> {
+    let session_id = dm
+        .find_session_id(id)?
+        .ok_or_else(|| anyhow::anyhow!("Session '{}' not found", id))?;
+    load_deferred_sessions(dm, persistence_manager, |d, _| *d == session_id);
+    anyhow::ensure!(dm.resume_session(session_id), "Session {} is not loaded", session_id);
+
+    let session = dm.current_session();
+    println!(
+        "↩️  Resumed session {} ({}, {} turns), new turns are appended to it",
+        session.id,
+        session.persona_name,
+        session.turn_count()
+    );
+    for turn in session.last_turns(RESUME_CONTEXT_TURNS) {
+        println!("\n[{}] 👤 {}", turn.timestamp.format("%Y-%m-%d %H:%M"), turn.user);
+        println!("🤖 {}", truncate_text(&turn.assistant, 300));
+    }
+    Ok(())
+}
+
+/// `/sessions [search QUERY | open ID | load | resume ID]` - поиск по истории, включая архив
+/// `/docs ingest PATH...` и `/docs list`
+fn handle_docs_command(command: &repl::Command) {
+    let Some(store) = DOCUMENTS.get() else {
+        println!("Documents are unavailable (see the warning at startup).");
+        return;
+    };
+    let mut store = store.lock().unwrap();
+
+    match command.subcommand.unwrap_or("list") {
+        "ingest" if !command.positional().is_empty() => {
+            for arg in command.positional() {
+                let path = std::path::Path::new(arg);
+                // каталог - все поддерживаемые файлы в нём, без подкаталогов
+                let files: Vec<std::path::PathBuf> = if path.is_dir() {
+                    let mut files: Vec<_> = std::fs::read_dir(path)
+                        .into_iter()
+                        .flatten()
+                        .filter_map(|entry| entry.ok().map(|e| e.path()))
+                        .filter(|p| p.is_file() && DocumentFormat::from_path(p).is_some())
+                        .collect();
+                    files.sort();
+                    files
+                } else {
+                    vec![path.to_path_buf()]
+                };
+                if files.is_empty() {
+                    println!("❌ No .pdf, .md or .txt files in {}", path.display());
+                }
+                for file in files {
+                    let started = std::time::Instant::now();
+                    match store.ingest_file_blocking(&file) {
+                        Ok(Ingested::Unchanged(d)) => println!("✓ {} is unchanged ({} excerpts)", d.title, d.chunks),
+                        Ok(ingested) => {
+                            let d = ingested.document();
+                            let verb = if matches!(ingested, Ingested::Replaced(_)) { "Updated" } else { "Added" };
+                            println!(
+                                "📄 {} \"{}\": {}, {} chars in {} excerpts ({:.1}s)",
+                                verb,
+                                d.title,
+                                d.format.as_str(),
+                                d.chars,
+                                d.chunks,
+                                started.elapsed().as_secs_f32()
+                            );
+                        }
+                        Err(e) => println!("❌ {}: {:#}", file.display(), e),
+                    }
+                }
+            }
+        }
+        "list" => {
+            let documents = store.documents();
+            if documents.is_empty() {
+                println!("No documents. Add one with /docs ingest PATH (.pdf, .md, .txt)");
+                return;
+            }
+            println!("\n📚 Documents: {} ({} excerpts)", documents.len(), store.chunk_count());
+            for d in documents {
+                println!(
+                    "   {} {} [{}, {} excerpts, {}] {}",
+                    &d.id.to_string()[..8],
+                    d.title,
+                    d.format.as_str(),
+                    d.chunks,
+                    d.ingested_at.format("%Y-%m-%d"),
+                    d.path.display()
+                );
+            }
+        }
+        _ => print!("{}", repl::command_help(command.spec)),
+    }
+}
+
+fn handle_sessions_command(
+    command: &repl::Command,
+    dialogue_manager: &mut Option<DialogueManager>,
+    persistence_manager: &PersistenceManager,
+) {
+    let Some(dm) = dialogue_manager else {
+        println!("Dialogue memory is disabled. Use --enable-memory to enable.");
+        return;
+    };
+    let arg = command.rest();
+    let arg = arg.as_str();
+
+    match command.subcommand.unwrap_or("list") {
+        "list" => {
+            let archived = dm.archive().map(|a| a.lock().len().unwrap_or(0)).unwrap_or(0);
+            println!(
+                "\n🗂️ Sessions: {} in memory, {} on disk (older or other personas), {} archived",
+                dm.session_history().len() + 1,
+                dm.deferred_sessions().len(),
+                archived
+            );
+            println!("   /sessions search QUERY - search history and archive summaries");
+            println!("   /sessions open ID      - unpack an archived session");
+            println!("   /sessions load         - load sessions left on disk");
+            println!("   /sessions resume ID    - continue a past session");
+        }
+        "resume" if !arg.is_empty() => {
+            if let Err(e) = resume_past_session(dm, persistence_manager, arg) {
+                println!("❌ {}", e);
+            }
+        }
+        "load" => {
+            if dm.deferred_sessions().is_empty() {
+                println!("All sessions are already loaded.");
+            }
+            load_deferred_sessions(dm, persistence_manager, |_, _| true);
+        }
+        "search" if !arg.is_empty() => {
+            load_deferred_sessions(dm, persistence_manager, |_, _| true);
+            let live = dm.search_sessions(command.text(), SESSION_SEARCH_LIMIT);
+            println!("\n🔎 In memory: {}", live.len());
+            for session in live {
+                let first = session.turns.first().map_or("", |t| t.user.as_str());
+                println!(
+                    "   {} {} ({} turns) {}",
+                    &session.id.to_string()[..8],
+                    session.updated_at.format("%Y-%m-%d"),
+                    session.turn_count(),
+                    truncate_text(first, 60)
+                );
+            }
+
+            let Some(archive) = dm.archive() else {
+                return;
+            };
+            match archive.lock().search(command.text(), SESSION_SEARCH_LIMIT) {
+                Ok(hits) => {
+                    println!("🗄️ Archived: {}", hits.len());
+                    for (score, entry) in hits {
+                        println!(
+                            "   {} {} ({} turns, {:.0}%) {}",
+                            &entry.id[..8.min(entry.id.len())],
+                            entry.updated_at.format("%Y-%m-%d"),
+                            entry.turn_count,
+                            score * 100.0,
+                            entry.summary
+                        );
+                    }
+                }
+                Err(e) => eprintln!("WARNING: Failed to search archive: {}", e),
+            }
+        }
+        "open" if !arg.is_empty() => {
+            let Some(archive) = dm.archive() else {
+                println!("No session archive.");
+                return;
+            };
+            match archive.lock().open_session(arg) {
+                Ok(session) => {
+                    println!(
+                        "\n🗄️ Session {} ({}, {} turns)",
+                        session.id,
+                        session.persona_name,
+                        session.turns.len()
+                    );
+                    for turn in &session.turns {
+                        println!("\n[{}] 👤 {}", turn.timestamp.format("%Y-%m-%d %H:%M"), turn.user);
+                        println!("🤖 {}", turn.assistant);
+                    }
+                }
+                Err(e) => println!("❌ {}", e),
+            }
+        }
+        _ => println!("Usage: /sessions [search QUERY | open ID | load | resume ID]"),
+    }
+}
+
+/// Сколько сессий показывает `/memory list` без числа
+const MEMORY_LIST_LIMIT: usize = 20;
+/// Сколько ходов показывает `/memory search`
+const MEMORY_SEARCH_LIMIT: usize = 5;
+/// Сколько последних недель показывает `/context timeline` без числа
+const TIMELINE_WEEKS: usize = 8;
+
+/// `/context timeline [weeks]`: о чём были прошлые сессии, неделя за неделей
+fn show_timeline(command: &repl::Command, dialogue_manager: &Option<DialogueManager>) {
+    let Some(dm) = dialogue_manager else {
+        println!("Dialogue memory is disabled. Use --enable-memory to enable.");
+        return;
+    };
+    let weeks = match command.rest().as_str() {
+        "" => TIMELINE_WEEKS,
+        n => match n.parse() {
+            Ok(n) if n > 0 => n,
+            _ => {
+                println!("Usage: /context timeline [weeks]");
+                return;
+            }
+        },
+    };
+
+    let entries = dm.get_timeline();
+    if entries.is_empty() {
+        println!("\n🗓️  No analyzed sessions yet: a session is summarized when its context is saved on exit");
+        return;
+    }
+    let all = timeline::by_week(&entries);
+    let shown = &all[all.len().saturating_sub(weeks)..];
+    println!("\n🗓️  Timeline, last {} of {} weeks:", shown.len(), all.len());
+    for (week, sessions) in shown {
+        println!("   {}", week);
+        for entry in sessions {
+            println!(
+                "     {} {} {} - {} turns: {}",
+                &entry.session_id.to_string()[..8],
+                entry.at.format("%Y-%m-%d"),
+                entry.persona_name,
+                entry.turns,
+                truncate_text(&entry.analysis.summary, 100)
+            );
+            if !entry.analysis.key_topics.is_empty() {
+                println!(
+                    "       Topics: {} (mood {:.1})",
+                    entry.analysis.key_topics.join(", "),
+                    entry.analysis.emotional_state
+                );
+            }
+        }
+    }
+}
+
+/// `/memory list [N] | search QUERY | session ID | delete ID | stats` - просмотр и правка эпизодической памяти
+fn handle_memory_command(
+    command: &repl::Command,
+    dialogue_manager: &mut Option<DialogueManager>,
+    persistence_manager: &PersistenceManager,
+    embedder: &Arc<dyn Embedder>,
+) {
+    let Some(dm) = dialogue_manager else {
+        println!("Dialogue memory is disabled. Use --enable-memory to enable.");
+        return;
+    };
+    let arg = command.rest();
+    let arg = arg.as_str();
+
+    match command.subcommand {
+        Some("list") => {
+            let limit = match arg {
+                "" => MEMORY_LIST_LIMIT,
+                n => match n.parse() {
+                    Ok(n) => n,
+                    Err(_) => {
+                        println!("Usage: /memory list [n]");
+                        return;
+                    }
+                },
+            };
+            let sessions = dm.list_sessions(limit);
+            if sessions.is_empty() {
+                println!("Nothing remembered yet.");
+            } else {
+                println!("\n🧠 Sessions, newest first:");
+            }
+            for s in sessions {
+                println!(
+                    "   {} {} {} - {} turns, {} indexed{}: {}",
+                    &s.id.to_string()[..8],
+                    s.updated_at.format("%Y-%m-%d %H:%M"),
+                    s.persona_name,
+                    s.turns,
+                    s.indexed,
+                    if s.current { " (current)" } else { "" },
+                    truncate_text(&s.first_question, 60)
+                );
+            }
+            let deferred = dm.deferred_sessions().len();
+            if deferred > 0 {
+                println!("   {} more sessions on disk: /sessions load", deferred);
+            }
+        }
+        Some("search") if !arg.is_empty() => match dm.search_memory_blocking(command.text(), MEMORY_SEARCH_LIMIT) {
+            Ok(hits) if hits.is_empty() => println!("Nothing remembered about that."),
+            Ok(hits) => {
+                for hit in hits {
+                    let recalled = match hit.recall_count {
+                        0 => String::new(),
+                        n => format!(", recalled {}x", n),
+                    };
+                    println!(
+                        "\n{:.0}% {}#{} [{}{}]\n   👤 {}\n   🤖 {}",
+                        hit.similarity * 100.0,
+                        &hit.session_id.to_string()[..8],
+                        hit.turn + 1,
+                        hit.timestamp.format("%Y-%m-%d %H:%M"),
+                        recalled,
+                        truncate_text(&hit.user, 120),
+                        truncate_text(&hit.assistant, 200)
+                    );
+                }
+            }
+            Err(e) => println!("❌ {}", e),
+        },
+        Some(sub @ ("session" | "delete")) if !arg.is_empty() => {
+            let session_id = match dm.find_session_id(arg) {
+                Ok(Some(id)) => id,
+                Ok(None) => {
+                    println!("❌ Session '{}' not found (archived sessions: /sessions open)", arg);
+                    return;
+                }
+                Err(e) => {
+                    println!("❌ {}", e);
+                    return;
+                }
+            };
+            load_deferred_sessions(dm, persistence_manager, |d, _| *d == session_id);
+            let Some(session) = dm.session(&session_id) else {
+                println!("❌ Session {} is not loaded", session_id);
+                return;
+            };
+            println!("\n🧠 Session {} ({}, {} turns)", session.id, session.persona_name, session.turn_count());
+            if sub == "session" {
+                for (i, turn) in session.turns.iter().enumerate() {
+                    println!("\n#{} [{}] 👤 {}", i + 1, turn.timestamp.format("%Y-%m-%d %H:%M"), turn.user);
+                    println!("🤖 {}", turn.assistant);
+                }
+                return;
+            }
+            if !ask_yes_no("Forget this session?", false) {
+                return;
+            }
+            dm.delete_session(session_id);
+            println!("🗑️ Forgot session {}; facts learned from it stay in semantic memory", &session_id.to_string()[..8]);
+            if let Err(e) = persistence_manager.save_with_embeddings_blocking(dm, embedder.embedding_dim()) {
+                eprintln!("WARNING: Failed to save memory: {}", e);
+            }
+        }
+        Some("stats") => {
+            println!("\n{}", dm.stats().format());
+            if let Some(archive) = dm.archive() {
+                println!("   Archived Sessions: {}", archive.lock().len().unwrap_or(0));
+            }
+        }
+        _ => println!("Usage: /memory [list [N] | search QUERY | session ID | delete ID | stats | on | off]"),
+    }
+}
+
+/// Сколько закладок показывает `/bookmarks QUERY`
+const BOOKMARK_RECALL_LIMIT: usize = 3;
+
+/// `/bookmark [NOTE]` отмечает последний ход, `/bookmarks [QUERY]` - список или поиск по закладкам
+fn handle_bookmark_command(
+    command: &repl::Command,
+    dialogue_manager: &mut Option<DialogueManager>,
+    persistence_manager: &PersistenceManager,
+    embedder: &Arc<dyn Embedder>,
+) {
+    let Some(dm) = dialogue_manager else {
+        println!("Dialogue memory is disabled. Use --enable-memory to enable.");
+        return;
+    };
+    let arg = command.text();
+
+    if command.name() == "bookmark" {
+        match dm.bookmark_last_turn(arg) {
+            Some(turn) => {
+                println!("🔖 Bookmarked turn {} of this session", turn + 1);
+                if let Err(e) = persistence_manager.save_with_embeddings_blocking(dm, embedder.embedding_dim()) {
+                    eprintln!("WARNING: Failed to save memory: {}", e);
+                }
+            }
+            None => println!("Nothing to bookmark yet."),
+        }
+        return;
+    }
+
+    // закладки могут быть и в сессиях, оставленных на диске
+    load_deferred_sessions(dm, persistence_manager, |_, _| true);
+    if arg.is_empty() {
+        let bookmarks = dm.bookmarks();
+        if bookmarks.is_empty() {
+            println!("No bookmarks yet. /bookmark [note] marks the last exchange.");
+        }
+        for b in bookmarks {
+            let note = if b.note.is_empty() { String::new() } else { format!(" [{}]", b.note) };
+            println!(
+                "🔖 {} {}{} {}",
+                &b.session_id.to_string()[..8],
+                b.timestamp.format("%Y-%m-%d %H:%M"),
+                note,
+                truncate_text(&b.user, 60)
+            );
+        }
+        return;
+    }
+    match dm.recall_bookmarks_blocking(arg, BOOKMARK_RECALL_LIMIT) {
+        Ok(hits) if hits.is_empty() => println!("No bookmarks yet."),
+        Ok(hits) => {
+            for (score, b) in hits {
+                let note = if b.note.is_empty() { String::new() } else { format!(" [{}]", b.note) };
+                println!("\n🔖 {}{} ({:.0}%)", b.timestamp.format("%Y-%m-%d %H:%M"), note, score * 100.0);
+                println!("👤 {}", b.user);
+                println!("🤖 {}", b.assistant);
+            }
+        }
+        Err(e) => println!("❌ {}", e),
+    }
+}
+
+/// Загружает Mistral (локально или с HF Hub) и собирает пайплайн генерации
+fn download_config(args: &Args) -> DownloadConfig {
+    DownloadConfig {
+        mirror: args.hf_mirror.clone(),
+        retries: args.download_retries,
+        progress: !args.quiet,
+    }
+}
+
+fn load_pipeline(args: &Args, device: &Device) -> Result<UnifiedPipeline> {
+    load_pipeline_with_adapter(args, device, archetype_adapter(&args.archetype).as_ref())
+}
+
+/// Время, на котором стоят часы профиля `--ci`
+const CI_EPOCH: &str = "2024-01-01T09:00:00Z";
+
+/// Профиль `--ci`: модели `--smoke-test`, но память пишется - в новый временный
+/// каталог, если `--data-dir` не задан, - а часы памяти и персоны стоят на [`CI_EPOCH`],
+/// так что прогон воспроизводим и ничего не скачивает
+fn apply_ci_profile(args: &mut Args) -> Result<()> {
+    args.smoke_test = true;
+    let dir = match args.data_dir.clone() {
+        Some(dir) => dir,
+        None => {
+            let dir = std::env::temp_dir().join(format!("zikkurat-ci-{}", std::process::id()));
+            std::fs::create_dir_all(&dir)
+                .with_context(|| format!("Failed to create CI data dir {}", dir.display()))?;
+            args.data_dir = Some(dir.clone());
+            dir
+        }
+    };
+    let epoch = chrono::DateTime::parse_from_rfc3339(CI_EPOCH)?.with_timezone(&chrono::Utc);
+    utils::clock::install(Arc::new(utils::clock::MockClock::new(epoch)));
+    println!("🧪 CI profile: echo model, dummy embeddings, memory in {}, clock at {}", dir.display(), CI_EPOCH);
+    Ok(())
+}
+
+/// Загружает базовую модель и вливает в неё LoRA-адаптер, если он задан
+fn load_pipeline_with_adapter(
+    args: &Args,
+    device: &Device,
+    adapter: Option<&AdapterConfig>,
+) -> Result<UnifiedPipeline> {
+    if args.smoke_test {
+        let mut pipeline = UnifiedPipeline::new(
+            Backend::Echo(EchoModel),
+            device.clone(),
+            EchoModel::window().with_limit(args.max_context),
+            Some(args.temperature),
+            args.top_p,
+            args.top_k,
+            1.1,
+            64,
+        );
+        pipeline.adapter = adapter.cloned();
+        pipeline.chat_template = args.chat_template.unwrap_or_default();
+        return Ok(pipeline);
+    }
+
+    let model_id = args
+        .model_id
+        .clone()
+        .unwrap_or_else(|| "mistralai/Mistral-7B-Instruct-v0.2".to_string());
+
+    let local_mistral_path = resolve_path("models/mistral-7b-instruct");
+    // An explicit --model-id (e.g. Mistral-Nemo) wins over the bundled 7B
+    let use_local_path = args.model_id.is_none()
+        && local_mistral_path.exists()
+        && local_mistral_path.join("tokenizer.json").exists()
+        && local_mistral_path
+            .join("model.safetensors.index.json")
+            .exists();
+
+    let (tokenizer, filenames, config_path, tokenizer_config): (
+        Tokenizer,
+        Vec<std::path::PathBuf>,
+        std::path::PathBuf,
+        Option<std::path::PathBuf>,
+    ) = if use_local_path {
+        let local_path = local_mistral_path.clone();
+
+        let tokenizer = Tokenizer::from_file(local_path.join("tokenizer.json")).map_err(E::msg)?;
+
+        let index_path = local_path.join("model.safetensors.index.json");
+        let index_content = std::fs::read_to_string(&index_path)?;
+        let index: serde_json::Value = serde_json::from_str(&index_content)?;
+
+        let mut unique_files = std::collections::HashSet::<String>::new();
+        if let Some(weight_map) = index.get("weight_map").and_then(|v| v.as_object()) {
+            for file in weight_map.values() {
+                if let Some(file_str) = file.as_str() {
+                    unique_files.insert(file_str.to_string());
+                }
+            }
+        }
+
+        if unique_files.is_empty() {
+            anyhow::bail!("No weight files found in safetensors index");
+        }
+
+        let mut filenames: Vec<_> = unique_files.into_iter().collect();
+        filenames.sort();
+
+        debug_log!(
+            "DEBUG: Found {} weight files: {:?}",
+            filenames.len(),
+            filenames
+        );
+
+        let tokenizer_config = Some(local_path.join("tokenizer_config.json")).filter(|p| p.exists());
+        (
+            tokenizer,
+            filenames.into_iter().map(|f| local_path.join(f)).collect(),
+            local_path.join("config.json"),
+            tokenizer_config,
+        )
+    } else {
+        let downloader = ModelDownloader::new(&model_id, args.revision.clone(), &download_config(args))?;
+        let tokenizer_filename = downloader.get("tokenizer.json")?;
+        let tokenizer = Tokenizer::from_file(tokenizer_filename).map_err(E::msg)?;
+        let filenames = downloader.safetensors("model.safetensors.index.json")?;
+        // Only used to detect the chat template, not every repo has one
+        let tokenizer_config = downloader.get("tokenizer_config.json").ok();
+        (tokenizer, filenames, downloader.get("config.json")?, tokenizer_config)
+    };
+
+    // Check available memory before loading model
+    let available_memory_mb = get_memory_mb();
+    let is_cuda = device.is_cuda();
+
+    if !is_cuda && available_memory_mb > 0 {
+        let required_memory_mb = 18000; // ~18GB for full model + overhead
+        if available_memory_mb < required_memory_mb {
+            eprintln!("\n⚠️  WARNING: Low memory situation!");
+            eprintln!("   Available: {} MB", available_memory_mb);
+            eprintln!("   Required:  ~{} MB for Mistral 7B", required_memory_mb);
+            eprintln!("\n   Options:");
+            eprintln!("   1. Use GPU (CUDA) - recommended");
+            eprintln!("   2. Close other applications to free RAM");
+            eprintln!("   3. Use a smaller model (7B quantized)");
+            eprintln!("\n   Continuing anyway, but may encounter OOM...\n");
+        }
+    }
+
+    log_memory_usage("before_model_load");
+
+    let mut config: Config = serde_json::from_slice(&std::fs::read(config_path)?)?;
+
+    // Budgets follow the model's own config, not Mistral-7B defaults
+    let window = ContextWindow::from_config(&config).with_limit(args.max_context);
+    window.apply_to(&mut config);
+    println!("📐 Context window: {}", window);
+
+    debug_log!(
+        "DEBUG: Config loaded - hidden_size: {}, num_heads: {}, num_layers: {}",
+        config.hidden_size, config.num_attention_heads, config.num_hidden_layers
+    );
+
+    let dtype = if device.is_cuda() {
+        println!("🎯 Using GPU (BF16 precision)");
+        DType::BF16
+    } else {
+        // CPU fallback: use quantized types to save memory
+        let available_memory_mb = get_memory_mb();
+        let mem_threshold = 16000; // 16GB threshold
+
+        if available_memory_mb > mem_threshold {
+            println!("💻 CPU mode: {} MB RAM available, using F32", available_memory_mb);
+            DType::F32
+        } else {
+            // Low memory: warn user
+            if available_memory_mb > 0 {
+                eprintln!("⚠️  WARNING: Only {} MB RAM available!", available_memory_mb);
+                eprintln!("    Mistral 7B requires ~16GB on CPU. Consider using GPU.");
+            }
+            println!("💻 CPU mode: F32 (full precision)");
+            DType::F32
+        }
+    };
+    let lora = match adapter {
+        Some(cfg) => {
+            let path = resolve_path(&cfg.path);
+            let lora = LoraAdapter::load(&path, cfg.scale)
+                .with_context(|| format!("Failed to load LoRA adapter {}", path.display()))?;
+            println!(
+                "🧬 LoRA adapter: {} ({} weights, scaling {:.2})",
+                path.display(),
+                lora.target_count(),
+                lora.scaling
+            );
+            Some(lora)
+        }
+        None => None,
+    };
+    let chat_template = resolve_chat_template(args, tokenizer_config.as_deref(), &tokenizer);
+
+    let vb = var_builder_with_adapter(&filenames, dtype, device, lora)?;
+    let model = Mistral::new(&config, vb)?;
+
+    let mut pipeline = UnifiedPipeline::new(
+        Backend::Mistral { model, tokenizer: Box::new(tokenizer) },
+        device.clone(),
+        window,
+        Some(args.temperature),
+        args.top_p,
+        args.top_k,
+        1.1,
+        64,
+    );
+    pipeline.adapter = adapter.cloned();
+    pipeline.watchdog = Watchdog::new(std::time::Duration::from_secs(args.generation_timeout));
+    pipeline.loop_guard = LoopGuard::new(args.loop_ngram);
+    pipeline.prefix_cache = PrefixCache::new(args.prefix_cache_min_tokens);
+    pipeline.chat_template = chat_template;
+    Ok(pipeline)
+}
+
+/// Шаблон чата: `--chat-template`, иначе из `tokenizer_config.json` или словаря модели, иначе Mistral
+fn resolve_chat_template(args: &Args, tokenizer_config: Option<&std::path::Path>, tokenizer: &Tokenizer) -> ChatTemplate {
+    let (template, source) = match args.chat_template {
+        Some(template) => (template, "--chat-template"),
+        None => {
+            let from_config = tokenizer_config.and_then(|path| match ChatTemplate::from_tokenizer_config(path) {
+                Ok(template) => template,
+                Err(e) => {
+                    eprintln!("WARNING: {}", e);
+                    None
+                }
+            });
+            match from_config {
+                Some(template) => (template, "tokenizer_config.json"),
+                None => match ChatTemplate::from_vocab(|token| tokenizer.token_to_id(token).is_some()) {
+                    Some(template) => (template, "tokenizer vocabulary"),
+                    None => (ChatTemplate::default(), "default"),
+                },
+            }
+        }
+    };
+    println!("💬 Chat template: {} ({})", template, source);
+    template
+}
+
+/// Сервер эмбеддингов из `--embedding-backend`, `--embedding-url` и `--embedding-model`
+fn remote_embedder_config(args: &Args) -> RemoteEmbedderConfig {
+    let mut config = RemoteEmbedderConfig::new(args.embedding_backend);
+    if let Some(ref url) = args.embedding_url {
+        config.url = url.clone();
+    }
+    if let Some(ref model) = args.embedding_model {
+        config.model = model.clone();
+    }
+    config.api_key = std::env::var("ZIKKURAT_EMBEDDING_API_KEY")
+        .ok()
+        .or_else(|| match args.embedding_backend {
+            EmbeddingBackend::OpenAi => std::env::var("OPENAI_API_KEY").ok(),
+            _ => None,
+        })
+        .filter(|key| !key.is_empty());
+    config
+}
+
+/// Загружает модель эмбеддингов из `--embedding-path` или подключается к серверу эмбеддингов
+fn load_embedder(args: &Args, embedding_path: &std::path::Path, device: &Device) -> Result<Arc<dyn Embedder>> {
+    if args.embedding_backend != EmbeddingBackend::Local {
+        let config = remote_embedder_config(args);
+        println!("🌐 Connecting to {} embeddings at {} ({})", args.embedding_backend.as_str(), config.url, config.model);
+        return Ok(Arc::new(RemoteEmbedder::connect(config)?));
+    }
+    println!(
+        "🧠 Loading embedding engine from: {}",
+        embedding_path.display()
+    );
+
+    if !embedding_path.exists() {
+        anyhow::bail!(
+            "Embedding model not found at: {}\n\
+             Current directory: {:?}\n\
+             Resolved from: {:?}",
+            embedding_path.display(),
+            std::env::current_dir().unwrap_or_default(),
+            args.embedding_path
+        );
+    }
+
+    Ok(Arc::new(EmbeddingEngine::new(
+        embedding_path.to_str().unwrap_or(&args.embedding_path),
+        device.clone(),
+    )?))
+}
+
+/// Имя модели эмбеддингов, которым подписываются векторы в `memory_data`: каталог модели,
+/// а не весь путь, чтобы `models/e5` и `./models/e5` не считались разными моделями
+fn embedding_model_id(args: &Args) -> String {
+    if args.smoke_test {
+        return "dummy".to_string();
+    }
+    if args.embedding_backend != EmbeddingBackend::Local {
+        let config = remote_embedder_config(args);
+        return format!("{}:{}", args.embedding_backend.as_str(), config.model);
+    }
+    std::path::Path::new(&args.embedding_path)
+        .file_name()
+        .map_or_else(|| args.embedding_path.clone(), |name| name.to_string_lossy().into_owned())
+}
+
+/// Файл warm-start бандла и отпечаток модели и эмбеддера, для которых он сохранён
+fn warm_start_target(args: &Args) -> Option<(std::path::PathBuf, String)> {
+    let path = resolve_path(args.warm_start.as_deref()?);
+    let model = if args.smoke_test {
+        "echo"
+    } else {
+        args.model_id.as_deref().unwrap_or("default")
+    };
+    let embeddings = match args.embedding_backend {
+        EmbeddingBackend::Local => args.embedding_path.clone(),
+        _ => embedding_model_id(args),
+    };
+    let fingerprint = format!("{}@{}|{}", model, args.revision, embeddings);
+    Some((path, fingerprint))
+}
+
+/// Прогревает кэши только что загруженной модели бандлом прошлого запуска
+fn restore_warm_start(
+    target: Option<&(std::path::PathBuf, String)>,
+    pipeline_arc: &Arc<std::sync::Mutex<UnifiedPipeline>>,
+    embedder: &Arc<dyn Embedder>,
+) {
+    let Some((path, fingerprint)) = target else {
+        return;
+    };
+    match WarmStart::load(path, fingerprint) {
+        Ok(Some(bundle)) => {
+            println!(
+                "♨️  Warm start from {} ({} prompt paragraphs, {} embeddings, saved {})",
+                path.display(),
+                bundle.prompt_tokens.entries.len(),
+                bundle.embeddings.len(),
+                bundle.saved_at().format("%Y-%m-%d %H:%M")
+            );
+            bundle.restore(&pipeline_arc.lock().unwrap().prompt_tokens, embedder.as_ref());
+        }
+        Ok(None) => println!("♨️  No warm-start bundle for this model yet, one is saved on exit"),
+        Err(e) => eprintln!("⚠️  Ignoring warm-start bundle: {:#}", e),
+    }
+}
+
+/// Сохраняет кэши при выходе: снимок под блокировкой модели, запись - после неё
+fn save_warm_start(
+    target: Option<&(std::path::PathBuf, String)>,
+    pipeline_arc: &Arc<std::sync::Mutex<UnifiedPipeline>>,
+    embedder: &Arc<dyn Embedder>,
+) {
+    let Some((path, fingerprint)) = target else {
+        return;
+    };
+    let bundle = {
+        let pipeline = pipeline_arc.lock().unwrap();
+        WarmStart::capture(fingerprint, &pipeline.prompt_tokens, embedder.as_ref())
+    };
+    match bundle.save(path) {
+        Ok(()) => println!("♨️  Warm-start bundle saved"),
+        Err(e) => eprintln!("WARNING: Failed to save warm-start bundle: {:#}", e),
+    }
+}
+
+/// Настройки процесса из `--system-config`; без файла - по умолчанию
+fn load_system_config(args: &Args) -> Result<SystemConfig> {
+    let path = resolve_path(&args.system_config);
+    if path.exists() {
+        SystemConfig::load(&path)
+    } else {
+        Ok(SystemConfig::default())
+    }
+}
+
+/// Веса и пороги поиска воспоминаний: `retrieval` из `--system-config` с поправками архетипа
+fn retrieval_config(persona: Option<&Persona>) -> RetrievalConfig {
+    let base = SYSTEM.get().map(|system| system.retrieval.clone()).unwrap_or_default();
+    match persona.and_then(|p| p.retrieval.as_ref()) {
+        Some(overrides) => overrides.apply(&base),
+        None => base,
+    }
+}
+
+/// Запускает расписание обслуживания из `--system-config`
+fn start_maintenance() -> Result<()> {
+    let config = SYSTEM.get().cloned().unwrap_or_default().maintenance;
+    if !config.enabled {
+        return Ok(());
+    }
+    let scheduler = MaintenanceScheduler::new(
+        config,
+        Some(&data_dir().join(MAINTENANCE_STATE_FILE)),
+        utils::clock::default_clock(),
+    );
+    if let Some(jobs) = JOBS.get() {
+        scheduler.set_jobs(jobs);
+    }
+    let scheduler = Arc::new(scheduler);
+    scheduler.start();
+    println!("🧹 Maintenance: {}", scheduler.describe());
+    let _ = MAINTENANCE.set(scheduler);
+    Ok(())
+}
+
+/// Квоты из `--quota-config`; `--requests-per-hour` и `--tokens-per-day` переопределяют общие лимиты
+fn quota_config(args: &Args) -> Result<QuotaConfig> {
+    let path = resolve_path(&args.quota_config);
+    let mut config = if path.exists() {
+        QuotaConfig::load(&path)?
+    } else {
+        QuotaConfig::default()
+    };
+    let overrides = QuotaLimits {
+        requests_per_hour: args.requests_per_hour,
+        tokens_per_day: args.tokens_per_day,
+    };
+    config.default = overrides.or(config.default);
+    Ok(config)
+}
+
+/// LoRA-адаптер архетипа (None, если адаптера нет или архетип не найден)
+fn archetype_adapter(archetype_id: &str) -> Option<AdapterConfig> {
+    ArchetypeLoader::load(archetype_id).ok().and_then(|a| a.adapter)
+}
+
+/// Перезагружает модель, если у новой персоны другой адаптер
+fn switch_adapter(
+    pipeline_arc: &Arc<std::sync::Mutex<UnifiedPipeline>>,
+    args: &Args,
+    adapter: Option<&AdapterConfig>,
+) -> Result<()> {
+    let mut pipeline = pipeline_arc.lock().unwrap();
+    if pipeline.adapter.as_ref() == adapter {
+        return Ok(());
+    }
+
+    let device = pipeline.device.clone();
+    match adapter {
+        Some(cfg) => println!("🧬 Reloading model with adapter {}...", cfg.path),
+        None => println!("🧬 Reloading base model without adapter..."),
+    }
+    *pipeline = load_pipeline_with_adapter(args, &device, adapter)?;
+    Ok(())
+}
+
+/// Загружает модель заново, если watchdog бросил зависшую генерацию вместе с ней
+fn reload_stalled_model(pipeline: &mut UnifiedPipeline, args: &Args) -> Result<()> {
+    if !pipeline.needs_reload() {
+        return Ok(());
+    }
+    println!("🔄 Reloading the model after a stuck generation...");
+    let (device, adapter) = (pipeline.device.clone(), pipeline.adapter.clone());
+    *pipeline = load_pipeline_with_adapter(args, &device, adapter.as_ref())?;
+    Ok(())
+}
+
+fn main() -> Result<()> {
+    let mut args = Args::parse();
+    if args.ci {
+        apply_ci_profile(&mut args)?;
+    }
+    
+    // Set global verbose flag for debug output
+    VERBOSE.store(args.verbose, Ordering::Relaxed);
+
+    #[cfg(feature = "monitoring")]
+    let _telemetry = logos::telemetry::init(args.tracing, args.otlp_endpoint.as_deref())?;
+    #[cfg(not(feature = "monitoring"))]
+    if args.tracing || args.otlp_endpoint.is_some() {
+        eprintln!("⚠️  --tracing and --otlp-endpoint require the `monitoring` feature, ignoring");
+    }
+
+    #[cfg(not(feature = "server"))]
+    if args.serve {
+        anyhow::bail!("--serve requires the `server` feature");
+    }
+
+    if let Some(ref addr) = args.metrics_addr {
+        let local = metrics::serve(addr)?;
+        println!("📈 Metrics: http://{}/metrics", local);
+    }
+
+    let data = data_dir::resolve(args.data_dir.as_deref(), &resolve_path(data_dir::LEGACY_DIR));
+    if data.source == DataDirSource::Legacy && !args.quiet {
+        eprintln!(
+            "💡 Memory is in the legacy location {}; `ziggurat-unified migrate-data` moves it to {}",
+            data.path.display(),
+            data_dir::platform_dir().map_or_else(|| "--data-dir".to_string(), |p| p.display().to_string())
+        );
+    }
+    let _ = DATA_DIR.set(data.path);
+    set_current_user(args.user.clone());
+
+    if let Some((key, source)) = crypto::MemoryKey::resolve().context("Failed to read the memory key")? {
+        crypto::install(key);
+        if !args.quiet {
+            eprintln!("🔐 Memory encryption: on (key from {})", source);
+        }
+    }
+
+    if let Some(Command::MigrateData { target }) = &args.command {
+        return migrate_data_command(target.as_deref());
+    }
+    if let Some(Command::EncryptMemory { decrypt }) = &args.command {
+        return encrypt_memory_command(*decrypt);
+    }
+
+    if let Some(Command::Scenario { files }) = &args.command {
+        if !scenario::run_scenarios(files)? {
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
+    if let Some(Command::ValidateArchetype { archetypes }) = &args.command {
+        if !validate_archetypes_command(archetypes)? {
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
+    if let Some(Command::Doctor { skip_generation }) = &args.command {
+        let report = doctor::run_doctor(&args, *skip_generation)?;
+        if report.has_failures() {
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
+    if let Some(ref session_id) = args.replay {
+        if !replay_session(&args, session_id)? {
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
+    if let Some(Command::ExportDataset { format, persona, only_good, since, until, output }) = &args.command {
+        let filter = DatasetFilter {
+            persona: persona.clone(),
+            only_good: *only_good,
+            since: *since,
+            until: *until,
+        };
+        return export_dataset_command(*format, &filter, output.as_deref());
+    }
+    if let Some(Command::ExportAnalytics { format, persona, since, until, output }) = &args.command {
+        let filter = ActivityFilter {
+            persona: persona.clone(),
+            since: *since,
+            until: *until,
+        };
+        return export_analytics_command(*format, &filter, output.as_deref());
+    }
+    if let Some(Command::ExportSession { id, sanitized, rules, output }) = &args.command {
+        return export_session_command(id, *sanitized, rules.as_deref(), output.as_deref());
+    }
+    if let Some(Command::ExportMemory { scope, persona, since, until, no_embeddings, output }) = &args.command {
+        let request = MemoryExportRequest {
+            scope: *scope,
+            persona: persona.clone(),
+            since: *since,
+            until: *until,
+            include_embeddings: !no_embeddings,
+        };
+        return export_memory_command(&request, output.as_deref());
+    }
+    if let Some(Command::Export { output }) = &args.command {
+        return memory_archive::export_command(&args, output);
+    }
+    if let Some(Command::Import { input }) = &args.command {
+        return memory_archive::import_command(&args, input);
+    }
+
+    let resume = match args.command.clone() {
+        Some(Command::Continue { prompt }) => Some(resume_from_checkpoint(&mut args, prompt)?),
+        _ => None,
+    };
+    if args.smoke_test && !args.ci {
+        // dummy embeddings must not end up next to real ones in memory_data
+        args.read_only = true;
+        println!("🧪 Smoke test: echo model and dummy embeddings, no downloads");
+    }
+    if args.checkpoint {
+        // the session lives in episodic memory between processes
+        args.enable_memory = true;
+        args.interactive = false;
+    }
+
+    println!("🏛️ ZIGGURAT MIND - Initializing...");
+
+    let device = select_device(args.cpu)?;
+    println!("📱 Device: {:?}", device);
+
+    // LLM weights load on their own thread while the embedder and memory load below;
+    // one-shot memory commands never need the model
+    let model_loading = if memory_command(&args) {
+        None
+    } else {
+        let (args, device) = (args.clone(), device.clone());
+        Some(Pending::spawn("model", move || load_pipeline(&args, &device))?)
+    };
+
+    let embedding_path = resolve_path(&args.embedding_path);
+    let embedder: Arc<dyn Embedder> = if args.smoke_test {
+        Arc::new(DummyEmbeddingEngine::new(device.clone(), EmbeddingConfig::default().embedding_dim))
+    } else {
+        load_embedder(&args, &embedding_path, &device)?
+    };
+    println!(
+        "✅ Embedding engine loaded (dim: {})",
+        embedder.embedding_dim()
+    );
+    let embedder: Arc<dyn Embedder> = if args.embedding_batch_size > 1 {
+        let config = SchedulerConfig {
+            batch_size: args.embedding_batch_size,
+            flush_interval: std::time::Duration::from_millis(args.embedding_flush_ms),
+        };
+        Arc::new(EmbeddingScheduler::new(embedder, config)?)
+    } else {
+        embedder
+    };
+    if let Some(path) = args.code_embedding_path.as_deref().filter(|_| !args.smoke_test) {
+        let code_path = resolve_path(path);
+        println!("🧩 Loading code embedding engine from: {}", code_path.display());
+        let code_embedder: Arc<dyn Embedder> =
+            Arc::new(EmbeddingEngine::new(code_path.to_str().unwrap_or(path), device.clone())?);
+        let _ = CODE_EMBEDDER.set((path.to_string(), code_embedder));
+    }
+
+    // One writer per memory_data: a second instance gets a clear error unless it is read-only
+    let _memory_lock = if args.read_only {
+        println!("🔒 Read-only memory: nothing will be saved to memory_data");
+        None
+    } else {
+        Some(MemoryLock::acquire(&data_dir())?)
+    };
+
+    // Initialize managers
+    let mut persistence_manager = Arc::new(open_persistence_manager(&args)?);
+    println!("💾 Persistence manager initialized");
+    if !current_user().is_default() {
+        println!("👤 User: {}", current_user());
+    }
+
+    if args.progressive_summaries && args.enable_memory {
+        if let Some(store) = open_summary_store(&persistence_manager, &args) {
+            let _ = SUMMARIES.set(Arc::new(std::sync::Mutex::new(store)));
+        }
+    }
+
+    if let Some(store) = open_document_store(&embedder, &args) {
+        let _ = DOCUMENTS.set(Arc::new(std::sync::Mutex::new(store)));
+    }
+
+    // Persisted memory hydrates in the background: the first prompt waits for it, startup does not
+    let mut memory_loading = {
+        let (args, resume) = (args.clone(), resume.clone());
+        let (persistence, embedder) = (persistence_manager.clone(), embedder.clone());
+        Some(Pending::spawn("memory", move || {
+            hydrate_memory(&args, resume.as_ref(), &persistence, &embedder)
+        })?)
+    };
+    let mut dialogue_manager: Option<DialogueManager> = None;
+    let mut semantic_manager: Option<Arc<std::sync::Mutex<SemanticMemoryManager>>> = None;
+    let mut facts_file: Option<FactsFile> = None;
+    let mut archetype_watcher: Option<ArchetypeWatcher> = None;
+
+    // Shell-command context providers from config; no file means no plugins
+    let providers_path = resolve_path(&args.context_providers);
+    if providers_path.exists() {
+        match CommandProvider::load_all(&providers_path) {
+            Ok(providers) => {
+                for provider in providers {
+                    context::register_plugin(Box::new(provider));
+                }
+                println!("🧩 Context providers: {}", context::plugin_names().join(", "));
+            }
+            Err(e) => eprintln!("WARNING: Failed to load context providers: {}", e),
+        }
+    }
+
+    if args.background_jobs {
+        let config = JobQueueConfig {
+            capacity: args.job_queue_capacity,
+            ..Default::default()
+        };
+        let _ = JOBS.set(JobQueue::start(config));
+        println!("🧵 Background jobs: extraction and maintenance run between answers");
+    }
+
+    let _ = SYSTEM.set(load_system_config(&args)?);
+    if (args.interactive || args.serve) && !args.read_only {
+        start_maintenance()?;
+    }
+
+    let quota = quota_config(&args)?;
+    if !quota.is_unlimited() {
+        let limit = |value: Option<u64>| value.map_or_else(|| "unlimited".to_string(), |v| v.to_string());
+        println!(
+            "⏳ Quotas: {} requests/hour, {} tokens/day by default, {} per-user overrides",
+            limit(quota.default.requests_per_hour.map(u64::from)),
+            limit(quota.default.tokens_per_day),
+            quota.users.len()
+        );
+        let _ = QUOTA.set(QuotaTracker::new(quota));
+    }
+
+    // Handle command-line semantic memory commands
+    if memory_command(&args) {
+        attach_memory(
+            &mut memory_loading,
+            &mut dialogue_manager,
+            &mut semantic_manager,
+            &mut facts_file,
+            &mut None,
+            None,
+            &args,
+        )?;
+    }
+    if args.apply_decay {
+        if let Some(ref sm) = semantic_manager {
+            let mut sm = sm.lock().unwrap();
+            match sm.apply_temporal_decay() {
+                Ok(updated_count) => {
+                    println!("🕰️ Applied temporal decay to {} concepts", updated_count);
+                }
+                Err(e) => eprintln!("ERROR: Failed to apply decay: {}", e),
+            }
+        }
+        return Ok(());
+    }
+
+    if args.decay_stats {
+        if let Some(ref sm) = semantic_manager {
+            let sm = sm.lock().unwrap();
+            let stats = sm.get_decay_stats();
+            println!("📊 Temporal Decay Statistics:");
+            println!("   Total concepts: {}", stats.total_concepts);
+            println!("   Decayed concepts: {}", stats.decayed_concepts);
+            println!("   Low confidence concepts: {}", stats.low_confidence_concepts);
+            println!();
+
+            for (category, cat_stats) in stats.category_stats {
+                println!("   {}: total={}, low_conf={}, avg_conf={:.2}",
+                         category, cat_stats.total, cat_stats.low_confidence, cat_stats.avg_confidence);
+            }
+        }
+        return Ok(());
+    }
+
+    if args.graph_stats {
+        if let Some(ref sm) = semantic_manager {
+            let mut sm = sm.lock().unwrap();
+            let stats = sm.get_graph_stats();
+            println!("🕸️ Knowledge Graph Statistics:");
+            println!("   Total triples: {}", stats.total_triples);
+            println!("   Total predicates: {}", stats.total_predicates);
+            println!("   Average degree: {:.2}", stats.avg_degree);
+        }
+        return Ok(());
+    }
+
+    if args.extract_relations {
+        if let Some(ref sm) = semantic_manager {
+            let mut sm = sm.lock().unwrap();
+            println!("📝 Enter text to extract relations from:");
+            let mut input = String::new();
+            std::io::stdin().read_line(&mut input)?;
+            let relations_added = sm.extract_relations_from_text_blocking(input.trim(), "manual")?;
+            println!("✅ Extracted {} relations", relations_added);
+        }
+        return Ok(());
+    }
+
+    if let Some(ref concept_query) = args.find_related {
+        if let Some(ref sm) = semantic_manager {
+            let mut sm = sm.lock().unwrap();
+            // Find concept by text search
+            let concepts = sm.search_by_text_blocking(concept_query, 5);
+            if let Some((_, best_concept)) = concepts.first() {
+                let related = sm.find_related_concepts(&best_concept.id);
+                println!("🔗 Related concepts for '{}':", best_concept.text);
+                for (related_id, predicate, confidence) in related {
+                    if let Some(related_concept) = sm.get_concept(&related_id) {
+                        println!("   {} [{}] ({:.2})",
+                                 related_concept.text, predicate, confidence);
+                    }
+                }
+            } else {
+                println!("❌ No concepts found matching '{}'", concept_query);
+            }
+        } else {
+            println!("❌ Semantic memory not enabled");
+        }
+        return Ok(());
+    }
+
+    // Инициализируем Persona (Demiurge Level)
+    let mut persona: Option<Persona> = None;
+    if args.interactive || args.checkpoint || args.serve {
+        match ArchetypeLoader::load(&args.archetype) {
+            Ok(archetype) => {
+                let mut p = Persona::from_archetype(std::sync::Arc::new(archetype));
+                p.set_user(current_user());
+                println!("🎭 Persona loaded: {} ({})", p.name, p.archetype_id);
+
+                if let Err(e) = p.load_narrative() {
+                    eprintln!("WARNING: Failed to load narrative: {}", e);
+                }
+                if let Err(e) = p.load_evolution() {
+                    eprintln!("WARNING: Failed to load persona state: {}", e);
+                }
+                greet_from_saved_context(&mut p)?;
+
+                persona = Some(p);
+            }
+            Err(e) => {
+                eprintln!("⚠️  Warning: Could not load archetype '{}': {}", args.archetype, e);
+                eprintln!("   Available archetypes: {:?}", ArchetypeLoader::list_ids().unwrap_or_default());
+            }
+        }
+    }
+    if args.watch_archetype {
+        reload_edited_archetype(&mut archetype_watcher, &mut persona, &args);
+        if let Some(ref w) = archetype_watcher {
+            println!("👀 Watching {} for edits", w.path().display());
+        }
+    }
+
+    // Show device selection status
+    if device.is_cuda() {
+        println!("🚀 Device: GPU (CUDA) - using VRAM, not system RAM");
+    } else {
+        let mem_mb = get_memory_mb();
+        println!("💻 Device: CPU - System RAM: {} MB", mem_mb);
+        if mem_mb > 0 && mem_mb < 16000 && !args.smoke_test {
+            eprintln!("⚠️  WARNING: CPU mode requires ~16GB RAM. GPU recommended!");
+        }
+    }
+
+    let pipeline = model_loading.context("No model to load")?.wait()?;
+    let pipeline_arc: std::sync::Arc<std::sync::Mutex<UnifiedPipeline>> =
+        std::sync::Arc::new(std::sync::Mutex::new(pipeline));
+
+    log_memory_usage("after_model_load");
+    let warm_start = warm_start_target(&args);
+    restore_warm_start(warm_start.as_ref(), &pipeline_arc, &embedder);
+
+    if args.smoke_test {
+        println!("✅ Echo model ready");
+    } else if device.is_cuda() {
+        println!("✅ Mistral 7B loaded on GPU (using VRAM)");
+    } else {
+        let mem_mb = get_memory_mb();
+        println!("✅ Mistral 7B loaded on CPU (using {} MB RAM)", mem_mb);
+        if mem_mb > 20000 {
+            println!("💡 Tip: Use --features cuda for GPU inference (faster + less RAM)");
+        }
+    }
+
+    #[cfg(feature = "server")]
+    if args.serve {
+        attach_memory(
+            &mut memory_loading,
+            &mut dialogue_manager,
+            &mut semantic_manager,
+            &mut facts_file,
+            &mut persona,
+            Some(&pipeline_arc),
+            &args,
+        )?;
+        install_exit_handler(
+            &pipeline_arc,
+            &persona,
+            &dialogue_manager,
+            &persistence_manager,
+            &embedder,
+            &semantic_manager,
+            &warm_start,
+        );
+        let server = server::Server {
+            pipeline: pipeline_arc,
+            dialogue: dialogue_manager,
+            semantic: semantic_manager,
+            persistence: persistence_manager,
+            embedder,
+            persona,
+            args: &args,
+        };
+        return server.run(&format!("{}:{}", args.host, args.port));
+    }
+
+    if args.interactive {
+        // the prompt opens with the model; memory still loading is waited for on the first message
+        let memory_pending = memory_loading.as_ref().is_some_and(|m| !m.is_ready());
+        if args.prompt.is_some() || !memory_pending {
+            attach_memory(
+                &mut memory_loading,
+                &mut dialogue_manager,
+                &mut semantic_manager,
+                &mut facts_file,
+                &mut persona,
+                Some(&pipeline_arc),
+                &args,
+            )?;
+            install_exit_handler(
+                &pipeline_arc,
+                &persona,
+                &dialogue_manager,
+                &persistence_manager,
+                &embedder,
+                &semantic_manager,
+                &warm_start,
+            );
+        }
+
+        println!("\n🗣️ Interactive mode - type 'quit'/'выход' to exit");
+        print!("{}", repl::help());
+        if memory_pending && args.prompt.is_none() {
+            println!("⏳ Memory is still loading in the background, you can type already");
+        }
+        println!("========================================");
+
+        if let Some(ref initial_prompt) = args.prompt {
+            pipeline_arc.lock().unwrap().clear_cache();
+            process_query(
+                initial_prompt,
+                &pipeline_arc,
+                &mut dialogue_manager,
+                &mut semantic_manager,
+                &persistence_manager,
+                &embedder,
+                &args,
+                &mut persona,
+            )?;
+        }
+
+        // managers turned off with /memory off and /semantic off
+        let mut suspended_memory: Option<DialogueManager> = None;
+        let mut suspended_semantic: Option<Arc<std::sync::Mutex<SemanticMemoryManager>>> = None;
+        // personas talking alongside the current one (/persona group)
+        let mut ensemble: Option<PersonaEnsemble> = None;
+
+        loop {
+            run_pending_maintenance(&mut dialogue_manager, &semantic_manager, &persistence_manager, &embedder, &args);
+            print!("\n📝 You: ");
+            std::io::stdout().flush()?;
+
+            let mut input = String::new();
+            // end of input (piped commands, Ctrl+D) ends the session like `exit`
+            if std::io::stdin().read_line(&mut input)? == 0 {
+                input = "exit".to_string();
+            }
+            let input = input.trim();
+
+            if input.is_empty() {
+                continue;
+            }
+            if attach_memory(
+                &mut memory_loading,
+                &mut dialogue_manager,
+                &mut semantic_manager,
+                &mut facts_file,
+                &mut persona,
+                Some(&pipeline_arc),
+                &args,
+            )? {
+                install_exit_handler(
+                    &pipeline_arc,
+                    &persona,
+                    &dialogue_manager,
+                    &persistence_manager,
+                    &embedder,
+                    &semantic_manager,
+                    &warm_start,
+                );
+            }
+            // Support English and Russian exit commands
+            let exit_commands = ["quit", "exit", "q", "выход", "выйти", "пока"];
+            if exit_commands.iter().any(|&cmd| input.eq_ignore_ascii_case(cmd) || input == cmd) {
+                save_conversation(
+                    &persona,
+                    &mut dialogue_manager,
+                    &semantic_manager,
+                    &persistence_manager,
+                    &embedder,
+                    &pipeline_arc,
+                );
+                save_warm_start(warm_start.as_ref(), &pipeline_arc, &embedder);
+                println!("👋 Goodbye!");
+                break;
+            }
+
+            pipeline_arc.lock().unwrap().clear_cache();
+
+            if let (Some(ref sm), Some(ref mut facts)) = (&semantic_manager, &mut facts_file) {
+                if facts.changed() {
+                    sync_facts_file(sm, facts);
+                }
+            }
+            if args.watch_archetype {
+                reload_edited_archetype(&mut archetype_watcher, &mut persona, &args);
+            }
+
+            let command = match repl::parse(input) {
+                Ok(command) => command,
+                Err(e) => {
+                    println!("❌ {}", e);
+                    continue;
+                }
+            };
+            if let Some(command) = command {
+                match command.name() {
+                    "help" => match command.arg(0) {
+                        Some(name) => match repl::find(name.trim_start_matches('/')) {
+                            Some(spec) => print!("{}", repl::command_help(spec)),
+                            None => println!("Unknown command '{}'. Type /help for the list", name),
+                        },
+                        None => print!("{}", repl::help()),
+                    },
+                    "sessions" => handle_sessions_command(&command, &mut dialogue_manager, &persistence_manager),
+                    "bookmark" | "bookmarks" => {
+                        handle_bookmark_command(&command, &mut dialogue_manager, &persistence_manager, &embedder)
+                    }
+                    "sensitive" => handle_sensitive_command(&command, &semantic_manager),
+                    "docs" => handle_docs_command(&command),
+                    "semantic" if matches!(command.subcommand, Some("on") | Some("off")) => {
+                        toggle_semantic_memory(
+                            command.subcommand == Some("on"),
+                            &mut args,
+                            &mut semantic_manager,
+                            &mut suspended_semantic,
+                            &embedder,
+                            &pipeline_arc,
+                            &mut persona,
+                        );
+                        if let (Some(ref sm), None) = (&semantic_manager, &facts_file) {
+                            facts_file = match args.facts_file {
+                                Some(ref path) => Some(FactsFile::new(path)),
+                                None => FactsFile::find(std::path::Path::new(".")),
+                            };
+                            if let Some(ref mut facts) = facts_file {
+                                sync_facts_file(sm, facts);
+                            }
+                        }
+                    }
+                    "semantic" if !args.enable_semantic => {
+                        println!("Semantic memory is off. Use /semantic on to enable it.")
+                    }
+                    "semantic" => handle_semantic_command(&command, &semantic_manager),
+                    "stats" => match command.subcommand {
+                        None | Some("perf") => {
+                            println!("{}", profiling::report());
+                            let pipeline = pipeline_arc.lock().unwrap();
+                            let (hits, misses) = pipeline.prompt_tokens.stats();
+                            println!("🧩 Prompt paragraphs: {} tokenized, {} from cache", misses, hits);
+                            let prefix = pipeline.prefix_cache.stats();
+                            println!(
+                                "♻️  Prompt prefix: {} prompts resumed from {} cached tokens ({} tokens skipped), {} prefilled from the start",
+                                prefix.hits, prefix.prefix_tokens, prefix.reused_tokens, prefix.misses
+                            );
+                        }
+                        Some("metrics") => println!("{}", metrics::report()),
+                        _ => print!("{}", repl::command_help(command.spec)),
+                    },
+                    "jobs" => match JOBS.get() {
+                        Some(jobs) => print!("{}", jobs.stats().report()),
+                        None => println!("🧵 Background jobs are off, extraction runs inline (--background-jobs)"),
+                    },
+                    "mem" if matches!(command.subcommand, Some("on") | Some("off")) => toggle_episodic_memory(
+                        command.subcommand == Some("on"),
+                        &mut args,
+                        &mut dialogue_manager,
+                        &mut suspended_memory,
+                        &persistence_manager,
+                        &embedder,
+                        &persona,
+                    ),
+                    "mem" if command.subcommand == Some("help") => print!("{}", repl::command_help(command.spec)),
+                    "mem" if command.subcommand.is_some() => {
+                        handle_memory_command(&command, &mut dialogue_manager, &persistence_manager, &embedder)
+                    }
+                    "mem" => {
+                        let mem_mb = get_memory_mb();
+                        if mem_mb > 0 {
+                            println!("💻 RAM: {} MB", mem_mb);
+                        }
+                        if let Some(gpu_mb) = get_gpu_memory_mb() {
+                            println!("🚀 VRAM: {} MB", gpu_mb);
+                        }
+                    }
+                    "context" => match command.subcommand {
+                        None | Some("show") => show_session_context(&mut persona),
+                        Some("timeline") => show_timeline(&command, &dialogue_manager),
+                        _ => print!("{}", repl::command_help(command.spec)),
+                    },
+                    "good" | "bad" => {
+                        let rating = if command.name() == "good" { Rating::Good } else { Rating::Bad };
+                        match dialogue_manager.as_mut() {
+                            Some(dm) => {
+                                if dm.rate_last_turn(rating) {
+                                    println!("👍 Rated last answer as {}", rating.as_str());
+                                    if let Err(e) = persistence_manager.save_with_embeddings_blocking(dm, embedder.embedding_dim()) {
+                                        eprintln!("WARNING: Failed to save memory: {}", e);
+                                    }
+                                } else {
+                                    println!("Nothing to rate yet.");
+                                }
+                            }
+                            None => println!("Episodic memory is disabled. Use --enable-memory to rate answers."),
+                        }
+                    }
+                    "remember" => match dialogue_manager.as_mut() {
+                        Some(dm) => match dm.remember_last_turn_blocking() {
+                            Ok(true) => {
+                                println!("📌 Remembered the last exchange");
+                                if let Err(e) = persistence_manager.save_with_embeddings_blocking(dm, embedder.embedding_dim()) {
+                                    eprintln!("WARNING: Failed to save memory: {}", e);
+                                }
+                                let origin = TurnRef::new(dm.current_session().id.to_string(), dm.current_session().turn_count());
+                                let last = dm.current_session().turns.last().cloned();
+                                if let Some(turn) = last.filter(|t| user_discloses(&t.user, &pipeline_arc)) {
+                                    let semantic_enabled = args.enable_semantic
+                                        && persona.as_ref().is_none_or(|p| p.semantic_manager.is_some());
+                                    extract_long_term_memory(
+                                        &turn.user,
+                                        &turn.assistant,
+                                        &origin,
+                                        semantic_enabled,
+                                        &semantic_manager,
+                                        &mut persona,
+                                        &args,
+                                    );
+                                }
+                            }
+                            Ok(false) => println!("Nothing to remember: the last exchange is already in memory."),
+                            Err(e) => println!("❌ {}", e),
+                        },
+                        None => println!("Episodic memory is disabled. Use --enable-memory to enable."),
+                    },
+                    "correct" => match (correction::parse_command(command.text()), semantic_manager.as_ref()) {
+                        (_, None) => println!("Semantic memory is not loaded."),
+                        (Err(e), _) => println!("❌ {}", e),
+                        (Ok(request), Some(sm)) => {
+                            let source = dialogue_manager
+                                .as_ref()
+                                .map_or_else(|| "manual".to_string(), |dm| dm.current_session().id.to_string());
+                            apply_user_correction(sm, &request, &source);
+                        }
+                    },
+                    "address" => handle_address_command(&command, &mut persona),
+                    "user" => match command.arg(0).map(UserId::new) {
+                        None => show_users(),
+                        Some(Err(e)) => println!("❌ {}", e),
+                        Some(Ok(user)) if user == current_user() => println!("👤 Already talking to {}", user),
+                        Some(Ok(user)) => {
+                            save_conversation(
+                                &persona,
+                                &mut dialogue_manager,
+                                &semantic_manager,
+                                &persistence_manager,
+                                &embedder,
+                                &pipeline_arc,
+                            );
+                            match switch_user(user, &args, &embedder) {
+                                Ok((persistence, loading)) => {
+                                    persistence_manager = persistence;
+                                    memory_loading = Some(loading);
+                                    // отложенная /mem off и /semantic off память - прежнего пользователя
+                                    suspended_memory = None;
+                                    suspended_semantic = None;
+                                    if let Some(ref mut p) = persona {
+                                        p.set_user(current_user());
+                                        p.semantic_manager = None;
+                                    }
+                                    attach_memory(
+                                        &mut memory_loading,
+                                        &mut dialogue_manager,
+                                        &mut semantic_manager,
+                                        &mut facts_file,
+                                        &mut persona,
+                                        Some(&pipeline_arc),
+                                        &args,
+                                    )?;
+                                    println!("👤 Switched to user {}", current_user());
+                                    if let Some(ref mut p) = persona {
+                                        greet_from_saved_context(p)?;
+                                    }
+                                }
+                                Err(e) => println!("❌ Staying with user {}: {:#}", current_user(), e),
+                            }
+                        }
+                    },
+                    "persona" if command.subcommand == Some("group") => {
+                        handle_group_command(&command, &mut ensemble, &mut persona, &semantic_manager)
+                    }
+                    "persona" => {
+                        if command.subcommand == Some("switch") && ensemble.take().is_some() {
+                            println!("👥 Group conversation ended");
+                        }
+                        handle_persona_command(
+                            &command,
+                            &mut persona,
+                            &mut dialogue_manager,
+                            &semantic_manager,
+                            &persistence_manager,
+                            &pipeline_arc,
+                            &args,
+                        )
+                    }
+                    other => println!("Command /{} is not available here", other),
+                }
+                continue;
+            }
+
+            let answered = match ensemble.as_mut() {
+                Some(group) => group_turn(
+                    input,
+                    group,
+                    &pipeline_arc,
+                    &mut dialogue_manager,
+                    &mut semantic_manager,
+                    &persistence_manager,
+                    &embedder,
+                    &args,
+                    &mut persona,
+                ),
+                None => process_query(
+                    input,
+                    &pipeline_arc,
+                    &mut dialogue_manager,
+                    &mut semantic_manager,
+                    &persistence_manager,
+                    &embedder,
+                    &args,
+                    &mut persona,
+                )
+                .map(|_| ()),
+            };
+            if let Err(e) = answered {
+                eprintln!("Error: {}", e);
+            }
+        }
+    } else {
+        let Some(ref prompt) = args.prompt else {
+            eprintln!("Error: --prompt is required (or use --interactive)");
+            std::process::exit(1);
+        };
+        attach_memory(
+            &mut memory_loading,
+            &mut dialogue_manager,
+            &mut semantic_manager,
+            &mut facts_file,
+            &mut persona,
+            Some(&pipeline_arc),
+            &args,
+        )?;
+        pipeline_arc.lock().unwrap().clear_cache();
+        let args_ref = &args;
+        process_query(
+            prompt,
+            &pipeline_arc,
+            &mut dialogue_manager,
+            &mut semantic_manager,
+            &persistence_manager,
+            &embedder,
+            args_ref,
+            &mut persona,
+        )?;
+
+        drain_background_jobs();
+        // Сохраняем память после выполнения
+        if let Some(ref dm) = dialogue_manager {
+            if let Err(e) = persistence_manager.save_with_embeddings_blocking(dm, embedder.embedding_dim()) {
+                eprintln!("WARNING: Failed to save memory: {}", e);
+            } else {
+                println!("💾 Episodic memory saved to disk");
+            }
+        }
+        if let Some(ref sm) = semantic_manager {
+            let mut sm = sm.lock().unwrap();
+            if let Err(e) = sm.save_blocking() {
+                eprintln!("WARNING: Failed to save semantic memory: {}", e);
+            } else {
+                let count = sm.count();
+                if count > 0 {
+                    println!("📚 Semantic memory: {} concepts saved", count);
+                } else {
+                    println!("ℹ️  No new semantic concepts to save");
+                }
+            }
+        }
+
+        if args.checkpoint && !args.read_only {
+            save_checkpoint(&args, prompt, &persona, &dialogue_manager)?;
+        }
+        save_warm_start(warm_start.as_ref(), &pipeline_arc, &embedder);
+    }
+
+    Ok(())
+}
+
+/// Перегенерирует записанные ходы сессии с сохранёнными seed и параметрами.
+/// Возвращает true, если все ответы совпали
+fn replay_session(args: &Args, session_id: &str) -> Result<bool> {
+    let persistence = totems::episodic::persistence::PersistenceManager::new(
+        Some(&user_data_dir()),
+        false,
+    )?;
+    let sessions = persistence
+        .load_sessions()?
+        .ok_or_else(|| anyhow::anyhow!("No saved episodic memory found"))?;
+
+    let matches: Vec<_> = sessions.iter().filter(|s| s.id.starts_with(session_id)).collect();
+    let session = match matches.as_slice() {
+        [session] => *session,
+        [] => anyhow::bail!("Session {} not found", session_id),
+        _ => anyhow::bail!("Session id prefix {} is ambiguous ({} matches)", session_id, matches.len()),
+    };
+
+    let recorded: Vec<_> = session
+        .turns
+        .iter()
+        .enumerate()
+        .filter_map(|(i, t)| t.sampling.as_ref().map(|s| (i, t, s)))
+        .collect();
+    anyhow::ensure!(
+        !recorded.is_empty(),
+        "Session {} has no recorded sampling state (saved before replay support)",
+        session.id
+    );
+
+    println!(
+        "🔁 Replaying session {} ({} of {} turns recorded)",
+        session.id,
+        recorded.len(),
+        session.turns.len()
+    );
+
+    let device = select_device(args.cpu)?;
+    // the adapter of the persona that produced the session
+    let mut pipeline =
+        load_pipeline_with_adapter(args, &device, archetype_adapter(&session.persona_name).as_ref())?;
+
+    let mut mismatches = 0;
+    for (index, turn, record) in recorded {
+        pipeline.clear_cache();
+        pipeline.apply_sampling(record);
+        let output = pipeline.run(&record.prompt, record.max_tokens, record.seed)?;
+
+        if output == turn.assistant && pipeline.last_generated_tokens == record.generated_tokens {
+            println!("  ✅ turn {}: match ({} tokens)", index + 1, record.generated_tokens);
+        } else {
+            mismatches += 1;
+            println!(
+                "  ❌ turn {}: mismatch\n     recorded: {}\n     replayed: {}",
+                index + 1,
+                truncate_text(&turn.assistant, 80),
+                truncate_text(&output, 80)
+            );
+        }
+    }
+
+    if mismatches == 0 {
+        println!("🔁 All turns reproduced exactly");
+    } else {
+        println!("🔁 {} turn(s) diverged", mismatches);
+    }
+    Ok(mismatches == 0)
+}
+
+/// `export-dataset`: сессии из memory_data → JSONL для fine-tuning
+fn export_dataset_command(
+    format: DatasetFormat,
+    filter: &DatasetFilter,
+    output: Option<&std::path::Path>,
+) -> Result<()> {
+    let persistence = totems::episodic::persistence::PersistenceManager::new(
+        Some(&user_data_dir()),
+        false,
+    )?;
+    let sessions = persistence
+        .load_sessions()?
+        .ok_or_else(|| anyhow::anyhow!("No saved episodic memory found"))?;
+
+    let stats = match output {
+        Some(path) => {
+            let mut file = std::io::BufWriter::new(std::fs::File::create(path)?);
+            let stats = export_dataset(&sessions, format, filter, &mut file)?;
+            file.flush()?;
+            stats
+        }
+        None => export_dataset(&sessions, format, filter, &mut std::io::stdout().lock())?,
+    };
+
+    eprintln!(
+        "📤 Exported {} records ({} turns from {} sessions), {} PII redactions",
+        stats.records, stats.turns, stats.sessions, stats.redactions
+    );
+    Ok(())
+}
+
+fn export_analytics_command(
+    format: AnalyticsFormat,
+    filter: &ActivityFilter,
+    output: Option<&std::path::Path>,
+) -> Result<()> {
+    let persistence = totems::episodic::persistence::PersistenceManager::new(
+        Some(&user_data_dir()),
+        false,
+    )?;
+    let sessions = persistence
+        .load_sessions()?
+        .ok_or_else(|| anyhow::anyhow!("No saved episodic memory found"))?;
+
+    // часы и дни - в местном времени пользователя
+    let offset = *chrono::Local::now().offset();
+    let report = totems::episodic::analytics::analyze(&sessions, filter, offset);
+    match output {
+        Some(path) => {
+            let mut file = std::io::BufWriter::new(std::fs::File::create(path)?);
+            totems::episodic::analytics::write_report(&report, format, &mut file)?;
+            file.flush()?;
+        }
+        None => totems::episodic::analytics::write_report(&report, format, &mut std::io::stdout().lock())?,
+    }
+
+    eprintln!(
+        "📊 Analytics for {} turns from {} sessions over {} days, {} topics",
+        report.interactions,
+        report.sessions,
+        report.per_day.len(),
+        report.topics.len()
+    );
+    Ok(())
+}
+
+/// `export-session`: транскрипт одной сессии в Markdown
+fn export_session_command(
+    id: &str,
+    sanitized: bool,
+    rules: Option<&std::path::Path>,
+    output: Option<&std::path::Path>,
+) -> Result<()> {
+    let persistence = totems::episodic::persistence::PersistenceManager::new(
+        Some(&user_data_dir()),
+        false,
+    )?;
+    let sessions = persistence
+        .load_sessions()?
+        .ok_or_else(|| anyhow::anyhow!("No saved episodic memory found"))?;
+    let prefix = id.to_lowercase();
+    let matches: Vec<_> = sessions.iter().filter(|s| s.id.starts_with(&prefix)).collect();
+    let session = match matches.as_slice() {
+        [session] => *session,
+        [] => anyhow::bail!("Session not found: {}", id),
+        many => anyhow::bail!("Ambiguous session id '{}': {} matches", id, many.len()),
+    };
+
+    let sanitizer = if sanitized {
+        let rules = match rules {
+            Some(path) => SanitizeRules::load(path)?,
+            None => SanitizeRules::default(),
+        };
+        Some(rules.compile()?)
+    } else {
+        None
+    };
+
+    let redactions = match output {
+        Some(path) => {
+            let mut file = std::io::BufWriter::new(std::fs::File::create(path)?);
+            let redactions = write_transcript(session, sanitizer.as_ref(), &mut file)?;
+            file.flush()?;
+            redactions
+        }
+        None => write_transcript(session, sanitizer.as_ref(), &mut std::io::stdout().lock())?,
+    };
+
+    eprintln!("📝 Exported {} turns, {} redactions", session.turns.len(), redactions);
+    Ok(())
+}
+
+/// `export-memory`: выбранные слои памяти одним JSON
+fn export_memory_command(request: &MemoryExportRequest, output: Option<&std::path::Path>) -> Result<()> {
+    let persistence = totems::episodic::persistence::PersistenceManager::new(
+        Some(&user_data_dir()),
+        false,
+    )?;
+    let sessions = persistence.load_sessions_with_embeddings_blocking()?.unwrap_or_default();
+    let concepts = SemanticPersistenceManager::new(Some(&user_data_dir().join("semantic")))?.load_serialized()?;
+
+    let export = request.apply(sessions, concepts);
+    match output {
+        Some(path) => {
+            let mut file = std::io::BufWriter::new(std::fs::File::create(path)?);
+            serde_json::to_writer_pretty(&mut file, &export)?;
+            file.flush()?;
+        }
+        None => {
+            serde_json::to_writer_pretty(std::io::stdout().lock(), &export)?;
+            println!();
+        }
+    }
+
+    let turns: usize = export.sessions.iter().map(|s| s.turns.len()).sum();
+    eprintln!(
+        "📦 Exported {} concepts and {} sessions ({} turns)",
+        export.concepts.len(),
+        export.sessions.len(),
+        turns
+    );
+    Ok(())
+}
+
+/// Что лежит в каталоге памяти: для сверки до и после переноса
+#[derive(Debug, PartialEq)]
+struct StoreSummary {
+    files: usize,
+    bytes: u64,
+    sessions: usize,
+    concepts: usize,
+    triples: usize,
+}
+
+impl StoreSummary {
+    /// Читает хранилища так же, как при старте: битый файл - ошибка
+    fn read(dir: &std::path::Path) -> Result<Self> {
+        let (files, bytes) = data_dir::tree_size(dir)?;
+        let sessions = PersistenceManager::new(Some(dir), false)?
+            .load_sessions()?
+            .map_or(0, |s| s.len());
+        let semantic = dir.join("semantic");
+        let semantic_persistence = SemanticPersistenceManager::new(Some(&semantic))?;
+        let concepts = semantic_persistence.load_serialized()?.len();
+        let graph_path = semantic.join(totems::semantic::persistence::KNOWLEDGE_GRAPH_FILE);
+        let triples = if graph_path.exists() {
+            let graph: totems::semantic::KnowledgeGraph =
+                serde_json::from_str(&semantic_persistence.unseal(std::fs::read(&graph_path)?)?)
+                .with_context(|| format!("Failed to parse {}", graph_path.display()))?;
+            graph.triples.len()
+        } else {
+            0
+        };
+        Ok(Self { files, bytes, sessions, concepts, triples })
+    }
+}
+
+/// `migrate-data`: переносит каталог памяти и проверяет, что на новом месте всё читается.
+/// При расхождении каталог возвращается обратно
+fn migrate_data_command(target: Option<&std::path::Path>) -> Result<()> {
+    let source = data_dir();
+    let target = match target {
+        Some(path) => std::env::current_dir()?.join(path),
+        None => data_dir::platform_dir().context("No platform data dir here, pass the target path")?,
+    };
+    anyhow::ensure!(source.exists(), "Nothing to migrate: {} does not exist", source.display());
+    anyhow::ensure!(source != target, "Memory is already in {}", target.display());
+
+    // a running instance would keep writing to the old place; the lock is held until
+    // the moved memory is checked (a rename takes the lock file along)
+    let _lock = MemoryLock::acquire(&source)?;
+    let before = StoreSummary::read(&source).context("The current memory does not load, fix it before moving")?;
+
+    println!("📦 Moving {} → {}", source.display(), target.display());
+    data_dir::move_dir(&source, &target)?;
+    match StoreSummary::read(&target) {
+        Ok(after) if after == before => {}
+        result => {
+            data_dir::move_dir(&target, &source).context("Migration check failed and the move could not be undone")?;
+            match result {
+                Ok(after) => anyhow::bail!("Moved memory differs ({:?} vs {:?}), moved it back", after, before),
+                Err(e) => return Err(e.context("Moved memory does not load, moved it back")),
+            }
+        }
+    }
+
+    println!(
+        "✅ {} files, {} sessions, {} concepts, {} graph triples in {}",
+        before.files,
+        before.sessions,
+        before.concepts,
+        before.triples,
+        target.display()
+    );
+    if Some(&target) != data_dir::platform_dir().as_ref() {
+        println!("   Start with --data-dir {} to use it", target.display());
+    }
+    Ok(())
+}
+
+/// `encrypt-memory`: переписывает хранилища текущего пользователя зашифрованными ключом
+/// памяти (или открытыми с `--decrypt`) и проверяет, что они читаются как прежде
+fn encrypt_memory_command(decrypt: bool) -> Result<()> {
+    let dir = user_data_dir();
+    anyhow::ensure!(dir.exists(), "Nothing to encrypt: {} does not exist", dir.display());
+    let Some(key) = crypto::installed() else {
+        anyhow::bail!(
+            "No memory key: set {} or store one in the OS keyring (service {}, account {})",
+            crypto::KEY_ENV,
+            crypto::KEYRING_SERVICE,
+            crypto::KEYRING_ACCOUNT
+        );
+    };
+
+    // a running instance would write the files back in the old form
+    let _lock = MemoryLock::acquire(&data_dir())?;
+    let before = StoreSummary::read(&dir).context("The current memory does not load, fix it before rewriting")?;
+    let episodic = PersistenceManager::new(Some(&dir), false)?.reseal(!decrypt)?;
+    let semantic = SemanticPersistenceManager::new(Some(&dir.join("semantic")))?.reseal(!decrypt)?;
+    let contexts = demiurge::ContextStorage::reseal(&key, !decrypt)?;
+    let after = StoreSummary::read(&dir)?;
+    anyhow::ensure!(
+        (after.sessions, after.concepts, after.triples) == (before.sessions, before.concepts, before.triples),
+        "Rewritten memory differs ({:?} vs {:?})",
+        after,
+        before
+    );
+
+    println!(
+        "{} {} files ({} sessions, {} concepts, {} graph triples) in {}",
+        if decrypt { "🔓 Decrypted" } else { "🔐 Encrypted" },
+        episodic + semantic + contexts,
+        after.sessions,
+        after.concepts,
+        after.triples,
+        dir.display()
+    );
+    println!("   metadata.json stays open: it holds no conversation text");
+    Ok(())
+}
+
+/// Флаги, которые только читают или меняют семантическую память и выходят без модели
+fn memory_command(args: &Args) -> bool {
+    args.apply_decay || args.decay_stats || args.graph_stats || args.extract_relations || args.find_related.is_some()
+}
+
+/// Память, поднятая в фоне при старте
+struct LoadedMemory {
+    dialogue_manager: Option<DialogueManager>,
+    semantic_manager: Option<Arc<std::sync::Mutex<SemanticMemoryManager>>>,
+}
+
+/// Эпизодическая память (с возобновлением сессии) и семантическая память с диска
+fn hydrate_memory(
+    args: &Args,
+    resume: Option<&demiurge::ConversationCheckpoint>,
+    persistence_manager: &PersistenceManager,
+    embedder: &Arc<dyn Embedder>,
+) -> Result<LoadedMemory> {
+    let mut dialogue_manager: Option<DialogueManager> = None;
+    if args.enable_memory {
+        let mut dm = open_dialogue_manager(persistence_manager, embedder, args.archetype.clone(), args)?;
+        if let Some(checkpoint) = resume {
+            let resumed = uuid::Uuid::parse_str(&checkpoint.session_id).is_ok_and(|id| {
+                load_deferred_sessions(&mut dm, persistence_manager, |d, _| *d == id);
+                dm.resume_session(id)
+            });
+            if resumed {
+                println!(
+                    "↩️  Resumed session {} ({} turns)",
+                    checkpoint.session_id,
+                    dm.current_session().turn_count()
+                );
+            } else {
+                eprintln!("⚠️  Checkpointed session {} not found, starting a new one", checkpoint.session_id);
+            }
+        }
+        if let Some(ref id) = args.resume_session {
+            if let Err(e) = resume_past_session(&mut dm, persistence_manager, id) {
+                eprintln!("⚠️  {}, starting a new session", e);
+            }
+        }
+        dialogue_manager = Some(dm);
+        println!("🗣️ Dialogue memory enabled");
+    }
+
+
+    let semantic_manager = if args.enable_semantic {
+        let sm = open_semantic_manager(embedder, args.read_only)?;
+        println!("🧠 Semantic memory enabled");
+        Some(sm)
+    } else {
+        None
+    };
+    Ok(LoadedMemory {
+        dialogue_manager,
+        semantic_manager,
+    })
+}
+
+/// Дожидается памяти из [`hydrate_memory`] и подключает её: персона, сиды архетипа,
+/// файл фактов и экстрактор концептов. `false`, если память уже подключена
+fn attach_memory(
+    loading: &mut Option<Pending<LoadedMemory>>,
+    dialogue_manager: &mut Option<DialogueManager>,
+    semantic_manager: &mut Option<Arc<std::sync::Mutex<SemanticMemoryManager>>>,
+    facts_file: &mut Option<FactsFile>,
+    persona: &mut Option<Persona>,
+    pipeline_arc: Option<&Arc<std::sync::Mutex<UnifiedPipeline>>>,
+    args: &Args,
+) -> Result<bool> {
+    let Some(loading) = loading.take() else {
+        return Ok(false);
+    };
+    if !loading.is_ready() {
+        println!("⏳ Waiting for memory to finish loading...");
+    }
+    let loaded = loading.wait()?;
+    *dialogue_manager = loaded.dialogue_manager;
+    *semantic_manager = loaded.semantic_manager;
+
+    // сессии --serve открывает и закрывает клиент, сами они не делятся
+    if let (Some(dm), Some(pipeline_arc)) = (dialogue_manager.as_mut(), pipeline_arc.filter(|_| args.interactive)) {
+        let config = SYSTEM.get().map(|system| system.segmentation.clone());
+        dm.set_segmentation(config, Some(Arc::new(ContextAnalyzerImpl::new(pipeline_arc.clone()))));
+    }
+
+    if let Some(ref sm) = *semantic_manager {
+        // памяти грузятся в фоне, ещё до чтения --system-config
+        let retention = SYSTEM.get().map(|system| system.retention.clone()).unwrap_or_default();
+        sm.lock().unwrap().set_retention(retention);
+        *facts_file = match args.facts_file {
+            Some(ref path) => Some(FactsFile::new(path)),
+            None => FactsFile::find(std::path::Path::new(".")),
+        };
+        if let Some(ref mut facts) = facts_file {
+            sync_facts_file(sm, facts);
+        }
+        if let Some(pipeline_arc) = pipeline_arc {
+            let extractor = Arc::new(std::sync::Mutex::new(ConceptExtractorImpl::new(pipeline_arc.clone())));
+            sm.lock().unwrap().set_extractor(extractor);
+        }
+    }
+
+    if let Some(ref mut p) = *persona {
+        if let Some(ref sm) = *semantic_manager {
+            p.set_semantic_manager(sm.clone());
+            println!("🧠 Connected semantic memory to persona");
+        }
+        apply_persona_seeds(p);
+    }
+    Ok(true)
+}
+/// Сохраняет контекст, память и warm start по Ctrl+C. Ставится, когда память подключена:
+/// обработчик берёт копии менеджеров
+fn install_exit_handler(
+    pipeline_arc: &Arc<std::sync::Mutex<UnifiedPipeline>>,
+    persona: &Option<Persona>,
+    dialogue_manager: &Option<DialogueManager>,
+    persistence_manager: &Arc<PersistenceManager>,
+    embedder: &Arc<dyn Embedder>,
+    semantic_manager: &Option<Arc<std::sync::Mutex<SemanticMemoryManager>>>,
+    warm_start: &Option<(std::path::PathBuf, String)>,
+) {
+    let pipeline_for_context = pipeline_arc.clone();
+    let persona_for_save = persona.clone();
+    let mut dm_for_save = dialogue_manager.clone();
+    let persistence_for_save = persistence_manager.clone();
+    let embedder_for_save = embedder.clone();
+    let semantic_for_save = semantic_manager.clone();
+    let warm_start_for_save = warm_start.clone();
+
+    let _ = ctrlc::set_handler(move || {
+        println!("\n\n💾 Saving context before exit...");
+        drain_background_jobs();
+        if let Some(ref mut dm) = dm_for_save {
+            dm.apply_segment_summaries();
+        }
+
+        if let Some(ref p) = persona_for_save {
+            if let Some(ref mut dm) = dm_for_save {
+                let context_analyzer = ContextAnalyzerImpl::new(pipeline_for_context.clone());
+                if let Ok(Some(_)) = p.save_session_context(dm, &context_analyzer) {
+                    println!("💾 Session context saved");
+                }
+            }
+        }
+
+        if let Some(ref dm) = dm_for_save {
+            if let Err(e) = persistence_for_save.save_with_embeddings_blocking(dm, embedder_for_save.embedding_dim()) {
+                eprintln!("WARNING: Failed to save memory: {}", e);
+            } else {
+                println!("💾 Episodic memory saved");
+            }
+        }
+
+        // Also save knowledge graph if enabled
+        if let Some(ref sm) = semantic_for_save {
+            let sm = sm.lock().unwrap();
+            if let Err(e) = sm.save_graph_blocking() {
+                eprintln!("WARNING: Failed to save knowledge graph: {}", e);
+            } else {
+                println!("🕸️ Knowledge graph saved");
+            }
+        }
+        save_warm_start(warm_start_for_save.as_ref(), &pipeline_for_context, &embedder_for_save);
+
+        std::process::exit(0);
+    });
+}
+
+/// Хранилище эпизодической памяти текущего пользователя
+fn open_persistence_manager(args: &Args) -> Result<PersistenceManager> {
+    Ok(PersistenceManager::new(Some(&user_data_dir()), true)?
+        .with_read_only(args.read_only)
+        .with_embedding_model(&embedding_model_id(args)))
+}
+
+/// Сводки (`--progressive-summaries`) рядом с эпизодической памятью
+fn open_summary_store(persistence_manager: &PersistenceManager, args: &Args) -> Option<SummaryStore> {
+    match SummaryStore::open(persistence_manager.memory_dir()) {
+        Ok(store) => {
+            let store = if args.read_only { store.read_only() } else { store };
+            let (chunks, sessions, weeks) = store.counts();
+            println!("📝 Summaries: {} chunks, {} sessions, {} weeks", chunks, sessions, weeks);
+            Some(store)
+        }
+        Err(e) => {
+            eprintln!("WARNING: Failed to load summaries: {}", e);
+            None
+        }
+    }
+}
+
+/// Эпизодическая память персоны с диска (за `--memory-window-days`) или пустая.
+/// Ошибка - только если векторы на диске от другой модели или повреждены, или память
+/// зашифрована, а ключа нет
+fn open_dialogue_manager(
+    persistence_manager: &PersistenceManager,
+    embedder: &Arc<dyn Embedder>,
+    persona_name: String,
+    args: &Args,
+) -> Result<DialogueManager> {
+    migrate_embeddings(persistence_manager, embedder, args)?;
+    let scope = LoadScope::persona(persona_name.clone()).recent(args.memory_window_days);
+    let mut dm = match persistence_manager.load_scoped_blocking(embedder.clone(), persona_name.clone(), &scope) {
+        Ok(Some((loaded_manager, _sessions))) => {
+            let session_count = loaded_manager.session_history().len();
+            let deferred = loaded_manager.deferred_sessions().len();
+            if deferred > 0 {
+                println!(
+                    "📚 Loaded episodic memory: {} sessions ({} older or other personas' left on disk)",
+                    session_count, deferred
+                );
+            } else {
+                println!("📚 Loaded episodic memory: {} sessions", session_count);
+            }
+            loaded_manager
+        }
+        Ok(None) => {
+            println!("📚 No saved episodic memory found, starting fresh");
+            DialogueManager::new(embedder.clone(), persona_name)
+        }
+        // с пустой памятью следующее сохранение затёрло бы сессии на диске
+        Err(e) if e.is::<EmbeddingsIntegrityError>() || e.is::<crypto::DecryptError>() => return Err(e),
+        Err(e) => {
+            eprintln!("WARNING: Failed to load episodic memory: {}", e);
+            DialogueManager::new(embedder.clone(), persona_name)
+        }
+    };
+    dm.set_archive(SessionArchive::open(persistence_manager.memory_dir()));
+    dm.set_user(current_user());
+    dm.set_utc_offset(user_utc_offset(args));
+    dm.set_consent_mode(args.memory_consent);
+    dm.set_significance_filter(significance_filter(args));
+    dm.set_recall_cache_threshold(args.recall_cache_threshold);
+    dm.set_retrieval_config(retrieval_config(None));
+    match dm.set_code_embedder_blocking(CODE_EMBEDDER.get().cloned()) {
+        Ok(0) => {}
+        Ok(indexed) => println!("🧩 Indexed code from {} past turns", indexed),
+        Err(e) => eprintln!("WARNING: Failed to index code of past turns: {}", e),
+    }
+    Ok(dm)
+}
+
+/// После смены модели эмбеддингов пересчитывает векторы всех сессий, печатая прогресс.
+/// Концепты семантической памяти эмбеддятся заново при каждой загрузке, их переводить не нужно
+fn migrate_embeddings(persistence_manager: &PersistenceManager, embedder: &Arc<dyn Embedder>, args: &Args) -> Result<()> {
+    let Some(mismatch) = persistence_manager.embedding_mismatch(embedder.embedding_dim())? else {
+        return Ok(());
+    };
+    // без записи на диск пересчёт пришлось бы повторять при каждом запуске
+    anyhow::ensure!(!args.read_only, "Cannot re-embed read-only episodic memory: {}", mismatch);
+    println!("🔁 Embedding model changed ({}), re-embedding episodic memory...", mismatch);
+    let started = std::time::Instant::now();
+    let step = |total: usize| (total / 10).max(1);
+    let report = persistence_manager.reembed_blocking(embedder.clone(), |done, total| {
+        if done % step(total) == 0 || done == total {
+            println!("   {}/{} sessions", done, total);
+        }
+    })?;
+    println!(
+        "✅ Re-embedded {} sessions ({} turn vectors) in {:.1}s",
+        report.sessions,
+        report.vectors,
+        started.elapsed().as_secs_f32()
+    );
+    Ok(())
+}
+
+/// Документы пользователя (`/docs`) из каталога данных
+fn open_document_store(embedder: &Arc<dyn Embedder>, args: &Args) -> Option<DocumentStore> {
+    match DocumentStore::open(&user_data_dir().join("documents"), embedder.clone()) {
+        Ok(store) => {
+            if !store.documents().is_empty() {
+                println!("📄 Documents: {} ({} excerpts)", store.documents().len(), store.chunk_count());
+            }
+            Some(if args.read_only { store.read_only() } else { store })
+        }
+        Err(e) => {
+            eprintln!("WARNING: Failed to open documents: {}", e);
+            None
+        }
+    }
+}
+
+/// Семантическая память с диска вместе с графом знаний
+fn open_semantic_manager(
+    embedder: &Arc<dyn Embedder>,
+    read_only: bool,
+) -> Result<Arc<std::sync::Mutex<SemanticMemoryManager>>> {
+    let storage_path = user_data_dir().join("semantic");
+    let persistence = SemanticPersistenceManager::new(Some(&storage_path))?.with_read_only(read_only);
+    let mut sm = SemanticMemoryManager::new(embedder.clone(), persistence)?;
+    sm.set_user(current_user());
+
+    // Load knowledge graph if exists
+    match sm.load_graph_blocking() {
+        Ok(()) => {}
+        // an empty graph would be saved over the encrypted one
+        Err(e) if e.is::<crypto::DecryptError>() => return Err(e),
+        Err(e) => eprintln!("WARNING: Failed to load knowledge graph: {}", e),
+    }
+    Ok(Arc::new(std::sync::Mutex::new(sm)))
+}
+
+/// `/memory on|off`: эпизодическая память без перезапуска. Выключенная сохраняется
+/// и откладывается, повторное включение возвращает её без чтения с диска
+fn toggle_episodic_memory(
+    on: bool,
+    args: &mut Args,
+    dialogue_manager: &mut Option<DialogueManager>,
+    suspended: &mut Option<DialogueManager>,
+    persistence_manager: &PersistenceManager,
+    embedder: &Arc<dyn Embedder>,
+    persona: &Option<Persona>,
+) {
+    if on == dialogue_manager.is_some() {
+        println!("Episodic memory is already {}.", if on { "on" } else { "off" });
+        return;
+    }
+    if on {
+        let dm = match suspended.take() {
+            Some(dm) => dm,
+            None => {
+                let persona_name = persona.as_ref().map_or_else(|| args.archetype.clone(), |p| p.archetype_id.clone());
+                match open_dialogue_manager(persistence_manager, embedder, persona_name, args) {
+                    Ok(dm) => dm,
+                    Err(e) => {
+                        eprintln!("❌ Episodic memory stays off: {}", e);
+                        return;
+                    }
+                }
+            }
+        };
+        *dialogue_manager = Some(dm);
+        args.enable_memory = true;
+        println!("🗣️ Dialogue memory enabled");
+    } else if let Some(dm) = dialogue_manager.take() {
+        if let Err(e) = persistence_manager.save_with_embeddings_blocking(&dm, embedder.embedding_dim()) {
+            eprintln!("WARNING: Failed to save memory: {}", e);
+        }
+        *suspended = Some(dm);
+        args.enable_memory = false;
+        println!("⏸️  Dialogue memory suspended (saved; /memory on resumes it)");
+    }
+}
+
+/// `/semantic on|off`: семантическая память без перезапуска. Персона отключается от
+/// неё на время паузы, экстрактор подключается к уже загруженной модели
+fn toggle_semantic_memory(
+    on: bool,
+    args: &mut Args,
+    semantic_manager: &mut Option<Arc<std::sync::Mutex<SemanticMemoryManager>>>,
+    suspended: &mut Option<Arc<std::sync::Mutex<SemanticMemoryManager>>>,
+    embedder: &Arc<dyn Embedder>,
+    pipeline_arc: &Arc<std::sync::Mutex<UnifiedPipeline>>,
+    persona: &mut Option<Persona>,
+) {
+    if on == semantic_manager.is_some() {
+        println!("Semantic memory is already {}.", if on { "on" } else { "off" });
+        return;
+    }
+    if on {
+        let sm = match suspended.take() {
+            Some(sm) => sm,
+            None => match open_semantic_manager(embedder, args.read_only) {
+                Ok(sm) => {
+                    let extractor = Arc::new(std::sync::Mutex::new(ConceptExtractorImpl::new(pipeline_arc.clone())));
+                    sm.lock().unwrap().set_extractor(extractor);
+                    sm
+                }
+                Err(e) => {
+                    println!("❌ Failed to open semantic memory: {}", e);
+                    return;
+                }
+            },
+        };
+        if let Some(ref mut p) = *persona {
+            p.set_semantic_manager(sm.clone());
+        }
+        *semantic_manager = Some(sm);
+        args.enable_semantic = true;
+        println!("🧠 Semantic memory enabled");
+    } else if let Some(sm) = semantic_manager.take() {
+        {
+            let guard = sm.lock().unwrap();
+            if let Err(e) = guard.save_blocking().and_then(|_| guard.save_graph_blocking()) {
+                eprintln!("WARNING: Failed to save semantic memory: {}", e);
+            }
+        }
+        if let Some(ref mut p) = *persona {
+            if p.semantic_manager.as_ref().is_some_and(|linked| Arc::ptr_eq(linked, &sm)) {
+                p.semantic_manager = None;
+            }
+        }
+        *suspended = Some(sm);
+        args.enable_semantic = false;
+        println!("⏸️  Semantic memory suspended (saved; /semantic on resumes it)");
+    }
+}
+
+/// Восстанавливает параметры разговора из последнего чекпоинта в args
+fn resume_from_checkpoint(
+    args: &mut Args,
+    prompt: Option<String>,
+) -> Result<demiurge::ConversationCheckpoint> {
+    let context = demiurge::ContextStorage::latest_checkpoint()?
+        .ok_or_else(|| anyhow::anyhow!("No checkpoint found. Start with --checkpoint --prompt \"...\""))?;
+    let checkpoint = context
+        .checkpoint
+        .clone()
+        .ok_or_else(|| anyhow::anyhow!("Checkpoint is missing from the session context"))?;
+
+    let prompt = match prompt {
+        Some(prompt) => prompt,
+        None => {
+            print!("📝 You: ");
+            std::io::stdout().flush()?;
+            let mut input = String::new();
+            std::io::stdin().read_line(&mut input)?;
+            input.trim().to_string()
+        }
+    };
+    anyhow::ensure!(!prompt.is_empty(), "Nothing to continue with: empty prompt");
+
+    println!(
+        "💤 Resuming {} conversation ({} turns, last: \"{}\")",
+        context.archetype_id,
+        checkpoint.turn_count,
+        truncate_text(&checkpoint.last_user_message, 60)
+    );
+
+    args.archetype = context.archetype_id.clone();
+    args.user = context.user.clone();
+    set_current_user(context.user.clone());
+    args.temperature = checkpoint.sampling.temperature;
+    args.top_p = checkpoint.sampling.top_p;
+    args.top_k = checkpoint.sampling.top_k;
+    args.seed = checkpoint.sampling.seed;
+    args.sample_len = checkpoint.sampling.sample_len;
+    args.enable_semantic = checkpoint.enable_semantic;
+    args.exclude_tags = checkpoint.exclude_tags.clone();
+    args.prompt = Some(prompt);
+    args.checkpoint = true;
+
+    Ok(checkpoint)
+}
+
+/// Сохраняет всё, что нужно для `continue`, в контекст сессии персоны
+fn save_checkpoint(
+    args: &Args,
+    prompt: &str,
+    persona: &Option<Persona>,
+    dialogue_manager: &Option<DialogueManager>,
+) -> Result<()> {
+    let (Some(p), Some(dm)) = (persona, dialogue_manager) else {
+        anyhow::bail!("Checkpoint needs a persona and episodic memory");
+    };
+
+    let checkpoint = demiurge::ConversationCheckpoint {
+        session_id: dm.current_session().id.to_string(),
+        turn_count: dm.current_session().turn_count(),
+        last_user_message: prompt.to_string(),
+        sampling: demiurge::SamplingState {
+            temperature: args.temperature,
+            top_p: args.top_p,
+            top_k: args.top_k,
+            seed: args.seed,
+            sample_len: args.sample_len,
+        },
+        enable_semantic: args.enable_semantic,
+        exclude_tags: args.exclude_tags.clone(),
+        saved_at: p.clock.unix_now(),
+    };
+    p.save_checkpoint(checkpoint)?;
+    println!("💤 Checkpoint saved. Resume with: ziggurat-unified continue \"<message>\"");
+    Ok(())
+}
@@ -0,0 +1,440 @@
+//! 📦 Переносимый архив памяти (.zmx)
+//!
+//! `export` собирает память пользователя в один файл: сессии с векторами ходов,
+//! архив старых сессий, концепты с журналом изменений, граф знаний и нарративы
+//! персон. Это tar, сжатый zstd; первым в нём лежит `manifest.json` с версией
+//! формата, моделью эмбеддингов и числом записей. `import` добавляет из архива то,
+//! чего в памяти ещё нет (сессии и концепты - по ID, связи графа - по тройке), а
+//! если архив записан другой моделью эмбеддингов, считает векторы ходов заново
+//! текущей. Так память переезжает между машинами и переживает смену модели.
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::io::Read;
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::demiurge::narrative::NARRATIVES_DIR;
+use crate::priests::device::select_device;
+use crate::priests::dummy_embeddings::DummyEmbeddingEngine;
+use crate::priests::embeddings::{Embedder, EmbeddingConfig};
+use crate::totems::episodic::archive::SessionArchive;
+use crate::totems::episodic::persistence::{PersistenceManager, SerializedSession};
+use crate::totems::semantic::persistence::{SerializedConcept, SemanticPersistenceManager, KNOWLEDGE_GRAPH_FILE};
+use crate::totems::semantic::{AuditEntry, KnowledgeGraph};
+use crate::utils::lock::MemoryLock;
+use crate::{
+    current_user, data_dir, embedding_model_id, load_embedder, open_dialogue_manager, open_persistence_manager,
+    resolve_path, user_data_dir, Args,
+};
+
+/// Версия формата архива; архив более новой версии не читается
+pub const ARCHIVE_FORMAT_VERSION: u32 = 1;
+/// Уровень zstd: архив пишется редко, важнее размер
+const ZSTD_LEVEL: i32 = 9;
+
+const MANIFEST_ENTRY: &str = "manifest.json";
+const SESSIONS_ENTRY: &str = "sessions.json";
+const ARCHIVED_ENTRY: &str = "archive.json";
+const CONCEPTS_ENTRY: &str = "semantic/concepts.json";
+const GRAPH_ENTRY: &str = "semantic/knowledge_graph.json";
+const AUDIT_ENTRY: &str = "semantic/semantic_audit.jsonl";
+const NARRATIVES_PREFIX: &str = "narratives/";
+
+/// Что лежит в архиве и чем посчитаны векторы
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ArchiveManifest {
+    pub format_version: u32,
+    pub app_version: String,
+    pub created_at: DateTime<Utc>,
+    pub user: String,
+    /// Модель векторов ходов, как её подписывает `memory_data`
+    pub embedding_model: String,
+    pub embedding_dim: usize,
+    pub sessions: usize,
+    pub archived_sessions: usize,
+    pub turns: usize,
+    pub concepts: usize,
+    pub triples: usize,
+    pub narratives: Vec<String>,
+}
+
+impl ArchiveManifest {
+    pub fn new(user: &str, embedding_model: &str, embedding_dim: usize) -> Self {
+        Self {
+            format_version: ARCHIVE_FORMAT_VERSION,
+            app_version: env!("CARGO_PKG_VERSION").to_string(),
+            created_at: Utc::now(),
+            user: user.to_string(),
+            embedding_model: embedding_model.to_string(),
+            embedding_dim,
+            sessions: 0,
+            archived_sessions: 0,
+            turns: 0,
+            concepts: 0,
+            triples: 0,
+            narratives: Vec::new(),
+        }
+    }
+}
+
+/// Содержимое архива
+pub struct MemoryArchive {
+    pub manifest: ArchiveManifest,
+    /// Сессии `memory_data` с векторами ходов
+    pub sessions: Vec<SerializedSession>,
+    /// Сессии, вытесненные в `memory_data/archive`
+    pub archived: Vec<SerializedSession>,
+    pub concepts: Vec<SerializedConcept>,
+    pub graph: Option<KnowledgeGraph>,
+    pub audit: Vec<AuditEntry>,
+    /// ID архетипа -> JSON его нарратива
+    pub narratives: BTreeMap<String, String>,
+}
+
+impl MemoryArchive {
+    pub fn new(manifest: ArchiveManifest) -> Self {
+        Self {
+            manifest,
+            sessions: Vec::new(),
+            archived: Vec::new(),
+            concepts: Vec::new(),
+            graph: None,
+            audit: Vec::new(),
+            narratives: BTreeMap::new(),
+        }
+    }
+
+    /// Пишет архив; счётчики манифеста берутся из содержимого
+    pub fn write(&mut self, path: &Path) -> Result<()> {
+        let manifest = &mut self.manifest;
+        manifest.sessions = self.sessions.len();
+        manifest.archived_sessions = self.archived.len();
+        manifest.turns = self.sessions.iter().chain(&self.archived).map(|s| s.turns.len()).sum();
+        manifest.concepts = self.concepts.len();
+        manifest.triples = self.graph.as_ref().map_or(0, |g| g.triples.len());
+        manifest.narratives = self.narratives.keys().cloned().collect();
+
+        let mut entries: Vec<(String, Vec<u8>)> = vec![
+            (MANIFEST_ENTRY.to_string(), serde_json::to_vec_pretty(&self.manifest)?),
+            (SESSIONS_ENTRY.to_string(), serde_json::to_vec(&self.sessions)?),
+            (ARCHIVED_ENTRY.to_string(), serde_json::to_vec(&self.archived)?),
+            (CONCEPTS_ENTRY.to_string(), serde_json::to_vec_pretty(&self.concepts)?),
+        ];
+        if let Some(ref graph) = self.graph {
+            entries.push((GRAPH_ENTRY.to_string(), serde_json::to_vec(graph)?));
+        }
+        let mut audit = Vec::new();
+        for entry in &self.audit {
+            serde_json::to_writer(&mut audit, entry)?;
+            audit.push(b'\n');
+        }
+        entries.push((AUDIT_ENTRY.to_string(), audit));
+        for (id, narrative) in &self.narratives {
+            entries.push((format!("{}{}.json", NARRATIVES_PREFIX, id), narrative.clone().into_bytes()));
+        }
+
+        let file = std::fs::File::create(path).with_context(|| format!("Failed to create {}", path.display()))?;
+        let mut encoder = zstd::Encoder::new(file, ZSTD_LEVEL)?;
+        encoder.include_checksum(true)?;
+        let mut tar = tar::Builder::new(encoder);
+        let mtime = self.manifest.created_at.timestamp().max(0) as u64;
+        for (name, data) in entries {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(data.len() as u64);
+            header.set_mode(0o644);
+            header.set_mtime(mtime);
+            header.set_cksum();
+            tar.append_data(&mut header, &name, data.as_slice())
+                .with_context(|| format!("Failed to add {} to the archive", name))?;
+        }
+        tar.into_inner()?.finish()?;
+        Ok(())
+    }
+
+    /// Читает архив; архив более новой версии формата - ошибка
+    pub fn read(path: &Path) -> Result<Self> {
+        let file = std::fs::File::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+        let mut tar = tar::Archive::new(zstd::Decoder::new(file)?);
+        let mut files = BTreeMap::new();
+        for entry in tar.entries().with_context(|| format!("{} is not a .zmx archive", path.display()))? {
+            let mut entry = entry?;
+            let name = entry.path()?.to_string_lossy().into_owned();
+            let mut data = Vec::new();
+            entry.read_to_end(&mut data)?;
+            files.insert(name, data);
+        }
+
+        let manifest: ArchiveManifest = match files.get(MANIFEST_ENTRY) {
+            Some(data) => serde_json::from_slice(data).context("Failed to parse the archive manifest")?,
+            None => anyhow::bail!("{} has no {}, it is not a .zmx archive", path.display(), MANIFEST_ENTRY),
+        };
+        anyhow::ensure!(
+            manifest.format_version <= ARCHIVE_FORMAT_VERSION,
+            "{} has format version {}, this build reads up to {}: update ziggurat-unified",
+            path.display(),
+            manifest.format_version,
+            ARCHIVE_FORMAT_VERSION
+        );
+
+        let json = |name: &str| -> Result<Option<serde_json::Value>> {
+            files
+                .get(name)
+                .map(|data| serde_json::from_slice(data).with_context(|| format!("Failed to parse {}", name)))
+                .transpose()
+        };
+        let list = |name: &str| -> Result<Vec<SerializedSession>> {
+            Ok(json(name)?.map(serde_json::from_value).transpose()?.unwrap_or_default())
+        };
+        let mut archive = Self::new(manifest);
+        archive.sessions = list(SESSIONS_ENTRY)?;
+        archive.archived = list(ARCHIVED_ENTRY)?;
+        archive.concepts = json(CONCEPTS_ENTRY)?.map(serde_json::from_value).transpose()?.unwrap_or_default();
+        archive.graph = json(GRAPH_ENTRY)?.map(serde_json::from_value).transpose()?;
+        if let Some(data) = files.get(AUDIT_ENTRY) {
+            let audit = String::from_utf8_lossy(data);
+            archive.audit = audit.lines().filter_map(|line| serde_json::from_str(line).ok()).collect();
+        }
+        for (name, data) in &files {
+            let Some(id) = name.strip_prefix(NARRATIVES_PREFIX).and_then(|n| n.strip_suffix(".json")) else {
+                continue;
+            };
+            // имя становится именем файла: никаких каталогов и скрытых файлов
+            if !is_plain_name(id) {
+                eprintln!("WARNING: Skipping narrative with unsafe name {:?}", name);
+                continue;
+            }
+            archive.narratives.insert(id.to_string(), String::from_utf8_lossy(data).into_owned());
+        }
+        Ok(archive)
+    }
+}
+
+fn is_plain_name(name: &str) -> bool {
+    !name.is_empty()
+        && !name.starts_with('.')
+        && name.chars().all(|c| c.is_alphanumeric() || matches!(c, '-' | '_' | '.'))
+}
+
+/// `export`: вся память текущего пользователя в архив `.zmx`
+pub fn export_command(args: &Args, output: &Path) -> Result<()> {
+    let model = embedding_model_id(args);
+    // векторы чужой модели здесь - ошибка сразу, а не неверный манифест
+    let persistence = PersistenceManager::new(Some(&user_data_dir()), false)?.with_embedding_model(&model);
+    let dim = persistence.get_stats()?.embedding_dim;
+
+    let mut archive = MemoryArchive::new(ArchiveManifest::new(&current_user().to_string(), &model, dim));
+    archive.sessions = persistence.load_sessions_with_embeddings_blocking()?.unwrap_or_default();
+    archive.archived = SessionArchive::open(persistence.memory_dir()).sessions()?;
+
+    let semantic_dir = user_data_dir().join("semantic");
+    let semantic = SemanticPersistenceManager::new(Some(&semantic_dir))?;
+    archive.concepts = semantic.load_serialized()?;
+    archive.audit = semantic.audit_log().entries()?;
+    let graph_path = semantic_dir.join(KNOWLEDGE_GRAPH_FILE);
+    if graph_path.exists() {
+        let graph = serde_json::from_str(&semantic.unseal(std::fs::read(&graph_path)?)?)
+            .with_context(|| format!("Failed to parse {}", graph_path.display()))?;
+        archive.graph = Some(graph);
+    }
+
+    let narratives_dir = Path::new(NARRATIVES_DIR);
+    if narratives_dir.exists() {
+        for entry in std::fs::read_dir(narratives_dir)? {
+            let path = entry?.path();
+            if path.extension().is_some_and(|e| e == "json") {
+                if let Some(id) = path.file_stem().map(|s| s.to_string_lossy().into_owned()) {
+                    archive.narratives.insert(id, std::fs::read_to_string(&path)?);
+                }
+            }
+        }
+    }
+
+    archive.write(output)?;
+    let m = &archive.manifest;
+    println!(
+        "📦 Exported {} sessions ({} turns, {} archived), {} concepts, {} graph triples, {} narratives to {} ({:.1} KB)",
+        m.sessions,
+        m.turns,
+        m.archived_sessions,
+        m.concepts,
+        m.triples,
+        m.narratives.len(),
+        output.display(),
+        std::fs::metadata(output)?.len() as f64 / 1024.0
+    );
+    Ok(())
+}
+
+/// `import`: добавляет в память текущего пользователя то, чего в ней нет
+pub fn import_command(args: &Args, input: &Path) -> Result<()> {
+    anyhow::ensure!(!args.read_only, "Memory is opened read-only, nothing can be imported");
+    let archive = MemoryArchive::read(input)?;
+    let manifest = &archive.manifest;
+    println!(
+        "📦 {}: format {}, written {} by {} for user {}",
+        input.display(),
+        manifest.format_version,
+        manifest.created_at.format("%Y-%m-%d %H:%M"),
+        manifest.app_version,
+        manifest.user
+    );
+
+    // второй экземпляр не должен писать в ту же память
+    let _lock = MemoryLock::acquire(&data_dir())?;
+
+    let embedder: Arc<dyn Embedder> = if args.smoke_test {
+        Arc::new(DummyEmbeddingEngine::new(select_device(args.cpu)?, EmbeddingConfig::default().embedding_dim))
+    } else {
+        load_embedder(args, &resolve_path(&args.embedding_path), &select_device(args.cpu)?)?
+    };
+    let model = embedding_model_id(args);
+    let dim = embedder.embedding_dim();
+    let reuse = manifest.embedding_model == model && manifest.embedding_dim == dim;
+    if !reuse {
+        println!(
+            "🔁 Archive vectors are from {} ({}d), re-embedding turns with {} ({}d)",
+            manifest.embedding_model, manifest.embedding_dim, model, dim
+        );
+    }
+
+    let persistence = open_persistence_manager(args)?;
+    let mut dm = open_dialogue_manager(&persistence, &embedder, args.archetype.clone(), args)?;
+    let live: HashSet<String> = dm
+        .session_history()
+        .keys()
+        .chain(dm.deferred_sessions().keys())
+        .map(|id| id.to_string())
+        .collect();
+    let (sessions, embedded) = dm.import_sessions_blocking(archive.sessions, reuse)?;
+    persistence.save_with_embeddings_blocking(&dm, dim)?;
+
+    let mut session_archive = SessionArchive::open(persistence.memory_dir());
+    let mut archived = 0;
+    for session in &archive.archived {
+        if !live.contains(&session.id) && !session_archive.contains(&session.id)? {
+            session_archive.add(session)?;
+            archived += 1;
+        }
+    }
+
+    let semantic_dir = user_data_dir().join("semantic");
+    let semantic = SemanticPersistenceManager::new(Some(&semantic_dir))?;
+    let mut concepts = semantic.load_serialized()?;
+    let known: HashSet<String> = concepts.iter().map(|c| c.id.clone()).collect();
+    let new_concepts: HashSet<String> = archive
+        .concepts
+        .iter()
+        .filter(|c| !known.contains(&c.id))
+        .map(|c| c.id.clone())
+        .collect();
+    concepts.extend(archive.concepts.into_iter().filter(|c| new_concepts.contains(&c.id)));
+    if !new_concepts.is_empty() {
+        semantic.save_serialized(concepts)?;
+        let audit: Vec<AuditEntry> = archive
+            .audit
+            .into_iter()
+            .filter(|e| new_concepts.contains(&e.concept_id.to_string()))
+            .collect();
+        semantic.audit_log().append(&audit)?;
+    }
+
+    let mut triples = 0;
+    if let Some(imported) = archive.graph {
+        let graph_path = semantic_dir.join(KNOWLEDGE_GRAPH_FILE);
+        let mut graph: KnowledgeGraph = if graph_path.exists() {
+            serde_json::from_str(&semantic.unseal(std::fs::read(&graph_path)?)?)
+                .with_context(|| format!("Failed to parse {}", graph_path.display()))?
+        } else {
+            KnowledgeGraph::new()
+        };
+        let before = graph.triples.len();
+        for triple in imported.triples.into_values() {
+            graph.add_triple(triple);
+        }
+        triples = graph.triples.len() - before;
+        std::fs::write(&graph_path, semantic.seal(serde_json::to_string_pretty(&graph)?)?)
+            .with_context(|| format!("Failed to write {}", graph_path.display()))?;
+    }
+
+    let narratives_dir = Path::new(NARRATIVES_DIR);
+    let mut narratives = 0;
+    for (id, narrative) in &archive.narratives {
+        let path = narratives_dir.join(format!("{}.json", id));
+        if path.exists() {
+            println!("   Narrative of {} already exists here, kept the local one", id);
+            continue;
+        }
+        std::fs::create_dir_all(narratives_dir)?;
+        std::fs::write(&path, narrative).with_context(|| format!("Failed to write {}", path.display()))?;
+        narratives += 1;
+    }
+
+    println!(
+        "✅ Imported {} sessions ({} turns re-embedded), {} archived sessions, {} concepts, {} graph triples, {} narratives",
+        sessions,
+        embedded,
+        archived,
+        new_concepts.len(),
+        triples,
+        narratives
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::totems::episodic::persistence::SerializedTurn;
+    use crate::totems::semantic::concept::Triple;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_archive_roundtrip() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("zikkurat-zmx-{}", std::process::id()));
+        std::fs::create_dir_all(&dir)?;
+        let path = dir.join("memory.zmx");
+
+        let mut archive = MemoryArchive::new(ArchiveManifest::new("default", "multilingual-e5-small", 2));
+        archive.sessions.push(SerializedSession {
+            id: uuid::Uuid::new_v4().to_string(),
+            persona_name: "programmer".to_string(),
+            turns: vec![SerializedTurn {
+                user: "I love tea".to_string(),
+                assistant: "Noted.".to_string(),
+                timestamp: Utc::now(),
+                metadata: HashMap::new(),
+                embedding: Some(vec![0.6, 0.8]),
+                sampling: None,
+            }],
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            metadata: HashMap::new(),
+        });
+        let mut graph = KnowledgeGraph::new();
+        graph.add_triple(Triple::new(uuid::Uuid::new_v4(), "likes".to_string(), uuid::Uuid::new_v4()));
+        archive.graph = Some(graph);
+        archive.narratives.insert("programmer".to_string(), "{}".to_string());
+        archive.write(&path)?;
+
+        let read = MemoryArchive::read(&path)?;
+        assert_eq!(read.manifest, archive.manifest);
+        assert_eq!(read.manifest.turns, 1);
+        assert_eq!(read.manifest.triples, 1);
+        assert_eq!(read.sessions[0].turns[0].embedding, Some(vec![0.6, 0.8]));
+        assert_eq!(read.graph.map(|g| g.triples.len()), Some(1));
+        assert_eq!(read.narratives.keys().collect::<Vec<_>>(), ["programmer"]);
+
+        // a newer format is refused instead of half-imported
+        archive.manifest.format_version = ARCHIVE_FORMAT_VERSION + 1;
+        archive.write(&path)?;
+        let err = MemoryArchive::read(&path).err().expect("newer format must fail");
+        assert!(err.to_string().contains("update ziggurat-unified"));
+        assert!(!is_plain_name("../etc") && is_plain_name("programmer"));
+
+        let _ = std::fs::remove_dir_all(&dir);
+        Ok(())
+    }
+}
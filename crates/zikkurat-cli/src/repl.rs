@@ -0,0 +1,522 @@
+//! ⌨️ Команды REPL
+//!
+//! Строка, начинающаяся с `/`, разбирается здесь: токенизация с кавычками и
+//! экранированием (`/semantic tag 1a2b "rust async"`), поиск команды по имени или
+//! алиасу, проверка подкоманды. Справка и варианты автодополнения строятся из той
+//! же таблицы [`COMMANDS`], так что новая команда описывается в одном месте.
+
+use anyhow::Result;
+
+/// Подкоманда (`/persona switch`)
+#[derive(Debug)]
+pub struct SubcommandSpec {
+    pub name: &'static str,
+    pub aliases: &'static [&'static str],
+    /// Аргументы после имени подкоманды
+    pub usage: &'static str,
+    pub about: &'static str,
+}
+
+/// Команда REPL
+#[derive(Debug)]
+pub struct CommandSpec {
+    pub name: &'static str,
+    pub aliases: &'static [&'static str],
+    /// Аргументы после имени команды (для команд без подкоманд)
+    pub usage: &'static str,
+    pub about: &'static str,
+    /// Если не пусто, первый аргумент обязан быть одной из подкоманд (или `help`)
+    pub subcommands: &'static [SubcommandSpec],
+}
+
+const fn sub(name: &'static str, aliases: &'static [&'static str], usage: &'static str, about: &'static str) -> SubcommandSpec {
+    SubcommandSpec { name, aliases, usage, about }
+}
+
+pub const COMMANDS: &[CommandSpec] = &[
+    CommandSpec {
+        name: "help",
+        aliases: &["h", "?"],
+        usage: "[command]",
+        about: "Show commands or details of one command",
+        subcommands: &[],
+    },
+    CommandSpec {
+        name: "semantic",
+        aliases: &["s"],
+        usage: "",
+        about: "Manage semantic memory",
+        subcommands: &[
+            sub("list", &["ls"], "[tag]", "List concepts (optionally by tag)"),
+            sub("get", &["show"], "<id>", "Show a concept with the utterance it came from"),
+            sub("history", &[], "<id>", "Why a concept has its text and confidence: evidence and changes"),
+            sub("tags", &[], "", "Show all tags"),
+            sub("tag", &[], "<id> <tag>", "Add a tag to a concept"),
+            sub("untag", &[], "<id> <tag>", "Remove a tag"),
+            sub("clusters", &[], "[k]", "Group concepts by meaning (k-means over embeddings)"),
+            sub("graph", &[], "<concept> [hops] [predicate...]", "Show relations around a concept (id or text)"),
+            sub("path", &[], "<from> <to> [predicate...]", "Shortest chain of relations between two concepts"),
+            sub("on", &[], "", "Enable semantic memory without restarting"),
+            sub("off", &[], "", "Save and suspend semantic memory"),
+        ],
+    },
+    CommandSpec {
+        name: "persona",
+        aliases: &["p"],
+        usage: "",
+        about: "Manage persona",
+        subcommands: &[
+            sub("show", &["s"], "", "Show current persona"),
+            sub("traits", &["t"], "", "Show persona traits"),
+            sub("evolution", &["e"], "", "Show evolution stats"),
+            sub("set", &[], "<trait> <0..1>", "Tune a trait now (saved, logged in evolution)"),
+            sub(
+                "switch",
+                &[],
+                "<name> [--fresh|--keep-session] [--carry|--isolate]",
+                "Switch archetype (asks about session/memory if not given)",
+            ),
+            sub("list", &["l"], "", "List available archetypes"),
+            sub(
+                "group",
+                &["ensemble"],
+                "[<name...>] [--debate|--route] | off",
+                "Talk to several personas: the best fit answers, or all in turn with --debate",
+            ),
+        ],
+    },
+    CommandSpec {
+        name: "user",
+        aliases: &[],
+        usage: "[id]",
+        about: "Show users, or switch to another user's memory",
+        subcommands: &[],
+    },
+    CommandSpec {
+        name: "mem",
+        aliases: &["memory"],
+        usage: "",
+        about: "Show RAM/VRAM usage, inspect and curate episodic memory",
+        subcommands: &[
+            sub("list", &["ls"], "[n]", "Recent sessions with their first question"),
+            sub("search", &[], "<query>", "Remembered turns closest to a query"),
+            sub("session", &["show"], "<id>", "All turns of one session"),
+            sub("delete", &["rm"], "<id>", "Forget a session: its turns and their vectors"),
+            sub("stats", &[], "", "Sessions, indexed turns and recall counters"),
+            sub("on", &[], "", "Enable episodic memory without restarting"),
+            sub("off", &[], "", "Save and suspend episodic memory"),
+        ],
+    },
+    CommandSpec {
+        name: "stats",
+        aliases: &[],
+        usage: "",
+        about: "Show performance statistics",
+        subcommands: &[
+            sub("perf", &[], "", "Per-stage latency of responses"),
+            sub("metrics", &[], "", "Request/token/latency counters (same as /metrics)"),
+        ],
+    },
+    CommandSpec {
+        name: "jobs",
+        aliases: &[],
+        usage: "",
+        about: "Show the background job queue (--background-jobs)",
+        subcommands: &[],
+    },
+    CommandSpec {
+        name: "context",
+        aliases: &["c"],
+        usage: "",
+        about: "Show current session context",
+        subcommands: &[
+            sub("show", &[], "", "Context saved for the next session (default)"),
+            sub("timeline", &["t"], "[weeks]", "What past sessions were about, week by week"),
+        ],
+    },
+    CommandSpec {
+        name: "address",
+        aliases: &[],
+        usage: "[formal|informal|auto]",
+        about: "Show or pin Вы/ты address",
+        subcommands: &[],
+    },
+    CommandSpec {
+        name: "good",
+        aliases: &[],
+        usage: "",
+        about: "Rate the last answer as good (used by export-dataset)",
+        subcommands: &[],
+    },
+    CommandSpec {
+        name: "bad",
+        aliases: &[],
+        usage: "",
+        about: "Rate the last answer as bad",
+        subcommands: &[],
+    },
+    CommandSpec {
+        name: "remember",
+        aliases: &[],
+        usage: "",
+        about: "Keep the last exchange in long-term memory (see --memory-consent)",
+        subcommands: &[],
+    },
+    CommandSpec {
+        name: "bookmark",
+        aliases: &[],
+        usage: "[note]",
+        about: "Bookmark the last exchange, with an optional note",
+        subcommands: &[],
+    },
+    CommandSpec {
+        name: "bookmarks",
+        aliases: &[],
+        usage: "[query]",
+        about: "List bookmarks, or show the ones matching a query in full",
+        subcommands: &[],
+    },
+    CommandSpec {
+        name: "correct",
+        aliases: &[],
+        usage: "<old text or id> -> <new text>",
+        about: "Replace a remembered fact with a corrected one",
+        subcommands: &[],
+    },
+    CommandSpec {
+        name: "sessions",
+        aliases: &["session"],
+        usage: "",
+        about: "Search past sessions, including the archive",
+        subcommands: &[
+            sub("list", &[], "", "Number of sessions in memory and in the archive"),
+            sub("search", &[], "<query>", "Search history and archive summaries"),
+            sub("open", &[], "<id>", "Unpack an archived session"),
+            sub("load", &[], "", "Load older and other personas' sessions left on disk"),
+            sub("resume", &[], "<id>", "Continue a past session as the current one"),
+        ],
+    },
+    CommandSpec {
+        name: "docs",
+        aliases: &["documents"],
+        usage: "",
+        about: "Files whose excerpts the assistant can quote",
+        subcommands: &[
+            sub("ingest", &["add"], "<path...>", "Add PDF, Markdown or text files (or a folder)"),
+            sub("list", &["ls"], "", "List ingested documents"),
+        ],
+    },
+    CommandSpec {
+        name: "sensitive",
+        aliases: &[],
+        usage: "[CATEGORY ask|store|skip]",
+        about: "Confirmation for health/financial/relationship facts",
+        subcommands: &[],
+    },
+];
+
+/// Разобранная команда
+#[derive(Debug)]
+pub struct Command {
+    pub spec: &'static CommandSpec,
+    /// Подкоманда с раскрытым алиасом; `help` - запрос справки по команде
+    pub subcommand: Option<&'static str>,
+    /// Аргументы после команды и подкоманды
+    pub args: Vec<String>,
+    /// Остаток строки после команды и подкоманды как есть, без разбора кавычек
+    text: String,
+}
+
+impl Command {
+    pub fn name(&self) -> &'static str {
+        self.spec.name
+    }
+
+    pub fn arg(&self, index: usize) -> Option<&str> {
+        self.args.get(index).map(String::as_str)
+    }
+
+    /// Аргументы без `--флагов`
+    pub fn positional(&self) -> Vec<&str> {
+        self.args.iter().map(String::as_str).filter(|a| !a.starts_with("--")).collect()
+    }
+
+    pub fn has_flag(&self, flag: &str) -> bool {
+        self.args.iter().any(|a| a == flag)
+    }
+
+    /// Все аргументы одной строкой (поисковый запрос без кавычек)
+    pub fn rest(&self) -> String {
+        self.args.join(" ")
+    }
+
+    /// Свободный текст после команды и подкоманды как введён: пробелы и кавычки сохраняются
+    /// (заметки, исправления, поисковые запросы)
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+}
+
+/// Команда по имени или алиасу (без `/`)
+pub fn find(name: &str) -> Option<&'static CommandSpec> {
+    let name = name.to_lowercase();
+    COMMANDS
+        .iter()
+        .find(|c| c.name == name || c.aliases.contains(&name.as_str()))
+}
+
+/// Разбирает строку ввода. `Ok(None)` - это не команда, а обычный запрос
+/// (не начинается с `/` или похоже на путь: `/etc/hosts`)
+pub fn parse(line: &str) -> Result<Option<Command>> {
+    let line = line.trim();
+    let Some(body) = line.strip_prefix('/') else {
+        return Ok(None);
+    };
+    let head = body.split_whitespace().next().unwrap_or("");
+    if head.is_empty() || head.contains('/') {
+        return Ok(None);
+    }
+
+    let mut words = split_args(body)?.into_iter();
+    let name = words.next().unwrap_or_default();
+    let Some(spec) = find(&name) else {
+        let hint = complete(&format!("/{}", name.to_lowercase()))
+            .first()
+            .map(|c| format!(" Did you mean {}?", c))
+            .unwrap_or_default();
+        anyhow::bail!("Unknown command '/{}'.{} Type /help for the list", name, hint);
+    };
+
+    let mut args: Vec<String> = words.collect();
+    let mut subcommand = None;
+    if !spec.subcommands.is_empty() && !args.is_empty() {
+        let first = args.remove(0);
+        let lower = first.to_lowercase();
+        subcommand = if lower == "help" {
+            Some("help")
+        } else {
+            let found = spec
+                .subcommands
+                .iter()
+                .find(|s| s.name == lower || s.aliases.contains(&lower.as_str()));
+            match found {
+                Some(s) => Some(s.name),
+                None => anyhow::bail!(
+                    "Unknown subcommand '{}' for /{}. Available: {}",
+                    first,
+                    spec.name,
+                    spec.subcommands.iter().map(|s| s.name).collect::<Vec<_>>().join(", ")
+                ),
+            }
+        };
+    }
+
+    let mut text = skip_word(body);
+    if subcommand.is_some() {
+        text = skip_word(text);
+    }
+    Ok(Some(Command { spec, subcommand, args, text: text.to_string() }))
+}
+
+/// Строка без первого слова и пробелов вокруг
+fn skip_word(line: &str) -> &str {
+    line.trim().split_once(char::is_whitespace).map_or("", |(_, rest)| rest.trim_start())
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Quote {
+    None,
+    Single,
+    Double,
+}
+
+/// Делит строку на аргументы как shell: `'...'` берётся буквально, в `"..."` и вне
+/// кавычек `\` экранирует кавычку, `\` и (вне кавычек) пробел; прочие `\` остаются как есть.
+/// `'` открывает кавычки только в начале слова (или сразу за закрытыми), так что
+/// апостроф в `don't` остаётся буквой
+pub fn split_args(line: &str) -> Result<Vec<String>> {
+    let mut args = Vec::new();
+    let mut current = String::new();
+    let mut in_word = false;
+    let mut quote = Quote::None;
+    let mut after_quote = false;
+    let mut chars = line.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        let closed_before = std::mem::take(&mut after_quote);
+        match (quote, c) {
+            (Quote::Single, '\'') | (Quote::Double, '"') => {
+                quote = Quote::None;
+                after_quote = true;
+            }
+            (Quote::Single, c) => current.push(c),
+            (_, '\\') => {
+                let escapable = |n: char| n == '"' || n == '\\' || (quote == Quote::None && (n == '\'' || n.is_whitespace()));
+                match chars.peek().copied() {
+                    Some(next) if escapable(next) => {
+                        current.push(next);
+                        chars.next();
+                    }
+                    _ => current.push('\\'),
+                }
+                in_word = true;
+            }
+            (Quote::Double, c) => current.push(c),
+            (Quote::None, '\'') if !in_word || closed_before => {
+                quote = Quote::Single;
+                in_word = true;
+            }
+            (Quote::None, '"') => {
+                quote = Quote::Double;
+                in_word = true;
+            }
+            (Quote::None, c) if c.is_whitespace() => {
+                if in_word {
+                    args.push(std::mem::take(&mut current));
+                    in_word = false;
+                }
+            }
+            (Quote::None, c) => {
+                current.push(c);
+                in_word = true;
+            }
+        }
+    }
+
+    anyhow::ensure!(quote == Quote::None, "Unterminated quote in: {}", line);
+    if in_word {
+        args.push(current);
+    }
+    Ok(args)
+}
+
+/// Варианты автодополнения последнего слова строки: имя команды или подкоманды
+pub fn complete(line: &str) -> Vec<String> {
+    let Some(body) = line.strip_prefix('/') else {
+        return Vec::new();
+    };
+    let words: Vec<&str> = body.split_whitespace().collect();
+    let ends_with_space = body.ends_with(char::is_whitespace);
+
+    match (words.as_slice(), ends_with_space) {
+        ([], _) | ([_], false) => {
+            let prefix = words.first().copied().unwrap_or("").to_lowercase();
+            COMMANDS
+                .iter()
+                .filter(|c| c.name.starts_with(&prefix))
+                .map(|c| format!("/{}", c.name))
+                .collect()
+        }
+        ([name], true) | ([name, _], false) => {
+            let prefix = if ends_with_space { "" } else { words[1] }.to_lowercase();
+            find(name)
+                .map(|spec| {
+                    spec.subcommands
+                        .iter()
+                        .filter(|s| s.name.starts_with(&prefix))
+                        .map(|s| s.name.to_string())
+                        .collect()
+                })
+                .unwrap_or_default()
+        }
+        _ => Vec::new(),
+    }
+}
+
+/// Список всех команд
+pub fn help() -> String {
+    let lines: Vec<(String, &str)> = COMMANDS
+        .iter()
+        .map(|c| {
+            let usage = if c.subcommands.is_empty() {
+                c.usage.to_string()
+            } else {
+                format!("[{}]", c.subcommands.iter().map(|s| s.name).collect::<Vec<_>>().join("|"))
+            };
+            (format!("/{} {}", c.name, usage).trim_end().to_string(), c.about)
+        })
+        .collect();
+    let mut text = format_table(&lines);
+    text.push_str("   Arguments with spaces go in quotes: /semantic tag 1a2b \"rust async\"\n");
+    text
+}
+
+/// Справка по одной команде с её подкомандами
+pub fn command_help(spec: &CommandSpec) -> String {
+    let aliases = if spec.aliases.is_empty() {
+        String::new()
+    } else {
+        format!(" (alias: {})", spec.aliases.iter().map(|a| format!("/{}", a)).collect::<Vec<_>>().join(", "))
+    };
+    let mut text = format!("📝 /{} - {}{}\n", spec.name, spec.about, aliases);
+    let lines: Vec<(String, &str)> = if spec.subcommands.is_empty() {
+        vec![(format!("/{} {}", spec.name, spec.usage).trim_end().to_string(), spec.about)]
+    } else {
+        spec.subcommands
+            .iter()
+            .map(|s| (format!("/{} {} {}", spec.name, s.name, s.usage).trim_end().to_string(), s.about))
+            .collect()
+    };
+    text.push_str(&format_table(&lines));
+    text
+}
+
+fn format_table(lines: &[(String, &str)]) -> String {
+    let width = lines.iter().map(|(usage, _)| usage.chars().count()).max().unwrap_or(0);
+    lines
+        .iter()
+        .map(|(usage, about)| format!("   {:<width$}  {}\n", usage, about, width = width))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_args_quotes() {
+        assert_eq!(
+            split_args(r#"semantic tag 1a2b "rust async"  'it''s' a\ b C:\dir"#).unwrap(),
+            ["semantic", "tag", "1a2b", "rust async", "its", "a b", r"C:\dir"]
+        );
+        assert_eq!(split_args(r#"x "say \"hi\"" """#).unwrap(), ["x", r#"say "hi""#, ""]);
+        assert!(split_args("tag \"open").is_err());
+        assert_eq!(split_args("I don't like tea").unwrap(), ["I", "don't", "like", "tea"]);
+        assert_eq!(split_args("that's 'the one'").unwrap(), ["that's", "the one"]);
+    }
+
+    #[test]
+    fn test_free_text_with_apostrophes() {
+        let cmd = parse("/correct I don't drink coffee -> I drink  tea").unwrap().unwrap();
+        assert_eq!(cmd.text(), "I don't drink coffee -> I drink  tea");
+        let cmd = parse("/bookmark that's the one").unwrap().unwrap();
+        assert_eq!(cmd.text(), "that's the one");
+        let cmd = parse("/memory search I'm   \"vegan\"").unwrap().unwrap();
+        assert_eq!((cmd.subcommand, cmd.text()), (Some("search"), "I'm   \"vegan\""));
+        assert_eq!(parse("/bookmarks").unwrap().unwrap().text(), "");
+    }
+
+    #[test]
+    fn test_parse_and_complete() {
+        let cmd = parse("/p switch 'shared/mentor.json' --fresh").unwrap().unwrap();
+        assert_eq!(cmd.name(), "persona");
+        assert_eq!(cmd.subcommand, Some("switch"));
+        assert_eq!(cmd.positional(), ["shared/mentor.json"]);
+        assert!(cmd.has_flag("--fresh"));
+
+        assert_eq!(parse("/stats").unwrap().unwrap().name(), "stats");
+        let cmd = parse("/session resume 1a2b3c4d").unwrap().unwrap();
+        assert_eq!((cmd.name(), cmd.subcommand, cmd.rest()), ("sessions", Some("resume"), "1a2b3c4d".to_string()));
+        let cmd = parse("/memory off").unwrap().unwrap();
+        assert_eq!((cmd.name(), cmd.subcommand), ("mem", Some("off")));
+        assert!(parse("hello").unwrap().is_none());
+        assert!(parse("/etc/hosts is broken").unwrap().is_none());
+        assert!(parse("/persona dance").is_err());
+        let err = parse("/sem list").unwrap_err().to_string();
+        assert!(err.contains("/semantic"), "{}", err);
+
+        assert_eq!(complete("/se"), ["/semantic", "/sessions", "/sensitive"]);
+        assert_eq!(complete("/persona s"), ["show", "set", "switch"]);
+        assert_eq!(complete("/stats ").len(), 2);
+    }
+}
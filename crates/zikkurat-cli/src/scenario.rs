@@ -0,0 +1,497 @@
+//! 🎬 Сценарии поведения в YAML
+//!
+//! Сценарий описывает разговор целиком: реплики пользователя, смену персоны,
+//! прыжки во времени и ожидания к памяти. `ziggurat-unified scenario FILE...`
+//! прогоняет его через полный `process_query` на echo-модели и dummy-эмбеддингах,
+//! так что регрессии эволюции персоны, затухания и приветствий ловятся без LLM.
+//!
+//! ```yaml
+//! name: Концепт забывается за пару лет тишины
+//! archetype: girlfriend
+//! steps:
+//!   - say: я люблю зелёный чай
+//!   - expect: { concept: зелёный чай, turns: 1 }
+//!   - advance: 800d
+//!   - decay
+//!   - expect: { concept_absent: зелёный чай }
+//! ```
+//!
+//! Каждый сценарий идёт в своей временной директории: `data/` и память
+//! создаются там с нуля, архетипы копируются из `config/archetypes`.
+//! Время идёт по [`MockClock`], который менеджеры памяти и персона получают
+//! вместо системных часов, и двигается только шагами `advance`.
+
+use anyhow::{bail, Context, Result};
+use chrono::Duration;
+use clap::Parser;
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use crate::demiurge::Persona;
+use crate::priests::dummy_embeddings::DummyEmbeddingEngine;
+use crate::priests::embeddings::{Embedder, EmbeddingConfig};
+use crate::totems::episodic::persistence::PersistenceManager;
+use crate::totems::episodic::DialogueManager;
+use crate::totems::semantic::persistence::SemanticPersistenceManager;
+use crate::totems::semantic::SemanticMemoryManager;
+use crate::utils::clock::{MockClock, SharedClock};
+use crate::{
+    handle_persona_command, load_pipeline, open_dialogue_manager, process_query, repl, Args, ConceptExtractorImpl,
+    ContextAnalyzerImpl, UnifiedPipeline,
+};
+
+const ARCHETYPES_DIR: &str = "config/archetypes";
+
+/// Скрипт разговора
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Scenario {
+    pub name: String,
+    /// Персона в начале сценария
+    #[serde(default = "default_archetype")]
+    pub archetype: String,
+    /// Шаги - ключ с аргументом (`say: ...`) или просто имя (`decay`)
+    #[serde(with = "serde_yaml::with::singleton_map_recursive")]
+    pub steps: Vec<Step>,
+}
+
+fn default_archetype() -> String {
+    "girlfriend".to_string()
+}
+
+/// Шаг сценария
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Step {
+    /// Реплика пользователя через полный `process_query`
+    Say(String),
+    /// `/persona switch`: сессия продолжается, семантическая память общая
+    SwitchPersona(String),
+    /// Сдвиг часов: `90m`, `12h`, `30d`, `2w`
+    Advance(String),
+    /// Затухание семантической памяти, как `--apply-decay`
+    Decay,
+    /// Конец сессии, как при выходе: контекст для приветствия сохраняется
+    EndSession,
+    Expect(Expect),
+}
+
+/// Ожидания к состоянию после предыдущих шагов; заданные поля проверяются все
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Expect {
+    /// Есть концепт, текст которого содержит подстроку (без учёта регистра)
+    pub concept: Option<String>,
+    /// Границы уверенности концепта из `concept`
+    pub min_confidence: Option<f32>,
+    pub max_confidence: Option<f32>,
+    /// Нет ни одного концепта с подстрокой
+    pub concept_absent: Option<String>,
+    /// Всего концептов в семантической памяти
+    pub concepts: Option<usize>,
+    /// Последний ответ содержит подстроку
+    pub reply_contains: Option<String>,
+    /// Ходов в текущей сессии
+    pub turns: Option<usize>,
+    /// Активный архетип
+    pub persona: Option<String>,
+    /// Взаимодействий, учтённых эволюцией персоны
+    pub interactions: Option<u64>,
+    /// Приветствие при следующем запуске содержит подстроку
+    pub greeting_contains: Option<String>,
+}
+
+/// Состояние пайплайна, с которым сверяются ожидания
+#[derive(Debug, Clone, Default)]
+pub struct Observed {
+    /// Текст и уверенность концептов
+    pub concepts: Vec<(String, f32)>,
+    pub reply: Option<String>,
+    pub turns: usize,
+    pub persona: Option<String>,
+    pub interactions: u64,
+    pub greeting: Option<String>,
+}
+
+impl Observed {
+    fn find_concept(&self, needle: &str) -> Option<&(String, f32)> {
+        self.concepts.iter().find(|(text, _)| contains(text, needle))
+    }
+}
+
+impl Expect {
+    /// Несбывшиеся ожидания, пустой список - шаг пройден
+    pub fn check(&self, observed: &Observed) -> Vec<String> {
+        let mut failures = Vec::new();
+        if let Some(ref needle) = self.concept {
+            match observed.find_concept(needle) {
+                None => failures.push(format!("no concept containing '{}'", needle)),
+                Some((text, confidence)) => {
+                    if let Some(min) = self.min_confidence.filter(|min| confidence < min) {
+                        failures.push(format!("'{}' confidence {:.2} is below {:.2}", text, confidence, min));
+                    }
+                    if let Some(max) = self.max_confidence.filter(|max| confidence > max) {
+                        failures.push(format!("'{}' confidence {:.2} is above {:.2}", text, confidence, max));
+                    }
+                }
+            }
+        }
+        if let Some((text, _)) = self.concept_absent.as_deref().and_then(|needle| observed.find_concept(needle)) {
+            failures.push(format!("unexpected concept '{}'", text));
+        }
+        if let Some(count) = self.concepts.filter(|count| *count != observed.concepts.len()) {
+            failures.push(format!("{} concepts, expected {}", observed.concepts.len(), count));
+        }
+        if let Some(ref needle) = self.reply_contains {
+            match observed.reply {
+                Some(ref reply) if contains(reply, needle) => {}
+                Some(ref reply) => failures.push(format!("reply '{}' does not contain '{}'", reply, needle)),
+                None => failures.push("no reply yet".to_string()),
+            }
+        }
+        if let Some(turns) = self.turns.filter(|turns| *turns != observed.turns) {
+            failures.push(format!("{} turns in session, expected {}", observed.turns, turns));
+        }
+        if let Some(ref persona) = self.persona {
+            if observed.persona.as_deref() != Some(persona.as_str()) {
+                failures.push(format!("persona {:?}, expected '{}'", observed.persona, persona));
+            }
+        }
+        if let Some(count) = self.interactions.filter(|count| *count != observed.interactions) {
+            failures.push(format!("{} interactions, expected {}", observed.interactions, count));
+        }
+        if let Some(ref needle) = self.greeting_contains {
+            let greeting = observed.greeting.as_deref().unwrap_or_default();
+            if !contains(greeting, needle) {
+                failures.push(format!("greeting '{}' does not contain '{}'", greeting, needle));
+            }
+        }
+        failures
+    }
+}
+
+fn contains(haystack: &str, needle: &str) -> bool {
+    haystack.to_lowercase().contains(&needle.to_lowercase())
+}
+
+/// `30d` -> 30 дней; единицы s, m, h, d, w
+pub fn parse_advance(text: &str) -> Result<Duration> {
+    let text = text.trim();
+    let split = text.find(|c: char| !c.is_ascii_digit()).unwrap_or(text.len());
+    let (amount, unit) = text.split_at(split);
+    let amount: i64 = amount.parse().with_context(|| format!("Bad time jump '{}'", text))?;
+    Ok(match unit.trim() {
+        "s" => Duration::seconds(amount),
+        "m" => Duration::minutes(amount),
+        "h" => Duration::hours(amount),
+        "d" => Duration::days(amount),
+        "w" => Duration::weeks(amount),
+        _ => bail!("Bad time jump '{}': use s, m, h, d or w", text),
+    })
+}
+
+impl Scenario {
+    pub fn parse(yaml: &str) -> Result<Self> {
+        let scenario: Scenario = serde_yaml::from_str(yaml)?;
+        for step in &scenario.steps {
+            if let Step::Advance(ref jump) = step {
+                parse_advance(jump)?;
+            }
+        }
+        Ok(scenario)
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let yaml = std::fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+        Self::parse(&yaml).with_context(|| format!("Invalid scenario {}", path.display()))
+    }
+}
+
+/// Файлы сценариев; директории раскрываются в свои `*.yaml` / `*.yml`
+pub fn collect_files(paths: &[PathBuf]) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    for path in paths {
+        if !path.is_dir() {
+            files.push(path.clone());
+            continue;
+        }
+        let mut found: Vec<PathBuf> = std::fs::read_dir(path)?
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|p| p.extension().is_some_and(|ext| ext == "yaml" || ext == "yml"))
+            .collect();
+        found.sort();
+        files.extend(found);
+    }
+    Ok(files)
+}
+
+/// Прогоняет сценарии и печатает итог; false, если хоть один не прошёл
+pub fn run_scenarios(paths: &[PathBuf]) -> Result<bool> {
+    let files = collect_files(paths)?;
+    if files.is_empty() {
+        bail!("No scenario files found");
+    }
+    let scenarios = files.iter().map(|f| Scenario::load(f)).collect::<Result<Vec<_>>>()?;
+
+    let mut failed = Vec::new();
+    for (file, scenario) in files.iter().zip(&scenarios) {
+        println!("\n🎬 {} ({})", scenario.name, file.display());
+        match run_scenario(scenario) {
+            Ok(()) => {}
+            Err(e) => {
+                println!("❌ {}: {:#}", scenario.name, e);
+                failed.push(scenario.name.clone());
+            }
+        }
+    }
+
+    println!("\n🎬 Scenarios: {} passed, {} failed", scenarios.len() - failed.len(), failed.len());
+    for name in &failed {
+        println!("   ❌ {}", name);
+    }
+    Ok(failed.is_empty())
+}
+
+/// Один сценарий в своей директории; ошибка - первый несбывшийся шаг
+pub fn run_scenario(scenario: &Scenario) -> Result<()> {
+    let _sandbox = Sandbox::enter()?;
+    let mut run = Run::start(&scenario.archetype)?;
+    for (index, step) in scenario.steps.iter().enumerate() {
+        let failures = run.step(step).with_context(|| format!("step {} ({:?})", index + 1, step))?;
+        if !failures.is_empty() {
+            bail!("step {}: {}", index + 1, failures.join("; "));
+        }
+    }
+    println!("✅ {}: {} steps passed", scenario.name, scenario.steps.len());
+    Ok(())
+}
+
+/// Временная рабочая директория и сдвиг часов на время сценария
+struct Sandbox {
+    dir: PathBuf,
+    previous: PathBuf,
+}
+
+impl Sandbox {
+    fn enter() -> Result<Self> {
+        let dir = std::env::temp_dir().join(format!("ziggurat-scenario-{}", uuid::Uuid::new_v4()));
+        let archetypes = dir.join(ARCHETYPES_DIR);
+        std::fs::create_dir_all(&archetypes)?;
+        for entry in std::fs::read_dir(ARCHETYPES_DIR).context("Run scenarios from the repository root")? {
+            let path = entry?.path();
+            if path.is_file() {
+                std::fs::copy(&path, archetypes.join(path.file_name().unwrap_or_default()))?;
+            }
+        }
+        let previous = std::env::current_dir()?;
+        std::env::set_current_dir(&dir)?;
+        Ok(Self { dir, previous })
+    }
+}
+
+impl Drop for Sandbox {
+    fn drop(&mut self) {
+        let _ = std::env::set_current_dir(&self.previous);
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+}
+
+/// Компоненты smoke-теста, собранные как в `main`
+struct Run {
+    args: Args,
+    pipeline: Arc<Mutex<UnifiedPipeline>>,
+    embedder: Arc<dyn Embedder>,
+    persistence: Arc<PersistenceManager>,
+    dialogue: Option<DialogueManager>,
+    semantic: Option<Arc<Mutex<SemanticMemoryManager>>>,
+    persona: Option<Persona>,
+    clock: Arc<MockClock>,
+}
+
+impl Run {
+    fn start(archetype: &str) -> Result<Self> {
+        let args = Args::try_parse_from([
+            "ziggurat-unified",
+            "--smoke-test",
+            "--enable-memory",
+            "--enable-semantic",
+            "--quiet",
+            "--archetype",
+            archetype,
+        ])?;
+        let device = candle_core::Device::Cpu;
+        let pipeline = Arc::new(Mutex::new(load_pipeline(&args, &device)?));
+        let embedder: Arc<dyn Embedder> =
+            Arc::new(DummyEmbeddingEngine::new(device, EmbeddingConfig::default().embedding_dim));
+
+        let memory_dir = std::env::current_dir()?.join("memory_data");
+        let persistence = Arc::new(PersistenceManager::new(Some(&memory_dir), true)?);
+        let dialogue = open_dialogue_manager(&persistence, &embedder, archetype.to_string(), &args)?;
+
+        let semantic_persistence = SemanticPersistenceManager::new(Some(&memory_dir.join("semantic")))?;
+        let mut semantic = SemanticMemoryManager::new(embedder.clone(), semantic_persistence)?;
+        semantic.set_extractor(Arc::new(Mutex::new(ConceptExtractorImpl::new(pipeline.clone()))));
+
+        let mut run = Self {
+            args,
+            pipeline,
+            embedder,
+            persistence,
+            dialogue: Some(dialogue),
+            semantic: Some(Arc::new(Mutex::new(semantic))),
+            persona: None,
+            clock: Arc::new(MockClock::starting_now()),
+        };
+        run.switch_persona(archetype)?;
+        Ok(run)
+    }
+
+    fn switch_persona(&mut self, archetype: &str) -> Result<()> {
+        let line = format!("/persona switch {} --keep-session --carry", archetype);
+        let command = repl::parse(&line)?.context("persona switch is not a command")?;
+        handle_persona_command(
+            &command,
+            &mut self.persona,
+            &mut self.dialogue,
+            &self.semantic,
+            &self.persistence,
+            &self.pipeline,
+            &self.args,
+        );
+        if self.persona.as_ref().map(|p| p.archetype_id.as_str()) != Some(archetype) {
+            bail!("Unknown archetype '{}'", archetype);
+        }
+        self.inject_clock();
+        Ok(())
+    }
+
+    /// Переключение персоны создаёт её заново - часы раздаются после каждого
+    fn inject_clock(&mut self) {
+        let clock: SharedClock = self.clock.clone();
+        if let Some(ref sm) = self.semantic {
+            sm.lock().unwrap().set_clock(clock.clone());
+        }
+        if let Some(ref mut dm) = self.dialogue {
+            dm.set_clock(clock.clone());
+        }
+        if let Some(ref mut p) = self.persona {
+            p.clock = clock;
+        }
+    }
+
+    fn step(&mut self, step: &Step) -> Result<Vec<String>> {
+        match step {
+            Step::Say(text) => {
+                process_query(
+                    text,
+                    &self.pipeline,
+                    &mut self.dialogue,
+                    &mut self.semantic,
+                    &self.persistence,
+                    &self.embedder,
+                    &self.args,
+                    &mut self.persona,
+                )?;
+            }
+            Step::SwitchPersona(archetype) => self.switch_persona(archetype)?,
+            Step::Advance(jump) => self.clock.advance(parse_advance(jump)?),
+            Step::Decay => {
+                if let Some(ref sm) = self.semantic {
+                    sm.lock().unwrap().apply_temporal_decay()?;
+                }
+            }
+            Step::EndSession => {
+                if let (Some(p), Some(dm)) = (self.persona.as_ref(), self.dialogue.as_mut()) {
+                    p.save_session_context(dm, &ContextAnalyzerImpl::new(self.pipeline.clone()))?;
+                    dm.start_new_session(p.archetype_id.clone());
+                }
+            }
+            Step::Expect(expect) => {
+                let observed = self.observe(expect.greeting_contains.is_some())?;
+                return Ok(expect.check(&observed));
+            }
+        }
+        Ok(Vec::new())
+    }
+
+    fn observe(&mut self, with_greeting: bool) -> Result<Observed> {
+        let mut observed = Observed::default();
+        if let Some(ref sm) = self.semantic {
+            observed.concepts = sm
+                .lock()
+                .unwrap()
+                .snapshot()
+                .concepts
+                .into_values()
+                .map(|c| (c.text, c.confidence))
+                .collect();
+        }
+        if let Some(ref dm) = self.dialogue {
+            let session = dm.current_session();
+            observed.turns = session.turn_count();
+            observed.reply = session.last_turn().map(|t| t.assistant.clone());
+        }
+        if let Some(ref mut p) = self.persona {
+            observed.persona = Some(p.archetype_id.clone());
+            observed.interactions = p.evolution.interactions_count;
+            if with_greeting {
+                // as at startup: a fresh saved context gives the contextual greeting
+                observed.greeting = Some(match p.load_session_context()? {
+                    Some(context) if !context.summary.is_empty() => p.generate_contextual_greeting(&context),
+                    _ => p.communication.greeting.clone(),
+                });
+            }
+        }
+        Ok(observed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scenario_parse_and_expectations() {
+        let scenario = Scenario::parse(
+            "name: decay\n\
+             steps:\n\
+             \x20 - say: я люблю зелёный чай\n\
+             \x20 - switch_persona: programmer\n\
+             \x20 - advance: 400d\n\
+             \x20 - decay\n\
+             \x20 - end_session\n\
+             \x20 - expect: { concept_absent: чай, turns: 0, persona: programmer }\n",
+        )
+        .unwrap();
+        assert_eq!(scenario.archetype, "girlfriend");
+        assert_eq!(scenario.steps[0], Step::Say("я люблю зелёный чай".to_string()));
+        assert_eq!(scenario.steps[3], Step::Decay);
+        assert!(Scenario::parse("name: x\nsteps:\n  - advance: soon\n").is_err());
+        assert_eq!(parse_advance("2w").unwrap(), Duration::days(14));
+
+        let observed = Observed {
+            concepts: vec![("Preference: зелёный чай".to_string(), 0.4)],
+            reply: Some("Hello there".to_string()),
+            turns: 1,
+            persona: Some("girlfriend".to_string()),
+            ..Default::default()
+        };
+        let expect = Expect {
+            concept: Some("ЗЕЛЁНЫЙ".to_string()),
+            min_confidence: Some(0.3),
+            reply_contains: Some("hello".to_string()),
+            turns: Some(1),
+            ..Default::default()
+        };
+        assert!(expect.check(&observed).is_empty());
+        let Step::Expect(ref last) = scenario.steps[5] else { panic!("expect step") };
+        assert_eq!(last.check(&observed).len(), 3);
+
+        // every shipped scenario stays loadable (config/ lives at the workspace root)
+        let shipped = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("../../config/scenarios");
+        for file in collect_files(&[shipped]).unwrap() {
+            Scenario::load(&file).unwrap();
+        }
+    }
+}
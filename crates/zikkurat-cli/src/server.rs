@@ -0,0 +1,727 @@
+//! 🌐 HTTP API - режим сервиса
+//!
+//! `ziggurat-unified --serve --port 8080` держит модель и память в одном процессе
+//! и отвечает на REST-запросы: чат через полный `process_query`, управление
+//! сессиями и поиск по памяти. У каждого соединения свой поток, а модель и
+//! менеджеры памяти общие и стоят за одним замком: запросы к ним идут по очереди,
+//! `/health` и `/metrics` отвечают, не дожидаясь идущего хода. HTTP/1.1 разбирается
+//! вручную, как у `/metrics`: тело только с `Content-Length`, соединение
+//! закрывается после ответа.
+//!
+//! `/v1/ws` переключает соединение на WebSocket для живого чата: клиент шлёт
+//! `{"message": "...", "session_id": "1a2b"}` текстовыми сообщениями, сервер
+//! отвечает потоком событий `{"type": ...}` - `memory_hit` (что из памяти попало в
+//! промпт), `token` (кусок ответа по мере генерации), `concept_extracted`,
+//! `persona_evolved` и в конце хода `done` с тем же телом, что у `/v1/chat`, или
+//! `error`. Сокет занимает замок только на время хода, между сообщениями HTTP
+//! обслуживается как обычно, а ходы HTTP-клиентов в сокет не попадают. Подписчик
+//! событий на процесс один, поэтому второй сокет получает `409`, пока открыт
+//! первый; сокет без сообщений закрывается через `SOCKET_IDLE_TIMEOUT`.
+//!
+//! | Метод | Путь | |
+//! |-------|------|-|
+//! | `GET` | `/health` | персона и включённая память |
+//! | `POST` | `/v1/chat` | `{"message": "...", "session_id": "1a2b", "user": "alice"}` → ответ персоны |
+//! | `GET` | `/v1/sessions` | сессии в памяти и число оставленных на диске |
+//! | `POST` | `/v1/sessions` | новая сессия |
+//! | `GET` | `/v1/sessions/{id}` | ходы сессии (id или его префикс) |
+//! | `POST` | `/v1/sessions/{id}/resume` | продолжить сессию |
+//! | `DELETE` | `/v1/sessions/{id}` | удалить сессию |
+//! | `GET` | `/v1/memory/search?q=...&k=5` | похожие диалоги и концепты |
+//! | `GET` | `/metrics` | метрики в формате Prometheus, как у `--metrics-addr` |
+//! | `GET` | `/v1/ws` | WebSocket: чат с потоком токенов и событий памяти |
+
+use anyhow::{bail, ensure, Context, Result};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::demiurge::Persona;
+use crate::logos::events;
+use crate::logos::metrics;
+use crate::priests::embeddings::Embedder;
+use crate::totems::episodic::persistence::PersistenceManager;
+use crate::totems::episodic::{DialogueManager, Session};
+use crate::totems::semantic::SemanticMemoryManager;
+use crate::websocket::{self, Message};
+use crate::totems::user::UserId;
+use crate::{
+    load_deferred_sessions, process_query, resume_past_session, run_pending_maintenance, with_quota_user, Args,
+    UnifiedPipeline,
+};
+
+/// Предел тела запроса
+const MAX_BODY_BYTES: usize = 1 << 20;
+/// Предел строки запроса и каждого заголовка
+const MAX_LINE_BYTES: usize = 8 << 10;
+/// Предел числа заголовков
+const MAX_HEADERS: usize = 100;
+/// Сколько ждать медленного клиента, прежде чем бросить соединение
+const READ_TIMEOUT: Duration = Duration::from_secs(30);
+/// Сколько WebSocket может молчать, прежде чем сервер его закроет
+const SOCKET_IDLE_TIMEOUT: Duration = Duration::from_secs(300);
+/// Результатов поиска по памяти, если `k` не задан
+const DEFAULT_SEARCH_K: usize = 5;
+/// Верхний предел `k`
+const MAX_SEARCH_K: usize = 50;
+
+/// Разобранный HTTP-запрос
+#[derive(Debug)]
+struct Request {
+    method: String,
+    path: String,
+    query: HashMap<String, String>,
+    /// Имена в нижнем регистре
+    headers: HashMap<String, String>,
+    body: Vec<u8>,
+}
+
+/// Тело ответа: JSON API или текст `/metrics`
+#[derive(Debug)]
+enum Body {
+    Json(Value),
+    Text(String),
+}
+
+/// Статус и тело ответа
+#[derive(Debug)]
+struct Response {
+    status: u16,
+    body: Body,
+}
+
+impl Response {
+    fn ok(body: Value) -> Self {
+        Self { status: 200, body: Body::Json(body) }
+    }
+
+    fn error(status: u16, message: impl std::fmt::Display) -> Self {
+        Self {
+            status,
+            body: Body::Json(json!({ "error": message.to_string() })),
+        }
+    }
+
+    fn metrics() -> Self {
+        Self {
+            status: 200,
+            body: Body::Text(metrics::render_prometheus()),
+        }
+    }
+
+    fn memory_disabled() -> Self {
+        Self::error(400, "Dialogue memory is disabled (start with --enable-memory)")
+    }
+}
+
+/// Маршруты API
+#[derive(Debug, PartialEq)]
+enum Route<'a> {
+    Health,
+    Chat,
+    ListSessions,
+    NewSession,
+    GetSession(&'a str),
+    ResumeSession(&'a str),
+    DeleteSession(&'a str),
+    SearchMemory,
+    Metrics,
+    Socket,
+}
+
+fn route<'a>(method: &str, path: &'a str) -> Option<Route<'a>> {
+    let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+    match (method, segments.as_slice()) {
+        ("GET", ["health"]) => Some(Route::Health),
+        ("POST", ["v1", "chat"]) => Some(Route::Chat),
+        ("GET", ["v1", "sessions"]) => Some(Route::ListSessions),
+        ("POST", ["v1", "sessions"]) => Some(Route::NewSession),
+        ("GET", ["v1", "sessions", id]) => Some(Route::GetSession(id)),
+        ("POST", ["v1", "sessions", id, "resume"]) => Some(Route::ResumeSession(id)),
+        ("DELETE", ["v1", "sessions", id]) => Some(Route::DeleteSession(id)),
+        ("GET", ["v1", "memory", "search"]) => Some(Route::SearchMemory),
+        ("GET", ["metrics"]) => Some(Route::Metrics),
+        ("GET", ["v1", "ws"]) => Some(Route::Socket),
+        _ => None,
+    }
+}
+
+/// Запрос отклонён до разбора: статус ответа и причина
+#[derive(Debug)]
+struct Rejected {
+    status: u16,
+    reason: String,
+}
+
+impl std::fmt::Display for Rejected {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.reason)
+    }
+}
+
+impl std::error::Error for Rejected {}
+
+/// Строка не длиннее `MAX_LINE_BYTES`: клиент не раздует память сервера одной строкой
+fn read_line_limited(reader: &mut impl BufRead, status: u16, what: &str) -> Result<String> {
+    let mut line = String::new();
+    reader.by_ref().take(MAX_LINE_BYTES as u64 + 1).read_line(&mut line)?;
+    if line.len() > MAX_LINE_BYTES {
+        return Err(Rejected {
+            status,
+            reason: format!("{} over {} bytes", what, MAX_LINE_BYTES),
+        }
+        .into());
+    }
+    Ok(line)
+}
+
+/// Читает строку запроса, заголовки и тело по `Content-Length`
+fn read_request(mut reader: impl BufRead) -> Result<Request> {
+    let line = read_line_limited(&mut reader, 400, "Request line")?;
+    let mut parts = line.split_whitespace();
+    let (Some(method), Some(target)) = (parts.next(), parts.next()) else {
+        bail!("Malformed request line");
+    };
+
+    let mut content_length = 0usize;
+    let mut headers = HashMap::new();
+    for count in 0.. {
+        let header = read_line_limited(&mut reader, 431, "Header")?;
+        if header.is_empty() {
+            break;
+        }
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if count == MAX_HEADERS {
+            return Err(Rejected {
+                status: 431,
+                reason: format!("More than {} headers", MAX_HEADERS),
+            }
+            .into());
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            let name = name.trim().to_ascii_lowercase();
+            if name == "content-length" {
+                content_length = value.trim().parse().context("Bad Content-Length")?;
+            }
+            headers.insert(name, value.trim().to_string());
+        }
+    }
+    ensure!(content_length <= MAX_BODY_BYTES, "Request body over {} bytes", MAX_BODY_BYTES);
+    let mut body = vec![0; content_length];
+    reader.read_exact(&mut body)?;
+
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    let query = query
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            (percent_decode(key), percent_decode(value))
+        })
+        .collect();
+    Ok(Request {
+        method: method.to_string(),
+        path: percent_decode(path),
+        query,
+        headers,
+        body,
+    })
+}
+
+/// `%D0%BF+x` → `п x`; битые последовательности остаются как есть
+fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'+' => out.push(b' '),
+            b'%' if i + 2 < bytes.len() && bytes[i + 1..i + 3].iter().all(u8::is_ascii_hexdigit) => {
+                let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).unwrap_or("00");
+                out.push(u8::from_str_radix(hex, 16).unwrap_or(0));
+                i += 2;
+            }
+            byte => out.push(byte),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+fn write_response(stream: &mut impl Write, response: &Response) -> std::io::Result<()> {
+    let reason = match response.status {
+        200 => "OK",
+        400 => "Bad Request",
+        409 => "Conflict",
+        426 => "Upgrade Required",
+        431 => "Request Header Fields Too Large",
+        404 => "Not Found",
+        _ => "Internal Server Error",
+    };
+    let (content_type, body) = match &response.body {
+        Body::Json(value) => ("application/json; charset=utf-8", value.to_string()),
+        Body::Text(text) => ("text/plain; version=0.0.4", text.clone()),
+    };
+    write!(
+        stream,
+        "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        response.status,
+        reason,
+        content_type,
+        body.len(),
+        body
+    )
+}
+
+/// Тело `POST /v1/chat`
+#[derive(Debug, Deserialize)]
+struct ChatRequest {
+    message: String,
+    /// Продолжить эту сессию (id или префикс); без него - текущая
+    #[serde(default)]
+    session_id: Option<String>,
+    /// Чья квота расходуется; без него - пользователя `--user`
+    #[serde(default)]
+    user: Option<String>,
+}
+
+/// Краткое описание сессии для списка
+fn session_summary(session: &Session, current: bool) -> Value {
+    json!({
+        "id": session.id.to_string(),
+        "persona": session.persona_name,
+        "turns": session.turn_count(),
+        "created_at": session.created_at.to_rfc3339(),
+        "updated_at": session.updated_at.to_rfc3339(),
+        "current": current,
+    })
+}
+
+/// Последнее событие хода в сокете: `done` с телом ответа `/v1/chat` или `error`
+fn final_event(response: Response) -> Value {
+    let mut event = match response.body {
+        Body::Json(Value::Object(body)) => Value::Object(body),
+        Body::Json(body) => json!({ "body": body }),
+        Body::Text(text) => json!({ "body": text }),
+    };
+    if response.status == 200 {
+        event["type"] = json!("done");
+    } else {
+        event["type"] = json!("error");
+        event["status"] = json!(response.status);
+    }
+    event
+}
+
+/// Компоненты интерактивного режима, собранные в `main`
+pub struct Server<'a> {
+    pub pipeline: Arc<Mutex<UnifiedPipeline>>,
+    pub dialogue: Option<DialogueManager>,
+    pub semantic: Option<Arc<Mutex<SemanticMemoryManager>>>,
+    pub persistence: Arc<PersistenceManager>,
+    pub embedder: Arc<dyn Embedder>,
+    pub persona: Option<Persona>,
+    pub args: &'a Args,
+}
+
+/// Общее для потоков соединений
+struct Shared<'a> {
+    server: Mutex<Server<'a>>,
+    args: &'a Args,
+    /// Ответ `/health`: персона и включённая память за время работы не меняются,
+    /// так что он не ждёт замка
+    health: Value,
+    /// Открыт ли WebSocket: подписчик [`events`] на процесс один
+    socket_open: AtomicBool,
+}
+
+impl Shared<'_> {
+    fn connection(&self, mut stream: TcpStream) {
+        let _ = stream.set_read_timeout(Some(READ_TIMEOUT));
+        let request = read_request(BufReader::new(&stream));
+        let (response, stateful) = match request {
+            Ok(request) if route(&request.method, &request.path) == Some(Route::Socket) => {
+                if self.socket_open.swap(true, Ordering::AcqRel) {
+                    (Response::error(409, "Another WebSocket client is connected"), false)
+                } else {
+                    let result = self.websocket(stream, &request);
+                    self.socket_open.store(false, Ordering::Release);
+                    match result {
+                        Ok(()) if !self.args.quiet => println!("🌐 WebSocket closed"),
+                        Ok(()) => {}
+                        Err(e) => eprintln!("WARNING: WebSocket closed: {:#}", e),
+                    }
+                    self.server.lock().unwrap().maintain();
+                    return;
+                }
+            }
+            Ok(request) => {
+                let (response, stateful) = match route(&request.method, &request.path) {
+                    Some(Route::Health) => (Response::ok(self.health.clone()), false),
+                    Some(Route::Metrics) => (Response::metrics(), false),
+                    _ => (self.server.lock().unwrap().handle(&request), true),
+                };
+                if !self.args.quiet {
+                    println!("🌐 {} {} → {}", request.method, request.path, response.status);
+                }
+                (response, stateful)
+            }
+            Err(e) => (Response::error(e.downcast_ref::<Rejected>().map_or(400, |r| r.status), e), false),
+        };
+        if let Err(e) = write_response(&mut stream, &response) {
+            eprintln!("WARNING: Failed to send response: {}", e);
+        }
+        // the client reads until the connection closes, it must not wait for maintenance
+        drop(stream);
+        if stateful {
+            self.server.lock().unwrap().maintain();
+        }
+    }
+
+    /// Живой чат: сообщения сокета идут через `chat`, события хода - подписчику
+    /// [`events`]. До закрытия сокета или `SOCKET_IDLE_TIMEOUT` тишины; замок
+    /// сервера берётся только на время хода
+    fn websocket(&self, mut stream: TcpStream, request: &Request) -> Result<()> {
+        let upgrade = request.headers.get("upgrade").is_some_and(|u| u.eq_ignore_ascii_case("websocket"));
+        let Some(key) = request.headers.get("sec-websocket-key").filter(|_| upgrade) else {
+            write_response(&mut stream, &Response::error(426, "Expected a WebSocket upgrade"))?;
+            return Ok(());
+        };
+        stream.set_read_timeout(Some(SOCKET_IDLE_TIMEOUT))?;
+        stream.write_all(websocket::handshake_response(key).as_bytes())?;
+        if !self.args.quiet {
+            println!("🌐 WebSocket opened");
+        }
+
+        let mut reader = BufReader::new(stream.try_clone()?);
+        // events come from the turn and from background extraction, frames must not interleave
+        let writer = Arc::new(Mutex::new(stream));
+        let _subscription = events::subscribe({
+            let writer = Arc::clone(&writer);
+            move |event| {
+                // a client that went away shows up on the next read
+                let event = serde_json::to_string(event).unwrap_or_default();
+                let _ = websocket::write_text(&mut *writer.lock().unwrap(), &event);
+            }
+        });
+        loop {
+            let message = websocket::read_message(&mut reader, |payload| {
+                websocket::write_pong(&mut *writer.lock().unwrap(), payload)
+            })?;
+            match message {
+                Message::Text(text) => {
+                    let mut server = self.server.lock().unwrap();
+                    let response = server
+                        .chat(text.as_bytes())
+                        .unwrap_or_else(|e| Response::error(500, format!("{:#}", e)));
+                    if !self.args.quiet {
+                        println!("🌐 WebSocket message → {}", response.status);
+                    }
+                    websocket::write_text(&mut *writer.lock().unwrap(), &final_event(response).to_string())?;
+                    server.maintain();
+                }
+                Message::Close => {
+                    websocket::write_close(&mut *writer.lock().unwrap())?;
+                    return Ok(());
+                }
+            }
+        }
+    }
+}
+
+impl Server<'_> {
+    /// Принимает соединения на `addr`, пока процесс не остановят; каждое - в своём потоке
+    pub fn run(self, addr: &str) -> Result<()> {
+        let listener = TcpListener::bind(addr).with_context(|| format!("Failed to listen on {}", addr))?;
+        println!("🌐 HTTP API: http://{}", listener.local_addr()?);
+
+        let shared = Shared {
+            health: self.health(),
+            args: self.args,
+            socket_open: AtomicBool::new(false),
+            server: Mutex::new(self),
+        };
+        std::thread::scope(|scope| {
+            for stream in listener.incoming() {
+                match stream {
+                    Ok(stream) => {
+                        let shared = &shared;
+                        scope.spawn(move || shared.connection(stream));
+                    }
+                    Err(e) => eprintln!("WARNING: Failed to accept connection: {}", e),
+                }
+            }
+        });
+        Ok(())
+    }
+
+    /// Обслуживание памяти, отложенное расписанием до конца запроса
+    fn maintain(&mut self) {
+        run_pending_maintenance(&mut self.dialogue, &self.semantic, &self.persistence, &self.embedder, self.args);
+    }
+
+    fn handle(&mut self, request: &Request) -> Response {
+        let result = match route(&request.method, &request.path) {
+            Some(Route::Health) => Ok(Response::ok(self.health())),
+            Some(Route::Chat) => {
+                let _silence = events::silence();
+                self.chat(&request.body)
+            }
+            Some(Route::ListSessions) => Ok(self.list_sessions()),
+            Some(Route::NewSession) => Ok(self.new_session()),
+            Some(Route::GetSession(id)) => self.get_session(id),
+            Some(Route::ResumeSession(id)) => self.resume_session(id),
+            Some(Route::DeleteSession(id)) => self.delete_session(id),
+            Some(Route::SearchMemory) => self.search_memory(&request.query),
+            Some(Route::Metrics) => Ok(Response::metrics()),
+            Some(Route::Socket) => Ok(Response::error(426, "Expected a WebSocket upgrade")),
+            None => Ok(Response::error(404, format!("No route for {} {}", request.method, request.path))),
+        };
+        result.unwrap_or_else(|e| Response::error(500, format!("{:#}", e)))
+    }
+
+    fn health(&self) -> Value {
+        json!({
+            "status": "ok",
+            "persona": self.persona.as_ref().map(|p| p.name.as_str()),
+            "archetype": self.persona.as_ref().map(|p| p.archetype_id.as_str()),
+            "memory": self.dialogue.is_some(),
+            "semantic": self.semantic.is_some(),
+        })
+    }
+
+    fn chat(&mut self, body: &[u8]) -> Result<Response> {
+        let request: ChatRequest = match serde_json::from_slice(body) {
+            Ok(request) => request,
+            Err(e) => return Ok(Response::error(400, format!("Bad chat request: {}", e))),
+        };
+        let message = request.message.trim();
+        if message.is_empty() {
+            return Ok(Response::error(400, "Empty message"));
+        }
+        let user = match request.user.as_deref().map(UserId::new) {
+            None => self.args.user.clone(),
+            Some(Ok(user)) => user,
+            Some(Err(e)) => return Ok(Response::error(400, e)),
+        };
+        if let Some(ref id) = request.session_id {
+            let Some(dm) = self.dialogue.as_mut() else {
+                return Ok(Response::memory_disabled());
+            };
+            if !dm.current_session().id.to_string().starts_with(&id.to_lowercase()) {
+                if let Err(e) = resume_past_session(dm, &self.persistence, id) {
+                    return Ok(Response::error(404, e));
+                }
+            }
+        }
+
+        self.pipeline.lock().unwrap().clear_cache();
+        let reply = with_quota_user(user, || {
+            process_query(
+                message,
+                &self.pipeline,
+                &mut self.dialogue,
+                &mut self.semantic,
+                &self.persistence,
+                &self.embedder,
+                self.args,
+                &mut self.persona,
+            )
+        })?;
+        Ok(Response::ok(json!({
+            "reply": reply,
+            "persona": self.persona.as_ref().map(|p| p.name.as_str()),
+            "session_id": self.dialogue.as_ref().map(|dm| dm.current_session().id.to_string()),
+            "turns": self.dialogue.as_ref().map(|dm| dm.current_session().turn_count()),
+        })))
+    }
+
+    fn list_sessions(&self) -> Response {
+        let Some(dm) = self.dialogue.as_ref() else {
+            return Response::memory_disabled();
+        };
+        let current = dm.current_session();
+        let mut history: Vec<&Session> = dm.session_history().values().filter(|s| s.id != current.id).collect();
+        history.sort_by_key(|s| std::cmp::Reverse(s.updated_at));
+
+        let sessions: Vec<Value> = std::iter::once(session_summary(current, true))
+            .chain(history.into_iter().map(|s| session_summary(s, false)))
+            .collect();
+        Response::ok(json!({
+            "sessions": sessions,
+            "on_disk": dm.deferred_sessions().len(),
+        }))
+    }
+
+    fn new_session(&mut self) -> Response {
+        let persona_name = self
+            .persona
+            .as_ref()
+            .map_or_else(|| self.args.archetype.clone(), |p| p.archetype_id.clone());
+        let Some(dm) = self.dialogue.as_mut() else {
+            return Response::memory_disabled();
+        };
+        let id = dm.start_new_session(persona_name);
+        Response::ok(json!({ "session_id": id.to_string() }))
+    }
+
+    fn get_session(&mut self, id: &str) -> Result<Response> {
+        let Some(dm) = self.dialogue.as_mut() else {
+            return Ok(Response::memory_disabled());
+        };
+        let Some(session_id) = dm.find_session_id(id)? else {
+            return Ok(Response::error(404, format!("Session '{}' not found", id)));
+        };
+        load_deferred_sessions(dm, &self.persistence, |d, _| *d == session_id);
+
+        let current = dm.current_session();
+        let session = if current.id == session_id {
+            current
+        } else {
+            match dm.session_history().get(&session_id) {
+                Some(session) => session,
+                None => return Ok(Response::error(404, format!("Session {} is not loaded", session_id))),
+            }
+        };
+        let turns: Vec<Value> = session
+            .turns
+            .iter()
+            .map(|t| {
+                json!({
+                    "user": t.user,
+                    "assistant": t.assistant,
+                    "timestamp": t.timestamp.to_rfc3339(),
+                })
+            })
+            .collect();
+        let mut body = session_summary(session, current.id == session_id);
+        body["turns"] = Value::Array(turns);
+        Ok(Response::ok(body))
+    }
+
+    fn resume_session(&mut self, id: &str) -> Result<Response> {
+        let Some(dm) = self.dialogue.as_mut() else {
+            return Ok(Response::memory_disabled());
+        };
+        if let Err(e) = resume_past_session(dm, &self.persistence, id) {
+            return Ok(Response::error(404, e));
+        }
+        Ok(Response::ok(session_summary(dm.current_session(), true)))
+    }
+
+    fn delete_session(&mut self, id: &str) -> Result<Response> {
+        let Some(dm) = self.dialogue.as_mut() else {
+            return Ok(Response::memory_disabled());
+        };
+        let Some(session_id) = dm.find_session_id(id)? else {
+            return Ok(Response::error(404, format!("Session '{}' not found", id)));
+        };
+        if dm.current_session().id == session_id {
+            return Ok(Response::error(400, "Cannot delete the current session, start a new one first"));
+        }
+        load_deferred_sessions(dm, &self.persistence, |d, _| *d == session_id);
+        if !dm.delete_session(session_id) {
+            return Ok(Response::error(404, format!("Session {} is not loaded", session_id)));
+        }
+        self.persistence
+            .save_with_embeddings_blocking(dm, self.embedder.embedding_dim())
+            .context("Failed to save memory")?;
+        Ok(Response::ok(json!({ "deleted": session_id.to_string() })))
+    }
+
+    fn search_memory(&mut self, query: &HashMap<String, String>) -> Result<Response> {
+        let Some(text) = query.get("q").map(|q| q.trim()).filter(|q| !q.is_empty()) else {
+            return Ok(Response::error(400, "Missing query parameter q"));
+        };
+        let k = match query.get("k").map(|k| k.parse::<usize>()) {
+            None => DEFAULT_SEARCH_K,
+            Some(Ok(k)) => k.clamp(1, MAX_SEARCH_K),
+            Some(Err(_)) => return Ok(Response::error(400, "k must be a number")),
+        };
+
+        let dialogues = match self.dialogue.as_mut() {
+            Some(dm) => dm.find_similar_dialogues_blocking(text, k)?,
+            None => Vec::new(),
+        };
+        let concepts: Vec<Value> = match self.semantic.as_ref() {
+            Some(sm) => sm
+                .lock()
+                .unwrap()
+                .search_by_text_blocking(text, k)
+                .into_iter()
+                .map(|(score, concept)| {
+                    json!({
+                        "id": concept.id.to_string(),
+                        "text": concept.text,
+                        "category": concept.category,
+                        "confidence": concept.confidence,
+                        "score": score,
+                    })
+                })
+                .collect(),
+            None => Vec::new(),
+        };
+        Ok(Response::ok(json!({ "dialogues": dialogues, "concepts": concepts })))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_request_parsing_and_routes() {
+        let raw = "POST /v1/chat HTTP/1.1\r\nHost: x\r\ncontent-length: 20\r\n\r\n{\"message\": \"hi\"}   ";
+        let request = read_request(raw.as_bytes()).unwrap();
+        assert_eq!(route(&request.method, &request.path), Some(Route::Chat));
+        assert_eq!(request.headers["host"], "x");
+        let chat: ChatRequest = serde_json::from_slice(&request.body).unwrap();
+        assert_eq!(chat.message, "hi");
+        assert!(chat.session_id.is_none() && chat.user.is_none());
+        let chat: ChatRequest = serde_json::from_str(r#"{"message": "hi", "user": "alice"}"#).unwrap();
+        assert_eq!(chat.user.as_deref(), Some("alice"));
+
+        let raw = "GET /v1/memory/search?q=%D1%87%D0%B0%D0%B9+rust&k=3 HTTP/1.1\r\n\r\n";
+        let request = read_request(raw.as_bytes()).unwrap();
+        assert_eq!(route(&request.method, &request.path), Some(Route::SearchMemory));
+        assert_eq!(request.query["q"], "чай rust");
+        assert_eq!(request.query["k"], "3");
+        assert_eq!(percent_decode("100%"), "100%");
+
+        assert_eq!(route("GET", "/v1/sessions/1a2b"), Some(Route::GetSession("1a2b")));
+        assert_eq!(route("POST", "/v1/sessions/1a2b/resume"), Some(Route::ResumeSession("1a2b")));
+        assert_eq!(route("DELETE", "/v1/sessions/1a2b/"), Some(Route::DeleteSession("1a2b")));
+        assert_eq!(route("GET", "/v1/chat"), None);
+        assert_eq!(route("GET", "/metrics"), Some(Route::Metrics));
+        assert_eq!(route("GET", "/v1/ws"), Some(Route::Socket));
+        let mut out = Vec::new();
+        write_response(&mut out, &Response::metrics()).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.contains("Content-Type: text/plain") && out.contains("ziggurat_requests_total"));
+
+        let raw = format!("POST /v1/chat HTTP/1.1\r\nContent-Length: {}\r\n\r\n", MAX_BODY_BYTES + 1);
+        assert!(read_request(raw.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn test_oversized_requests_are_rejected() {
+        let status = |raw: String| {
+            let error = read_request(raw.as_bytes()).unwrap_err();
+            error.downcast_ref::<Rejected>().map(|r| r.status)
+        };
+        assert_eq!(status(format!("GET /{} HTTP/1.1\r\n\r\n", "a".repeat(MAX_LINE_BYTES))), Some(400));
+        assert_eq!(status(format!("GET / HTTP/1.1\r\nX-Big: {}\r\n\r\n", "a".repeat(MAX_LINE_BYTES))), Some(431));
+        assert_eq!(status(format!("GET / HTTP/1.1\r\n{}\r\n", "X-A: 1\r\n".repeat(MAX_HEADERS + 1))), Some(431));
+
+        let request = read_request(format!("GET /health HTTP/1.1\r\n{}\r\n", "X-A: 1\r\n".repeat(MAX_HEADERS)).as_bytes());
+        assert_eq!(request.unwrap().path, "/health");
+    }
+}
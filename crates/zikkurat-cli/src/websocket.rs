@@ -0,0 +1,254 @@
+//! 🔌 WebSocket (RFC 6455) для `--serve`
+//!
+//! Ровно столько протокола, сколько нужно живому чату: рукопожатие по
+//! `Sec-WebSocket-Key`, текстовые сообщения (в том числе из нескольких фреймов),
+//! ответ на ping прямо при чтении и закрытие. Расширения и подпротоколы не
+//! поддерживаются, бинарные сообщения отвергаются. SHA-1 и base64 для ключа
+//! рукопожатия написаны здесь же, как и разбор HTTP в `server.rs`: ради одной
+//! строки не нужны зависимости.
+
+use anyhow::{bail, ensure, Result};
+use std::io::{Read, Write};
+
+/// Дописывается к ключу клиента перед хешированием (RFC 6455, 1.3)
+const ACCEPT_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+/// Предел сообщения, как у тела HTTP-запроса
+pub const MAX_MESSAGE_BYTES: usize = 1 << 20;
+
+const OP_CONTINUATION: u8 = 0x0;
+const OP_TEXT: u8 = 0x1;
+const OP_BINARY: u8 = 0x2;
+const OP_CLOSE: u8 = 0x8;
+const OP_PING: u8 = 0x9;
+const OP_PONG: u8 = 0xA;
+
+/// Сообщение клиента
+#[derive(Debug, PartialEq)]
+pub enum Message {
+    Text(String),
+    Close,
+}
+
+/// Ответ `101 Switching Protocols` на запрос с ключом `key`
+pub fn handshake_response(key: &str) -> String {
+    format!(
+        "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Accept: {}\r\n\r\n",
+        accept_key(key)
+    )
+}
+
+/// `Sec-WebSocket-Accept`: base64(SHA-1(ключ + GUID))
+pub fn accept_key(key: &str) -> String {
+    base64(&sha1(format!("{}{}", key.trim(), ACCEPT_GUID).as_bytes()))
+}
+
+/// Читает фреймы до целого сообщения. На ping отвечает `pong` сразу, в том числе
+/// между фрагментами сообщения; pong клиента пропускается
+pub fn read_message(
+    reader: &mut impl Read,
+    mut pong: impl FnMut(&[u8]) -> std::io::Result<()>,
+) -> Result<Message> {
+    let mut text = Vec::new();
+    let mut in_text = false;
+    loop {
+        let mut head = [0u8; 2];
+        reader.read_exact(&mut head)?;
+        let fin = head[0] & 0x80 != 0;
+        let opcode = head[0] & 0x0F;
+        ensure!(head[1] & 0x80 != 0, "Client frames must be masked");
+        let len = match head[1] & 0x7F {
+            126 => {
+                let mut len = [0u8; 2];
+                reader.read_exact(&mut len)?;
+                u16::from_be_bytes(len) as usize
+            }
+            127 => {
+                let mut len = [0u8; 8];
+                reader.read_exact(&mut len)?;
+                usize::try_from(u64::from_be_bytes(len)).unwrap_or(usize::MAX)
+            }
+            len => len as usize,
+        };
+        ensure!(text.len().saturating_add(len) <= MAX_MESSAGE_BYTES, "Message over {} bytes", MAX_MESSAGE_BYTES);
+        let mut mask = [0u8; 4];
+        reader.read_exact(&mut mask)?;
+        let mut payload = vec![0; len];
+        reader.read_exact(&mut payload)?;
+        for (i, byte) in payload.iter_mut().enumerate() {
+            *byte ^= mask[i % 4];
+        }
+
+        match opcode {
+            OP_TEXT if !in_text => {
+                text = payload;
+                in_text = true;
+            }
+            OP_CONTINUATION if in_text => text.extend_from_slice(&payload),
+            OP_PING => {
+                pong(&payload)?;
+                continue;
+            }
+            OP_PONG => continue,
+            OP_CLOSE => return Ok(Message::Close),
+            OP_BINARY => bail!("Binary messages are not supported"),
+            _ => bail!("Unexpected frame (opcode {:#x})", opcode),
+        }
+        if fin {
+            return Ok(Message::Text(String::from_utf8(text)?));
+        }
+    }
+}
+
+pub fn write_text(writer: &mut impl Write, text: &str) -> std::io::Result<()> {
+    write_frame(writer, OP_TEXT, text.as_bytes())
+}
+
+pub fn write_pong(writer: &mut impl Write, payload: &[u8]) -> std::io::Result<()> {
+    write_frame(writer, OP_PONG, payload)
+}
+
+pub fn write_close(writer: &mut impl Write) -> std::io::Result<()> {
+    write_frame(writer, OP_CLOSE, &[])
+}
+
+/// Один фрейм сервера: без маски и без фрагментации
+fn write_frame(writer: &mut impl Write, opcode: u8, payload: &[u8]) -> std::io::Result<()> {
+    let mut frame = Vec::with_capacity(payload.len() + 10);
+    frame.push(0x80 | opcode);
+    match payload.len() {
+        len @ 0..=125 => frame.push(len as u8),
+        len @ 126..=0xFFFF => {
+            frame.push(126);
+            frame.extend_from_slice(&(len as u16).to_be_bytes());
+        }
+        len => {
+            frame.push(127);
+            frame.extend_from_slice(&(len as u64).to_be_bytes());
+        }
+    }
+    frame.extend_from_slice(payload);
+    writer.write_all(&frame)?;
+    writer.flush()
+}
+
+fn sha1(data: &[u8]) -> [u8; 20] {
+    let mut h: [u32; 5] = [0x67452301, 0xEFCDAB89, 0x98BADCFE, 0x10325476, 0xC3D2E1F0];
+    let mut message = data.to_vec();
+    message.push(0x80);
+    while message.len() % 64 != 56 {
+        message.push(0);
+    }
+    message.extend_from_slice(&((data.len() as u64) * 8).to_be_bytes());
+
+    for block in message.chunks(64) {
+        let mut w = [0u32; 80];
+        for (i, word) in block.chunks(4).enumerate() {
+            w[i] = u32::from_be_bytes([word[0], word[1], word[2], word[3]]);
+        }
+        for i in 16..80 {
+            w[i] = (w[i - 3] ^ w[i - 8] ^ w[i - 14] ^ w[i - 16]).rotate_left(1);
+        }
+        let [mut a, mut b, mut c, mut d, mut e] = h;
+        for (i, word) in w.iter().enumerate() {
+            let (f, k) = match i {
+                0..=19 => ((b & c) | (!b & d), 0x5A827999),
+                20..=39 => (b ^ c ^ d, 0x6ED9EBA1),
+                40..=59 => ((b & c) | (b & d) | (c & d), 0x8F1BBCDC),
+                _ => (b ^ c ^ d, 0xCA62C1D6),
+            };
+            let temp = a.rotate_left(5).wrapping_add(f).wrapping_add(e).wrapping_add(k).wrapping_add(*word);
+            e = d;
+            d = c;
+            c = b.rotate_left(30);
+            b = a;
+            a = temp;
+        }
+        for (state, value) in h.iter_mut().zip([a, b, c, d, e]) {
+            *state = state.wrapping_add(value);
+        }
+    }
+
+    let mut digest = [0u8; 20];
+    for (chunk, state) in digest.chunks_mut(4).zip(h) {
+        chunk.copy_from_slice(&state.to_be_bytes());
+    }
+    digest
+}
+
+fn base64(data: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let bytes = [chunk[0], *chunk.get(1).unwrap_or(&0), *chunk.get(2).unwrap_or(&0)];
+        let n = u32::from_be_bytes([0, bytes[0], bytes[1], bytes[2]]);
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[(n >> (18 - 6 * i) & 0x3F) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Фрейм клиента: с маской, как шлёт браузер
+    fn client_frame(fin: bool, opcode: u8, payload: &[u8]) -> Vec<u8> {
+        let mask = [0x37, 0xFA, 0x21, 0x3D];
+        let mut frame = vec![if fin { 0x80 | opcode } else { opcode }];
+        if payload.len() < 126 {
+            frame.push(0x80 | payload.len() as u8);
+        } else {
+            frame.push(0x80 | 126);
+            frame.extend_from_slice(&(payload.len() as u16).to_be_bytes());
+        }
+        frame.extend_from_slice(&mask);
+        frame.extend(payload.iter().enumerate().map(|(i, b)| b ^ mask[i % 4]));
+        frame
+    }
+
+    #[test]
+    fn test_handshake_and_frames() {
+        // пример из RFC 6455
+        assert_eq!(accept_key("dGhlIHNhbXBsZSBub25jZQ=="), "s3pPLMBiTxaQ9kYGzzhZRbK+xOo=");
+        assert_eq!(base64(b"ab"), "YWI=");
+        assert!(handshake_response("dGhlIHNhbXBsZSBub25jZQ==").starts_with("HTTP/1.1 101"));
+
+        let long = "чай ".repeat(40);
+        let mut stream = client_frame(false, OP_TEXT, "Привет, ".as_bytes());
+        stream.extend(client_frame(true, OP_PONG, b""));
+        stream.extend(client_frame(true, OP_PING, b"mid"));
+        stream.extend(client_frame(true, OP_CONTINUATION, "мир".as_bytes()));
+        stream.extend(client_frame(true, OP_PING, b"hb"));
+        stream.extend(client_frame(true, OP_TEXT, long.as_bytes()));
+        stream.extend(client_frame(true, OP_CLOSE, b""));
+        let mut reader = stream.as_slice();
+        let mut pings = Vec::new();
+        let mut read = || {
+            read_message(&mut reader, |payload| {
+                pings.push(payload.to_vec());
+                Ok(())
+            })
+            .unwrap()
+        };
+        // ping посреди фрагментов не теряет начало сообщения
+        assert_eq!(read(), Message::Text("Привет, мир".to_string()));
+        assert_eq!(read(), Message::Text(long.clone()));
+        assert_eq!(read(), Message::Close);
+        assert_eq!(pings, [b"mid".to_vec(), b"hb".to_vec()]);
+
+        let mut out = Vec::new();
+        write_text(&mut out, &long).unwrap();
+        assert_eq!(out[..2], [0x81, 126]);
+        assert_eq!(out[2..4], (long.len() as u16).to_be_bytes());
+        assert_eq!(&out[4..], long.as_bytes());
+
+        // без маски и бинарные сообщения отвергаются
+        assert!(read_message(&mut [0x81u8, 0x00].as_slice(), |_| Ok(())).is_err());
+        assert!(read_message(&mut client_frame(true, OP_BINARY, b"x").as_slice(), |_| Ok(())).is_err());
+    }
+}
@@ -0,0 +1,166 @@
+//! 🧪 REPL в профиле `--ci`
+//!
+//! `cargo test -p zikkurat-cli --features ci` запускает бинарник на echo-модели и
+//! dummy-эмбеддингах, с памятью во временном каталоге и стоящими часами. Все
+//! команды из `/help` выполняются без моделей и без терминала, а память
+//! переживает перезапуск - так изменения логики памяти и персоны проверяются
+//! без скачивания весов.
+
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+/// Прогон дольше - завис (например, команда ждёт ввода, которого нет)
+const RUN_TIMEOUT: Duration = Duration::from_secs(180);
+
+/// Команды, которым без аргументов нужен терминал: вызываются с аргументами
+const INVOCATIONS: &[(&str, &str)] = &[("persona switch", "/persona switch programmer --keep-session --carry")];
+
+/// Рабочий каталог теста, как у сценариев: память, `data/` персоны и копия
+/// `config/archetypes` - всё внутри, репозиторий не трогается
+fn sandbox(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("zikkurat-ci-test-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let archetypes = dir.join("config/archetypes");
+    std::fs::create_dir_all(&archetypes).unwrap();
+    let shipped = Path::new(env!("CARGO_MANIFEST_DIR")).join("../../config/archetypes");
+    for entry in std::fs::read_dir(shipped).unwrap() {
+        let path = entry.unwrap().path();
+        if path.is_file() {
+            std::fs::copy(&path, archetypes.join(path.file_name().unwrap())).unwrap();
+        }
+    }
+    dir
+}
+
+/// Запускает REPL в каталоге `dir`, подаёт строки `input` и возвращает stdout и
+/// stderr вместе. Конец ввода завершает сессию, как `exit`
+fn run(dir: &Path, input: &[String]) -> String {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_ziggurat-unified"))
+        .args(["--ci", "--interactive", "--enable-memory", "--enable-semantic", "--data-dir"])
+        .arg(dir)
+        .current_dir(dir)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("failed to start ziggurat-unified");
+
+    let mut stdin = child.stdin.take().unwrap();
+    stdin.write_all(input.join("\n").as_bytes()).unwrap();
+    drop(stdin);
+
+    // читаем в потоках, иначе заполненный pipe остановит процесс
+    let mut readers: Vec<_> = [
+        Box::new(child.stdout.take().unwrap()) as Box<dyn Read + Send>,
+        Box::new(child.stderr.take().unwrap()),
+    ]
+    .into_iter()
+    .map(|mut pipe| {
+        std::thread::spawn(move || {
+            let mut text = String::new();
+            let _ = pipe.read_to_string(&mut text);
+            text
+        })
+    })
+    .collect();
+
+    let started = Instant::now();
+    let status = loop {
+        if let Some(status) = child.try_wait().unwrap() {
+            break status;
+        }
+        if started.elapsed() > RUN_TIMEOUT {
+            let _ = child.kill();
+            panic!("ziggurat-unified did not finish in {}s", RUN_TIMEOUT.as_secs());
+        }
+        std::thread::sleep(Duration::from_millis(50));
+    };
+    let output: String = readers.drain(..).map(|r| r.join().unwrap()).collect::<Vec<_>>().join("\n");
+    assert!(status.success(), "ziggurat-unified failed ({}):\n{}", status, output);
+    assert!(!output.contains("panicked"), "ziggurat-unified panicked:\n{}", output);
+    output
+}
+
+fn lines(input: &[&str]) -> Vec<String> {
+    input.iter().map(|s| s.to_string()).collect()
+}
+
+/// Команды и подкоманды из вывода `/help`: `/semantic [list|get|...]  Manage ...`
+fn help_commands(help: &str) -> Vec<(String, Vec<String>)> {
+    help.lines()
+        .filter_map(|line| {
+            let mut words = line.trim_start().strip_prefix('/')?.split_whitespace();
+            let name = words.next()?.to_string();
+            let subcommands = words
+                .next()
+                .and_then(|w| w.strip_prefix('[')?.strip_suffix(']'))
+                .filter(|list| list.contains('|') && list.chars().all(|c| c.is_ascii_lowercase() || c == '|'))
+                .map(|list| list.split('|').map(str::to_string).collect())
+                .unwrap_or_default();
+            Some((name, subcommands))
+        })
+        .collect()
+}
+
+#[test]
+fn test_every_repl_command() {
+    let dir = sandbox("commands");
+    let help = run(&dir, &lines(&["/help"]));
+    let commands = help_commands(&help);
+    assert!(commands.len() >= 10, "too few commands in /help:\n{}", help);
+
+    let mut script = lines(&["Меня зовут Анна", "Я люблю зелёный чай"]);
+    for (name, subcommands) in &commands {
+        script.push(format!("/{}", name));
+        for sub in subcommands {
+            let key = format!("{} {}", name, sub);
+            match INVOCATIONS.iter().find(|(k, _)| *k == key) {
+                Some((_, line)) => script.push(line.to_string()),
+                None => script.push(format!("/{}", key)),
+            }
+            // выключенная память мешала бы следующим командам
+            if sub == "off" {
+                script.push(format!("/{} on", name));
+            }
+        }
+    }
+    let output = run(&dir, &script);
+
+    assert!(output.contains("👋 Goodbye!"), "session did not end normally:\n{}", output);
+    assert!(!output.contains("Unknown command"), "a /help command is not handled:\n{}", output);
+    assert!(!output.contains("Unknown subcommand"), "a /help subcommand is not handled:\n{}", output);
+    let prompts = output.matches("📝 You:").count();
+    assert!(prompts >= script.len(), "{} prompts for {} input lines:\n{}", prompts, script.len(), output);
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn test_memory_survives_restart() {
+    let dir = sandbox("restart");
+    let first = run(&dir, &lines(&["Я люблю зелёный чай", "/bookmark про чай", "exit"]));
+    assert!(first.contains("clock at 2024-01-01"), "not the CI profile:\n{}", first);
+    assert!(dir.join("memory_data").exists(), "memory was not written to {}", dir.display());
+
+    let second = run(&dir, &lines(&["/bookmarks", "/semantic list"]));
+    assert!(second.contains("про чай"), "bookmark lost after restart:\n{}", second);
+    assert!(second.to_lowercase().contains("зелёный чай"), "concept lost after restart:\n{}", second);
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn test_users_keep_separate_memory() {
+    let dir = sandbox("users");
+    let script = ["Я люблю зелёный чай", "/bookmark про чай", "/user anna", "Я люблю кофе", "/bookmark про кофе"];
+    run(&dir, &lines(&script));
+    assert!(dir.join("users/anna/memory_data").exists(), "anna's memory is not in users/anna");
+
+    let output = run(&dir, &lines(&["/bookmarks", "/user anna", "/bookmarks"]));
+    let (default, anna) = output
+        .split_once("👤 Switched to user anna")
+        .unwrap_or_else(|| panic!("/user did not switch:\n{}", output));
+    assert!(default.contains("про чай") && !default.contains("про кофе"), "default user's bookmarks:\n{}", default);
+    assert!(anna.contains("про кофе") && !anna.contains("про чай"), "anna's bookmarks:\n{}", anna);
+    let _ = std::fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,167 @@
+//! Address Style Module
+//!
+//! Tracks whether the user addresses the persona with "Вы" or "ты".
+//! The style is kept across the session with hysteresis, so a single
+//! ambiguous message doesn't flip it, and can be pinned with `/address`.
+
+use serde::{Deserialize, Serialize};
+
+/// Consecutive contrary messages needed to switch an established style
+const SWITCH_THRESHOLD: u8 = 2;
+
+const FORMAL_MARKERS: &[&str] = &[
+    "вы", "вас", "вам", "вами", "ваш", "ваша", "ваше", "ваши", "вашего", "вашей", "вашим",
+    "скажите", "подскажите", "расскажите", "объясните", "помогите", "посоветуйте", "можете",
+];
+
+const INFORMAL_MARKERS: &[&str] = &[
+    "ты", "тебя", "тебе", "тобой", "твой", "твоя", "твоё", "твое", "твои", "твоего", "твоей",
+    "скажи", "подскажи", "расскажи", "объясни", "помоги", "посоветуй", "можешь",
+];
+
+/// How the user addresses the persona
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AddressStyle {
+    Formal,
+    Informal,
+}
+
+impl AddressStyle {
+    /// Detects the address style of a single message, `None` if there is no clear signal
+    pub fn detect(text: &str) -> Option<Self> {
+        let lower = text.to_lowercase();
+        let words: Vec<&str> = lower
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|w| !w.is_empty())
+            .collect();
+
+        let formal = words.iter().any(|w| FORMAL_MARKERS.contains(w));
+        let informal = words.iter().any(|w| INFORMAL_MARKERS.contains(w));
+
+        match (formal, informal) {
+            (true, false) => Some(AddressStyle::Formal),
+            (false, true) => Some(AddressStyle::Informal),
+            _ => None,
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.to_lowercase().as_str() {
+            "formal" | "вы" => Some(AddressStyle::Formal),
+            "informal" | "ты" => Some(AddressStyle::Informal),
+            _ => None,
+        }
+    }
+
+    /// Prompt constraint for this style
+    pub fn constraint(&self) -> &'static str {
+        match self {
+            AddressStyle::Formal => "Обращаться на Вы",
+            AddressStyle::Informal => "Обращаться на ты",
+        }
+    }
+}
+
+impl std::fmt::Display for AddressStyle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AddressStyle::Formal => write!(f, "formal (Вы)"),
+            AddressStyle::Informal => write!(f, "informal (ты)"),
+        }
+    }
+}
+
+/// User's address style, persisted in the session context
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct AddressTracker {
+    /// Style established from the user's messages
+    pub observed: Option<AddressStyle>,
+    /// Explicit choice from `/address`, wins over the observed style
+    pub pinned: Option<AddressStyle>,
+    /// Contrary style seen in the last messages and how many times in a row
+    #[serde(default)]
+    pending: Option<(AddressStyle, u8)>,
+}
+
+impl AddressTracker {
+    /// Feeds a user message. Returns true if the observed style changed
+    pub fn observe(&mut self, text: &str) -> bool {
+        let Some(signal) = AddressStyle::detect(text) else {
+            return false;
+        };
+
+        match self.observed {
+            None => {
+                self.observed = Some(signal);
+                true
+            }
+            Some(current) if current == signal => {
+                self.pending = None;
+                false
+            }
+            Some(_) => {
+                let count = match self.pending {
+                    Some((style, count)) if style == signal => count + 1,
+                    _ => 1,
+                };
+                if count >= SWITCH_THRESHOLD {
+                    self.observed = Some(signal);
+                    self.pending = None;
+                    true
+                } else {
+                    self.pending = Some((signal, count));
+                    false
+                }
+            }
+        }
+    }
+
+    /// Pins the style (`None` returns to automatic tracking)
+    pub fn pin(&mut self, style: Option<AddressStyle>) {
+        self.pinned = style;
+    }
+
+    /// Style to use: pinned, then observed, then the persona default
+    pub fn resolve(&self, persona_uses_honorifics: bool) -> AddressStyle {
+        self.pinned.or(self.observed).unwrap_or(if persona_uses_honorifics {
+            AddressStyle::Formal
+        } else {
+            AddressStyle::Informal
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_address_hysteresis() {
+        let mut tracker = AddressTracker::default();
+        assert!(tracker.observe("Скажите, как работает borrow checker?"));
+        assert_eq!(tracker.resolve(false), AddressStyle::Formal);
+
+        // a message without pronouns or a single slip keeps the style
+        assert!(!tracker.observe("А lifetimes?"));
+        assert!(!tracker.observe("ты уверен?"));
+        assert_eq!(tracker.resolve(false), AddressStyle::Formal);
+
+        // a formal message resets the streak
+        assert!(!tracker.observe("Спасибо вам"));
+        assert!(!tracker.observe("ты уверен?"));
+        assert!(tracker.observe("а ты можешь проще?"));
+        assert_eq!(tracker.resolve(true), AddressStyle::Informal);
+
+        tracker.pin(Some(AddressStyle::Formal));
+        assert_eq!(tracker.resolve(false), AddressStyle::Formal);
+    }
+
+    #[test]
+    fn test_detect_address() {
+        assert_eq!(AddressStyle::detect("Вы знаете Rust?"), Some(AddressStyle::Formal));
+        assert_eq!(AddressStyle::detect("объясни trait objects"), Some(AddressStyle::Informal));
+        // "вы" inside other words is not a signal
+        assert_eq!(AddressStyle::detect("выход из цикла"), None);
+    }
+}
@@ -0,0 +1,588 @@
+//! Archetype System - Persona Templates
+//!
+//! Archetypes are JSON templates that define the base personality,
+//! communication style, directives, and evolution rules for a persona.
+
+use anyhow::{Error, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use super::sanitize::{sanitize_archetype, ArchetypeSource};
+use super::topics::ForbiddenTopic;
+use crate::totems::retrieval::RetrievalOverrides;
+use crate::totems::semantic::ConceptCategory;
+
+const ARCHETYPES_DIR: &str = "config/archetypes";
+
+/// Traits of [`BaseTraits`]; one left out of the file silently becomes 0.0
+pub const TRAIT_NAMES: &[&str] = &[
+    "analytical", "curious", "verbose", "patient", "humor", "empathy", "technical", "pedagogical",
+    "creative", "supportive", "skeptical", "formal",
+];
+/// `communication.style` values the prompt builder knows
+pub const COMMUNICATION_STYLES: &[&str] = &["technical", "casual", "formal", "warm", "academic", "socratic", "neutral"];
+/// `communication.emoji_frequency` values
+pub const EMOJI_FREQUENCIES: &[&str] = &["none", "rare", "moderate", "frequent"];
+/// `communication.max_response_length` values
+pub const RESPONSE_LENGTHS: &[&str] = &["short", "medium", "long"];
+
+fn resolve_project_path(rel_path: &str) -> String {
+    let exe_path = std::env::current_exe().unwrap_or(std::path::PathBuf::from("."));
+    let mut current = exe_path.as_path();
+
+    while let Some(parent) = current.parent() {
+        if parent.join("Cargo.toml").exists() {
+            return parent.join(rel_path).to_string_lossy().into_owned();
+        }
+        current = parent;
+    }
+
+    rel_path.to_string()
+}
+
+/// Main archetype structure loaded from JSON
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Archetype {
+    pub id: String,
+    pub name: String,
+    pub description: String,
+    pub base_traits: BaseTraits,
+    pub communication: CommunicationStyle,
+    pub directives: Vec<ArchetypeDirective>,
+    pub evolution_rules: EvolutionRules,
+    #[serde(default)]
+    pub memory_seeds: MemorySeeds,
+    /// LoRA adapter applied on top of the shared base model
+    #[serde(default)]
+    pub adapter: Option<AdapterConfig>,
+    /// Topics the persona declines to discuss
+    #[serde(default)]
+    pub forbidden_topics: Vec<ForbiddenTopic>,
+    /// Past conversations reach the prompt only when the closest stored turn is at
+    /// least this similar to the query; overrides `--memory-gate-threshold`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub memory_gate_threshold: Option<f32>,
+    /// Per-persona tweaks to the `retrieval` weights and thresholds of the system config
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retrieval: Option<RetrievalOverrides>,
+}
+
+/// LoRA/QLoRA adapter of the persona (PEFT directory with adapter_model.safetensors)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AdapterConfig {
+    /// Adapter directory, relative to the project root
+    pub path: String,
+    /// Strength multiplier, 1.0 = as trained
+    #[serde(default = "default_adapter_scale")]
+    pub scale: f64,
+}
+
+fn default_adapter_scale() -> f64 {
+    1.0
+}
+
+/// Base personality traits (0.0 - 1.0 scale)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BaseTraits {
+    #[serde(default)]
+    pub analytical: f32,
+    #[serde(default)]
+    pub curious: f32,
+    #[serde(default)]
+    pub verbose: f32,
+    #[serde(default)]
+    pub patient: f32,
+    #[serde(default)]
+    pub humor: f32,
+    #[serde(default)]
+    pub empathy: f32,
+    #[serde(default)]
+    pub technical: f32,
+    #[serde(default)]
+    pub pedagogical: f32,
+    #[serde(default)]
+    pub creative: f32,
+    #[serde(default)]
+    pub supportive: f32,
+    #[serde(default)]
+    pub skeptical: f32,
+    #[serde(default)]
+    pub formal: f32,
+}
+
+impl Default for BaseTraits {
+    fn default() -> Self {
+        Self {
+            analytical: 0.5,
+            curious: 0.5,
+            verbose: 0.5,
+            patient: 0.5,
+            humor: 0.5,
+            empathy: 0.5,
+            technical: 0.5,
+            pedagogical: 0.5,
+            creative: 0.5,
+            supportive: 0.5,
+            skeptical: 0.5,
+            formal: 0.5,
+        }
+    }
+}
+
+/// Communication style parameters
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CommunicationStyle {
+    pub style: String, // "technical", "casual", "formal", "warm", "socratic"
+    pub greeting: String,
+    #[serde(default)]
+    pub use_honorifics: bool, // Use "Вы" vs "ты"
+    #[serde(default)]
+    pub emoji_frequency: String, // "rare", "moderate", "frequent"
+    #[serde(default)]
+    pub max_response_length: String, // "short", "medium", "long"
+    #[serde(default)]
+    pub signature: String, // End-of-message signature
+}
+
+impl Default for CommunicationStyle {
+    fn default() -> Self {
+        Self {
+            style: "neutral".to_string(),
+            greeting: "Hello!".to_string(),
+            use_honorifics: false,
+            emoji_frequency: "rare".to_string(),
+            max_response_length: "medium".to_string(),
+            signature: String::new(),
+        }
+    }
+}
+
+/// Directive defined in archetype
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArchetypeDirective {
+    pub rule: String,
+    pub priority: u8,
+    #[serde(default)]
+    pub params: HashMap<String, serde_json::Value>,
+}
+
+/// Initial knowledge shipped with the archetype, applied on first use
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MemorySeeds {
+    #[serde(default)]
+    pub concepts: Vec<ConceptSeed>,
+    #[serde(default)]
+    pub narrative: Vec<NarrativeSeed>,
+}
+
+/// Semantic concept seeded as `KnowledgeSource::Predefined`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConceptSeed {
+    pub text: String,
+    #[serde(default = "default_seed_category")]
+    pub category: String, // "facts", "rules", "preferences", ...
+    #[serde(default = "default_seed_confidence")]
+    pub confidence: f32,
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+fn default_seed_category() -> String {
+    "rules".to_string()
+}
+
+fn default_seed_confidence() -> f32 {
+    0.9
+}
+
+/// Biography entry seeded into the persona narrative
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NarrativeSeed {
+    pub title: String,
+    pub content: String,
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+/// Evolution rules for trait changes
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EvolutionRules {
+    #[serde(default)]
+    pub trait_changes: HashMap<String, TraitChangeRule>,
+    #[serde(default)]
+    pub decay: HashMap<String, f32>,
+    #[serde(default)]
+    pub unlock_conditions: Vec<UnlockCondition>,
+}
+
+/// Rule for trait modification
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TraitChangeRule {
+    pub rate: f32,
+    #[serde(default)]
+    pub trigger: String,
+    #[serde(default)]
+    pub condition: String,
+}
+
+/// Condition for unlocking new traits
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UnlockCondition {
+    pub r#trait: String,
+    pub require: UnlockRequirements,
+    #[serde(default)]
+    pub description: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UnlockRequirements {
+    #[serde(default)]
+    pub interactions: u32,
+    #[serde(default)]
+    pub successful_help: u32,
+    #[serde(default)]
+    pub empathy_threshold: f32,
+    #[serde(default)]
+    pub relationship_arc_affection: f32,
+    #[serde(default)]
+    pub topics_covers: Vec<String>,
+}
+
+/// Result of [`ArchetypeLoader::validate`]
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ValidationReport {
+    /// Problems the archetype does not load with
+    pub errors: Vec<String>,
+    /// Likely mistakes the archetype still loads with
+    pub warnings: Vec<String>,
+}
+
+impl ValidationReport {
+    pub fn is_valid(&self) -> bool {
+        self.errors.is_empty()
+    }
+}
+
+/// Archetype file watched for edits, see [`ArchetypeLoader::watch`]
+#[derive(Debug)]
+pub struct ArchetypeWatcher {
+    path: PathBuf,
+    source: ArchetypeSource,
+    archetype_id: String,
+    modified: Option<SystemTime>,
+}
+
+impl ArchetypeWatcher {
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// ID of the archetype the file held at the last successful load
+    pub fn archetype_id(&self) -> &str {
+        &self.archetype_id
+    }
+
+    /// The archetype re-read from the file if it changed since the last poll. A broken
+    /// edit is reported once; the caller keeps the last good version until the next save
+    pub fn poll(&mut self) -> Option<Result<Archetype>> {
+        let modified = fs::metadata(&self.path).and_then(|m| m.modified()).ok();
+        if modified == self.modified {
+            return None;
+        }
+        self.modified = modified;
+        let result = ArchetypeLoader::load_from_path(&self.path, self.source);
+        if let Ok(ref archetype) = result {
+            self.archetype_id = archetype.id.clone();
+        }
+        Some(result)
+    }
+}
+
+/// Archetype loader from JSON files
+pub struct ArchetypeLoader;
+
+impl ArchetypeLoader {
+    /// Load archetype by ID (without .json extension) or by path to a shared `.json` file
+    pub fn load(archetype_id: &str) -> Result<Archetype> {
+        let (path, source) = Self::locate(archetype_id)?;
+        Self::load_from_path(path, source)
+    }
+
+    /// Watch the archetype file (ID or path, as for [`ArchetypeLoader::load`]) so edits
+    /// reach a running persona without a restart: [`ArchetypeWatcher::poll`] compares
+    /// the file's modification time and re-reads it after a change
+    pub fn watch(archetype_id: &str) -> Result<ArchetypeWatcher> {
+        let (path, source) = Self::locate(archetype_id)?;
+        let path = PathBuf::from(path);
+        let modified = fs::metadata(&path).and_then(|m| m.modified()).ok();
+        let archetype = Self::load_from_path(&path, source)?;
+        Ok(ArchetypeWatcher {
+            path,
+            source,
+            archetype_id: archetype.id,
+            modified,
+        })
+    }
+
+    /// Check an archetype file (ID or path) without loading it: errors that stop it from
+    /// loading plus warnings about missing traits, unknown communication styles, duplicate
+    /// directives and whatever sanitizing would change
+    pub fn validate(archetype_id: &str) -> Result<ValidationReport> {
+        let (path, source) = Self::locate(archetype_id)?;
+        let content = fs::read_to_string(&path)?;
+        Ok(Self::check(&content, source).1)
+    }
+
+    /// File of an archetype ID, or a path to a shared `.json` file
+    fn locate(archetype_id: &str) -> Result<(String, ArchetypeSource)> {
+        if archetype_id.ends_with(".json") {
+            Ok((archetype_id.to_string(), ArchetypeSource::External))
+        } else {
+            Ok((Self::get_archetype_path(archetype_id)?, ArchetypeSource::Bundled))
+        }
+    }
+
+    /// Load an archetype from outside `config/archetypes` (untrusted, strictly sanitized)
+    pub fn load_external(path: impl AsRef<Path>) -> Result<Archetype> {
+        Self::load_from_path(path, ArchetypeSource::External)
+    }
+
+    /// Load all available archetypes
+    pub fn load_all() -> Result<Vec<Archetype>> {
+        let mut archetypes = Vec::new();
+        let dir_path = resolve_project_path(ARCHETYPES_DIR);
+        let dir = Path::new(&dir_path);
+
+        if !dir.exists() {
+            return Ok(archetypes);
+        }
+
+        for entry in fs::read_dir(dir)? {
+            let entry = entry?;
+            let path = entry.path();
+
+            if path.extension().map(|e| e == "json").unwrap_or(false) {
+                if let Ok(archetype) = Self::load_from_path(&path, ArchetypeSource::Bundled) {
+                    archetypes.push(archetype);
+                }
+            }
+        }
+
+        Ok(archetypes)
+    }
+
+    /// Get path to archetype file
+    fn get_archetype_path(archetype_id: &str) -> Result<String> {
+        let dir_path = resolve_project_path(ARCHETYPES_DIR);
+        let path = format!("{}/{}.json", dir_path, archetype_id);
+
+        if Path::new(&path).exists() {
+            Ok(path)
+        } else {
+            Err(Error::msg(format!(
+                "Archetype '{}' not found at {}",
+                archetype_id, path
+            )))
+        }
+    }
+
+    /// Load archetype from file path
+    fn load_from_path(path: impl AsRef<Path>, source: ArchetypeSource) -> Result<Archetype> {
+        let content = fs::read_to_string(path.as_ref())?;
+        let (archetype, report) = Self::check(&content, source);
+        match archetype {
+            Some(archetype) if report.is_valid() => {
+                for warning in report.warnings {
+                    eprintln!("⚠️  Archetype '{}': {}", archetype.id, warning);
+                }
+                Ok(archetype)
+            }
+            _ => Err(Error::msg(format!(
+                "Archetype {:?} rejected: {}",
+                path.as_ref(),
+                report.errors.join("; ")
+            ))),
+        }
+    }
+
+    /// Parse and validate the archetype JSON. The archetype is returned sanitized, as it
+    /// goes into prompts; `None` when the JSON does not parse
+    fn check(content: &str, source: ArchetypeSource) -> (Option<Archetype>, ValidationReport) {
+        let mut report = ValidationReport::default();
+        let parsed = serde_json::from_str::<serde_json::Value>(content)
+            .and_then(|raw| Ok((serde_json::from_value::<Archetype>(raw.clone())?, raw)));
+        let (mut archetype, raw) = match parsed {
+            Ok(parsed) => parsed,
+            Err(e) => {
+                report.errors.push(format!("invalid archetype JSON: {}", e));
+                return (None, report);
+            }
+        };
+
+        if archetype.id.is_empty() {
+            report.errors.push("Archetype ID cannot be empty".to_string());
+        }
+        if archetype.name.is_empty() {
+            report.errors.push("Archetype name cannot be empty".to_string());
+        }
+        let traits = raw["base_traits"].as_object();
+        for name in TRAIT_NAMES {
+            match traits.and_then(|t| t.get(*name)).map(|v| v.as_f64()) {
+                None => report.warnings.push(format!("trait '{}' is missing and defaults to 0.0", name)),
+                Some(Some(value)) if (0.0..=1.0).contains(&value) => {}
+                Some(_) => report.errors.push(format!("trait '{}' must be between 0.0 and 1.0", name)),
+            }
+        }
+        for name in traits.into_iter().flat_map(|t| t.keys()) {
+            if !TRAIT_NAMES.contains(&name.as_str()) {
+                report.warnings.push(format!("unknown trait '{}' is ignored", name));
+            }
+        }
+        for seed in &archetype.memory_seeds.concepts {
+            if let Err(e) = seed.category.parse::<ConceptCategory>() {
+                report.errors.push(format!("Invalid memory seed '{}': {}", seed.text, e));
+            }
+        }
+
+        let communication = &archetype.communication;
+        let known = [
+            ("style", &communication.style, COMMUNICATION_STYLES),
+            ("emoji_frequency", &communication.emoji_frequency, EMOJI_FREQUENCIES),
+            ("max_response_length", &communication.max_response_length, RESPONSE_LENGTHS),
+        ];
+        for (field, value, allowed) in known {
+            if !value.is_empty() && !allowed.contains(&value.as_str()) {
+                report.warnings.push(format!(
+                    "communication.{} '{}' is not one of {}",
+                    field,
+                    value,
+                    allowed.join(", ")
+                ));
+            }
+        }
+
+        let mut seen = HashSet::new();
+        for directive in &archetype.directives {
+            if !seen.insert(directive.rule.as_str()) {
+                report.warnings.push(format!("directive '{}' is listed more than once", directive.rule));
+            }
+        }
+
+        // Archetype text goes straight into prompts
+        match sanitize_archetype(&mut archetype, source) {
+            Ok(changes) => report.warnings.extend(changes),
+            Err(e) => report.errors.push(e.to_string()),
+        }
+
+        (Some(archetype), report)
+    }
+
+    /// List available archetype IDs
+    pub fn list_ids() -> Result<Vec<String>> {
+        let mut ids = Vec::new();
+        let dir_path = resolve_project_path(ARCHETYPES_DIR);
+        let dir = Path::new(&dir_path);
+
+        if !dir.exists() {
+            return Ok(ids);
+        }
+
+        for entry in fs::read_dir(dir)? {
+            if let Ok(entry) = entry {
+                if let Some(name) = entry.file_name().to_str() {
+                    if name.ends_with(".json") {
+                        if let Some(id) = name.strip_suffix(".json") {
+                            ids.push(id.to_string());
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok(ids)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_base_traits_default() {
+        let traits = BaseTraits::default();
+        assert_eq!(traits.analytical, 0.5);
+        assert_eq!(traits.empathy, 0.5);
+    }
+
+    #[test]
+    fn test_memory_seeds_parse() {
+        let json = r#"{
+            "concepts": [
+                {"text": "User prefers Rust examples", "tags": ["work"]},
+                {"text": "User writes backend code", "category": "facts", "confidence": 0.6}
+            ],
+            "narrative": [{"title": "First commit", "content": "Started with a hello world"}]
+        }"#;
+        let seeds: MemorySeeds = serde_json::from_str(json).unwrap();
+        assert_eq!(seeds.concepts[0].category, "rules");
+        assert_eq!(seeds.concepts[0].confidence, 0.9);
+        assert_eq!(seeds.concepts[1].confidence, 0.6);
+        assert_eq!(seeds.narrative.len(), 1);
+    }
+
+    #[test]
+    fn test_validate_and_watch() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("zikkurat-archetype-watch-{}", std::process::id()));
+        fs::create_dir_all(&dir)?;
+        let path = dir.join("mentor.json");
+        let write = |traits: &str, style: &str| {
+            let json = format!(
+                r#"{{"id": "mentor", "name": "Mentor", "description": "Guide", "base_traits": {{{}}},
+                    "communication": {{"style": "{}", "greeting": "Hi"}},
+                    "directives": [{{"rule": "short_responses", "priority": 5}}, {{"rule": "short_responses", "priority": 6}}],
+                    "evolution_rules": {{}}}}"#,
+                traits, style
+            );
+            fs::write(&path, json)
+        };
+        let all_traits: Vec<String> = TRAIT_NAMES.iter().map(|t| format!("\"{}\": 0.5", t)).collect();
+
+        write(r#""analytical": 0.9, "empathyy": 0.7"#, "chatty")?;
+        let report = ArchetypeLoader::validate(path.to_str().unwrap())?;
+        assert!(report.is_valid());
+        assert!(report.warnings.iter().any(|w| w.contains("'empathy' is missing")));
+        assert!(report.warnings.iter().any(|w| w.contains("unknown trait 'empathyy'")));
+        assert!(report.warnings.iter().any(|w| w.contains("style 'chatty'")));
+        assert!(report.warnings.iter().any(|w| w.contains("'short_responses' is listed more than once")));
+
+        let mut watcher = ArchetypeLoader::watch(path.to_str().unwrap())?;
+        assert_eq!(watcher.archetype_id(), "mentor");
+        assert!(watcher.poll().is_none());
+
+        // mtime has a coarse resolution on some filesystems
+        std::thread::sleep(std::time::Duration::from_millis(1100));
+        write(&all_traits.join(", ").replace("\"formal\": 0.5", "\"formal\": 1.5"), "warm")?;
+        assert!(watcher.poll().is_some_and(|r| r.is_err()));
+        assert!(watcher.poll().is_none(), "a broken edit is reported once");
+
+        std::thread::sleep(std::time::Duration::from_millis(1100));
+        write(&all_traits.join(", "), "warm")?;
+        let reloaded = watcher.poll().expect("edit not noticed")?;
+        assert_eq!(reloaded.communication.style, "warm");
+        assert_eq!(ArchetypeLoader::validate(path.to_str().unwrap())?.warnings.len(), 1);
+
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_communication_style_default() {
+        let style = CommunicationStyle::default();
+        assert_eq!(style.style, "neutral");
+        assert!(!style.use_honorifics);
+    }
+}
@@ -0,0 +1,303 @@
+//! Session Context Module
+//!
+//! Defines PersonaSessionContext and related structures for
+//! context transfer between sessions.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::demiurge::address::AddressTracker;
+use crate::totems::user::UserId;
+use crate::utils::clock::{Clock, SystemClock};
+use crate::utils::crypto::{self, MemoryKey};
+
+const CONTEXT_DIR: &str = "data/session_context";
+
+/// Session context for transfer between sessions
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PersonaSessionContext {
+    pub version: String,
+    pub archetype_id: String,
+    pub previous_session_id: String,
+    pub last_interaction_date: u64,
+    pub summary: String,
+    pub key_topics: Vec<String>,
+    pub user_preferences: Vec<Preference>,
+    pub emotional_state: f32,
+    pub last_topic: String,
+    pub pending_questions: Vec<String>,
+    pub custom_data: HashMap<String, String>,
+    /// Conversation state saved by `--checkpoint`, resumed by `continue`
+    #[serde(default)]
+    pub checkpoint: Option<ConversationCheckpoint>,
+    /// How the user addresses the persona (Вы/ты)
+    #[serde(default)]
+    pub address: AddressTracker,
+    /// Whose conversation this is; each user has their own context per persona
+    #[serde(default, skip_serializing_if = "UserId::is_default")]
+    pub user: UserId,
+}
+
+/// Pipeline-independent state needed to resume a conversation in a new process
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConversationCheckpoint {
+    /// Episodic session to continue
+    pub session_id: String,
+    pub turn_count: usize,
+    pub last_user_message: String,
+    pub sampling: SamplingState,
+    pub enable_semantic: bool,
+    #[serde(default)]
+    pub exclude_tags: Vec<String>,
+    pub saved_at: u64,
+}
+
+/// Sampling parameters of the checkpointed conversation
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SamplingState {
+    pub temperature: f64,
+    pub top_p: Option<f64>,
+    pub top_k: Option<usize>,
+    pub seed: u64,
+    pub sample_len: usize,
+}
+
+/// User preference
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Preference {
+    pub topic: String,
+    pub statement: String,
+    pub confidence: f32,
+    pub mentioned_at: u64,
+}
+
+/// Storage for contexts
+pub struct ContextStorage;
+
+impl ContextStorage {
+    /// Storage key of a persona's context with a user: the archetype for the
+    /// default user (the layout before users), `archetype@user` for the others
+    pub fn key(archetype_id: &str, user: &UserId) -> String {
+        if user.is_default() {
+            archetype_id.to_string()
+        } else {
+            format!("{}@{}", archetype_id, user)
+        }
+    }
+
+    /// Save session context
+    pub fn save(context: &PersonaSessionContext) -> std::io::Result<()> {
+        let dir = std::path::Path::new(CONTEXT_DIR);
+        std::fs::create_dir_all(&dir)?;
+
+        let key = Self::key(&context.archetype_id, &context.user);
+        let file_path = dir.join(format!("{}.json", key));
+        let json = serde_json::to_string_pretty(context)?;
+
+        let sealed = crypto::seal(crypto::installed().as_ref(), json).map_err(std::io::Error::other)?;
+        std::fs::write(&file_path, sealed)?;
+        println!("💾 Контекст сессии сохранён: {}", key);
+        Ok(())
+    }
+
+    /// Load session context by its [`ContextStorage::key`]
+    pub fn load(key: &str) -> std::io::Result<Option<PersonaSessionContext>> {
+        let file_path = std::path::Path::new(CONTEXT_DIR).join(format!("{}.json", key));
+
+        if !file_path.exists() {
+            return Ok(None);
+        }
+
+        let context = read_context(&file_path, crypto::installed().as_ref())?;
+
+        println!("💭 Контекст сессии загружен: {}", key);
+        Ok(Some(context))
+    }
+
+    /// Most recent context that carries a conversation checkpoint (any archetype)
+    pub fn latest_checkpoint() -> std::io::Result<Option<PersonaSessionContext>> {
+        let dir = std::path::Path::new(CONTEXT_DIR);
+        if !dir.exists() {
+            return Ok(None);
+        }
+
+        let mut latest: Option<PersonaSessionContext> = None;
+        for entry in std::fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.extension().is_none_or(|e| e != "json") {
+                continue;
+            }
+            let Ok(context) = read_context(&path, crypto::installed().as_ref()) else {
+                continue;
+            };
+            let Some(saved_at) = context.checkpoint.as_ref().map(|c| c.saved_at) else {
+                continue;
+            };
+            let newer = latest
+                .as_ref()
+                .and_then(|l| l.checkpoint.as_ref())
+                .is_none_or(|l| saved_at >= l.saved_at);
+            if newer {
+                latest = Some(context);
+            }
+        }
+
+        Ok(latest)
+    }
+
+    /// Check if context exists
+    pub fn exists(key: &str) -> bool {
+        std::path::Path::new(CONTEXT_DIR)
+            .join(format!("{}.json", key))
+            .exists()
+    }
+
+    /// Delete old context
+    pub fn delete(key: &str) -> std::io::Result<()> {
+        let file_path = std::path::Path::new(CONTEXT_DIR).join(format!("{}.json", key));
+        if file_path.exists() {
+            std::fs::remove_file(&file_path)?;
+            println!("🗑️ Старый контекст удалён: {}", key);
+        }
+        Ok(())
+    }
+
+    /// Rewrite every stored context encrypted with the memory key (`encrypt`) or in the clear.
+    /// Returns how many files were rewritten
+    pub fn reseal(key: &MemoryKey, encrypt: bool) -> anyhow::Result<usize> {
+        reseal_dir(std::path::Path::new(CONTEXT_DIR), key, encrypt)
+    }
+
+    /// Check if context is older than `max_days` at unix time `now`
+    pub fn is_expired(key: &str, max_days: i64, now: u64) -> bool {
+        if let Ok(Some(context)) = Self::load(key) {
+            let days_old = now.saturating_sub(context.last_interaction_date) / (24 * 60 * 60);
+            days_old > max_days as u64
+        } else {
+            false
+        }
+    }
+}
+
+/// Read a context file, decrypting it when it is sealed
+fn read_context(path: &std::path::Path, key: Option<&MemoryKey>) -> std::io::Result<PersonaSessionContext> {
+    let content = crypto::unseal_string(key, std::fs::read(path)?).map_err(std::io::Error::other)?;
+    serde_json::from_str(&content).map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+}
+
+fn reseal_dir(dir: &std::path::Path, key: &MemoryKey, encrypt: bool) -> anyhow::Result<usize> {
+    if !dir.exists() {
+        return Ok(0);
+    }
+    let mut rewritten = 0;
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.extension().is_some_and(|e| e == "json") && crypto::reseal_file(key, &path, encrypt)? {
+            rewritten += 1;
+        }
+    }
+    Ok(rewritten)
+}
+
+impl PersonaSessionContext {
+    pub fn new(archetype_id: &str) -> Self {
+        let now = SystemClock.unix_now();
+
+        Self {
+            version: "1.0".to_string(),
+            archetype_id: archetype_id.to_string(),
+            previous_session_id: String::new(),
+            last_interaction_date: now,
+            summary: String::new(),
+            key_topics: Vec::new(),
+            user_preferences: Vec::new(),
+            emotional_state: 0.5,
+            last_topic: String::new(),
+            pending_questions: Vec::new(),
+            custom_data: HashMap::new(),
+            checkpoint: None,
+            address: AddressTracker::default(),
+            user: UserId::default(),
+        }
+    }
+
+    pub fn version() -> String {
+        "1.0".to_string()
+    }
+}
+
+impl Default for PersonaSessionContext {
+    fn default() -> Self {
+        Self {
+            version: "1.0".to_string(),
+            archetype_id: String::new(),
+            previous_session_id: String::new(),
+            last_interaction_date: SystemClock.unix_now(),
+            summary: String::new(),
+            key_topics: Vec::new(),
+            user_preferences: Vec::new(),
+            emotional_state: 0.5,
+            last_topic: String::new(),
+            pending_questions: Vec::new(),
+            custom_data: HashMap::new(),
+            checkpoint: None,
+            address: AddressTracker::default(),
+            user: UserId::default(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_context_without_checkpoint_parses() {
+        let mut context = PersonaSessionContext::new("programmer");
+        let mut json = serde_json::to_value(&context).unwrap();
+        json.as_object_mut().unwrap().remove("checkpoint");
+        let parsed: PersonaSessionContext = serde_json::from_value(json).unwrap();
+        assert!(parsed.checkpoint.is_none());
+
+        context.checkpoint = Some(ConversationCheckpoint {
+            session_id: "s1".to_string(),
+            turn_count: 2,
+            last_user_message: "hi".to_string(),
+            sampling: SamplingState {
+                temperature: 0.7,
+                top_p: None,
+                top_k: Some(40),
+                seed: 1,
+                sample_len: 256,
+            },
+            enable_semantic: true,
+            exclude_tags: Vec::new(),
+            saved_at: 10,
+        });
+        let round_trip: PersonaSessionContext =
+            serde_json::from_str(&serde_json::to_string(&context).unwrap()).unwrap();
+        assert_eq!(round_trip.checkpoint.unwrap().sampling.top_k, Some(40));
+    }
+
+    #[cfg(feature = "encryption")]
+    #[test]
+    fn test_contexts_resealed() -> anyhow::Result<()> {
+        let dir = std::env::temp_dir().join(format!("ziggurat-context-test-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir)?;
+        let key = MemoryKey::from_secret("correct horse battery staple")?;
+        let mut context = PersonaSessionContext::new("programmer");
+        context.summary = "Talked about the cat Tom".to_string();
+        let path = dir.join("programmer.json");
+        std::fs::write(&path, serde_json::to_string_pretty(&context)?)?;
+
+        assert_eq!(reseal_dir(&dir, &key, true)?, 1);
+        assert!(crypto::is_sealed(&std::fs::read(&path)?));
+        assert_eq!(read_context(&path, Some(&key))?.summary, "Talked about the cat Tom");
+        assert!(read_context(&path, None).is_err());
+        assert_eq!(reseal_dir(&dir, &key, false)?, 1);
+        assert_eq!(read_context(&path, None)?.summary, "Talked about the cat Tom");
+
+        std::fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+}
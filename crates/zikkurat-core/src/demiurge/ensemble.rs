@@ -0,0 +1,314 @@
+//! Persona Ensemble - Several Personas in One Conversation
+//!
+//! Two or more loaded personas share a session. In `route` mode each user turn
+//! goes to the persona whose traits and topics fit it best, or to the one the
+//! user named; in `debate` mode every persona answers in turn and sees what the
+//! others said before it. The personas stay separate instances, so each keeps
+//! its own evolution state and narrative.
+//!
+//! The ensemble holds the guests; the host is the persona the caller already
+//! has in hand. [`PersonaEnsemble::swap_speaker`] lends a guest to the caller
+//! for one answer, so code written for a single persona serves the whole group.
+
+use anyhow::Result;
+use std::collections::HashSet;
+use std::str::FromStr;
+
+use crate::demiurge::Persona;
+
+/// Being named outweighs any topic match
+const NAME_BONUS: f32 = 10.0;
+/// Description words shorter than this say little about the topic ("по", "and")
+const MIN_STEM_CHARS: usize = 5;
+/// Characters of another persona's reply quoted to the next speaker in a debate
+const REPLY_QUOTE_CHARS: usize = 600;
+
+/// Query markers of what each trait is good for: word beginnings, or substrings
+/// for phrases, as in forbidden topics
+const TRAIT_TOPICS: &[(&str, &[&str])] = &[
+    (
+        "technical",
+        &[
+            "код", "программ", "rust", "python", "баг", "компил", "docker", "kubernet", "сервер", "деплой",
+            "linux", "code", "bug", "compil", "deploy", "server", "api", "sql",
+        ],
+    ),
+    (
+        "analytical",
+        &["анализ", "данны", "статист", "логик", "доказ", "исследов", "analy", "data", "statist", "logic", "proof"],
+    ),
+    (
+        "empathy",
+        &[
+            "грустн", "тоскл", "одинок", "устал", "обид", "чувств", "пережива", "тревог", "sad", "lonely", "tired",
+            "feel", "anxious", "upset",
+        ],
+    ),
+    ("creative", &["иде", "придума", "творч", "стих", "сюжет", "idea", "creat", "poem", "story", "design"]),
+    ("pedagogical", &["объясн", "научи", "что такое", "как работает", "explain", "teach", "learn", "how does"]),
+    ("humor", &["шутк", "пошути", "анекдот", "смешн", "joke", "funny"]),
+    ("curious", &["смысл", "философ", "зачем", "почему", "meaning", "philosoph", "why"]),
+    ("supportive", &["помоги", "поддерж", "совет", "help", "support", "advice"]),
+];
+
+/// Who answers a user turn
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum EnsembleMode {
+    /// The best-fitting persona answers
+    #[default]
+    Route,
+    /// Every persona answers, the first speaker rotates from turn to turn
+    Debate,
+}
+
+impl EnsembleMode {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            EnsembleMode::Route => "route",
+            EnsembleMode::Debate => "debate",
+        }
+    }
+}
+
+impl FromStr for EnsembleMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "route" => Ok(EnsembleMode::Route),
+            "debate" => Ok(EnsembleMode::Debate),
+            other => anyhow::bail!("Unknown ensemble mode '{}' (route, debate)", other),
+        }
+    }
+}
+
+/// Guests that talk alongside the host persona. Member 0 is the host, guest `i` is member `i + 1`
+pub struct PersonaEnsemble {
+    guests: Vec<Persona>,
+    pub mode: EnsembleMode,
+    /// Debate turns so far: who opens the next one
+    rotation: usize,
+    last_speaker: Option<usize>,
+}
+
+impl PersonaEnsemble {
+    pub fn new(host: &Persona, guests: Vec<Persona>, mode: EnsembleMode) -> Result<Self> {
+        anyhow::ensure!(!guests.is_empty(), "A group needs at least one more persona");
+        let mut ids = HashSet::from([host.archetype_id.as_str()]);
+        for guest in &guests {
+            anyhow::ensure!(ids.insert(guest.archetype_id.as_str()), "'{}' is in the group twice", guest.archetype_id);
+        }
+        Ok(Self {
+            guests,
+            mode,
+            rotation: 0,
+            last_speaker: None,
+        })
+    }
+
+    /// Members with the host
+    pub fn len(&self) -> usize {
+        self.guests.len() + 1
+    }
+
+    pub fn is_empty(&self) -> bool {
+        false
+    }
+
+    pub fn member<'a>(&'a self, host: &'a Persona, index: usize) -> &'a Persona {
+        match index {
+            0 => host,
+            i => &self.guests[i - 1],
+        }
+    }
+
+    pub fn guests(&self) -> &[Persona] {
+        &self.guests
+    }
+
+    /// Names of all members, the host first
+    pub fn names<'a>(&'a self, host: &'a Persona) -> Vec<&'a str> {
+        (0..self.len()).map(|i| self.member(host, i).name.as_str()).collect()
+    }
+
+    /// How well a persona fits the query; `None` if the query is on a topic it declines
+    pub fn relevance(persona: &Persona, query: &str) -> Option<f32> {
+        if persona.forbidden_topics.iter().any(|t| t.matches(query)) {
+            return None;
+        }
+        let lower = query.to_lowercase();
+        let words: Vec<&str> = lower
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|w| !w.is_empty())
+            .collect();
+
+        let mut score = 0.0;
+        let name = persona.name.to_lowercase();
+        if words.iter().any(|w| *w == name || w.starts_with(&name)) {
+            score += NAME_BONUS;
+        }
+        let traits = persona.get_all_traits();
+        for (name, markers) in TRAIT_TOPICS {
+            if markers.iter().any(|m| mentions(&lower, &words, m)) {
+                score += traits.get(*name).copied().unwrap_or(0.0);
+            }
+        }
+        // the archetype's own subject: "DevOps инженер, специалист по инфраструктуре"
+        let about = format!("{} {}", persona.archetype_id, persona.description).to_lowercase();
+        let stems: HashSet<String> = about
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|w| w.chars().count() >= MIN_STEM_CHARS)
+            .map(|w| w.chars().take(MIN_STEM_CHARS).collect())
+            .collect();
+        score += stems.iter().filter(|s| words.iter().any(|w| w.starts_with(s.as_str()))).count() as f32;
+        Some(score)
+    }
+
+    /// The member that answers in `route` mode. Ties and queries that fit no one stay
+    /// with the last speaker, so a follow-up question goes to whoever answered it
+    pub fn route(&self, host: &Persona, query: &str) -> usize {
+        let last = self.last_speaker.unwrap_or(0);
+        let scores: Vec<Option<f32>> = (0..self.len()).map(|i| Self::relevance(self.member(host, i), query)).collect();
+        let best = scores.iter().flatten().copied().fold(f32::NEG_INFINITY, f32::max);
+        if best == f32::NEG_INFINITY {
+            return last;
+        }
+        if scores[last] == Some(best) {
+            return last;
+        }
+        scores.iter().position(|s| *s == Some(best)).unwrap_or(last)
+    }
+
+    /// Who answers this turn, in order
+    pub fn speakers(&mut self, host: &Persona, query: &str) -> Vec<usize> {
+        let speakers = match self.mode {
+            EnsembleMode::Route => vec![self.route(host, query)],
+            EnsembleMode::Debate => {
+                let start = self.rotation % self.len();
+                self.rotation += 1;
+                (0..self.len()).map(|i| (start + i) % self.len()).collect()
+            }
+        };
+        self.last_speaker = speakers.last().copied();
+        speakers
+    }
+
+    /// Lends member `index` to the caller: `slot` holding the host gets that member.
+    /// The same call with the same index gives the host back
+    pub fn swap_speaker(&mut self, slot: &mut Persona, index: usize) {
+        if index > 0 {
+            std::mem::swap(slot, &mut self.guests[index - 1]);
+        }
+    }
+
+    /// Prompt note for `speaker`: who else is in the conversation and, in a debate,
+    /// what they already said this turn (`replies` by member index)
+    pub fn group_note(&self, host: &Persona, speaker: usize, replies: &[(usize, String)]) -> String {
+        let others: Vec<String> = (0..self.len())
+            .filter(|&i| i != speaker)
+            .map(|i| {
+                let p = self.member(host, i);
+                format!("{} ({})", p.name, p.description)
+            })
+            .collect();
+        let mut note = format!(
+            "You are {} in a group conversation with the user and {}.",
+            self.member(host, speaker).name,
+            others.join(", ")
+        );
+        if replies.is_empty() {
+            note.push_str(" Answer for yourself only.");
+            return note;
+        }
+        note.push_str(" This turn the others have already answered:");
+        for (index, reply) in replies {
+            note.push_str(&format!("\n{}: {}", self.member(host, *index).name, quote(reply)));
+        }
+        note.push_str("\nAdd your own view: agree or argue with them by name, do not repeat them.");
+        note
+    }
+
+    /// Writes the start of the group conversation into every member's narrative
+    pub fn record_start(&mut self, host: &mut Persona) {
+        let names = self.names(host).join(", ");
+        let description = format!("Group conversation: {} ({})", names, self.mode.as_str());
+        for persona in std::iter::once(host).chain(self.guests.iter_mut()) {
+            persona.narrative.add_milestone("group_conversation", &description, "system", 0.0);
+            if let Err(e) = persona.narrative.save() {
+                eprintln!("WARNING: Failed to save narrative: {}", e);
+            }
+        }
+    }
+
+    /// Ends the group; the guests are handed back to be saved or dropped
+    pub fn into_guests(self) -> Vec<Persona> {
+        self.guests
+    }
+}
+
+fn mentions(lower: &str, words: &[&str], marker: &str) -> bool {
+    if marker.contains(|c: char| !c.is_alphanumeric()) {
+        lower.contains(marker)
+    } else {
+        words.iter().any(|w| w.starts_with(marker))
+    }
+}
+
+fn quote(reply: &str) -> String {
+    let reply = reply.trim().replace('\n', " ");
+    if reply.chars().count() <= REPLY_QUOTE_CHARS {
+        return reply;
+    }
+    let cut: String = reply.chars().take(REPLY_QUOTE_CHARS).collect();
+    format!("{}…", cut.trim_end())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::demiurge::Archetype;
+    use std::sync::Arc;
+
+    fn persona(id: &str, name: &str, description: &str, traits: &str) -> Persona {
+        let json = format!(
+            r#"{{"id": "{}", "name": "{}", "description": "{}", "base_traits": {{{}}},
+                "communication": {{"style": "warm", "greeting": "Hi"}}, "directives": [], "evolution_rules": {{}}}}"#,
+            id, name, description, traits
+        );
+        Persona::from_archetype(Arc::new(serde_json::from_str::<Archetype>(&json).unwrap()))
+    }
+
+    #[test]
+    fn test_route_and_debate() {
+        let mut host = persona("programmer", "Алекс", "Программист на Rust", r#""technical": 0.95, "empathy": 0.3"#);
+        let friend = persona("girlfriend", "Лея", "Близкий друг", r#""technical": 0.2, "empathy": 0.95"#);
+        let devops = persona("devops", "Крис", "DevOps инженер по инфраструктуре", r#""technical": 0.9"#);
+        assert!(PersonaEnsemble::new(&host, vec![], EnsembleMode::Route).is_err());
+        let twin = persona("programmer", "Алекс", "", "");
+        assert!(PersonaEnsemble::new(&host, vec![twin], EnsembleMode::Route).is_err());
+
+        let mut group = PersonaEnsemble::new(&host, vec![friend, devops], EnsembleMode::Route).unwrap();
+        assert_eq!(group.speakers(&host, "Почему падает компиляция?"), vec![0]);
+        assert_eq!(group.speakers(&host, "Мне сегодня грустно и одиноко"), vec![1]);
+        // nothing fits: the last speaker keeps the floor
+        assert_eq!(group.speakers(&host, "ну и ну"), vec![1]);
+        assert_eq!(group.speakers(&host, "как поднять инфраструктуру?"), vec![2]);
+        assert_eq!(group.speakers(&host, "Лея, что скажешь про код?"), vec![1]);
+
+        group.mode = EnsembleMode::Debate;
+        assert_eq!(group.speakers(&host, "Rust или Go?"), vec![0, 1, 2]);
+        assert_eq!(group.speakers(&host, "А ещё?"), vec![1, 2, 0]);
+
+        let note = group.group_note(&host, 2, &[(1, "Rust, конечно".to_string())]);
+        assert!(note.starts_with("You are Крис") && note.contains("Лея: Rust, конечно"), "{}", note);
+
+        // a guest answers in the host's place and goes back
+        host.evolution.interactions_count = 5;
+        group.swap_speaker(&mut host, 1);
+        assert_eq!(host.name, "Лея");
+        host.evolution.interactions_count += 1;
+        group.swap_speaker(&mut host, 1);
+        assert_eq!((host.name.as_str(), host.evolution.interactions_count), ("Алекс", 5));
+        assert_eq!(group.guests()[0].evolution.interactions_count, 1);
+    }
+}
@@ -3,8 +3,15 @@
 //! Tracks interaction outcomes and modifies persona traits
 //! over time based on evolution rules.
 
+use anyhow::Result;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::path::Path;
+
+use crate::utils::clock::{self, SharedClock};
+
+/// Persona state store: evolution state per archetype
+pub const PERSONA_STATE_DIR: &str = "data/persona_state";
 
 /// Interaction data for evolution tracking
 #[derive(Debug, Clone)]
@@ -30,12 +37,64 @@ pub struct EvolutionState {
     pub unlocked_traits: Vec<String>,
     pub last_interaction_time: u64,
     pub decay_applied_at: u64,
+    /// Audit trail of trait changes made by hand (`/persona set`)
+    #[serde(default)]
+    pub adjustments: Vec<TraitAdjustment>,
+}
+
+/// A trait value changed outside the evolution rules
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TraitAdjustment {
+    pub trait_name: String,
+    pub from: f32,
+    pub to: f32,
+    /// "manual" for `/persona set`
+    pub source: String,
+    pub timestamp: u64,
+}
+
+impl EvolutionState {
+    /// Loads the saved state of an archetype, `None` if nothing was saved yet
+    pub fn load(archetype_id: &str) -> Result<Option<Self>> {
+        let path = Path::new(PERSONA_STATE_DIR).join(format!("{}.json", archetype_id));
+        if !path.exists() {
+            return Ok(None);
+        }
+        Ok(Some(serde_json::from_str(&std::fs::read_to_string(path)?)?))
+    }
+
+    pub fn save(&self, archetype_id: &str) -> Result<()> {
+        let dir = Path::new(PERSONA_STATE_DIR);
+        std::fs::create_dir_all(dir)?;
+        std::fs::write(
+            dir.join(format!("{}.json", archetype_id)),
+            serde_json::to_string_pretty(self)?,
+        )?;
+        Ok(())
+    }
+
+    /// Pins a trait to `value` given its archetype `base`, recording the change
+    pub fn set_trait(&mut self, trait_name: &str, base: f32, value: f32, now: u64) -> TraitAdjustment {
+        let from = (base + self.trait_offsets.get(trait_name).copied().unwrap_or(0.0)).clamp(0.0, 1.0);
+        self.trait_offsets.insert(trait_name.to_string(), value - base);
+
+        let adjustment = TraitAdjustment {
+            trait_name: trait_name.to_string(),
+            from,
+            to: value,
+            source: "manual".to_string(),
+            timestamp: now,
+        };
+        self.adjustments.push(adjustment.clone());
+        adjustment
+    }
 }
 
 /// Evolution engine for trait modifications
 pub struct EvolutionEngine {
     state: EvolutionState,
     rules: EvolutionRules,
+    clock: SharedClock,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -75,16 +134,20 @@ impl EvolutionEngine {
         Self {
             state: EvolutionState::default(),
             rules,
+            clock: clock::default_clock(),
         }
     }
 
+    /// Use `clock` for interaction times and decay
+    pub fn with_clock(mut self, clock: SharedClock) -> Self {
+        self.clock = clock;
+        self
+    }
+
     /// Apply interaction and update evolution state
     pub fn apply_interaction(&mut self, interaction: &Interaction) {
         self.state.interactions_count += 1;
-        self.state.last_interaction_time = std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap_or_default()
-            .as_secs();
+        self.state.last_interaction_time = self.clock.unix_now();
 
         if interaction.successful_help {
             self.state.successful_helps += 1;
@@ -139,13 +202,10 @@ impl EvolutionEngine {
 
     /// Apply decay to unused traits
     fn apply_decay(&mut self) {
-        let now = std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap_or_default()
-            .as_secs();
+        let now = self.clock.unix_now();
 
         // Only decay if enough time has passed (1 hour)
-        if now - self.state.decay_applied_at < 3600 {
+        if now.saturating_sub(self.state.decay_applied_at) < 3600 {
             return;
         }
 
@@ -291,3 +351,22 @@ impl Default for EvolutionRules {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_manual_trait_adjustment() {
+        let mut state = EvolutionState::default();
+        state.trait_offsets.insert("humor".to_string(), 0.1);
+
+        let adjustment = state.set_trait("humor", 0.5, 0.9, 1_700_000_000);
+        assert!((adjustment.from - 0.6).abs() < 1e-6);
+        assert!((state.trait_offsets["humor"] - 0.4).abs() < 1e-6);
+        assert_eq!(adjustment.source, "manual");
+
+        let restored: EvolutionState = serde_json::from_str(&serde_json::to_string(&state).unwrap()).unwrap();
+        assert_eq!(restored.adjustments, vec![adjustment]);
+    }
+}
@@ -4,21 +4,31 @@
 //! The Demiurge creates and manages AI personas with dynamic traits,
 //! communication styles, and evolving narratives.
 
+pub mod address;
 pub mod archetype;
 pub mod context;
 pub mod directives;
+pub mod ensemble;
 pub mod evolution;
 pub mod narrative;
 pub mod persona;
+pub mod sanitize;
+pub mod topics;
 
+pub use address::{AddressStyle, AddressTracker};
 pub use archetype::{
-    Archetype, ArchetypeDirective, ArchetypeLoader, BaseTraits, CommunicationStyle,
+    AdapterConfig, Archetype, ArchetypeDirective, ArchetypeLoader, ArchetypeWatcher, BaseTraits, CommunicationStyle,
+    MemorySeeds, ValidationReport,
+};
+pub use context::{
+    ContextStorage, ConversationCheckpoint, PersonaSessionContext, Preference, SamplingState,
 };
-pub use context::{ContextStorage, PersonaSessionContext, Preference};
 pub use directives::Directive;
-pub use evolution::{EvolutionState, Interaction};
+pub use ensemble::{EnsembleMode, PersonaEnsemble};
+pub use evolution::{EvolutionState, Interaction, TraitAdjustment};
 pub use narrative::NarrativeManager;
 pub use persona::Persona;
+pub use topics::ForbiddenTopic;
 
 use anyhow::Result;
 use std::sync::Arc;
@@ -11,6 +11,8 @@ use std::path::Path;
 use std::time::{SystemTime, UNIX_EPOCH};
 use uuid::Uuid;
 
+use crate::demiurge::archetype::NarrativeSeed;
+
 pub const NARRATIVES_DIR: &str = "data/narratives";
 
 /// Main narrative structure
@@ -189,6 +191,34 @@ impl NarrativeManager {
 
         self.narrative.last_updated = now;
     }
+
+    /// Add archetype biography seeds, skipping titles already present
+    pub fn seed_biography(&mut self, seeds: &[NarrativeSeed]) -> usize {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+
+        let mut added = 0;
+        for seed in seeds {
+            if self.narrative.biography.iter().any(|b| b.title == seed.title) {
+                continue;
+            }
+            self.narrative.biography.push(BioEntry {
+                id: Uuid::new_v4().to_string(),
+                title: seed.title.clone(),
+                content: seed.content.clone(),
+                timestamp: now,
+                tags: seed.tags.clone(),
+            });
+            added += 1;
+        }
+
+        if added > 0 {
+            self.narrative.last_updated = now;
+        }
+        added
+    }
 }
 
 /// Generate origin story based on archetype
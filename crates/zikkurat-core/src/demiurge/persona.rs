@@ -4,17 +4,21 @@
 //! communication settings, and evolution state.
 
 use crate::demiurge::{
-    Archetype, ArchetypeDirective, BaseTraits, CommunicationStyle, ContextStorage, Directive,
-    EvolutionState, NarrativeManager, PersonaSessionContext,
+    AdapterConfig, AddressStyle, AddressTracker, Archetype, ArchetypeDirective, BaseTraits, CommunicationStyle, ContextStorage, Directive,
+    ConversationCheckpoint, EvolutionState, ForbiddenTopic, MemorySeeds, NarrativeManager, PersonaSessionContext, TraitAdjustment,
 };
+use crate::logos::metrics::{self, ExtractionResult};
 use crate::totems::episodic::{DialogueManager, LlmPipeline};
-use crate::totems::semantic::{ConceptCategory, SemanticMemoryManager};
+use crate::totems::retrieval::RetrievalOverrides;
+use crate::totems::semantic::perspective::ConfidencePhrasing;
+use crate::totems::semantic::{is_self_disclosure, Concept, ConceptCategory, KnowledgeSource, SemanticMemoryManager, TurnRef};
+use crate::totems::user::UserId;
+use crate::utils::clock::{self, SharedClock};
 use anyhow::Result;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::io;
 use std::sync::{Arc, Mutex};
-use std::time::{SystemTime, UNIX_EPOCH};
 
 pub const MAX_CONTEXT_AGE_DAYS: i64 = 30;
 pub const MIN_TURNS_FOR_SAVE: usize = 3;
@@ -30,6 +34,21 @@ pub struct Persona {
     pub narrative: NarrativeManager,
     pub evolution: EvolutionState,
     pub semantic_manager: Option<Arc<Mutex<SemanticMemoryManager>>>,
+    pub memory_seeds: MemorySeeds,
+    /// Вы/ты of the user, restored from the session context
+    pub address: AddressTracker,
+    /// LoRA adapter for this persona
+    pub adapter: Option<AdapterConfig>,
+    /// Topics the persona declines to discuss, from the archetype
+    pub forbidden_topics: Vec<ForbiddenTopic>,
+    /// Memory gate threshold of the archetype, if it sets its own
+    pub memory_gate_threshold: Option<f32>,
+    /// Retrieval weights and thresholds the archetype overrides
+    pub retrieval: Option<RetrievalOverrides>,
+    /// Time source for session context age and trait adjustments
+    pub clock: SharedClock,
+    /// Who the persona is talking to: the session context and relationship arc are theirs
+    pub user: UserId,
 }
 
 impl Persona {
@@ -48,34 +67,67 @@ impl Persona {
             narrative: NarrativeManager::new(&archetype.id),
             evolution: EvolutionState::default(),
             semantic_manager: None,
+            memory_seeds: archetype.memory_seeds.clone(),
+            address: AddressTracker::default(),
+            adapter: archetype.adapter.clone(),
+            forbidden_topics: archetype.forbidden_topics.clone(),
+            memory_gate_threshold: archetype.memory_gate_threshold,
+            retrieval: archetype.retrieval.clone(),
+            clock: clock::default_clock(),
+            user: UserId::default(),
         }
     }
 
+    /// Applies an edited version of the archetype: traits, communication style, directives,
+    /// seeds and topics change, while evolution, narrative and memory stay as they are
+    pub fn reload_archetype(&mut self, archetype: Archetype) -> Result<()> {
+        anyhow::ensure!(
+            archetype.id == self.archetype_id,
+            "Archetype id changed from '{}' to '{}'; switch personas instead",
+            self.archetype_id,
+            archetype.id
+        );
+        self.name = archetype.name;
+        self.description = archetype.description;
+        self.base_traits = Self::extract_traits(&archetype.base_traits);
+        self.communication = archetype.communication;
+        self.directives = Self::extract_directives(&archetype.directives);
+        self.memory_seeds = archetype.memory_seeds;
+        self.adapter = archetype.adapter;
+        self.forbidden_topics = archetype.forbidden_topics;
+        self.memory_gate_threshold = archetype.memory_gate_threshold;
+        self.retrieval = archetype.retrieval;
+        Ok(())
+    }
+
+    /// Talk to another user from now on; their session context is loaded separately
+    pub fn set_user(&mut self, user: UserId) {
+        self.user = user;
+        self.address = AddressTracker::default();
+    }
+
     /// Set semantic memory manager for this persona
     pub fn set_semantic_manager(&mut self, manager: Arc<Mutex<SemanticMemoryManager>>) {
         self.semantic_manager = Some(manager);
     }
 
-    /// Get user preferences from semantic memory
-    pub fn get_user_preferences(&self) -> Vec<(String, String)> {
-        if let Some(ref sm) = self.semantic_manager {
-            let sm = sm.lock().unwrap();
-            let prefs = sm.get_concepts_by_category(&ConceptCategory::Preferences);
-            prefs
-                .into_iter()
-                .map(|c| (c.text.clone(), format!("{:.2}", c.confidence)))
-                .collect()
-        } else {
-            Vec::new()
-        }
+    /// Get user preferences from semantic memory with their confidence
+    pub fn get_user_preferences(&self) -> Vec<(String, f32)> {
+        self.concepts_with_confidence(&ConceptCategory::Preferences)
+    }
+
+    /// Get user facts from semantic memory with their confidence
+    pub fn get_user_facts(&self) -> Vec<(String, f32)> {
+        self.concepts_with_confidence(&ConceptCategory::Facts)
     }
 
-    /// Get user facts from semantic memory
-    pub fn get_user_facts(&self) -> Vec<String> {
+    fn concepts_with_confidence(&self, category: &ConceptCategory) -> Vec<(String, f32)> {
         if let Some(ref sm) = self.semantic_manager {
             let sm = sm.lock().unwrap();
-            let facts = sm.get_concepts_by_category(&ConceptCategory::Facts);
-            facts.into_iter().map(|c| c.text.clone()).collect()
+            sm.get_concepts_by_category(category)
+                .into_iter()
+                .map(|c| (c.text.clone(), c.confidence))
+                .collect()
         } else {
             Vec::new()
         }
@@ -85,7 +137,7 @@ impl Persona {
     pub fn search_semantic(&self, query: &str, limit: usize) -> Vec<(String, f32)> {
         if let Some(ref sm) = self.semantic_manager {
             let sm = sm.lock().unwrap();
-            let results = sm.search_by_text(query, limit);
+            let results = sm.search_by_text_blocking(query, limit);
             results
                 .into_iter()
                 .map(|(sim, c)| (c.text.clone(), sim))
@@ -95,10 +147,15 @@ impl Persona {
         }
     }
 
-    /// Get all user knowledge as formatted string
-    pub fn get_user_knowledge_summary(&self) -> String {
-        let preferences = self.get_user_preferences();
-        let facts = self.get_user_facts();
+    /// Get all user knowledge as formatted string. Facts are rewritten in the third
+    /// person so the model does not take "I love pizza" for its own preference, and
+    /// hedged when their confidence is low
+    pub fn get_user_knowledge_summary(&self, phrasing: &ConfidencePhrasing) -> String {
+        let phrase = |items: Vec<(String, f32)>| -> Vec<String> {
+            items.into_iter().map(|(text, conf)| phrasing.phrase(&text, conf)).collect()
+        };
+        let facts = phrase(self.get_user_facts());
+        let preferences = phrase(self.get_user_preferences());
 
         if preferences.is_empty() && facts.is_empty() {
             return String::new();
@@ -111,43 +168,52 @@ impl Persona {
         }
 
         if !preferences.is_empty() {
-            let prefs_list: Vec<String> = preferences
-                .into_iter()
-                .map(|(text, conf)| format!("{} (confidence: {})", text, conf))
-                .collect();
-            parts.push(format!("USER PREFERENCES:\n- {}", prefs_list.join("\n- ")));
+            parts.push(format!("USER PREFERENCES:\n- {}", preferences.join("\n- ")));
         }
 
         parts.join("\n\n")
     }
 
-    /// Extract and store concepts from current dialogue
-    pub fn extract_and_store_concepts(&self, user_input: &str, assistant_response: &str) {
+    /// Memory the persona's concepts go to and their session id, to extract off the main thread
+    pub fn extraction_target(&self) -> Option<(Arc<Mutex<SemanticMemoryManager>>, String)> {
+        let sm = self.semantic_manager.clone()?;
+        Some((sm, format!("persona_{}", self.archetype_id)))
+    }
+
+    /// Apply archetype memory seeds (narrative + semantic concepts), skipping ones already present.
+    /// Returns (concepts added, narrative entries added)
+    pub fn apply_memory_seeds(&mut self) -> Result<(usize, usize)> {
+        let narrative_added = self.narrative.seed_biography(&self.memory_seeds.narrative);
+        if narrative_added > 0 {
+            self.narrative.save()?;
+        }
+
+        let mut concepts_added = 0;
         if let Some(ref sm) = self.semantic_manager {
-            let has_self_disclosure = user_input.to_lowercase().contains("я ")
-                || user_input.to_lowercase().contains("мой ")
-                || user_input.to_lowercase().contains("моя ")
-                || user_input.to_lowercase().contains("моё ")
-                || user_input.to_lowercase().contains("мои ")
-                || user_input.to_lowercase().contains("люблю")
-                || user_input.to_lowercase().contains("предпочитаю")
-                || user_input.to_lowercase().contains("нравится")
-                || user_input.to_lowercase().contains("не люблю")
-                || user_input.to_lowercase().contains("i ")
-                || user_input.to_lowercase().contains("my ")
-                || user_input.to_lowercase().contains("i'm")
-                || user_input.to_lowercase().contains("i am");
-
-            if has_self_disclosure {
-                let session_id = format!("persona_{}", self.archetype_id);
+            let source = format!("archetype:{}", self.archetype_id);
+            let seeds: Vec<Concept> = self
+                .memory_seeds
+                .concepts
+                .iter()
+                .map(|seed| {
+                    let category = seed.category.parse().unwrap_or(ConceptCategory::Rules);
+                    Concept::new(seed.text.clone(), category, source.clone())
+                        .with_confidence(seed.confidence)
+                        .with_tags(&seed.tags)
+                        .with_knowledge_source(KnowledgeSource::Predefined)
+                })
+                .collect();
+
+            if !seeds.is_empty() {
                 let mut sm = sm.lock().unwrap();
-                if let Err(e) =
-                    sm.extract_from_dialogue(user_input, assistant_response, &session_id)
-                {
-                    eprintln!("Warning: Failed to extract concepts: {}", e);
+                concepts_added = sm.seed_concepts_blocking(seeds)?;
+                if concepts_added > 0 {
+                    sm.save_blocking()?;
                 }
             }
         }
+
+        Ok((concepts_added, narrative_added))
     }
 
     /// Extract traits into HashMap
@@ -193,6 +259,37 @@ impl Persona {
         (base + offset).clamp(0.0, 1.0)
     }
 
+    /// Sets a trait at runtime (`/persona set`) and persists the evolution state.
+    /// The value must be within 0..1; only traits of the archetype can be set
+    pub fn set_trait(&mut self, name: &str, value: f32) -> Result<TraitAdjustment> {
+        anyhow::ensure!(
+            (0.0..=1.0).contains(&value),
+            "Trait value must be between 0 and 1, got {}",
+            value
+        );
+        let Some(&base) = self.base_traits.get(name) else {
+            let mut known: Vec<&str> = self.base_traits.keys().map(String::as_str).collect();
+            known.sort();
+            anyhow::bail!("Unknown trait '{}'. Known: {}", name, known.join(", "));
+        };
+
+        let adjustment = self.evolution.set_trait(name, base, value, self.clock.unix_now());
+        self.save_evolution()?;
+        Ok(adjustment)
+    }
+
+    /// Restores the evolution state (trait offsets, counters, adjustments) from the state store
+    pub fn load_evolution(&mut self) -> Result<()> {
+        if let Some(state) = EvolutionState::load(&self.archetype_id)? {
+            self.evolution = state;
+        }
+        Ok(())
+    }
+
+    pub fn save_evolution(&self) -> Result<()> {
+        self.evolution.save(&self.archetype_id)
+    }
+
     /// Get all current traits (base + evolution)
     pub fn get_all_traits(&self) -> HashMap<String, f32> {
         let mut traits = self.base_traits.clone();
@@ -275,9 +372,24 @@ impl Persona {
     }
 
     /// Apply interaction and evolve
-    pub fn apply_interaction(&mut self, _interaction: crate::demiurge::Interaction) {
+    pub fn apply_interaction(&mut self, interaction: crate::demiurge::Interaction) {
         self.evolution.interactions_count += 1;
 
+        // the relationship arc is per user: each one builds trust separately
+        let emotion = if interaction.user_gave_feedback && !interaction.feedback_positive {
+            "disappointment"
+        } else if interaction.successful_help {
+            "gratitude"
+        } else {
+            "neutral"
+        };
+        self.narrative.update_relationship(
+            self.user.as_str(),
+            emotion,
+            interaction.emotional_depth,
+            &interaction.topics.join(", "),
+        );
+
         // Apply to evolution engine
         // This will be implemented in evolution.rs
     }
@@ -293,19 +405,60 @@ impl Persona {
         self.narrative.load()
     }
 
+    /// Record a persona switch in the narrative and persist it
+    pub fn record_switch(&mut self, from: &str, to: &str, fresh_session: bool, carry_semantic: bool) {
+        let description = format!(
+            "Persona switch {} → {} (session: {}, semantic memory: {})",
+            from,
+            to,
+            if fresh_session { "fresh" } else { "continued" },
+            if carry_semantic { "shared" } else { "isolated" }
+        );
+        self.narrative.add_milestone("persona_switch", &description, "system", 0.0);
+        if let Err(e) = self.narrative.save() {
+            eprintln!("WARNING: Failed to save narrative: {}", e);
+        }
+    }
+
+    /// Where the session context with the current user is stored
+    fn context_key(&self) -> String {
+        ContextStorage::key(&self.archetype_id, &self.user)
+    }
+
+    fn new_context(&self) -> PersonaSessionContext {
+        let mut context = PersonaSessionContext::new(&self.archetype_id);
+        context.user = self.user.clone();
+        context
+    }
+
     pub fn load_session_context(&mut self) -> Result<Option<PersonaSessionContext>> {
-        if ContextStorage::is_expired(&self.archetype_id, MAX_CONTEXT_AGE_DAYS) {
-            let _ = ContextStorage::delete(&self.archetype_id);
+        if ContextStorage::is_expired(&self.context_key(), MAX_CONTEXT_AGE_DAYS, self.clock.unix_now()) {
+            let _ = ContextStorage::delete(&self.context_key());
             return Ok(None);
         }
 
-        Ok(ContextStorage::load(&self.archetype_id)?)
+        let context = ContextStorage::load(&self.context_key())?;
+        if let Some(ref context) = context {
+            self.address = context.address.clone();
+        }
+        Ok(context)
     }
 
     pub fn save_session_context<D: LlmPipeline>(
         &self,
-        dialogue_manager: &DialogueManager,
+        dialogue_manager: &mut DialogueManager,
+        pipeline: &D,
+    ) -> Result<Option<PersonaSessionContext>> {
+        self.save_session_context_with_summary(dialogue_manager, pipeline, None)
+    }
+
+    /// Like [`Persona::save_session_context`], reusing a stored session summary when there is one.
+    /// The analysis is also kept in the session itself for the timeline
+    pub fn save_session_context_with_summary<D: LlmPipeline>(
+        &self,
+        dialogue_manager: &mut DialogueManager,
         pipeline: &D,
+        summary: Option<String>,
     ) -> Result<Option<PersonaSessionContext>> {
         let turn_count = dialogue_manager.current_session().turn_count();
 
@@ -313,28 +466,63 @@ impl Persona {
             return Ok(None);
         }
 
-        let analysis = dialogue_manager.analyze_for_context(pipeline, 10)?;
+        let analysis = dialogue_manager.analyze_for_context_with_summary(pipeline, 10, summary)?;
+        dialogue_manager.record_analysis(&analysis);
 
-        let now = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap_or_default()
-            .as_secs();
+        let now = self.clock.unix_now();
 
         let previous_session_id = dialogue_manager.current_session().id.to_string();
 
-        let mut context = PersonaSessionContext::new(&self.archetype_id.clone());
+        let mut context = self.new_context();
         context.previous_session_id = previous_session_id;
         context.last_interaction_date = now;
         context.summary = analysis.summary;
         context.key_topics = analysis.key_topics;
         context.emotional_state = analysis.emotional_state;
         context.last_topic = analysis.last_topic;
+        context.address = self.address.clone();
 
         ContextStorage::save(&context)?;
 
         Ok(Some(context))
     }
 
+    /// Store a conversation checkpoint in the session context, keeping the saved summary
+    pub fn save_checkpoint(&self, checkpoint: ConversationCheckpoint) -> Result<()> {
+        let mut context = ContextStorage::load(&self.context_key())?
+            .unwrap_or_else(|| self.new_context());
+        context.previous_session_id = checkpoint.session_id.clone();
+        context.last_interaction_date = checkpoint.saved_at;
+        context.checkpoint = Some(checkpoint);
+        context.address = self.address.clone();
+        ContextStorage::save(&context)?;
+        Ok(())
+    }
+
+    /// Updates the address style from a user message, persisting it when it changes
+    pub fn observe_address(&mut self, user_input: &str) -> AddressStyle {
+        if self.address.observe(user_input) {
+            if let Err(e) = self.save_address() {
+                eprintln!("WARNING: Failed to save address style: {}", e);
+            }
+        }
+        self.address_style()
+    }
+
+    /// Address style for the next answer
+    pub fn address_style(&self) -> AddressStyle {
+        self.address.resolve(self.communication.use_honorifics)
+    }
+
+    /// Stores the address style in the session context, keeping the rest of it
+    pub fn save_address(&self) -> Result<()> {
+        let mut context = ContextStorage::load(&self.context_key())?
+            .unwrap_or_else(|| self.new_context());
+        context.address = self.address.clone();
+        ContextStorage::save(&context)?;
+        Ok(())
+    }
+
     pub fn generate_contextual_greeting(&self, context: &PersonaSessionContext) -> String {
         let emoji = match self.communication.emoji_frequency.as_str() {
             "frequent" => " 💫✨",
@@ -404,8 +592,8 @@ impl Persona {
     }
 
     pub fn has_saved_context(&self) -> bool {
-        ContextStorage::exists(&self.archetype_id)
-            && !ContextStorage::is_expired(&self.archetype_id, MAX_CONTEXT_AGE_DAYS)
+        ContextStorage::exists(&self.context_key())
+            && !ContextStorage::is_expired(&self.context_key(), MAX_CONTEXT_AGE_DAYS, self.clock.unix_now())
     }
 }
 
@@ -441,3 +629,25 @@ impl From<&Persona> for PersonaInfo {
         }
     }
 }
+
+/// Concepts of a self-disclosure into `sm`
+pub fn extract_concepts_into(
+    sm: &Mutex<SemanticMemoryManager>,
+    session_id: &str,
+    user_input: &str,
+    assistant_response: &str,
+) {
+    // rules only: the caller has already asked the LLM about unclear messages
+    if !is_self_disclosure(user_input, None).unwrap_or(false) {
+        return;
+    }
+    let mut sm = sm.lock().unwrap();
+    match sm.extract_from_dialogue_blocking(user_input, assistant_response, &TurnRef::session(session_id)) {
+        Ok(0) => metrics::record_extraction(ExtractionResult::Empty),
+        Ok(_) => metrics::record_extraction(ExtractionResult::Ok),
+        Err(e) => {
+            metrics::record_extraction(ExtractionResult::Error);
+            eprintln!("Warning: Failed to extract concepts: {}", e);
+        }
+    }
+}
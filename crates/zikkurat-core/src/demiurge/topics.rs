@@ -0,0 +1,77 @@
+//! Forbidden Topics - What a Persona Does Not Engage With
+//!
+//! An archetype can list topics it stays away from (medical advice for the
+//! "girlfriend" persona, say). A topic is a set of markers matched like the
+//! sensitive-concept markers: word beginnings, or substrings for phrases.
+//! Memories on such topics are kept out of the prompt, and a query on one gets
+//! a refusal directive instead of an answer.
+
+use serde::{Deserialize, Serialize};
+
+/// A topic the persona declines to discuss
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ForbiddenTopic {
+    /// Short name shown in logs and in the refusal directive ("medical advice")
+    pub topic: String,
+    /// Word beginnings ("диагноз", "medicat"); phrases with a space or hyphen match as substrings
+    pub markers: Vec<String>,
+    /// How to decline; a generic refusal when empty
+    #[serde(default)]
+    pub refusal: String,
+}
+
+impl ForbiddenTopic {
+    /// The text touches this topic
+    pub fn matches(&self, text: &str) -> bool {
+        let lower = text.to_lowercase();
+        let words: Vec<&str> = lower
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|w| !w.is_empty())
+            .collect();
+        self.markers.iter().map(|m| m.to_lowercase()).any(|m| {
+            if m.contains(|c: char| !c.is_alphanumeric()) {
+                lower.contains(&m)
+            } else {
+                !m.is_empty() && words.iter().any(|w| w.starts_with(&m))
+            }
+        })
+    }
+
+    /// Constraint for the prompt when the query is on this topic
+    pub fn directive(&self) -> String {
+        if self.refusal.is_empty() {
+            format!(
+                "Тема «{}» не для тебя: мягко откажись её обсуждать и предложи поговорить о другом",
+                self.topic
+            )
+        } else {
+            format!("Тема «{}» не для тебя: {}", self.topic, self.refusal)
+        }
+    }
+}
+
+/// First topic of `topics` the text touches
+pub fn find_forbidden<'a>(topics: &'a [ForbiddenTopic], text: &str) -> Option<&'a ForbiddenTopic> {
+    topics.iter().find(|t| t.matches(text))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_forbidden_topic_matching() {
+        let topics = vec![ForbiddenTopic {
+            topic: "medical advice".to_string(),
+            markers: vec!["лекарств".into(), "диагноз".into(), "Medicat".into(), "side effect".into()],
+            refusal: String::new(),
+        }];
+        let found = find_forbidden(&topics, "Какие лекарства пить от простуды?").map(|t| t.topic.as_str());
+        assert_eq!(found, Some("medical advice"));
+        assert!(find_forbidden(&topics, "Does this medication have side effects?").is_some());
+        // markers are word beginnings, not arbitrary substrings
+        assert!(find_forbidden(&topics, "premedication protocols").is_none());
+        assert!(find_forbidden(&topics, "Как дела на работе?").is_none());
+        assert!(topics[0].directive().contains("«medical advice»"));
+    }
+}
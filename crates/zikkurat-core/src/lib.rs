@@ -0,0 +1,13 @@
+//! ZIGGURAT MIND - ядро памяти
+//!
+//! VectorStore, эпизодическая и семантическая память с подключаемым [`priests::embeddings::Embedder`],
+//! персоны (`demiurge`) и общие счётчики генерации (`logos`).
+//! Без фичи `inference` собирается без candle и tokio (в том числе под `wasm32`),
+//! эмбеддинги тогда приходят от внешнего сервиса через реализацию `Embedder`.
+
+pub mod demiurge;
+pub mod logos;
+pub mod priests;
+pub mod testing;
+pub mod totems;
+pub mod utils;
@@ -0,0 +1,394 @@
+//! Service metrics in Prometheus text format
+//!
+//! Counters, gauges and latency histograms fed by [`profiling::end_response`](super::profiling)
+//! and the memory subsystems: embedding and vector search time, tokens per second and how
+//! often a memory lookup finds something. The same registry is rendered for `GET /metrics`
+//! (`--metrics-addr`, or the HTTP API in `--serve` mode) and for `/stats metrics` in the CLI.
+
+use parking_lot::Mutex;
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::time::Duration;
+
+use super::profiling::{ResponseTimings, Stage};
+
+/// Upper bounds of latency buckets, seconds
+const LATENCY_BUCKETS: [f64; 11] = [0.01, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0];
+/// Buckets for embedding and vector search, which take milliseconds
+const FAST_BUCKETS: [f64; 11] = [0.0005, 0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0];
+
+/// Outcome of a concept extraction pass
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExtractionResult {
+    /// At least one concept stored
+    Ok,
+    /// Ran fine but found nothing
+    Empty,
+    Error,
+}
+
+impl ExtractionResult {
+    fn label(&self) -> &'static str {
+        match self {
+            ExtractionResult::Ok => "ok",
+            ExtractionResult::Empty => "empty",
+            ExtractionResult::Error => "error",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Histogram {
+    bounds: &'static [f64; LATENCY_BUCKETS.len()],
+    buckets: [u64; LATENCY_BUCKETS.len()],
+    count: u64,
+    sum: f64,
+}
+
+impl Default for Histogram {
+    fn default() -> Self {
+        Self {
+            bounds: &LATENCY_BUCKETS,
+            buckets: Default::default(),
+            count: 0,
+            sum: 0.0,
+        }
+    }
+}
+
+impl Histogram {
+    fn fast() -> Self {
+        Self {
+            bounds: &FAST_BUCKETS,
+            ..Default::default()
+        }
+    }
+
+    fn observe(&mut self, secs: f64) {
+        for (bucket, bound) in self.buckets.iter_mut().zip(self.bounds) {
+            if secs <= *bound {
+                *bucket += 1;
+            }
+        }
+        self.count += 1;
+        self.sum += secs;
+    }
+
+    fn mean(&self) -> f64 {
+        if self.count == 0 {
+            0.0
+        } else {
+            self.sum / self.count as f64
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Registry {
+    requests: u64,
+    tokens: u64,
+    /// Generations aborted by the watchdog
+    timeouts: u64,
+    /// Repetition loops: (penalized, stopped)
+    loops: (u64, u64),
+    extractions: BTreeMap<&'static str, u64>,
+    generation: Histogram,
+    retrieval: Histogram,
+    response: Histogram,
+    /// store name -> entries
+    store_sizes: BTreeMap<&'static str, u64>,
+    /// Texts through the embedding model and time per model call
+    embedded_texts: u64,
+    embedding: Histogram,
+    /// Vector store searches
+    search: Histogram,
+    /// Generation speed of the last answer
+    tokens_per_second: f64,
+    /// (store, found something) -> lookups
+    lookups: BTreeMap<(&'static str, bool), u64>,
+}
+
+impl Registry {
+    fn new() -> Self {
+        Self {
+            embedding: Histogram::fast(),
+            search: Histogram::fast(),
+            ..Default::default()
+        }
+    }
+}
+
+static REGISTRY: Mutex<Option<Registry>> = parking_lot::const_mutex(None);
+
+fn with_registry<R>(f: impl FnOnce(&mut Registry) -> R) -> R {
+    let mut guard = REGISTRY.lock();
+    f(guard.get_or_insert_with(Registry::new))
+}
+
+/// Учитывает завершённый ответ (вызывается из `profiling::end_response`)
+pub fn record_response(timings: &ResponseTimings) {
+    let generation = timings.get(Stage::Forward) + timings.get(Stage::Sampling);
+    with_registry(|r| {
+        r.requests += 1;
+        r.tokens += timings.tokens as u64;
+        r.generation.observe(secs(generation));
+        r.retrieval.observe(secs(timings.get(Stage::Retrieval)));
+        r.response.observe(secs(timings.total));
+        if timings.tokens > 0 && !generation.is_zero() {
+            r.tokens_per_second = timings.tokens as f64 / secs(generation);
+        }
+    });
+}
+
+/// Учитывает вызов модели эмбеддингов на `texts` текстов (кэш не считается)
+pub fn record_embedding(texts: usize, elapsed: Duration) {
+    with_registry(|r| {
+        r.embedded_texts += texts as u64;
+        r.embedding.observe(secs(elapsed));
+    });
+}
+
+/// Учитывает поиск по векторному хранилищу
+pub fn record_search(elapsed: Duration) {
+    with_registry(|r| r.search.observe(secs(elapsed)));
+}
+
+/// Учитывает обращение к памяти `store`: `hit` - нашлось что-то достаточно близкое
+pub fn record_lookup(store: &'static str, hit: bool) {
+    with_registry(|r| *r.lookups.entry((store, hit)).or_default() += 1);
+}
+
+/// Доля обращений к памяти `store`, которые что-то нашли
+pub fn hit_rate(store: &str) -> Option<f64> {
+    with_registry(|r| {
+        let (mut hits, mut total) = (0, 0);
+        for ((s, hit), n) in &r.lookups {
+            if *s == store {
+                total += n;
+                if *hit {
+                    hits += n;
+                }
+            }
+        }
+        (total > 0).then(|| hits as f64 / total as f64)
+    })
+}
+
+/// Учитывает генерацию, прерванную watchdog'ом
+pub fn record_timeout() {
+    with_registry(|r| r.timeouts += 1);
+}
+
+/// Учитывает петлю повторов: `stopped` - генерация остановлена, иначе петлю разорвал штраф
+pub fn record_loop(stopped: bool) {
+    with_registry(|r| {
+        if stopped {
+            r.loops.1 += 1;
+        } else {
+            r.loops.0 += 1;
+        }
+    });
+}
+
+/// Учитывает проход экстракции концептов
+pub fn record_extraction(result: ExtractionResult) {
+    with_registry(|r| *r.extractions.entry(result.label()).or_default() += 1);
+}
+
+/// Размер хранилища памяти: `episodic`, `semantic`, `sessions`, ...
+pub fn set_store_size(store: &'static str, entries: usize) {
+    with_registry(|r| {
+        r.store_sizes.insert(store, entries as u64);
+    });
+}
+
+/// Доля успешных экстракций (ok / все попытки)
+pub fn extraction_success_rate() -> Option<f64> {
+    with_registry(|r| {
+        let total: u64 = r.extractions.values().sum();
+        (total > 0).then(|| *r.extractions.get("ok").unwrap_or(&0) as f64 / total as f64)
+    })
+}
+
+/// Prometheus text exposition format
+pub fn render_prometheus() -> String {
+    with_registry(|r| {
+        let mut out = String::new();
+        counter(&mut out, "ziggurat_requests_total", "Answered requests", r.requests);
+        counter(&mut out, "ziggurat_tokens_generated_total", "Generated tokens", r.tokens);
+        counter(&mut out, "ziggurat_generation_timeouts_total", "Generations aborted by the watchdog", r.timeouts);
+        let _ = writeln!(out, "# HELP ziggurat_generation_loops_total Repetition loops by outcome");
+        let _ = writeln!(out, "# TYPE ziggurat_generation_loops_total counter");
+        let _ = writeln!(out, "ziggurat_generation_loops_total{{outcome=\"penalized\"}} {}", r.loops.0);
+        let _ = writeln!(out, "ziggurat_generation_loops_total{{outcome=\"stopped\"}} {}", r.loops.1);
+
+        let _ = writeln!(out, "# HELP ziggurat_extractions_total Concept extraction passes by result");
+        let _ = writeln!(out, "# TYPE ziggurat_extractions_total counter");
+        for result in ["ok", "empty", "error"] {
+            let n = r.extractions.get(result).copied().unwrap_or(0);
+            let _ = writeln!(out, "ziggurat_extractions_total{{result=\"{}\"}} {}", result, n);
+        }
+
+        histogram(&mut out, "ziggurat_generation_seconds", "Model forward and sampling time per answer", &r.generation);
+        histogram(&mut out, "ziggurat_retrieval_seconds", "Memory retrieval time per answer", &r.retrieval);
+        histogram(&mut out, "ziggurat_response_seconds", "End-to-end time per answer", &r.response);
+        let _ = writeln!(out, "# HELP ziggurat_tokens_per_second Generation speed of the last answer");
+        let _ = writeln!(out, "# TYPE ziggurat_tokens_per_second gauge");
+        let _ = writeln!(out, "ziggurat_tokens_per_second {:.2}", r.tokens_per_second);
+
+        counter(&mut out, "ziggurat_embedded_texts_total", "Texts through the embedding model", r.embedded_texts);
+        histogram(&mut out, "ziggurat_embedding_seconds", "Embedding model time per call", &r.embedding);
+        histogram(&mut out, "ziggurat_vector_search_seconds", "Vector store search time", &r.search);
+
+        let _ = writeln!(out, "# HELP ziggurat_memory_lookups_total Memory lookups by store and whether they found anything");
+        let _ = writeln!(out, "# TYPE ziggurat_memory_lookups_total counter");
+        for ((store, hit), n) in &r.lookups {
+            let result = if *hit { "hit" } else { "miss" };
+            let _ = writeln!(out, "ziggurat_memory_lookups_total{{store=\"{}\",result=\"{}\"}} {}", store, result, n);
+        }
+
+        let _ = writeln!(out, "# HELP ziggurat_memory_entries Entries in memory stores");
+        let _ = writeln!(out, "# TYPE ziggurat_memory_entries gauge");
+        for (store, n) in &r.store_sizes {
+            let _ = writeln!(out, "ziggurat_memory_entries{{store=\"{}\"}} {}", store, n);
+        }
+        out
+    })
+}
+
+/// Краткий отчёт для `/stats metrics`
+pub fn report() -> String {
+    let rate = extraction_success_rate();
+    with_registry(|r| {
+        let mut out = format!(
+            "📈 Requests: {}, tokens generated: {}, watchdog timeouts: {}, loops penalized/stopped: {}/{}\n",
+            r.requests, r.tokens, r.timeouts, r.loops.0, r.loops.1
+        );
+        let _ = writeln!(
+            out,
+            "   generation avg {:.2}s, retrieval avg {:.3}s, response avg {:.2}s",
+            r.generation.mean(),
+            r.retrieval.mean(),
+            r.response.mean()
+        );
+        let _ = writeln!(
+            out,
+            "   {:.1} tokens/s, embedding avg {:.1} ms ({} texts), vector search avg {:.2} ms",
+            r.tokens_per_second,
+            r.embedding.mean() * 1000.0,
+            r.embedded_texts,
+            r.search.mean() * 1000.0
+        );
+        let stores: std::collections::BTreeSet<&str> = r.lookups.keys().map(|(store, _)| *store).collect();
+        let rates: Vec<String> = stores
+            .into_iter()
+            .map(|store| {
+                let hits = r.lookups.get(&(store, true)).copied().unwrap_or(0);
+                let misses = r.lookups.get(&(store, false)).copied().unwrap_or(0);
+                format!("{} {:.0}%", store, hits as f64 * 100.0 / (hits + misses) as f64)
+            })
+            .collect();
+        if !rates.is_empty() {
+            let _ = writeln!(out, "   memory hit rate: {}", rates.join(", "));
+        }
+        match rate {
+            Some(rate) => {
+                let _ = writeln!(out, "   extraction success: {:.0}%", rate * 100.0);
+            }
+            None => {
+                let _ = writeln!(out, "   extraction success: n/a");
+            }
+        }
+        for (store, n) in &r.store_sizes {
+            let _ = writeln!(out, "   {} entries: {}", store, n);
+        }
+        out
+    })
+}
+
+fn counter(out: &mut String, name: &str, help: &str, value: u64) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} counter", name);
+    let _ = writeln!(out, "{} {}", name, value);
+}
+
+fn histogram(out: &mut String, name: &str, help: &str, h: &Histogram) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} histogram", name);
+    for (bound, n) in h.bounds.iter().zip(h.buckets) {
+        let _ = writeln!(out, "{}_bucket{{le=\"{}\"}} {}", name, bound, n);
+    }
+    let _ = writeln!(out, "{}_bucket{{le=\"+Inf\"}} {}", name, h.count);
+    let _ = writeln!(out, "{}_sum {}", name, h.sum);
+    let _ = writeln!(out, "{}_count {}", name, h.count);
+}
+
+fn secs(d: Duration) -> f64 {
+    d.as_secs_f64()
+}
+
+/// Отдаёт `GET /metrics` на `addr` в фоновом потоке
+pub fn serve(addr: &str) -> anyhow::Result<std::net::SocketAddr> {
+    use std::io::{BufRead, BufReader, Write};
+
+    let listener = std::net::TcpListener::bind(addr)?;
+    let local = listener.local_addr()?;
+
+    std::thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let mut request_line = String::new();
+            if BufReader::new(&stream).read_line(&mut request_line).is_err() {
+                continue;
+            }
+            let (status, body) = if request_line.starts_with("GET /metrics") {
+                ("200 OK", render_prometheus())
+            } else {
+                ("404 Not Found", "not found\n".to_string())
+            };
+            let mut stream = stream;
+            let _ = write!(
+                stream,
+                "HTTP/1.1 {}\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                status,
+                body.len(),
+                body
+            );
+        }
+    });
+
+    Ok(local)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_prometheus_rendering() {
+        let mut timings = ResponseTimings {
+            tokens: 7,
+            total: Duration::from_millis(300),
+            ..Default::default()
+        };
+        timings.stages[1] = Duration::from_millis(200); // forward
+        record_response(&timings);
+        record_extraction(ExtractionResult::Ok);
+        record_extraction(ExtractionResult::Error);
+        set_store_size("episodic", 42);
+        record_embedding(3, Duration::from_millis(4));
+        record_search(Duration::from_micros(300));
+        record_lookup("test", true);
+        record_lookup("test", false);
+
+        let text = render_prometheus();
+        assert!(text.contains("ziggurat_memory_entries{store=\"episodic\"} 42"));
+        assert!(text.contains("ziggurat_generation_seconds_bucket{le=\"0.25\"}"));
+        assert!(text.contains("ziggurat_extractions_total{result=\"error\"}"));
+        assert!(text.contains("ziggurat_embedding_seconds_bucket{le=\"0.005\"}"));
+        assert!(text.contains("ziggurat_vector_search_seconds_bucket{le=\"0.0005\"}"));
+        assert!(text.contains("ziggurat_memory_lookups_total{store=\"test\",result=\"miss\"} 1"));
+        assert!(text.contains("# TYPE ziggurat_tokens_per_second gauge"));
+        assert_eq!(hit_rate("test"), Some(0.5));
+        assert!(report().contains("extraction success"));
+    }
+}
@@ -0,0 +1,4 @@
+//! Профилирование стадий ответа и метрики, общие для пайплайна генерации и CLI
+
+pub mod metrics;
+pub mod profiling;
@@ -0,0 +1,242 @@
+//! Latency profiling for the generation loop
+//!
+//! Each stage (tokenization, forward, sampling, detokenization, retrieval, IO)
+//! is timed with a [`StageTimer`] that also opens a tracing span, so the same
+//! numbers show up in `/stats perf`, in a `--tracing` chrome trace and in the
+//! OTLP trace of the query.
+
+use parking_lot::Mutex;
+use std::time::{Duration, Instant};
+
+/// Stage of handling a single response
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stage {
+    Tokenize,
+    Forward,
+    Sampling,
+    Detokenize,
+    Retrieval,
+    Extraction,
+    Io,
+}
+
+impl Stage {
+    pub const ALL: [Stage; 7] = [
+        Stage::Tokenize,
+        Stage::Forward,
+        Stage::Sampling,
+        Stage::Detokenize,
+        Stage::Retrieval,
+        Stage::Extraction,
+        Stage::Io,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Stage::Tokenize => "tokenize",
+            Stage::Forward => "forward",
+            Stage::Sampling => "sampling",
+            Stage::Detokenize => "detokenize",
+            Stage::Retrieval => "retrieval",
+            Stage::Extraction => "extraction",
+            Stage::Io => "io",
+        }
+    }
+
+    fn index(&self) -> usize {
+        *self as usize
+    }
+
+    /// Extraction runs its own LLM passes; they are attributed to extraction, not to the model stages
+    fn is_exclusive(&self) -> bool {
+        matches!(self, Stage::Extraction)
+    }
+}
+
+/// Timings of one response
+#[derive(Debug, Clone, Default)]
+pub struct ResponseTimings {
+    pub stages: [Duration; 7],
+    pub tokens: usize,
+    pub total: Duration,
+}
+
+impl ResponseTimings {
+    pub fn get(&self, stage: Stage) -> Duration {
+        self.stages[stage.index()]
+    }
+
+    pub fn tokens_per_sec(&self) -> f64 {
+        let forward = self.get(Stage::Forward).as_secs_f64();
+        if forward > 0.0 {
+            self.tokens as f64 / forward
+        } else {
+            0.0
+        }
+    }
+
+    pub fn format(&self) -> String {
+        let mut out = format!(
+            "   total {:>8.1} ms  ({} tokens, {:.1} tok/s forward)\n",
+            ms(self.total),
+            self.tokens,
+            self.tokens_per_sec()
+        );
+        for stage in Stage::ALL {
+            let d = self.get(stage);
+            let share = if self.total.is_zero() {
+                0.0
+            } else {
+                d.as_secs_f64() / self.total.as_secs_f64() * 100.0
+            };
+            out.push_str(&format!(
+                "   {:<10} {:>8.1} ms  {:>5.1}%\n",
+                stage.name(),
+                ms(d),
+                share
+            ));
+        }
+        out
+    }
+}
+
+#[derive(Debug, Default)]
+struct Collector {
+    current: Option<(Instant, ResponseTimings)>,
+    exclusive_depth: usize,
+    last: Option<ResponseTimings>,
+    totals: ResponseTimings,
+    responses: usize,
+}
+
+static COLLECTOR: Mutex<Option<Collector>> = parking_lot::const_mutex(None);
+
+fn with_collector<R>(f: impl FnOnce(&mut Collector) -> R) -> R {
+    let mut guard = COLLECTOR.lock();
+    f(guard.get_or_insert_with(Collector::default))
+}
+
+/// Начинает сбор таймингов нового ответа
+pub fn begin_response() {
+    with_collector(|c| c.current = Some((Instant::now(), ResponseTimings::default())));
+}
+
+/// Завершает ответ и добавляет его к накопленной статистике
+pub fn end_response(tokens: usize) -> Option<ResponseTimings> {
+    with_collector(|c| {
+        let (start, mut timings) = c.current.take()?;
+        timings.tokens = tokens;
+        timings.total = start.elapsed();
+
+        for stage in Stage::ALL {
+            c.totals.stages[stage.index()] += timings.get(stage);
+        }
+        c.totals.tokens += timings.tokens;
+        c.totals.total += timings.total;
+        c.responses += 1;
+        c.last = Some(timings.clone());
+        super::metrics::record_response(&timings);
+
+        tracing::debug!(
+            total_ms = ms(timings.total),
+            forward_ms = ms(timings.get(Stage::Forward)),
+            retrieval_ms = ms(timings.get(Stage::Retrieval)),
+            io_ms = ms(timings.get(Stage::Io)),
+            tokens = timings.tokens,
+            "response timings"
+        );
+        Some(timings)
+    })
+}
+
+fn record(stage: Stage, elapsed: Duration) {
+    with_collector(|c| {
+        if c.exclusive_depth > 0 && !stage.is_exclusive() {
+            return;
+        }
+        if let Some((_, timings)) = c.current.as_mut() {
+            timings.stages[stage.index()] += elapsed;
+        }
+    });
+}
+
+/// RAII-таймер стадии: открывает tracing span и записывает длительность при drop
+pub struct StageTimer {
+    stage: Stage,
+    start: Instant,
+    _span: tracing::span::EnteredSpan,
+}
+
+/// Начинает замер стадии
+pub fn time(stage: Stage) -> StageTimer {
+    if stage.is_exclusive() {
+        with_collector(|c| c.exclusive_depth += 1);
+    }
+    StageTimer {
+        stage,
+        start: Instant::now(),
+        // otel.name: in Jaeger the span is called after the stage, not "stage"
+        _span: tracing::trace_span!("stage", name = stage.name(), otel.name = stage.name()).entered(),
+    }
+}
+
+impl Drop for StageTimer {
+    fn drop(&mut self) {
+        if self.stage.is_exclusive() {
+            with_collector(|c| c.exclusive_depth = c.exclusive_depth.saturating_sub(1));
+        }
+        record(self.stage, self.start.elapsed());
+    }
+}
+
+/// Отчёт для `/stats perf`: последний ответ и среднее за сессию
+pub fn report() -> String {
+    with_collector(|c| {
+        let Some(last) = &c.last else {
+            return "No responses profiled yet.".to_string();
+        };
+
+        let n = c.responses as u32;
+        let mut avg = ResponseTimings {
+            tokens: c.totals.tokens / c.responses,
+            total: c.totals.total / n,
+            ..Default::default()
+        };
+        for stage in Stage::ALL {
+            avg.stages[stage.index()] = c.totals.get(stage) / n;
+        }
+
+        format!(
+            "⏱️  Last response:\n{}\n⏱️  Average over {} responses:\n{}",
+            last.format(),
+            c.responses,
+            avg.format()
+        )
+    })
+}
+
+fn ms(d: Duration) -> f64 {
+    d.as_secs_f64() * 1000.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stage_timings_aggregate() {
+        begin_response();
+        record(Stage::Forward, Duration::from_millis(30));
+        record(Stage::Forward, Duration::from_millis(20));
+        {
+            let _extraction = time(Stage::Extraction);
+            // nested model work inside extraction is not counted as forward
+            record(Stage::Forward, Duration::from_millis(100));
+        }
+        let timings = end_response(5).unwrap();
+
+        assert_eq!(timings.get(Stage::Forward), Duration::from_millis(50));
+        assert_eq!(timings.tokens, 5);
+        assert!(report().contains("Average over"));
+    }
+}
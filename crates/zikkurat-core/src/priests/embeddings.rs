@@ -0,0 +1,655 @@
+//! 🜂 Уровень 1: Жрецы Железа - Эмбеддинг движок
+//!
+//! Высокопроизводительный движок векторизации на базе intfloat/multilingual-e5-small
+//! Оптимизирован для RTX 4090 32GB с батчингом и кэшированием
+
+#![allow(dead_code)]
+
+use anyhow::Result;
+use async_trait::async_trait;
+use std::sync::Arc;
+
+#[cfg(feature = "embeddings-local")]
+use anyhow::anyhow;
+#[cfg(feature = "embeddings-local")]
+use candle_core::{DType, Device, Tensor};
+#[cfg(feature = "embeddings-local")]
+use candle_nn::VarBuilder;
+#[cfg(feature = "embeddings-local")]
+use candle_transformers::models::bert::{BertModel, Config};
+#[cfg(feature = "embeddings-local")]
+use parking_lot::RwLock;
+#[cfg(feature = "embeddings-local")]
+use std::collections::HashMap;
+#[cfg(feature = "embeddings-local")]
+use tokenizers::Tokenizer;
+
+/// Trait для эмбеддингов, поддерживает разные реализации
+pub trait Embedder: Send + Sync {
+    fn embed(&self, text: &str) -> Result<Vec<f32>>;
+    fn embedding_dim(&self) -> usize;
+
+    /// Векторы для нескольких текстов, в том же порядке. По умолчанию - по одному
+    fn embed_batch(&self, texts: &[String]) -> Result<Vec<Vec<f32>>> {
+        texts.iter().map(|text| self.embed(text)).collect()
+    }
+
+    /// Закэшированные эмбеддинги для warm-start снапшота; у эмбеддеров без кэша пусто
+    fn cached_embeddings(&self) -> Vec<(String, Vec<f32>)> {
+        Vec::new()
+    }
+
+    /// Прогревает кэш эмбеддингами из прошлого запуска
+    fn warm_cache(&self, _entries: Vec<(String, Vec<f32>)>) {}
+}
+
+/// Асинхронный доступ к эмбеддеру: вычисления уходят в blocking-пул tokio,
+/// не останавливая остальные задачи
+#[async_trait]
+pub trait AsyncEmbedder: Send + Sync {
+    async fn embed_async(&self, text: &str) -> Result<Vec<f32>>;
+
+    /// Векторы для нескольких текстов одним вызовом эмбеддера
+    async fn embed_batch_async(&self, texts: Vec<String>) -> Result<Vec<Vec<f32>>>;
+}
+
+#[cfg(feature = "runtime")]
+#[async_trait]
+impl AsyncEmbedder for Arc<dyn Embedder> {
+    async fn embed_async(&self, text: &str) -> Result<Vec<f32>> {
+        let embedder = Arc::clone(self);
+        let text = text.to_string();
+        tokio::task::spawn_blocking(move || embedder.embed(&text)).await?
+    }
+
+    async fn embed_batch_async(&self, texts: Vec<String>) -> Result<Vec<Vec<f32>>> {
+        let embedder = Arc::clone(self);
+        tokio::task::spawn_blocking(move || embedder.embed_batch(&texts)).await?
+    }
+}
+
+/// Без runtime (WASM) blocking-пула нет - эмбеддер вызывается напрямую
+#[cfg(not(feature = "runtime"))]
+#[async_trait]
+impl AsyncEmbedder for Arc<dyn Embedder> {
+    async fn embed_async(&self, text: &str) -> Result<Vec<f32>> {
+        self.embed(text)
+    }
+
+    async fn embed_batch_async(&self, texts: Vec<String>) -> Result<Vec<Vec<f32>>> {
+        self.embed_batch(&texts)
+    }
+}
+
+/// Настройки [`EmbeddingScheduler`]
+#[cfg(feature = "runtime")]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SchedulerConfig {
+    /// Сколько текстов набирается в батч, прежде чем он уходит в модель
+    pub batch_size: usize,
+    /// Сколько первый текст батча ждёт попутчиков
+    pub flush_interval: std::time::Duration,
+}
+
+#[cfg(feature = "runtime")]
+impl Default for SchedulerConfig {
+    fn default() -> Self {
+        Self {
+            batch_size: 16,
+            flush_interval: std::time::Duration::from_millis(2),
+        }
+    }
+}
+
+/// Запрос к очереди: тексты и куда вернуть их векторы
+#[cfg(feature = "runtime")]
+struct ScheduledRequest {
+    texts: Vec<String>,
+    reply: std::sync::mpsc::Sender<Result<Vec<Vec<f32>>>>,
+}
+
+/// Очередь эмбеддингов: тексты от всех потоков (ходы диалога, концепты, поиск)
+/// собираются в батчи и считаются одним проходом модели в отдельном потоке.
+/// Сам тоже [`Embedder`], поэтому подставляется вместо модели без правок менеджеров
+#[cfg(feature = "runtime")]
+pub struct EmbeddingScheduler {
+    inner: Arc<dyn Embedder>,
+    queue: parking_lot::Mutex<std::sync::mpsc::Sender<ScheduledRequest>>,
+}
+
+#[cfg(feature = "runtime")]
+impl EmbeddingScheduler {
+    pub fn new(inner: Arc<dyn Embedder>, config: SchedulerConfig) -> Result<Self> {
+        let (queue, requests) = std::sync::mpsc::channel::<ScheduledRequest>();
+        let worker = Arc::clone(&inner);
+        std::thread::Builder::new()
+            .name("embedding-scheduler".to_string())
+            .spawn(move || run_scheduler(worker.as_ref(), requests, config))?;
+        Ok(Self {
+            inner,
+            queue: parking_lot::Mutex::new(queue),
+        })
+    }
+}
+
+/// Поток очереди: ждёт первый запрос, добирает попутчиков до `batch_size` текстов или
+/// `flush_interval` и раздаёт векторы. Завершается, когда очередь закрыта
+#[cfg(feature = "runtime")]
+fn run_scheduler(embedder: &dyn Embedder, requests: std::sync::mpsc::Receiver<ScheduledRequest>, config: SchedulerConfig) {
+    while let Ok(first) = requests.recv() {
+        let deadline = std::time::Instant::now() + config.flush_interval;
+        let mut pending = first.texts.len();
+        let mut batch = vec![first];
+        while pending < config.batch_size {
+            let wait = deadline.saturating_duration_since(std::time::Instant::now());
+            match requests.recv_timeout(wait) {
+                Ok(request) => {
+                    pending += request.texts.len();
+                    batch.push(request);
+                }
+                Err(_) => break,
+            }
+        }
+
+        let texts: Vec<String> = batch.iter().flat_map(|r| r.texts.iter().cloned()).collect();
+        match embedder.embed_batch(&texts) {
+            Ok(embeddings) => {
+                let mut embeddings = embeddings.into_iter();
+                for request in batch {
+                    let own: Vec<Vec<f32>> = embeddings.by_ref().take(request.texts.len()).collect();
+                    let _ = request.reply.send(Ok(own));
+                }
+            }
+            Err(e) => {
+                for request in batch {
+                    let _ = request.reply.send(Err(anyhow::anyhow!("Embedding batch failed: {}", e)));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(feature = "runtime")]
+impl Embedder for EmbeddingScheduler {
+    fn embed(&self, text: &str) -> Result<Vec<f32>> {
+        self.embed_batch(&[text.to_string()])?
+            .pop()
+            .ok_or_else(|| anyhow::anyhow!("Embedding scheduler returned no vector"))
+    }
+
+    fn embedding_dim(&self) -> usize {
+        self.inner.embedding_dim()
+    }
+
+    fn embed_batch(&self, texts: &[String]) -> Result<Vec<Vec<f32>>> {
+        if texts.is_empty() {
+            return Ok(Vec::new());
+        }
+        let (reply, response) = std::sync::mpsc::channel();
+        let request = ScheduledRequest {
+            texts: texts.to_vec(),
+            reply,
+        };
+        if self.queue.lock().send(request).is_err() {
+            // поток очереди упал - считаем сами
+            return self.inner.embed_batch(texts);
+        }
+        response
+            .recv()
+            .map_err(|_| anyhow::anyhow!("Embedding scheduler stopped"))?
+    }
+
+    fn cached_embeddings(&self) -> Vec<(String, Vec<f32>)> {
+        self.inner.cached_embeddings()
+    }
+
+    fn warm_cache(&self, entries: Vec<(String, Vec<f32>)>) {
+        self.inner.warm_cache(entries)
+    }
+}
+
+/// Конфигурация эмбеддинг движка
+#[derive(Debug, Clone)]
+pub struct EmbeddingConfig {
+    /// Размерность эмбеддинга (384 для e5-small)
+    pub embedding_dim: usize,
+    /// Максимальная длина последовательности
+    pub max_length: usize,
+    /// Размер батча для оптимизации GPU
+    pub batch_size: usize,
+    /// Размер кэша для хранения результатов
+    pub cache_size: usize,
+    /// Нормализовать ли векторы (cosine similarity)
+    pub normalize: bool,
+}
+
+impl Default for EmbeddingConfig {
+    fn default() -> Self {
+        Self {
+            embedding_dim: 384, // e5-small
+            max_length: 512,
+            batch_size: 32,   // Оптимизировано для RTX 4090
+            cache_size: 1000, // Кэш для часто используемых текстов
+            normalize: true,  // Для cosine similarity
+        }
+    }
+}
+
+/// Высокопроизводительный эмбеддинг движок
+#[cfg(feature = "embeddings-local")]
+pub struct EmbeddingEngine {
+    /// BERT модель для векторизации
+    model: BertModel,
+    /// Токенайзер для предобработки текста
+    tokenizer: Tokenizer,
+    /// Устройство вычислений (GPU/CPU)
+    device: Device,
+    /// Конфигурация движка
+    config: EmbeddingConfig,
+    /// Кэш для хранения вычисленных эмбеддингов
+    cache: Arc<RwLock<HashMap<String, Vec<f32>>>>,
+    /// Статистика использования
+    stats: Arc<RwLock<EmbeddingStats>>,
+}
+
+/// Статистика эмбеддинг движка
+#[derive(Debug, Default, Clone)]
+pub struct EmbeddingStats {
+    pub total_requests: usize,
+    pub cache_hits: usize,
+    pub cache_misses: usize,
+    pub total_tokens_processed: usize,
+    pub avg_batch_size: f32,
+}
+
+#[cfg(feature = "embeddings-local")]
+impl EmbeddingEngine {
+    /// Создает новый эмбеддинг движок
+    pub fn new(model_path: &str, device: Device) -> Result<Self> {
+        let config = EmbeddingConfig::default();
+        Self::with_config(model_path, device, config)
+    }
+
+    /// Создает движок с кастомной конфигурацией
+    pub fn with_config(model_path: &str, device: Device, config: EmbeddingConfig) -> Result<Self> {
+        println!("🧠 Загрузка эмбеддинг модели: {}", model_path);
+
+        // Загрузка конфигурации модели
+        let config_path = std::path::Path::new(model_path).join("config.json");
+        let config_content = std::fs::read_to_string(config_path)?;
+        let model_config: Config = serde_json::from_str(&config_content)?;
+
+        // Загрузка весов модели
+        let weights_path = std::path::Path::new(model_path).join("model.safetensors");
+        let vb =
+            unsafe { VarBuilder::from_mmaped_safetensors(&[&weights_path], DType::F32, &device)? };
+        let model = BertModel::load(vb, &model_config)?;
+
+        // Загрузка токенайзера
+        let tokenizer_path = std::path::Path::new(model_path).join("tokenizer.json");
+        let tokenizer = Tokenizer::from_file(tokenizer_path)
+            .map_err(|e| anyhow!("Failed to load tokenizer: {}", e))?;
+
+        println!(
+            "✅ Эмбеддинг движок загружен (dim: {})",
+            config.embedding_dim
+        );
+
+        Ok(Self {
+            model,
+            tokenizer,
+            device,
+            config,
+            cache: Arc::new(RwLock::new(HashMap::new())),
+            stats: Arc::new(RwLock::new(EmbeddingStats::default())),
+        })
+    }
+
+    /// Векторизует один текст
+    pub fn embed(&self, text: &str) -> Result<Vec<f32>> {
+        // Проверяем кэш
+        if let Some(embedding) = self.get_from_cache(text) {
+            self.update_stats(true, 0);
+            return Ok(embedding);
+        }
+
+        // Вычисляем эмбеддинг
+        let started = std::time::Instant::now();
+        let embedding = {
+            let _span = tracing::debug_span!("embed", texts = 1).entered();
+            self.compute_embedding(text)?
+        };
+        crate::logos::metrics::record_embedding(1, started.elapsed());
+
+        // Сохраняем в кэш
+        self.add_to_cache(text.to_string(), embedding.clone());
+        self.update_stats(false, 1);
+
+        Ok(embedding)
+    }
+
+    /// Векторизует батч текстов с оптимизацией GPU
+    pub fn embed_batch(&self, texts: &[String]) -> Result<Vec<Vec<f32>>> {
+        if texts.is_empty() {
+            return Ok(vec![]);
+        }
+
+        // Проверяем кэш для каждого текста
+        let mut results: Vec<Vec<f32>> = Vec::with_capacity(texts.len());
+        let mut uncached_texts: Vec<(usize, String)> = Vec::new();
+
+        for (i, text) in texts.iter().enumerate() {
+            if let Some(embedding) = self.get_from_cache(text) {
+                results.push(embedding);
+                self.update_stats(true, 0);
+            } else {
+                results.push(Vec::new()); // Заполнитель
+                uncached_texts.push((i, text.clone()));
+            }
+        }
+
+        // Вычисляем эмбеддинги для незакэшированных текстов батчами
+        if !uncached_texts.is_empty() {
+            let started = std::time::Instant::now();
+            let batch_embeddings = {
+                let _span = tracing::debug_span!("embed", texts = uncached_texts.len()).entered();
+                self.compute_batch_embeddings(
+                    &uncached_texts
+                        .iter()
+                        .map(|(_, t)| t.as_str())
+                        .collect::<Vec<_>>(),
+                )?
+            };
+            crate::logos::metrics::record_embedding(uncached_texts.len(), started.elapsed());
+
+            // Обновляем результаты и кэш
+            for ((idx, text), embedding) in uncached_texts.iter().zip(batch_embeddings.iter()) {
+                results[*idx] = embedding.clone();
+                self.add_to_cache(text.clone(), embedding.clone());
+            }
+
+            self.update_stats(false, uncached_texts.len());
+        }
+
+        Ok(results)
+    }
+
+    /// Вычисляет эмбеддинг для одного текста
+    fn compute_embedding(&self, text: &str) -> Result<Vec<f32>> {
+        // Предобработка текста для e5-small
+        let processed_text = format!("query: {}", text);
+
+        // Токенизация
+        let tokens = self
+            .tokenizer
+            .encode(processed_text.as_str(), true)
+            .map_err(|e| anyhow!("Tokenization failed: {}", e))?;
+
+        // Подготовка тензоров (2D: batch_size=1, seq_len)
+        let token_ids = Tensor::new(tokens.get_ids(), &self.device)?.unsqueeze(0)?;
+        let attention_mask =
+            Tensor::new(tokens.get_attention_mask(), &self.device)?.unsqueeze(0)?;
+
+        // Forward pass
+        let output = self.model.forward(&token_ids, &attention_mask, None)?;
+
+        // Mean pooling для получения эмбеддинга
+        let pooled = output.mean(1)?.squeeze(0)?;
+        let embedding = if self.config.normalize {
+            self.l2_normalize(&pooled.to_vec1()?)?
+        } else {
+            pooled.to_vec1()?
+        };
+
+        Ok(embedding)
+    }
+
+    /// Вычисляет эмбеддинги для батча текстов
+    fn compute_batch_embeddings(&self, texts: &[&str]) -> Result<Vec<Vec<f32>>> {
+        let mut embeddings = Vec::with_capacity(texts.len());
+
+        // Обрабатываем батчами для оптимизации GPU
+        for chunk in texts.chunks(self.config.batch_size) {
+            // Предобработка для e5-small
+            let processed_texts: Vec<String> =
+                chunk.iter().map(|t| format!("query: {}", t)).collect();
+
+            // Токенизация батча
+            let encodings = processed_texts
+                .iter()
+                .map(|text| self.tokenizer.encode(text.as_str(), true))
+                .collect::<std::result::Result<Vec<_>, _>>()
+                .map_err(|e| anyhow!("Tokenization failed: {}", e))?;
+
+            // Тексты разной длины дополняются до самого длинного; маска скрывает дополнение
+            let batch_size = chunk.len();
+            let seq_len = encodings.iter().map(|e| e.get_ids().len()).max().unwrap_or(0);
+            let mut all_token_ids: Vec<u32> = Vec::with_capacity(batch_size * seq_len);
+            let mut all_attention_masks: Vec<u32> = Vec::with_capacity(batch_size * seq_len);
+            for encoding in &encodings {
+                let padding = seq_len - encoding.get_ids().len();
+                all_token_ids.extend(encoding.get_ids());
+                all_token_ids.extend(std::iter::repeat_n(0, padding));
+                all_attention_masks.extend(encoding.get_attention_mask());
+                all_attention_masks.extend(std::iter::repeat_n(0, padding));
+            }
+
+            let token_ids = Tensor::from_vec(all_token_ids, (batch_size, seq_len), &self.device)?;
+            let attention_mask =
+                Tensor::from_vec(all_attention_masks, (batch_size, seq_len), &self.device)?;
+
+            // Forward pass
+            let output = self.model.forward(&token_ids, &attention_mask, None)?;
+
+            // Mean pooling для каждого элемента батча - только по его собственным токенам
+            for (i, encoding) in encodings.iter().enumerate() {
+                let pooled = output.get(i)?.narrow(0, 0, encoding.get_ids().len())?.mean(0)?;
+                let embedding = if self.config.normalize {
+                    self.l2_normalize(&pooled.to_vec1()?)?
+                } else {
+                    pooled.to_vec1()?
+                };
+                embeddings.push(embedding);
+            }
+        }
+
+        Ok(embeddings)
+    }
+
+    /// L2 нормализация вектора
+    fn l2_normalize(&self, vec: &[f32]) -> Result<Vec<f32>> {
+        let norm: f32 = vec.iter().map(|x| x * x).sum::<f32>().sqrt();
+        if norm == 0.0 {
+            return Ok(vec.to_vec());
+        }
+        Ok(vec.iter().map(|x| x / norm).collect())
+    }
+
+    /// Проверяет кэш и возвращает эмбеддинг если найден
+    fn get_from_cache(&self, text: &str) -> Option<Vec<f32>> {
+        let cache = self.cache.read();
+        cache.get(text).cloned()
+    }
+
+    /// Добавляет эмбеддинг в кэш с LRU eviction
+    fn add_to_cache(&self, text: String, embedding: Vec<f32>) {
+        let mut cache = self.cache.write();
+
+        // Если кэш переполнен, удаляем самые старые записи
+        if cache.len() >= self.config.cache_size {
+            // Простая LRU стратегия - удаляем первые 20% записей
+            let remove_count = self.config.cache_size / 5;
+            let keys_to_remove: Vec<String> = cache.keys().take(remove_count).cloned().collect();
+
+            for key in keys_to_remove {
+                cache.remove(&key);
+            }
+        }
+
+        cache.insert(text, embedding);
+    }
+
+    /// Обновляет статистику
+    fn update_stats(&self, cache_hit: bool, tokens_processed: usize) {
+        let mut stats = self.stats.write();
+        stats.total_requests += 1;
+
+        if cache_hit {
+            stats.cache_hits += 1;
+        } else {
+            stats.cache_misses += 1;
+            stats.total_tokens_processed += tokens_processed;
+        }
+    }
+
+    /// Вычисляет косинусное сходство между двумя векторами
+    pub fn cosine_similarity(&self, a: &[f32], b: &[f32]) -> Result<f32> {
+        if a.len() != b.len() {
+            return Err(anyhow!(
+                "Vector dimensions don't match: {} vs {}",
+                a.len(),
+                b.len()
+            ));
+        }
+
+        let dot_product: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+        let norm_a: f32 = a.iter().map(|x| x * x).sum::<f32>().sqrt();
+        let norm_b: f32 = b.iter().map(|x| x * x).sum::<f32>().sqrt();
+
+        if norm_a == 0.0 || norm_b == 0.0 {
+            return Ok(0.0);
+        }
+
+        Ok(dot_product / (norm_a * norm_b))
+    }
+
+    /// Возвращает статистику использования
+    pub fn get_stats(&self) -> EmbeddingStats {
+        self.stats.read().clone()
+    }
+
+    /// Очищает кэш
+    pub fn clear_cache(&self) {
+        self.cache.write().clear();
+    }
+
+    /// Возвращает размер кэша
+    pub fn cache_size(&self) -> usize {
+        self.cache.read().len()
+    }
+
+    /// Возвращает размерность эмбеддинга
+    pub fn embedding_dim(&self) -> usize {
+        self.config.embedding_dim
+    }
+}
+
+#[cfg(feature = "embeddings-local")]
+impl Embedder for EmbeddingEngine {
+    fn embed(&self, text: &str) -> Result<Vec<f32>> {
+        self.embed(text)
+    }
+
+    fn embedding_dim(&self) -> usize {
+        self.embedding_dim()
+    }
+
+    fn embed_batch(&self, texts: &[String]) -> Result<Vec<Vec<f32>>> {
+        EmbeddingEngine::embed_batch(self, texts)
+    }
+
+    fn cached_embeddings(&self) -> Vec<(String, Vec<f32>)> {
+        let cache = self.cache.read();
+        cache.iter().map(|(text, embedding)| (text.clone(), embedding.clone())).collect()
+    }
+
+    fn warm_cache(&self, entries: Vec<(String, Vec<f32>)>) {
+        let dim = self.embedding_dim();
+        for (text, embedding) in entries.into_iter().take(self.config.cache_size) {
+            if embedding.len() == dim {
+                self.add_to_cache(text, embedding);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_embedding_config_default() {
+        let config = EmbeddingConfig::default();
+        assert_eq!(config.embedding_dim, 384);
+        assert_eq!(config.max_length, 512);
+        assert_eq!(config.batch_size, 32);
+        assert!(config.normalize);
+    }
+
+    #[test]
+    fn test_cosine_similarity() -> Result<()> {
+        use crate::testing::MockEmbedder;
+        use crate::totems::retrieval::vector_store::cosine_similarity;
+
+        assert_eq!(cosine_similarity(&[1.0, 0.0, 0.0], &[0.0, 1.0, 0.0]), 0.0);
+        assert_eq!(cosine_similarity(&[1.0, 0.0, 0.0], &[1.0, 0.0, 0.0]), 1.0);
+        assert_eq!(cosine_similarity(&[1.0, 0.0], &[1.0, 0.0, 0.0]), 0.0);
+
+        let embedder = MockEmbedder::default();
+        let a = embedder.embed("Rust memory model")?;
+        let b = embedder.embed("rust memory model")?;
+        assert!((cosine_similarity(&a, &b) - 1.0).abs() < 1e-5);
+        Ok(())
+    }
+
+    /// Вектор - длина текста; запоминает размеры батчей
+    #[cfg(feature = "runtime")]
+    struct BatchRecorder(parking_lot::Mutex<Vec<usize>>);
+
+    #[cfg(feature = "runtime")]
+    impl Embedder for BatchRecorder {
+        fn embed(&self, text: &str) -> Result<Vec<f32>> {
+            Ok(vec![text.len() as f32])
+        }
+
+        fn embedding_dim(&self) -> usize {
+            1
+        }
+
+        fn embed_batch(&self, texts: &[String]) -> Result<Vec<Vec<f32>>> {
+            self.0.lock().push(texts.len());
+            texts.iter().map(|t| self.embed(t)).collect()
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "runtime")]
+    fn test_embedding_scheduler_batches_callers() {
+        let recorder = Arc::new(BatchRecorder(parking_lot::Mutex::new(Vec::new())));
+        let config = SchedulerConfig {
+            batch_size: 8,
+            flush_interval: std::time::Duration::from_millis(200),
+        };
+        let scheduler = Arc::new(EmbeddingScheduler::new(recorder.clone(), config).unwrap());
+
+        // four threads, two texts each: one full batch, every caller gets its own vectors
+        let handles: Vec<_> = (1..=4)
+            .map(|n| {
+                let scheduler = scheduler.clone();
+                std::thread::spawn(move || {
+                    let texts = vec!["x".repeat(n), "y".repeat(n * 10)];
+                    (n, scheduler.embed_batch(&texts).unwrap())
+                })
+            })
+            .collect();
+        for handle in handles {
+            let (n, embeddings) = handle.join().unwrap();
+            assert_eq!(embeddings, vec![vec![n as f32], vec![(n * 10) as f32]]);
+        }
+        assert_eq!(*recorder.0.lock(), vec![8]);
+
+        // a lone text leaves after the flush interval
+        assert_eq!(scheduler.embed("abc").unwrap(), vec![3.0]);
+        assert_eq!(recorder.0.lock().last(), Some(&1));
+    }
+}
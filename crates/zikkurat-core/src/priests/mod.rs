@@ -0,0 +1,7 @@
+#[cfg(feature = "embeddings-local")]
+pub mod device;
+#[cfg(feature = "embeddings-local")]
+pub mod dummy_embeddings;
+pub mod embeddings;
+#[cfg(feature = "embeddings-remote")]
+pub mod remote_embeddings;
@@ -0,0 +1,306 @@
+//! 🌐 Удалённые эмбеддинги
+//!
+//! Без локальной модели e5 память всё равно работает: векторы считает сервер.
+//! [`RemoteEmbedder`] говорит на двух протоколах - OpenAI-совместимом
+//! `/v1/embeddings` (OpenAI, vLLM, LM Studio, llama.cpp server) и Ollama
+//! `/api/embed`. Батч из [`super::embeddings::EmbeddingScheduler`] уходит одним
+//! запросом, а размерность узнаётся пробным запросом при подключении.
+
+use anyhow::{Context, Result};
+use serde_json::{json, Value};
+use std::time::Duration;
+
+use super::embeddings::Embedder;
+
+/// Текст пробного запроса при подключении
+const DIMENSION_PROBE: &str = "dimension probe";
+/// Сколько ждать ответа сервера
+pub const DEFAULT_REMOTE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Откуда берутся эмбеддинги
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum EmbeddingBackend {
+    /// Локальная модель из `--embedding-path` ([`super::embeddings::EmbeddingEngine`])
+    #[default]
+    Local,
+    /// OpenAI-совместимый `/v1/embeddings`
+    OpenAi,
+    /// Ollama `/api/embed`
+    Ollama,
+}
+
+impl std::str::FromStr for EmbeddingBackend {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "local" => Ok(EmbeddingBackend::Local),
+            "openai" | "open-ai" => Ok(EmbeddingBackend::OpenAi),
+            "ollama" => Ok(EmbeddingBackend::Ollama),
+            other => anyhow::bail!("Unknown embedding backend '{}' (expected local, openai or ollama)", other),
+        }
+    }
+}
+
+impl EmbeddingBackend {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            EmbeddingBackend::Local => "local",
+            EmbeddingBackend::OpenAi => "openai",
+            EmbeddingBackend::Ollama => "ollama",
+        }
+    }
+
+    /// Адрес сервера, если он не задан
+    pub fn default_url(self) -> &'static str {
+        match self {
+            EmbeddingBackend::Local => "",
+            EmbeddingBackend::OpenAi => "https://api.openai.com",
+            EmbeddingBackend::Ollama => "http://localhost:11434",
+        }
+    }
+
+    /// Модель, если она не задана
+    pub fn default_model(self) -> &'static str {
+        match self {
+            EmbeddingBackend::Local => "",
+            EmbeddingBackend::OpenAi => "text-embedding-3-small",
+            EmbeddingBackend::Ollama => "nomic-embed-text",
+        }
+    }
+
+    /// Путь эндпоинта дописывается к адресу сервера, если адрес не указывает на него сам
+    fn endpoint(self, url: &str) -> String {
+        let path = match self {
+            EmbeddingBackend::OpenAi => "/v1/embeddings",
+            _ => "/api/embed",
+        };
+        let url = url.trim_end_matches('/');
+        if url.ends_with(path) || (self == EmbeddingBackend::OpenAi && url.ends_with("/embeddings")) {
+            url.to_string()
+        } else {
+            format!("{}{}", url, path)
+        }
+    }
+
+    fn request_body(self, model: &str, texts: &[String]) -> Value {
+        json!({ "model": model, "input": texts })
+    }
+
+    /// Векторы из ответа сервера в порядке текстов запроса
+    fn parse_response(self, body: &Value, expected: usize) -> Result<Vec<Vec<f32>>> {
+        let to_vector = |v: &Value| -> Option<Vec<f32>> {
+            v.as_array()?.iter().map(|x| x.as_f64().map(|x| x as f32)).collect()
+        };
+        let vectors: Vec<Vec<f32>> = match self {
+            EmbeddingBackend::OpenAi => {
+                let data = body["data"].as_array().context("No 'data' in the embeddings response")?;
+                let mut indexed = data
+                    .iter()
+                    .enumerate()
+                    .map(|(i, item)| {
+                        let index = item["index"].as_u64().map_or(i, |n| n as usize);
+                        let vector = to_vector(&item["embedding"]).context("Malformed 'embedding' in the response")?;
+                        Ok((index, vector))
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+                indexed.sort_by_key(|(index, _)| *index);
+                indexed.into_iter().map(|(_, vector)| vector).collect()
+            }
+            _ => body["embeddings"]
+                .as_array()
+                .context("No 'embeddings' in the embeddings response")?
+                .iter()
+                .map(|v| to_vector(v).context("Malformed vector in 'embeddings'"))
+                .collect::<Result<_>>()?,
+        };
+        anyhow::ensure!(
+            vectors.len() == expected,
+            "Embedding server returned {} vectors for {} texts",
+            vectors.len(),
+            expected
+        );
+        Ok(vectors)
+    }
+}
+
+/// Подключение к серверу эмбеддингов
+#[derive(Debug, Clone)]
+pub struct RemoteEmbedderConfig {
+    pub backend: EmbeddingBackend,
+    pub url: String,
+    pub model: String,
+    /// `Authorization: Bearer ...`; Ollama обычно без ключа
+    pub api_key: Option<String>,
+    pub timeout: Duration,
+}
+
+impl RemoteEmbedderConfig {
+    /// Адрес и модель по умолчанию для `backend`
+    pub fn new(backend: EmbeddingBackend) -> Self {
+        Self {
+            backend,
+            url: backend.default_url().to_string(),
+            model: backend.default_model().to_string(),
+            api_key: None,
+            timeout: DEFAULT_REMOTE_TIMEOUT,
+        }
+    }
+}
+
+/// Эмбеддер поверх HTTP API
+pub struct RemoteEmbedder {
+    config: RemoteEmbedderConfig,
+    endpoint: String,
+    agent: ureq::Agent,
+    dim: usize,
+}
+
+impl RemoteEmbedder {
+    /// Подключается к серверу: пробный запрос проверяет адрес, модель и ключ
+    /// и даёт размерность векторов
+    pub fn connect(config: RemoteEmbedderConfig) -> Result<Self> {
+        anyhow::ensure!(
+            config.backend != EmbeddingBackend::Local,
+            "The local backend is EmbeddingEngine, not a server"
+        );
+        let agent = ureq::AgentBuilder::new().timeout(config.timeout).build();
+        let endpoint = config.backend.endpoint(&config.url);
+        let mut embedder = Self {
+            config,
+            endpoint,
+            agent,
+            dim: 0,
+        };
+        let probe = embedder
+            .request(&[DIMENSION_PROBE.to_string()])
+            .with_context(|| format!("Embedding server {} is not usable", embedder.endpoint))?;
+        embedder.dim = probe[0].len();
+        anyhow::ensure!(embedder.dim > 0, "Embedding server returned an empty vector");
+        Ok(embedder)
+    }
+
+    /// Имя, которым подписываются векторы в памяти: `ollama:nomic-embed-text`
+    pub fn name(&self) -> String {
+        format!("{}:{}", self.config.backend.as_str(), self.config.model)
+    }
+
+    fn request(&self, texts: &[String]) -> Result<Vec<Vec<f32>>> {
+        let _span = tracing::debug_span!("embed", texts = texts.len(), backend = self.config.backend.as_str()).entered();
+        let started = std::time::Instant::now();
+        let mut request = self.agent.post(&self.endpoint).set("Content-Type", "application/json");
+        if let Some(ref key) = self.config.api_key {
+            request = request.set("Authorization", &format!("Bearer {}", key));
+        }
+        let body = self.config.backend.request_body(&self.config.model, texts);
+        let response = match request.send_json(body) {
+            Ok(response) => response,
+            Err(ureq::Error::Status(code, response)) => {
+                let text = response.into_string().unwrap_or_default();
+                anyhow::bail!("Embedding server answered {}: {}", code, text.trim());
+            }
+            Err(e) => return Err(e).with_context(|| format!("Failed to reach {}", self.endpoint)),
+        };
+        let body: Value = response.into_json().context("Embedding server sent invalid JSON")?;
+        let vectors = self.config.backend.parse_response(&body, texts.len())?;
+        crate::logos::metrics::record_embedding(texts.len(), started.elapsed());
+        Ok(vectors.into_iter().map(l2_normalize).collect())
+    }
+}
+
+/// Локальная модель нормализует векторы; серверы - не все
+fn l2_normalize(mut vector: Vec<f32>) -> Vec<f32> {
+    let norm = vector.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm > 0.0 {
+        vector.iter_mut().for_each(|x| *x /= norm);
+    }
+    vector
+}
+
+impl Embedder for RemoteEmbedder {
+    fn embed(&self, text: &str) -> Result<Vec<f32>> {
+        let mut vectors = self.request(&[text.to_string()])?;
+        Ok(vectors.remove(0))
+    }
+
+    fn embedding_dim(&self) -> usize {
+        self.dim
+    }
+
+    fn embed_batch(&self, texts: &[String]) -> Result<Vec<Vec<f32>>> {
+        if texts.is_empty() {
+            return Ok(Vec::new());
+        }
+        self.request(texts)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+
+    /// Сервер Ollama на один раз: на каждый запрос отвечает вектором `[3, 4]` на текст
+    fn serve_ollama(requests: usize) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        std::thread::spawn(move || {
+            for stream in listener.incoming().take(requests) {
+                let mut reader = BufReader::new(stream.unwrap());
+                let mut length = 0;
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    if let Some(value) = line.to_lowercase().strip_prefix("content-length:") {
+                        length = value.trim().parse().unwrap();
+                    }
+                    if line.trim().is_empty() {
+                        break;
+                    }
+                }
+                let mut body = vec![0; length];
+                reader.read_exact(&mut body).unwrap();
+                let request: Value = serde_json::from_slice(&body).unwrap();
+                let count = request["input"].as_array().unwrap().len();
+                let reply = json!({ "embeddings": vec![[3.0, 4.0]; count] }).to_string();
+                let mut stream = reader.into_inner();
+                write!(
+                    stream,
+                    "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    reply.len(),
+                    reply
+                )
+                .unwrap();
+            }
+        });
+        url
+    }
+
+    #[test]
+    fn test_remote_embedder() -> Result<()> {
+        let openai = EmbeddingBackend::OpenAi;
+        assert_eq!(openai.endpoint("https://api.openai.com/"), "https://api.openai.com/v1/embeddings");
+        assert_eq!(openai.endpoint("http://vllm:8000/v1/embeddings"), "http://vllm:8000/v1/embeddings");
+        assert_eq!(EmbeddingBackend::Ollama.endpoint("http://localhost:11434"), "http://localhost:11434/api/embed");
+        assert_eq!("Ollama".parse::<EmbeddingBackend>()?, EmbeddingBackend::Ollama);
+        assert!("bert".parse::<EmbeddingBackend>().is_err());
+
+        // OpenAI может вернуть векторы не по порядку
+        let body = json!({ "data": [
+            { "index": 1, "embedding": [0.0, 1.0] },
+            { "index": 0, "embedding": [1.0, 0.0] },
+        ]});
+        assert_eq!(openai.parse_response(&body, 2)?, vec![vec![1.0, 0.0], vec![0.0, 1.0]]);
+        assert!(openai.parse_response(&body, 3).is_err());
+
+        let mut config = RemoteEmbedderConfig::new(EmbeddingBackend::Ollama);
+        config.url = serve_ollama(2);
+        let embedder = RemoteEmbedder::connect(config)?;
+        assert_eq!(embedder.embedding_dim(), 2);
+        assert_eq!(embedder.name(), "ollama:nomic-embed-text");
+        let vectors = embedder.embed_batch(&["один".to_string(), "два".to_string()])?;
+        assert_eq!(vectors, vec![vec![0.6, 0.8], vec![0.6, 0.8]]);
+        Ok(())
+    }
+}
@@ -0,0 +1,146 @@
+//! 🧪 Детерминированные заглушки моделей для тестов
+//!
+//! Стек памяти проверяется без скачанных моделей и без candle: [`MockEmbedder`]
+//! строит вектор из хешей слов текста (одинаковый текст - одинаковый вектор, общие
+//! слова - близкие векторы, от запуска к запуску то же самое), [`MockLlmPipeline`]
+//! отвечает заготовленными ответами по подстроке промпта и запоминает промпты.
+//! Как [`crate::utils::clock::MockClock`] для времени, доступны и вне `cfg(test)` -
+//! сценариям и тестам других крейтов.
+
+use anyhow::Result;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+use crate::priests::embeddings::Embedder;
+use crate::totems::episodic::persistence::fnv1a;
+use crate::totems::episodic::LlmPipeline;
+
+/// Размерность по умолчанию, как у multilingual-e5-small
+pub const MOCK_EMBEDDING_DIM: usize = 384;
+
+/// Эмбеддер «мешок слов»: каждое слово (в нижнем регистре) даёт ±1 в позиции по своему
+/// хешу, вектор нормируется. Косинус двух текстов растёт с долей общих слов
+pub struct MockEmbedder {
+    dim: usize,
+    calls: AtomicUsize,
+}
+
+impl Default for MockEmbedder {
+    fn default() -> Self {
+        Self::new(MOCK_EMBEDDING_DIM)
+    }
+}
+
+impl MockEmbedder {
+    pub fn new(dim: usize) -> Self {
+        assert!(dim > 0, "embedding dimension must be positive");
+        Self {
+            dim,
+            calls: AtomicUsize::new(0),
+        }
+    }
+
+    /// Сколько текстов эмбеддер посчитал (проверки кэшей)
+    pub fn calls(&self) -> usize {
+        self.calls.load(Ordering::Relaxed)
+    }
+}
+
+impl Embedder for MockEmbedder {
+    fn embed(&self, text: &str) -> Result<Vec<f32>> {
+        self.calls.fetch_add(1, Ordering::Relaxed);
+        let lower = text.to_lowercase();
+        let mut words: Vec<&str> = lower.split(|c: char| !c.is_alphanumeric()).filter(|w| !w.is_empty()).collect();
+        if words.is_empty() {
+            // текст без слов всё равно получает свой ненулевой вектор
+            words.push(lower.as_str());
+        }
+
+        let mut vector = vec![0.0f32; self.dim];
+        for word in words {
+            let hash = fnv1a(word.as_bytes());
+            let sign = if hash >> 63 == 0 { 1.0 } else { -1.0 };
+            vector[(hash % self.dim as u64) as usize] += sign;
+        }
+        let norm = vector.iter().map(|v| v * v).sum::<f32>().sqrt();
+        if norm > 0.0 {
+            vector.iter_mut().for_each(|v| *v /= norm);
+        } else {
+            // слова взаимно погасились
+            vector[0] = 1.0;
+        }
+        Ok(vector)
+    }
+
+    fn embedding_dim(&self) -> usize {
+        self.dim
+    }
+}
+
+/// LLM с заготовленными ответами: первое правило, чья подстрока есть в промпте,
+/// иначе ответ по умолчанию
+pub struct MockLlmPipeline {
+    rules: Vec<(String, String)>,
+    default: String,
+    prompts: Mutex<Vec<String>>,
+}
+
+impl MockLlmPipeline {
+    pub fn new(default: impl Into<String>) -> Self {
+        Self {
+            rules: Vec::new(),
+            default: default.into(),
+            prompts: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Ответ `reply` на промпты, содержащие `needle`
+    pub fn with_reply(mut self, needle: impl Into<String>, reply: impl Into<String>) -> Self {
+        self.rules.push((needle.into(), reply.into()));
+        self
+    }
+
+    /// Промпты всех вызовов, по порядку
+    pub fn prompts(&self) -> Vec<String> {
+        self.prompts.lock().unwrap().clone()
+    }
+}
+
+impl LlmPipeline for MockLlmPipeline {
+    fn generate(&self, prompt: &str, _max_tokens: usize) -> Result<String> {
+        self.prompts.lock().unwrap().push(prompt.to_string());
+        let reply = self
+            .rules
+            .iter()
+            .find(|(needle, _)| prompt.contains(needle.as_str()))
+            .map_or(&self.default, |(_, reply)| reply);
+        Ok(reply.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::totems::retrieval::vector_store::cosine_similarity;
+
+    #[test]
+    fn test_mock_models_are_deterministic() -> Result<()> {
+        let embedder = MockEmbedder::new(64);
+        let cat = embedder.embed("My cat is Murka")?;
+        assert_eq!(cat, MockEmbedder::new(64).embed("my cat is murka!")?);
+        assert_eq!(cat.len(), 64);
+        assert!((cosine_similarity(&cat, &cat) - 1.0).abs() < 1e-5);
+        let close = cosine_similarity(&cat, &embedder.embed("What is my cat called?")?);
+        let far = cosine_similarity(&cat, &embedder.embed("Rust borrow checker")?);
+        assert!(close > far, "{} vs {}", close, far);
+        assert!(embedder.embed("?!")?.iter().any(|v| *v != 0.0));
+        assert_eq!(embedder.calls(), 4);
+
+        let llm = MockLlmPipeline::new("не знаю").with_reply("Темы:", "rust, память").with_reply("Тема", "rust");
+        assert_eq!(llm.generate("Темы: ...", 10)?, "rust, память");
+        assert_eq!(llm.generate("Тема?", 10)?, "rust");
+        assert_eq!(llm.generate("Привет", 10)?, "не знаю");
+        assert_eq!(llm.prompts(), ["Темы: ...", "Тема?", "Привет"]);
+        Ok(())
+    }
+}
@@ -0,0 +1,86 @@
+//! 🤝 Согласие на запоминание
+//!
+//! По умолчанию всё сказанное попадает в долговременную память. В режиме
+//! `on-request` ходы живут только в рабочей памяти текущего разговора, пока
+//! пользователь явно не попросит запомнить: фразой («запомни», «remember this»)
+//! или командой `/remember`. Без согласия ход не индексируется, не сохраняется
+//! на диск и не проходит экстракцию концептов.
+
+/// Ключ метаданных хода, который существует только в рабочей памяти
+pub const EPHEMERAL_KEY: &str = "ephemeral";
+
+/// Просьбы запомнить; ищутся в тексте без учёта регистра
+const REMEMBER_PHRASES: &[&str] = &[
+    "remember this",
+    "remember that",
+    "please remember",
+    "keep in mind",
+    "don't forget",
+    "do not forget",
+    "запомни",
+    "запомните",
+    "не забудь",
+    "не забывай",
+    "имей в виду",
+    "имейте в виду",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ConsentMode {
+    /// Запоминается всё
+    #[default]
+    Always,
+    /// Только то, что пользователь попросил запомнить
+    OnRequest,
+}
+
+impl std::str::FromStr for ConsentMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        match s.to_lowercase().as_str() {
+            "always" => Ok(ConsentMode::Always),
+            "on-request" | "on_request" | "request" => Ok(ConsentMode::OnRequest),
+            other => anyhow::bail!("Unknown memory consent mode '{}' (expected always or on-request)", other),
+        }
+    }
+}
+
+impl ConsentMode {
+    /// Можно ли сохранить ход с этим сообщением пользователя надолго
+    pub fn allows(self, user_message: &str) -> bool {
+        match self {
+            ConsentMode::Always => true,
+            ConsentMode::OnRequest => asks_to_remember(user_message),
+        }
+    }
+}
+
+/// Пользователь просит запомнить сказанное
+pub fn asks_to_remember(text: &str) -> bool {
+    let lower = text.to_lowercase().replace('’', "'");
+    REMEMBER_PHRASES.iter().any(|phrase| {
+        lower.match_indices(phrase).any(|(i, _)| {
+            // фраза начинается с начала слова
+            let before = lower[..i].chars().next_back();
+            before.is_none_or(|c| !c.is_alphanumeric())
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_consent_mode() {
+        let mode: ConsentMode = "on-request".parse().unwrap();
+        assert!(mode.allows("Remember this: my sister's name is Ира"));
+        assert!(mode.allows("Запомни, что я не ем мясо"));
+        assert!(mode.allows("Don’t forget I have a meeting at 5"));
+        assert!(!mode.allows("Я не ем мясо"));
+        assert!(!mode.allows("Напомни, о чём мы говорили"));
+        assert!(ConsentMode::Always.allows("Я не ем мясо"));
+        assert!("sometimes".parse::<ConsentMode>().is_err());
+    }
+}
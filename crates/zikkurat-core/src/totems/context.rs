@@ -0,0 +1,473 @@
+//! 🧩 Источники контекста промпта
+//!
+//! Всё, что попадает в промпт помимо вопроса - воспоминания, знания, профиль
+//! пользователя, отношения с персоной - приходит от [`ContextProvider`]. Встроенные
+//! источники регистрируются на каждый запрос, сторонние - один раз через
+//! [`register_plugin`] (из своего крейта) или как shell-команды из конфига
+//! ([`CommandProvider`]). Так календарь или погода добавляются без правки сборщика промпта.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::io::Read;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+use crate::totems::episodic::is_near_duplicate;
+use crate::totems::retrieval::vector_store::cosine_similarity;
+
+/// Приоритет сторонних источников по умолчанию
+pub const DEFAULT_PRIORITY: i32 = 20;
+/// Грубая оценка для перевода бюджета в токенах в символы
+const CHARS_PER_TOKEN: usize = 4;
+
+/// Секция промпта: заголовок, текст и необязательная приписка после текста
+#[derive(Debug, Clone, PartialEq)]
+pub struct Section {
+    /// Имя источника
+    pub name: String,
+    /// Секции с меньшим приоритетом урезаются первыми, когда промпт не помещается в окно
+    pub priority: i32,
+    pub title: String,
+    /// Урезаемая часть
+    pub body: String,
+    pub footer: String,
+}
+
+impl Section {
+    pub fn new(title: impl Into<String>, body: impl Into<String>) -> Self {
+        Self {
+            name: String::new(),
+            priority: DEFAULT_PRIORITY,
+            title: title.into(),
+            body: body.into(),
+            footer: String::new(),
+        }
+    }
+
+    pub fn with_footer(mut self, footer: impl Into<String>) -> Self {
+        self.footer = footer.into();
+        self
+    }
+
+    /// Оставляет строки текста, для которых `keep` вернул `true`. Возвращает число убранных
+    pub fn retain_lines(&mut self, mut keep: impl FnMut(&str) -> bool) -> usize {
+        let before = self.body.lines().count();
+        let body: Vec<&str> = self.body.lines().filter(|line| keep(line)).collect();
+        let removed = before - body.len();
+        if removed > 0 {
+            self.body = body.join("\n");
+        }
+        removed
+    }
+
+    /// Пункты текста без маркеров списка и префиксов, как их сравнивает [`dedupe_sections`]
+    pub fn items(&self) -> impl Iterator<Item = &str> {
+        self.body.lines().filter_map(dedupe_item)
+    }
+
+    pub fn render(&self) -> String {
+        let mut text = format!("{}\n{}", self.title, self.body);
+        if !self.footer.is_empty() {
+            text.push('\n');
+            text.push_str(&self.footer);
+        }
+        text
+    }
+}
+
+/// Источник секции промпта
+pub trait ContextProvider {
+    fn name(&self) -> &str;
+
+    fn priority(&self) -> i32 {
+        DEFAULT_PRIORITY
+    }
+
+    /// Секция для запроса `query` или `None`, если сказать нечего.
+    /// `budget` - сколько токенов промпта осталось под контекст
+    fn provide(&mut self, query: &str, budget: usize) -> Result<Option<Section>>;
+}
+
+/// Уже посчитанный текст - тоже источник: отдаёт себя
+impl ContextProvider for Section {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn priority(&self) -> i32 {
+        self.priority
+    }
+
+    fn provide(&mut self, _query: &str, _budget: usize) -> Result<Option<Section>> {
+        Ok((!self.body.is_empty()).then(|| self.clone()))
+    }
+}
+
+/// Сторонние источники, живущие всё время работы процесса
+static PLUGINS: parking_lot::Mutex<Vec<Box<dyn ContextProvider + Send>>> = parking_lot::Mutex::new(Vec::new());
+
+/// Добавляет источник ко всем следующим запросам. Источник с тем же именем заменяется
+pub fn register_plugin(provider: Box<dyn ContextProvider + Send>) {
+    let mut plugins = PLUGINS.lock();
+    plugins.retain(|p| p.name() != provider.name());
+    plugins.push(provider);
+}
+
+/// Имена подключённых сторонних источников
+pub fn plugin_names() -> Vec<String> {
+    PLUGINS.lock().iter().map(|p| p.name().to_string()).collect()
+}
+
+/// Источники одного запроса: встроенные в порядке регистрации, затем сторонние
+#[derive(Default)]
+pub struct ContextRegistry<'a> {
+    providers: Vec<Box<dyn ContextProvider + 'a>>,
+}
+
+impl<'a> ContextRegistry<'a> {
+    pub fn new() -> Self {
+        Self { providers: Vec::new() }
+    }
+
+    pub fn register(&mut self, provider: impl ContextProvider + 'a) {
+        self.providers.push(Box::new(provider));
+    }
+
+    /// Секции всех источников в порядке регистрации. Ошибка источника не мешает остальным
+    pub fn collect(&mut self, query: &str, budget: usize) -> Vec<Section> {
+        let mut sections = Vec::new();
+        let mut plugins = PLUGINS.lock();
+        let providers = self
+            .providers
+            .iter_mut()
+            .map(|p| p.as_mut() as &mut dyn ContextProvider)
+            .chain(plugins.iter_mut().map(|p| p.as_mut() as &mut dyn ContextProvider));
+        for provider in providers {
+            match provider.provide(query, budget) {
+                Ok(Some(mut section)) if !section.body.trim().is_empty() => {
+                    section.name = provider.name().to_string();
+                    section.priority = provider.priority();
+                    sections.push(section);
+                }
+                Ok(_) => {}
+                Err(e) => eprintln!("⚠️  Context provider '{}' failed: {}", provider.name(), e),
+            }
+        }
+        sections
+    }
+}
+
+/// Строки короче этого (после снятия маркеров) не сравниваются: «User: да» повторяется законно
+const MIN_DEDUPE_CHARS: usize = 12;
+
+/// Текст пункта секции без маркера списка и префикса вида `[fact 0.82 #food, 2 days ago]`.
+/// Заголовки (`KNOWN FACTS ABOUT USER:`) и короткие строки пунктами не считаются
+fn dedupe_item(line: &str) -> Option<&str> {
+    let mut text = line.trim();
+    if text.ends_with(':') {
+        return None;
+    }
+    text = text.strip_prefix("- ").unwrap_or(text);
+    if text.starts_with('[') {
+        if let Some(end) = text.find(']') {
+            text = text[end + 1..].trim_start();
+        }
+    }
+    (text.chars().count() >= MIN_DEDUPE_CHARS).then_some(text)
+}
+
+/// Убирает пункты, повторяющиеся в разных секциях: остаётся вхождение в секции с
+/// наибольшим приоритетом (при равенстве - в зарегистрированной раньше). Пункты
+/// совпадают, если одинаковы с точностью до регистра и пунктуации
+/// ([`is_near_duplicate`]) или если косинус их эмбеддингов не меньше
+/// `min_similarity`; `embed` может вернуть `None`, тогда остаётся только текстовое
+/// сравнение. Заголовки, под которыми не осталось пунктов, убираются вместе с ними.
+/// Возвращает число убранных строк
+pub fn dedupe_sections(
+    sections: &mut [Section],
+    mut embed: impl FnMut(&str) -> Option<Vec<f32>>,
+    min_similarity: f32,
+) -> usize {
+    let mut order: Vec<usize> = (0..sections.len()).collect();
+    order.sort_by_key(|&i| std::cmp::Reverse(sections[i].priority));
+
+    // Оставленные пункты: секция, текст, эмбеддинг
+    let mut kept: Vec<(usize, String, Option<Vec<f32>>)> = Vec::new();
+    let mut removed = 0;
+    for i in order {
+        let mut dropped = HashSet::new();
+        let mut own = Vec::new();
+        for (n, line) in sections[i].body.lines().enumerate() {
+            let Some(item) = dedupe_item(line) else { continue };
+            let mut embedding = None;
+            let mut duplicate = kept.iter().any(|(s, text, _)| *s != i && is_near_duplicate(text, item));
+            if !duplicate && min_similarity > 0.0 {
+                embedding = embed(item);
+                duplicate = embedding.as_ref().is_some_and(|e| {
+                    kept.iter().any(|(s, _, other)| {
+                        *s != i && other.as_ref().is_some_and(|o| cosine_similarity(e, o) >= min_similarity)
+                    })
+                });
+            }
+            if duplicate {
+                dropped.insert(n);
+            } else {
+                own.push((i, item.to_string(), embedding));
+            }
+        }
+        kept.extend(own);
+        if dropped.is_empty() {
+            continue;
+        }
+
+        removed += dropped.len();
+        let lines: Vec<&str> = sections[i]
+            .body
+            .lines()
+            .enumerate()
+            .filter(|(n, _)| !dropped.contains(n))
+            .map(|(_, line)| line)
+            .collect();
+        // заголовок без пунктов до следующего заголовка или конца секции
+        let is_header = |line: &str| line.trim().ends_with(':');
+        let mut body: Vec<&str> = Vec::with_capacity(lines.len());
+        for (n, line) in lines.iter().enumerate() {
+            let orphan = is_header(line)
+                && lines[n + 1..]
+                    .iter()
+                    .take_while(|l| !is_header(l))
+                    .all(|l| l.trim().is_empty());
+            if !orphan {
+                body.push(line);
+            }
+        }
+        let empty = body.iter().all(|l| l.trim().is_empty() || is_header(l));
+        sections[i].body = if empty { String::new() } else { body.join("\n").trim().to_string() };
+    }
+    removed
+}
+
+/// Источник из конфига: shell-команда, чей stdout становится секцией.
+/// Запрос и бюджет передаются в переменных `ZIGGURAT_QUERY` и `ZIGGURAT_BUDGET`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CommandProvider {
+    pub name: String,
+    pub command: String,
+    /// Заголовок секции, по умолчанию - имя в верхнем регистре
+    #[serde(default)]
+    pub title: Option<String>,
+    #[serde(default = "default_priority")]
+    pub priority: i32,
+    #[serde(default = "default_timeout_ms")]
+    pub timeout_ms: u64,
+}
+
+fn default_priority() -> i32 {
+    DEFAULT_PRIORITY
+}
+
+fn default_timeout_ms() -> u64 {
+    2000
+}
+
+impl CommandProvider {
+    /// Список источников из JSON-файла (массив [`CommandProvider`])
+    pub fn load_all(path: &Path) -> Result<Vec<Self>> {
+        let content = std::fs::read_to_string(path).with_context(|| format!("Failed to read {:?}", path))?;
+        serde_json::from_str(&content).with_context(|| format!("Failed to parse {:?}", path))
+    }
+
+    fn shell(&self) -> Command {
+        if cfg!(windows) {
+            let mut cmd = Command::new("cmd");
+            cmd.args(["/C", &self.command]);
+            cmd
+        } else {
+            let mut cmd = Command::new("sh");
+            cmd.args(["-c", &self.command]);
+            cmd
+        }
+    }
+}
+
+impl ContextProvider for CommandProvider {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn priority(&self) -> i32 {
+        self.priority
+    }
+
+    fn provide(&mut self, query: &str, budget: usize) -> Result<Option<Section>> {
+        let mut child = self
+            .shell()
+            .env("ZIGGURAT_QUERY", query)
+            .env("ZIGGURAT_BUDGET", budget.to_string())
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .with_context(|| format!("Failed to run '{}'", self.command))?;
+
+        // вывод читается, пока команда работает: иначе она встанет на полном канале (64 KiB)
+        // и дождётся таймаута. Больше бюджета не копим, остаток только вычитываем
+        let max_chars = budget.saturating_mul(CHARS_PER_TOKEN);
+        let mut stdout = child.stdout.take().context("Command stdout is not piped")?;
+        let reader = std::thread::spawn(move || -> std::io::Result<Vec<u8>> {
+            let mut output = Vec::new();
+            // a char takes at most 4 bytes in UTF-8
+            stdout.by_ref().take(max_chars.saturating_mul(4) as u64).read_to_end(&mut output)?;
+            std::io::copy(&mut stdout, &mut std::io::sink())?;
+            Ok(output)
+        });
+
+        // не ждём зависшую команду дольше таймаута: ответ важнее календаря
+        let deadline = Instant::now() + Duration::from_millis(self.timeout_ms);
+        let status = loop {
+            if let Some(status) = child.try_wait()? {
+                break status;
+            }
+            if Instant::now() >= deadline {
+                let _ = child.kill();
+                let _ = child.wait();
+                anyhow::bail!("'{}' timed out after {} ms", self.command, self.timeout_ms);
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        };
+        anyhow::ensure!(status.success(), "'{}' exited with {}", self.command, status);
+
+        let output = reader
+            .join()
+            .map_err(|_| anyhow::anyhow!("Reading the output of '{}' panicked", self.command))??;
+        let output = String::from_utf8_lossy(&output);
+        let output = output.trim();
+        if output.is_empty() {
+            return Ok(None);
+        }
+        let body: String = output.chars().take(max_chars).collect();
+        let title = self.title.clone().unwrap_or_else(|| format!("{}:", self.name.to_uppercase()));
+        Ok(Some(Section::new(title, body)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Weather;
+
+    impl ContextProvider for Weather {
+        fn name(&self) -> &str {
+            "weather"
+        }
+
+        fn provide(&mut self, query: &str, _budget: usize) -> Result<Option<Section>> {
+            anyhow::ensure!(!query.is_empty(), "empty query");
+            Ok(query.contains("umbrella").then(|| Section::new("WEATHER:", "Rain in Kazan")))
+        }
+    }
+
+    #[test]
+    fn test_context_registry() {
+        let mut registry = ContextRegistry::new();
+        let mut fixed = Section::new("Current conversation:", "User: hi");
+        fixed.name = "conversation".to_string();
+        fixed.priority = 40;
+        registry.register(fixed);
+        registry.register(Weather);
+
+        let sections = registry.collect("Do I need an umbrella?", 100);
+        let names: Vec<&str> = sections.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, ["conversation", "weather"]);
+        assert_eq!(sections[0].priority, 40);
+        assert_eq!(sections[1].priority, DEFAULT_PRIORITY);
+        assert_eq!(sections[1].render(), "WEATHER:\nRain in Kazan");
+        // a failing provider is skipped
+        assert_eq!(registry.collect("", 100).len(), 1);
+
+        let provider: CommandProvider =
+            serde_json::from_str(r#"{"name": "echo", "command": "printf '%s' \"$ZIGGURAT_QUERY\""}"#).unwrap();
+        assert_eq!(provider.timeout_ms, 2000);
+        if cfg!(unix) {
+            let mut provider = provider;
+            let section = provider.provide("calendar today", 2).unwrap().unwrap();
+            assert_eq!(section.render(), "ECHO:\ncalendar");
+
+            // output past the pipe buffer does not stall the command until the timeout
+            let mut provider: CommandProvider =
+                serde_json::from_str(r#"{"name": "log", "command": "yes ziggurat | head -c 200000"}"#).unwrap();
+            let section = provider.provide("", 3).unwrap().unwrap();
+            assert_eq!(section.render(), "LOG:\nziggurat\nzig");
+        }
+    }
+
+    #[test]
+    fn test_dedupe_sections() {
+        let section = |name: &str, priority: i32, title: &str, body: &str| {
+            let mut s = Section::new(title, body);
+            s.name = name.to_string();
+            s.priority = priority;
+            s
+        };
+        let mut sections = vec![
+            section(
+                "knowledge",
+                50,
+                "KNOWLEDGE:",
+                "[fact 0.82 #food, 2 days ago] User likes Neapolitan pizza.\n\
+                 [fact 0.61, yesterday] User works as a backend developer\n\
+                 [fact 0.55, today] User lives in Kazan with two cats",
+            ),
+            section(
+                "profile",
+                60,
+                "USER PROFILE (use when relevant):",
+                "KNOWN FACTS ABOUT USER:\n- user likes neapolitan pizza\n- User is a software engineer\n\n\
+                 USER PREFERENCES:\n- User prefers short answers",
+            ),
+            section("episodic", 30, "Past:", "User likes Neapolitan pizza!"),
+        ];
+        // "backend developer" and "software engineer" are the same fact only by meaning
+        let embed = |text: &str| -> Option<Vec<f32>> {
+            if text.contains("backend developer") || text.contains("software engineer") {
+                Some(vec![1.0, 0.1])
+            } else if text.contains("Kazan") {
+                Some(vec![0.0, 1.0])
+            } else {
+                None
+            }
+        };
+
+        let removed = dedupe_sections(&mut sections, embed, 0.95);
+        assert_eq!(removed, 3);
+        // profile has the highest priority and keeps everything
+        assert!(sections[1].body.contains("neapolitan pizza"));
+        assert!(sections[1].body.contains("software engineer"));
+        assert_eq!(sections[0].body, "[fact 0.55, today] User lives in Kazan with two cats");
+        // nothing left but the repeat - the section goes away
+        assert!(sections[2].body.is_empty());
+
+        // without embeddings only the textual repeat is caught
+        let mut sections = vec![
+            section("knowledge", 50, "KNOWLEDGE:", "[fact 0.61, yesterday] User works as a backend developer"),
+            section("profile", 60, "USER PROFILE:", "KNOWN FACTS ABOUT USER:\n- User is a software engineer"),
+        ];
+        assert_eq!(dedupe_sections(&mut sections, |_| None, 0.95), 0);
+
+        // a header whose items were all repeats is dropped with them
+        let mut sections = vec![
+            section("profile", 60, "USER PROFILE:", "KNOWN FACTS ABOUT USER:\n- User likes Neapolitan pizza"),
+            section(
+                "plugin",
+                20,
+                "NOTES:",
+                "FOOD:\n- User likes Neapolitan pizza\nWEATHER:\n- Rain in Kazan all week long",
+            ),
+        ];
+        assert_eq!(dedupe_sections(&mut sections, |_| None, 0.0), 1);
+        assert_eq!(sections[1].body, "WEATHER:\n- Rain in Kazan all week long");
+    }
+}
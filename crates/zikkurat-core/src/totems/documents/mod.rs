@@ -0,0 +1,445 @@
+//! 📄 Документы пользователя (RAG по файлам)
+//!
+//! [`DocumentStore::ingest_file`] режет PDF, Markdown или текстовый файл на куски по
+//! абзацам (с перекрытием, куски Markdown помнят заголовок своего раздела), эмбеддит
+//! их текущим [`Embedder`] и кладёт в свой [`VectorStore`] как [`MemoryType::Document`],
+//! по шарду на документ. Сборка промпта берёт ближайшие к вопросу куски
+//! ([`DocumentStore::search`]). Каталог (`documents.json`) и шарды лежат в одном каталоге;
+//! векторы другой размерности (смена модели эмбеддингов) пересчитываются при открытии.
+
+pub mod pdf;
+
+use anyhow::{ensure, Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::priests::embeddings::{AsyncEmbedder, Embedder};
+use crate::totems::episodic::persistence::fnv1a;
+use crate::totems::retrieval::{MemoryEntry, MemoryType, VectorStore};
+
+/// Предел куска в символах
+pub const CHUNK_CHARS: usize = 1000;
+/// Сколько символов конца куска повторяется в начале следующего (не больше пятой части куска)
+const CHUNK_OVERLAP_CHARS: usize = 150;
+/// Файлы больше не читаются: книга целиком не нужна в промпте по кускам
+pub const MAX_FILE_BYTES: u64 = 50 << 20;
+const CATALOG_FILE: &str = "documents.json";
+const TITLE_KEY: &str = "title";
+
+/// Формат файла по расширению
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DocumentFormat {
+    Pdf,
+    Markdown,
+    Text,
+}
+
+impl DocumentFormat {
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?.to_lowercase();
+        match extension.as_str() {
+            "pdf" => Some(Self::Pdf),
+            "md" | "markdown" => Some(Self::Markdown),
+            "txt" | "text" | "rst" | "org" | "log" => Some(Self::Text),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Pdf => "pdf",
+            Self::Markdown => "markdown",
+            Self::Text => "text",
+        }
+    }
+}
+
+/// Документ в каталоге
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Document {
+    pub id: Uuid,
+    /// Первый заголовок Markdown или имя файла
+    pub title: String,
+    pub path: PathBuf,
+    pub format: DocumentFormat,
+    pub chunks: usize,
+    pub chars: usize,
+    /// FNV-1a содержимого файла: неизменившийся файл повторно не эмбеддится
+    pub checksum: u64,
+    pub ingested_at: DateTime<Utc>,
+}
+
+/// Что сделал [`DocumentStore::ingest_file`]
+#[derive(Debug, Clone, PartialEq)]
+pub enum Ingested {
+    Added(Document),
+    /// Файл уже был в каталоге и изменился: куски пересчитаны
+    Replaced(Document),
+    /// Файл не изменился с прошлого раза
+    Unchanged(Document),
+}
+
+impl Ingested {
+    pub fn document(&self) -> &Document {
+        match self {
+            Self::Added(d) | Self::Replaced(d) | Self::Unchanged(d) => d,
+        }
+    }
+}
+
+/// Кусок документа, найденный по запросу
+#[derive(Debug, Clone, PartialEq)]
+pub struct DocumentHit {
+    pub similarity: f32,
+    pub document_id: Uuid,
+    pub title: String,
+    pub chunk: usize,
+    pub text: String,
+}
+
+pub struct DocumentStore {
+    /// `None` - в памяти или только чтение: на диск не пишется
+    dir: Option<PathBuf>,
+    embedder: Arc<dyn Embedder>,
+    documents: Vec<Document>,
+    vectors: VectorStore,
+}
+
+impl DocumentStore {
+    /// Пустое хранилище в памяти
+    pub fn new(embedder: Arc<dyn Embedder>) -> Self {
+        let vectors = VectorStore::new(embedder.embedding_dim());
+        Self { dir: None, embedder, documents: Vec::new(), vectors }
+    }
+
+    /// Хранилище из `dir`. Куски, посчитанные моделью другой размерности, эмбеддятся заново
+    pub fn open(dir: &Path, embedder: Arc<dyn Embedder>) -> Result<Self> {
+        std::fs::create_dir_all(dir).with_context(|| format!("Failed to create documents directory {:?}", dir))?;
+        let catalog = dir.join(CATALOG_FILE);
+        let documents: Vec<Document> = if catalog.exists() {
+            serde_json::from_str(&std::fs::read_to_string(&catalog)?)
+                .with_context(|| format!("Failed to parse documents catalog {:?}", catalog))?
+        } else {
+            Vec::new()
+        };
+        let dimension = embedder.embedding_dim();
+        let mut vectors = VectorStore::open_sharded(dir)?.unwrap_or_else(|| VectorStore::new(dimension));
+        let stale = vectors.dimension() != dimension && !vectors.is_empty();
+        if stale {
+            let entries: Vec<MemoryEntry> = vectors.entries().cloned().collect();
+            let documents_by_id = |id: Uuid| documents.iter().find(|d| d.id == id).map_or("", |d| d.title.as_str());
+            let texts: Vec<String> = entries
+                .iter()
+                .map(|e| match e.memory_type {
+                    MemoryType::Document { document_id, .. } => embedding_text(documents_by_id(document_id), &e.text),
+                    _ => e.text.clone(),
+                })
+                .collect();
+            let embeddings = embedder.embed_batch(&texts)?;
+            vectors = VectorStore::new(dimension);
+            for (mut entry, embedding) in entries.into_iter().zip(embeddings) {
+                entry.embedding = embedding;
+                vectors.add(entry)?;
+            }
+            tracing::info!(chunks = vectors.len(), dimension, "document chunks re-embedded for the current model");
+        } else if vectors.dimension() != dimension {
+            vectors = VectorStore::new(dimension);
+        }
+        let store = Self { dir: Some(dir.to_path_buf()), embedder, documents, vectors };
+        if stale {
+            store.save()?;
+        }
+        Ok(store)
+    }
+
+    /// Не пишет на диск
+    pub fn read_only(mut self) -> Self {
+        self.dir = None;
+        self
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let Some(dir) = &self.dir else {
+            return Ok(());
+        };
+        self.vectors.save_shards(dir)?;
+        std::fs::write(dir.join(CATALOG_FILE), serde_json::to_string_pretty(&self.documents)?)
+            .with_context(|| format!("Failed to write documents catalog in {:?}", dir))?;
+        Ok(())
+    }
+
+    /// Документы в порядке добавления
+    pub fn documents(&self) -> &[Document] {
+        &self.documents
+    }
+
+    pub fn chunk_count(&self) -> usize {
+        self.vectors.len()
+    }
+
+    /// Читает файл, режет на куски и эмбеддит их. Файл, уже бывший в каталоге, заменяется,
+    /// если изменился. Хранилище сохраняется на диск
+    pub async fn ingest_file(&mut self, path: &Path) -> Result<Ingested> {
+        let format = DocumentFormat::from_path(path).with_context(|| {
+            format!("Unsupported document type {:?}: expected .pdf, .md or .txt", path)
+        })?;
+        let size = std::fs::metadata(path).with_context(|| format!("Cannot read {:?}", path))?.len();
+        ensure!(size <= MAX_FILE_BYTES, "{:?} is larger than {} MB", path, MAX_FILE_BYTES >> 20);
+        let bytes = crate::utils::fs::read(path).await.with_context(|| format!("Failed to read {:?}", path))?;
+        let path = std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+        let checksum = fnv1a(&bytes);
+
+        let existing = self.documents.iter().position(|d| d.path == path);
+        if let Some(document) = existing.map(|i| &self.documents[i]).filter(|d| d.checksum == checksum) {
+            return Ok(Ingested::Unchanged(document.clone()));
+        }
+
+        let text = match format {
+            DocumentFormat::Pdf => pdf::extract_text(&bytes).with_context(|| format!("Cannot extract text from {:?}", path))?,
+            _ => String::from_utf8(bytes).with_context(|| format!("{:?} is not UTF-8 text", path))?,
+        };
+        let chunks = chunk_text(&text, format, CHUNK_CHARS);
+        ensure!(!chunks.is_empty(), "{:?} has no text", path);
+        let title = document_title(&text, format, &path);
+        let texts = chunks.iter().map(|chunk| embedding_text(&title, chunk)).collect();
+        let embeddings = self.embedder.embed_batch_async(texts).await?;
+
+        let id = existing.map_or_else(Uuid::new_v4, |i| self.documents[i].id);
+        let document = Document {
+            id,
+            title: title.clone(),
+            path,
+            format,
+            chunks: chunks.len(),
+            chars: text.chars().count(),
+            checksum,
+            ingested_at: Utc::now(),
+        };
+        self.vectors.clear_document(&id);
+        for (i, (chunk, embedding)) in chunks.into_iter().zip(embeddings).enumerate() {
+            let entry = MemoryEntry::new(chunk, embedding, MemoryType::Document { document_id: id, chunk: i })
+                .with_metadata(TITLE_KEY.to_string(), title.clone());
+            self.vectors.add(entry)?;
+        }
+        let ingested = match existing {
+            Some(i) => {
+                self.documents[i] = document.clone();
+                Ingested::Replaced(document)
+            }
+            None => {
+                self.documents.push(document.clone());
+                Ingested::Added(document)
+            }
+        };
+        self.save()?;
+        Ok(ingested)
+    }
+
+    /// Куски всех документов, ближайшие к запросу
+    pub async fn search(&mut self, query: &str, top_k: usize) -> Result<Vec<DocumentHit>> {
+        if top_k == 0 || self.vectors.is_empty() {
+            return Ok(Vec::new());
+        }
+        let embedding = self.embedder.embed_async(query).await?;
+        let hits = self
+            .vectors
+            .search(&embedding, top_k)
+            .into_iter()
+            .filter_map(|(similarity, entry)| match entry.memory_type {
+                MemoryType::Document { document_id, chunk } => Some(DocumentHit {
+                    similarity,
+                    document_id,
+                    title: entry.metadata.get(TITLE_KEY).cloned().unwrap_or_default(),
+                    chunk,
+                    text: entry.text.clone(),
+                }),
+                _ => None,
+            })
+            .collect();
+        Ok(hits)
+    }
+
+    // ============ Sync facade (CLI) ============
+
+    /// Синхронная версия [`DocumentStore::ingest_file`]
+    pub fn ingest_file_blocking(&mut self, path: &Path) -> Result<Ingested> {
+        crate::utils::block_on(self.ingest_file(path))
+    }
+
+    /// Синхронная версия [`DocumentStore::search`]
+    pub fn search_blocking(&mut self, query: &str, top_k: usize) -> Result<Vec<DocumentHit>> {
+        crate::utils::block_on(self.search(query, top_k))
+    }
+}
+
+/// Кусок эмбеддится вместе с названием документа: «Глава 3» без него ни о чём не говорит
+fn embedding_text(title: &str, chunk: &str) -> String {
+    format!("{}: {}", title, chunk)
+}
+
+fn document_title(text: &str, format: DocumentFormat, path: &Path) -> String {
+    let heading = (format == DocumentFormat::Markdown)
+        .then(|| text.lines().find_map(|line| line.strip_prefix("# ")))
+        .flatten()
+        .map(str::trim)
+        .filter(|h| !h.is_empty());
+    heading.map_or_else(
+        || path.file_stem().map_or_else(|| path.display().to_string(), |s| s.to_string_lossy().into_owned()),
+        str::to_string,
+    )
+}
+
+/// Режет текст на куски до `max_chars` символов по границам абзацев; длинный абзац - по
+/// предложениям. Следующий кусок начинается с хвоста предыдущего. В Markdown заголовок
+/// начинает новый кусок, а кусок из середины раздела начинается с его заголовка
+pub fn chunk_text(text: &str, format: DocumentFormat, max_chars: usize) -> Vec<String> {
+    let mut chunks = Vec::new();
+    let mut current = String::new();
+    let mut heading = String::new();
+    // в `current` есть что-то кроме заголовка и перекрытия
+    let mut has_body = false;
+
+    let flush = |chunks: &mut Vec<String>, current: &mut String, has_body: &mut bool, heading: &str, overlap: bool| {
+        if *has_body {
+            let tail = if overlap { overlap_tail(current, CHUNK_OVERLAP_CHARS.min(max_chars / 5)) } else { String::new() };
+            chunks.push(std::mem::take(current).trim().to_string());
+            if !heading.is_empty() {
+                current.push_str(heading);
+                current.push_str("\n\n");
+            }
+            current.push_str(&tail);
+        } else {
+            current.clear();
+        }
+        *has_body = false;
+    };
+
+    for paragraph in paragraphs(text) {
+        if format == DocumentFormat::Markdown && paragraph.starts_with('#') {
+            flush(&mut chunks, &mut current, &mut has_body, "", false);
+            current.clear();
+            heading = paragraph.lines().next().unwrap_or_default().trim().to_string();
+            current.push_str(paragraph);
+            current.push_str("\n\n");
+            has_body = paragraph.lines().count() > 1;
+            continue;
+        }
+        for piece in split_long(paragraph, max_chars) {
+            if has_body && current.chars().count() + piece.chars().count() > max_chars {
+                flush(&mut chunks, &mut current, &mut has_body, &heading, true);
+            }
+            current.push_str(piece);
+            current.push_str("\n\n");
+            has_body = true;
+        }
+    }
+    flush(&mut chunks, &mut current, &mut has_body, "", false);
+    chunks
+}
+
+/// Абзацы: блоки строк между пустыми строками
+fn paragraphs(text: &str) -> impl Iterator<Item = &str> {
+    text.split("\n\n").flat_map(|block| block.split("\r\n\r\n")).map(str::trim).filter(|p| !p.is_empty())
+}
+
+/// Абзац длиннее `max_chars` - по концам предложений, а без них по пробелам
+fn split_long(paragraph: &str, max_chars: usize) -> Vec<&str> {
+    let mut pieces = Vec::new();
+    let mut rest = paragraph;
+    while rest.chars().count() > max_chars {
+        let limit = rest.char_indices().nth(max_chars).map_or(rest.len(), |(i, _)| i);
+        let window = &rest[..limit];
+        let half = limit / 2;
+        let cut = window
+            .rmatch_indices(['.', '!', '?', '\n'])
+            .map(|(i, s)| i + s.len())
+            .find(|&i| i > half)
+            .or_else(|| window.rfind(char::is_whitespace).filter(|&i| i > half))
+            .unwrap_or(limit);
+        pieces.push(rest[..cut].trim());
+        rest = rest[cut..].trim_start();
+    }
+    if !rest.is_empty() {
+        pieces.push(rest);
+    }
+    pieces
+}
+
+/// Последние слова куска, не больше `max_chars` символов
+fn overlap_tail(chunk: &str, max_chars: usize) -> String {
+    let chunk = chunk.trim_end();
+    let total = chunk.chars().count();
+    if total <= max_chars {
+        return String::new();
+    }
+    let start = chunk.char_indices().nth(total - max_chars).map_or(0, |(i, _)| i);
+    let tail = &chunk[start..];
+    // с начала слова
+    let tail = if chunk[..start].ends_with(char::is_whitespace) {
+        tail
+    } else {
+        tail.find(char::is_whitespace).map_or("", |i| &tail[i..])
+    };
+    let tail = tail.trim_start();
+    if tail.is_empty() {
+        String::new()
+    } else {
+        format!("…{}\n\n", tail)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::MockEmbedder;
+
+    #[test]
+    fn test_ingest_chunk_search_and_reopen() -> Result<()> {
+        let markdown = "# Tea guide\n\nIntro paragraph.\n\n## Brewing\n\nGreen tea wants 80 degrees. \
+                        Black tea wants boiling water.\n\n## Storage\n\nKeep it dry.";
+        let chunks = chunk_text(markdown, DocumentFormat::Markdown, 40);
+        assert_eq!(chunks[0], "# Tea guide\n\nIntro paragraph.");
+        assert_eq!(chunks[1], "## Brewing\n\nGreen tea wants 80 degrees.");
+        assert_eq!(chunks[2], "## Brewing\n\n…degrees.\n\nBlack tea wants boiling water.");
+        assert_eq!(chunks.last().unwrap(), "## Storage\n\nKeep it dry.");
+        let long = "word ".repeat(500);
+        let pieces = chunk_text(&long, DocumentFormat::Text, CHUNK_CHARS);
+        assert_eq!(pieces.len(), 3);
+        assert!(pieces.iter().all(|p| p.chars().count() <= CHUNK_CHARS + CHUNK_OVERLAP_CHARS + 4));
+
+        let dir = std::env::temp_dir().join(format!("ziggurat-documents-test-{}", Uuid::new_v4()));
+        std::fs::create_dir_all(&dir)?;
+        let file = dir.join("tea.md");
+        std::fs::write(&file, markdown)?;
+        let embedder: Arc<dyn Embedder> = Arc::new(MockEmbedder::new(384));
+        let mut store = DocumentStore::open(&dir.join("documents"), embedder.clone())?;
+        let Ingested::Added(document) = store.ingest_file_blocking(&file)? else {
+            panic!("a new document");
+        };
+        assert_eq!((document.title.as_str(), document.format), ("Tea guide", DocumentFormat::Markdown));
+        assert!(matches!(store.ingest_file_blocking(&file)?, Ingested::Unchanged(_)));
+        assert!(store.ingest_file_blocking(&dir.join("tea.docx")).is_err());
+
+        let target = chunk_text(markdown, DocumentFormat::Markdown, CHUNK_CHARS).pop().unwrap();
+        let hits = store.search_blocking(&embedding_text("Tea guide", &target), 1)?;
+        assert_eq!((hits[0].title.as_str(), hits[0].text.as_str()), ("Tea guide", target.as_str()));
+
+        std::fs::write(&file, format!("{}\n\n## Serving\n\nWith lemon.", markdown))?;
+        assert!(matches!(store.ingest_file_blocking(&file)?, Ingested::Replaced(ref d) if d.id == document.id));
+        let chunks = store.chunk_count();
+
+        // другая модель эмбеддингов: куски пересчитываются при открытии
+        let bigger: Arc<dyn Embedder> = Arc::new(MockEmbedder::new(512));
+        let mut reopened = DocumentStore::open(&dir.join("documents"), bigger)?;
+        assert_eq!(reopened.documents().len(), 1);
+        assert_eq!(reopened.chunk_count(), chunks);
+        assert_eq!(reopened.search_blocking("lemon", 2)?.len(), 2);
+
+        let _ = std::fs::remove_dir_all(&dir);
+        Ok(())
+    }
+}
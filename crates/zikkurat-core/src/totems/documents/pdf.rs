@@ -0,0 +1,367 @@
+//! 📑 Текст из PDF
+//!
+//! Ровно столько разбора PDF, сколько нужно для поиска по документу: потоки
+//! содержимого (`stream ... endstream`, без фильтра или сжатые `FlateDecode` с фичей
+//! `pdf`) и текстовые операторы в них (`Tj`, `TJ`, `'`, `"`). Таблицы xref и дерево
+//! страниц не разбираются - потоки читаются по порядку в файле. Шрифты со своими
+//! кодировками (Identity-H, CID) и сканы без текстового слоя дают ошибку, а не мусор в памяти.
+
+use anyhow::{bail, ensure, Result};
+
+/// Доля читаемых символов, ниже которой текст считается кодами глифов, а не буквами
+const MIN_READABLE_SHARE: f32 = 0.8;
+/// Словарь потока ищется не дальше стольких байт перед `stream`
+const MAX_DICT_BYTES: usize = 4096;
+/// Потоки, в которых нет текста страниц: шрифты, картинки, xref, объекты, XMP
+const SKIPPED_STREAMS: &[&[u8]] = &[
+    b"/Length1",
+    b"/Subtype/Image",
+    b"/Type/XRef",
+    b"/Type/ObjStm",
+    b"/Type/Metadata",
+];
+/// Сдвиг в `TJ` (тысячные доли кегля), начиная с которого он считается пробелом
+const TJ_SPACE_SHIFT: f32 = -200.0;
+
+/// Текст всех страниц документа, строка на строку текста в PDF
+pub fn extract_text(data: &[u8]) -> Result<String> {
+    ensure!(data.starts_with(b"%PDF-"), "Not a PDF file (no %PDF- header)");
+    let mut text = String::new();
+    let mut undecoded = 0;
+    let mut pos = 0;
+    while let Some(found) = find(&data[pos..], b"stream") {
+        let start = pos + found;
+        pos = start + b"stream".len();
+        if data[..start].ends_with(b"end") {
+            continue;
+        }
+        // данные начинаются со следующей строки
+        let body_start = match &data[pos..] {
+            [b'\r', b'\n', ..] => pos + 2,
+            [b'\n' | b'\r', ..] => pos + 1,
+            _ => continue,
+        };
+        let Some(len) = find(&data[body_start..], b"endstream") else {
+            break;
+        };
+        pos = body_start + len + b"endstream".len();
+
+        let dict = stream_dict(data, start);
+        if SKIPPED_STREAMS.iter().any(|key| find(&dict, key).is_some()) {
+            continue;
+        }
+        let raw = trim_eol(&data[body_start..body_start + len]);
+        let content = if find(&dict, b"/FlateDecode").is_some() {
+            match inflate(raw) {
+                Some(content) => content,
+                None => {
+                    undecoded += 1;
+                    continue;
+                }
+            }
+        } else if find(&dict, b"/Filter").is_some() {
+            continue;
+        } else {
+            raw.to_vec()
+        };
+        text.push_str(&content_text(&content));
+        text.push('\n');
+    }
+
+    let text = normalize(&text);
+    if text.is_empty() {
+        ensure!(
+            undecoded == 0 || cfg!(feature = "pdf"),
+            "The PDF text is compressed; build with the `pdf` feature to read it"
+        );
+        bail!("The PDF has no text layer (scanned pages?)");
+    }
+    ensure!(
+        readable_share(&text) >= MIN_READABLE_SHARE,
+        "The PDF uses font encodings whose text cannot be decoded"
+    );
+    Ok(text)
+}
+
+#[cfg(feature = "pdf")]
+fn inflate(raw: &[u8]) -> Option<Vec<u8>> {
+    use std::io::Read;
+    let mut out = Vec::new();
+    flate2::read::ZlibDecoder::new(raw).read_to_end(&mut out).ok()?;
+    Some(out)
+}
+
+#[cfg(not(feature = "pdf"))]
+fn inflate(_raw: &[u8]) -> Option<Vec<u8>> {
+    None
+}
+
+/// Словарь объекта перед `stream` без пробелов, чтобы `/Type /XRef` и `/Type/XRef` совпадали
+fn stream_dict(data: &[u8], stream_at: usize) -> Vec<u8> {
+    let from = stream_at.saturating_sub(MAX_DICT_BYTES);
+    let head = &data[from..stream_at];
+    let start = rfind(head, b"obj").map_or(0, |i| i + 3);
+    head[start..].iter().copied().filter(|b| !b.is_ascii_whitespace()).collect()
+}
+
+fn trim_eol(body: &[u8]) -> &[u8] {
+    let body = body.strip_suffix(b"\n").unwrap_or(body);
+    body.strip_suffix(b"\r").unwrap_or(body)
+}
+
+/// Текст одного потока содержимого: строки текстовых операторов между `BT` и `ET`
+fn content_text(content: &[u8]) -> String {
+    let mut out = String::new();
+    let mut strings: Vec<String> = Vec::new();
+    let mut numbers: Vec<f32> = Vec::new();
+    let mut in_text = false;
+    let mut in_array = false;
+    let mut i = 0;
+    while i < content.len() {
+        match content[i] {
+            b'%' => {
+                while i < content.len() && content[i] != b'\n' && content[i] != b'\r' {
+                    i += 1;
+                }
+            }
+            b'(' => {
+                let (bytes, next) = literal_string(content, i + 1);
+                strings.push(decode(&bytes));
+                i = next;
+            }
+            b'<' | b'>' if content.get(i + 1) == Some(&content[i]) => i += 2,
+            b'<' => {
+                let end = content[i..].iter().position(|&b| b == b'>').map_or(content.len(), |p| i + p);
+                strings.push(decode(&hex_string(&content[i + 1..end])));
+                i = end + 1;
+            }
+            b'[' => {
+                in_array = true;
+                i += 1;
+            }
+            b']' => {
+                in_array = false;
+                i += 1;
+            }
+            b'/' => {
+                i += 1;
+                while i < content.len() && is_regular(content[i]) {
+                    i += 1;
+                }
+            }
+            b if b.is_ascii_whitespace() || matches!(b, b'{' | b'}' | b'>' | b')') => i += 1,
+            b if b.is_ascii_digit() || matches!(b, b'-' | b'+' | b'.') => {
+                let start = i;
+                i += 1;
+                while i < content.len() && (content[i].is_ascii_digit() || content[i] == b'.') {
+                    i += 1;
+                }
+                let number = std::str::from_utf8(&content[start..i]).ok().and_then(|s| s.parse().ok()).unwrap_or(0.0);
+                if in_array && number <= TJ_SPACE_SHIFT {
+                    strings.push(" ".to_string());
+                } else if !in_array {
+                    numbers.push(number);
+                }
+            }
+            _ => {
+                let start = i;
+                while i < content.len() && is_regular(content[i]) {
+                    i += 1;
+                }
+                let operator = &content[start..i.max(start + 1)];
+                i = i.max(start + 1);
+                match operator {
+                    b"BT" => in_text = true,
+                    b"ET" => {
+                        in_text = false;
+                        out.push('\n');
+                    }
+                    b"Tj" | b"TJ" if in_text => out.extend(strings.drain(..)),
+                    b"'" | b"\"" if in_text => {
+                        out.push('\n');
+                        out.extend(strings.drain(..));
+                    }
+                    // перенос строки, если сдвиг по вертикали, иначе пробел между кусками
+                    b"Td" | b"TD" if in_text => {
+                        let vertical = numbers.last().is_some_and(|dy| dy.abs() > 0.1);
+                        out.push(if vertical { '\n' } else { ' ' });
+                    }
+                    b"T*" | b"Tm" if in_text => out.push('\n'),
+                    _ => {}
+                }
+                strings.clear();
+                numbers.clear();
+            }
+        }
+    }
+    out
+}
+
+/// Обычный символ PDF: не пробел и не разделитель
+fn is_regular(b: u8) -> bool {
+    !b.is_ascii_whitespace() && !matches!(b, b'(' | b')' | b'<' | b'>' | b'[' | b']' | b'{' | b'}' | b'/' | b'%')
+}
+
+/// Строка `(...)` с вложенными скобками и экранированием; `start` - после открывающей скобки
+fn literal_string(content: &[u8], start: usize) -> (Vec<u8>, usize) {
+    let mut bytes = Vec::new();
+    let mut depth = 1;
+    let mut i = start;
+    while i < content.len() {
+        let b = content[i];
+        i += 1;
+        match b {
+            b'\\' => {
+                let Some(&next) = content.get(i) else {
+                    break;
+                };
+                i += 1;
+                match next {
+                    b'n' => bytes.push(b'\n'),
+                    b'r' => bytes.push(b'\r'),
+                    b't' => bytes.push(b'\t'),
+                    b'b' => bytes.push(0x08),
+                    b'f' => bytes.push(0x0C),
+                    b'0'..=b'7' => {
+                        let mut value = (next - b'0') as u32;
+                        for _ in 0..2 {
+                            match content.get(i) {
+                                Some(&d @ b'0'..=b'7') => {
+                                    value = value * 8 + (d - b'0') as u32;
+                                    i += 1;
+                                }
+                                _ => break,
+                            }
+                        }
+                        bytes.push(value as u8);
+                    }
+                    // перенос строки внутри строки не входит в текст
+                    b'\r' => {
+                        if content.get(i) == Some(&b'\n') {
+                            i += 1;
+                        }
+                    }
+                    b'\n' => {}
+                    other => bytes.push(other),
+                }
+            }
+            b'(' => {
+                depth += 1;
+                bytes.push(b);
+            }
+            b')' => {
+                depth -= 1;
+                if depth == 0 {
+                    break;
+                }
+                bytes.push(b);
+            }
+            _ => bytes.push(b),
+        }
+    }
+    (bytes, i)
+}
+
+fn hex_string(hex: &[u8]) -> Vec<u8> {
+    let digits: Vec<u8> = hex
+        .iter()
+        .filter_map(|&b| (b as char).to_digit(16).map(|d| d as u8))
+        .collect();
+    digits
+        .chunks(2)
+        .map(|pair| pair[0] << 4 | pair.get(1).copied().unwrap_or(0))
+        .collect()
+}
+
+/// UTF-16BE с меткой порядка байт или однобайтовая кодировка (Latin-1 как приближение
+/// WinAnsi). Управляющие байты - коды глифов, а не текст: они становятся U+FFFD
+fn decode(bytes: &[u8]) -> String {
+    if let Some(utf16) = bytes.strip_prefix(&[0xFE, 0xFF]) {
+        let units: Vec<u16> = utf16.chunks_exact(2).map(|p| u16::from_be_bytes([p[0], p[1]])).collect();
+        return String::from_utf16_lossy(&units);
+    }
+    bytes
+        .iter()
+        .map(|&b| match b {
+            b'\n' | b'\r' | b'\t' => ' ',
+            0x00..=0x1F | 0x7F => char::REPLACEMENT_CHARACTER,
+            _ => b as char,
+        })
+        .collect()
+}
+
+/// Строки без лишних пробелов, не больше одной пустой строки подряд
+fn normalize(text: &str) -> String {
+    let mut out = String::new();
+    let mut blank = false;
+    for line in text.lines() {
+        let line = line.split_whitespace().collect::<Vec<_>>().join(" ");
+        if line.is_empty() {
+            blank = !out.is_empty();
+            continue;
+        }
+        if !out.is_empty() {
+            out.push_str(if blank { "\n\n" } else { "\n" });
+        }
+        out.push_str(&line);
+        blank = false;
+    }
+    out
+}
+
+fn readable_share(text: &str) -> f32 {
+    let total = text.chars().filter(|c| !c.is_whitespace()).count();
+    let readable = text
+        .chars()
+        .filter(|c| !c.is_whitespace() && (c.is_alphanumeric() || c.is_ascii_punctuation() || matches!(c, '–' | '—' | '«' | '»' | '…' | '’' | '“' | '”' | '•' | '№')))
+        .count();
+    readable as f32 / total.max(1) as f32
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|w| w == needle)
+}
+
+fn rfind(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).rposition(|w| w == needle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pdf(objects: &[(&str, Vec<u8>)]) -> Vec<u8> {
+        let mut out = b"%PDF-1.4\n".to_vec();
+        for (i, (dict, body)) in objects.iter().enumerate() {
+            out.extend(format!("{} 0 obj\n<< {} /Length {} >>\nstream\n", i + 1, dict, body.len()).bytes());
+            out.extend(body);
+            out.extend(b"\nendstream\nendobj\n");
+        }
+        out.extend(b"%%EOF\n");
+        out
+    }
+
+    #[test]
+    fn test_extracts_text_operators() {
+        let page = b"BT /F1 12 Tf 72 712 Td (Hello \\(PDF\\)) Tj 0 -14 Td [(Wor) -20 (ld) -300 (again)] TJ ET".to_vec();
+        let font = b"BT (not text) Tj ET".to_vec();
+        let text = extract_text(&pdf(&[("/Length1 9", font), ("", page)])).unwrap();
+        assert_eq!(text, "Hello (PDF)\nWorld again");
+
+        assert!(extract_text(b"plain text").is_err());
+        // коды глифов шрифта Identity-H вместо букв
+        let glyphs = b"BT <0001000200030004> Tj ET".to_vec();
+        assert!(extract_text(&pdf(&[("", glyphs)])).is_err());
+        assert!(extract_text(&pdf(&[("", b"0 0 m 10 10 l S".to_vec())])).is_err());
+
+        #[cfg(feature = "pdf")]
+        {
+            use std::io::Write;
+            let mut encoder = flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+            encoder.write_all(b"BT 50 700 Td <FEFF041F04400438043204350442> Tj (chapter 2) ' ET").unwrap();
+            let compressed = encoder.finish().unwrap();
+            let text = extract_text(&pdf(&[("/Filter /FlateDecode", compressed)])).unwrap();
+            assert_eq!(text, "Привет\nchapter 2");
+        }
+    }
+}
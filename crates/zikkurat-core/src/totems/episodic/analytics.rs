@@ -0,0 +1,337 @@
+//! 📊 Аналитика активности
+//!
+//! Сводка по сохранённым сессиям для внешних графиков: число ходов по дням и
+//! часам, тепловая карта «день недели × час», средняя тональность вопросов и
+//! распределение тем (частые значимые слова). Тональность считается по
+//! небольшому RU/EN словарю с учётом отрицаний, без модели.
+
+use anyhow::Result;
+use chrono::{Datelike, FixedOffset, NaiveDate, Timelike};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::io::Write;
+
+use super::persistence::SerializedSession;
+
+/// Сколько тем попадает в распределение
+pub const TOP_TOPICS: usize = 20;
+const MIN_TOPIC_CHARS: usize = 4;
+const WEEKDAYS: [&str; 7] = ["mon", "tue", "wed", "thu", "fri", "sat", "sun"];
+
+const POSITIVE: &[&str] = &[
+    "good", "great", "thanks", "thank", "love", "like", "awesome", "nice", "cool", "perfect", "happy",
+    "glad", "excellent", "wonderful", "helpful", "works", "спасибо", "отлично", "хорошо", "класс",
+    "круто", "супер", "люблю", "нравится", "рад", "рада", "здорово", "прекрасно", "помогло", "работает",
+];
+const NEGATIVE: &[&str] = &[
+    "bad", "hate", "wrong", "error", "broken", "sad", "angry", "terrible", "awful", "annoying", "fail",
+    "failed", "problem", "tired", "worse", "worst", "bug", "плохо", "ужасно", "ненавижу", "ошибка",
+    "сломалось", "грустно", "устал", "устала", "проблема", "бесит", "раздражает", "тяжело", "надоело",
+];
+const NEGATIONS: &[&str] = &["not", "no", "don't", "doesn't", "isn't", "never", "не", "нет", "никогда"];
+const STOP_WORDS: &[&str] = &[
+    "what", "that", "this", "with", "have", "from", "about", "your", "there", "they", "would", "could",
+    "should", "which", "when", "where", "does", "will", "just", "like", "know", "want", "please",
+    "thanks", "можно", "если", "когда", "тебя", "меня", "чтобы", "какой", "какая", "какие", "который",
+    "только", "очень", "есть", "было", "будет", "этот", "этого", "этом", "спасибо", "привет", "почему",
+    "сейчас", "теперь", "тоже", "также", "просто", "нужно", "надо", "могу", "хочу",
+];
+
+/// Какие ходы попадают в аналитику
+#[derive(Debug, Clone, Default)]
+pub struct ActivityFilter {
+    pub persona: Option<String>,
+    pub since: Option<NaiveDate>,
+    pub until: Option<NaiveDate>,
+}
+
+/// Формат выгрузки
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AnalyticsFormat {
+    /// Одна таблица: `kind,key,interactions,avg_sentiment`
+    Csv,
+    Json,
+}
+
+impl std::str::FromStr for AnalyticsFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "csv" => Ok(AnalyticsFormat::Csv),
+            "json" => Ok(AnalyticsFormat::Json),
+            other => anyhow::bail!("Unknown analytics format '{}' (expected csv or json)", other),
+        }
+    }
+}
+
+/// Число ходов и средняя тональность за период
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Activity {
+    pub key: String,
+    pub interactions: usize,
+    pub avg_sentiment: f32,
+}
+
+/// Тема (значимое слово) и доля ходов, где она встречается
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TopicShare {
+    pub topic: String,
+    pub interactions: usize,
+    pub share: f32,
+    pub avg_sentiment: f32,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ActivityReport {
+    pub sessions: usize,
+    pub interactions: usize,
+    pub avg_sentiment: f32,
+    /// По дням (`YYYY-MM-DD`), по возрастанию
+    pub per_day: Vec<Activity>,
+    /// По часам суток (`00`..`23`), все 24
+    pub per_hour: Vec<Activity>,
+    /// Тепловая карта: `heatmap[день недели с понедельника][час]` - число ходов
+    pub heatmap: Vec<Vec<usize>>,
+    pub topics: Vec<TopicShare>,
+}
+
+#[derive(Default)]
+struct Accumulator {
+    count: usize,
+    sentiment: f32,
+}
+
+impl Accumulator {
+    fn add(&mut self, sentiment: f32) {
+        self.count += 1;
+        self.sentiment += sentiment;
+    }
+
+    fn activity(&self, key: String) -> Activity {
+        Activity {
+            key,
+            interactions: self.count,
+            avg_sentiment: if self.count == 0 { 0.0 } else { self.sentiment / self.count as f32 },
+        }
+    }
+}
+
+/// Строит сводку по сессиям. Часы и дни считаются в часовом поясе `offset`
+pub fn analyze(sessions: &[SerializedSession], filter: &ActivityFilter, offset: FixedOffset) -> ActivityReport {
+    let mut report = ActivityReport {
+        heatmap: vec![vec![0; 24]; 7],
+        ..Default::default()
+    };
+    let mut total = Accumulator::default();
+    let mut days: BTreeMap<NaiveDate, Accumulator> = BTreeMap::new();
+    let mut hours: Vec<Accumulator> = (0..24).map(|_| Accumulator::default()).collect();
+    let mut topics: HashMap<String, Accumulator> = HashMap::new();
+
+    let sessions = sessions.iter().filter(|s| {
+        filter
+            .persona
+            .as_deref()
+            .is_none_or(|p| s.persona_name.eq_ignore_ascii_case(p))
+    });
+    for session in sessions {
+        let mut counted = false;
+        for turn in &session.turns {
+            let local = turn.timestamp.with_timezone(&offset);
+            let date = local.date_naive();
+            if filter.since.is_some_and(|since| date < since) || filter.until.is_some_and(|until| date > until) {
+                continue;
+            }
+            counted = true;
+
+            let score = sentiment(&turn.user);
+            total.add(score);
+            days.entry(date).or_default().add(score);
+            hours[local.hour() as usize].add(score);
+            report.heatmap[local.weekday().num_days_from_monday() as usize][local.hour() as usize] += 1;
+            for word in topic_words(&turn.user) {
+                topics.entry(word).or_default().add(score);
+            }
+        }
+        if counted {
+            report.sessions += 1;
+        }
+    }
+
+    report.interactions = total.count;
+    report.avg_sentiment = total.activity(String::new()).avg_sentiment;
+    report.per_day = days
+        .iter()
+        .map(|(date, acc)| acc.activity(date.format("%Y-%m-%d").to_string()))
+        .collect();
+    report.per_hour = hours
+        .iter()
+        .enumerate()
+        .map(|(hour, acc)| acc.activity(format!("{:02}", hour)))
+        .collect();
+
+    let mut topics: Vec<(String, Accumulator)> = topics.into_iter().filter(|(_, acc)| acc.count > 1).collect();
+    topics.sort_by(|a, b| b.1.count.cmp(&a.1.count).then_with(|| a.0.cmp(&b.0)));
+    report.topics = topics
+        .into_iter()
+        .take(TOP_TOPICS)
+        .map(|(topic, acc)| {
+            let activity = acc.activity(topic);
+            TopicShare {
+                share: activity.interactions as f32 / report.interactions.max(1) as f32,
+                topic: activity.key,
+                interactions: activity.interactions,
+                avg_sentiment: activity.avg_sentiment,
+            }
+        })
+        .collect();
+    report
+}
+
+/// Тональность текста от -1 до 1 по словарю; слово после отрицания меняет знак
+pub fn sentiment(text: &str) -> f32 {
+    let (mut positive, mut negative) = (0i32, 0i32);
+    let mut negated = false;
+    for word in words(text) {
+        let polarity = if POSITIVE.contains(&word.as_str()) {
+            1
+        } else if NEGATIVE.contains(&word.as_str()) {
+            -1
+        } else {
+            0
+        };
+        match (polarity, negated) {
+            (1, false) | (-1, true) => positive += 1,
+            (-1, false) | (1, true) => negative += 1,
+            _ => {}
+        }
+        negated = NEGATIONS.contains(&word.as_str());
+    }
+    if positive + negative == 0 {
+        0.0
+    } else {
+        (positive - negative) as f32 / (positive + negative) as f32
+    }
+}
+
+fn words(text: &str) -> impl Iterator<Item = String> + '_ {
+    text.split(|c: char| !c.is_alphanumeric() && c != '\'')
+        .filter(|w| !w.is_empty())
+        .map(str::to_lowercase)
+}
+
+/// Значимые слова хода без повторов
+fn topic_words(text: &str) -> Vec<String> {
+    let mut words: Vec<String> = words(text)
+        .filter(|w| w.chars().count() >= MIN_TOPIC_CHARS && !w.chars().all(|c| c.is_ascii_digit()))
+        .filter(|w| !STOP_WORDS.contains(&w.as_str()))
+        .collect();
+    words.sort();
+    words.dedup();
+    words
+}
+
+/// Пишет сводку в выбранном формате
+pub fn write_report(report: &ActivityReport, format: AnalyticsFormat, out: &mut dyn Write) -> Result<()> {
+    match format {
+        AnalyticsFormat::Json => {
+            serde_json::to_writer_pretty(&mut *out, report)?;
+            writeln!(out)?;
+        }
+        AnalyticsFormat::Csv => {
+            writeln!(out, "kind,key,interactions,avg_sentiment")?;
+            let rows = [("day", &report.per_day), ("hour", &report.per_hour)];
+            for (kind, activities) in rows {
+                for a in activities {
+                    writeln!(out, "{},{},{},{:.3}", kind, a.key, a.interactions, a.avg_sentiment)?;
+                }
+            }
+            for (day, hours) in report.heatmap.iter().enumerate() {
+                for (hour, count) in hours.iter().enumerate() {
+                    writeln!(out, "heatmap,{}-{:02},{},", WEEKDAYS[day], hour, count)?;
+                }
+            }
+            for t in &report.topics {
+                writeln!(out, "topic,{},{},{:.3}", csv_field(&t.topic), t.interactions, t.avg_sentiment)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::totems::episodic::persistence::SerializedTurn;
+    use chrono::{TimeZone, Utc};
+
+    fn session(persona: &str, turns: &[(&str, u32, u32)]) -> SerializedSession {
+        SerializedSession {
+            id: uuid::Uuid::new_v4().to_string(),
+            persona_name: persona.to_string(),
+            turns: turns
+                .iter()
+                .map(|(user, day, hour)| SerializedTurn {
+                    user: user.to_string(),
+                    assistant: "ok".to_string(),
+                    // 2024-05-06 - понедельник
+                    timestamp: Utc.with_ymd_and_hms(2024, 5, *day, *hour, 0, 0).unwrap(),
+                    metadata: Default::default(),
+                    embedding: None,
+                    sampling: None,
+                })
+                .collect(),
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            metadata: Default::default(),
+        }
+    }
+
+    #[test]
+    fn test_activity_report() {
+        let sessions = [
+            session(
+                "programmer",
+                &[
+                    ("Thanks, the borrow checker works now", 6, 9),
+                    ("The borrow checker is not helpful, error again", 6, 22),
+                    ("Спасибо, отлично", 7, 9),
+                ],
+            ),
+            session("philosopher", &[("What is virtue?", 7, 9)]),
+        ];
+        assert_eq!(sentiment("This is not good"), -1.0);
+        assert_eq!(sentiment("Спасибо, всё работает"), 1.0);
+
+        let filter = ActivityFilter {
+            persona: Some("programmer".to_string()),
+            ..Default::default()
+        };
+        let report = analyze(&sessions, &filter, FixedOffset::east_opt(0).unwrap());
+        assert_eq!((report.sessions, report.interactions), (1, 3));
+        assert_eq!(report.per_day.len(), 2);
+        assert_eq!(report.per_day[0].key, "2024-05-06");
+        assert_eq!(report.per_hour[9].interactions, 2);
+        assert_eq!(report.heatmap[0][22], 1);
+        assert_eq!(report.topics[0].topic, "borrow");
+        assert!((report.topics[0].share - 2.0 / 3.0).abs() < 1e-6);
+
+        // +03:00 сдвигает 22:00 понедельника на 01:00 вторника
+        let report = analyze(&sessions, &filter, FixedOffset::east_opt(3 * 3600).unwrap());
+        assert_eq!(report.heatmap[1][1], 1);
+
+        let mut csv = Vec::new();
+        write_report(&report, AnalyticsFormat::Csv, &mut csv).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        assert!(csv.starts_with("kind,key,interactions,avg_sentiment\nday,2024-05-06,1,"));
+        assert!(csv.contains("\nheatmap,tue-01,1,\n"));
+    }
+}
@@ -0,0 +1,311 @@
+//! 🗄️ Архив сессий
+//!
+//! Сессии, вытесненные лимитом `max_sessions`, не удаляются, а сжимаются в
+//! `memory_data/archive/<id>.json.lz4`. Для поиска рядом лежит лёгкий индекс
+//! с кратким описанием и ключевыми словами каждой сессии: он загружается только
+//! при первом поиске, а сам архив распаковывается, когда сессию открывают.
+//! С ключом памяти и архивы, и индекс пишутся зашифрованными (см. [`crypto`]).
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use super::persistence::SerializedSession;
+use crate::utils::crypto::{self, MemoryKey};
+
+const ARCHIVE_DIR: &str = "archive";
+const INDEX_FILE: &str = "index.json";
+/// Без `inference` (нет lz4) архив пишется несжатым JSON
+#[cfg(feature = "inference")]
+const BLOB_EXT: &str = "json.lz4";
+#[cfg(not(feature = "inference"))]
+const BLOB_EXT: &str = "json";
+
+const SUMMARY_CHARS: usize = 160;
+const MAX_KEYWORDS: usize = 12;
+const MIN_KEYWORD_CHARS: usize = 4;
+
+/// Запись индекса: всё, что нужно для поиска без распаковки
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArchiveEntry {
+    pub id: String,
+    pub persona_name: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub turn_count: usize,
+    /// Первый и последний вопрос пользователя
+    pub summary: String,
+    pub keywords: Vec<String>,
+}
+
+impl ArchiveEntry {
+    fn describe(session: &SerializedSession) -> Self {
+        let mut questions = session.turns.iter().map(|t| t.user.trim()).filter(|u| !u.is_empty());
+        let first = questions.next().unwrap_or_default();
+        let last = questions.next_back();
+        let summary = match last {
+            Some(last) => format!("{} … {}", clip(first, SUMMARY_CHARS / 2), clip(last, SUMMARY_CHARS / 2)),
+            None => clip(first, SUMMARY_CHARS),
+        };
+
+        let mut counts: HashMap<String, usize> = HashMap::new();
+        for turn in &session.turns {
+            for word in words(&turn.user).chain(words(&turn.assistant)) {
+                if word.chars().count() >= MIN_KEYWORD_CHARS {
+                    *counts.entry(word).or_default() += 1;
+                }
+            }
+        }
+        let mut keywords: Vec<(String, usize)> = counts.into_iter().collect();
+        keywords.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+
+        Self {
+            id: session.id.clone(),
+            persona_name: session.persona_name.clone(),
+            created_at: session.created_at,
+            updated_at: session.updated_at,
+            turn_count: session.turns.len(),
+            summary,
+            keywords: keywords.into_iter().take(MAX_KEYWORDS).map(|(w, _)| w).collect(),
+        }
+    }
+
+    /// Доля слов запроса, найденных в ключевых словах или описании
+    fn score(&self, query_words: &[String]) -> f32 {
+        if query_words.is_empty() {
+            return 0.0;
+        }
+        let summary = self.summary.to_lowercase();
+        let hits = query_words
+            .iter()
+            .filter(|q| summary.contains(q.as_str()) || self.keywords.iter().any(|k| k.starts_with(q.as_str())))
+            .count();
+        hits as f32 / query_words.len() as f32
+    }
+}
+
+/// Сжатые сессии на диске и индекс по ним
+#[derive(Debug)]
+pub struct SessionArchive {
+    dir: PathBuf,
+    /// Загружается при первом обращении
+    index: Option<Vec<ArchiveEntry>>,
+    key: Option<MemoryKey>,
+}
+
+impl SessionArchive {
+    /// Архив в `memory_dir/archive` (каталог создаётся при первой записи)
+    pub fn open(memory_dir: &Path) -> Self {
+        Self {
+            dir: memory_dir.join(ARCHIVE_DIR),
+            index: None,
+            key: crypto::installed(),
+        }
+    }
+
+    /// Ключ шифрования вместо поставленного [`crypto::install`]
+    pub fn with_encryption(mut self, key: Option<MemoryKey>) -> Self {
+        self.key = key;
+        self
+    }
+
+    fn blob_path(&self, id: &str) -> PathBuf {
+        self.dir.join(format!("{}.{}", id, BLOB_EXT))
+    }
+
+    fn index(&mut self) -> Result<&mut Vec<ArchiveEntry>> {
+        if self.index.is_none() {
+            let path = self.dir.join(INDEX_FILE);
+            let entries = if path.exists() {
+                let content = crypto::unseal_string(self.key.as_ref(), std::fs::read(&path)?)
+                    .with_context(|| format!("Failed to decrypt archive index {:?}", path))?;
+                serde_json::from_str(&content).with_context(|| format!("Failed to parse archive index {:?}", path))?
+            } else {
+                Vec::new()
+            };
+            self.index = Some(entries);
+        }
+        Ok(self.index.get_or_insert_with(Vec::new))
+    }
+
+    /// Сжимает сессию в архив и добавляет её в индекс
+    pub fn add(&mut self, session: &SerializedSession) -> Result<()> {
+        std::fs::create_dir_all(&self.dir)
+            .with_context(|| format!("Failed to create archive directory {:?}", self.dir))?;
+        let blob = crypto::seal(self.key.as_ref(), encode(serde_json::to_vec(session)?)?)?;
+        std::fs::write(self.blob_path(&session.id), blob)
+            .with_context(|| format!("Failed to archive session {}", session.id))?;
+
+        let entry = ArchiveEntry::describe(session);
+        let index = self.index()?;
+        index.retain(|e| e.id != entry.id);
+        index.push(entry);
+        let content = serde_json::to_string_pretty(index)?;
+        let content = crypto::seal(self.key.as_ref(), content)?;
+        std::fs::write(self.dir.join(INDEX_FILE), content).context("Failed to write archive index")?;
+        Ok(())
+    }
+
+    pub fn len(&mut self) -> Result<usize> {
+        Ok(self.index()?.len())
+    }
+
+    pub fn is_empty(&mut self) -> Result<bool> {
+        Ok(self.len()? == 0)
+    }
+
+    /// Ищет по индексу, не распаковывая архивы. Возвращает (score, entry) по убыванию score
+    pub fn search(&mut self, query: &str, limit: usize) -> Result<Vec<(f32, ArchiveEntry)>> {
+        let query_words: Vec<String> = words(query).filter(|w| w.chars().count() >= 3).collect();
+        let mut results: Vec<(f32, ArchiveEntry)> = self
+            .index()?
+            .iter()
+            .map(|e| (e.score(&query_words), e))
+            .filter(|(score, _)| *score > 0.0)
+            .map(|(score, e)| (score, e.clone()))
+            .collect();
+        results.sort_by(|a, b| {
+            b.0.partial_cmp(&a.0)
+                .unwrap_or(std::cmp::Ordering::Equal)
+                .then_with(|| b.1.updated_at.cmp(&a.1.updated_at))
+        });
+        results.truncate(limit);
+        Ok(results)
+    }
+
+    pub fn contains(&mut self, id: &str) -> Result<bool> {
+        Ok(self.index()?.iter().any(|e| e.id == id))
+    }
+
+    /// Все сессии архива, распакованные (для переноса памяти)
+    pub fn sessions(&mut self) -> Result<Vec<SerializedSession>> {
+        let ids: Vec<String> = self.index()?.iter().map(|e| e.id.clone()).collect();
+        ids.iter().map(|id| self.open_session(id)).collect()
+    }
+
+    /// Распаковывает одну сессию. `id_or_prefix` - полный UUID или его начало
+    pub fn open_session(&mut self, id_or_prefix: &str) -> Result<SerializedSession> {
+        let matches: Vec<String> = self
+            .index()?
+            .iter()
+            .filter(|e| e.id.starts_with(id_or_prefix))
+            .map(|e| e.id.clone())
+            .collect();
+        let id = match matches.as_slice() {
+            [id] => id,
+            [] => anyhow::bail!("No archived session '{}'", id_or_prefix),
+            _ => anyhow::bail!("'{}' matches {} archived sessions", id_or_prefix, matches.len()),
+        };
+
+        let blob = std::fs::read(self.blob_path(id))
+            .with_context(|| format!("Archived session {} is missing on disk", id))?;
+        let blob = crypto::unseal(self.key.as_ref(), blob)
+            .with_context(|| format!("Failed to decrypt archived session {}", id))?;
+        serde_json::from_slice(&decode(&blob)?).context("Failed to decode archived session")
+    }
+
+    /// Переписывает архивы и индекс зашифрованными ключом (`encrypt`) или открытыми.
+    /// Возвращает число переписанных файлов
+    pub fn reseal(&self, key: &MemoryKey, encrypt: bool) -> Result<usize> {
+        if !self.dir.exists() {
+            return Ok(0);
+        }
+        let mut rewritten = 0;
+        for entry in std::fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            if path.is_file()
+                && crypto::reseal_file(key, &path, encrypt).with_context(|| format!("Failed to rewrite {:?}", path))?
+            {
+                rewritten += 1;
+            }
+        }
+        Ok(rewritten)
+    }
+}
+
+#[cfg(feature = "inference")]
+fn encode(json: Vec<u8>) -> Result<Vec<u8>> {
+    Ok(lz4::block::compress(&json, None, true)?)
+}
+
+#[cfg(feature = "inference")]
+fn decode(blob: &[u8]) -> Result<Vec<u8>> {
+    Ok(lz4::block::decompress(blob, None)?)
+}
+
+#[cfg(not(feature = "inference"))]
+fn encode(json: Vec<u8>) -> Result<Vec<u8>> {
+    Ok(json)
+}
+
+#[cfg(not(feature = "inference"))]
+fn decode(blob: &[u8]) -> Result<Vec<u8>> {
+    Ok(blob.to_vec())
+}
+
+fn words(text: &str) -> impl Iterator<Item = String> + '_ {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .map(str::to_lowercase)
+}
+
+fn clip(text: &str, max_chars: usize) -> String {
+    if text.chars().count() <= max_chars {
+        text.to_string()
+    } else {
+        format!("{}…", text.chars().take(max_chars).collect::<String>())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::totems::episodic::persistence::SerializedTurn;
+
+    fn session(questions: &[&str]) -> SerializedSession {
+        SerializedSession {
+            id: uuid::Uuid::new_v4().to_string(),
+            persona_name: "programmer".to_string(),
+            turns: questions
+                .iter()
+                .map(|q| SerializedTurn {
+                    user: q.to_string(),
+                    assistant: "Ответ про это".to_string(),
+                    timestamp: Utc::now(),
+                    metadata: HashMap::new(),
+                    embedding: None,
+                    sampling: None,
+                })
+                .collect(),
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            metadata: HashMap::new(),
+        }
+    }
+
+    #[test]
+    fn test_archive_search_and_open() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("zikkurat_archive_{}", uuid::Uuid::new_v4()));
+        let rust = session(&["Как устроен borrow checker?", "А lifetimes в замыканиях?"]);
+        let garden = session(&["Когда сажать томаты?"]);
+
+        let mut archive = SessionArchive::open(&dir);
+        archive.add(&rust)?;
+        archive.add(&garden)?;
+
+        // a fresh handle reads the index lazily, archives stay packed
+        let mut archive = SessionArchive::open(&dir);
+        let hits = archive.search("lifetimes borrow", 5)?;
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].1.id, rust.id);
+        assert!(archive.search("квантовая физика", 5)?.is_empty());
+
+        let opened = archive.open_session(&garden.id[..8])?;
+        assert_eq!(opened.turns[0].user, "Когда сажать томаты?");
+
+        std::fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,77 @@
+//! 🔖 Закладки - отмеченные пользователем ходы
+//!
+//! `/bookmark [заметка]` отмечает последний ход текущей сессии. Закладка хранится в
+//! метаданных сессии (`bookmark:<номер хода>` → заметка), поэтому переживает
+//! рестарт вместе с `sessions.json`. Вспоминаются закладки поиском по векторному
+//! индексу эпизодов, отфильтрованному до отмеченных ходов, и по тексту заметок.
+
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+
+use super::Session;
+
+/// Префикс ключа закладки в метаданных сессии
+pub const BOOKMARK_PREFIX: &str = "bookmark:";
+
+/// Отмеченный ход
+#[derive(Debug, Clone, PartialEq)]
+pub struct Bookmark {
+    pub session_id: Uuid,
+    /// Номер хода в сессии
+    pub turn: usize,
+    /// Заметка пользователя, может быть пустой
+    pub note: String,
+    pub user: String,
+    pub assistant: String,
+    pub timestamp: DateTime<Utc>,
+}
+
+/// Ключ закладки хода в метаданных сессии
+pub fn bookmark_key(turn: usize) -> String {
+    format!("{}{}", BOOKMARK_PREFIX, turn)
+}
+
+/// Закладки сессии по порядку ходов; ключи на несуществующие ходы пропускаются
+pub fn session_bookmarks(session: &Session) -> Vec<Bookmark> {
+    let mut bookmarks: Vec<Bookmark> = session
+        .metadata
+        .iter()
+        .filter_map(|(key, note)| {
+            let turn: usize = key.strip_prefix(BOOKMARK_PREFIX)?.parse().ok()?;
+            let t = session.turns.get(turn)?;
+            Some(Bookmark {
+                session_id: session.id,
+                turn,
+                note: note.clone(),
+                user: t.user.clone(),
+                assistant: t.assistant.clone(),
+                timestamp: t.timestamp,
+            })
+        })
+        .collect();
+    bookmarks.sort_by_key(|b| b.turn);
+    bookmarks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::totems::episodic::Turn;
+
+    #[test]
+    fn test_session_bookmarks() {
+        let mut session = Session::new("programmer".to_string());
+        session.add_turn(Turn::new("что такое lifetimes?".to_string(), "Время жизни ссылки...".to_string()));
+        session.add_turn(Turn::new("спасибо".to_string(), "Пожалуйста!".to_string()));
+        session.metadata.insert(bookmark_key(1), String::new());
+        session.metadata.insert(bookmark_key(0), "lifetimes".to_string());
+        session.metadata.insert(bookmark_key(7), "gone".to_string());
+        session.metadata.insert("resumed_at".to_string(), "x".to_string());
+
+        let bookmarks = session_bookmarks(&session);
+        assert_eq!(bookmarks.len(), 2);
+        assert_eq!((bookmarks[0].turn, bookmarks[0].note.as_str()), (0, "lifetimes"));
+        assert_eq!(bookmarks[0].user, "что такое lifetimes?");
+        assert_eq!(bookmarks[1].turn, 1);
+    }
+}
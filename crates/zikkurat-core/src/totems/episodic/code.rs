@@ -0,0 +1,249 @@
+//! 🧩 Код в ходах диалога
+//!
+//! Sentence-модели плохо эмбеддят ходы, где половина текста - SQL или Rust:
+//! вектор такого хода не похож ни на вопрос, ни на код. Поэтому ход делится
+//! на прозу и код. Проза идёт в основной индекс эпизодов, код из вопроса и
+//! ответа - отдельным вектором в индекс кода (при желании - своей моделью,
+//! `--code-embedding-path`). Тогда «тот SQL-запрос, что ты писал» находит ход
+//! по коду, а обычные вопросы не тонут в синтаксисе.
+
+use uuid::Uuid;
+
+use crate::totems::retrieval::{MemoryEntry, MemoryType};
+
+/// Ключ метаданных хода с языками найденного в нём кода (`sql,rust`)
+pub const CODE_LANGS_KEY: &str = "code_langs";
+/// Ключ метаданных записи индекса кода
+pub const PART_KEY: &str = "part";
+/// Язык блока без подписи, который не удалось угадать
+const UNKNOWN_LANG: &str = "code";
+/// Сколько символов кода хода идёт в эмбеддинг
+const MAX_CODE_CHARS: usize = 2000;
+/// Сколько символов вопроса и кода показывается в найденном по коду воспоминании
+const MAX_CONTEXT_QUERY_CHARS: usize = 50;
+const MAX_CONTEXT_CODE_CHARS: usize = 100;
+/// Сообщение без разметки считается кодом, если столько его строк похожи на код
+const CODE_LINE_SHARE: f32 = 0.6;
+/// ...и строк не меньше этого
+const MIN_CODE_LINES: usize = 3;
+/// Начала строк, которые в прозе почти не встречаются
+const CODE_LINE_STARTS: &[&str] = &[
+    "SELECT ", "FROM ", "WHERE ", "JOIN ", "LEFT JOIN ", "GROUP BY ", "ORDER BY ", "INSERT ", "UPDATE ",
+    "VALUES", "fn ", "let ", "def ", "import ", "return ", "//", "#", "<", "}",
+];
+
+/// Блок кода из сообщения
+#[derive(Debug, Clone, PartialEq)]
+pub struct CodeBlock {
+    /// Язык из подписи блока (```sql) или угаданный
+    pub language: String,
+    pub code: String,
+}
+
+/// Сообщение, разделённое на прозу и код
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SplitText {
+    pub prose: String,
+    pub code: Vec<CodeBlock>,
+}
+
+/// Делит текст на прозу и блоки ``` / ~~~. Незакрытый блок идёт до конца текста.
+/// Сообщение без разметки, почти целиком из строк кода, считается одним блоком
+pub fn split_code(text: &str) -> SplitText {
+    let mut split = SplitText::default();
+    let mut prose = Vec::new();
+    let mut block: Option<(String, &str, Vec<&str>)> = None;
+
+    for line in text.lines() {
+        let trimmed = line.trim_start();
+        match block.as_mut() {
+            Some((_, fence, _)) if trimmed.starts_with(*fence) => {
+                let (label, _, lines) = block.take().expect("open block");
+                split.code.push(code_block(&label, &lines.join("\n")));
+            }
+            Some((_, _, lines)) => lines.push(line),
+            None => match ["```", "~~~"].into_iter().find(|f| trimmed.starts_with(f)) {
+                // блок в одну строку: ```SELECT 1```
+                Some(fence) if trimmed.len() > 2 * fence.len() && trimmed.trim_end().ends_with(fence) => {
+                    let inner = trimmed.trim_end();
+                    split.code.push(code_block("", &inner[fence.len()..inner.len() - fence.len()]));
+                }
+                Some(fence) => {
+                    let rest = trimmed[fence.len()..].trim();
+                    let (label, first_line) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+                    let lines = if first_line.is_empty() { Vec::new() } else { vec![first_line] };
+                    block = Some((label.to_lowercase(), fence, lines));
+                }
+                None => prose.push(line),
+            },
+        }
+    }
+    if let Some((label, _, lines)) = block {
+        split.code.push(code_block(&label, &lines.join("\n")));
+    }
+
+    let prose = prose.join("\n");
+    if split.code.is_empty() && looks_like_code(&prose) {
+        split.code.push(code_block("", &prose));
+        return split;
+    }
+    split.prose = prose.split_whitespace().collect::<Vec<_>>().join(" ");
+    split
+}
+
+fn code_block(label: &str, code: &str) -> CodeBlock {
+    let language = match label {
+        "" => guess_language(code).unwrap_or(UNKNOWN_LANG).to_string(),
+        label => label.to_string(),
+    };
+    CodeBlock {
+        language,
+        code: code.trim_matches('\n').to_string(),
+    }
+}
+
+/// Язык блока без подписи по характерным конструкциям
+pub fn guess_language(code: &str) -> Option<&'static str> {
+    let lower = code.to_lowercase();
+    let flat = format!(" {} ", lower.split_whitespace().collect::<Vec<_>>().join(" "));
+    let has = |needles: &[&str]| needles.iter().any(|n| flat.contains(n));
+    let sql = has(&["select ", "insert into", "create table", "update ", "delete from"])
+        && has(&[" from ", " where ", " values", "table ", " set "]);
+    if sql {
+        Some("sql")
+    } else if has(&["fn ", "let mut ", "impl ", "pub struct", "::new("]) {
+        Some("rust")
+    } else if has(&["def ", "import ", "print("]) && lower.contains(':') {
+        Some("python")
+    } else if has(&["function ", "const ", "=> {", "console.log"]) {
+        Some("javascript")
+    } else if lower.lines().any(|l| l.trim_start().starts_with("$ ") || l.contains("sudo ")) {
+        Some("shell")
+    } else {
+        None
+    }
+}
+
+/// Строки, похожие на код: заканчиваются `;` `{` `}` `)` или начинаются с ключевого слова
+fn looks_like_code(text: &str) -> bool {
+    let lines: Vec<&str> = text.lines().map(str::trim).filter(|l| !l.is_empty()).collect();
+    if lines.len() < MIN_CODE_LINES {
+        return false;
+    }
+    let code_lines = lines
+        .iter()
+        .filter(|l| l.ends_with([';', '{', '}', ')']) || CODE_LINE_STARTS.iter().any(|s| l.starts_with(s)))
+        .count();
+    code_lines as f32 / lines.len() as f32 >= CODE_LINE_SHARE
+}
+
+/// Языки кода хода (без повторов, в порядке появления); `None`, если кода нет
+pub fn code_languages(user: &str, assistant: &str) -> Option<String> {
+    let mut langs: Vec<String> = Vec::new();
+    for block in split_code(user).code.into_iter().chain(split_code(assistant).code) {
+        if !langs.contains(&block.language) {
+            langs.push(block.language);
+        }
+    }
+    (!langs.is_empty()).then(|| langs.join(","))
+}
+
+/// Текст вопроса для основного индекса: проза без кода (или весь вопрос, если он - только код)
+pub fn prose_for_embedding(user: &str) -> String {
+    let split = split_code(user);
+    if split.code.is_empty() || split.prose.is_empty() {
+        user.to_string()
+    } else {
+        split.prose
+    }
+}
+
+/// Весь код хода одним текстом для индекса кода; `None`, если кода нет
+pub fn code_for_embedding(user: &str, assistant: &str) -> Option<String> {
+    let blocks: Vec<String> = split_code(user)
+        .code
+        .into_iter()
+        .chain(split_code(assistant).code)
+        .filter(|b| !b.code.trim().is_empty())
+        .map(|b| format!("{}:\n{}", b.language, b.code))
+        .collect();
+    if blocks.is_empty() {
+        return None;
+    }
+    let text = blocks.join("\n\n");
+    Some(text.chars().take(MAX_CODE_CHARS).collect())
+}
+
+/// Запись индекса кода для хода `turn` сессии `session_id`
+pub(super) fn code_entry(
+    session_id: Uuid,
+    turn: usize,
+    persona: &str,
+    user: &str,
+    assistant: &str,
+    code: String,
+    embedding: Vec<f32>,
+) -> MemoryEntry {
+    MemoryEntry::new(code, embedding, MemoryType::Episodic { session_id, turn })
+        .with_metadata("session_id".to_string(), session_id.to_string())
+        .with_metadata("turn".to_string(), turn.to_string())
+        .with_metadata("persona".to_string(), persona.to_string())
+        .with_metadata("user_query".to_string(), user.to_string())
+        .with_metadata("assistant_response".to_string(), assistant.to_string())
+        .with_metadata(PART_KEY.to_string(), "code".to_string())
+}
+
+/// Найденная по коду запись
+pub fn is_code_entry(entry: &MemoryEntry) -> bool {
+    entry.metadata.get(PART_KEY).is_some_and(|p| p == "code")
+}
+
+/// Воспоминание, найденное по коду: начало вопроса и код одной строкой
+pub fn code_context(user: &str, code: &str) -> String {
+    let user = user.split_whitespace().collect::<Vec<_>>().join(" ");
+    let code = code.split_whitespace().collect::<Vec<_>>().join(" ");
+    format!(
+        "FROM PAST: User said \"{}\" and got code: {}",
+        clip(&user, MAX_CONTEXT_QUERY_CHARS),
+        clip(&code, MAX_CONTEXT_CODE_CHARS)
+    )
+}
+
+fn clip(text: &str, max_chars: usize) -> String {
+    match text.char_indices().nth(max_chars) {
+        Some((cut, _)) => format!("{}...", &text[..cut]),
+        None => text.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_code() {
+        let answer = "Here is the query:\n```sql\nSELECT name FROM users WHERE age > 30;\n```\nAnd the struct:\n```\npub struct User { name: String }\n```";
+        let split = split_code(answer);
+        assert_eq!(split.prose, "Here is the query: And the struct:");
+        assert_eq!(split.code.len(), 2);
+        assert_eq!(split.code[0].code, "SELECT name FROM users WHERE age > 30;");
+        assert_eq!((split.code[0].language.as_str(), split.code[1].language.as_str()), ("sql", "rust"));
+
+        // unlabeled paste without fences, unterminated fence
+        let pasted = "SELECT id\nFROM orders\nWHERE total > 100;\n";
+        assert_eq!(split_code(pasted).code[0].language, "sql");
+        assert_eq!(split_code("look:\n~~~python\ndef f(x):\n    return x").code[0].code, "def f(x):\n    return x");
+        assert!(split_code("Как дела?\nВсё хорошо.\nСпасибо!").code.is_empty());
+        assert_eq!(split_code("```SELECT * FROM t WHERE id = 1```").code[0].language, "sql");
+
+        assert_eq!(code_languages("why is this slow?\n```sql\nSELECT 1\n```", answer).as_deref(), Some("sql,rust"));
+        assert_eq!(prose_for_embedding("why is this slow?\n```sql\nSELECT 1\n```"), "why is this slow?");
+        assert_eq!(prose_for_embedding(pasted), pasted);
+        assert!(code_for_embedding("hi", "hello").is_none());
+        assert!(code_for_embedding("hi", answer).unwrap().starts_with("sql:\nSELECT name"));
+        assert_eq!(
+            code_context("why is this slow?", "sql:\nSELECT 1"),
+            "FROM PAST: User said \"why is this slow?\" and got code: sql: SELECT 1"
+        );
+    }
+}
@@ -0,0 +1,270 @@
+//! 📤 Экспорт диалогов в датасеты для fine-tuning
+//!
+//! Превращает сохранённые сессии в JSONL (ShareGPT или Alpaca) с фильтрами
+//! по персоне, оценке `/good` и датам. Персональные данные вырезаются.
+
+use anyhow::Result;
+use chrono::NaiveDate;
+use regex::Regex;
+use serde_json::json;
+use std::io::Write;
+use std::sync::OnceLock;
+
+use super::persistence::{SerializedSession, SerializedTurn};
+
+/// Ключ метаданных хода с оценкой пользователя
+pub const RATING_KEY: &str = "rating";
+
+/// Формат датасета
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DatasetFormat {
+    /// Строка на сессию: `{"conversations": [{"from": "human", ...}, {"from": "gpt", ...}]}`
+    ShareGpt,
+    /// Строка на ход: `{"instruction", "input", "output"}`
+    Alpaca,
+}
+
+impl std::str::FromStr for DatasetFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "sharegpt" => Ok(DatasetFormat::ShareGpt),
+            "alpaca" => Ok(DatasetFormat::Alpaca),
+            other => anyhow::bail!("Unknown dataset format '{}' (expected sharegpt or alpaca)", other),
+        }
+    }
+}
+
+/// Оценка хода, поставленная через `/good` / `/bad`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rating {
+    Good,
+    Bad,
+}
+
+impl Rating {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Rating::Good => "good",
+            Rating::Bad => "bad",
+        }
+    }
+
+    pub fn of(turn: &SerializedTurn) -> Option<Self> {
+        match turn.metadata.get(RATING_KEY).map(String::as_str) {
+            Some("good") => Some(Rating::Good),
+            Some("bad") => Some(Rating::Bad),
+            _ => None,
+        }
+    }
+}
+
+/// Какие ходы попадают в датасет. Ходы с оценкой `/bad` и повторы не экспортируются никогда
+#[derive(Debug, Clone, Default)]
+pub struct DatasetFilter {
+    pub persona: Option<String>,
+    /// Только ходы с оценкой `/good`
+    pub only_good: bool,
+    pub since: Option<NaiveDate>,
+    pub until: Option<NaiveDate>,
+}
+
+impl DatasetFilter {
+    fn accepts_session(&self, session: &SerializedSession) -> bool {
+        self.persona
+            .as_deref()
+            .is_none_or(|p| session.persona_name.eq_ignore_ascii_case(p))
+    }
+
+    fn accepts_turn(&self, turn: &SerializedTurn) -> bool {
+        let date = turn.timestamp.date_naive();
+        let rating = Rating::of(turn);
+
+        rating != Some(Rating::Bad)
+            && (!self.only_good || rating == Some(Rating::Good))
+            && self.since.is_none_or(|since| date >= since)
+            && self.until.is_none_or(|until| date <= until)
+            && !turn.metadata.contains_key(super::DUPLICATE_OF_KEY)
+            && !turn.user.trim().is_empty()
+            && !turn.assistant.trim().is_empty()
+    }
+}
+
+/// Итог экспорта
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ExportStats {
+    pub sessions: usize,
+    pub turns: usize,
+    pub records: usize,
+    pub redactions: usize,
+}
+
+/// Пишет датасет в `out` построчно (JSONL)
+pub fn export_dataset(
+    sessions: &[SerializedSession],
+    format: DatasetFormat,
+    filter: &DatasetFilter,
+    out: &mut dyn Write,
+) -> Result<ExportStats> {
+    let mut stats = ExportStats::default();
+
+    for session in sessions.iter().filter(|s| filter.accepts_session(s)) {
+        let turns: Vec<(String, String)> = session
+            .turns
+            .iter()
+            .filter(|t| filter.accepts_turn(t))
+            .map(|t| {
+                let (user, n_user) = redact_pii(&t.user);
+                let (assistant, n_assistant) = redact_pii(&t.assistant);
+                stats.redactions += n_user + n_assistant;
+                (user, assistant)
+            })
+            .collect();
+
+        if turns.is_empty() {
+            continue;
+        }
+        stats.sessions += 1;
+        stats.turns += turns.len();
+
+        match format {
+            DatasetFormat::ShareGpt => {
+                let conversations: Vec<_> = turns
+                    .iter()
+                    .flat_map(|(user, assistant)| {
+                        [
+                            json!({"from": "human", "value": user}),
+                            json!({"from": "gpt", "value": assistant}),
+                        ]
+                    })
+                    .collect();
+                writeln!(out, "{}", json!({ "conversations": conversations }))?;
+                stats.records += 1;
+            }
+            DatasetFormat::Alpaca => {
+                for (user, assistant) in &turns {
+                    writeln!(
+                        out,
+                        "{}",
+                        json!({"instruction": user, "input": "", "output": assistant})
+                    )?;
+                    stats.records += 1;
+                }
+            }
+        }
+    }
+
+    Ok(stats)
+}
+
+/// Заменяет персональные данные плейсхолдерами. Возвращает текст и число замен
+pub fn redact_pii(text: &str) -> (String, usize) {
+    static PATTERNS: OnceLock<Vec<(Regex, &'static str)>> = OnceLock::new();
+    let patterns = PATTERNS.get_or_init(|| {
+        [
+            (r"[\w.+-]+@[\w-]+(?:\.[\w-]+)+", "[EMAIL]"),
+            // ключи API и токены: sk-..., ghp_..., длинные hex/base64 строки
+            (r"\b(?:sk|pk|ghp|gho|xox[abp])[-_][A-Za-z0-9_-]{16,}\b", "[SECRET]"),
+            (r"\b[A-Fa-f0-9]{32,}\b", "[SECRET]"),
+            (r"\b\d(?:[ -]?\d){12,18}\b", "[CARD]"),
+            (r"\b(?:\d{1,3}\.){3}\d{1,3}\b", "[IP]"),
+            (r"(?:\+\d{1,3}[\s-]?)?\(?\d{3}\)?[\s-]?\d{3}[\s-]?\d{2}[\s-]?\d{2}\b", "[PHONE]"),
+        ]
+        .into_iter()
+        .map(|(pattern, placeholder)| (Regex::new(pattern).expect("valid PII pattern"), placeholder))
+        .collect()
+    });
+
+    let mut result = text.to_string();
+    let mut count = 0;
+    for (re, placeholder) in patterns {
+        let matches = re.find_iter(&result).count();
+        if matches > 0 {
+            count += matches;
+            result = re.replace_all(&result, *placeholder).into_owned();
+        }
+    }
+    (result, count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{TimeZone, Utc};
+    use std::collections::HashMap;
+
+    fn turn(user: &str, assistant: &str, day: u32, rating: Option<&str>) -> SerializedTurn {
+        let mut metadata = HashMap::new();
+        if let Some(rating) = rating {
+            metadata.insert(RATING_KEY.to_string(), rating.to_string());
+        }
+        SerializedTurn {
+            user: user.to_string(),
+            assistant: assistant.to_string(),
+            timestamp: Utc.with_ymd_and_hms(2024, 5, day, 12, 0, 0).unwrap(),
+            metadata,
+            embedding: None,
+            sampling: None,
+        }
+    }
+
+    fn session(persona: &str, turns: Vec<SerializedTurn>) -> SerializedSession {
+        SerializedSession {
+            id: uuid::Uuid::new_v4().to_string(),
+            persona_name: persona.to_string(),
+            turns,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            metadata: HashMap::new(),
+        }
+    }
+
+    #[test]
+    fn test_redact_pii() {
+        let (text, n) = redact_pii("Пиши на ivan.petrov@mail.ru или звони +7 916 123-45-67");
+        assert_eq!(text, "Пиши на [EMAIL] или звони [PHONE]");
+        assert_eq!(n, 2);
+
+        let (text, _) = redact_pii("key sk-abcdefghijklmnop1234, card 4111 1111 1111 1111, host 10.0.0.12");
+        assert_eq!(text, "key [SECRET], card [CARD], host [IP]");
+
+        assert_eq!(redact_pii("Rust 1.75 вышел в 2023").1, 0);
+    }
+
+    #[test]
+    fn test_export_filters_and_formats() {
+        let sessions = vec![
+            session(
+                "programmer",
+                vec![
+                    turn("What is Rust?", "A systems language.", 1, Some("good")),
+                    turn("Mail me at a@b.io", "Sure.", 2, None),
+                    turn("Bad one", "Wrong answer", 3, Some("bad")),
+                ],
+            ),
+            session("philosopher", vec![turn("Who am I?", "A question.", 1, Some("good"))]),
+        ];
+
+        let filter = DatasetFilter {
+            persona: Some("programmer".to_string()),
+            ..Default::default()
+        };
+        let mut out = Vec::new();
+        let stats = export_dataset(&sessions, DatasetFormat::ShareGpt, &filter, &mut out).unwrap();
+        assert_eq!((stats.sessions, stats.turns, stats.records, stats.redactions), (1, 2, 1, 1));
+        let line: serde_json::Value = serde_json::from_slice(&out).unwrap();
+        assert_eq!(line["conversations"].as_array().unwrap().len(), 4);
+        assert_eq!(line["conversations"][2]["value"], "Mail me at [EMAIL]");
+
+        let filter = DatasetFilter {
+            only_good: true,
+            until: NaiveDate::from_ymd_opt(2024, 5, 1),
+            ..Default::default()
+        };
+        let mut out = Vec::new();
+        let stats = export_dataset(&sessions, DatasetFormat::Alpaca, &filter, &mut out).unwrap();
+        assert_eq!(stats.records, 2);
+        assert!(String::from_utf8(out).unwrap().contains("\"instruction\":\"Who am I?\""));
+    }
+}
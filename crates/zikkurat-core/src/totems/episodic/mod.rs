@@ -0,0 +1,2084 @@
+//! 📜 Эпизодическая память - История диалогов и событий
+//!
+//! Управляет диалоговыми сессиями с автоматической векторизацией
+//! и поиском похожих разговоров из прошлого
+
+#![allow(dead_code)]
+
+pub mod analytics;
+pub mod archive;
+pub mod bookmarks;
+pub mod code;
+pub mod export;
+pub mod persistence;
+pub mod segmentation;
+pub mod style;
+pub mod summaries;
+pub mod timeline;
+pub mod transcript;
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, FixedOffset, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::logos::metrics;
+use crate::priests::embeddings::{AsyncEmbedder, Embedder};
+use crate::totems::consent::{ConsentMode, EPHEMERAL_KEY};
+use crate::totems::language::{Language, LANGUAGE_KEY};
+use crate::totems::significance::{SignificanceFilter, ACK_KEY};
+use crate::totems::user::UserId;
+use crate::utils::clock::{self, SharedClock};
+use crate::utils::relative_time::humanize;
+use crate::totems::retrieval::vector_store::cosine_similarity;
+use crate::totems::retrieval::{MemoryEntry, MemoryType, RecallCache, RetrievalConfig, VectorStore};
+
+/// Обмен в диалоге (пользователь - ассистент)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Turn {
+    /// Вопрос пользователя
+    pub user: String,
+    /// Ответ ассистента
+    pub assistant: String,
+    /// Временная метка
+    pub timestamp: DateTime<Utc>,
+    /// Дополнительные метаданные
+    pub metadata: HashMap<String, String>,
+    /// Состояние сэмплирования, с которым был сгенерирован ответ (для `--replay`)
+    #[serde(default)]
+    pub sampling: Option<SamplingRecord>,
+}
+
+/// Параметры генерации одного ответа: по ним `--replay` воспроизводит его побитово
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SamplingRecord {
+    /// Seed LogitsProcessor для этого хода
+    pub seed: u64,
+    pub temperature: f64,
+    pub top_p: Option<f64>,
+    pub top_k: Option<usize>,
+    pub repeat_penalty: f32,
+    pub repeat_last_n: usize,
+    /// Лимит токенов, переданный в генерацию
+    pub max_tokens: usize,
+    /// Число токенов промпта
+    pub prompt_tokens: usize,
+    /// Число сгенерированных токенов
+    pub generated_tokens: usize,
+    /// Точный вход модели (промпт с контекстом памяти)
+    pub prompt: String,
+}
+
+impl Turn {
+    /// Создает новый обмен
+    pub fn new(user: String, assistant: String) -> Self {
+        Self {
+            user,
+            assistant,
+            timestamp: Utc::now(),
+            metadata: HashMap::new(),
+            sampling: None,
+        }
+    }
+
+    /// Прикрепляет состояние сэмплирования
+    pub fn with_sampling(mut self, sampling: SamplingRecord) -> Self {
+        self.sampling = Some(sampling);
+        self
+    }
+
+    /// Объединенный текст для векторизации
+    pub fn combined_text(&self) -> String {
+        format!("User: {}\nAssistant: {}", self.user, self.assistant)
+    }
+
+    /// Добавляет метаданные
+    pub fn with_metadata(mut self, key: String, value: String) -> Self {
+        self.metadata.insert(key, value);
+        self
+    }
+}
+
+/// Прибавка к сходству воспоминания на том же языке, что и запрос
+pub const LANGUAGE_BOOST: f32 = 0.05;
+
+/// Метаданные хода: когда он последний раз попал в промпт как воспоминание
+pub const RECALLED_AT_KEY: &str = "recalled_at";
+/// Метаданные хода: сколько раз он попадал в промпт как воспоминание
+pub const RECALL_COUNT_KEY: &str = "recall_count";
+
+/// Метаданные сессии: когда её продолжили из истории
+pub const RESUMED_AT_KEY: &str = "resumed_at";
+/// Метаданные сессии: чья она, если не пользователя по умолчанию
+pub const USER_KEY: &str = "user";
+
+/// [`LANGUAGE_BOOST`], если язык записи совпадает с языком запроса.
+/// У записей без метки (старые данные) язык определяется по тексту вопроса
+fn language_boost(query: Option<Language>, entry: &MemoryEntry) -> f32 {
+    let Some(query) = query else {
+        return 0.0;
+    };
+    let entry_language = match entry.metadata.get(LANGUAGE_KEY) {
+        Some(tag) => tag.parse().ok(),
+        None => Language::detect(entry.metadata.get("user_query").unwrap_or(&entry.text)),
+    };
+    if entry_language == Some(query) {
+        LANGUAGE_BOOST
+    } else {
+        0.0
+    }
+}
+
+/// Слова запроса, по которым идёт keyword-поиск
+fn query_keywords(query: &str) -> Vec<String> {
+    query
+        .split_whitespace()
+        .filter(|w| w.len() > 3)
+        .map(str::to_lowercase)
+        .collect()
+}
+
+/// Доля слов запроса, встречающихся в вопросе или ответе записи; `None` - ни одного
+fn keyword_score(keywords: &[String], entry: &MemoryEntry) -> Option<f32> {
+    if keywords.is_empty() {
+        return None;
+    }
+    let user_text = entry.metadata.get("user_query").unwrap_or(&entry.text);
+    let assistant_text = entry.metadata.get("assistant_response").map(String::as_str).unwrap_or("");
+    let full_text = format!("{} {}", user_text, assistant_text).to_lowercase();
+
+    let keyword_count = keywords.iter().filter(|k| full_text.contains(k.as_str())).count();
+    (keyword_count > 0).then(|| (keyword_count as f32 / keywords.len() as f32).min(1.0))
+}
+
+/// Ключ метаданных хода-дубля: номер хода, который он повторяет
+pub const DUPLICATE_OF_KEY: &str = "duplicate_of";
+
+/// Порог сходства триграмм, с которого сообщения считаются одинаковыми
+const DUPLICATE_SIMILARITY: f32 = 0.9;
+
+/// Одинаковые сообщения с точностью до регистра, пробелов, пунктуации и мелких опечаток
+pub fn is_near_duplicate(a: &str, b: &str) -> bool {
+    let normalize = |s: &str| -> String {
+        s.to_lowercase()
+            .chars()
+            .map(|c| if c.is_alphanumeric() { c } else { ' ' })
+            .collect::<String>()
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" ")
+    };
+    let (a, b) = (normalize(a), normalize(b));
+    if a.is_empty() || b.is_empty() {
+        return false;
+    }
+    if a == b {
+        return true;
+    }
+    // "Rust 1.74" и "Rust 1.75" - разные вопросы, хотя почти совпадают
+    let digits = |s: &str| -> String { s.chars().filter(|c| c.is_ascii_digit()).collect() };
+    if digits(&a) != digits(&b) {
+        return false;
+    }
+
+    let trigrams = |s: &str| -> std::collections::HashSet<Vec<char>> {
+        let chars: Vec<char> = s.chars().collect();
+        chars.windows(3).map(|w| w.to_vec()).collect()
+    };
+    let (ta, tb) = (trigrams(&a), trigrams(&b));
+    if ta.is_empty() || tb.is_empty() {
+        return false;
+    }
+    let common = ta.intersection(&tb).count() as f32;
+    common / ta.union(&tb).count() as f32 >= DUPLICATE_SIMILARITY
+}
+
+/// Текст, по которому ищется ход. Код из вопроса не размывает вектор прозы: он уходит в индекс кода
+fn query_text(user: &str) -> String {
+    format!("User query: {}", code::prose_for_embedding(user))
+}
+
+/// Ход, который не попадает в векторный индекс: рабочая память, повтор или подтверждение
+fn is_unindexed(turn: &Turn) -> bool {
+    [EPHEMERAL_KEY, DUPLICATE_OF_KEY, ACK_KEY]
+        .iter()
+        .any(|key| turn.metadata.contains_key(*key))
+}
+
+/// Запись векторного индекса для хода `turn_id` сессии
+fn turn_entry(session: &Session, turn_id: usize, embedding: Vec<f32>) -> MemoryEntry {
+    let turn = &session.turns[turn_id];
+    let mut entry = MemoryEntry::new(
+        turn.user.clone(),
+        embedding,
+        MemoryType::Episodic {
+            session_id: session.id,
+            turn: turn_id,
+        },
+    )
+    .with_metadata("session_id".to_string(), session.id.to_string())
+    .with_metadata("turn".to_string(), turn_id.to_string())
+    .with_metadata("persona".to_string(), session.persona_name.clone())
+    .with_metadata("user_query".to_string(), turn.user.clone())
+    .with_metadata("assistant_response".to_string(), turn.assistant.clone());
+    entry.timestamp = turn.timestamp;
+    restore_access(&mut entry, &turn.metadata);
+    match turn.metadata.get(LANGUAGE_KEY) {
+        Some(language) => entry.with_metadata(LANGUAGE_KEY.to_string(), language.clone()),
+        None => entry,
+    }
+}
+
+/// Счётчики обращений записи из метаданных её хода
+pub(crate) fn restore_access(entry: &mut MemoryEntry, metadata: &HashMap<String, String>) {
+    entry.last_accessed = metadata
+        .get(RECALLED_AT_KEY)
+        .and_then(|at| DateTime::parse_from_rfc3339(at).ok())
+        .map(|at| at.with_timezone(&Utc));
+    entry.access_count = metadata.get(RECALL_COUNT_KEY).and_then(|c| c.parse().ok()).unwrap_or(0);
+}
+
+/// Диалоговая сессия
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Session {
+    /// Уникальный идентификатор сессии
+    pub id: Uuid,
+    /// Имя личности (архетипа)
+    pub persona_name: String,
+    /// Список обменов в диалоге
+    pub turns: Vec<Turn>,
+    /// Время создания сессии
+    pub created_at: DateTime<Utc>,
+    /// Время последнего обновления
+    pub updated_at: DateTime<Utc>,
+    /// Метаданные сессии
+    pub metadata: HashMap<String, String>,
+}
+
+impl Session {
+    /// Создает новую сессию
+    pub fn new(persona_name: String) -> Self {
+        let now = Utc::now();
+        Self {
+            id: Uuid::new_v4(),
+            persona_name,
+            turns: Vec::new(),
+            created_at: now,
+            updated_at: now,
+            metadata: HashMap::new(),
+        }
+    }
+
+    /// Добавляет обмен в сессию
+    pub fn add_turn(&mut self, turn: Turn) {
+        self.updated_at = turn.timestamp;
+        self.turns.push(turn);
+    }
+
+    /// Возвращает количество обменов
+    pub fn turn_count(&self) -> usize {
+        self.turns.len()
+    }
+
+    /// Возвращает текст последнего обмена
+    pub fn last_turn(&self) -> Option<&Turn> {
+        self.turns.last()
+    }
+
+    /// Получает последние N обменов
+    pub fn last_turns(&self, n: usize) -> &[Turn] {
+        let start = if self.turns.len() > n {
+            self.turns.len() - n
+        } else {
+            0
+        };
+        &self.turns[start..]
+    }
+
+    /// Ходы, которые можно хранить и пересказывать: без ходов рабочей памяти,
+    /// на которые нет согласия ([`EPHEMERAL_KEY`])
+    pub fn durable_turns(&self) -> impl Iterator<Item = &Turn> {
+        self.turns.iter().filter(|t| !t.metadata.contains_key(EPHEMERAL_KEY))
+    }
+
+    /// Последние `n` из [`Session::durable_turns`]
+    pub fn last_durable_turns(&self, n: usize) -> Vec<Turn> {
+        let durable: Vec<&Turn> = self.durable_turns().collect();
+        durable[durable.len().saturating_sub(n)..].iter().map(|t| (*t).clone()).collect()
+    }
+
+    /// Формирует контекст из последних обменов
+    pub fn format_context(&self, max_turns: usize, max_chars: usize) -> String {
+        let recent_turns = self.last_turns(max_turns);
+        let mut context = String::new();
+
+        for turn in recent_turns {
+            let user_char_count = turn.user.chars().count();
+            let user = if user_char_count > max_chars / 4 {
+                let byte_pos = turn
+                    .user
+                    .char_indices()
+                    .nth(max_chars / 4)
+                    .unwrap_or((turn.user.len(), ' '))
+                    .0;
+                &turn.user[..byte_pos]
+            } else {
+                &turn.user
+            };
+
+            let assistant_char_count = turn.assistant.chars().count();
+            let assistant = if assistant_char_count > max_chars / 4 {
+                let byte_pos = turn
+                    .assistant
+                    .char_indices()
+                    .nth(max_chars / 4)
+                    .unwrap_or((turn.assistant.len(), ' '))
+                    .0;
+                &turn.assistant[..byte_pos]
+            } else {
+                &turn.assistant
+            };
+            context.push_str(&format!("User: {}\nAssistant: {}\n\n", user, assistant));
+
+            if context.chars().count() > max_chars {
+                let context_byte_pos = context
+                    .char_indices()
+                    .nth(max_chars)
+                    .unwrap_or((context.len(), ' '))
+                    .0;
+                return context[..context_byte_pos].to_string();
+            }
+        }
+
+        context.trim_end().to_string()
+    }
+}
+
+/// Сессия, оставшаяся на диске: её ходы и эмбеддинги подгружаются по требованию
+#[derive(Debug, Clone)]
+pub struct DeferredSession {
+    pub persona_name: String,
+    pub updated_at: DateTime<Utc>,
+    pub turns: usize,
+    /// Разбор сессии для ленты (см. [`timeline`])
+    pub analysis: Option<SessionAnalysis>,
+}
+
+/// Менеджер эпизодической памяти
+pub struct DialogueManager {
+    /// Текущая сессия
+    current_session: Session,
+    /// Векторное хранилище для быстрого поиска
+    vector_store: VectorStore,
+    /// Индекс собственных ответов персоны для STYLE MEMORY (см. [`style`])
+    style_store: VectorStore,
+    /// Индекс кода из ходов (см. [`code`])
+    code_store: VectorStore,
+    /// Эмбеддинг движок
+    embedder: Arc<dyn Embedder>,
+    /// Отдельная модель для кода; `None` - основной эмбеддер
+    code_embedder: Option<Arc<dyn Embedder>>,
+    /// Имя модели, которой построен `code_store`
+    code_model: Option<String>,
+    /// История всех сессий
+    session_history: HashMap<Uuid, Session>,
+    /// Максимальное количество хранимых сессий
+    max_sessions: usize,
+    /// Куда уходят сессии сверх `max_sessions` (без архива они удаляются)
+    archive: Option<Arc<parking_lot::Mutex<archive::SessionArchive>>>,
+    /// Сессии, оставленные на диске при частичной загрузке (см. [`persistence::LoadScope`])
+    deferred: HashMap<Uuid, DeferredSession>,
+    /// Часовой пояс пользователя для «3 weeks ago» в найденных воспоминаниях
+    utc_offset: FixedOffset,
+    /// Какие ходы попадают в долговременную память
+    consent: ConsentMode,
+    /// Подтверждения («ok», «спасибо») остаются в сессии, но не индексируются
+    significance: SignificanceFilter,
+    /// Время ходов, сессий и «3 weeks ago»
+    clock: SharedClock,
+    /// Кандидаты прошлого поиска для уточняющих вопросов в той же теме
+    recall_cache: RecallCache,
+    /// Веса и пороги поиска воспоминаний
+    retrieval: RetrievalConfig,
+    /// Чья это память: помечает новые сессии
+    user: UserId,
+    /// Автоматическая смена сессии (см. [`segmentation`]); `None` - только вручную
+    segmentation: Option<segmentation::SegmentationConfig>,
+    /// Разбирает закрытую автоматически сессию для ленты
+    segment_summarizer: Option<Arc<dyn LlmPipeline>>,
+    /// Автоматические смены сессии, о которых ещё не спросили
+    session_breaks: Vec<segmentation::SessionBreak>,
+    /// Разборы закрытых сессий, посчитанные фоновыми задачами ([`DialogueManager::segment_summary_job`])
+    segment_summaries: Arc<parking_lot::Mutex<Vec<(Uuid, SessionAnalysis)>>>,
+}
+
+impl Clone for DialogueManager {
+    fn clone(&self) -> Self {
+        Self {
+            current_session: self.current_session.clone(),
+            vector_store: self.vector_store.clone(),
+            style_store: self.style_store.clone(),
+            code_store: self.code_store.clone(),
+            embedder: self.embedder.clone(),
+            code_embedder: self.code_embedder.clone(),
+            code_model: self.code_model.clone(),
+            session_history: self.session_history.clone(),
+            max_sessions: self.max_sessions,
+            archive: self.archive.clone(),
+            deferred: self.deferred.clone(),
+            utc_offset: self.utc_offset,
+            consent: self.consent,
+            significance: self.significance.clone(),
+            clock: self.clock.clone(),
+            recall_cache: self.recall_cache.clone(),
+            retrieval: self.retrieval.clone(),
+            user: self.user.clone(),
+            segmentation: self.segmentation.clone(),
+            segment_summarizer: self.segment_summarizer.clone(),
+            session_breaks: self.session_breaks.clone(),
+            segment_summaries: self.segment_summaries.clone(),
+        }
+    }
+}
+
+impl DialogueManager {
+    /// Создает новый менеджер диалогов
+    pub fn new(embedder: Arc<dyn Embedder>, persona_name: String) -> Self {
+        let dimension = embedder.embedding_dim();
+        Self {
+            current_session: Session::new(persona_name),
+            vector_store: VectorStore::new(dimension),
+            style_store: VectorStore::new(dimension),
+            code_store: VectorStore::new(dimension),
+            embedder,
+            code_embedder: None,
+            code_model: None,
+            session_history: HashMap::new(),
+            max_sessions: 100, // Ограничиваем количество сессий
+            archive: None,
+            deferred: HashMap::new(),
+            utc_offset: FixedOffset::east_opt(0).expect("zero offset"),
+            consent: ConsentMode::default(),
+            significance: SignificanceFilter::default(),
+            clock: clock::default_clock(),
+            recall_cache: RecallCache::default(),
+            retrieval: RetrievalConfig::default(),
+            user: UserId::default(),
+            segmentation: None,
+            segment_summarizer: None,
+            session_breaks: Vec::new(),
+            segment_summaries: Arc::default(),
+        }
+    }
+
+    /// Создает с кастомными параметрами
+    pub fn with_config(
+        embedder: Arc<dyn Embedder>,
+        persona_name: String,
+        max_sessions: usize,
+    ) -> Self {
+        let dimension = embedder.embedding_dim();
+        Self {
+            current_session: Session::new(persona_name),
+            vector_store: VectorStore::new(dimension),
+            style_store: VectorStore::new(dimension),
+            code_store: VectorStore::new(dimension),
+            embedder,
+            code_embedder: None,
+            code_model: None,
+            session_history: HashMap::new(),
+            max_sessions,
+            archive: None,
+            deferred: HashMap::new(),
+            utc_offset: FixedOffset::east_opt(0).expect("zero offset"),
+            consent: ConsentMode::default(),
+            significance: SignificanceFilter::default(),
+            clock: clock::default_clock(),
+            recall_cache: RecallCache::default(),
+            retrieval: RetrievalConfig::default(),
+            user: UserId::default(),
+            segmentation: None,
+            segment_summarizer: None,
+            session_breaks: Vec::new(),
+            segment_summaries: Arc::default(),
+        }
+    }
+
+    /// Добавляет обмен в текущую сессию и векторизует его
+    pub async fn add_exchange(&mut self, user: String, assistant: String) -> Result<()> {
+        self.add_exchange_with_sampling(user, assistant, None).await
+    }
+
+    /// Добавляет обмен вместе с состоянием сэмплирования ответа
+    pub async fn add_exchange_with_sampling(
+        &mut self,
+        user: String,
+        assistant: String,
+        sampling: Option<SamplingRecord>,
+    ) -> Result<()> {
+        let mut turn = Turn::new(user.clone(), assistant.clone());
+        turn.timestamp = self.clock.now();
+        turn.sampling = sampling;
+        let language = Language::detect(&user);
+        if let Some(language) = language {
+            turn.metadata
+                .insert(LANGUAGE_KEY.to_string(), language.as_str().to_string());
+        }
+        if let Some(langs) = code::code_languages(&user, &assistant) {
+            turn.metadata.insert(code::CODE_LANGS_KEY.to_string(), langs);
+        }
+        let indexable = self.consent.allows(&user) && !self.significance.is_ack(&user);
+        self.apply_segment_summaries();
+        let query_embedding = self.segment_if_needed(&user, indexable).await?;
+
+        // Без согласия ход остаётся только в рабочей памяти: не индексируется и не сохраняется
+        if !self.consent.allows(&user) {
+            turn.metadata.insert(EPHEMERAL_KEY.to_string(), "true".to_string());
+            self.current_session.add_turn(turn);
+            return Ok(());
+        }
+
+        if self.significance.is_ack(&user) {
+            turn.metadata.insert(ACK_KEY.to_string(), "true".to_string());
+            self.current_session.add_turn(turn);
+            return Ok(());
+        }
+
+        // Повторно отправленное сообщение остаётся в истории, но не в векторном индексе,
+        // иначе дубли вытесняют остальное при поиске
+        let previous_turn = self.current_session.turn_count().checked_sub(1);
+        if let Some(prev) = previous_turn {
+            if is_near_duplicate(&self.current_session.turns[prev].user, &user) {
+                turn.metadata
+                    .insert(DUPLICATE_OF_KEY.to_string(), prev.to_string());
+                self.current_session.add_turn(turn);
+                return Ok(());
+            }
+        }
+        let turn_id = self.current_session.turn_count();
+        self.current_session.add_turn(turn);
+        self.index_turn(turn_id, query_embedding).await?;
+
+        self.cleanup_if_needed();
+
+        Ok(())
+    }
+
+    /// Переводит последний ход из рабочей памяти в долговременную (`/remember`).
+    /// `false`, если ходов нет или последний уже запомнен
+    pub async fn remember_last_turn(&mut self) -> Result<bool> {
+        let Some(turn_id) = self.current_session.turn_count().checked_sub(1) else {
+            return Ok(false);
+        };
+        if self.current_session.turns[turn_id].metadata.remove(EPHEMERAL_KEY).is_none() {
+            return Ok(false);
+        }
+        self.index_turn(turn_id, None).await?;
+        Ok(true)
+    }
+
+    pub fn remember_last_turn_blocking(&mut self) -> Result<bool> {
+        crate::utils::block_on(self.remember_last_turn())
+    }
+
+    /// Синхронная версия [`DialogueManager::recall_bookmarks`]
+    pub fn recall_bookmarks_blocking(&mut self, query: &str, top_k: usize) -> Result<Vec<(f32, bookmarks::Bookmark)>> {
+        crate::utils::block_on(self.recall_bookmarks(query, top_k))
+    }
+
+    /// Подменяет часы (тесты и сценарии поведения)
+    pub fn set_clock(&mut self, clock: SharedClock) {
+        self.clock = clock;
+        self.restamp_empty_session();
+        self.apply_ranking();
+    }
+
+    pub fn clock(&self) -> &SharedClock {
+        &self.clock
+    }
+
+    /// Пустая сессия начинается по часам менеджера
+    fn restamp_empty_session(&mut self) {
+        if self.current_session.turns.is_empty() {
+            let now = self.clock.now();
+            self.current_session.created_at = now;
+            self.current_session.updated_at = now;
+        }
+    }
+
+    /// Пользователь, с которым идёт разговор; текущая и новые сессии помечаются его ID
+    pub fn set_user(&mut self, user: UserId) {
+        self.user = user;
+        self.stamp_user();
+    }
+
+    /// Пользователь, чья это память
+    pub fn user(&self) -> &UserId {
+        &self.user
+    }
+
+    /// Пользователь по умолчанию не помечается: так выглядят сессии до `--user`
+    fn stamp_user(&mut self) {
+        if self.user.is_default() {
+            self.current_session.metadata.remove(USER_KEY);
+        } else {
+            let user = self.user.to_string();
+            self.current_session.metadata.insert(USER_KEY.to_string(), user);
+        }
+    }
+
+    /// Режим согласия на запоминание
+    pub fn set_consent_mode(&mut self, mode: ConsentMode) {
+        self.consent = mode;
+    }
+
+    /// Какие реплики считаются подтверждениями и не индексируются
+    pub fn set_significance_filter(&mut self, filter: SignificanceFilter) {
+        self.significance = filter;
+    }
+
+    /// Добавляет ход текущей сессии в векторный индекс; `query_embedding` - уже посчитанный
+    /// вектор вопроса
+    #[tracing::instrument(name = "episodic_index", skip(self, query_embedding))]
+    async fn index_turn(&mut self, turn_id: usize, query_embedding: Option<Vec<f32>>) -> Result<()> {
+        let turn = &self.current_session.turns[turn_id];
+        let (user, assistant) = (turn.user.clone(), turn.assistant.clone());
+        let style_excerpt = style::style_excerpt(&assistant);
+        let code = code::code_for_embedding(&user, &assistant);
+        let timestamp = turn.timestamp;
+
+        // код из вопроса не размывает вектор прозы: он уходит в индекс кода
+        let query_for_embedding = query_text(&user);
+        // вопрос и фрагмент ответа для памяти стиля - одним батчем
+        let texts: Vec<String> = std::iter::once(query_for_embedding)
+            .filter(|_| query_embedding.is_none())
+            .chain(style_excerpt.clone())
+            .collect();
+        let mut embeddings = if texts.is_empty() {
+            Vec::new().into_iter()
+        } else {
+            self.embedder.embed_batch_async(texts).await?.into_iter()
+        };
+        let embedding = match query_embedding {
+            Some(embedding) => embedding,
+            None => embeddings.next().context("Embedder returned no vector for the query")?,
+        };
+        let style_embedding = embeddings.next();
+        let code_entry = match code {
+            Some(code) => {
+                let embedding = self.code_embedder().embed_async(&code).await?;
+                let session = &self.current_session;
+                let mut entry =
+                    code::code_entry(session.id, turn_id, &session.persona_name, &user, &assistant, code, embedding);
+                entry.timestamp = timestamp;
+                Some(entry)
+            }
+            None => None,
+        };
+
+        self.vector_store.add(turn_entry(&self.current_session, turn_id, embedding))?;
+        if let Some(entry) = code_entry {
+            self.code_store.add(entry)?;
+        }
+
+        if let Some((excerpt, embedding)) = style_excerpt.zip(style_embedding) {
+            let session = &self.current_session;
+            self.style_store
+                .add(style::style_entry(session.id, turn_id, &session.persona_name, excerpt, embedding))?;
+        }
+        Ok(())
+    }
+
+    /// Включает автоматическую смену сессии (`None` или `enabled: false` - выключает).
+    /// `summarizer` разбирает закрытую сессию для ленты (см. [`DialogueManager::segment_summary_job`]);
+    /// без него она закрывается без разбора
+    pub fn set_segmentation(
+        &mut self,
+        config: Option<segmentation::SegmentationConfig>,
+        summarizer: Option<Arc<dyn LlmPipeline>>,
+    ) {
+        self.segmentation = config.filter(|c| c.enabled);
+        self.segment_summarizer = summarizer;
+    }
+
+    /// Автоматические смены сессии с прошлого вызова
+    pub fn take_session_breaks(&mut self) -> Vec<segmentation::SessionBreak> {
+        std::mem::take(&mut self.session_breaks)
+    }
+
+    /// Разбор закрытой автоматически сессии для ленты - задача для очереди фоновых задач
+    /// ([`crate::totems::jobs`]), чтобы ход не ждал LLM. `None`, если суммаризатора нет или
+    /// сессия короче `min_turns`. Готовый разбор попадает в сессию на следующем ходу
+    /// или при [`DialogueManager::apply_segment_summaries`]
+    pub fn segment_summary_job(
+        &self,
+        session_break: &segmentation::SessionBreak,
+    ) -> Option<impl FnOnce() -> Result<()> + Send + 'static> {
+        let summarizer = self.segment_summarizer.clone()?;
+        let min_turns = self.segmentation.as_ref()?.min_turns;
+        let session = self
+            .session_history
+            .get(&session_break.closed)
+            .filter(|s| s.durable_turns().count() >= min_turns)?;
+        let (id, turns) = (session.id, session.last_durable_turns(segmentation::SUMMARY_TURNS));
+        let summaries = self.segment_summaries.clone();
+        Some(move || {
+            let analysis = analyze_turns(&turns, summarizer.as_ref(), None)?;
+            summaries.lock().push((id, analysis));
+            Ok(())
+        })
+    }
+
+    /// Переносит готовые разборы закрытых сессий в их метаданные; возвращает их число
+    pub fn apply_segment_summaries(&mut self) -> usize {
+        let ready = std::mem::take(&mut *self.segment_summaries.lock());
+        let mut applied = 0;
+        for (id, analysis) in ready {
+            let session = if self.current_session.id == id {
+                Some(&mut self.current_session)
+            } else {
+                self.session_history.get_mut(&id)
+            };
+            if let Some(session) = session {
+                timeline::store_analysis(&mut session.metadata, &analysis);
+                applied += 1;
+            }
+        }
+        applied
+    }
+
+    /// Закрывает текущую сессию перед новым ходом, если разговор простоял или ушёл на другую
+    /// тему. Возвращает вектор вопроса, если он понадобился для сравнения: индексация его не пересчитывает
+    async fn segment_if_needed(&mut self, user: &str, indexable: bool) -> Result<Option<Vec<f32>>> {
+        let Some(config) = self.segmentation.clone() else {
+            return Ok(None);
+        };
+        let session = &self.current_session;
+        if session.turns.is_empty() {
+            return Ok(None);
+        }
+        // продолженная сессия простаивает с момента продолжения, а не с последнего хода
+        let resumed_at = session
+            .metadata
+            .get(RESUMED_AT_KEY)
+            .and_then(|at| DateTime::parse_from_rfc3339(at).ok())
+            .map(|at| at.with_timezone(&Utc));
+        let last_activity = resumed_at.map_or(session.updated_at, |at| at.max(session.updated_at));
+        if let Some(reason) = config.idle_reason(last_activity, self.clock.now()) {
+            self.close_segment(reason);
+            return Ok(None);
+        }
+
+        // сравнивается с последним проиндексированным ходом: подтверждения и повторы темы не задают
+        if !indexable || session.turn_count() < config.min_turns {
+            return Ok(None);
+        }
+        let Some(previous) = self.vector_store.get_session(&session.id).last().map(|e| e.embedding.clone()) else {
+            return Ok(None);
+        };
+        let embedding = self.embedder.embed_async(&query_text(user)).await?;
+        if let Some(reason) = config.drift_reason(&previous, &embedding, self.current_session.turn_count()) {
+            self.close_segment(reason);
+        }
+        Ok(Some(embedding))
+    }
+
+    /// Помечает причину в текущей сессии и открывает новую; разбор закрытой - [`DialogueManager::segment_summary_job`]
+    fn close_segment(&mut self, reason: segmentation::SegmentReason) {
+        let closed = self.current_session.id;
+        self.current_session
+            .metadata
+            .insert(segmentation::CLOSED_BY_KEY.to_string(), reason.to_string());
+        let opened = self.start_new_session(self.current_session.persona_name.clone());
+        self.recall_cache.invalidate();
+        self.session_breaks.push(segmentation::SessionBreak { closed, opened, reason });
+    }
+
+    /// Эмбеддер для кода: отдельная модель, если подключена
+    fn code_embedder(&self) -> &Arc<dyn Embedder> {
+        self.code_embedder.as_ref().unwrap_or(&self.embedder)
+    }
+
+    /// Подключает модель для кода (`None` - основной эмбеддер). Если индекс кода построен
+    /// другой моделью, он строится заново. Возвращает число проиндексированных ходов
+    pub async fn set_code_embedder(&mut self, code: Option<(String, Arc<dyn Embedder>)>) -> Result<usize> {
+        let (model, embedder) = code.unzip();
+        self.code_embedder = embedder;
+        if model != self.code_model {
+            self.code_model = model;
+            self.code_store = VectorStore::new(self.code_embedder().embedding_dim());
+            self.code_store.set_ranking_boost(self.retrieval.ranking(), self.clock.clone());
+        }
+        self.index_missing_code(None).await
+    }
+
+    /// Индексирует код ходов, у которых его ещё нет в индексе кода (данные до появления
+    /// индекса, смена модели). `only` ограничивает сессии
+    pub(crate) async fn index_missing_code(&mut self, only: Option<&HashSet<Uuid>>) -> Result<usize> {
+        let embedder = self.code_embedder().clone();
+        let sessions = self
+            .session_history
+            .values()
+            .chain(std::iter::once(&self.current_session))
+            .filter(|s| only.is_none_or(|ids| ids.contains(&s.id)));
+
+        let mut entries = Vec::new();
+        for session in sessions {
+            let indexed: HashSet<usize> = self
+                .code_store
+                .get_session(&session.id)
+                .iter()
+                .filter_map(|e| match e.memory_type {
+                    MemoryType::Episodic { turn, .. } => Some(turn),
+                    _ => None,
+                })
+                .collect();
+            for (turn_id, turn) in session.turns.iter().enumerate() {
+                if is_unindexed(turn) || indexed.contains(&turn_id) {
+                    continue;
+                }
+                let Some(code) = code::code_for_embedding(&turn.user, &turn.assistant) else {
+                    continue;
+                };
+                let embedding = embedder.embed_async(&code).await?;
+                let mut entry = code::code_entry(
+                    session.id,
+                    turn_id,
+                    &session.persona_name,
+                    &turn.user,
+                    &turn.assistant,
+                    code,
+                    embedding,
+                );
+                entry.timestamp = turn.timestamp;
+                entries.push(entry);
+            }
+        }
+
+        let count = entries.len();
+        self.code_store.add_batch(entries)?;
+        Ok(count)
+    }
+
+    /// Добавляет сессии из архива памяти (`import`); уже известные пропускаются. Векторы ходов
+    /// берутся из архива, если `reuse_embeddings` и размерность совпадает, иначе считаются
+    /// текущим эмбеддером; векторы стиля и кода считаются всегда. Возвращает число добавленных
+    /// сессий и пересчитанных векторов ходов
+    pub async fn import_sessions(
+        &mut self,
+        sessions: Vec<persistence::SerializedSession>,
+        reuse_embeddings: bool,
+    ) -> Result<(usize, usize)> {
+        let dimension = self.embedder.embedding_dim();
+        let mut imported = HashSet::new();
+        let mut embedded = 0;
+        for serialized in sessions {
+            let id = Uuid::parse_str(&serialized.id)
+                .with_context(|| format!("Invalid session UUID: {}", serialized.id))?;
+            if id == self.current_session.id || self.session_history.contains_key(&id) || self.deferred.contains_key(&id) {
+                continue;
+            }
+            let mut stored: Vec<Option<Vec<f32>>> = serialized
+                .turns
+                .iter()
+                .map(|t| t.embedding.clone().filter(|e| reuse_embeddings && e.len() == dimension))
+                .collect();
+            let session = persistence::deserialize_session_simple(serialized)?;
+            let indexed: Vec<usize> = (0..session.turns.len()).filter(|&i| !is_unindexed(&session.turns[i])).collect();
+
+            let missing: Vec<usize> = indexed.iter().copied().filter(|&i| stored[i].is_none()).collect();
+            if !missing.is_empty() {
+                let texts = missing.iter().map(|&i| query_text(&session.turns[i].user)).collect();
+                let fresh = self.embedder.embed_batch_async(texts).await?;
+                embedded += fresh.len();
+                for (i, embedding) in missing.into_iter().zip(fresh) {
+                    stored[i] = Some(embedding);
+                }
+            }
+            let entries = indexed
+                .iter()
+                .filter_map(|&i| Some(turn_entry(&session, i, stored[i].take()?)))
+                .collect();
+            self.vector_store.add_batch(entries)?;
+
+            let excerpts: Vec<(usize, String)> = indexed
+                .iter()
+                .filter_map(|&i| style::style_excerpt(&session.turns[i].assistant).map(|e| (i, e)))
+                .collect();
+            if !excerpts.is_empty() {
+                let texts = excerpts.iter().map(|(_, e)| e.clone()).collect();
+                let vectors = self.embedder.embed_batch_async(texts).await?;
+                let entries = excerpts
+                    .into_iter()
+                    .zip(vectors)
+                    .map(|((i, excerpt), embedding)| {
+                        let mut entry = style::style_entry(session.id, i, &session.persona_name, excerpt, embedding);
+                        entry.timestamp = session.turns[i].timestamp;
+                        entry
+                    })
+                    .collect();
+                self.style_store.add_batch(entries)?;
+            }
+
+            imported.insert(id);
+            self.session_history.insert(id, session);
+        }
+        self.index_missing_code(Some(&imported)).await?;
+        Ok((imported.len(), embedded))
+    }
+
+    /// Очищает старые сессии если превышен лимит
+    fn cleanup_if_needed(&mut self) {
+        let total = self.session_history.len() + 1; // +1 для текущей сессии
+        if total > self.max_sessions {
+            let to_remove = total - self.max_sessions;
+            let mut session_ids: Vec<Uuid> = self.session_history.keys().copied().collect();
+            session_ids.sort_by_key(|id| {
+                self.session_history.get(id)
+                    .map(|s| s.updated_at)
+                    .unwrap_or_else(Utc::now)
+            });
+
+            for id in session_ids.into_iter().take(to_remove) {
+                self.evict_session(&id);
+            }
+        }
+    }
+
+    /// Убирает сессию из истории и векторной памяти, сохраняя её в архив (если он есть)
+    fn evict_session(&mut self, id: &Uuid) {
+        let Some(session) = self.session_history.remove(id) else {
+            return;
+        };
+        self.vector_store.clear_session(id);
+        self.style_store.clear_session(id);
+        self.code_store.clear_session(id);
+
+        if let Some(ref archive) = self.archive {
+            if let Err(e) = archive.lock().add(&persistence::serialize_session(&session)) {
+                eprintln!("WARNING: Failed to archive session {}: {}", id, e);
+            }
+        }
+    }
+
+    /// Убирает из истории сессии без ходов позже `cutoff` (в архив, если он есть).
+    /// Текущая сессия и сессии, оставленные на диске частичной загрузкой, не трогаются
+    pub fn evict_older_than(&mut self, cutoff: DateTime<Utc>) -> usize {
+        let old: Vec<Uuid> = self
+            .session_history
+            .iter()
+            .filter(|(id, s)| **id != self.current_session.id && s.updated_at < cutoff)
+            .map(|(id, _)| *id)
+            .collect();
+        for id in &old {
+            self.evict_session(id);
+        }
+        old.len()
+    }
+
+    /// Подключает архив для вытесняемых сессий
+    pub fn set_archive(&mut self, archive: archive::SessionArchive) {
+        self.archive = Some(Arc::new(parking_lot::Mutex::new(archive)));
+    }
+
+    pub fn archive(&self) -> Option<&Arc<parking_lot::Mutex<archive::SessionArchive>>> {
+        self.archive.as_ref()
+    }
+
+    /// Сессии из истории, где встречаются слова запроса (без учёта регистра)
+    pub fn search_sessions(&self, query: &str, limit: usize) -> Vec<&Session> {
+        let needle = query.to_lowercase();
+        let mut found: Vec<&Session> = self
+            .session_history
+            .values()
+            .chain(std::iter::once(&self.current_session))
+            .filter(|s| {
+                s.turns.iter().any(|t| {
+                    t.user.to_lowercase().contains(&needle) || t.assistant.to_lowercase().contains(&needle)
+                })
+            })
+            .collect();
+        found.sort_by_key(|s| std::cmp::Reverse(s.updated_at));
+        found.truncate(limit);
+        found
+    }
+
+    /// Сессии для `/memory list`, новые первыми
+    pub fn list_sessions(&self, limit: usize) -> Vec<SessionOverview> {
+        let mut sessions: Vec<SessionOverview> = self
+            .session_history
+            .values()
+            .chain(std::iter::once(&self.current_session))
+            .filter(|s| s.id != self.current_session.id || s.turn_count() > 0)
+            .map(|s| SessionOverview {
+                id: s.id,
+                persona_name: s.persona_name.clone(),
+                updated_at: s.updated_at,
+                turns: s.turn_count(),
+                indexed: self.vector_store.get_session(&s.id).len(),
+                first_question: s.turns.first().map(|t| t.user.clone()).unwrap_or_default(),
+                current: s.id == self.current_session.id,
+            })
+            .collect();
+        sessions.sort_by_key(|s| std::cmp::Reverse(s.updated_at));
+        sessions.truncate(limit);
+        sessions
+    }
+
+    /// Текущая сессия или сессия из истории
+    pub fn session(&self, id: &Uuid) -> Option<&Session> {
+        if self.current_session.id == *id {
+            Some(&self.current_session)
+        } else {
+            self.session_history.get(id)
+        }
+    }
+
+    /// Ходы, ближайшие к запросу, для `/memory search`. В отличие от
+    /// [`DialogueManager::find_similar_dialogues`] ничего не отсекает и не
+    /// засчитывает обращения: это просмотр памяти, а не воспоминание
+    pub async fn search_memory(&mut self, query: &str, top_k: usize) -> Result<Vec<TurnHit>> {
+        let query_embedding = self.embedder.embed_async(query).await?;
+        let memory_type = MemoryType::Episodic {
+            session_id: Uuid::nil(),
+            turn: 0,
+        };
+        let entries: Vec<MemoryEntry> = self
+            .vector_store
+            .search_by_type(&query_embedding, &memory_type, top_k)
+            .into_iter()
+            .map(|(_, entry)| entry.clone())
+            .collect();
+        Ok(entries
+            .into_iter()
+            .filter_map(|entry| {
+                let MemoryType::Episodic { session_id, turn } = entry.memory_type else {
+                    return None;
+                };
+                let stored = self.session(&session_id).and_then(|s| s.turns.get(turn));
+                let field = |key: &str| entry.metadata.get(key).cloned().unwrap_or_default();
+                Some(TurnHit {
+                    similarity: cosine_similarity(&query_embedding, &entry.embedding),
+                    session_id,
+                    turn,
+                    user: stored.map_or_else(|| field("user_query"), |t| t.user.clone()),
+                    assistant: stored.map_or_else(|| field("assistant_response"), |t| t.assistant.clone()),
+                    timestamp: entry.timestamp,
+                    recall_count: entry.access_count,
+                })
+            })
+            .collect())
+    }
+
+    /// Сходство запроса с ближайшим ходом памяти: один векторный поиск без
+    /// ранжирования и форматирования, чтобы решить, нужна ли память вообще.
+    /// Пустая память даёт 0
+    #[tracing::instrument(name = "memory_gate", skip_all)]
+    pub async fn recall_score(&mut self, query: &str) -> Result<f32> {
+        let query_embedding = self.embedder.embed_async(query).await?;
+        let memory_type = MemoryType::Episodic {
+            session_id: Uuid::nil(),
+            turn: 0,
+        };
+        let best = self.vector_store.search_by_type(&query_embedding, &memory_type, 1);
+        // порог - по чистому сходству, без прибавки за свежесть и частоту
+        Ok(best.first().map_or(0.0, |(_, entry)| cosine_similarity(&query_embedding, &entry.embedding)))
+    }
+
+    /// Ищет похожие диалоги по запросу
+    #[tracing::instrument(name = "episodic_recall", skip(self, query), fields(found))]
+    pub async fn find_similar_dialogues(
+        &mut self,
+        query: &str,
+        top_k: usize,
+    ) -> Result<Vec<String>> {
+        let query_embedding = self.embedder.embed_async(query).await?;
+        let mut candidates = self.recall_candidates(&query_embedding, query, top_k);
+
+        // Ходы с кодом ищутся ещё и по индексу кода; запрос к нему - вектором модели кода
+        let code_query = match (&self.code_embedder, self.code_store.is_empty()) {
+            (_, true) => None,
+            (Some(embedder), false) => Some(embedder.embed_async(query).await?),
+            (None, false) => Some(query_embedding.clone()),
+        };
+        if let Some(code_query) = &code_query {
+            candidates.extend(self.code_store.search(code_query, top_k).into_iter().map(|(_, e)| e.clone()));
+        }
+
+        // Кандидаты оцениваются под этот запрос, даже если найдены для прошлого;
+        // воспоминания на языке запроса чуть выше, остальные не отсекаются
+        let keywords = query_keywords(query);
+        let query_language = Language::detect(query);
+        let now = self.clock.now();
+        let mut all_entries: Vec<(f32, crate::totems::retrieval::MemoryEntry)> = candidates
+            .into_iter()
+            .map(|e| {
+                let similarity = match &code_query {
+                    Some(code_query) if code::is_code_entry(&e) => cosine_similarity(code_query, &e.embedding),
+                    _ => cosine_similarity(&query_embedding, &e.embedding),
+                };
+                let score = self.retrieval.score(similarity, keyword_score(&keywords, &e), &e, now);
+                (score + language_boost(query_language, &e), e)
+            })
+            .collect();
+
+        all_entries.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(std::cmp::Ordering::Equal));
+        all_entries.truncate(top_k);
+
+        let mut dialogues = Vec::new();
+        let mut seen = std::collections::HashSet::new();
+        let mut recalled = Vec::new();
+
+        for (similarity, entry) in all_entries {
+            if let Some(key) = self.retrieval.dedup_key(&entry) {
+                if !seen.insert(key) {
+                    continue;
+                }
+            }
+
+            if similarity < self.retrieval.min_similarity {
+                continue;
+            }
+
+            let user_query = entry
+                .metadata
+                .get("user_query")
+                .cloned()
+                .unwrap_or_else(|| entry.text.clone());
+
+            // Skip test/placeholder entries
+            if user_query.contains("# Test") || user_query.contains("TEST") || user_query.is_empty() {
+                continue;
+            }
+
+            let assistant_response = entry
+                .metadata
+                .get("assistant_response")
+                .cloned()
+                .unwrap_or_default();
+
+            let context = format!("FROM PAST: User said \"{}\"", user_query);
+
+            let truncated = if code::is_code_entry(&entry) {
+                code::code_context(&user_query, &entry.text)
+            } else if context.chars().count() > 200 {
+                if let Some((byte_pos, _)) = context.char_indices().nth(200) {
+                    let trunc = &context[..byte_pos];
+                    if let Some(newline_pos) = trunc.rfind('"') {
+                        &context[..=newline_pos]
+                    } else if let Some(space_pos) = trunc.rfind(' ') {
+                        &context[..space_pos]
+                    } else {
+                        trunc
+                    }
+                } else {
+                    &context
+                }
+                .to_string()
+                    + "\"..."
+            } else {
+                context
+            };
+
+            let score_pct = (similarity * 100.0) as u32;
+            let when = humanize(entry.timestamp, self.clock.now(), self.utc_offset);
+            let formatted = format!("[Relevance: {}%, {}] {}", score_pct, when, truncated);
+            dialogues.push(formatted);
+            recalled.push((entry.memory_type, entry.id));
+        }
+        for (memory_type, id) in recalled {
+            self.record_recall(&memory_type, id, now);
+        }
+
+        tracing::Span::current().record("found", dialogues.len());
+        metrics::record_lookup("episodic", !dialogues.is_empty());
+        Ok(dialogues)
+    }
+
+    /// Отмечает, что запись попала в промпт: в индексе для ранжирования и в
+    /// метаданных хода, чтобы счётчики пережили перезапуск
+    fn record_recall(&mut self, memory_type: &MemoryType, id: Uuid, at: DateTime<Utc>) {
+        if !self.vector_store.record_access(memory_type, id, at) && !self.code_store.record_access(memory_type, id, at) {
+            return;
+        }
+        let MemoryType::Episodic { session_id, turn } = *memory_type else {
+            return;
+        };
+        let session = if self.current_session.id == session_id {
+            Some(&mut self.current_session)
+        } else {
+            self.session_history.get_mut(&session_id)
+        };
+        if let Some(turn) = session.and_then(|s| s.turns.get_mut(turn)) {
+            let count = turn.metadata.get(RECALL_COUNT_KEY).and_then(|c| c.parse::<u32>().ok()).unwrap_or(0);
+            turn.metadata.insert(RECALL_COUNT_KEY.to_string(), count.saturating_add(1).to_string());
+            turn.metadata.insert(RECALLED_AT_KEY.to_string(), at.to_rfc3339());
+        }
+    }
+
+    /// Кандидаты для поиска воспоминаний: из кэша, если запрос продолжает тему прошлого
+    /// (плюс ходы, добавленные с тех пор), иначе векторный и keyword-поиск по всей памяти
+    fn recall_candidates(&mut self, query_embedding: &[f32], query: &str, top_k: usize) -> Vec<MemoryEntry> {
+        let session_id = self.current_session.id;
+        let session_len = self.vector_store.get_session(&session_id).len();
+        let store_len = self.vector_store.len();
+
+        if let Some(cached) = self.recall_cache.reuse(query_embedding, session_id, session_len, store_len) {
+            let top_up = self.vector_store.get_session(&session_id)[cached.top_up_from..].to_vec();
+            self.recall_cache.extend(&top_up, session_len, store_len);
+            return cached.candidates.into_iter().chain(top_up).collect();
+        }
+
+        let memory_type = MemoryType::Episodic {
+            session_id: Uuid::nil(),
+            turn: 0,
+        };
+        let mut candidates: Vec<MemoryEntry> = self
+            .vector_store
+            .search_by_type(query_embedding, &memory_type, top_k * 3)
+            .into_iter()
+            .map(|(_, e)| e.clone())
+            .collect();
+        let mut seen: std::collections::HashSet<Uuid> = candidates.iter().map(|e| e.id).collect();
+        let keyword_matches: Vec<MemoryEntry> = self
+            .keyword_search(query, top_k)
+            .into_iter()
+            .filter(|(_, e)| seen.insert(e.id))
+            .map(|(_, e)| e.clone())
+            .collect();
+        candidates.extend(keyword_matches);
+        self.recall_cache
+            .store(query_embedding.to_vec(), candidates.clone(), session_id, session_len, store_len);
+        candidates
+    }
+
+    /// Сбрасывает кэш воспоминаний; `threshold >= 1.0` отключает его
+    pub fn set_recall_cache_threshold(&mut self, threshold: f32) {
+        self.recall_cache.set_threshold(threshold);
+    }
+
+    /// Веса и пороги поиска воспоминаний: общие из `--system-config` или с поправками персоны
+    pub fn set_retrieval_config(&mut self, config: RetrievalConfig) {
+        self.retrieval = config;
+        self.apply_ranking();
+    }
+
+    /// Прибавка за свежесть и частоту из настроек поиска - в векторный поиск
+    fn apply_ranking(&mut self) {
+        let ranking = self.retrieval.ranking();
+        self.vector_store.set_ranking_boost(ranking, self.clock.clone());
+        self.code_store.set_ranking_boost(ranking, self.clock.clone());
+    }
+
+    pub fn retrieval_config(&self) -> &RetrievalConfig {
+        &self.retrieval
+    }
+
+    fn keyword_search(
+        &self,
+        query: &str,
+        top_k: usize,
+    ) -> Vec<(f32, &crate::totems::retrieval::MemoryEntry)> {
+        let keywords = query_keywords(query);
+
+        if keywords.is_empty() {
+            return Vec::new();
+        }
+
+        let mut matches: Vec<(f32, &crate::totems::retrieval::MemoryEntry)> = self
+            .vector_store
+            .entries()
+            .filter_map(|entry| keyword_score(&keywords, entry).map(|score| (score, entry)))
+            .collect();
+
+        matches.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(std::cmp::Ordering::Equal));
+        matches.truncate(top_k);
+        matches
+    }
+
+    /// Ищет диалоги с конкретной сессии
+    pub fn find_session_dialogues(&self, session_id: &Uuid, top_k: usize) -> Vec<String> {
+        let entries = self.vector_store.get_session(session_id);
+        let mut dialogues = Vec::new();
+
+        for entry in entries.iter().take(top_k) {
+            dialogues.push(format!(
+                "Turn {}: {}",
+                entry.metadata.get("turn").unwrap_or(&"?".to_string()),
+                entry.text
+            ));
+        }
+
+        dialogues
+    }
+
+    /// Получает контекст текущей сессии
+    pub fn get_current_context(&self, max_turns: usize) -> String {
+        self.current_session.format_context(max_turns, 512)
+    }
+
+    /// Ставит оценку последнему ходу текущей сессии (`/good`, `/bad`)
+    pub fn rate_last_turn(&mut self, rating: export::Rating) -> bool {
+        match self.current_session.turns.last_mut() {
+            Some(turn) => {
+                turn.metadata
+                    .insert(export::RATING_KEY.to_string(), rating.as_str().to_string());
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Ставит закладку на последний ход текущей сессии (`/bookmark`).
+    /// Номер хода или `None`, если ходов ещё нет
+    pub fn bookmark_last_turn(&mut self, note: &str) -> Option<usize> {
+        let turn = self.current_session.turn_count().checked_sub(1)?;
+        self.current_session
+            .metadata
+            .insert(bookmarks::bookmark_key(turn), note.trim().to_string());
+        Some(turn)
+    }
+
+    /// Закладки текущей сессии и загруженной истории, от старых к новым
+    pub fn bookmarks(&self) -> Vec<bookmarks::Bookmark> {
+        let mut all: Vec<bookmarks::Bookmark> = std::iter::once(&self.current_session)
+            .chain(self.session_history.values().filter(|s| s.id != self.current_session.id))
+            .flat_map(bookmarks::session_bookmarks)
+            .collect();
+        all.sort_by_key(|b| b.timestamp);
+        all
+    }
+
+    /// Закладки, похожие на запрос: поиск по индексу эпизодов только среди отмеченных
+    /// ходов и по заметкам. Сходство - лучшее из двух
+    pub async fn recall_bookmarks(&mut self, query: &str, top_k: usize) -> Result<Vec<(f32, bookmarks::Bookmark)>> {
+        let marks = self.bookmarks();
+        if marks.is_empty() || top_k == 0 {
+            return Ok(Vec::new());
+        }
+        let query_embedding = self.embedder.embed_async(query).await?;
+        let marked: HashSet<(Uuid, usize)> = marks.iter().map(|b| (b.session_id, b.turn)).collect();
+
+        let mut scores: HashMap<(Uuid, usize), f32> = self
+            .vector_store
+            .search_filtered(
+                &query_embedding,
+                |entry| {
+                    matches!(entry.memory_type, MemoryType::Episodic { session_id, turn }
+                        if marked.contains(&(session_id, turn)))
+                },
+                marks.len(),
+            )
+            .into_iter()
+            .filter_map(|(similarity, entry)| match entry.memory_type {
+                MemoryType::Episodic { session_id, turn } => Some(((session_id, turn), similarity)),
+                _ => None,
+            })
+            .collect();
+        for mark in marks.iter().filter(|b| !b.note.is_empty()) {
+            let note_similarity = cosine_similarity(&query_embedding, &self.embedder.embed_async(&mark.note).await?);
+            let score = scores.entry((mark.session_id, mark.turn)).or_insert(note_similarity);
+            *score = score.max(note_similarity);
+        }
+
+        let mut ranked: Vec<(f32, bookmarks::Bookmark)> = marks
+            .into_iter()
+            .filter_map(|b| scores.get(&(b.session_id, b.turn)).map(|score| (*score, b)))
+            .collect();
+        ranked.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(std::cmp::Ordering::Equal));
+        ranked.truncate(top_k);
+        Ok(ranked)
+    }
+
+    /// Начинает новую сессию
+    /// Как персона уже отвечала на похожие темы в прошлых сессиях (для STYLE MEMORY).
+    /// Текущая сессия не ищется - она и так видна в контексте
+    pub async fn find_own_phrasings(&mut self, query: &str, top_k: usize) -> Result<Vec<style::Phrasing>> {
+        if top_k == 0 || self.style_store.is_empty() {
+            return Ok(Vec::new());
+        }
+        let query_embedding = self.embedder.embed_async(query).await?;
+        let current = self.current_session.id;
+        let limit = top_k * 3 + self.current_session.turn_count();
+
+        let mut phrasings: Vec<style::Phrasing> = Vec::new();
+        for (similarity, entry) in self.style_store.search(&query_embedding, limit) {
+            let in_current = matches!(entry.memory_type, MemoryType::Episodic { session_id, .. } if session_id == current);
+            if in_current || similarity < style::STYLE_MIN_SIMILARITY {
+                continue;
+            }
+            if phrasings.iter().any(|p| is_near_duplicate(&p.excerpt, &entry.text)) {
+                continue;
+            }
+            phrasings.push(style::Phrasing {
+                similarity,
+                excerpt: entry.text.clone(),
+                timestamp: entry.timestamp,
+            });
+            if phrasings.len() == top_k {
+                break;
+            }
+        }
+        Ok(phrasings)
+    }
+
+    pub fn start_new_session(&mut self, persona_name: String) -> Uuid {
+        // Сохраняем текущую сессию в историю
+        let old_session_id = self.current_session.id;
+        self.session_history
+            .insert(old_session_id, self.current_session.clone());
+
+        // Очищаем старую сессию из векторной памяти (опционально)
+        let cutoff = self.clock.now() - chrono::Duration::days(7); // Удаляем сессии старше недели
+        self.vector_store.cleanup_old(cutoff);
+
+        // Ограничиваем количество сессий
+        if self.session_history.len() > self.max_sessions {
+            let oldest_sessions = self
+                .session_history
+                .iter()
+                .min_by_key(|(_, s)| s.created_at)
+                .map(|(id, _)| *id);
+
+            if let Some(oldest_id) = oldest_sessions {
+                self.evict_session(&oldest_id);
+            }
+        }
+
+        // Создаем новую сессию
+        self.current_session = Session::new(persona_name);
+        self.restamp_empty_session();
+        self.stamp_user();
+        self.current_session.id
+    }
+
+    /// Возвращает текущую сессию
+    pub fn current_session(&self) -> &Session {
+        &self.current_session
+    }
+
+    /// Возвращает историю сессий
+    pub fn session_history(&self) -> &HashMap<Uuid, Session> {
+        &self.session_history
+    }
+
+    /// Сессии, которые есть на диске, но ещё не загружены
+    pub fn deferred_sessions(&self) -> &HashMap<Uuid, DeferredSession> {
+        &self.deferred
+    }
+
+    /// Возвращает статистику
+    pub fn stats(&self) -> DialogueManagerStats {
+        let store_stats = self.vector_store.stats();
+
+        DialogueManagerStats {
+            current_session_id: self.current_session.id,
+            current_session_turns: self.current_session.turn_count(),
+            total_sessions: self.session_history.len() + 1, // +1 for current
+            total_turns: store_stats.episodic_count,
+            last_activity: self.current_session.updated_at,
+            recall_cache: self.recall_cache.stats(),
+            deferred_sessions: self.deferred.len(),
+            code_entries: self.code_store.len(),
+            style_entries: self.style_store.len(),
+            recalled_turns: self.vector_store.entries().filter(|e| e.access_count > 0).count(),
+        }
+    }
+
+    /// Загружает сессию из истории
+    pub fn load_session(&mut self, session_id: Uuid) -> Result<bool> {
+        if let Some(session) = self.session_history.get(&session_id).cloned() {
+            // Сохраняем текущую сессию
+            let current_id = self.current_session.id;
+            self.session_history
+                .insert(current_id, self.current_session.clone());
+
+            // Загружаем запрошенную сессию
+            self.current_session = session;
+            Ok(true)
+        } else {
+            Ok(false)
+        }
+    }
+
+    /// Продолжает сессию из истории как текущую (после рестарта процесса).
+    /// Пустая текущая сессия в историю не попадает
+    pub fn resume_session(&mut self, session_id: Uuid) -> bool {
+        if self.current_session.id == session_id {
+            return true;
+        }
+        let Some(mut session) = self.session_history.remove(&session_id) else {
+            return false;
+        };
+        if self.current_session.turn_count() > 0 {
+            self.session_history
+                .insert(self.current_session.id, self.current_session.clone());
+        }
+        session
+            .metadata
+            .insert(RESUMED_AT_KEY.to_string(), self.clock.now().to_rfc3339());
+        self.current_session = session;
+        true
+    }
+
+    /// Часовой пояс пользователя: от него зависят «today» и «yesterday» у воспоминаний
+    pub fn set_utc_offset(&mut self, offset: FixedOffset) {
+        self.utc_offset = offset;
+    }
+
+    /// Текущая сессия продолжена из истории: её прошлые ходы нужны в промпте
+    pub fn was_resumed(&self) -> bool {
+        self.current_session.metadata.contains_key(RESUMED_AT_KEY)
+    }
+
+    /// Находит сессию по id или его префиксу среди текущей, истории и оставленных на диске
+    pub fn find_session_id(&self, prefix: &str) -> Result<Option<Uuid>> {
+        let prefix = prefix.trim().to_lowercase();
+        anyhow::ensure!(!prefix.is_empty(), "Empty session id");
+        let mut matches: Vec<Uuid> = std::iter::once(&self.current_session.id)
+            .chain(self.session_history.keys())
+            .chain(self.deferred.keys())
+            .filter(|id| id.to_string().starts_with(&prefix))
+            .copied()
+            .collect();
+        matches.sort();
+        matches.dedup();
+        anyhow::ensure!(
+            matches.len() <= 1,
+            "Session id '{}' is ambiguous ({} sessions match)",
+            prefix,
+            matches.len()
+        );
+        Ok(matches.pop())
+    }
+
+    /// Удаляет сессию из истории и векторной памяти. Удалённая текущая
+    /// сессия сменяется пустой той же персоны
+    pub fn delete_session(&mut self, session_id: Uuid) -> bool {
+        let existed = if self.current_session.id == session_id {
+            self.current_session = Session::new(self.current_session.persona_name.clone());
+            self.restamp_empty_session();
+            self.stamp_user();
+            true
+        } else {
+            self.session_history.remove(&session_id).is_some()
+        };
+
+        if existed {
+            self.recall_cache.invalidate();
+            // Очищаем записи из векторной памяти
+            self.vector_store.clear_session(&session_id);
+            self.style_store.clear_session(&session_id);
+            self.code_store.clear_session(&session_id);
+        }
+
+        existed
+    }
+
+    /// Запоминает разбор текущей сессии в её метаданных, для ленты (см. [`timeline`])
+    pub fn record_analysis(&mut self, analysis: &SessionAnalysis) {
+        timeline::store_analysis(&mut self.current_session.metadata, analysis);
+    }
+
+    /// Разобранные сессии по времени: текущая, история и оставленные на диске.
+    /// Сессии без разбора (короткие или закрытые без сохранения контекста) пропускаются
+    pub fn get_timeline(&self) -> Vec<timeline::TimelineEntry> {
+        let loaded = std::iter::once(&self.current_session)
+            .chain(self.session_history.values())
+            .filter_map(|s| {
+                Some(timeline::TimelineEntry {
+                    session_id: s.id,
+                    persona_name: s.persona_name.clone(),
+                    at: s.updated_at,
+                    turns: s.turns.len(),
+                    analysis: timeline::stored_analysis(&s.metadata)?,
+                })
+            });
+        let deferred = self.deferred.iter().filter_map(|(id, d)| {
+            Some(timeline::TimelineEntry {
+                session_id: *id,
+                persona_name: d.persona_name.clone(),
+                at: d.updated_at,
+                turns: d.turns,
+                analysis: d.analysis.clone()?,
+            })
+        });
+        let mut entries: Vec<timeline::TimelineEntry> = loaded.chain(deferred).collect();
+        entries.sort_by_key(|e| (e.at, e.session_id));
+        entries
+    }
+
+    /// Последние ходы для разбора сессии: ходы без согласия в разбор не попадают
+    pub fn get_turns_for_context(&self, max_turns: usize) -> Vec<Turn> {
+        self.current_session.last_durable_turns(max_turns)
+    }
+
+    pub fn analyze_for_context(
+        &self,
+        pipeline: &dyn LlmPipeline,
+        max_turns: usize,
+    ) -> Result<SessionAnalysis> {
+        self.analyze_for_context_with_summary(pipeline, max_turns, None)
+    }
+
+    /// Как [`DialogueManager::analyze_for_context`], но с готовой сводкой сессии
+    /// (см. [`summaries::SummaryStore`]) - сессия заново не суммаризируется
+    pub fn analyze_for_context_with_summary(
+        &self,
+        pipeline: &dyn LlmPipeline,
+        max_turns: usize,
+        summary: Option<String>,
+    ) -> Result<SessionAnalysis> {
+        analyze_turns(&self.get_turns_for_context(max_turns), pipeline, summary)
+    }
+}
+
+/// Разбор ходов сессии: сводка (если не дана готовая), темы, эмоциональный фон
+fn analyze_turns(turns: &[Turn], pipeline: &dyn LlmPipeline, summary: Option<String>) -> Result<SessionAnalysis> {
+    let analyzer = ContextAnalyzer::new(pipeline);
+
+    let summary = match summary {
+        Some(summary) => summary,
+        None => analyzer.summarize_session(turns)?,
+    };
+    let key_topics = analyzer.extract_topics(turns)?;
+    let emotional_state = analyzer.analyze_emotions(turns)?;
+    let last_topic = analyzer.extract_last_topic(turns)?;
+
+    Ok(SessionAnalysis {
+        summary,
+        key_topics,
+        emotional_state,
+        last_topic,
+        turn_count: turns.len(),
+    })
+}
+
+// ============ Sync facade (CLI) ============
+
+impl DialogueManager {
+    /// Синхронная версия [`DialogueManager::import_sessions`]
+    pub fn import_sessions_blocking(
+        &mut self,
+        sessions: Vec<persistence::SerializedSession>,
+        reuse_embeddings: bool,
+    ) -> Result<(usize, usize)> {
+        crate::utils::block_on(self.import_sessions(sessions, reuse_embeddings))
+    }
+
+    /// Синхронная версия [`DialogueManager::add_exchange`]
+    pub fn add_exchange_blocking(&mut self, user: String, assistant: String) -> Result<()> {
+        crate::utils::block_on(self.add_exchange(user, assistant))
+    }
+
+    /// Синхронная версия [`DialogueManager::add_exchange_with_sampling`]
+    pub fn add_exchange_with_sampling_blocking(
+        &mut self,
+        user: String,
+        assistant: String,
+        sampling: Option<SamplingRecord>,
+    ) -> Result<()> {
+        crate::utils::block_on(self.add_exchange_with_sampling(user, assistant, sampling))
+    }
+
+    /// Синхронная версия [`DialogueManager::find_similar_dialogues`]
+    pub fn find_similar_dialogues_blocking(
+        &mut self,
+        query: &str,
+        top_k: usize,
+    ) -> Result<Vec<String>> {
+        crate::utils::block_on(self.find_similar_dialogues(query, top_k))
+    }
+
+    /// Синхронная версия [`DialogueManager::search_memory`]
+    pub fn search_memory_blocking(&mut self, query: &str, top_k: usize) -> Result<Vec<TurnHit>> {
+        crate::utils::block_on(self.search_memory(query, top_k))
+    }
+
+    /// Синхронная версия [`DialogueManager::recall_score`]
+    pub fn recall_score_blocking(&mut self, query: &str) -> Result<f32> {
+        crate::utils::block_on(self.recall_score(query))
+    }
+
+    /// Синхронная версия [`DialogueManager::set_code_embedder`]
+    pub fn set_code_embedder_blocking(&mut self, code: Option<(String, Arc<dyn Embedder>)>) -> Result<usize> {
+        crate::utils::block_on(self.set_code_embedder(code))
+    }
+
+    /// Синхронная версия [`DialogueManager::find_own_phrasings`]
+    pub fn find_own_phrasings_blocking(&mut self, query: &str, top_k: usize) -> Result<Vec<style::Phrasing>> {
+        crate::utils::block_on(self.find_own_phrasings(query, top_k))
+    }
+}
+
+/// Статистика менеджера диалогов
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DialogueManagerStats {
+    pub current_session_id: Uuid,
+    pub current_session_turns: usize,
+    pub total_sessions: usize,
+    pub total_turns: usize,
+    pub last_activity: DateTime<Utc>,
+    /// (попадания, промахи) кэша воспоминаний
+    #[serde(default)]
+    pub recall_cache: (u64, u64),
+    /// Сессии, оставленные на диске частичной загрузкой
+    #[serde(default)]
+    pub deferred_sessions: usize,
+    /// Записи индекса кода
+    #[serde(default)]
+    pub code_entries: usize,
+    /// Ответы в индексе стиля
+    #[serde(default)]
+    pub style_entries: usize,
+    /// Ходы, хоть раз попавшие в промпт как воспоминание
+    #[serde(default)]
+    pub recalled_turns: usize,
+}
+
+impl DialogueManagerStats {
+    /// Форматирует статистику для вывода
+    pub fn format(&self) -> String {
+        format!(
+            "💬 Dialogue Manager Stats:\n   Current Session: {} ({} turns)\n   Total Sessions: {} (+{} on disk)\n   Total Turns: {} indexed, {} recalled at least once\n   Code / Style Entries: {} / {}\n   Last Activity: {}\n   Recall Cache: {} hits / {} misses",
+            self.current_session_id,
+            self.current_session_turns,
+            self.total_sessions,
+            self.deferred_sessions,
+            self.total_turns,
+            self.recalled_turns,
+            self.code_entries,
+            self.style_entries,
+            self.last_activity.format("%Y-%m-%d %H:%M:%S"),
+            self.recall_cache.0,
+            self.recall_cache.1
+        )
+    }
+}
+
+/// Сессия в списке `/memory list`
+#[derive(Debug, Clone)]
+pub struct SessionOverview {
+    pub id: Uuid,
+    pub persona_name: String,
+    pub updated_at: DateTime<Utc>,
+    pub turns: usize,
+    /// Ходы в векторном индексе (без рабочей памяти, повторов и подтверждений)
+    pub indexed: usize,
+    pub first_question: String,
+    pub current: bool,
+}
+
+/// Ход, найденный `/memory search`
+#[derive(Debug, Clone)]
+pub struct TurnHit {
+    /// Косинусное сходство с запросом
+    pub similarity: f32,
+    pub session_id: Uuid,
+    /// Номер хода в сессии, с нуля
+    pub turn: usize,
+    pub user: String,
+    pub assistant: String,
+    pub timestamp: DateTime<Utc>,
+    /// Сколько раз ход попадал в промпт
+    pub recall_count: u32,
+}
+
+pub trait LlmPipeline: Send + Sync {
+    fn generate(&self, prompt: &str, max_tokens: usize) -> Result<String>;
+}
+
+struct ContextAnalyzer<'a> {
+    pipeline: &'a dyn LlmPipeline,
+}
+
+impl<'a> ContextAnalyzer<'a> {
+    fn new(pipeline: &'a dyn LlmPipeline) -> Self {
+        Self { pipeline }
+    }
+
+    fn summarize_session(&self, turns: &[Turn]) -> Result<String> {
+        if turns.is_empty() {
+            return Ok(String::new());
+        }
+
+        let dialogue_text = turns
+            .iter()
+            .enumerate()
+            .map(|(i, t)| format!("Turn {}:\nUser: {}\nAssistant: {}\n", i + 1, t.user, t.assistant))
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        let prompt = format!(
+            r#"<s>[INST] Ты — ассистент по анализу диалогов. Кратко опиши, о чём был разговор (2-3 предложения на русском).
+
+Диалог:
+{dialogue_text}
+
+Краткое содержание:[/INST]"#,
+            dialogue_text = dialogue_text
+        );
+
+        let response = self.pipeline.generate(&prompt, 300)?;
+        Ok(response.trim().to_string())
+    }
+
+    fn extract_topics(&self, turns: &[Turn]) -> Result<Vec<String>> {
+        if turns.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let dialogue_text = turns
+            .iter()
+            .map(|t| format!("User: {}\nAssistant: {}", t.user, t.assistant))
+            .collect::<Vec<_>>()
+            .join("\n---\n");
+
+        let prompt = format!(
+            r#"<s>[INST] Извлеки ключевые темы из диалога. Верни только JSON массив строк, например: ["тема1", "тема2", "тема3"].
+Не более 5 тем. Темы должны быть короткими (1-2 слова), на русском языке.
+
+Диалог:
+{dialogue_text}
+
+Темы:[/INST]"#,
+            dialogue_text = dialogue_text
+        );
+
+        let response = self.pipeline.generate(&prompt, 200)?;
+        self.parse_topics(&response)
+    }
+
+    fn parse_topics(&self, response: &str) -> Result<Vec<String>> {
+        crate::utils::llm_json::parse_list(response).context("Failed to parse topics")
+    }
+
+    fn analyze_emotions(&self, turns: &[Turn]) -> Result<f32> {
+        if turns.is_empty() {
+            return Ok(0.5);
+        }
+
+        let dialogue_text = turns
+            .iter()
+            .map(|t| format!("User: {}\nAssistant: {}", t.user, t.assistant))
+            .collect::<Vec<_>>()
+            .join("\n---\n");
+
+        let prompt = format!(
+            r#"<s>[INST] Определи эмоциональное состояние пользователя по диалогу.
+ Верни только число от 0.0 (негативное/грустное) до 1.0 (позитивное/радостное).
+
+Диалог:
+{dialogue_text}
+
+Число:[/INST]"#,
+            dialogue_text = dialogue_text
+        );
+
+        let response = self.pipeline.generate(&prompt, 50)?;
+        let cleaned = response.trim();
+
+        cleaned
+            .parse::<f32>()
+            .map(|v| v.clamp(0.0, 1.0))
+            .map_err(|_| anyhow::anyhow!("Failed to parse emotional state"))
+    }
+
+    fn extract_last_topic(&self, turns: &[Turn]) -> Result<String> {
+        if let Some(last_turn) = turns.last() {
+            let prompt = format!(
+                r#"<s>[INST] Определи, о чём был последний вопрос пользователя (1-2 слова на русском).
+Вопрос: {question}
+
+Тема:[/INST]"#,
+                question = last_turn.user
+            );
+
+            let response = self.pipeline.generate(&prompt, 50)?;
+            return Ok(response.trim().to_string());
+        }
+        Ok(String::new())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SessionAnalysis {
+    pub summary: String,
+    pub key_topics: Vec<String>,
+    pub emotional_state: f32,
+    pub last_topic: String,
+    pub turn_count: usize,
+}
+
+#[cfg(all(test, feature = "runtime"))]
+mod tests {
+    use super::*;
+    use crate::testing::MockEmbedder;
+
+    #[tokio::test]
+    async fn test_dialogue_manager() -> Result<()> {
+        let embedder: Arc<dyn Embedder> = Arc::new(MockEmbedder::default());
+        let mut manager = DialogueManager::new(embedder.clone(), "test_persona".to_string());
+
+        manager
+            .add_exchange(
+                "Hello, how are you?".to_string(),
+                "I'm doing well, thank you!".to_string(),
+            )
+            .await?;
+
+        let stats = manager.stats();
+        assert_eq!(stats.current_session_turns, 1);
+
+        let context = manager.get_current_context(5);
+        assert!(context.contains("Hello, how are you?"));
+        assert!(context.contains("I'm doing well, thank you!"));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_sampling_record_on_turn() -> Result<()> {
+        let embedder: Arc<dyn Embedder> = Arc::new(MockEmbedder::default());
+        let mut manager = DialogueManager::new(embedder, "test_persona".to_string());
+
+        let record = SamplingRecord {
+            seed: 299792458,
+            temperature: 0.7,
+            top_p: Some(0.9),
+            top_k: None,
+            repeat_penalty: 1.1,
+            repeat_last_n: 64,
+            max_tokens: 256,
+            prompt_tokens: 12,
+            generated_tokens: 5,
+            prompt: "[INST] Hi [/INST]".to_string(),
+        };
+        manager
+            .add_exchange_with_sampling("Hi".to_string(), "Hello!".to_string(), Some(record.clone()))
+            .await?;
+        assert_eq!(manager.current_session().turns[0].sampling.as_ref(), Some(&record));
+
+        // turns saved before sampling was recorded still load
+        let legacy = r#"{"user":"a","assistant":"b","timestamp":"2024-01-01T00:00:00Z","metadata":{}}"#;
+        let turn: Turn = serde_json::from_str(legacy)?;
+        assert!(turn.sampling.is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_language_boost() {
+        let entry = |user: &str, lang: Option<&str>| {
+            let entry = MemoryEntry::new(
+                user.to_string(),
+                vec![0.0; 4],
+                MemoryType::Episodic { session_id: Uuid::nil(), turn: 0 },
+            )
+            .with_metadata("user_query".to_string(), user.to_string());
+            match lang {
+                Some(lang) => entry.with_metadata(LANGUAGE_KEY.to_string(), lang.to_string()),
+                None => entry,
+            }
+        };
+
+        let ru = Language::detect("Как дела с проектом?");
+        assert_eq!(language_boost(ru, &entry("Hello there", Some("ru"))), LANGUAGE_BOOST);
+        assert_eq!(language_boost(ru, &entry("Hello there", Some("en"))), 0.0);
+        // untagged entries from older stores are detected on the fly
+        assert_eq!(language_boost(ru, &entry("Привет, как дела?", None)), LANGUAGE_BOOST);
+        assert_eq!(language_boost(None, &entry("Привет, как дела?", None)), 0.0);
+    }
+
+    #[tokio::test]
+    async fn test_repeated_input_is_not_indexed() -> Result<()> {
+        let embedder: Arc<dyn Embedder> = Arc::new(MockEmbedder::default());
+        let mut manager = DialogueManager::new(embedder, "test_persona".to_string());
+
+        manager
+            .add_exchange("How do I sort a Vec in Rust?".to_string(), "Use sort().".to_string())
+            .await?;
+        manager
+            .add_exchange("how do I sort a vec in rust".to_string(), "sort() or sort_by().".to_string())
+            .await?;
+        manager
+            .add_exchange("And a HashMap?".to_string(), "Collect into a Vec first.".to_string())
+            .await?;
+
+        assert_eq!(manager.current_session().turn_count(), 3);
+        assert_eq!(
+            manager.current_session().turns[1].metadata.get(DUPLICATE_OF_KEY).map(String::as_str),
+            Some("0")
+        );
+        assert_eq!(manager.vector_store.len(), 2);
+
+        assert!(is_near_duplicate("Привет, как дела?", "привет как дела"));
+        assert!(!is_near_duplicate("What changed in Rust 1.74?", "What changed in Rust 1.75?"));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_own_phrasings_skip_current_session() -> Result<()> {
+        let embedder: Arc<dyn Embedder> = Arc::new(MockEmbedder::default());
+        let mut manager = DialogueManager::new(embedder, "teacher".to_string());
+        let answer = "Ownership is like a library card: only one reader holds the book at a time.";
+
+        manager.add_exchange("What is ownership?".to_string(), answer.to_string()).await?;
+        manager.add_exchange("Thanks".to_string(), "You're welcome!".to_string()).await?;
+        assert_eq!(manager.style_store.len(), 1);
+        assert!(manager.find_own_phrasings(answer, 2).await?.is_empty());
+
+        manager.start_new_session("teacher".to_string());
+        let found = manager.find_own_phrasings(answer, 2).await?;
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].excerpt, answer);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_recall_score() -> Result<()> {
+        let embedder: Arc<dyn Embedder> = Arc::new(MockEmbedder::default());
+        let mut manager = DialogueManager::new(embedder, "test_persona".to_string());
+        assert_eq!(manager.recall_score("What is my cat's name?").await?, 0.0);
+
+        manager.add_exchange("My cat is Murka".to_string(), "Nice name!".to_string()).await?;
+        // ход индексируется с префиксом, тот же текст находится с полным сходством
+        assert!(manager.recall_score("User query: My cat is Murka").await? > 0.99);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_memory_inspection() -> Result<()> {
+        let embedder: Arc<dyn Embedder> = Arc::new(MockEmbedder::default());
+        let mut manager = DialogueManager::new(embedder, "test_persona".to_string());
+        assert!(manager.list_sessions(10).is_empty());
+
+        manager.add_exchange("My cat is Murka".to_string(), "Nice name!".to_string()).await?;
+        manager.add_exchange("I live in Kazan".to_string(), "Lovely city.".to_string()).await?;
+        let first = manager.current_session().id;
+        manager.start_new_session("test_persona".to_string());
+        manager.add_exchange("Tell me about Rust".to_string(), "It is a language.".to_string()).await?;
+        let second = manager.current_session().id;
+
+        let sessions = manager.list_sessions(10);
+        assert_eq!(sessions.iter().map(|s| (s.id, s.turns, s.current)).collect::<Vec<_>>(), [(second, 1, true), (first, 2, false)]);
+        assert_eq!(sessions[1].first_question, "My cat is Murka");
+
+        let hits = manager.search_memory("User query: My cat is Murka", 2).await?;
+        assert_eq!((hits[0].session_id, hits[0].turn, hits[0].assistant.as_str()), (first, 0, "Nice name!"));
+        assert!(hits[0].similarity > 0.99);
+        assert_eq!(hits[0].recall_count, 0);
+
+        // deleting the current session leaves an empty one in its place
+        assert!(manager.delete_session(second));
+        assert_ne!(manager.current_session().id, second);
+        assert!(manager.session(&second).is_none());
+        assert_eq!(manager.stats().total_turns, 2);
+        assert!(manager.delete_session(first));
+        assert!(manager.list_sessions(10).is_empty());
+        assert!(!manager.delete_session(first));
+        Ok(())
+    }
+}
@@ -0,0 +1,1680 @@
+//! 💾 Persistence Layer - Сохранение памяти на диск
+//!
+//! Сохраняет сессии и эмбеддинги для полного восстановления состояния
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::priests::embeddings::Embedder;
+use crate::totems::consent::EPHEMERAL_KEY;
+use crate::totems::language::LANGUAGE_KEY;
+use crate::totems::retrieval::{MemoryEntry, MemoryType, VectorStore};
+use crate::utils::crypto::{self, MemoryKey};
+use crate::utils::lock::io_guard;
+
+use super::DeferredSession;
+
+const MEMORY_DIR: &str = "memory_data";
+const SESSIONS_FILE: &str = "sessions.json";
+const EMBEDDINGS_FILE: &str = "embeddings.bin";
+/// Эмбеддинги ответов персоны (память стиля), формат как у `embeddings.bin`
+const STYLE_EMBEDDINGS_FILE: &str = "style_embeddings.bin";
+/// Эмбеддинги кода ходов (см. [`super::code`]), формат как у `embeddings.bin`
+const CODE_EMBEDDINGS_FILE: &str = "code_embeddings.bin";
+const METADATA_FILE: &str = "metadata.json";
+
+/// Версия формата `*.bin`: 2 - отпечаток модели в заголовке и контрольная сумма каждого вектора
+const EMBEDDINGS_FORMAT_VERSION: u32 = 2;
+const HEADER_V1_SIZE: usize = 32;
+const HEADER_V2_SIZE: usize = 48;
+const INDEX_V1_SIZE: usize = 32;
+const INDEX_V2_SIZE: usize = 40;
+
+/// Векторы на диске нельзя использовать: посчитаны другой моделью или повреждены.
+/// Начинать с пустой памятью в этом случае нельзя - следующее сохранение затрёт сессии
+#[derive(Debug, Clone, PartialEq)]
+pub enum EmbeddingsIntegrityError {
+    /// Отпечаток модели в заголовке не совпадает с текущим эмбеддером
+    ModelMismatch { path: PathBuf, stored: u64, current: u64 },
+    /// У части векторов не сходится контрольная сумма
+    Corrupted { path: PathBuf, bad: usize, total: usize },
+    /// Векторы на диске другой размерности, чем у текущего эмбеддера
+    DimensionMismatch { stored: usize, current: usize },
+}
+
+impl std::fmt::Display for EmbeddingsIntegrityError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::ModelMismatch { path, stored, current } => write!(
+                f,
+                "{:?} was written by another embedding model (fingerprint {:016x}, current {:016x}). \
+                 Start with the embedding model the memory was saved with (--embedding-path), \
+                 restore memory_data from a backup, or move the file aside and re-embed the sessions",
+                path, stored, current
+            ),
+            Self::Corrupted { path, bad, total } => write!(
+                f,
+                "{:?} is corrupted: {} of {} vectors fail their checksum. \
+                 Restore memory_data from a backup, or move the file aside and re-embed the sessions",
+                path, bad, total
+            ),
+            Self::DimensionMismatch { stored, current } => write!(
+                f,
+                "episodic memory holds {}-dimensional vectors, the current embedding model produces {}. \
+                 Start with the embedding model the memory was saved with or re-embed the sessions",
+                stored, current
+            ),
+        }
+    }
+}
+
+impl std::error::Error for EmbeddingsIntegrityError {}
+
+/// FNV-1a: стабильный между сборками хэш для отпечатка модели и контрольных сумм на диске
+pub(crate) fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, &b| (hash ^ b as u64).wrapping_mul(0x100000001b3))
+}
+
+/// Контрольная сумма вектора в том виде, как он лежит в файле
+fn embedding_checksum(embedding: &[f32]) -> u64 {
+    let bytes: Vec<u8> = embedding.iter().flat_map(|v| v.to_le_bytes()).collect();
+    fnv1a(&bytes)
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StorageMetadata {
+    pub version: String,
+    pub created_at: DateTime<Utc>,
+    pub last_saved_at: DateTime<Utc>,
+    pub total_sessions: usize,
+    pub total_turns: usize,
+    #[serde(default = "default_embedding_dim")]
+    pub embedding_dim: usize,
+    /// Размерность векторов кода, если они от отдельной модели
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub code_embedding_dim: Option<usize>,
+    /// Модель векторов кода (`--code-embedding-path`); `None` - основной эмбеддер
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub code_model: Option<String>,
+    /// Модель эмбеддингов, которой посчитаны векторы; `None` - сохранено без имени модели
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub embedding_model: Option<String>,
+}
+
+fn default_embedding_dim() -> usize {
+    384
+}
+
+impl Default for StorageMetadata {
+    fn default() -> Self {
+        Self {
+            version: "1.0".to_string(),
+            created_at: Utc::now(),
+            last_saved_at: Utc::now(),
+            total_sessions: 0,
+            total_turns: 0,
+            embedding_dim: 384,
+            code_embedding_dim: None,
+            code_model: None,
+            embedding_model: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MemoryStorage {
+    pub metadata: StorageMetadata,
+    pub sessions: Vec<SerializedSession>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SerializedSession {
+    pub id: String,
+    pub persona_name: String,
+    pub turns: Vec<SerializedTurn>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub metadata: HashMap<String, String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SerializedTurn {
+    pub user: String,
+    pub assistant: String,
+    pub timestamp: DateTime<Utc>,
+    pub metadata: HashMap<String, String>,
+    #[serde(default)]
+    pub embedding: Option<Vec<f32>>,
+    #[serde(default)]
+    pub sampling: Option<super::SamplingRecord>,
+}
+
+/// Какие сессии загружать сразу. Остальные остаются на диске до
+/// [`PersistenceManager::load_deferred`] и при сохранении переписываются как есть
+#[derive(Debug, Clone, Default)]
+pub struct LoadScope {
+    /// Только сессии этой персоны (`None` - всех)
+    pub persona: Option<String>,
+    /// Только сессии, обновлённые за последние N дней (`None` - без ограничения)
+    pub recent_days: Option<i64>,
+}
+
+impl LoadScope {
+    /// Все сессии
+    pub fn all() -> Self {
+        Self::default()
+    }
+
+    /// Все сессии персоны
+    pub fn persona(persona: impl Into<String>) -> Self {
+        Self {
+            persona: Some(persona.into()),
+            recent_days: None,
+        }
+    }
+
+    /// Ограничение по давности; 0 снимает ограничение
+    pub fn recent(mut self, days: i64) -> Self {
+        self.recent_days = (days > 0).then_some(days);
+        self
+    }
+
+    pub fn includes(&self, persona_name: &str, updated_at: DateTime<Utc>) -> bool {
+        self.persona.as_deref().is_none_or(|p| p == persona_name)
+            && self
+                .recent_days
+                .is_none_or(|days| updated_at >= Utc::now() - chrono::Duration::days(days))
+    }
+}
+
+/// Эмбеддинг хода, прочитанный из `embeddings.bin`
+#[derive(Debug, Clone)]
+struct StoredEmbedding {
+    session_id: Uuid,
+    turn_idx: u32,
+    embedding: Vec<f32>,
+}
+
+/// Память на диске посчитана не той моделью эмбеддингов, что сейчас
+#[derive(Debug, Clone, PartialEq)]
+pub struct EmbeddingMismatch {
+    /// `None` - память сохранена до того, как имя модели стало записываться
+    pub stored_model: Option<String>,
+    pub stored_dim: usize,
+    pub current_model: Option<String>,
+    pub current_dim: usize,
+}
+
+impl std::fmt::Display for EmbeddingMismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = |model: &Option<String>| model.clone().unwrap_or_else(|| "another model".to_string());
+        write!(
+            f,
+            "memory was embedded by {} ({}d), current model is {} ({}d)",
+            name(&self.stored_model),
+            self.stored_dim,
+            name(&self.current_model),
+            self.current_dim
+        )
+    }
+}
+
+/// Итог [`PersistenceManager::reembed`]
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ReembedReport {
+    pub sessions: usize,
+    /// Пересчитанные векторы ходов
+    pub vectors: usize,
+}
+
+pub struct PersistenceManager {
+    memory_dir: PathBuf,
+    auto_save: bool,
+    last_save: DateTime<Utc>,
+    /// Каталог принадлежит другому процессу: читаем, но не пишем (см. [`crate::utils::lock`])
+    read_only: bool,
+    /// Отпечаток модели эмбеддингов; 0 - неизвестна, векторы принимаются от любой
+    model_fingerprint: u64,
+    /// Имя той же модели для `metadata.json`
+    embedding_model: Option<String>,
+    /// Ключ шифрования `sessions.json`, `*.bin`, сводок и архива; `None` - пишутся открытыми
+    key: Option<MemoryKey>,
+}
+
+impl PersistenceManager {
+    pub fn new(base_path: Option<&Path>, auto_save: bool) -> Result<Self> {
+        let memory_dir = base_path
+            .map(|p| p.join(MEMORY_DIR))
+            .unwrap_or_else(|| PathBuf::from(MEMORY_DIR));
+
+        if !memory_dir.exists() {
+            fs::create_dir_all(&memory_dir)
+                .with_context(|| format!("Failed to create memory directory: {:?}", memory_dir))?;
+        }
+
+        Ok(Self {
+            memory_dir,
+            auto_save,
+            last_save: Utc::now(),
+            read_only: false,
+            model_fingerprint: 0,
+            embedding_model: None,
+            key: crypto::installed(),
+        })
+    }
+
+    /// Ключ шифрования вместо поставленного [`crypto::install`]
+    pub fn with_encryption(mut self, key: Option<MemoryKey>) -> Self {
+        self.key = key;
+        self
+    }
+
+    pub fn is_encrypted(&self) -> bool {
+        self.key.is_some()
+    }
+
+    /// Только чтение: сохранения пропускаются, изменения живут до конца процесса
+    pub fn with_read_only(mut self, read_only: bool) -> Self {
+        self.read_only = read_only;
+        self
+    }
+
+    /// Модель эмбеддингов, которой посчитаны векторы: записывается в `*.bin` и сверяется
+    /// при загрузке, чтобы векторы другой модели не портили поиск молча
+    pub fn with_embedding_model(mut self, model: &str) -> Self {
+        self.model_fingerprint = fnv1a(model.as_bytes());
+        self.embedding_model = Some(model.to_string());
+        self
+    }
+
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    fn sessions_path(&self) -> PathBuf {
+        self.memory_dir.join(SESSIONS_FILE)
+    }
+
+    fn embeddings_path(&self) -> PathBuf {
+        self.memory_dir.join(EMBEDDINGS_FILE)
+    }
+
+    fn style_embeddings_path(&self) -> PathBuf {
+        self.memory_dir.join(STYLE_EMBEDDINGS_FILE)
+    }
+
+    fn code_embeddings_path(&self) -> PathBuf {
+        self.memory_dir.join(CODE_EMBEDDINGS_FILE)
+    }
+
+    fn metadata_path(&self) -> PathBuf {
+        self.memory_dir.join(METADATA_FILE)
+    }
+
+    pub async fn save_with_embeddings(
+        &self,
+        manager: &super::DialogueManager,
+        embedding_dim: usize,
+    ) -> Result<()> {
+        if self.read_only {
+            return Ok(());
+        }
+        let _guard = io_guard(&self.memory_dir, true)?;
+        let deferred = self.read_deferred(manager).await?;
+        let sessions: Vec<SerializedSession> = manager
+            .session_history()
+            .values()
+            .map(serialize_session)
+            .chain(std::iter::once(
+                serialize_session(manager.current_session()),
+            ))
+            .chain(deferred.sessions)
+            .collect();
+
+        let total_turns: usize = sessions.iter().map(|s| s.turns.len()).sum();
+
+        let storage = MemoryStorage {
+            metadata: StorageMetadata {
+                version: "2.0".to_string(),
+                created_at: Utc::now(),
+                last_saved_at: Utc::now(),
+                total_sessions: sessions.len(),
+                total_turns,
+                embedding_dim,
+                code_embedding_dim: (manager.code_store.dimension() != embedding_dim)
+                    .then(|| manager.code_store.dimension()),
+                code_model: manager.code_model.clone(),
+                embedding_model: self.embedding_model.clone(),
+            },
+            sessions,
+        };
+
+        let sessions_content =
+            serde_json::to_string_pretty(&storage).context("Failed to serialize sessions")?;
+        let embeddings_content =
+            self.encode_embeddings_binary(&manager.vector_store, manager, embedding_dim, deferred.embeddings);
+        let style_content =
+            self.encode_embeddings_binary(&manager.style_store, manager, embedding_dim, deferred.style);
+        let code_dim = manager.code_store.dimension();
+        let code_content = self.encode_embeddings_binary(&manager.code_store, manager, code_dim, deferred.code);
+        let metadata_content = serde_json::to_string_pretty(&storage.metadata)
+            .context("Failed to serialize metadata")?;
+
+        let key = self.key.as_ref();
+        crate::utils::fs::write(self.sessions_path(), crypto::seal(key, sessions_content)?)
+            .await
+            .context("Failed to write sessions file")?;
+        crate::utils::fs::write(self.embeddings_path(), crypto::seal(key, embeddings_content)?)
+            .await
+            .context("Failed to write embeddings file")?;
+        crate::utils::fs::write(self.style_embeddings_path(), crypto::seal(key, style_content)?)
+            .await
+            .context("Failed to write style embeddings file")?;
+        crate::utils::fs::write(self.code_embeddings_path(), crypto::seal(key, code_content)?)
+            .await
+            .context("Failed to write code embeddings file")?;
+        crate::utils::fs::write(self.metadata_path(), metadata_content)
+            .await
+            .context("Failed to write metadata file")?;
+
+        Ok(())
+    }
+
+    fn encode_embeddings_binary(
+        &self,
+        store: &VectorStore,
+        manager: &super::DialogueManager,
+        embedding_dim: usize,
+        deferred: Vec<StoredEmbedding>,
+    ) -> Vec<u8> {
+        let mut embeddings_data: Vec<f32> = Vec::new();
+        let mut index_data: Vec<EmbeddingIndex> = Vec::new();
+
+        let sessions = manager
+            .session_history()
+            .values()
+            .chain(std::iter::once(manager.current_session()));
+        for session in sessions {
+            // на диске нет эфемерных ходов, поэтому номер хода - среди сохраняемых
+            for (saved_idx, (turn_idx, _turn)) in persisted_turns(session).enumerate() {
+                let entry = store.entries().find(|e| {
+                    if let MemoryType::Episodic {
+                        session_id: e_session_id,
+                        turn: e_turn,
+                    } = &e.memory_type
+                    {
+                        *e_session_id == session.id && *e_turn == turn_idx
+                    } else {
+                        false
+                    }
+                });
+
+                if let Some(entry) = entry {
+                    let offset = embeddings_data.len() as u64;
+                    embeddings_data.extend(&entry.embedding);
+                    index_data.push(EmbeddingIndex {
+                        session_id: session.id,
+                        turn_idx: saved_idx as u32,
+                        offset,
+                        size: entry.embedding.len() as u32,
+                        checksum: Some(embedding_checksum(&entry.embedding)),
+                    });
+                }
+            }
+        }
+
+        for stored in deferred.into_iter().filter(|e| e.embedding.len() == embedding_dim) {
+            let offset = embeddings_data.len() as u64;
+            embeddings_data.extend(&stored.embedding);
+            index_data.push(EmbeddingIndex {
+                session_id: stored.session_id,
+                turn_idx: stored.turn_idx,
+                offset,
+                size: stored.embedding.len() as u32,
+                checksum: Some(embedding_checksum(&stored.embedding)),
+            });
+        }
+
+        let index_data_len = index_data.len() as u64;
+
+        let header = EmbeddingsHeader {
+            version: EMBEDDINGS_FORMAT_VERSION,
+            embedding_dim: embedding_dim as u32,
+            num_embeddings: index_data_len,
+            index_offset: HEADER_V2_SIZE as u64,
+            data_offset: HEADER_V2_SIZE as u64 + index_data_len * INDEX_V2_SIZE as u64,
+            model_fingerprint: self.model_fingerprint,
+        };
+
+        let mut file_content = Vec::new();
+        file_content.extend_from_slice(&header.to_bytes());
+
+        for idx in &index_data {
+            file_content.extend_from_slice(&idx.to_bytes());
+        }
+
+        for emb in &embeddings_data {
+            file_content.extend_from_slice(&emb.to_le_bytes());
+        }
+
+        file_content
+    }
+
+    /// Загружает все сессии персоны; сессии других персон откладываются
+    pub async fn load_with_embeddings(
+        &self,
+        embedder: Arc<dyn Embedder>,
+        persona_name: String,
+    ) -> Result<Option<(super::DialogueManager, Vec<SerializedSession>)>> {
+        let scope = LoadScope::persona(persona_name.clone());
+        self.load_scoped(embedder, persona_name, &scope).await
+    }
+
+    /// Загружает сессии из `scope` вместе с их эмбеддингами. Остальные сессии
+    /// только помечаются как отложенные: ни ходы, ни векторы не попадают в память
+    pub async fn load_scoped(
+        &self,
+        embedder: Arc<dyn Embedder>,
+        persona_name: String,
+        scope: &LoadScope,
+    ) -> Result<Option<(super::DialogueManager, Vec<SerializedSession>)>> {
+        let _guard = io_guard(&self.memory_dir, false)?;
+        let Some(storage) = self.read_storage().await? else {
+            return Ok(None);
+        };
+
+        let dimension = storage.metadata.embedding_dim;
+        let code_dimension = storage.metadata.code_embedding_dim.unwrap_or(dimension);
+        // индекс другой размерности ничего не нашёл бы: лучше ошибка, чем пустой поиск
+        if !storage.sessions.is_empty() && dimension != embedder.embedding_dim() {
+            return Err(EmbeddingsIntegrityError::DimensionMismatch {
+                stored: dimension,
+                current: embedder.embedding_dim(),
+            }
+            .into());
+        }
+
+        let mut manager = super::DialogueManager {
+            current_session: super::Session::new(persona_name.clone()),
+            vector_store: VectorStore::new(dimension),
+            style_store: VectorStore::new(dimension),
+            code_store: VectorStore::new(code_dimension),
+            code_embedder: None,
+            code_model: storage.metadata.code_model.clone(),
+            embedder: embedder.clone(),
+            session_history: HashMap::new(),
+            max_sessions: 100,
+            archive: None,
+            deferred: HashMap::new(),
+            utc_offset: chrono::FixedOffset::east_opt(0).expect("zero offset"),
+            consent: Default::default(),
+            significance: Default::default(),
+            clock: crate::utils::clock::default_clock(),
+            recall_cache: Default::default(),
+            retrieval: Default::default(),
+            user: Default::default(),
+            segmentation: None,
+            segment_summarizer: None,
+            session_breaks: Vec::new(),
+            segment_summaries: Default::default(),
+        };
+
+        let mut eager = HashSet::new();
+        for session in &storage.sessions {
+            if scope.includes(&session.persona_name, session.updated_at) {
+                if let Ok(deserialized) = self.deserialize_session(session.clone()) {
+                    eager.insert(deserialized.id);
+                    manager
+                        .session_history
+                        .insert(deserialized.id, deserialized);
+                }
+            } else if let Ok(id) = Uuid::parse_str(&session.id) {
+                manager.deferred.insert(id, deferred_session(session));
+            }
+        }
+
+        let embeddings = self.read_embeddings(&self.embeddings_path(), dimension).await?;
+        add_memory_entries(&mut manager, &storage.sessions, embeddings, &eager)?;
+        let style = self.read_embeddings(&self.style_embeddings_path(), dimension).await?;
+        add_style_entries(&mut manager, &storage.sessions, style, &eager)?;
+        let code = self.read_embeddings(&self.code_embeddings_path(), code_dimension).await?;
+        add_code_entries(&mut manager, &storage.sessions, code, &eager)?;
+
+        Ok(Some((manager, storage.sessions)))
+    }
+
+    /// Подгружает отложенные сессии, подходящие под `filter`, вместе с эмбеддингами.
+    /// Возвращает число загруженных сессий
+    pub async fn load_deferred(
+        &self,
+        manager: &mut super::DialogueManager,
+        filter: impl Fn(&Uuid, &DeferredSession) -> bool,
+    ) -> Result<usize> {
+        let wanted: HashSet<Uuid> = manager
+            .deferred
+            .iter()
+            .filter(|(id, d)| filter(id, d))
+            .map(|(id, _)| *id)
+            .collect();
+        if wanted.is_empty() {
+            return Ok(0);
+        }
+
+        let _guard = io_guard(&self.memory_dir, false)?;
+        let storage = self
+            .read_storage()
+            .await?
+            .context("Sessions file is missing, deferred sessions cannot be loaded")?;
+        let mut loaded = HashSet::new();
+        for session in &storage.sessions {
+            let Ok(id) = Uuid::parse_str(&session.id) else {
+                continue;
+            };
+            if wanted.contains(&id) && !manager.session_history.contains_key(&id) {
+                manager
+                    .session_history
+                    .insert(id, self.deserialize_session(session.clone())?);
+                loaded.insert(id);
+            }
+        }
+
+        let dimension = storage.metadata.embedding_dim;
+        let embeddings = self.read_embeddings(&self.embeddings_path(), dimension).await?;
+        add_memory_entries(manager, &storage.sessions, embeddings, &loaded)?;
+        let style = self.read_embeddings(&self.style_embeddings_path(), dimension).await?;
+        add_style_entries(manager, &storage.sessions, style, &loaded)?;
+        // векторы кода другой модели не подходят к индексу: такой код эмбеддится заново
+        if storage.metadata.code_model == manager.code_model {
+            let code_dimension = storage.metadata.code_embedding_dim.unwrap_or(dimension);
+            let code = self.read_embeddings(&self.code_embeddings_path(), code_dimension).await?;
+            add_code_entries(manager, &storage.sessions, code, &loaded)?;
+        }
+        manager.index_missing_code(Some(&loaded)).await?;
+        for id in &wanted {
+            manager.deferred.remove(id);
+        }
+
+        Ok(loaded.len())
+    }
+
+    /// Сверяет векторы на диске с текущей моделью эмбеддингов (имя в `metadata.json`,
+    /// отпечаток в `embeddings.bin`) и размерностью. `None` - совпадают или сессий нет
+    pub fn embedding_mismatch(&self, current_dim: usize) -> Result<Option<EmbeddingMismatch>> {
+        let _guard = io_guard(&self.memory_dir, false)?;
+        if !self.metadata_path().exists() {
+            return Ok(None);
+        }
+        let stored = self.get_stats()?;
+        if stored.total_sessions == 0 {
+            return Ok(None);
+        }
+        let model_changed = match (&stored.embedding_model, &self.embedding_model) {
+            (Some(stored), Some(current)) => stored != current,
+            // метаданные без имени модели: остаётся отпечаток в заголовке
+            _ => self
+                .stored_fingerprint()
+                .is_some_and(|stored| self.model_fingerprint != 0 && stored != self.model_fingerprint),
+        };
+        if !model_changed && stored.embedding_dim == current_dim {
+            return Ok(None);
+        }
+        Ok(Some(EmbeddingMismatch {
+            stored_model: stored.embedding_model,
+            stored_dim: stored.embedding_dim,
+            current_model: self.embedding_model.clone(),
+            current_dim,
+        }))
+    }
+
+    /// Отпечаток модели из заголовка `embeddings.bin`, если он там есть
+    fn stored_fingerprint(&self) -> Option<u64> {
+        let mut header = [0u8; HEADER_V2_SIZE];
+        let mut file = fs::File::open(self.embeddings_path()).ok()?;
+        std::io::Read::read_exact(&mut file, &mut header).ok()?;
+        if crypto::is_sealed(&header) {
+            // заголовок зашифрован вместе с векторами
+            let content = crypto::unseal(self.key.as_ref(), fs::read(self.embeddings_path()).ok()?).ok()?;
+            header.copy_from_slice(content.get(..HEADER_V2_SIZE)?);
+        }
+        Some(EmbeddingsHeader::from_bytes(&header).model_fingerprint).filter(|&f| f != 0)
+    }
+
+    /// Пересчитывает векторы всех сессий (ходы, стиль, код) моделью `embedder` и перезаписывает
+    /// `*.bin`: миграция после смены модели эмбеддингов. Сессии берутся из `sessions.json`,
+    /// старые векторы не читаются. `progress` получает число готовых сессий и их общее число
+    pub async fn reembed(
+        &self,
+        embedder: Arc<dyn Embedder>,
+        mut progress: impl FnMut(usize, usize),
+    ) -> Result<ReembedReport> {
+        anyhow::ensure!(!self.read_only, "Episodic memory is opened read-only");
+        let sessions = {
+            let _guard = io_guard(&self.memory_dir, false)?;
+            match self.read_storage().await? {
+                Some(storage) => storage.sessions,
+                None => return Ok(ReembedReport::default()),
+            }
+        };
+
+        let dimension = embedder.embedding_dim();
+        let mut manager = super::DialogueManager::new(embedder, String::new());
+        let mut report = ReembedReport::default();
+        let total = sessions.len();
+        for (done, session) in sessions.into_iter().enumerate() {
+            let (imported, vectors) = manager.import_sessions(vec![session], false).await?;
+            report.sessions += imported;
+            report.vectors += vectors;
+            progress(done + 1, total);
+        }
+        // последняя сессия становится текущей, иначе сохранение добавит пустую
+        let latest = manager.session_history.values().max_by_key(|s| s.updated_at).map(|s| s.id);
+        if let Some(session) = latest.and_then(|id| manager.session_history.remove(&id)) {
+            manager.current_session = session;
+        }
+        self.save_with_embeddings(&manager, dimension).await?;
+        Ok(report)
+    }
+
+    async fn read_storage(&self) -> Result<Option<MemoryStorage>> {
+        if !self.sessions_path().exists() {
+            return Ok(None);
+        }
+
+        let content = crate::utils::fs::read(self.sessions_path())
+            .await
+            .context("Failed to read sessions file")?;
+        let content = crypto::unseal_string(self.key.as_ref(), content).context("Failed to decrypt sessions file")?;
+
+        let storage: MemoryStorage =
+            serde_json::from_str(&content).context("Failed to deserialize sessions")?;
+        Ok(Some(storage))
+    }
+
+    async fn read_embeddings(&self, path: &Path, embedding_dim: usize) -> Result<Vec<StoredEmbedding>> {
+        if !path.exists() {
+            return Ok(Vec::new());
+        }
+        let file_content = crate::utils::fs::read(path)
+            .await
+            .with_context(|| format!("Failed to read {:?}", path))?;
+        let file_content =
+            crypto::unseal(self.key.as_ref(), file_content).with_context(|| format!("Failed to decrypt {:?}", path))?;
+        self.decode_embeddings_binary(path, embedding_dim, &file_content)
+    }
+
+    /// Отложенные сессии и их эмбеддинги (ходов, стиля и кода) с диска, чтобы сохранение их не затёрло
+    async fn read_deferred(&self, manager: &super::DialogueManager) -> Result<DeferredData> {
+        if manager.deferred.is_empty() {
+            return Ok(DeferredData::default());
+        }
+        let Some(storage) = self.read_storage().await? else {
+            return Ok(DeferredData::default());
+        };
+
+        let dimension = storage.metadata.embedding_dim;
+        let code_dimension = storage.metadata.code_embedding_dim.unwrap_or(dimension);
+        let deferred = |stored: Vec<StoredEmbedding>| -> Vec<StoredEmbedding> {
+            stored
+                .into_iter()
+                .filter(|e| manager.deferred.contains_key(&e.session_id))
+                .collect()
+        };
+        let embeddings = deferred(self.read_embeddings(&self.embeddings_path(), dimension).await?);
+        let style = deferred(self.read_embeddings(&self.style_embeddings_path(), dimension).await?);
+        let code = if storage.metadata.code_model == manager.code_model {
+            deferred(self.read_embeddings(&self.code_embeddings_path(), code_dimension).await?)
+        } else {
+            Vec::new()
+        };
+        let sessions = storage
+            .sessions
+            .into_iter()
+            .filter(|s| Uuid::parse_str(&s.id).is_ok_and(|id| manager.deferred.contains_key(&id)))
+            .collect();
+        Ok(DeferredData {
+            sessions,
+            embeddings,
+            style,
+            code,
+        })
+    }
+
+    /// Векторы из `*.bin`. Файл формата 2 сверяется с моделью эмбеддингов и контрольными
+    /// суммами: несовпадение - [`EmbeddingsIntegrityError`], а не тихо испорченный поиск
+    fn decode_embeddings_binary(
+        &self,
+        path: &Path,
+        embedding_dim: usize,
+        file_content: &[u8],
+    ) -> Result<Vec<StoredEmbedding>> {
+        if file_content.len() < HEADER_V1_SIZE {
+            anyhow::bail!(
+                "Embeddings file is too small: {} < {}",
+                file_content.len(),
+                HEADER_V1_SIZE
+            );
+        }
+
+        let header = EmbeddingsHeader::from_bytes(file_content);
+        let (header_size, index_size) = if header.version >= 2 {
+            (HEADER_V2_SIZE, INDEX_V2_SIZE)
+        } else {
+            (HEADER_V1_SIZE, INDEX_V1_SIZE)
+        };
+        // у файлов без отпечатка (формат 1 или модель не указана) модель не проверить
+        if header.model_fingerprint != 0
+            && self.model_fingerprint != 0
+            && header.model_fingerprint != self.model_fingerprint
+        {
+            return Err(EmbeddingsIntegrityError::ModelMismatch {
+                path: path.to_path_buf(),
+                stored: header.model_fingerprint,
+                current: self.model_fingerprint,
+            }
+            .into());
+        }
+
+        let expected_file_size =
+            header.data_offset as usize + (header.num_embeddings as usize * embedding_dim * 4);
+
+        if file_content.len() < expected_file_size {
+            eprintln!("Warning: Embeddings file may be corrupted");
+        }
+
+        let data_start = header.data_offset as usize;
+
+        let mut stored = Vec::new();
+        let mut bad = 0;
+        let mut offset = header_size;
+        for _ in 0..header.num_embeddings {
+            if offset + index_size > file_content.len() {
+                break;
+            }
+
+            let index = EmbeddingIndex::from_bytes(&file_content[offset..offset + index_size]);
+            offset += index_size;
+
+            // смещение в индексе считается в f32, а не в байтах
+            let data_offset = data_start + index.offset as usize * 4;
+            let data_end = data_offset + (index.size as usize) * 4;
+
+            if data_end > file_content.len() {
+                continue;
+            }
+
+            let embedding_data = &file_content[data_offset..data_end];
+            let embedding: Vec<f32> = embedding_data
+                .chunks_exact(4)
+                .map(|bytes| f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+                .collect();
+
+            if index.checksum.is_some_and(|checksum| checksum != embedding_checksum(&embedding)) {
+                bad += 1;
+                continue;
+            }
+            if embedding.len() == embedding_dim {
+                stored.push(StoredEmbedding {
+                    session_id: index.session_id,
+                    turn_idx: index.turn_idx,
+                    embedding,
+                });
+            }
+        }
+
+        if bad > 0 {
+            return Err(EmbeddingsIntegrityError::Corrupted {
+                path: path.to_path_buf(),
+                bad,
+                total: header.num_embeddings as usize,
+            }
+            .into());
+        }
+        Ok(stored)
+    }
+
+    /// Сессии с диска с эмбеддингами ходов из `embeddings.bin` (для экспорта)
+    pub async fn load_sessions_with_embeddings(&self) -> Result<Option<Vec<SerializedSession>>> {
+        let _guard = io_guard(&self.memory_dir, false)?;
+        let Some(storage) = self.read_storage().await? else {
+            return Ok(None);
+        };
+        let mut sessions = storage.sessions;
+        let index: HashMap<Uuid, usize> = sessions
+            .iter()
+            .enumerate()
+            .filter_map(|(i, s)| Uuid::parse_str(&s.id).ok().map(|id| (id, i)))
+            .collect();
+        for stored in self
+            .read_embeddings(&self.embeddings_path(), storage.metadata.embedding_dim)
+            .await?
+        {
+            let turn = index
+                .get(&stored.session_id)
+                .and_then(|&i| sessions[i].turns.get_mut(stored.turn_idx as usize));
+            if let Some(turn) = turn {
+                turn.embedding = Some(stored.embedding);
+            }
+        }
+        Ok(Some(sessions))
+    }
+
+    pub fn load_sessions(&self) -> Result<Option<Vec<SerializedSession>>> {
+        let _guard = io_guard(&self.memory_dir, false)?;
+        if !self.sessions_path().exists() {
+            return Ok(None);
+        }
+
+        let content = self.read_sessions_file()?;
+
+        let storage: MemoryStorage =
+            serde_json::from_str(&content).context("Failed to deserialize sessions")?;
+
+        Ok(Some(storage.sessions))
+    }
+
+    /// `sessions.json`, расшифрованный при необходимости
+    fn read_sessions_file(&self) -> Result<String> {
+        let content = fs::read(self.sessions_path()).context("Failed to read sessions file")?;
+        crypto::unseal_string(self.key.as_ref(), content).context("Failed to decrypt sessions file")
+    }
+
+    fn deserialize_session(&self, serialized: SerializedSession) -> Result<super::Session> {
+        let id = Uuid::parse_str(&serialized.id)
+            .with_context(|| format!("Invalid session UUID: {}", serialized.id))?;
+
+        let turns: Vec<super::Turn> = serialized
+            .turns
+            .into_iter()
+            .map(|t| super::Turn {
+                user: t.user,
+                assistant: t.assistant,
+                timestamp: t.timestamp,
+                metadata: t.metadata,
+                sampling: t.sampling,
+            })
+            .collect();
+
+        Ok(super::Session {
+            id,
+            persona_name: serialized.persona_name,
+            turns,
+            created_at: serialized.created_at,
+            updated_at: serialized.updated_at,
+            metadata: serialized.metadata,
+        })
+    }
+
+    pub fn cleanup_old(&self, days_old: i64) -> Result<usize> {
+        anyhow::ensure!(!self.read_only, "Episodic memory is opened read-only");
+        let cutoff = Utc::now() - chrono::Duration::days(days_old);
+        let _guard = io_guard(&self.memory_dir, true)?;
+
+        if !self.sessions_path().exists() {
+            return Ok(0);
+        }
+
+        let content = self.read_sessions_file()?;
+
+        let mut storage: MemoryStorage =
+            serde_json::from_str(&content).context("Failed to deserialize sessions")?;
+
+        let before_count = storage.sessions.len();
+        storage.sessions.retain(|s| s.updated_at > cutoff);
+
+        if storage.sessions.len() < before_count {
+            storage.metadata.total_sessions = storage.sessions.len();
+            storage.metadata.total_turns = storage.sessions.iter().map(|s| s.turns.len()).sum();
+            storage.metadata.last_saved_at = Utc::now();
+
+            let sessions_content =
+                serde_json::to_string_pretty(&storage).context("Failed to serialize sessions")?;
+            fs::write(self.sessions_path(), crypto::seal(self.key.as_ref(), sessions_content)?)
+                .context("Failed to write sessions file")?;
+
+            if let Ok(metadata_content) = serde_json::to_string_pretty(&storage.metadata) {
+                let _ = fs::write(self.metadata_path(), metadata_content);
+            }
+        }
+
+        Ok(before_count - storage.sessions.len())
+    }
+
+    /// Переписывает `sessions.json`, `*.bin`, сводки и архив сессий зашифрованными ключом
+    /// (`encrypt`) или открытыми: миграция существующего хранилища. Возвращает число
+    /// переписанных файлов. `metadata.json` остаётся открытым - по нему сверяется модель
+    /// эмбеддингов, а текста разговоров в нём нет
+    pub fn reseal(&self, encrypt: bool) -> Result<usize> {
+        anyhow::ensure!(!self.read_only, "Episodic memory is opened read-only");
+        let Some(key) = &self.key else {
+            anyhow::bail!("No memory key: set {} or store one in the OS keyring", crypto::KEY_ENV);
+        };
+        let _guard = io_guard(&self.memory_dir, true)?;
+        let mut rewritten = 0;
+        for path in [
+            self.sessions_path(),
+            self.embeddings_path(),
+            self.style_embeddings_path(),
+            self.code_embeddings_path(),
+            self.memory_dir.join(super::summaries::SUMMARIES_FILE),
+        ] {
+            if crypto::reseal_file(key, &path, encrypt).with_context(|| format!("Failed to rewrite {:?}", path))? {
+                rewritten += 1;
+            }
+        }
+        rewritten += super::archive::SessionArchive::open(&self.memory_dir).reseal(key, encrypt)?;
+        Ok(rewritten)
+    }
+
+    pub fn get_stats(&self) -> Result<StorageMetadata> {
+        if self.metadata_path().exists() {
+            let content =
+                fs::read_to_string(self.metadata_path()).context("Failed to read metadata file")?;
+            Ok(serde_json::from_str(&content).context("Failed to deserialize metadata")?)
+        } else {
+            Ok(StorageMetadata::default())
+        }
+    }
+
+    pub fn memory_dir(&self) -> &PathBuf {
+        &self.memory_dir
+    }
+
+    // ============ Sync facade (CLI) ============
+
+    /// Синхронная версия [`PersistenceManager::save_with_embeddings`]
+    pub fn save_with_embeddings_blocking(
+        &self,
+        manager: &super::DialogueManager,
+        embedding_dim: usize,
+    ) -> Result<()> {
+        crate::utils::block_on(self.save_with_embeddings(manager, embedding_dim))
+    }
+
+    /// Синхронная версия [`PersistenceManager::load_with_embeddings`]
+    pub fn load_with_embeddings_blocking(
+        &self,
+        embedder: Arc<dyn Embedder>,
+        persona_name: String,
+    ) -> Result<Option<(super::DialogueManager, Vec<SerializedSession>)>> {
+        crate::utils::block_on(self.load_with_embeddings(embedder, persona_name))
+    }
+
+    /// Синхронная версия [`PersistenceManager::load_scoped`]
+    pub fn load_scoped_blocking(
+        &self,
+        embedder: Arc<dyn Embedder>,
+        persona_name: String,
+        scope: &LoadScope,
+    ) -> Result<Option<(super::DialogueManager, Vec<SerializedSession>)>> {
+        crate::utils::block_on(self.load_scoped(embedder, persona_name, scope))
+    }
+
+    /// Синхронная версия [`PersistenceManager::load_sessions_with_embeddings`]
+    pub fn load_sessions_with_embeddings_blocking(&self) -> Result<Option<Vec<SerializedSession>>> {
+        crate::utils::block_on(self.load_sessions_with_embeddings())
+    }
+
+    /// Синхронная версия [`PersistenceManager::reembed`]
+    pub fn reembed_blocking(
+        &self,
+        embedder: Arc<dyn Embedder>,
+        progress: impl FnMut(usize, usize),
+    ) -> Result<ReembedReport> {
+        crate::utils::block_on(self.reembed(embedder, progress))
+    }
+
+    /// Синхронная версия [`PersistenceManager::load_deferred`]
+    pub fn load_deferred_blocking(
+        &self,
+        manager: &mut super::DialogueManager,
+        filter: impl Fn(&Uuid, &DeferredSession) -> bool,
+    ) -> Result<usize> {
+        crate::utils::block_on(self.load_deferred(manager, filter))
+    }
+}
+
+/// Заголовок `*.bin`. В формате 2 за полями формата 1 идут отпечаток модели и 8 резервных байт
+#[derive(Debug, Clone)]
+struct EmbeddingsHeader {
+    version: u32,
+    embedding_dim: u32,
+    num_embeddings: u64,
+    index_offset: u64,
+    data_offset: u64,
+    /// 0 - неизвестен (формат 1 или модель не указана)
+    model_fingerprint: u64,
+}
+
+impl EmbeddingsHeader {
+    fn to_bytes(&self) -> [u8; HEADER_V2_SIZE] {
+        let mut bytes = [0u8; HEADER_V2_SIZE];
+        bytes[0..4].copy_from_slice(&self.version.to_le_bytes());
+        bytes[4..8].copy_from_slice(&self.embedding_dim.to_le_bytes());
+        bytes[8..16].copy_from_slice(&self.num_embeddings.to_le_bytes());
+        bytes[16..24].copy_from_slice(&self.index_offset.to_le_bytes());
+        bytes[24..32].copy_from_slice(&self.data_offset.to_le_bytes());
+        bytes[32..40].copy_from_slice(&self.model_fingerprint.to_le_bytes());
+        bytes
+    }
+
+    fn from_bytes(data: &[u8]) -> Self {
+        let u64_at = |at: usize| u64::from_le_bytes(data[at..at + 8].try_into().expect("8 bytes"));
+        let version = u32::from_le_bytes([data[0], data[1], data[2], data[3]]);
+        let embedding_dim = u32::from_le_bytes([data[4], data[5], data[6], data[7]]);
+        let model_fingerprint = if version >= 2 && data.len() >= HEADER_V2_SIZE {
+            u64_at(32)
+        } else {
+            0
+        };
+        Self {
+            version,
+            embedding_dim,
+            num_embeddings: u64_at(8),
+            index_offset: u64_at(16),
+            data_offset: u64_at(24),
+            model_fingerprint,
+        }
+    }
+}
+
+/// Запись индекса: чей вектор и где он лежит. В формате 2 - ещё контрольная сумма вектора
+#[derive(Debug, Clone)]
+struct EmbeddingIndex {
+    session_id: Uuid,
+    turn_idx: u32,
+    offset: u64,
+    size: u32,
+    checksum: Option<u64>,
+}
+
+impl EmbeddingIndex {
+    fn to_bytes(&self) -> [u8; INDEX_V2_SIZE] {
+        let mut bytes = [0u8; INDEX_V2_SIZE];
+        let id_bytes = self.session_id.as_bytes();
+        bytes[..16].copy_from_slice(id_bytes);
+        bytes[16..20].copy_from_slice(&self.turn_idx.to_le_bytes());
+        bytes[20..28].copy_from_slice(&self.offset.to_le_bytes());
+        bytes[28..32].copy_from_slice(&self.size.to_le_bytes());
+        bytes[32..40].copy_from_slice(&self.checksum.unwrap_or_default().to_le_bytes());
+        bytes
+    }
+
+    /// `data` - запись формата 1 (32 байта) или 2 (40 байт)
+    fn from_bytes(data: &[u8]) -> Self {
+        let mut id_bytes = [0u8; 16];
+        id_bytes.copy_from_slice(&data[..16]);
+        let session_id = Uuid::from_bytes(id_bytes);
+        let turn_idx = u32::from_le_bytes([data[16], data[17], data[18], data[19]]);
+        let offset = u64::from_le_bytes([
+            data[20], data[21], data[22], data[23], data[24], data[25], data[26], data[27],
+        ]);
+        let size = u32::from_le_bytes([data[28], data[29], data[30], data[31]]);
+        let checksum = (data.len() >= INDEX_V2_SIZE)
+            .then(|| u64::from_le_bytes(data[32..40].try_into().expect("8 bytes")));
+        Self {
+            session_id,
+            turn_idx,
+            offset,
+            size,
+            checksum,
+        }
+    }
+}
+
+fn deferred_session(session: &SerializedSession) -> DeferredSession {
+    DeferredSession {
+        persona_name: session.persona_name.clone(),
+        updated_at: session.updated_at,
+        turns: session.turns.len(),
+        analysis: super::timeline::stored_analysis(&session.metadata),
+    }
+}
+
+/// Добавляет в векторное хранилище эмбеддинги ходов из сессий `session_ids`
+fn add_memory_entries(
+    manager: &mut super::DialogueManager,
+    sessions: &[SerializedSession],
+    embeddings: Vec<StoredEmbedding>,
+    session_ids: &HashSet<Uuid>,
+) -> Result<()> {
+    let by_id: HashMap<Uuid, &SerializedSession> = sessions
+        .iter()
+        .filter_map(|s| Uuid::parse_str(&s.id).ok().map(|id| (id, s)))
+        .collect();
+
+    for stored in embeddings.into_iter().filter(|e| session_ids.contains(&e.session_id)) {
+        let turn = by_id
+            .get(&stored.session_id)
+            .and_then(|s| s.turns.get(stored.turn_idx as usize));
+        let (user_query, assistant_response, language) = match turn {
+            Some(turn) => (turn.user.clone(), turn.assistant.clone(), turn.metadata.get(LANGUAGE_KEY).cloned()),
+            None => ("unknown".to_string(), "unknown".to_string(), None),
+        };
+
+        let mut memory_entry = MemoryEntry::new(
+            user_query.clone(),
+            stored.embedding,
+            MemoryType::Episodic {
+                session_id: stored.session_id,
+                turn: stored.turn_idx as usize,
+            },
+        )
+        .with_metadata("session_id".to_string(), stored.session_id.to_string())
+        .with_metadata("turn".to_string(), stored.turn_idx.to_string())
+        .with_metadata("user_query".to_string(), user_query)
+        .with_metadata("assistant_response".to_string(), assistant_response);
+        // время хода, а не загрузки: от него считается «3 weeks ago» в промпте
+        if let Some(turn) = turn {
+            memory_entry.timestamp = turn.timestamp;
+            super::restore_access(&mut memory_entry, &turn.metadata);
+        }
+        let memory_entry = match language {
+            Some(language) => memory_entry.with_metadata(LANGUAGE_KEY.to_string(), language),
+            None => memory_entry,
+        };
+
+        manager.vector_store.add(memory_entry)?;
+    }
+
+    Ok(())
+}
+
+/// Отложенные сессии и их эмбеддинги, которые сохранение переписывает как есть
+#[derive(Default)]
+struct DeferredData {
+    sessions: Vec<SerializedSession>,
+    embeddings: Vec<StoredEmbedding>,
+    style: Vec<StoredEmbedding>,
+    code: Vec<StoredEmbedding>,
+}
+
+/// Добавляет в индекс кода эмбеддинги кода ходов из сессий `session_ids`
+fn add_code_entries(
+    manager: &mut super::DialogueManager,
+    sessions: &[SerializedSession],
+    embeddings: Vec<StoredEmbedding>,
+    session_ids: &HashSet<Uuid>,
+) -> Result<()> {
+    let by_id: HashMap<Uuid, &SerializedSession> = sessions
+        .iter()
+        .filter_map(|s| Uuid::parse_str(&s.id).ok().map(|id| (id, s)))
+        .collect();
+
+    for stored in embeddings.into_iter().filter(|e| session_ids.contains(&e.session_id)) {
+        let Some(session) = by_id.get(&stored.session_id) else {
+            continue;
+        };
+        let Some(turn) = session.turns.get(stored.turn_idx as usize) else {
+            continue;
+        };
+        let Some(code) = super::code::code_for_embedding(&turn.user, &turn.assistant) else {
+            continue;
+        };
+        let mut entry = super::code::code_entry(
+            stored.session_id,
+            stored.turn_idx as usize,
+            &session.persona_name,
+            &turn.user,
+            &turn.assistant,
+            code,
+            stored.embedding,
+        );
+        entry.timestamp = turn.timestamp;
+        super::restore_access(&mut entry, &turn.metadata);
+        manager.code_store.add(entry)?;
+    }
+
+    Ok(())
+}
+
+/// Добавляет в индекс стиля эмбеддинги ответов из сессий `session_ids`
+fn add_style_entries(
+    manager: &mut super::DialogueManager,
+    sessions: &[SerializedSession],
+    embeddings: Vec<StoredEmbedding>,
+    session_ids: &HashSet<Uuid>,
+) -> Result<()> {
+    let by_id: HashMap<Uuid, &SerializedSession> = sessions
+        .iter()
+        .filter_map(|s| Uuid::parse_str(&s.id).ok().map(|id| (id, s)))
+        .collect();
+
+    for stored in embeddings.into_iter().filter(|e| session_ids.contains(&e.session_id)) {
+        let Some(session) = by_id.get(&stored.session_id) else {
+            continue;
+        };
+        let Some(turn) = session.turns.get(stored.turn_idx as usize) else {
+            continue;
+        };
+        let Some(excerpt) = super::style::style_excerpt(&turn.assistant) else {
+            continue;
+        };
+        let mut entry = super::style::style_entry(
+            stored.session_id,
+            stored.turn_idx as usize,
+            &session.persona_name,
+            excerpt,
+            stored.embedding,
+        );
+        entry.timestamp = turn.timestamp;
+        manager.style_store.add(entry)?;
+    }
+
+    Ok(())
+}
+
+/// Сессия в формате хранения (без эмбеддингов)
+pub(super) fn serialize_session(session: &super::Session) -> SerializedSession {
+    SerializedSession {
+        id: session.id.to_string(),
+        persona_name: session.persona_name.clone(),
+        turns: persisted_turns(session).map(|(_, t)| serialize_turn(t)).collect(),
+        created_at: session.created_at,
+        updated_at: session.updated_at,
+        metadata: session.metadata.clone(),
+    }
+}
+
+/// Ходы, которые пишутся на диск: без тех, что живут только в рабочей памяти
+fn persisted_turns(session: &super::Session) -> impl Iterator<Item = (usize, &super::Turn)> {
+    session
+        .turns
+        .iter()
+        .enumerate()
+        .filter(|(_, t)| !t.metadata.contains_key(EPHEMERAL_KEY))
+}
+
+fn serialize_turn(turn: &super::Turn) -> SerializedTurn {
+    SerializedTurn {
+        user: turn.user.clone(),
+        assistant: turn.assistant.clone(),
+        timestamp: turn.timestamp,
+        metadata: turn.metadata.clone(),
+        embedding: None,
+        sampling: turn.sampling.clone(),
+    }
+}
+
+pub fn create_dialogue_manager_with_sessions(
+    embedder: Arc<dyn Embedder>,
+    persona_name: String,
+    sessions: Vec<SerializedSession>,
+) -> super::DialogueManager {
+    let dimension = embedder.embedding_dim();
+
+    let mut manager = super::DialogueManager {
+        current_session: super::Session::new(persona_name.clone()),
+        vector_store: VectorStore::new(dimension),
+        style_store: VectorStore::new(dimension),
+        code_store: VectorStore::new(dimension),
+        code_embedder: None,
+        code_model: None,
+        embedder: embedder.clone(),
+        session_history: HashMap::new(),
+        max_sessions: 100,
+        archive: None,
+        deferred: HashMap::new(),
+        utc_offset: chrono::FixedOffset::east_opt(0).expect("zero offset"),
+        consent: Default::default(),
+        significance: Default::default(),
+        clock: crate::utils::clock::default_clock(),
+        recall_cache: Default::default(),
+        retrieval: Default::default(),
+        user: Default::default(),
+        segmentation: None,
+        segment_summarizer: None,
+        session_breaks: Vec::new(),
+        segment_summaries: Default::default(),
+    };
+
+    for session in sessions {
+        if session.persona_name == persona_name {
+            if let Ok(deserialized) = deserialize_session_simple(session) {
+                manager
+                    .session_history
+                    .insert(deserialized.id, deserialized);
+            }
+        } else if let Ok(id) = Uuid::parse_str(&session.id) {
+            manager.deferred.insert(id, deferred_session(&session));
+        }
+    }
+
+    manager
+}
+
+pub(super) fn deserialize_session_simple(serialized: SerializedSession) -> Result<super::Session> {
+    let id = Uuid::parse_str(&serialized.id)
+        .with_context(|| format!("Invalid session UUID: {}", serialized.id))?;
+
+    let turns: Vec<super::Turn> = serialized
+        .turns
+        .into_iter()
+        .map(|t| super::Turn {
+            user: t.user,
+            assistant: t.assistant,
+            timestamp: t.timestamp,
+            metadata: t.metadata,
+            sampling: t.sampling,
+        })
+        .collect();
+
+    Ok(super::Session {
+        id,
+        persona_name: serialized.persona_name,
+        turns,
+        created_at: serialized.created_at,
+        updated_at: serialized.updated_at,
+        metadata: serialized.metadata,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::MockEmbedder;
+
+    #[test]
+    fn test_scoped_load_keeps_deferred_sessions() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("ziggurat-scope-test-{}", Uuid::new_v4()));
+        let persistence = PersistenceManager::new(Some(&dir), false)?;
+        let embedder: Arc<dyn Embedder> = Arc::new(MockEmbedder::new(384));
+
+        let mut manager = super::super::DialogueManager::new(embedder.clone(), "programmer".to_string());
+        manager.add_exchange_blocking("How do I sort a Vec?".to_string(), "Use sort().".to_string())?;
+        manager.start_new_session("philosopher".to_string());
+        manager.add_exchange_blocking("What is virtue?".to_string(), "Knowledge.".to_string())?;
+        persistence.save_with_embeddings_blocking(&manager, 384)?;
+
+        let (mut loaded, _) = persistence
+            .load_with_embeddings_blocking(embedder.clone(), "programmer".to_string())?
+            .unwrap();
+        assert_eq!(loaded.session_history().len(), 1);
+        assert_eq!(loaded.deferred_sessions().len(), 1);
+        assert_eq!(loaded.vector_store.len(), 1);
+
+        // saving a partially loaded manager must not drop the deferred session
+        persistence.save_with_embeddings_blocking(&loaded, 384)?;
+        let (mut reloaded, _) = persistence
+            .load_scoped_blocking(embedder, "programmer".to_string(), &LoadScope::persona("programmer"))?
+            .unwrap();
+        assert_eq!(reloaded.deferred_sessions().len(), 1);
+
+        let count = persistence.load_deferred_blocking(&mut reloaded, |_, d| d.persona_name == "philosopher")?;
+        assert_eq!(count, 1);
+        assert!(reloaded.deferred_sessions().is_empty());
+        assert_eq!(reloaded.vector_store.len(), 2);
+        assert_eq!(persistence.load_deferred_blocking(&mut loaded, |_, _| true)?, 1);
+
+        assert!(LoadScope::persona("a").recent(7).includes("a", Utc::now() - chrono::Duration::days(6)));
+        assert!(!LoadScope::persona("a").recent(7).includes("a", Utc::now() - chrono::Duration::days(8)));
+        assert!(LoadScope::all().recent(0).includes("b", Utc::now() - chrono::Duration::days(800)));
+
+        let _ = fs::remove_dir_all(&dir);
+        Ok(())
+    }
+
+    #[test]
+    fn test_on_request_consent_keeps_turns_off_disk() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("ziggurat-consent-test-{}", Uuid::new_v4()));
+        let persistence = PersistenceManager::new(Some(&dir), false)?;
+        let embedder: Arc<dyn Embedder> = Arc::new(MockEmbedder::new(384));
+
+        let mut manager = super::super::DialogueManager::new(embedder.clone(), "programmer".to_string());
+        manager.set_consent_mode(crate::totems::consent::ConsentMode::OnRequest);
+        manager.add_exchange_blocking("I have a headache today".to_string(), "Rest.".to_string())?;
+        manager.add_exchange_blocking("Remember this: I use Arch".to_string(), "Noted.".to_string())?;
+        manager.add_exchange_blocking("My cat is Tom".to_string(), "Cute.".to_string())?;
+        assert_eq!(manager.current_session().turn_count(), 3);
+        assert_eq!(manager.vector_store.len(), 1);
+        assert!(manager.remember_last_turn_blocking()?);
+        assert!(!manager.remember_last_turn_blocking()?);
+        persistence.save_with_embeddings_blocking(&manager, 384)?;
+
+        let (loaded, sessions) = persistence
+            .load_with_embeddings_blocking(embedder, "programmer".to_string())?
+            .unwrap();
+        let users: Vec<&str> = sessions[0].turns.iter().map(|t| t.user.as_str()).collect();
+        assert_eq!(users, ["Remember this: I use Arch", "My cat is Tom"]);
+        // индексы эмбеддингов указывают на сохранённые ходы
+        let turns: Vec<usize> = loaded
+            .vector_store
+            .entries()
+            .filter_map(|e| match e.memory_type {
+                MemoryType::Episodic { turn, .. } => Some(turn),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(turns.len(), 2);
+        assert!(turns.contains(&0) && turns.contains(&1));
+
+        let _ = fs::remove_dir_all(&dir);
+        Ok(())
+    }
+
+    #[test]
+    fn test_on_request_consent_keeps_turns_out_of_summaries() -> Result<()> {
+        use crate::testing::MockLlmPipeline;
+        use crate::totems::episodic::summaries::{run_summary_tasks, SummaryStore};
+
+        let dir = std::env::temp_dir().join(format!("ziggurat-consent-summary-test-{}", Uuid::new_v4()));
+        let persistence = PersistenceManager::new(Some(&dir), false)?;
+        let embedder: Arc<dyn Embedder> = Arc::new(MockEmbedder::new(384));
+        let mut manager = super::super::DialogueManager::new(embedder, "programmer".to_string());
+        manager.set_consent_mode(crate::totems::consent::ConsentMode::OnRequest);
+        manager.add_exchange_blocking("I have a headache today".to_string(), "Rest.".to_string())?;
+        manager.add_exchange_blocking("Remember this: I use Arch".to_string(), "Noted.".to_string())?;
+
+        let llm = MockLlmPipeline::new("rust").with_reply("Темы:", "[\"arch\"]").with_reply("Число:", "0.6");
+        let store = std::sync::Mutex::new(SummaryStore::open(persistence.memory_dir())?);
+        let tasks = store.lock().unwrap().plan([manager.current_session()], None, Utc::now());
+        assert_eq!(run_summary_tasks(&store, &tasks, &llm)?, 1);
+        assert_eq!(store.lock().unwrap().current_session_summary(&manager.current_session().id, 1), Some("rust"));
+
+        let analysis = manager.analyze_for_context_with_summary(&llm, 10, None)?;
+        manager.record_analysis(&analysis);
+        assert!(!llm.prompts().is_empty());
+        assert!(llm.prompts().iter().all(|p| !p.contains("headache") && p.contains("Arch")));
+
+        let _ = fs::remove_dir_all(&dir);
+        Ok(())
+    }
+
+    #[test]
+    fn test_embeddings_fingerprint_and_checksums() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("ziggurat-checksum-test-{}", Uuid::new_v4()));
+        let persistence = PersistenceManager::new(Some(&dir), false)?.with_embedding_model("multilingual-e5-small");
+        let embedder: Arc<dyn Embedder> = Arc::new(MockEmbedder::new(384));
+
+        let mut manager = super::super::DialogueManager::new(embedder.clone(), "programmer".to_string());
+        manager.add_exchange_blocking("How do I sort a Vec?".to_string(), "Use sort().".to_string())?;
+        manager.add_exchange_blocking("And in reverse?".to_string(), "sort_by with b.cmp(a).".to_string())?;
+        persistence.save_with_embeddings_blocking(&manager, 384)?;
+        let load = |p: &PersistenceManager| p.load_with_embeddings_blocking(embedder.clone(), "programmer".to_string());
+        assert_eq!(load(&persistence)?.unwrap().0.vector_store.len(), 2);
+
+        // vectors of another model are refused instead of polluting search
+        let other = PersistenceManager::new(Some(&dir), false)?.with_embedding_model("all-MiniLM-L6-v2");
+        let err = load(&other).err().expect("model mismatch");
+        assert!(matches!(
+            err.downcast_ref::<EmbeddingsIntegrityError>(),
+            Some(EmbeddingsIntegrityError::ModelMismatch { .. })
+        ));
+        // a manager that does not know its model accepts any vectors
+        assert!(load(&PersistenceManager::new(Some(&dir), false)?).is_ok());
+
+        // a flipped bit in the last vector
+        let path = persistence.embeddings_path();
+        let mut bytes = fs::read(&path)?;
+        let last = bytes.len() - 1;
+        bytes[last] ^= 0x01;
+        fs::write(&path, bytes)?;
+        let err = load(&persistence).err().expect("checksum mismatch");
+        assert_eq!(
+            err.downcast_ref::<EmbeddingsIntegrityError>(),
+            Some(&EmbeddingsIntegrityError::Corrupted { path, bad: 1, total: 2 })
+        );
+
+        let _ = fs::remove_dir_all(&dir);
+        Ok(())
+    }
+
+    #[cfg(feature = "encryption")]
+    #[test]
+    fn test_encrypted_store_loads_and_migrates() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("ziggurat-crypto-test-{}", Uuid::new_v4()));
+        let key = MemoryKey::from_secret("correct horse battery staple")?;
+        let plain = PersistenceManager::new(Some(&dir), false)?.with_embedding_model("multilingual-e5-small");
+        let sealed = PersistenceManager::new(Some(&dir), false)?
+            .with_embedding_model("multilingual-e5-small")
+            .with_encryption(Some(key));
+        let embedder: Arc<dyn Embedder> = Arc::new(MockEmbedder::new(384));
+        let mut manager = super::super::DialogueManager::new(embedder.clone(), "programmer".to_string());
+        manager.add_exchange_blocking("My cat is Tom".to_string(), "Cute.".to_string())?;
+        manager.add_exchange_blocking("I use Arch".to_string(), "Noted.".to_string())?;
+        plain.save_with_embeddings_blocking(&manager, 384)?;
+        let load = |p: &PersistenceManager| p.load_with_embeddings_blocking(embedder.clone(), "programmer".to_string());
+
+        // an existing plaintext store loads with the key, then gets migrated
+        assert_eq!(load(&sealed)?.unwrap().0.vector_store.len(), 2);
+        assert_eq!(sealed.reseal(true)?, 4);
+        assert_eq!(sealed.reseal(true)?, 0);
+        let on_disk = fs::read(plain.sessions_path())?;
+        assert!(crypto::is_sealed(&on_disk) && !on_disk.windows(3).any(|w| w == b"Tom"));
+        assert!(crypto::is_sealed(&fs::read(plain.embeddings_path())?));
+
+        let err = load(&plain).err().unwrap();
+        assert_eq!(err.downcast_ref::<crypto::DecryptError>(), Some(&crypto::DecryptError::NoKey));
+        assert_eq!(load(&sealed)?.unwrap().0.vector_store.len(), 2);
+        assert_eq!(sealed.load_sessions()?.unwrap()[0].turns[0].user, "My cat is Tom");
+        assert_eq!(sealed.embedding_mismatch(384)?, None);
+        sealed.save_with_embeddings_blocking(&manager, 384)?;
+        assert!(crypto::is_sealed(&fs::read(plain.sessions_path())?));
+        assert!(plain.reseal(false).is_err());
+
+        assert_eq!(sealed.reseal(false)?, 4);
+        assert_eq!(load(&plain)?.unwrap().0.vector_store.len(), 2);
+
+        let _ = fs::remove_dir_all(&dir);
+        Ok(())
+    }
+
+    #[cfg(feature = "encryption")]
+    #[test]
+    fn test_no_plaintext_left_after_encrypt_memory() -> Result<()> {
+        use crate::totems::episodic::{archive::SessionArchive, summaries::SummaryStore};
+        use crate::totems::semantic::concept::{Concept, ConceptCategory};
+        use crate::totems::semantic::persistence::SemanticPersistenceManager;
+        use crate::totems::semantic::provenance::{AuditAction, AuditEntry};
+
+        let dir = std::env::temp_dir().join(format!("ziggurat-crypto-stores-test-{}", Uuid::new_v4()));
+        let key = MemoryKey::from_secret("correct horse battery staple")?;
+        let plain = PersistenceManager::new(Some(&dir), false)?.with_encryption(None);
+        let embedder: Arc<dyn Embedder> = Arc::new(MockEmbedder::new(384));
+        let mut manager = super::super::DialogueManager::new(embedder, "programmer".to_string());
+        manager.add_exchange_blocking("My cat is Tom".to_string(), "Cute, Tom.".to_string())?;
+        manager.add_exchange_blocking("Tom likes tea".to_string(), "Noted.".to_string())?;
+        manager.record_analysis(&super::super::SessionAnalysis {
+            summary: "Talked about Tom".to_string(),
+            key_topics: vec!["Tom".to_string()],
+            emotional_state: 0.5,
+            last_topic: "Tom".to_string(),
+            turn_count: 2,
+        });
+        manager.current_session.metadata.insert(super::super::bookmarks::bookmark_key(0), "Tom".to_string());
+        plain.save_with_embeddings_blocking(&manager, 384)?;
+
+        // every store that holds conversation text, written in the clear
+        let mut summaries = SummaryStore::open_with_encryption(plain.memory_dir(), None)?;
+        let now = Utc::now();
+        for task in summaries.plan([&manager.current_session], None, now) {
+            summaries.apply(&task, "The user's cat Tom likes tea", now);
+        }
+        summaries.save()?;
+        let session = plain.load_sessions()?.unwrap().remove(0);
+        SessionArchive::open(plain.memory_dir()).with_encryption(None).add(&session)?;
+        let semantic = SemanticPersistenceManager::new(Some(&dir.join("semantic")))?.with_encryption(None);
+        let concept = Concept::new("User's cat is Tom".to_string(), ConceptCategory::Facts, "test".to_string());
+        semantic.audit_log().append(&[AuditEntry::new(AuditAction::Created, &concept, "test", now)])?;
+
+        let files_with_tom = || -> Result<Vec<PathBuf>> {
+            let mut found = Vec::new();
+            let mut dirs = vec![dir.clone()];
+            while let Some(d) = dirs.pop() {
+                for entry in fs::read_dir(d)? {
+                    let path = entry?.path();
+                    if path.is_dir() {
+                        dirs.push(path);
+                    } else if fs::read(&path)?.windows(3).any(|w| w == b"Tom") {
+                        found.push(path);
+                    }
+                }
+            }
+            found.sort();
+            Ok(found)
+        };
+        assert_eq!(files_with_tom()?.len(), 5, "{:?}", files_with_tom()?);
+
+        // what `encrypt-memory` does
+        PersistenceManager::new(Some(&dir), false)?.with_encryption(Some(key.clone())).reseal(true)?;
+        let semantic = semantic.with_encryption(Some(key.clone()));
+        semantic.reseal(true)?;
+        assert_eq!(files_with_tom()?, Vec::<PathBuf>::new());
+
+        // and everything still reads back with the key
+        let memory_dir = plain.memory_dir();
+        let summaries = SummaryStore::open_with_encryption(memory_dir, Some(key.clone()))?;
+        assert_eq!(summaries.counts().1, 1);
+        let mut archive = SessionArchive::open(memory_dir).with_encryption(Some(key.clone()));
+        assert_eq!(archive.open_session(&session.id)?.turns[0].user, "My cat is Tom");
+        assert_eq!(semantic.audit_log().entries()?[0].text, "User's cat is Tom");
+        assert!(SessionArchive::open(memory_dir).with_encryption(None).open_session(&session.id).is_err());
+
+        let _ = fs::remove_dir_all(&dir);
+        Ok(())
+    }
+
+    #[test]
+    fn test_model_switch_detected_and_reembedded() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("ziggurat-reembed-test-{}", Uuid::new_v4()));
+        let old = PersistenceManager::new(Some(&dir), false)?.with_embedding_model("multilingual-e5-small");
+        let embedder: Arc<dyn Embedder> = Arc::new(MockEmbedder::new(384));
+        let mut manager = super::super::DialogueManager::new(embedder.clone(), "programmer".to_string());
+        manager.add_exchange_blocking("How do I sort a Vec?".to_string(), "Use sort().".to_string())?;
+        manager.add_exchange_blocking("And in reverse?".to_string(), "sort_by with b.cmp(a).".to_string())?;
+        manager.start_new_session("philosopher".to_string());
+        manager.add_exchange_blocking("What is virtue?".to_string(), "Knowledge.".to_string())?;
+        old.save_with_embeddings_blocking(&manager, 384)?;
+        assert_eq!(old.embedding_mismatch(384)?, None);
+
+        let new = PersistenceManager::new(Some(&dir), false)?.with_embedding_model("bge-m3");
+        let bigger: Arc<dyn Embedder> = Arc::new(MockEmbedder::new(512));
+        let mismatch = new.embedding_mismatch(512)?.expect("model switch");
+        assert_eq!(mismatch.stored_model.as_deref(), Some("multilingual-e5-small"));
+        assert_eq!((mismatch.stored_dim, mismatch.current_dim), (384, 512));
+        let err = new.load_with_embeddings_blocking(bigger.clone(), "programmer".to_string()).err().unwrap();
+        assert_eq!(
+            err.downcast_ref::<EmbeddingsIntegrityError>(),
+            Some(&EmbeddingsIntegrityError::DimensionMismatch { stored: 384, current: 512 })
+        );
+
+        let mut seen = Vec::new();
+        let report = new.reembed_blocking(bigger.clone(), |done, total| seen.push((done, total)))?;
+        assert_eq!(report, ReembedReport { sessions: 2, vectors: 3 });
+        assert_eq!(seen, vec![(1, 2), (2, 2)]);
+        assert_eq!(new.embedding_mismatch(512)?, None);
+        assert_eq!(new.get_stats()?.total_sessions, 2);
+        let (loaded, _) = new.load_with_embeddings_blocking(bigger, "programmer".to_string())?.unwrap();
+        assert_eq!(loaded.vector_store.len(), 2);
+        assert_eq!(loaded.deferred_sessions().len(), 1);
+        assert!(old.embedding_mismatch(384)?.is_some());
+
+        let _ = fs::remove_dir_all(&dir);
+        Ok(())
+    }
+}
@@ -0,0 +1,159 @@
+//! ✂️ Автоматическая нарезка разговора на сессии
+//!
+//! Раньше сессия менялась только с рестартом процесса или вызовом
+//! [`super::DialogueManager::start_new_session`]. С [`SegmentationConfig`] менеджер
+//! сам закрывает текущую сессию, когда разговор ушёл на другую тему (косинусное
+//! расстояние между новым вопросом и последним проиндексированным ходом больше
+//! `drift_threshold`) или простоял дольше `idle_minutes`. Закрытая сессия получает
+//! причину в метаданных, новый ход уже идёт в новую сессию той же персоны. Разбор
+//! закрытой сессии для ленты (если менеджеру дан суммаризатор) считается фоновой
+//! задачей из [`super::DialogueManager::segment_summary_job`], ход LLM не ждёт.
+
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::totems::retrieval::vector_store::cosine_similarity;
+
+/// Ключ причины закрытия в метаданных сессии
+pub const CLOSED_BY_KEY: &str = "closed_by";
+/// Сколько последних ходов закрытой сессии смотрит её разбор
+pub const SUMMARY_TURNS: usize = 10;
+
+/// Когда разговор делится на сессии; раздел `segmentation` в `--system-config`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SegmentationConfig {
+    pub enabled: bool,
+    /// Косинусное расстояние (1 - сходство) между соседними ходами, с которого тема считается новой
+    pub drift_threshold: f32,
+    /// Простой в минутах, после которого разговор начинается в новой сессии (0 - не смотреть)
+    pub idle_minutes: u64,
+    /// Сессия короче этого не закрывается из-за смены темы и не разбирается при закрытии
+    pub min_turns: usize,
+}
+
+impl Default for SegmentationConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            drift_threshold: 0.35,
+            idle_minutes: 360,
+            min_turns: 4,
+        }
+    }
+}
+
+impl SegmentationConfig {
+    /// Причина закрыть сессию, последний ход которой был в `last`
+    pub fn idle_reason(&self, last: DateTime<Utc>, now: DateTime<Utc>) -> Option<SegmentReason> {
+        let idle = now - last;
+        (self.idle_minutes > 0 && idle >= Duration::minutes(self.idle_minutes as i64))
+            .then(|| SegmentReason::Idle { minutes: idle.num_minutes() })
+    }
+
+    /// Причина закрыть сессию из `turns` ходов: вопрос `next` далеко от прошлого хода `previous`
+    pub fn drift_reason(&self, previous: &[f32], next: &[f32], turns: usize) -> Option<SegmentReason> {
+        if turns < self.min_turns {
+            return None;
+        }
+        let distance = 1.0 - cosine_similarity(previous, next);
+        (distance > self.drift_threshold).then_some(SegmentReason::TopicDrift { distance })
+    }
+}
+
+/// Почему сессия закрыта
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SegmentReason {
+    /// Новый вопрос далеко от прошлого хода
+    TopicDrift { distance: f32 },
+    /// Разговор простоял
+    Idle { minutes: i64 },
+}
+
+impl std::fmt::Display for SegmentReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SegmentReason::TopicDrift { distance } => write!(f, "topic changed (distance {:.2})", distance),
+            SegmentReason::Idle { minutes } if *minutes >= 120 => write!(f, "idle for {}h", minutes / 60),
+            SegmentReason::Idle { minutes } => write!(f, "idle for {} min", minutes),
+        }
+    }
+}
+
+/// Смена сессии, которую менеджер сделал сам
+#[derive(Debug, Clone, PartialEq)]
+pub struct SessionBreak {
+    pub closed: Uuid,
+    pub opened: Uuid,
+    pub reason: SegmentReason,
+}
+
+#[cfg(all(test, feature = "runtime"))]
+mod tests {
+    use super::*;
+    use crate::priests::embeddings::Embedder;
+    use crate::testing::{MockEmbedder, MockLlmPipeline};
+    use crate::totems::episodic::{timeline, DialogueManager};
+    use crate::utils::clock::MockClock;
+    use anyhow::Result;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_drift_and_idle_open_new_sessions() -> Result<()> {
+        let embedder: Arc<dyn Embedder> = Arc::new(MockEmbedder::default());
+        let clock = Arc::new(MockClock::new(Utc::now()));
+        let mut manager = DialogueManager::new(embedder, "programmer".to_string());
+        manager.set_clock(clock.clone());
+        let config = SegmentationConfig {
+            min_turns: 2,
+            ..Default::default()
+        };
+        let summarizer = MockLlmPipeline::new("rust")
+            .with_reply("Краткое содержание", "Sorting vectors in Rust")
+            .with_reply("Темы:", "[\"rust\"]")
+            .with_reply("Число:", "0.6");
+        manager.set_segmentation(Some(config), Some(Arc::new(summarizer)));
+
+        manager.add_exchange_blocking("How do I sort a Rust vector".to_string(), "Use sort().".to_string())?;
+        manager.add_exchange_blocking("How do I sort a Rust vector in reverse".to_string(), "sort_by".to_string())?;
+        manager.add_exchange_blocking("How do I sort a Rust vector by key".to_string(), "sort_by_key".to_string())?;
+        assert!(manager.take_session_breaks().is_empty(), "same topic stays in one session");
+
+        let first = manager.current_session().id;
+        manager.add_exchange_blocking("Recommend a good sourdough bread recipe".to_string(), "Flour, water, salt.".to_string())?;
+        let breaks = manager.take_session_breaks();
+        assert_eq!(breaks.len(), 1);
+        assert!(matches!(breaks[0].reason, SegmentReason::TopicDrift { distance } if distance > 0.35));
+        assert_eq!((breaks[0].closed, breaks[0].opened), (first, manager.current_session().id));
+        assert_eq!(manager.current_session().turn_count(), 1);
+
+        let closed = &manager.session_history()[&first];
+        assert_eq!(closed.turn_count(), 3);
+        assert!(closed.metadata[CLOSED_BY_KEY].starts_with("topic changed"));
+        // the summary is a background job, the turn did not wait for it
+        assert!(timeline::stored_analysis(&closed.metadata).is_none());
+        let job = manager.segment_summary_job(&breaks[0]).expect("a long session is summarized");
+        job()?;
+        assert_eq!(manager.apply_segment_summaries(), 1);
+        let analysis = timeline::stored_analysis(&manager.session_history()[&first].metadata).unwrap();
+        assert_eq!(analysis.summary, "Sorting vectors in Rust");
+
+        // a short session is not summarized, but a long pause still splits it
+        clock.advance(Duration::hours(7));
+        manager.add_exchange_blocking("Recommend a good sourdough bread recipe again".to_string(), "Same.".to_string())?;
+        let breaks = manager.take_session_breaks();
+        assert_eq!(breaks.len(), 1);
+        assert_eq!(breaks[0].reason.to_string(), "idle for 7h");
+        assert!(manager.segment_summary_job(&breaks[0]).is_none());
+
+        // a session resumed after a long pause is idle only from the resume
+        let resumed = breaks[0].closed;
+        clock.advance(Duration::hours(8));
+        assert!(manager.resume_session(resumed));
+        manager.add_exchange_blocking("And a rye sourdough bread recipe".to_string(), "Rye flour.".to_string())?;
+        assert!(manager.take_session_breaks().is_empty());
+        assert_eq!((manager.current_session().id, manager.current_session().turn_count()), (resumed, 2));
+        Ok(())
+    }
+}
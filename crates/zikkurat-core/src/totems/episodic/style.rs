@@ -0,0 +1,94 @@
+//! 🎨 Память стиля - прошлые формулировки персоны
+//!
+//! Векторный индекс эпизодов ищет по вопросам пользователя, чтобы вспомнить факты.
+//! Здесь отдельный индекс по ответам самой персоны: по теме запроса находится, как
+//! персона уже объясняла похожее, и это попадает в небольшую секцию STYLE MEMORY.
+//! Тогда термины, аналогии и тон не меняются от сессии к сессии.
+
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+
+use crate::totems::retrieval::{MemoryEntry, MemoryType};
+
+/// Ответы короче этого не индексируются: «Пожалуйста!» ничего не говорит о стиле
+pub const MIN_STYLE_CHARS: usize = 40;
+/// Сколько символов ответа идёт в индекс и в промпт
+pub const STYLE_EXCERPT_CHARS: usize = 280;
+/// Ниже этого сходства прошлый ответ считается другой темой
+pub const STYLE_MIN_SIMILARITY: f32 = 0.5;
+
+/// Прошлая формулировка персоны, найденная по теме запроса
+#[derive(Debug, Clone, PartialEq)]
+pub struct Phrasing {
+    pub similarity: f32,
+    pub excerpt: String,
+    pub timestamp: DateTime<Utc>,
+}
+
+/// Фрагмент ответа для индекса стиля: начало ответа до первого блока кода,
+/// обрезанное по границе предложения. `None` для коротких ответов
+pub fn style_excerpt(response: &str) -> Option<String> {
+    let prose = response.split("```").next().unwrap_or_default();
+    let prose = prose.split_whitespace().collect::<Vec<_>>().join(" ");
+    if prose.chars().count() < MIN_STYLE_CHARS {
+        return None;
+    }
+    let Some((cut, _)) = prose.char_indices().nth(STYLE_EXCERPT_CHARS) else {
+        return Some(prose);
+    };
+    let head = &prose[..cut];
+    let excerpt = match head.rfind(['.', '!', '?']) {
+        Some(end) if end >= MIN_STYLE_CHARS => head[..=end].to_string(),
+        _ => format!("{}...", head.rsplit_once(' ').map_or(head, |(h, _)| h)),
+    };
+    Some(excerpt)
+}
+
+/// Запись индекса стиля для хода `turn` сессии `session_id`
+pub(super) fn style_entry(
+    session_id: Uuid,
+    turn: usize,
+    persona: &str,
+    excerpt: String,
+    embedding: Vec<f32>,
+) -> MemoryEntry {
+    MemoryEntry::new(excerpt, embedding, MemoryType::Episodic { session_id, turn })
+        .with_metadata("session_id".to_string(), session_id.to_string())
+        .with_metadata("turn".to_string(), turn.to_string())
+        .with_metadata("persona".to_string(), persona.to_string())
+}
+
+/// Секция промпта STYLE MEMORY; пустая строка, если ничего не нашлось
+pub fn format_style_memory(phrasings: &[Phrasing]) -> String {
+    phrasings
+        .iter()
+        .map(|p| format!("- «{}»", p.excerpt))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_style_excerpt() {
+        assert_eq!(style_excerpt("Пожалуйста!"), None);
+        assert_eq!(style_excerpt("```rust\nfn main() {}\n```\nThat is all the code you need here."), None);
+
+        let short = "Borrowing is like lending a book:   you still own it,\nbut someone reads it.";
+        assert_eq!(
+            style_excerpt(short).as_deref(),
+            Some("Borrowing is like lending a book: you still own it, but someone reads it.")
+        );
+
+        let long = "Think of ownership as a library card. ".repeat(20);
+        let excerpt = style_excerpt(&long).unwrap();
+        assert!(excerpt.chars().count() <= STYLE_EXCERPT_CHARS);
+        assert!(excerpt.ends_with("card."));
+
+        let phrasings = [Phrasing { similarity: 0.8, excerpt: "Like a library card.".to_string(), timestamp: Utc::now() }];
+        assert_eq!(format_style_memory(&phrasings), "- «Like a library card.»");
+        assert_eq!(format_style_memory(&[]), "");
+    }
+}
@@ -0,0 +1,442 @@
+//! 📝 Многоуровневые сводки сессий
+//!
+//! Сводки составляются постепенно, по мере накопления ходов: по каждым
+//! [`CHUNK_TURNS`] ходам, по сессии целиком (из сводок её кусков и последних
+//! реплик) и дайджест недели (из сводок её сессий). Хранятся они в
+//! `memory_data/summaries.json` рядом с `sessions.json` (с ключом памяти -
+//! зашифрованными, как и он). Сборка контекста и
+//! приветствие после перерыва берут готовую сводку нужной подробности
+//! ([`SummaryLevel::for_gap`]), а не суммаризируют заново.
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Datelike, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use uuid::Uuid;
+
+use super::{LlmPipeline, Session, Turn};
+use crate::utils::crypto::{self, MemoryKey};
+
+/// Ходов в одном куске
+pub const CHUNK_TURNS: usize = 10;
+/// Задач за один проход: догоняющая суммаризация большой истории растягивается на несколько ходов
+pub const MAX_TASKS_PER_PASS: usize = 4;
+pub const SUMMARIES_FILE: &str = "summaries.json";
+const SUMMARY_MAX_TOKENS: usize = 200;
+/// Символов реплики в промпте суммаризации
+const TURN_CHARS: usize = 300;
+
+/// Подробность сводки
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SummaryLevel {
+    /// [`CHUNK_TURNS`] ходов
+    Chunk,
+    /// Сессия целиком
+    Session,
+    /// Все сессии недели
+    Week,
+}
+
+impl SummaryLevel {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            SummaryLevel::Chunk => "chunk",
+            SummaryLevel::Session => "session",
+            SummaryLevel::Week => "week",
+        }
+    }
+
+    /// Подробность для перерыва такой длины: после нескольких часов - последний кусок,
+    /// на этой неделе - сессия целиком, дольше - дайджест недели
+    pub fn for_gap(gap: Duration) -> Self {
+        if gap < Duration::days(1) {
+            SummaryLevel::Chunk
+        } else if gap < Duration::days(7) {
+            SummaryLevel::Session
+        } else {
+            SummaryLevel::Week
+        }
+    }
+
+    /// Уровни по убыванию пригодности, когда нужен этот
+    fn fallbacks(self) -> [SummaryLevel; 3] {
+        use SummaryLevel::*;
+        match self {
+            Chunk => [Chunk, Session, Week],
+            Session => [Session, Chunk, Week],
+            Week => [Week, Session, Chunk],
+        }
+    }
+}
+
+/// Сводки одной сессии
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SessionSummaries {
+    /// Сводки по [`CHUNK_TURNS`] ходов, по порядку
+    #[serde(default)]
+    pub chunks: Vec<String>,
+    /// Сводка всей сессии, пустая - ещё не составлена
+    #[serde(default)]
+    pub session: String,
+    /// Ходов в сессии, когда составлена `session`
+    #[serde(default)]
+    pub session_turns: usize,
+    /// Время последнего хода сессии
+    pub last_turn_at: DateTime<Utc>,
+}
+
+/// Дайджест недели
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WeekDigest {
+    pub text: String,
+    /// Сессии, по сводкам которых составлен
+    pub sessions: Vec<Uuid>,
+}
+
+/// Что суммаризировать; тексты кусков снимаются при планировании, сводки
+/// предыдущих уровней читаются из хранилища при выполнении
+#[derive(Debug, Clone, PartialEq)]
+pub enum SummaryTask {
+    Chunk { session: Uuid, index: usize, dialogue: String },
+    Session { session: Uuid, turns: usize, last_turn_at: DateTime<Utc>, tail: String },
+    Week { week: String, sessions: Vec<Uuid> },
+}
+
+impl SummaryTask {
+    pub fn level(&self) -> SummaryLevel {
+        match self {
+            SummaryTask::Chunk { .. } => SummaryLevel::Chunk,
+            SummaryTask::Session { .. } => SummaryLevel::Session,
+            SummaryTask::Week { .. } => SummaryLevel::Week,
+        }
+    }
+}
+
+/// ISO-неделя вида `2026-W42`
+pub fn week_key(time: DateTime<Utc>) -> String {
+    let week = time.iso_week();
+    format!("{}-W{:02}", week.year(), week.week())
+}
+
+fn clip(text: &str, max_chars: usize) -> String {
+    match text.char_indices().nth(max_chars) {
+        Some((end, _)) => format!("{}…", &text[..end]),
+        None => text.to_string(),
+    }
+}
+
+fn format_turns(turns: &[&Turn]) -> String {
+    turns
+        .iter()
+        .map(|t| format!("User: {}\nAssistant: {}", clip(&t.user, TURN_CHARS), clip(&t.assistant, TURN_CHARS)))
+        .collect::<Vec<_>>()
+        .join("\n---\n")
+}
+
+/// Сводки всех сессий и недель
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct SummaryStore {
+    #[serde(default)]
+    sessions: HashMap<Uuid, SessionSummaries>,
+    /// По ключу [`week_key`]
+    #[serde(default)]
+    weeks: BTreeMap<String, WeekDigest>,
+    /// Файл хранилища; `None` - только в памяти (read-only)
+    #[serde(skip)]
+    path: Option<PathBuf>,
+    #[serde(skip)]
+    key: Option<MemoryKey>,
+}
+
+impl SummaryStore {
+    /// Хранилище в `memory_dir/summaries.json`; файла нет - пустое
+    pub fn open(memory_dir: &Path) -> Result<Self> {
+        Self::open_with_encryption(memory_dir, crypto::installed())
+    }
+
+    /// [`SummaryStore::open`] с ключом шифрования вместо поставленного [`crypto::install`]
+    pub fn open_with_encryption(memory_dir: &Path, key: Option<MemoryKey>) -> Result<Self> {
+        let path = memory_dir.join(SUMMARIES_FILE);
+        let mut store: SummaryStore = if path.exists() {
+            let content = crypto::unseal_string(key.as_ref(), std::fs::read(&path)?)
+                .with_context(|| format!("Failed to decrypt summaries {:?}", path))?;
+            serde_json::from_str(&content).with_context(|| format!("Failed to parse summaries {:?}", path))?
+        } else {
+            SummaryStore::default()
+        };
+        store.path = Some(path);
+        store.key = key;
+        Ok(store)
+    }
+
+    /// Не пишет на диск
+    pub fn read_only(mut self) -> Self {
+        self.path = None;
+        self
+    }
+
+    pub fn save(&self) -> Result<()> {
+        if let Some(path) = &self.path {
+            std::fs::write(path, crypto::seal(self.key.as_ref(), serde_json::to_string_pretty(self)?)?)
+                .with_context(|| format!("Failed to write summaries {:?}", path))?;
+        }
+        Ok(())
+    }
+
+    pub fn session(&self, id: &Uuid) -> Option<&SessionSummaries> {
+        self.sessions.get(id)
+    }
+
+    /// Сводка сессии, если она составлена по всем `turns` ходам
+    pub fn current_session_summary(&self, id: &Uuid, turns: usize) -> Option<&str> {
+        self.sessions
+            .get(id)
+            .filter(|s| !s.session.is_empty() && s.session_turns == turns)
+            .map(|s| s.session.as_str())
+    }
+
+    pub fn week(&self, key: &str) -> Option<&WeekDigest> {
+        self.weeks.get(key)
+    }
+
+    /// Число сводок по уровням: (куски, сессии, недели)
+    pub fn counts(&self) -> (usize, usize, usize) {
+        let chunks = self.sessions.values().map(|s| s.chunks.len()).sum();
+        let sessions = self.sessions.values().filter(|s| !s.session.is_empty()).count();
+        (chunks, sessions, self.weeks.len())
+    }
+
+    /// Чего не хватает: новые куски, устаревшие сводки сессий, дайджесты прошедших
+    /// недель. Текущая сессия пересуммаризируется на границах кусков, законченные -
+    /// когда в них прибавилось ходов. Не больше [`MAX_TASKS_PER_PASS`] задач.
+    /// Считаются и пересказываются только [`Session::durable_turns`]: ходы без согласия
+    /// в сводки не попадают
+    pub fn plan<'a>(
+        &self,
+        sessions: impl IntoIterator<Item = &'a Session>,
+        current: Option<Uuid>,
+        now: DateTime<Utc>,
+    ) -> Vec<SummaryTask> {
+        let mut tasks = Vec::new();
+        let mut weeks: BTreeMap<String, Vec<Uuid>> = BTreeMap::new();
+        let mut turn_counts: HashMap<Uuid, usize> = HashMap::new();
+        for session in sessions {
+            let durable: Vec<&Turn> = session.durable_turns().collect();
+            let turns = durable.len();
+            if turns == 0 {
+                continue;
+            }
+            let stored = self.sessions.get(&session.id);
+            let done_chunks = stored.map_or(0, |s| s.chunks.len());
+            for index in done_chunks..turns / CHUNK_TURNS {
+                let chunk = &durable[index * CHUNK_TURNS..(index + 1) * CHUNK_TURNS];
+                tasks.push(SummaryTask::Chunk { session: session.id, index, dialogue: format_turns(chunk) });
+            }
+            let finished = current != Some(session.id);
+            let stale = stored.is_none_or(|s| s.session_turns != turns);
+            if stale && (finished || turns.is_multiple_of(CHUNK_TURNS)) {
+                tasks.push(SummaryTask::Session {
+                    session: session.id,
+                    turns,
+                    last_turn_at: session.updated_at,
+                    tail: format_turns(&durable[turns / CHUNK_TURNS * CHUNK_TURNS..]),
+                });
+            }
+            if finished {
+                weeks.entry(week_key(session.updated_at)).or_default().push(session.id);
+                turn_counts.insert(session.id, turns);
+            }
+        }
+
+        // неделя подводится, когда закончилась и все её сессии уже просуммированы
+        let this_week = week_key(now);
+        for (week, mut ids) in weeks.into_iter().filter(|(week, _)| *week != this_week) {
+            ids.sort();
+            let ready = ids.iter().all(|id| self.current_session_summary(id, turn_counts[id]).is_some());
+            if ready && self.weeks.get(&week).is_none_or(|d| d.sessions != ids) {
+                tasks.push(SummaryTask::Week { week, sessions: ids });
+            }
+        }
+        tasks.truncate(MAX_TASKS_PER_PASS);
+        tasks
+    }
+
+    /// Промпт задачи; `None`, если суммаризировать нечего
+    pub fn prompt(&self, task: &SummaryTask) -> Option<String> {
+        let (instruction, material) = match task {
+            SummaryTask::Chunk { dialogue, .. } => {
+                ("Кратко перескажи этот отрывок диалога (1-2 предложения на русском).", dialogue.clone())
+            }
+            SummaryTask::Session { session, tail, .. } => {
+                let chunks = self.sessions.get(session).map(|s| s.chunks.as_slice()).unwrap_or_default();
+                let mut parts: Vec<String> = chunks.iter().map(|c| format!("- {}", c)).collect();
+                if !tail.is_empty() {
+                    parts.push(tail.clone());
+                }
+                ("Кратко опиши, о чём был разговор (2-3 предложения на русском).", parts.join("\n"))
+            }
+            SummaryTask::Week { sessions, .. } => {
+                let parts: Vec<String> = sessions
+                    .iter()
+                    .filter_map(|id| self.sessions.get(id))
+                    .filter(|s| !s.session.is_empty())
+                    .map(|s| format!("- {}", s.session))
+                    .collect();
+                ("Составь дайджест недели по сводкам разговоров (3-4 предложения на русском).", parts.join("\n"))
+            }
+        };
+        if material.trim().is_empty() {
+            return None;
+        }
+        Some(format!(
+            "<s>[INST] Ты — ассистент по анализу диалогов. {}\n\n{}\n\nКраткое содержание:[/INST]",
+            instruction, material
+        ))
+    }
+
+    /// Записывает сводку задачи
+    pub fn apply(&mut self, task: &SummaryTask, text: &str, now: DateTime<Utc>) {
+        let text = text.trim().to_string();
+        if text.is_empty() {
+            return;
+        }
+        match task {
+            SummaryTask::Chunk { session, index, .. } => {
+                let entry = self.sessions.entry(*session).or_insert_with(|| SessionSummaries {
+                    last_turn_at: now,
+                    ..Default::default()
+                });
+                // повторная задача по тому же куску заменяет сводку, пропуск кусков не допускается
+                match entry.chunks.len().cmp(index) {
+                    std::cmp::Ordering::Equal => entry.chunks.push(text),
+                    std::cmp::Ordering::Greater => entry.chunks[*index] = text,
+                    std::cmp::Ordering::Less => {}
+                }
+            }
+            SummaryTask::Session { session, turns, last_turn_at, .. } => {
+                let entry = self.sessions.entry(*session).or_insert_with(|| SessionSummaries {
+                    last_turn_at: *last_turn_at,
+                    ..Default::default()
+                });
+                entry.session = text;
+                entry.session_turns = *turns;
+                entry.last_turn_at = *last_turn_at;
+            }
+            SummaryTask::Week { week, sessions } => {
+                self.weeks.insert(week.clone(), WeekDigest { text, sessions: sessions.clone() });
+            }
+        }
+    }
+
+    /// Сводка для приветствия после перерыва `gap` с последней сессии: уровень по
+    /// [`SummaryLevel::for_gap`], а если его нет - ближайший составленный
+    pub fn catch_up(&self, last_session: &Uuid, gap: Duration) -> Option<(SummaryLevel, &str)> {
+        let session = self.sessions.get(last_session);
+        SummaryLevel::for_gap(gap).fallbacks().into_iter().find_map(|level| {
+            let text = match level {
+                SummaryLevel::Chunk => session.and_then(|s| s.chunks.last()).map(String::as_str),
+                SummaryLevel::Session => session.map(|s| s.session.as_str()),
+                SummaryLevel::Week => self
+                    .weeks
+                    .values()
+                    .rev()
+                    .find(|d| d.sessions.contains(last_session))
+                    .map(|d| d.text.as_str()),
+            };
+            text.filter(|t| !t.is_empty()).map(|t| (level, t))
+        })
+    }
+
+    /// Прошлые разговоры для контекста, от новых к старым: сводки сессий последней
+    /// недели, затем дайджесты более ранних недель. Не больше `limit`
+    pub fn earlier(&self, current: &Uuid, now: DateTime<Utc>, limit: usize) -> Vec<(DateTime<Utc>, SummaryLevel, &str)> {
+        let week_ago = now - Duration::days(7);
+        let mut recent: Vec<(DateTime<Utc>, SummaryLevel, &str)> = self
+            .sessions
+            .iter()
+            .filter(|(id, s)| *id != current && !s.session.is_empty() && s.last_turn_at >= week_ago)
+            .map(|(_, s)| (s.last_turn_at, SummaryLevel::Session, s.session.as_str()))
+            .collect();
+        recent.sort_by_key(|r| std::cmp::Reverse(r.0));
+        let older = self.weeks.values().rev().filter_map(|d| {
+            let last = d.sessions.iter().filter_map(|id| self.sessions.get(id)).map(|s| s.last_turn_at).max()?;
+            (last < week_ago).then_some((last, SummaryLevel::Week, d.text.as_str()))
+        });
+        recent.into_iter().chain(older).take(limit).collect()
+    }
+}
+
+/// Выполняет задачи по порядку: хранилище заблокировано только на чтение
+/// материала и запись сводки, не на время генерации. Сохраняет файл после каждой
+/// сводки. Возвращает число составленных
+pub fn run_summary_tasks(store: &Mutex<SummaryStore>, tasks: &[SummaryTask], llm: &dyn LlmPipeline) -> Result<usize> {
+    let mut done = 0;
+    for task in tasks {
+        let Some(prompt) = store.lock().unwrap().prompt(task) else {
+            continue;
+        };
+        let text = llm.generate(&prompt, SUMMARY_MAX_TOKENS)?;
+        let mut store = store.lock().unwrap();
+        store.apply(task, &text, Utc::now());
+        store.save()?;
+        done += 1;
+    }
+    Ok(done)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::MockLlmPipeline;
+
+    fn echo() -> MockLlmPipeline {
+        MockLlmPipeline::new("chunk summary")
+            .with_reply("дайджест", "week summary")
+            .with_reply("разговор", "session summary")
+    }
+
+    fn session_with(turns: usize, at: DateTime<Utc>) -> Session {
+        let mut session = Session::new("programmer".to_string());
+        for i in 0..turns {
+            let mut turn = Turn::new(format!("вопрос {}", i), format!("ответ {}", i));
+            turn.timestamp = at;
+            session.add_turn(turn);
+        }
+        session
+    }
+
+    #[test]
+    fn test_progressive_summaries() -> Result<()> {
+        let now = Utc::now();
+        let old = session_with(12, now - Duration::days(14));
+        let current = session_with(10, now);
+        let store = Mutex::new(SummaryStore::default());
+
+        // первый проход: кусок и сводка старой сессии, кусок и сводка текущей на границе куска
+        let tasks = store.lock().unwrap().plan([&old, &current], Some(current.id), now);
+        let levels: Vec<SummaryLevel> = tasks.iter().map(SummaryTask::level).collect();
+        use SummaryLevel::*;
+        assert_eq!(levels, vec![Chunk, Session, Chunk, Session]);
+        assert_eq!(run_summary_tasks(&store, &tasks, &echo())?, 4);
+
+        // второй: неделя старой сессии подводится, когда её сводка готова
+        let tasks = store.lock().unwrap().plan([&old, &current], Some(current.id), now);
+        assert_eq!(tasks, vec![SummaryTask::Week { week: week_key(old.updated_at), sessions: vec![old.id] }]);
+        run_summary_tasks(&store, &tasks, &echo())?;
+        assert!(store.lock().unwrap().plan([&old, &current], Some(current.id), now).is_empty());
+
+        let store = store.into_inner().unwrap();
+        assert_eq!(store.counts(), (2, 2, 1));
+        assert_eq!(store.current_session_summary(&current.id, 10), Some("session summary"));
+        assert_eq!(store.current_session_summary(&current.id, 11), None);
+        assert_eq!(store.catch_up(&old.id, Duration::hours(3)), Some((Chunk, "chunk summary")));
+        assert_eq!(store.catch_up(&old.id, Duration::days(30)), Some((Week, "week summary")));
+        let earlier = store.earlier(&current.id, now, 5);
+        assert_eq!(earlier.len(), 1);
+        assert_eq!((earlier[0].1, earlier[0].2), (Week, "week summary"));
+        Ok(())
+    }
+}
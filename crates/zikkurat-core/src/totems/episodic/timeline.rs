@@ -0,0 +1,119 @@
+//! 🗓️ Лента сессий - о чём говорили, неделя за неделей
+//!
+//! Разбор сессии ([`SessionAnalysis`]: сводка, темы, эмоциональный фон) считается,
+//! когда персона сохраняет контекст сессии, и хранится в метаданных сессии
+//! (`analysis` → JSON), поэтому переживает рестарт вместе с `sessions.json`.
+//! [`super::DialogueManager::get_timeline`] собирает разборы всех сессий, включая
+//! оставленные на диске, по времени; `/context timeline` показывает их по неделям.
+
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+use uuid::Uuid;
+
+use super::summaries::week_key;
+use super::SessionAnalysis;
+
+/// Ключ разбора в метаданных сессии
+pub const ANALYSIS_KEY: &str = "analysis";
+
+/// Разобранная сессия в ленте
+#[derive(Debug, Clone, PartialEq)]
+pub struct TimelineEntry {
+    pub session_id: Uuid,
+    pub persona_name: String,
+    /// Последнее обновление сессии
+    pub at: DateTime<Utc>,
+    /// Ходов в сессии (разбор смотрит только на последние)
+    pub turns: usize,
+    pub analysis: SessionAnalysis,
+}
+
+impl TimelineEntry {
+    /// Неделя сессии в формате [`week_key`]
+    pub fn week(&self) -> String {
+        week_key(self.at)
+    }
+}
+
+/// Разбор из метаданных сессии; испорченный JSON считается отсутствующим
+pub fn stored_analysis(metadata: &HashMap<String, String>) -> Option<SessionAnalysis> {
+    serde_json::from_str(metadata.get(ANALYSIS_KEY)?).ok()
+}
+
+/// Записывает разбор в метаданные сессии, заменяя прежний
+pub fn store_analysis(metadata: &mut HashMap<String, String>, analysis: &SessionAnalysis) {
+    if let Ok(json) = serde_json::to_string(analysis) {
+        metadata.insert(ANALYSIS_KEY.to_string(), json);
+    }
+}
+
+/// Лента по неделям; `entries` уже по времени, как из `get_timeline`
+pub fn by_week(entries: &[TimelineEntry]) -> Vec<(String, Vec<&TimelineEntry>)> {
+    let mut weeks: Vec<(String, Vec<&TimelineEntry>)> = Vec::new();
+    for entry in entries {
+        let week = entry.week();
+        match weeks.last_mut() {
+            Some((last, group)) if *last == week => group.push(entry),
+            _ => weeks.push((week, vec![entry])),
+        }
+    }
+    weeks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::MockEmbedder;
+    use crate::priests::embeddings::Embedder;
+    use crate::totems::episodic::persistence::{LoadScope, PersistenceManager};
+    use crate::totems::episodic::DialogueManager;
+    use anyhow::Result;
+    use std::sync::Arc;
+
+    fn analysis(summary: &str, topic: &str) -> SessionAnalysis {
+        SessionAnalysis {
+            summary: summary.to_string(),
+            key_topics: vec![topic.to_string()],
+            emotional_state: 0.5,
+            last_topic: topic.to_string(),
+            turn_count: 1,
+        }
+    }
+
+    #[test]
+    fn test_timeline_survives_restart_and_groups_by_week() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("ziggurat-timeline-test-{}", Uuid::new_v4()));
+        let persistence = PersistenceManager::new(Some(&dir), false)?;
+        let embedder: Arc<dyn Embedder> = Arc::new(MockEmbedder::new(384));
+
+        let mut manager = DialogueManager::new(embedder.clone(), "programmer".to_string());
+        manager.add_exchange_blocking("How do I sort a Vec?".to_string(), "Use sort().".to_string())?;
+        manager.record_analysis(&analysis("Sorting vectors", "rust"));
+        manager.start_new_session("philosopher".to_string());
+        manager.add_exchange_blocking("What is virtue?".to_string(), "Knowledge.".to_string())?;
+        manager.record_analysis(&analysis("Virtue as knowledge", "ethics"));
+        manager.start_new_session("programmer".to_string());
+        manager.add_exchange_blocking("hi".to_string(), "hello".to_string())?;
+        persistence.save_with_embeddings_blocking(&manager, 384)?;
+
+        // the philosopher's session stays on disk, its analysis still counts
+        let (loaded, _) = persistence
+            .load_scoped_blocking(embedder, "programmer".to_string(), &LoadScope::persona("programmer"))?
+            .unwrap();
+        assert_eq!(loaded.deferred_sessions().len(), 1);
+        let timeline = loaded.get_timeline();
+        let summaries: Vec<&str> = timeline.iter().map(|e| e.analysis.summary.as_str()).collect();
+        assert_eq!(summaries, ["Sorting vectors", "Virtue as knowledge"]);
+        assert_eq!((timeline[1].persona_name.as_str(), timeline[1].turns), ("philosopher", 1));
+
+        let mut earlier = timeline[0].clone();
+        earlier.at -= chrono::Duration::days(14);
+        let entries = [earlier, timeline[0].clone(), timeline[1].clone()];
+        let weeks = by_week(&entries);
+        assert_eq!(weeks.iter().map(|(_, s)| s.len()).collect::<Vec<_>>(), [1, 2]);
+        assert_eq!(weeks[1].0, week_key(timeline[0].at));
+
+        let _ = std::fs::remove_dir_all(&dir);
+        Ok(())
+    }
+}
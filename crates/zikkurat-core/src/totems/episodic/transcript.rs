@@ -0,0 +1,195 @@
+//! 📝 Транскрипт сессии для баг-репортов
+//!
+//! Сессия выгружается в Markdown. С `--sanitized` из текста убираются данные,
+//! по которым можно узнать пользователя (email, телефоны, ключи, имена из правил),
+//! и ругательства маскируются (`f***`), чтобы транскрипт можно было приложить к
+//! баг-репорту без ручной чистки. Правила - JSON-файл [`SanitizeRules`].
+
+use anyhow::{Context, Result};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::path::Path;
+
+use super::export::redact_pii;
+use super::persistence::SerializedSession;
+
+/// Начала грубых слов; совпадение - от начала слова
+const PROFANITY: &[&str] = &[
+    "fuck", "shit", "bullshit", "bitch", "asshole", "bastard", "cunt", "dickhead", "motherfuck",
+    "хуй", "хуе", "хуё", "пизд", "бля", "ебан", "ебат", "ебал", "ёб", "заеб", "наеб", "выеб",
+    "сука", "суки", "мудак", "мудил", "гандон", "пидор", "говн",
+];
+
+/// Что вычищать из транскрипта
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SanitizeRules {
+    /// Email, телефоны, карты, IP и ключи (как в export-dataset)
+    pub pii: bool,
+    /// Встроенный список ругательств
+    pub profanity: bool,
+    /// Дополнительные начала слов, которые надо маскировать
+    pub extra_words: Vec<String>,
+    /// Имена и прочие идентификаторы -> `[NAME]`
+    pub names: Vec<String>,
+    /// Свои регулярные выражения
+    pub patterns: Vec<RedactionPattern>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RedactionPattern {
+    pub pattern: String,
+    pub replacement: String,
+}
+
+impl Default for SanitizeRules {
+    fn default() -> Self {
+        Self {
+            pii: true,
+            profanity: true,
+            extra_words: Vec::new(),
+            names: Vec::new(),
+            patterns: Vec::new(),
+        }
+    }
+}
+
+impl SanitizeRules {
+    pub fn load(path: &Path) -> Result<Self> {
+        serde_json::from_str(&std::fs::read_to_string(path)?).with_context(|| format!("Failed to parse {:?}", path))
+    }
+
+    pub fn compile(&self) -> Result<Sanitizer> {
+        let words: Vec<String> = self
+            .profanity
+            .then_some(PROFANITY)
+            .into_iter()
+            .flatten()
+            .map(|w| w.to_string())
+            .chain(self.extra_words.iter().map(|w| w.to_lowercase()))
+            .filter(|w| !w.trim().is_empty())
+            .map(|w| regex::escape(w.trim()))
+            .collect();
+        let words = (!words.is_empty())
+            .then(|| Regex::new(&format!(r"(?i)\b(?:{})\w*", words.join("|"))))
+            .transpose()?;
+
+        let names: Vec<String> = self
+            .names
+            .iter()
+            .filter(|n| !n.trim().is_empty())
+            .map(|n| regex::escape(n.trim()))
+            .collect();
+        let names = (!names.is_empty())
+            .then(|| Regex::new(&format!(r"(?i)\b(?:{})\b", names.join("|"))))
+            .transpose()?;
+
+        let patterns = self
+            .patterns
+            .iter()
+            .map(|p| {
+                Regex::new(&p.pattern)
+                    .map(|re| (re, p.replacement.clone()))
+                    .with_context(|| format!("Invalid redaction pattern '{}'", p.pattern))
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(Sanitizer { pii: self.pii, words, names, patterns })
+    }
+}
+
+/// Скомпилированные [`SanitizeRules`]
+pub struct Sanitizer {
+    pii: bool,
+    words: Option<Regex>,
+    names: Option<Regex>,
+    patterns: Vec<(Regex, String)>,
+}
+
+impl Sanitizer {
+    /// Очищенный текст и число замен
+    pub fn sanitize(&self, text: &str) -> (String, usize) {
+        let (mut result, mut count) = if self.pii { redact_pii(text) } else { (text.to_string(), 0) };
+        for (re, replacement) in &self.patterns {
+            count += re.find_iter(&result).count();
+            result = re.replace_all(&result, replacement.as_str()).into_owned();
+        }
+        if let Some(ref re) = self.names {
+            count += re.find_iter(&result).count();
+            result = re.replace_all(&result, "[NAME]").into_owned();
+        }
+        if let Some(ref re) = self.words {
+            count += re.find_iter(&result).count();
+            result = re
+                .replace_all(&result, |caps: &regex::Captures| mask(&caps[0]))
+                .into_owned();
+        }
+        (result, count)
+    }
+}
+
+/// Первая буква остаётся, остальные - звёздочки
+fn mask(word: &str) -> String {
+    let mut chars = word.chars();
+    chars
+        .next()
+        .into_iter()
+        .chain(chars.map(|_| '*'))
+        .collect()
+}
+
+/// Пишет сессию в Markdown, очищая текст, если передан `sanitizer`. Возвращает число замен
+pub fn write_transcript(
+    session: &SerializedSession,
+    sanitizer: Option<&Sanitizer>,
+    out: &mut impl Write,
+) -> Result<usize> {
+    let mut redactions = 0;
+    let mut clean = |text: &str| match sanitizer {
+        Some(s) => {
+            let (text, n) = s.sanitize(text);
+            redactions += n;
+            text
+        }
+        None => text.to_string(),
+    };
+
+    writeln!(out, "# Session {} ({})", session.id, session.persona_name)?;
+    writeln!(out)?;
+    writeln!(out, "Started {}", session.created_at.format("%Y-%m-%d %H:%M UTC"))?;
+    for turn in &session.turns {
+        writeln!(out)?;
+        writeln!(out, "**User** ({}):", turn.timestamp.format("%H:%M"))?;
+        writeln!(out, "{}", clean(&turn.user))?;
+        writeln!(out)?;
+        writeln!(out, "**Assistant**:")?;
+        writeln!(out, "{}", clean(&turn.assistant))?;
+    }
+    Ok(redactions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sanitize_transcript() {
+        let rules = SanitizeRules {
+            names: vec!["Ирина".to_string()],
+            extra_words: vec!["darn".to_string()],
+            ..Default::default()
+        };
+        let sanitizer = rules.compile().unwrap();
+
+        let (text, n) = sanitizer.sanitize("Ирина, this fucking build is darn broken, write to ira@mail.ru");
+        assert_eq!(text, "[NAME], this f****** build is d*** broken, write to [EMAIL]");
+        assert_eq!(n, 4);
+        let (text, _) = sanitizer.sanitize("Бля, опять сломалось. Скупой шитьё не портит");
+        assert_eq!(text, "Б**, опять сломалось. Скупой шитьё не портит");
+
+        let quiet = SanitizeRules { pii: false, profanity: false, ..Default::default() }.compile().unwrap();
+        assert_eq!(quiet.sanitize("shit at a@b.io").0, "shit at a@b.io");
+        assert!(serde_json::from_str::<SanitizeRules>(r#"{"names": ["Bob"]}"#).unwrap().profanity);
+    }
+}
@@ -0,0 +1,144 @@
+//! 🔎 Самопроверка ответа по памяти
+//!
+//! Промпт велит опираться на воспоминания, и модель уверенно «вспоминает» то, чего
+//! в них нет: путает имена, даты, предпочтения. С `--self-check` ответ, ссылающийся
+//! на память, до показа сверяется с найденными пунктами одним коротким запросом к
+//! LLM. Расхождение исправляется, а если исправить не удалось - помечается.
+
+use anyhow::Result;
+
+use crate::totems::episodic::LlmPipeline;
+use crate::totems::language::Language;
+
+/// Слова, которыми ответ ссылается на сказанное раньше
+const RECALL_MARKERS: &[&str] = &[
+    "помню", "помнишь", "говорил", "рассказывал", "упоминал", "в прошлый раз", "remember",
+    "mentioned", "told me", "you said", "last time",
+];
+/// Общий префикс такой длины у слов ответа и пункта памяти - ответ опирается на пункт
+const SHARED_PREFIX_CHARS: usize = 5;
+/// Пунктов в промпте проверки не больше
+const MAX_CHECK_ENTRIES: usize = 12;
+/// Бюджет ответа проверяющего: исправленный ответ должен поместиться целиком
+pub const SELF_CHECK_MAX_TOKENS: usize = 192;
+
+/// Итог проверки
+#[derive(Debug, Clone, PartialEq)]
+pub enum Verdict {
+    /// Ответ не ссылается на память или согласуется с ней
+    Consistent,
+    /// Ответ расходился с памятью, вот исправленный
+    Corrected(String),
+    /// Расхождение найдено, но исправления нет
+    Flagged,
+}
+
+impl Verdict {
+    /// Ответ проверяющего: `OK`, `CORRECTED: <ответ>` или `MISMATCH`.
+    /// Непонятный ответ расхождением не считается
+    pub fn parse(output: &str) -> Self {
+        let text = output.trim();
+        let upper = text.to_uppercase();
+        if upper.starts_with("CORRECTED") {
+            let rest = text.get("CORRECTED".len()..).unwrap_or("");
+            let fixed = rest.trim_start_matches([':', ' ', '\n']).trim();
+            if fixed.is_empty() {
+                Verdict::Flagged
+            } else {
+                Verdict::Corrected(fixed.to_string())
+            }
+        } else if upper.starts_with("MISMATCH") {
+            Verdict::Flagged
+        } else {
+            Verdict::Consistent
+        }
+    }
+}
+
+/// Приписка к помеченному ответу на языке ответа
+pub fn flag_note(language: Option<Language>) -> &'static str {
+    match language {
+        Some(Language::En) => "⚠️ (I'm not sure I remember this right)",
+        _ => "⚠️ (не уверен, что правильно это помню)",
+    }
+}
+
+/// Префиксы значимых слов текста
+fn word_stems(text: &str) -> Vec<String> {
+    text.to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|w| w.chars().count() >= SHARED_PREFIX_CHARS)
+        .map(|w| w.chars().take(SHARED_PREFIX_CHARS).collect())
+        .collect()
+}
+
+/// Ответ ссылается на память: говорит о сказанном раньше или повторяет слова пунктов
+pub fn cites_memory(answer: &str, entries: &[String]) -> bool {
+    if entries.is_empty() {
+        return false;
+    }
+    let lower = answer.to_lowercase();
+    if RECALL_MARKERS.iter().any(|m| lower.contains(m)) {
+        return true;
+    }
+    let stems = word_stems(answer);
+    entries.iter().any(|entry| word_stems(entry).iter().any(|s| stems.contains(s)))
+}
+
+/// Промпт проверки ответа по пунктам памяти
+pub fn self_check_prompt(answer: &str, entries: &[String]) -> String {
+    let entries: Vec<&str> = entries.iter().take(MAX_CHECK_ENTRIES).map(String::as_str).collect();
+    format!(
+        "<s>[INST] Check the assistant's answer against the memory entries. \
+         Look only at claims about the user or past conversations.\n\
+         \n\
+         MEMORY ENTRIES:\n- {}\n\
+         \n\
+         Answer: {}\n\
+         \n\
+         If every such claim agrees with the entries, reply OK. If a claim contradicts them \
+         or is not in them, reply CORRECTED: followed by the whole answer fixed, in the same \
+         language and tone. If you cannot fix it, reply MISMATCH.[/INST]",
+        entries.join("\n- "),
+        answer
+    )
+}
+
+/// Сверяет ответ с пунктами памяти. Ответ, не ссылающийся на память, LLM не проверяет
+pub fn self_check(answer: &str, entries: &[String], llm: &dyn LlmPipeline) -> Result<Verdict> {
+    if !cites_memory(answer, entries) {
+        return Ok(Verdict::Consistent);
+    }
+    let output = llm.generate(&self_check_prompt(answer, entries), SELF_CHECK_MAX_TOKENS)?;
+    Ok(match Verdict::parse(&output) {
+        Verdict::Corrected(fixed) if fixed == answer.trim() => Verdict::Consistent,
+        verdict => verdict,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::MockLlmPipeline;
+
+    #[test]
+    fn test_self_check() -> Result<()> {
+        let entries = vec!["Пользователь любит кошек".to_string(), "Работает врачом в Казани".to_string()];
+
+        assert!(cites_memory("Как работа в Казани?", &entries));
+        assert!(cites_memory("You mentioned a trip", &entries));
+        assert!(!cites_memory("Rust - системный язык", &entries));
+        assert!(!cites_memory("Помню, ты любишь кошек", &[]));
+
+        let answer = "Ты же программист в Казани, верно?";
+        let fixed = self_check(answer, &entries, &MockLlmPipeline::new("CORRECTED: Ты же врач в Казани, верно?"))?;
+        assert_eq!(fixed, Verdict::Corrected("Ты же врач в Казани, верно?".to_string()));
+        assert_eq!(self_check(answer, &entries, &MockLlmPipeline::new("OK"))?, Verdict::Consistent);
+        assert_eq!(self_check(answer, &entries, &MockLlmPipeline::new("mismatch"))?, Verdict::Flagged);
+        assert_eq!(self_check(answer, &entries, &MockLlmPipeline::new("CORRECTED:"))?, Verdict::Flagged);
+        // непонятный ответ и ответ без ссылок на память ничего не меняют
+        assert_eq!(self_check(answer, &entries, &MockLlmPipeline::new("Echo: ..."))?, Verdict::Consistent);
+        assert_eq!(self_check("Привет!", &entries, &MockLlmPipeline::new("MISMATCH"))?, Verdict::Consistent);
+        Ok(())
+    }
+}
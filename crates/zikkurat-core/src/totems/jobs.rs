@@ -0,0 +1,366 @@
+//! 🧵 Фоновые задачи с приоритетами
+//!
+//! Экстракция концептов, суммаризация и decay не обязаны задерживать ответ.
+//! Они уходят в очередь одного фонового потока с тремя классами приоритета.
+//! Пока идёт интерактивная генерация (жив [`InteractiveGuard`]), новые задачи
+//! не стартуют - генерация ждёт максимум одну уже начатую. Обслуживание
+//! (`Low`) откладывается ещё на `idle_delay` после последнего ответа.
+//!
+//! Backpressure: очередь ограничена `capacity`. Когда она полна, новая задача
+//! вытесняет самую старую задачу низшего приоритета, если та ниже новой, а
+//! иначе отбрасывается сама. Счётчики и содержимое очереди показывает `/jobs`.
+
+use parking_lot::{Condvar, Mutex};
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Класс задачи; при нехватке места первыми теряются низшие
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum JobPriority {
+    /// Обслуживание: decay, компактизация - только в простое
+    Low,
+    /// Суммаризация и прочее, что подождёт несколько ходов
+    Normal,
+    /// Экстракция: нужна к следующему ответу
+    High,
+}
+
+impl JobPriority {
+    const ALL: [JobPriority; 3] = [JobPriority::High, JobPriority::Normal, JobPriority::Low];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            JobPriority::Low => "low",
+            JobPriority::Normal => "normal",
+            JobPriority::High => "high",
+        }
+    }
+
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+/// Что стало с поставленной задачей
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Submitted {
+    Queued,
+    /// Очередь полна, вытеснена задача с этим именем
+    Displaced(String),
+    /// Очередь полна задачами не ниже приоритетом, задача отброшена
+    Shed,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct JobQueueConfig {
+    /// Задач в очереди, не считая выполняемой
+    pub capacity: usize,
+    /// Пауза после интерактивного запроса, прежде чем браться за `Low`
+    pub idle_delay: Duration,
+}
+
+impl Default for JobQueueConfig {
+    fn default() -> Self {
+        Self {
+            capacity: 32,
+            idle_delay: Duration::from_secs(2),
+        }
+    }
+}
+
+type JobFn = Box<dyn FnOnce() -> anyhow::Result<()> + Send>;
+
+struct Job {
+    name: String,
+    priority: JobPriority,
+    queued_at: Instant,
+    run: JobFn,
+}
+
+/// Снимок очереди для `/jobs`
+#[derive(Debug, Clone, Default)]
+pub struct JobStats {
+    /// Имя, приоритет и сколько ждёт
+    pub pending: Vec<(String, JobPriority, Duration)>,
+    pub running: Option<String>,
+    pub completed: usize,
+    pub failed: usize,
+    /// Отброшено или вытеснено из-за переполнения
+    pub shed: usize,
+    /// Сколько раз очередь с задачами уступила интерактивному запросу
+    pub deferrals: usize,
+    pub interactive: bool,
+}
+
+impl JobStats {
+    pub fn report(&self) -> String {
+        let mut out = format!(
+            "🧵 Jobs: {} pending, {} done, {} failed, {} shed, {} deferred to interactive{}\n",
+            self.pending.len(),
+            self.completed,
+            self.failed,
+            self.shed,
+            self.deferrals,
+            if self.interactive { " (generating now)" } else { "" }
+        );
+        if let Some(ref name) = self.running {
+            out.push_str(&format!("   ▶ {}\n", name));
+        }
+        for (name, priority, waited) in &self.pending {
+            out.push_str(&format!("   · {} [{}] waiting {:.1}s\n", name, priority.as_str(), waited.as_secs_f32()));
+        }
+        out
+    }
+}
+
+#[derive(Default)]
+struct State {
+    /// По очереди на приоритет, индекс - [`JobPriority::index`]
+    queues: [VecDeque<Job>; 3],
+    running: Option<String>,
+    interactive: usize,
+    last_interactive: Option<Instant>,
+    completed: usize,
+    failed: usize,
+    shed: usize,
+    deferrals: usize,
+    stopping: bool,
+}
+
+impl State {
+    fn pending(&self) -> usize {
+        self.queues.iter().map(VecDeque::len).sum()
+    }
+
+    /// Следующая задача, которую можно начать сейчас
+    fn next_runnable(&mut self, idle_delay: Duration) -> Option<Job> {
+        if self.interactive > 0 {
+            return None;
+        }
+        let idle = self.last_interactive.is_none_or(|t| t.elapsed() >= idle_delay);
+        JobPriority::ALL
+            .into_iter()
+            .filter(|p| *p != JobPriority::Low || idle || self.stopping)
+            .find_map(|p| self.queues[p.index()].pop_front())
+    }
+}
+
+struct Shared {
+    config: JobQueueConfig,
+    state: Mutex<State>,
+    changed: Condvar,
+}
+
+/// Очередь фоновых задач и её поток
+#[derive(Clone)]
+pub struct JobQueue {
+    shared: Arc<Shared>,
+}
+
+impl JobQueue {
+    /// Запускает фоновый поток
+    pub fn start(config: JobQueueConfig) -> Self {
+        let shared = Arc::new(Shared {
+            config,
+            state: Mutex::new(State::default()),
+            changed: Condvar::new(),
+        });
+        let worker = Arc::clone(&shared);
+        std::thread::Builder::new()
+            .name("ziggurat-jobs".to_string())
+            .spawn(move || work(&worker))
+            .expect("failed to spawn job worker");
+        Self { shared }
+    }
+
+    /// Ставит задачу в очередь с учётом backpressure
+    pub fn submit(
+        &self,
+        name: impl Into<String>,
+        priority: JobPriority,
+        run: impl FnOnce() -> anyhow::Result<()> + Send + 'static,
+    ) -> Submitted {
+        let job = Job {
+            name: name.into(),
+            priority,
+            queued_at: Instant::now(),
+            run: Box::new(run),
+        };
+        let mut state = self.shared.state.lock();
+        let mut submitted = Submitted::Queued;
+        if state.pending() >= self.shared.config.capacity {
+            let lowest = JobPriority::ALL
+                .into_iter()
+                .rev()
+                .find(|p| !state.queues[p.index()].is_empty())
+                .filter(|p| *p < priority);
+            match lowest.and_then(|p| state.queues[p.index()].pop_front()) {
+                Some(displaced) => submitted = Submitted::Displaced(displaced.name),
+                None => {
+                    state.shed += 1;
+                    return Submitted::Shed;
+                }
+            }
+            state.shed += 1;
+        }
+        state.queues[priority.index()].push_back(job);
+        self.shared.changed.notify_all();
+        submitted
+    }
+
+    /// Интерактивный запрос: пока guard жив, фоновые задачи не начинаются
+    pub fn interactive(&self) -> InteractiveGuard {
+        let mut state = self.shared.state.lock();
+        state.interactive += 1;
+        if state.pending() > 0 {
+            state.deferrals += 1;
+        }
+        drop(state);
+        InteractiveGuard {
+            shared: Arc::clone(&self.shared),
+        }
+    }
+
+    pub fn stats(&self) -> JobStats {
+        let state = self.shared.state.lock();
+        let now = Instant::now();
+        let pending = JobPriority::ALL
+            .into_iter()
+            .flat_map(|p| state.queues[p.index()].iter())
+            .map(|job| (job.name.clone(), job.priority, now - job.queued_at))
+            .collect();
+        JobStats {
+            pending,
+            running: state.running.clone(),
+            completed: state.completed,
+            failed: state.failed,
+            shed: state.shed,
+            deferrals: state.deferrals,
+            interactive: state.interactive > 0,
+        }
+    }
+
+    /// Дожидается пустой очереди (включая `Low`, без паузы простоя); false по таймауту
+    pub fn drain(&self, timeout: Duration) -> bool {
+        let deadline = Instant::now() + timeout;
+        let mut state = self.shared.state.lock();
+        state.stopping = true;
+        self.shared.changed.notify_all();
+        while state.pending() > 0 || state.running.is_some() {
+            if self.shared.changed.wait_until(&mut state, deadline).timed_out() {
+                state.stopping = false;
+                return false;
+            }
+        }
+        state.stopping = false;
+        true
+    }
+}
+
+/// Держит фоновые задачи на паузе, см. [`JobQueue::interactive`]
+pub struct InteractiveGuard {
+    shared: Arc<Shared>,
+}
+
+impl Drop for InteractiveGuard {
+    fn drop(&mut self) {
+        let mut state = self.shared.state.lock();
+        state.interactive -= 1;
+        state.last_interactive = Some(Instant::now());
+        self.shared.changed.notify_all();
+    }
+}
+
+fn work(shared: &Shared) {
+    let mut state = shared.state.lock();
+    loop {
+        let Some(job) = state.next_runnable(shared.config.idle_delay) else {
+            // отложенный Low проверяется снова по истечении паузы простоя
+            shared.changed.wait_for(&mut state, shared.config.idle_delay);
+            continue;
+        };
+        state.running = Some(job.name.clone());
+        drop(state);
+
+        // a panicking job is a failed job, the worker keeps serving the queue
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(job.run))
+            .unwrap_or_else(|panic| Err(anyhow::anyhow!("panicked: {}", panic_message(&*panic))));
+
+        state = shared.state.lock();
+        state.running = None;
+        match result {
+            Ok(()) => state.completed += 1,
+            Err(e) => {
+                state.failed += 1;
+                eprintln!("WARNING: Background job '{}' failed: {}", job.name, e);
+            }
+        }
+        shared.changed.notify_all();
+    }
+}
+
+fn panic_message(panic: &(dyn std::any::Any + Send)) -> &str {
+    panic
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| panic.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("unknown cause")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[test]
+    fn test_priorities_and_backpressure() {
+        let queue = JobQueue::start(JobQueueConfig {
+            capacity: 2,
+            idle_delay: Duration::from_millis(10),
+        });
+        let done = Arc::new(AtomicUsize::new(0));
+        let job = |done: &Arc<AtomicUsize>| {
+            let done = Arc::clone(done);
+            move || {
+                done.fetch_add(1, Ordering::SeqCst);
+                Ok(())
+            }
+        };
+
+        // during generation nothing starts, the full queue sheds low-priority work first
+        let guard = queue.interactive();
+        assert_eq!(queue.submit("decay", JobPriority::Low, job(&done)), Submitted::Queued);
+        assert_eq!(queue.submit("summary", JobPriority::Normal, job(&done)), Submitted::Queued);
+        assert_eq!(
+            queue.submit("extraction", JobPriority::High, job(&done)),
+            Submitted::Displaced("decay".to_string())
+        );
+        assert_eq!(queue.submit("decay", JobPriority::Low, job(&done)), Submitted::Shed);
+        std::thread::sleep(Duration::from_millis(30));
+        assert_eq!(done.load(Ordering::SeqCst), 0);
+        let stats = queue.stats();
+        assert_eq!(stats.pending[0].0, "extraction");
+        assert_eq!(stats.shed, 2);
+
+        drop(guard);
+        assert!(queue.drain(Duration::from_secs(5)));
+        assert_eq!(done.load(Ordering::SeqCst), 2);
+        assert_eq!(queue.stats().completed, 2);
+    }
+
+    #[test]
+    fn test_panicking_job_counts_as_failed() {
+        let queue = JobQueue::start(JobQueueConfig {
+            capacity: 4,
+            idle_delay: Duration::from_millis(10),
+        });
+        queue.submit("extraction", JobPriority::High, || panic!("model is gone"));
+        queue.submit("summary", JobPriority::Normal, || Ok(()));
+
+        assert!(queue.drain(Duration::from_secs(5)));
+        let stats = queue.stats();
+        assert_eq!((stats.failed, stats.completed), (1, 1));
+        assert!(stats.running.is_none());
+    }
+}
@@ -0,0 +1,86 @@
+//! 🌐 Язык текста
+//!
+//! Память смешанная RU/EN: язык определяется по алфавиту (кириллица/латиница),
+//! этого хватает, чтобы пометить ход и при поиске поднять воспоминания на языке
+//! запроса, не отсекая остальные.
+
+use serde::{Deserialize, Serialize};
+
+/// Ключ метаданных с языком хода / записи памяти
+pub const LANGUAGE_KEY: &str = "lang";
+
+/// Меньше букв - язык не определяется
+const MIN_LETTERS: usize = 3;
+/// Доля букв одного алфавита, начиная с которой текст считается на этом языке
+const DOMINANT_SHARE: f32 = 0.6;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Language {
+    Ru,
+    En,
+}
+
+impl Language {
+    /// Язык по преобладающему алфавиту; `None` для коротких и смешанных текстов
+    pub fn detect(text: &str) -> Option<Self> {
+        let (mut cyrillic, mut latin) = (0usize, 0usize);
+        for c in text.chars().filter(|c| c.is_alphabetic()) {
+            match c {
+                'а'..='я' | 'А'..='Я' | 'ё' | 'Ё' => cyrillic += 1,
+                'a'..='z' | 'A'..='Z' => latin += 1,
+                _ => {}
+            }
+        }
+        let total = cyrillic + latin;
+        if total < MIN_LETTERS {
+            return None;
+        }
+        if cyrillic as f32 / total as f32 >= DOMINANT_SHARE {
+            Some(Language::Ru)
+        } else if latin as f32 / total as f32 >= DOMINANT_SHARE {
+            Some(Language::En)
+        } else {
+            None
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Language::Ru => "ru",
+            Language::En => "en",
+        }
+    }
+}
+
+impl std::str::FromStr for Language {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        match s.to_lowercase().as_str() {
+            "ru" => Ok(Language::Ru),
+            "en" => Ok(Language::En),
+            other => anyhow::bail!("Unknown language '{}' (ru, en)", other),
+        }
+    }
+}
+
+impl std::fmt::Display for Language {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect_language() {
+        assert_eq!(Language::detect("Как правильно настроить borrow checker в проекте?"), Some(Language::Ru));
+        assert_eq!(Language::detect("How do lifetimes work in Rust?"), Some(Language::En));
+        assert_eq!(Language::detect("ok"), None);
+        assert_eq!(Language::detect("Rust лучше"), None);
+        assert_eq!("EN".parse::<Language>().unwrap(), Language::En);
+    }
+}
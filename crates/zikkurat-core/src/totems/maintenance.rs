@@ -0,0 +1,351 @@
+//! 🧹 Обслуживание памяти по расписанию
+//!
+//! Затухание концептов раньше запускалось только `--apply-decay` или запросом,
+//! попавшим в пять минут после трёх часов ночи. Теперь в интерактивном режиме и в
+//! `--serve` обслуживанием занимается [`MaintenanceScheduler`]: задача tokio раз в
+//! `tick_secs` смотрит, какие работы просрочены по интервалам из
+//! [`SystemConfig`], и запускает их. Семантическая память разделяемая, её
+//! затухание и слияние похожих концептов идут прямо в фоне (через очередь
+//! `--background-jobs`, если она есть). Эпизодическая живёт в потоке REPL или
+//! сервера, поэтому уборка старых сессий и перезапись файлов памяти ждут там
+//! конца текущего запроса ([`MaintenanceScheduler::take_pending`]). Время
+//! последних запусков лежит в `maintenance.json`: суточный интервал не
+//! начинается заново с каждым запуском.
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Duration, Utc};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::totems::episodic::segmentation::SegmentationConfig;
+use crate::totems::jobs::{JobPriority, JobQueue};
+use crate::totems::retrieval::RetrievalConfig;
+use crate::totems::semantic::{RetentionConfig, SemanticMemoryManager};
+use crate::utils::clock::SharedClock;
+
+/// Время последних запусков, в каталоге данных
+pub const MAINTENANCE_STATE_FILE: &str = "maintenance.json";
+
+/// Интервалы обслуживания; интервал 0 выключает работу
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MaintenanceConfig {
+    pub enabled: bool,
+    /// Как часто проверять расписание
+    pub tick_secs: u64,
+    pub decay_hours: u64,
+    pub merge_hours: u64,
+    /// Косинусное сходство, с которого концепты одной категории сливаются
+    pub merge_threshold: f32,
+    pub session_cleanup_hours: u64,
+    /// Сессии без новых ходов дольше этого уходят в архив
+    pub session_max_age_days: u64,
+    pub compaction_hours: u64,
+}
+
+impl Default for MaintenanceConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            tick_secs: 60,
+            decay_hours: 24,
+            merge_hours: 24,
+            merge_threshold: 0.95,
+            session_cleanup_hours: 24,
+            session_max_age_days: 180,
+            compaction_hours: 6,
+        }
+    }
+}
+
+/// Настройки процесса, которые не относятся к модели и персоне:
+/// `{"maintenance": {"decay_hours": 12, "session_max_age_days": 90}, "retrieval": {"min_similarity": 0.4}}`
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SystemConfig {
+    pub maintenance: MaintenanceConfig,
+    /// Поиск воспоминаний; архетип может поправить его своим `retrieval`
+    pub retrieval: RetrievalConfig,
+    /// Автоматическая смена сессии по смене темы и простою
+    pub segmentation: SegmentationConfig,
+    /// Затухание и пределы концептов по категориям; применяются работой `decay`
+    pub retention: RetentionConfig,
+}
+
+impl SystemConfig {
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path).with_context(|| format!("Failed to read {:?}", path))?;
+        serde_json::from_str(&content).with_context(|| format!("Failed to parse {:?}", path))
+    }
+}
+
+/// Работа обслуживания
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MaintenanceTask {
+    /// Затухание уверенности концептов
+    Decay,
+    /// Слияние почти одинаковых концептов
+    Merge,
+    /// Старые сессии в архив
+    SessionCleanup,
+    /// Перезапись файлов памяти без удалённых сессий и концептов
+    Compaction,
+}
+
+impl MaintenanceTask {
+    pub const ALL: [MaintenanceTask; 4] = [
+        MaintenanceTask::Decay,
+        MaintenanceTask::Merge,
+        MaintenanceTask::SessionCleanup,
+        MaintenanceTask::Compaction,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            MaintenanceTask::Decay => "decay",
+            MaintenanceTask::Merge => "merge",
+            MaintenanceTask::SessionCleanup => "session-cleanup",
+            MaintenanceTask::Compaction => "compaction",
+        }
+    }
+
+    /// Интервал работы; `None` - выключена
+    pub fn interval(self, config: &MaintenanceConfig) -> Option<Duration> {
+        let hours = match self {
+            MaintenanceTask::Decay => config.decay_hours,
+            MaintenanceTask::Merge => config.merge_hours,
+            MaintenanceTask::SessionCleanup => config.session_cleanup_hours,
+            MaintenanceTask::Compaction => config.compaction_hours,
+        };
+        (hours > 0).then(|| Duration::hours(hours as i64))
+    }
+
+    /// Работа над семантической памятью, которую можно делать из фона
+    pub fn is_semantic(self) -> bool {
+        matches!(self, MaintenanceTask::Decay | MaintenanceTask::Merge)
+    }
+}
+
+#[derive(Default)]
+struct ScheduleState {
+    last_run: BTreeMap<MaintenanceTask, DateTime<Utc>>,
+    /// Работы для эпизодической памяти, ждущие её потока
+    pending: Vec<MaintenanceTask>,
+    semantic: Option<Arc<std::sync::Mutex<SemanticMemoryManager>>>,
+    jobs: Option<&'static JobQueue>,
+}
+
+/// Расписание обслуживания памяти
+pub struct MaintenanceScheduler {
+    config: MaintenanceConfig,
+    clock: SharedClock,
+    /// Без пути время запусков живёт до конца процесса
+    state_path: Option<PathBuf>,
+    state: Mutex<ScheduleState>,
+}
+
+impl MaintenanceScheduler {
+    /// Расписание с временем прошлых запусков из `state_path`
+    pub fn new(config: MaintenanceConfig, state_path: Option<&Path>, clock: SharedClock) -> Self {
+        let last_run = state_path
+            .filter(|path| path.exists())
+            .and_then(|path| match std::fs::read_to_string(path).map(|s| serde_json::from_str(&s)) {
+                Ok(Ok(last_run)) => Some(last_run),
+                Ok(Err(e)) => {
+                    eprintln!("WARNING: Ignoring malformed {:?}: {}", path, e);
+                    None
+                }
+                Err(e) => {
+                    eprintln!("WARNING: Failed to read {:?}: {}", path, e);
+                    None
+                }
+            })
+            .unwrap_or_default();
+        Self {
+            config,
+            clock,
+            state_path: state_path.map(Path::to_path_buf),
+            state: Mutex::new(ScheduleState { last_run, ..Default::default() }),
+        }
+    }
+
+    pub fn config(&self) -> &MaintenanceConfig {
+        &self.config
+    }
+
+    /// Семантическая память, которую обслуживать; меняется с пользователем и `/semantic off`
+    pub fn set_semantic(&self, semantic: Option<Arc<std::sync::Mutex<SemanticMemoryManager>>>) {
+        self.state.lock().semantic = semantic;
+    }
+
+    /// Очередь `--background-jobs`: фоновые работы ждут в ней простоя
+    pub fn set_jobs(&self, jobs: &'static JobQueue) {
+        self.state.lock().jobs = Some(jobs);
+    }
+
+    /// Просроченные работы. Они сразу считаются запущенными: не успевшая работа
+    /// повторится через интервал, а не на каждой проверке
+    pub fn due(&self) -> Vec<MaintenanceTask> {
+        let now = self.clock.now();
+        let mut state = self.state.lock();
+        let due: Vec<MaintenanceTask> = MaintenanceTask::ALL
+            .into_iter()
+            .filter(|task| {
+                task.interval(&self.config)
+                    .is_some_and(|interval| state.last_run.get(task).is_none_or(|last| now - *last >= interval))
+            })
+            .collect();
+        if due.is_empty() {
+            return due;
+        }
+        for task in &due {
+            state.last_run.insert(*task, now);
+        }
+        if let Some(ref path) = self.state_path {
+            let saved = serde_json::to_string_pretty(&state.last_run)
+                .map_err(anyhow::Error::from)
+                .and_then(|json| std::fs::write(path, json).with_context(|| format!("Failed to write {:?}", path)));
+            if let Err(e) = saved {
+                eprintln!("WARNING: Failed to save maintenance schedule: {}", e);
+            }
+        }
+        due
+    }
+
+    /// Одна проверка расписания: семантические работы запускаются, остальные ждут
+    /// [`MaintenanceScheduler::take_pending`]
+    pub fn tick(&self) {
+        for task in self.due() {
+            if !task.is_semantic() {
+                let mut state = self.state.lock();
+                if !state.pending.contains(&task) {
+                    state.pending.push(task);
+                }
+                continue;
+            }
+            let (semantic, jobs) = {
+                let state = self.state.lock();
+                (state.semantic.clone(), state.jobs)
+            };
+            let Some(semantic) = semantic else {
+                continue;
+            };
+            let threshold = self.config.merge_threshold;
+            let run = move || run_semantic(task, &semantic, threshold);
+            match jobs {
+                Some(jobs) => {
+                    jobs.submit(task.as_str(), JobPriority::Low, run);
+                }
+                None => {
+                    if let Err(e) = run() {
+                        eprintln!("WARNING: Maintenance {} failed: {}", task.as_str(), e);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Работы для эпизодической памяти, накопившиеся с прошлого вызова
+    pub fn take_pending(&self) -> Vec<MaintenanceTask> {
+        std::mem::take(&mut self.state.lock().pending)
+    }
+
+    /// Интервалы одной строкой для стартового сообщения
+    pub fn describe(&self) -> String {
+        let tasks: Vec<String> = MaintenanceTask::ALL
+            .into_iter()
+            .filter_map(|task| {
+                let hours = task.interval(&self.config)?.num_hours();
+                Some(format!("{} every {}h", task.as_str(), hours))
+            })
+            .collect();
+        if tasks.is_empty() {
+            "all tasks off".to_string()
+        } else {
+            tasks.join(", ")
+        }
+    }
+
+    /// Проверяет расписание в задаче tokio раз в `tick_secs`; первая проверка -
+    /// через `tick_secs` после старта, а не во время загрузки
+    #[cfg(feature = "runtime")]
+    pub fn start(self: &Arc<Self>) -> tokio::task::JoinHandle<()> {
+        let scheduler = Arc::clone(self);
+        let tick = std::time::Duration::from_secs(self.config.tick_secs.max(1));
+        crate::utils::runtime().spawn(async move {
+            loop {
+                tokio::time::sleep(tick).await;
+                let scheduler = Arc::clone(&scheduler);
+                if tokio::task::spawn_blocking(move || scheduler.tick()).await.is_err() {
+                    eprintln!("WARNING: Maintenance check panicked");
+                }
+            }
+        })
+    }
+}
+
+/// Затухание или слияние концептов под замком менеджера
+fn run_semantic(
+    task: MaintenanceTask,
+    semantic: &std::sync::Mutex<SemanticMemoryManager>,
+    merge_threshold: f32,
+) -> Result<()> {
+    let _span = tracing::info_span!("maintenance", task = task.as_str()).entered();
+    let mut sm = semantic.lock().map_err(|_| anyhow::anyhow!("Semantic memory lock is poisoned"))?;
+    let changed = match task {
+        MaintenanceTask::Decay => sm.apply_temporal_decay()?,
+        MaintenanceTask::Merge => {
+            let merged = sm.merge_similar(merge_threshold)?;
+            if merged > 0 {
+                sm.save_blocking()?;
+                sm.save_graph_blocking()?;
+            }
+            merged
+        }
+        MaintenanceTask::SessionCleanup | MaintenanceTask::Compaction => 0,
+    };
+    tracing::info!(changed, "maintenance done");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::clock::MockClock;
+
+    #[test]
+    fn test_schedule_survives_restart() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("zikkurat-maintenance-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir)?;
+        let path = dir.join(MAINTENANCE_STATE_FILE);
+
+        let config: SystemConfig = serde_json::from_str(r#"{"maintenance": {"merge_hours": 0}}"#)?;
+        let config = config.maintenance;
+        assert_eq!(config.decay_hours, 24);
+        let mock = Arc::new(MockClock::new("2024-01-01T00:00:00Z".parse()?));
+        let scheduler = MaintenanceScheduler::new(config.clone(), Some(&path), mock.clone());
+        assert!(scheduler.describe().starts_with("decay every 24h, session-cleanup"));
+
+        // never run: everything that is on is due at once
+        use MaintenanceTask::*;
+        scheduler.tick();
+        assert_eq!(scheduler.take_pending(), [SessionCleanup, Compaction]);
+        assert!(scheduler.take_pending().is_empty());
+        mock.advance(Duration::hours(1));
+        assert!(scheduler.due().is_empty());
+
+        // the next start remembers the last runs
+        mock.advance(Duration::hours(5));
+        let restarted = MaintenanceScheduler::new(config, Some(&path), mock.clone());
+        assert_eq!(restarted.due(), [Compaction]);
+        mock.advance(Duration::hours(18));
+        assert_eq!(restarted.due(), [Decay, SessionCleanup, Compaction]);
+        let _ = std::fs::remove_dir_all(&dir);
+        Ok(())
+    }
+}
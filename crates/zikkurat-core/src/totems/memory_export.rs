@@ -0,0 +1,231 @@
+//! 📦 Выборочный экспорт памяти
+//!
+//! Вместо копирования всей `memory_data` можно выгрузить только нужный слой:
+//! одну семантику (знания о пользователе), сессии одной персоны, диапазон дат,
+//! без эмбеддингов. Так между установками передаётся лёгкий слой знаний.
+//! Эмбеддинги концептов не хранятся вовсе - они пересчитываются при загрузке;
+//! `include_embeddings` относится к эмбеддингам ходов.
+
+use anyhow::Result;
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+use super::episodic::persistence::SerializedSession;
+use super::semantic::persistence::SerializedConcept;
+
+/// Версия формата файла экспорта
+pub const MEMORY_EXPORT_VERSION: &str = "1.0";
+
+/// Какие слои памяти выгружать
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MemoryScope {
+    #[default]
+    All,
+    /// Только сессии
+    Episodic,
+    /// Только концепты
+    Semantic,
+}
+
+impl std::str::FromStr for MemoryScope {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "all" => Ok(MemoryScope::All),
+            "episodic" | "sessions" => Ok(MemoryScope::Episodic),
+            "semantic" | "knowledge" => Ok(MemoryScope::Semantic),
+            other => anyhow::bail!("Unknown memory scope '{}' (expected all, episodic or semantic)", other),
+        }
+    }
+}
+
+impl MemoryScope {
+    fn episodic(self) -> bool {
+        matches!(self, MemoryScope::All | MemoryScope::Episodic)
+    }
+
+    fn semantic(self) -> bool {
+        matches!(self, MemoryScope::All | MemoryScope::Semantic)
+    }
+}
+
+/// Что выгрузить. Даты включительно, по UTC
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MemoryExportRequest {
+    pub scope: MemoryScope,
+    /// Только сессии этой персоны и концепты, извлечённые из них
+    pub persona: Option<String>,
+    pub since: Option<NaiveDate>,
+    pub until: Option<NaiveDate>,
+    pub include_embeddings: bool,
+}
+
+impl Default for MemoryExportRequest {
+    fn default() -> Self {
+        Self {
+            scope: MemoryScope::All,
+            persona: None,
+            since: None,
+            until: None,
+            include_embeddings: true,
+        }
+    }
+}
+
+/// Файл экспорта
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MemoryExport {
+    pub version: String,
+    pub exported_at: DateTime<Utc>,
+    pub request: MemoryExportRequest,
+    #[serde(default)]
+    pub sessions: Vec<SerializedSession>,
+    #[serde(default)]
+    pub concepts: Vec<SerializedConcept>,
+}
+
+impl MemoryExportRequest {
+    fn in_range(&self, at: DateTime<Utc>) -> bool {
+        let date = at.date_naive();
+        self.since.is_none_or(|since| date >= since) && self.until.is_none_or(|until| date <= until)
+    }
+
+    fn persona_matches(&self, persona_name: &str) -> bool {
+        self.persona
+            .as_ref()
+            .is_none_or(|p| p.eq_ignore_ascii_case(persona_name))
+    }
+
+    /// Отбирает из всех сессий и концептов то, что просили
+    pub fn apply(&self, sessions: Vec<SerializedSession>, concepts: Vec<SerializedConcept>) -> MemoryExport {
+        // источник концепта - ID сессии, из которой он извлечён
+        let persona_sessions: HashSet<String> = sessions
+            .iter()
+            .filter(|s| self.persona_matches(&s.persona_name))
+            .map(|s| s.id.clone())
+            .collect();
+
+        let concepts = if self.scope.semantic() {
+            concepts
+                .into_iter()
+                .filter(|c| self.persona.is_none() || persona_sessions.contains(&c.source))
+                .filter(|c| self.in_range(c.updated_at))
+                .collect()
+        } else {
+            Vec::new()
+        };
+
+        let sessions = if self.scope.episodic() {
+            sessions
+                .into_iter()
+                .filter(|s| persona_sessions.contains(&s.id))
+                .filter_map(|mut s| {
+                    s.turns.retain(|t| self.in_range(t.timestamp));
+                    if !self.include_embeddings {
+                        s.turns.iter_mut().for_each(|t| t.embedding = None);
+                    }
+                    (!s.turns.is_empty()).then_some(s)
+                })
+                .collect()
+        } else {
+            Vec::new()
+        };
+
+        MemoryExport {
+            version: MEMORY_EXPORT_VERSION.to_string(),
+            exported_at: Utc::now(),
+            request: self.clone(),
+            sessions,
+            concepts,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::totems::episodic::persistence::SerializedTurn;
+    use chrono::TimeZone;
+    use std::collections::HashMap;
+
+    fn session(id: &str, persona: &str, days: &[u32]) -> SerializedSession {
+        let turns = days
+            .iter()
+            .map(|&day| SerializedTurn {
+                user: format!("day {}", day),
+                assistant: "ok".to_string(),
+                timestamp: Utc.with_ymd_and_hms(2024, 5, day, 12, 0, 0).unwrap(),
+                metadata: HashMap::new(),
+                embedding: Some(vec![0.1, 0.2]),
+                sampling: None,
+            })
+            .collect();
+        SerializedSession {
+            id: id.to_string(),
+            persona_name: persona.to_string(),
+            turns,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            metadata: HashMap::new(),
+        }
+    }
+
+    fn concept(text: &str, source: &str, day: u32) -> SerializedConcept {
+        let at = Utc.with_ymd_and_hms(2024, 5, day, 12, 0, 0).unwrap();
+        SerializedConcept {
+            id: uuid::Uuid::new_v4().to_string(),
+            text: text.to_string(),
+            category: "facts".to_string(),
+            confidence: 0.8,
+            source: source.to_string(),
+            metadata: serde_json::Value::Null,
+            created_at: at,
+            updated_at: at,
+            usage_count: 0,
+            tags: Vec::new(),
+            knowledge_source: Default::default(),
+            version: 1,
+            previous_texts: Vec::new(),
+            language: None,
+            utterance: None,
+            evidence: Vec::new(),
+            last_used: None,
+        }
+    }
+
+    #[test]
+    fn test_scoped_export() {
+        let sessions = vec![session("s1", "Ada", &[1, 10]), session("s2", "Kant", &[2])];
+        let concepts = vec![
+            concept("User lives in Kazan", "s1", 1),
+            concept("User likes Hegel", "s2", 2),
+            concept("User drinks tea", "manual", 20),
+        ];
+
+        let knowledge = MemoryExportRequest {
+            scope: "semantic".parse().unwrap(),
+            since: NaiveDate::from_ymd_opt(2024, 5, 2),
+            ..Default::default()
+        }
+        .apply(sessions.clone(), concepts.clone());
+        assert!(knowledge.sessions.is_empty());
+        let texts: Vec<&str> = knowledge.concepts.iter().map(|c| c.text.as_str()).collect();
+        assert_eq!(texts, ["User likes Hegel", "User drinks tea"]);
+
+        let ada = MemoryExportRequest {
+            persona: Some("ada".to_string()),
+            until: NaiveDate::from_ymd_opt(2024, 5, 5),
+            include_embeddings: false,
+            ..Default::default()
+        }
+        .apply(sessions, concepts);
+        assert_eq!(ada.sessions.len(), 1);
+        assert_eq!(ada.sessions[0].turns.len(), 1);
+        assert_eq!(ada.sessions[0].turns[0].embedding, None);
+        assert_eq!(ada.concepts.len(), 1);
+        assert_eq!(ada.concepts[0].text, "User lives in Kazan");
+    }
+}
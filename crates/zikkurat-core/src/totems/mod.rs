@@ -0,0 +1,16 @@
+#![allow(dead_code)]
+
+pub mod consent;
+pub mod context;
+pub mod documents;
+pub mod episodic;
+pub mod grounding;
+pub mod jobs;
+pub mod language;
+pub mod maintenance;
+pub mod memory_export;
+pub mod quota;
+pub mod retrieval;
+pub mod semantic;
+pub mod significance;
+pub mod user;
@@ -0,0 +1,266 @@
+//! ⏳ Квоты пользователей
+//!
+//! Публичному боту нужен предел: столько-то запросов в час и токенов в сутки
+//! на пользователя. Окна скользящие - старые запросы выпадают из них по одному,
+//! а не по границе часа. Лимиты задаются общими и переопределяются для
+//! отдельных пользователей; при превышении персона отвечает, когда вернуться,
+//! а модель не вызывается.
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Duration, Utc};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::path::Path;
+
+use crate::totems::language::Language;
+
+/// Лимиты одного пользователя; `None` - без ограничения
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QuotaLimits {
+    #[serde(default)]
+    pub requests_per_hour: Option<u32>,
+    /// Токены промпта и ответа
+    #[serde(default)]
+    pub tokens_per_day: Option<u64>,
+}
+
+impl QuotaLimits {
+    /// Поля `self`, недостающие берутся из `fallback`
+    pub fn or(self, fallback: QuotaLimits) -> QuotaLimits {
+        QuotaLimits {
+            requests_per_hour: self.requests_per_hour.or(fallback.requests_per_hour),
+            tokens_per_day: self.tokens_per_day.or(fallback.tokens_per_day),
+        }
+    }
+
+    pub fn is_unlimited(&self) -> bool {
+        self.requests_per_hour.is_none() && self.tokens_per_day.is_none()
+    }
+}
+
+/// Общие лимиты и переопределения по пользователям:
+/// `{"default": {"requests_per_hour": 30}, "users": {"alice": {"tokens_per_day": 200000}}}`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct QuotaConfig {
+    #[serde(default)]
+    pub default: QuotaLimits,
+    #[serde(default)]
+    pub users: HashMap<String, QuotaLimits>,
+}
+
+impl QuotaConfig {
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path).with_context(|| format!("Failed to read {:?}", path))?;
+        serde_json::from_str(&content).with_context(|| format!("Failed to parse {:?}", path))
+    }
+
+    /// Лимиты пользователя: его поля поверх общих
+    pub fn limits_for(&self, user: &str) -> QuotaLimits {
+        self.users.get(user).copied().unwrap_or_default().or(self.default)
+    }
+
+    pub fn is_unlimited(&self) -> bool {
+        self.default.is_unlimited() && self.users.values().all(QuotaLimits::is_unlimited)
+    }
+}
+
+/// Квота исчерпана
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QuotaExceeded {
+    Requests { limit: u32, retry_after: Duration },
+    Tokens { limit: u64, retry_after: Duration },
+}
+
+impl QuotaExceeded {
+    /// Через сколько можно вернуться
+    pub fn retry_after(&self) -> Duration {
+        match self {
+            QuotaExceeded::Requests { retry_after, .. } | QuotaExceeded::Tokens { retry_after, .. } => *retry_after,
+        }
+    }
+
+    /// Ответ персоны вместо генерации, на языке пользователя
+    pub fn reply(&self, language: Option<Language>) -> String {
+        let wait = format_wait(self.retry_after(), language);
+        match (self, language) {
+            (QuotaExceeded::Requests { limit, .. }, Some(Language::Ru)) => format!(
+                "Давай сделаем паузу: вопросы на этот час закончились (лимит {}). Возвращайся через {} - продолжим с того же места.",
+                limit, wait
+            ),
+            (QuotaExceeded::Tokens { .. }, Some(Language::Ru)) => format!(
+                "На сегодня мы наговорили всё, что мне положено. Возвращайся через {} - продолжим с того же места.",
+                wait
+            ),
+            (QuotaExceeded::Requests { limit, .. }, _) => format!(
+                "Let's take a short break: you've used this hour's questions (limit {}). Come back in {} and we'll pick up where we left off.",
+                limit, wait
+            ),
+            (QuotaExceeded::Tokens { .. }, _) => format!(
+                "We've talked through my daily allowance. Come back in {} and we'll pick up where we left off.",
+                wait
+            ),
+        }
+    }
+}
+
+impl std::fmt::Display for QuotaExceeded {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            QuotaExceeded::Requests { limit, retry_after } => {
+                write!(f, "{} requests/hour exceeded, retry in {}s", limit, retry_after.num_seconds())
+            }
+            QuotaExceeded::Tokens { limit, retry_after } => {
+                write!(f, "{} tokens/day exceeded, retry in {}s", limit, retry_after.num_seconds())
+            }
+        }
+    }
+}
+
+/// «5 min», «2 h 10 min»; не меньше минуты
+fn format_wait(wait: Duration, language: Option<Language>) -> String {
+    let minutes = ((wait.num_seconds() + 59) / 60).max(1);
+    let (h, m) = match language {
+        Some(Language::Ru) => ("ч", "мин"),
+        _ => ("h", "min"),
+    };
+    match (minutes / 60, minutes % 60) {
+        (0, mins) => format!("{} {}", mins, m),
+        (hours, 0) => format!("{} {}", hours, h),
+        (hours, mins) => format!("{} {} {} {}", hours, h, mins, m),
+    }
+}
+
+/// Запросы и токены пользователя внутри окон
+#[derive(Debug, Default)]
+struct Usage {
+    requests: VecDeque<DateTime<Utc>>,
+    tokens: VecDeque<(DateTime<Utc>, u64)>,
+}
+
+impl Usage {
+    fn expire(&mut self, now: DateTime<Utc>) {
+        while self.requests.front().is_some_and(|t| now - *t >= Duration::hours(1)) {
+            self.requests.pop_front();
+        }
+        while self.tokens.front().is_some_and(|(t, _)| now - *t >= Duration::days(1)) {
+            self.tokens.pop_front();
+        }
+    }
+
+    fn tokens_used(&self) -> u64 {
+        self.tokens.iter().map(|(_, n)| n).sum()
+    }
+
+    fn check(&self, limits: QuotaLimits, now: DateTime<Utc>) -> std::result::Result<(), QuotaExceeded> {
+        if let Some(limit) = limits.requests_per_hour {
+            if self.requests.len() >= limit as usize {
+                // освобождается место, когда выпадает запрос, после которого осталось limit - 1
+                let freeing = self.requests[self.requests.len() - limit as usize];
+                return Err(QuotaExceeded::Requests {
+                    limit,
+                    retry_after: freeing + Duration::hours(1) - now,
+                });
+            }
+        }
+        if let Some(limit) = limits.tokens_per_day {
+            let mut used = self.tokens_used();
+            if used >= limit {
+                let mut retry_at = now;
+                for (t, n) in &self.tokens {
+                    used -= n;
+                    retry_at = *t + Duration::days(1);
+                    if used < limit {
+                        break;
+                    }
+                }
+                return Err(QuotaExceeded::Tokens {
+                    limit,
+                    retry_after: retry_at - now,
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Учёт квот всех пользователей
+#[derive(Debug, Default)]
+pub struct QuotaTracker {
+    config: QuotaConfig,
+    usage: Mutex<HashMap<String, Usage>>,
+}
+
+impl QuotaTracker {
+    pub fn new(config: QuotaConfig) -> Self {
+        Self {
+            config,
+            usage: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn config(&self) -> &QuotaConfig {
+        &self.config
+    }
+
+    /// Пропускает запрос и засчитывает его, либо говорит, когда вернуться
+    pub fn admit(&self, user: &str, now: DateTime<Utc>) -> std::result::Result<(), QuotaExceeded> {
+        let limits = self.config.limits_for(user);
+        if limits.is_unlimited() {
+            return Ok(());
+        }
+        let mut usage = self.usage.lock();
+        let usage = usage.entry(user.to_string()).or_default();
+        usage.expire(now);
+        usage.check(limits, now)?;
+        usage.requests.push_back(now);
+        Ok(())
+    }
+
+    /// Засчитывает токены ответа на пропущенный запрос
+    pub fn record_tokens(&self, user: &str, tokens: u64, now: DateTime<Utc>) {
+        if self.config.limits_for(user).tokens_per_day.is_none() {
+            return;
+        }
+        let mut usage = self.usage.lock();
+        usage.entry(user.to_string()).or_default().tokens.push_back((now, tokens));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quota_windows() {
+        let config: QuotaConfig = serde_json::from_str(
+            r#"{"default": {"requests_per_hour": 2, "tokens_per_day": 1000},
+                "users": {"vip": {"requests_per_hour": 100}}}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            config.limits_for("vip"),
+            QuotaLimits { requests_per_hour: Some(100), tokens_per_day: Some(1000) }
+        );
+
+        let tracker = QuotaTracker::new(config);
+        let start = Utc::now();
+        assert!(tracker.admit("anna", start).is_ok());
+        assert!(tracker.admit("anna", start + Duration::minutes(10)).is_ok());
+        let exceeded = tracker.admit("anna", start + Duration::minutes(20)).unwrap_err();
+        assert_eq!(exceeded.retry_after(), Duration::minutes(40));
+        assert!(exceeded.reply(Some(Language::Ru)).contains("40 мин"));
+        assert!(tracker.admit("vip", start).is_ok());
+
+        // окно скользящее: первый запрос выпал через час
+        assert!(tracker.admit("anna", start + Duration::minutes(61)).is_ok());
+
+        tracker.record_tokens("bob", 600, start);
+        tracker.record_tokens("bob", 500, start + Duration::hours(2));
+        let exceeded = tracker.admit("bob", start + Duration::hours(3)).unwrap_err();
+        assert!(matches!(exceeded, QuotaExceeded::Tokens { limit: 1000, .. }));
+        assert_eq!(exceeded.retry_after(), Duration::hours(21));
+        assert!(exceeded.reply(None).contains("21 h"));
+        assert!(tracker.admit("bob", start + Duration::hours(24)).is_ok());
+    }
+}
@@ -0,0 +1,9 @@
+#![allow(dead_code)]
+
+pub mod config;
+pub mod recall_cache;
+pub mod vector_store;
+
+pub use config::{DedupPolicy, RetrievalConfig, RetrievalOverrides};
+pub use recall_cache::RecallCache;
+pub use vector_store::{MemoryEntry, MemoryType, RankingBoost, VectorStore};
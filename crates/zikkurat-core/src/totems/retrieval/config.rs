@@ -0,0 +1,177 @@
+//! 🎚️ Настройки поиска воспоминаний
+//!
+//! Порог 0.3 и бонус +0.1 за совпадение ключевых слов в
+//! [`find_similar_dialogues`](crate::totems::episodic::DialogueManager::find_similar_dialogues)
+//! были зашиты в код. Теперь они в [`RetrievalConfig`]: раздел `retrieval` в
+//! `--system-config`, который архетип может поправить своим `retrieval`
+//! ([`RetrievalOverrides`]). Персона-справочник поднимает `min_similarity` ради
+//! точности, болтливая опускает его ради полноты.
+//!
+//! Оценка воспоминания:
+//! `max(vector_weight * сходство, keyword_weight * (доля слов + keyword_bonus))`
+//! плюс прибавка за язык и [`RankingBoost`] за свежесть и частоту обращений.
+//! Значения по умолчанию дают прежний поиск.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use super::vector_store::{MemoryEntry, RankingBoost};
+
+/// Какие найденные записи считаются повторами
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DedupPolicy {
+    /// Один ход сессии - одно воспоминание (ход с кодом находится и по индексу кода)
+    #[default]
+    Turn,
+    /// Вдобавок один и тот же вопрос из разных сессий показывается один раз
+    Question,
+    /// Без отсева повторов
+    Off,
+}
+
+/// Веса и пороги поиска воспоминаний
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RetrievalConfig {
+    /// Множитель косинусного сходства
+    pub vector_weight: f32,
+    /// Множитель keyword-оценки
+    pub keyword_weight: f32,
+    /// Прибавка к доле найденных ключевых слов, если нашлось хоть одно
+    pub keyword_bonus: f32,
+    /// Прибавка только что сказанному или вспомненному; убывает вдвое каждые
+    /// `recency_half_life_days`. 0 - возраст не важен
+    pub recency_boost: f32,
+    pub recency_half_life_days: f32,
+    /// Предел прибавки часто вспоминаемым ходам. 0 - частота не важна
+    pub frequency_boost: f32,
+    /// Воспоминания с оценкой ниже не попадают в промпт
+    pub min_similarity: f32,
+    pub dedup: DedupPolicy,
+}
+
+impl Default for RetrievalConfig {
+    fn default() -> Self {
+        Self {
+            vector_weight: 1.0,
+            keyword_weight: 1.0,
+            keyword_bonus: 0.1,
+            recency_boost: 0.0,
+            recency_half_life_days: 30.0,
+            frequency_boost: 0.0,
+            min_similarity: 0.3,
+            dedup: DedupPolicy::default(),
+        }
+    }
+}
+
+impl RetrievalConfig {
+    /// Оценка записи без прибавки за язык; `keyword` - доля найденных ключевых слов
+    pub fn score(&self, similarity: f32, keyword: Option<f32>, entry: &MemoryEntry, now: DateTime<Utc>) -> f32 {
+        let keyword = keyword.map_or(0.0, |share| self.keyword_weight * (share + self.keyword_bonus));
+        (self.vector_weight * similarity).max(keyword) + self.ranking().boost(entry, now)
+    }
+
+    /// Прибавка за свежесть и частоту для векторного хранилища
+    pub fn ranking(&self) -> RankingBoost {
+        RankingBoost {
+            recency: self.recency_boost,
+            half_life_days: self.recency_half_life_days,
+            frequency: self.frequency_boost,
+        }
+    }
+
+    /// Ключ повтора; `None` - запись не сравнивается с другими
+    pub fn dedup_key(&self, entry: &MemoryEntry) -> Option<String> {
+        let field = |key: &str| entry.metadata.get(key).map(String::as_str).unwrap_or("");
+        match self.dedup {
+            DedupPolicy::Turn => Some(format!("{}-{}", field("session_id"), field("turn"))),
+            DedupPolicy::Question => {
+                let question = entry.metadata.get("user_query").unwrap_or(&entry.text);
+                Some(question.trim().to_lowercase())
+            }
+            DedupPolicy::Off => None,
+        }
+    }
+}
+
+/// Поправки архетипа к общим настройкам: заданные поля заменяют общие
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RetrievalOverrides {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub vector_weight: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub keyword_weight: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub keyword_bonus: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub recency_boost: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub recency_half_life_days: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub frequency_boost: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_similarity: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dedup: Option<DedupPolicy>,
+}
+
+impl RetrievalOverrides {
+    /// Общие настройки с поправками
+    pub fn apply(&self, base: &RetrievalConfig) -> RetrievalConfig {
+        RetrievalConfig {
+            vector_weight: self.vector_weight.unwrap_or(base.vector_weight),
+            keyword_weight: self.keyword_weight.unwrap_or(base.keyword_weight),
+            keyword_bonus: self.keyword_bonus.unwrap_or(base.keyword_bonus),
+            recency_boost: self.recency_boost.unwrap_or(base.recency_boost),
+            recency_half_life_days: self.recency_half_life_days.unwrap_or(base.recency_half_life_days),
+            frequency_boost: self.frequency_boost.unwrap_or(base.frequency_boost),
+            min_similarity: self.min_similarity.unwrap_or(base.min_similarity),
+            dedup: self.dedup.unwrap_or(base.dedup),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::totems::retrieval::MemoryType;
+    use chrono::Duration;
+    use uuid::Uuid;
+
+    #[test]
+    fn test_persona_overrides_system_retrieval() -> anyhow::Result<()> {
+        let now = Utc::now();
+        let mut old = MemoryEntry::new("old".to_string(), vec![], MemoryType::ShortTerm);
+        old.timestamp = now - Duration::days(10_000);
+        let mut fresh = old.clone();
+        fresh.timestamp = now;
+
+        // defaults keep the old scoring: max(similarity, keywords + 0.1), cutoff 0.3
+        let default = RetrievalConfig::default();
+        assert_eq!(default.score(0.25, Some(0.5), &old, now), 0.6);
+        assert_eq!(default.score(0.7, None, &fresh, now), 0.7);
+
+        let system: RetrievalConfig = serde_json::from_str(r#"{"min_similarity": 0.5, "recency_boost": 0.2}"#)?;
+        assert_eq!(system.keyword_bonus, 0.1);
+        let overrides: RetrievalOverrides = serde_json::from_str(r#"{"keyword_weight": 0.0, "dedup": "question"}"#)?;
+        let persona = overrides.apply(&system);
+        assert_eq!(persona.min_similarity, 0.5);
+        assert_eq!(persona.dedup, DedupPolicy::Question);
+        assert_eq!(persona.score(0.25, Some(1.0), &old, now), 0.25);
+        assert!((persona.score(0.25, None, &fresh, now) - 0.45).abs() < 1e-6);
+
+        let entry = |session: Uuid, question: &str| {
+            MemoryEntry::new(question.to_string(), vec![], MemoryType::Episodic { session_id: session, turn: 0 })
+                .with_metadata("session_id".to_string(), session.to_string())
+                .with_metadata("turn".to_string(), "0".to_string())
+                .with_metadata("user_query".to_string(), question.to_string())
+        };
+        let (a, b) = (entry(Uuid::new_v4(), "Как дела?"), entry(Uuid::new_v4(), "как дела? "));
+        assert_ne!(system.dedup_key(&a), system.dedup_key(&b));
+        assert_eq!(persona.dedup_key(&a), persona.dedup_key(&b));
+        Ok(())
+    }
+}
@@ -0,0 +1,166 @@
+//! ♻️ Кэш воспоминаний между ходами
+//!
+//! Уточняющие вопросы в одной теме раз за разом находят почти одно и то же.
+//! Кэш помнит кандидатов последнего полного поиска и эмбеддинг запроса, с
+//! которым они найдены. Если новый запрос ближе к нему, чем `threshold`,
+//! кандидаты переоцениваются под новый запрос, а поиск идёт только по ходам,
+//! добавленным в текущую сессию после кэширования (дозаполнение).
+
+use uuid::Uuid;
+
+use super::vector_store::{cosine_similarity, MemoryEntry};
+
+/// Сходство с запросом полного поиска, начиная с которого кандидаты переиспользуются
+pub const DEFAULT_REUSE_THRESHOLD: f32 = 0.9;
+
+/// Кандидаты последнего полного поиска
+#[derive(Debug, Clone)]
+struct CachedRecall {
+    query: Vec<f32>,
+    candidates: Vec<MemoryEntry>,
+    session_id: Uuid,
+    /// Ходов текущей сессии в хранилище на момент кэширования
+    session_len: usize,
+    /// Записей в хранилище на момент кэширования
+    store_len: usize,
+}
+
+/// Переиспользуемые кандидаты: дозаполнить ходами сессии начиная с `top_up_from`
+#[derive(Debug)]
+pub struct CachedCandidates {
+    pub candidates: Vec<MemoryEntry>,
+    pub top_up_from: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct RecallCache {
+    threshold: f32,
+    last: Option<CachedRecall>,
+    hits: u64,
+    misses: u64,
+}
+
+impl Default for RecallCache {
+    fn default() -> Self {
+        Self::new(DEFAULT_REUSE_THRESHOLD)
+    }
+}
+
+impl RecallCache {
+    /// `threshold >= 1.0` отключает переиспользование
+    pub fn new(threshold: f32) -> Self {
+        Self {
+            threshold,
+            last: None,
+            hits: 0,
+            misses: 0,
+        }
+    }
+
+    pub fn set_threshold(&mut self, threshold: f32) {
+        self.threshold = threshold;
+        self.last = None;
+    }
+
+    /// Кандидаты для запроса `query`, если он продолжает тему закэшированного.
+    /// Хранилище, изменившееся не только новыми ходами текущей сессии, сбрасывает кэш
+    pub fn reuse(
+        &mut self,
+        query: &[f32],
+        session_id: Uuid,
+        session_len: usize,
+        store_len: usize,
+    ) -> Option<CachedCandidates> {
+        let reusable = self.threshold < 1.0
+            && self.last.as_ref().is_some_and(|last| {
+                last.session_id == session_id
+                    && session_len >= last.session_len
+                    && store_len == last.store_len + (session_len - last.session_len)
+                    && cosine_similarity(query, &last.query) > self.threshold
+            });
+        if !reusable {
+            self.misses += 1;
+            self.last = None;
+            return None;
+        }
+        self.hits += 1;
+        let last = self.last.as_ref()?;
+        Some(CachedCandidates {
+            candidates: last.candidates.clone(),
+            top_up_from: last.session_len,
+        })
+    }
+
+    /// Запоминает кандидатов полного поиска
+    pub fn store(
+        &mut self,
+        query: Vec<f32>,
+        candidates: Vec<MemoryEntry>,
+        session_id: Uuid,
+        session_len: usize,
+        store_len: usize,
+    ) {
+        self.last = Some(CachedRecall {
+            query,
+            candidates,
+            session_id,
+            session_len,
+            store_len,
+        });
+    }
+
+    /// Добавляет дозаполненных кандидатов; запрос полного поиска остаётся якорем темы,
+    /// чтобы медленный дрейф разговора не держал кэш вечно
+    pub fn extend(&mut self, top_up: &[MemoryEntry], session_len: usize, store_len: usize) {
+        if let Some(last) = self.last.as_mut() {
+            last.candidates.extend_from_slice(top_up);
+            last.session_len = session_len;
+            last.store_len = store_len;
+        }
+    }
+
+    pub fn invalidate(&mut self) {
+        self.last = None;
+    }
+
+    /// (попадания, промахи)
+    pub fn stats(&self) -> (u64, u64) {
+        (self.hits, self.misses)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::totems::retrieval::MemoryType;
+
+    fn entry(text: &str, embedding: Vec<f32>) -> MemoryEntry {
+        MemoryEntry::new(text.to_string(), embedding, MemoryType::ShortTerm)
+    }
+
+    #[test]
+    fn test_reuse_only_on_same_topic() {
+        let session = Uuid::new_v4();
+        let mut cache = RecallCache::new(0.9);
+        assert!(cache.reuse(&[1.0, 0.0], session, 2, 10).is_none());
+        cache.store(vec![1.0, 0.0], vec![entry("rust", vec![1.0, 0.0])], session, 2, 10);
+
+        // a follow-up after one more turn: reuse and top up from that turn
+        let hit = cache.reuse(&[0.99, 0.1], session, 3, 11).unwrap();
+        assert_eq!((hit.candidates.len(), hit.top_up_from), (1, 2));
+        cache.extend(&[entry("cargo", vec![0.9, 0.1])], 3, 11);
+        assert_eq!(cache.reuse(&[1.0, 0.05], session, 3, 11).unwrap().candidates.len(), 2);
+
+        // another topic, another session or a changed store: full search
+        assert!(cache.reuse(&[0.0, 1.0], session, 3, 11).is_none());
+        cache.store(vec![1.0, 0.0], Vec::new(), session, 3, 11);
+        assert!(cache.reuse(&[1.0, 0.0], Uuid::new_v4(), 3, 11).is_none());
+        cache.store(vec![1.0, 0.0], Vec::new(), session, 3, 11);
+        assert!(cache.reuse(&[1.0, 0.0], session, 3, 9).is_none());
+        assert_eq!(cache.stats(), (2, 4));
+
+        cache.set_threshold(1.0);
+        cache.store(vec![1.0, 0.0], Vec::new(), session, 3, 11);
+        assert!(cache.reuse(&[1.0, 0.0], session, 3, 11).is_none());
+    }
+}
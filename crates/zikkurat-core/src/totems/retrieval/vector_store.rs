@@ -0,0 +1,903 @@
+//! 🜃 Уровень 2: Тотемы Памяти - Векторный поиск
+//!
+//! In-memory векторная база данных для поиска семантически схожих записей
+//! Оптимизирована для cosine similarity и быстрого извлечения
+
+#![allow(dead_code)]
+
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::time::Instant;
+use uuid::Uuid;
+
+use crate::logos::metrics;
+use crate::utils::clock::SharedClock;
+
+/// Тип памяти для классификации записей
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub enum MemoryType {
+    /// Эпизодическая память (диалоги, события)
+    Episodic { session_id: Uuid, turn: usize },
+    /// Семантическая память (знания, концепты)
+    Semantic { category: String },
+    /// Кратковременная память (текущий контекст)
+    ShortTerm,
+    /// Кусок документа пользователя (см. [`crate::totems::documents`])
+    Document { document_id: Uuid, chunk: usize },
+}
+
+/// Запись в векторной базе данных
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MemoryEntry {
+    /// Уникальный идентификатор записи
+    pub id: Uuid,
+    /// Исходный текст
+    pub text: String,
+    /// Векторное представление
+    pub embedding: Vec<f32>,
+    /// Метаданные для дополнительной информации
+    pub metadata: HashMap<String, String>,
+    /// Временная метка создания
+    pub timestamp: chrono::DateTime<chrono::Utc>,
+    /// Тип памяти
+    pub memory_type: MemoryType,
+    /// Когда запись последний раз попала в ответ (`None` - ни разу)
+    #[serde(default)]
+    pub last_accessed: Option<DateTime<Utc>>,
+    /// Сколько раз запись попадала в ответ
+    #[serde(default)]
+    pub access_count: u32,
+}
+
+impl MemoryEntry {
+    /// Создает новую запись
+    pub fn new(text: String, embedding: Vec<f32>, memory_type: MemoryType) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            text,
+            embedding,
+            metadata: HashMap::new(),
+            timestamp: chrono::Utc::now(),
+            memory_type,
+            last_accessed: None,
+            access_count: 0,
+        }
+    }
+
+    /// Отмечает, что запись попала в ответ
+    pub fn record_access(&mut self, at: DateTime<Utc>) {
+        self.last_accessed = Some(at);
+        self.access_count = self.access_count.saturating_add(1);
+    }
+
+    /// Последнее обращение, а без обращений - создание записи
+    pub fn last_seen(&self) -> DateTime<Utc> {
+        self.last_accessed.unwrap_or(self.timestamp)
+    }
+
+    /// Добавляет метаданные
+    pub fn with_metadata(mut self, key: String, value: String) -> Self {
+        self.metadata.insert(key, value);
+        self
+    }
+}
+
+/// Число обращений, дающее половину прибавки за частоту
+const FREQUENCY_HALF_COUNT: f32 = 3.0;
+
+/// Прибавка к косинусному сходству за свежесть и частоту обращений.
+/// Нули (по умолчанию) - ранжирование только по сходству
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RankingBoost {
+    /// Прибавка записи, к которой только что обращались; убывает вдвое каждые `half_life_days`
+    pub recency: f32,
+    pub half_life_days: f32,
+    /// Предел прибавки за обращения: три обращения дают половину
+    pub frequency: f32,
+}
+
+impl RankingBoost {
+    pub fn is_off(&self) -> bool {
+        self.recency == 0.0 && self.frequency == 0.0
+    }
+
+    /// Прибавка записи на момент `now`
+    pub fn boost(&self, entry: &MemoryEntry, now: DateTime<Utc>) -> f32 {
+        let recency = if self.recency != 0.0 && self.half_life_days > 0.0 {
+            let days = (now - entry.last_seen()).num_seconds().max(0) as f32 / 86_400.0;
+            self.recency * 0.5f32.powf(days / self.half_life_days)
+        } else {
+            0.0
+        };
+        let count = entry.access_count as f32;
+        recency + self.frequency * count / (count + FREQUENCY_HALF_COUNT)
+    }
+}
+
+/// Прибавка вместе с часами, от которых считается свежесть
+#[derive(Clone)]
+struct Ranking {
+    boost: RankingBoost,
+    clock: SharedClock,
+}
+
+impl std::fmt::Debug for Ranking {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.boost.fmt(f)
+    }
+}
+
+/// Ключ шарда: эпизодические записи шардируются по сессии, куски документов - по документу,
+/// остальные - по типу памяти
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ShardKey {
+    Session(Uuid),
+    Semantic,
+    ShortTerm,
+    Document(Uuid),
+}
+
+impl ShardKey {
+    /// Шард, в который попадает запись данного типа
+    pub fn of(memory_type: &MemoryType) -> Self {
+        match memory_type {
+            MemoryType::Episodic { session_id, .. } => ShardKey::Session(*session_id),
+            MemoryType::Semantic { .. } => ShardKey::Semantic,
+            MemoryType::ShortTerm => ShardKey::ShortTerm,
+            MemoryType::Document { document_id, .. } => ShardKey::Document(*document_id),
+        }
+    }
+
+    /// Совпадает ли вид шарда с типом памяти (без учёта сессии)
+    fn same_kind(&self, memory_type: &MemoryType) -> bool {
+        matches!(
+            (self, memory_type),
+            (ShardKey::Session(_), MemoryType::Episodic { .. })
+                | (ShardKey::Semantic, MemoryType::Semantic { .. })
+                | (ShardKey::ShortTerm, MemoryType::ShortTerm)
+                | (ShardKey::Document(_), MemoryType::Document { .. })
+        )
+    }
+
+    fn file_name(&self) -> String {
+        match self {
+            ShardKey::Session(id) => format!("session-{}.json", id),
+            ShardKey::Semantic => "semantic.json".to_string(),
+            ShardKey::ShortTerm => "short_term.json".to_string(),
+            ShardKey::Document(id) => format!("document-{}.json", id),
+        }
+    }
+
+    /// Ключ по имени файла шарда; чужие файлы каталога - `None`
+    fn from_file_name(name: &str) -> Option<Self> {
+        let stem = name.strip_suffix(".json")?;
+        if let Some(id) = stem.strip_prefix("session-") {
+            return Uuid::parse_str(id).ok().map(ShardKey::Session);
+        }
+        if let Some(id) = stem.strip_prefix("document-") {
+            return Uuid::parse_str(id).ok().map(ShardKey::Document);
+        }
+        match stem {
+            "semantic" => Some(ShardKey::Semantic),
+            "short_term" => Some(ShardKey::ShortTerm),
+            _ => None,
+        }
+    }
+}
+
+/// Шард записей. Шарды, открытые через [`VectorStore::open_sharded`],
+/// читаются с диска при первом обращении
+#[derive(Debug, Clone, Default)]
+struct Shard {
+    entries: OnceLock<Loaded>,
+    /// Файл шарда, пока он не загружен
+    path: Option<PathBuf>,
+    /// Число записей по индексу (до загрузки)
+    indexed_len: usize,
+}
+
+/// Записи шарда после первого обращения
+#[derive(Debug, Clone, Default)]
+struct Loaded {
+    entries: Vec<MemoryEntry>,
+    /// Файл не прочитался: записи пусты, и при сохранении файл не перезаписывается
+    failed: bool,
+}
+
+impl Shard {
+    fn lazy(path: PathBuf, len: usize) -> Self {
+        Self {
+            entries: OnceLock::new(),
+            path: Some(path),
+            indexed_len: len,
+        }
+    }
+
+    fn load(&self) -> &Loaded {
+        self.entries.get_or_init(|| {
+            let Some(path) = &self.path else {
+                return Loaded::default();
+            };
+            match std::fs::read_to_string(path)
+                .map_err(anyhow::Error::from)
+                .and_then(|content| Ok(serde_json::from_str(&content)?))
+            {
+                Ok(entries) => Loaded { entries, failed: false },
+                Err(e) => {
+                    tracing::warn!("Failed to load shard {}: {}", path.display(), e);
+                    Loaded { entries: Vec::new(), failed: true }
+                }
+            }
+        })
+    }
+
+    fn entries(&self) -> &[MemoryEntry] {
+        &self.load().entries
+    }
+
+    fn entries_mut(&mut self) -> &mut Vec<MemoryEntry> {
+        self.load();
+        &mut self.entries.get_mut().expect("shard is loaded").entries
+    }
+
+    fn loaded(&self) -> Option<&[MemoryEntry]> {
+        self.entries.get().filter(|loaded| !loaded.failed).map(|loaded| loaded.entries.as_slice())
+    }
+
+    fn is_loaded(&self) -> bool {
+        self.loaded().is_some()
+    }
+
+    /// Шард, который не прочитался, считается по индексу: его файл остаётся как есть
+    fn len(&self) -> usize {
+        self.loaded().map_or(self.indexed_len, <[MemoryEntry]>::len)
+    }
+}
+
+/// Индекс шардов на диске (`shards.json`)
+#[derive(Debug, Serialize, Deserialize)]
+struct ShardIndex {
+    dimension: usize,
+    shards: Vec<(ShardKey, usize)>,
+}
+
+const SHARD_INDEX_FILE: &str = "shards.json";
+
+/// In-memory векторное хранилище с поиском по косинусному сходству.
+/// Записи разложены по шардам ([`ShardKey`]): удаление сессии - O(1),
+/// поиск внутри сессии не трогает остальные записи
+#[derive(Debug, Clone)]
+pub struct VectorStore {
+    /// Векторные записи по шардам
+    shards: HashMap<ShardKey, Shard>,
+    /// Размерность векторов
+    dimension: usize,
+    /// Общее количество запросов к хранилищу
+    query_count: u64,
+    /// Прибавка за свежесть и частоту; `None` - только сходство
+    ranking: Option<Ranking>,
+}
+
+impl VectorStore {
+    /// Создает новое хранилище
+    pub fn new(dimension: usize) -> Self {
+        Self {
+            shards: HashMap::new(),
+            dimension,
+            query_count: 0,
+            ranking: None,
+        }
+    }
+
+    /// Включает прибавку за свежесть и частоту обращений в поиске
+    pub fn set_ranking_boost(&mut self, boost: RankingBoost, clock: SharedClock) {
+        self.ranking = (!boost.is_off()).then_some(Ranking { boost, clock });
+    }
+
+    /// Отмечает обращение к записи `id`; `false` - такой записи нет
+    pub fn record_access(&mut self, memory_type: &MemoryType, id: Uuid, at: DateTime<Utc>) -> bool {
+        let Some(shard) = self.shards.get_mut(&ShardKey::of(memory_type)) else {
+            return false;
+        };
+        match shard.entries_mut().iter_mut().find(|entry| entry.id == id) {
+            Some(entry) => {
+                entry.record_access(at);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Добавляет запись в хранилище
+    pub fn add(&mut self, entry: MemoryEntry) -> Result<()> {
+        // Проверяем размерность вектора
+        if entry.embedding.len() != self.dimension {
+            return Err(anyhow!(
+                "Embedding dimension mismatch: expected {}, got {}",
+                self.dimension,
+                entry.embedding.len()
+            ));
+        }
+
+        self.shards
+            .entry(ShardKey::of(&entry.memory_type))
+            .or_default()
+            .entries_mut()
+            .push(entry);
+        Ok(())
+    }
+
+    /// Добавляет несколько записей (batch operation)
+    pub fn add_batch(&mut self, entries: Vec<MemoryEntry>) -> Result<()> {
+        for entry in entries {
+            self.add(entry)?;
+        }
+        Ok(())
+    }
+
+    /// Ищет наиболее похожие записи по косинусному сходству
+    pub fn search(&mut self, query_embedding: &[f32], top_k: usize) -> Vec<(f32, &MemoryEntry)> {
+        self.query_count += 1;
+
+        if query_embedding.len() != self.dimension {
+            return Vec::new();
+        }
+
+        rank(query_embedding, self.entries(), top_k, self.ranking.as_ref())
+    }
+
+    /// Ищет записи по типу памяти
+    pub fn search_by_type(
+        &mut self,
+        query_embedding: &[f32],
+        memory_type: &MemoryType,
+        top_k: usize,
+    ) -> Vec<(f32, &MemoryEntry)> {
+        self.query_count += 1;
+
+        if query_embedding.len() != self.dimension {
+            return Vec::new();
+        }
+
+        rank(query_embedding, self.get_by_type(memory_type), top_k, self.ranking.as_ref())
+    }
+
+    /// Ищет среди записей, прошедших фильтр (например, по сессии и ходу или метаданным)
+    pub fn search_filtered(
+        &mut self,
+        query_embedding: &[f32],
+        filter: impl Fn(&MemoryEntry) -> bool,
+        top_k: usize,
+    ) -> Vec<(f32, &MemoryEntry)> {
+        self.query_count += 1;
+
+        if query_embedding.len() != self.dimension {
+            return Vec::new();
+        }
+
+        rank(query_embedding, self.entries().filter(|entry| filter(entry)), top_k, self.ranking.as_ref())
+    }
+
+    /// Ищет только среди записей одной сессии
+    pub fn search_session(
+        &mut self,
+        query_embedding: &[f32],
+        session_id: &Uuid,
+        top_k: usize,
+    ) -> Vec<(f32, &MemoryEntry)> {
+        self.query_count += 1;
+
+        if query_embedding.len() != self.dimension {
+            return Vec::new();
+        }
+
+        rank(query_embedding, self.get_session(session_id), top_k, self.ranking.as_ref())
+    }
+
+    /// Возвращает все записи указанного типа
+    pub fn get_by_type(&self, memory_type: &MemoryType) -> Vec<&MemoryEntry> {
+        self.shards
+            .iter()
+            .filter(|(key, _)| key.same_kind(memory_type))
+            .flat_map(|(_, shard)| shard.entries())
+            .collect()
+    }
+
+    /// Возвращает записи одной сессии в порядке добавления
+    pub fn get_session(&self, session_id: &Uuid) -> &[MemoryEntry] {
+        self.shards
+            .get(&ShardKey::Session(*session_id))
+            .map_or(&[], |shard| shard.entries())
+    }
+
+    /// Удаляет записи старше указанного времени
+    pub fn cleanup_old(&mut self, before: chrono::DateTime<chrono::Utc>) -> usize {
+        let mut removed = 0;
+        for shard in self.shards.values_mut() {
+            let entries = shard.entries_mut();
+            let initial_len = entries.len();
+            entries.retain(|entry| entry.timestamp > before);
+            removed += initial_len - entries.len();
+        }
+        self.shards.retain(|_, shard| shard.len() > 0);
+        removed
+    }
+
+    /// Удаляет записи по типу (для эпизодической памяти - все сессии)
+    pub fn clear_by_type(&mut self, memory_type: &MemoryType) -> usize {
+        let keys: Vec<ShardKey> = self
+            .shards
+            .keys()
+            .filter(|key| key.same_kind(memory_type))
+            .copied()
+            .collect();
+        keys.iter()
+            .filter_map(|key| self.shards.remove(key))
+            .map(|shard| shard.len())
+            .sum()
+    }
+
+    /// Удаляет записи одной сессии целиком
+    pub fn clear_session(&mut self, session_id: &Uuid) -> usize {
+        self.shards
+            .remove(&ShardKey::Session(*session_id))
+            .map_or(0, |shard| shard.len())
+    }
+
+    /// Удаляет куски одного документа
+    pub fn clear_document(&mut self, document_id: &Uuid) -> usize {
+        self.shards
+            .remove(&ShardKey::Document(*document_id))
+            .map_or(0, |shard| shard.len())
+    }
+
+    /// Статистика хранилища (не загружает ленивые шарды)
+    pub fn stats(&self) -> VectorStoreStats {
+        let mut episodic_count = 0;
+        let mut semantic_count = 0;
+        let mut short_term_count = 0;
+        let mut document_count = 0;
+
+        for (key, shard) in &self.shards {
+            match key {
+                ShardKey::Session(_) => episodic_count += shard.len(),
+                ShardKey::Semantic => semantic_count += shard.len(),
+                ShardKey::ShortTerm => short_term_count += shard.len(),
+                ShardKey::Document(_) => document_count += shard.len(),
+            }
+        }
+
+        VectorStoreStats {
+            total_entries: episodic_count + semantic_count + short_term_count + document_count,
+            episodic_count,
+            semantic_count,
+            short_term_count,
+            document_count,
+            session_shards: self.session_count(),
+            loaded_shards: self.shards.values().filter(|s| s.is_loaded()).count(),
+            dimension: self.dimension,
+            query_count: self.query_count,
+        }
+    }
+
+    /// Размер хранилища в байтах (приблизительно, только загруженные шарды)
+    pub fn size_bytes(&self) -> usize {
+        let base_size = std::mem::size_of::<VectorStore>();
+        let entries_size = self
+            .shards
+            .values()
+            .filter_map(Shard::loaded)
+            .flatten()
+            .map(|e| {
+                std::mem::size_of::<MemoryEntry>()
+                    + e.text.len()
+                    + e.embedding.len() * std::mem::size_of::<f32>()
+                    + e.metadata.len() * (std::mem::size_of::<String>() + 32) // примерно
+            })
+            .sum::<usize>();
+
+        base_size + entries_size
+    }
+
+    /// Очищает все записи
+    pub fn clear(&mut self) {
+        self.shards.clear();
+        self.query_count = 0;
+    }
+
+    /// Возвращает количество записей
+    pub fn len(&self) -> usize {
+        self.shards.values().map(Shard::len).sum()
+    }
+
+    /// Проверяет пустое ли хранилище
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Возвращает размерность векторов
+    pub fn dimension(&self) -> usize {
+        self.dimension
+    }
+
+    /// Количество сессий, у которых есть записи
+    pub fn session_count(&self) -> usize {
+        self.shards
+            .keys()
+            .filter(|key| matches!(key, ShardKey::Session(_)))
+            .count()
+    }
+
+    /// Возвращает итератор по всем записям (для персистентности)
+    pub fn entries(&self) -> impl Iterator<Item = &MemoryEntry> {
+        self.shards.values().flat_map(|shard| shard.entries())
+    }
+
+    /// Сохраняет шарды по файлам в `dir` вместе с индексом `shards.json`.
+    /// Незагруженные шарды уже лежат на диске и не перезаписываются; шард, файл
+    /// которого не прочитался, тоже: пустой список на его месте стёр бы записи
+    pub fn save_shards(&self, dir: &Path) -> Result<usize> {
+        std::fs::create_dir_all(dir)?;
+
+        let mut index = ShardIndex {
+            dimension: self.dimension,
+            shards: Vec::with_capacity(self.shards.len()),
+        };
+        for (key, shard) in &self.shards {
+            let path = dir.join(key.file_name());
+            let in_place = shard.path.as_deref() == Some(path.as_path());
+            if in_place && shard.entries.get().is_none() {
+                index.shards.push((*key, shard.len()));
+                continue;
+            }
+            let loaded = shard.load();
+            match &shard.path {
+                Some(source) if loaded.failed => {
+                    tracing::warn!("Shard {} is kept as it was: it failed to load", source.display());
+                    if !in_place {
+                        std::fs::copy(source, &path)?;
+                    }
+                }
+                _ => std::fs::write(&path, serde_json::to_string(&loaded.entries)?)?,
+            }
+            index.shards.push((*key, shard.len()));
+        }
+
+        // файлы удалённых сессий; остальные файлы каталога (каталог документов и т.п.) не трогаем
+        for entry in std::fs::read_dir(dir)? {
+            let name = entry?.file_name().to_string_lossy().to_string();
+            if ShardKey::from_file_name(&name).is_some_and(|key| !self.shards.contains_key(&key)) {
+                std::fs::remove_file(dir.join(&name))?;
+            }
+        }
+
+        std::fs::write(dir.join(SHARD_INDEX_FILE), serde_json::to_string_pretty(&index)?)?;
+        Ok(index.shards.len())
+    }
+
+    /// Открывает хранилище, сохранённое [`VectorStore::save_shards`]: читается
+    /// только индекс, шард загружается при первом обращении к его записям
+    pub fn open_sharded(dir: &Path) -> Result<Option<Self>> {
+        let index_path = dir.join(SHARD_INDEX_FILE);
+        if !index_path.exists() {
+            return Ok(None);
+        }
+
+        let index: ShardIndex = serde_json::from_str(&std::fs::read_to_string(&index_path)?)?;
+        let shards = index
+            .shards
+            .into_iter()
+            .map(|(key, len)| (key, Shard::lazy(dir.join(key.file_name()), len)))
+            .collect();
+
+        Ok(Some(Self {
+            shards,
+            dimension: index.dimension,
+            query_count: 0,
+            ranking: None,
+        }))
+    }
+}
+
+/// Ранжирует записи по косинусному сходству с запросом (плюс прибавка `ranking`)
+fn rank<'a>(
+    query_embedding: &[f32],
+    entries: impl IntoIterator<Item = &'a MemoryEntry>,
+    top_k: usize,
+    ranking: Option<&Ranking>,
+) -> Vec<(f32, &'a MemoryEntry)> {
+    let span = tracing::trace_span!("vector_search", top_k, candidates = tracing::field::Empty).entered();
+    let started = Instant::now();
+    let now = ranking.map(|r| r.clock.now());
+    let mut similarities: Vec<(f32, &MemoryEntry)> = entries
+        .into_iter()
+        .map(|entry| {
+            let similarity = cosine_similarity(query_embedding, &entry.embedding);
+            match (ranking, now) {
+                (Some(ranking), Some(now)) => (similarity + ranking.boost.boost(entry, now), entry),
+                _ => (similarity, entry),
+            }
+        })
+        .collect();
+    span.record("candidates", similarities.len());
+
+    // Сортируем по убыванию сходства
+    similarities.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(std::cmp::Ordering::Equal));
+    similarities.truncate(top_k);
+    metrics::record_search(started.elapsed());
+    similarities
+}
+
+/// Статистика векторного хранилища
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VectorStoreStats {
+    pub total_entries: usize,
+    pub episodic_count: usize,
+    pub semantic_count: usize,
+    pub short_term_count: usize,
+    #[serde(default)]
+    pub document_count: usize,
+    /// Количество сессионных шардов
+    pub session_shards: usize,
+    /// Шарды, загруженные в память
+    pub loaded_shards: usize,
+    pub dimension: usize,
+    pub query_count: u64,
+}
+
+impl VectorStoreStats {
+    /// Форматирует статистику для вывода
+    pub fn format(&self) -> String {
+        format!(
+            "📊 VectorStore Stats:\n   Entries: {} total ({} episodic, {} semantic, {} short-term, {} document)\n   Shards: {} sessions, {} loaded\n   Dimension: {}D\n   Queries: {}",
+            self.total_entries,
+            self.episodic_count,
+            self.semantic_count,
+            self.short_term_count,
+            self.document_count,
+            self.session_shards,
+            self.loaded_shards,
+            self.dimension,
+            self.query_count
+        )
+    }
+}
+
+/// Вычисляет косинусное сходство между двумя векторами
+pub fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    if a.len() != b.len() {
+        return 0.0;
+    }
+
+    let dot_product: f32 = a.iter().zip(b.iter()).map(|(x, y)| x * y).sum();
+    let norm_a: f32 = a.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let norm_b: f32 = b.iter().map(|x| x * x).sum::<f32>().sqrt();
+
+    if norm_a == 0.0 || norm_b == 0.0 {
+        return 0.0;
+    }
+
+    dot_product / (norm_a * norm_b)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cosine_similarity() {
+        let a = vec![1.0, 0.0, 0.0];
+        let b = vec![0.0, 1.0, 0.0];
+        let c = vec![1.0, 0.0, 0.0];
+        let d = vec![2.0, 0.0, 0.0];
+
+        assert_eq!(cosine_similarity(&a, &b), 0.0);
+        assert_eq!(cosine_similarity(&a, &c), 1.0);
+        assert_eq!(cosine_similarity(&a, &d), 1.0);
+    }
+
+    #[test]
+    fn test_vector_store_basic() {
+        let mut store = VectorStore::new(3);
+
+        let entry1 = MemoryEntry::new(
+            "hello".to_string(),
+            vec![1.0, 0.0, 0.0],
+            MemoryType::ShortTerm,
+        );
+
+        let entry2 = MemoryEntry::new(
+            "world".to_string(),
+            vec![0.0, 1.0, 0.0],
+            MemoryType::ShortTerm,
+        );
+
+        store.add(entry1).unwrap();
+        store.add(entry2).unwrap();
+
+        assert_eq!(store.len(), 2);
+        assert_eq!(store.dimension(), 3);
+    }
+
+    #[test]
+    fn test_search() {
+        let mut store = VectorStore::new(3);
+
+        store
+            .add(MemoryEntry::new(
+                "hello".to_string(),
+                vec![1.0, 0.0, 0.0],
+                MemoryType::ShortTerm,
+            ))
+            .unwrap();
+
+        store
+            .add(MemoryEntry::new(
+                "world".to_string(),
+                vec![0.0, 1.0, 0.0],
+                MemoryType::ShortTerm,
+            ))
+            .unwrap();
+
+        let results = store.search(&vec![1.0, 0.0, 0.0], 2);
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].0, 1.0); // Первое совпадение
+        assert_eq!(results[1].0, 0.0); // Второе совпадение
+    }
+
+    #[test]
+    fn test_memory_type_filtering() {
+        let mut store = VectorStore::new(3);
+
+        store
+            .add(MemoryEntry::new(
+                "dialogue".to_string(),
+                vec![1.0, 0.0, 0.0],
+                MemoryType::Episodic {
+                    session_id: Uuid::new_v4(),
+                    turn: 1,
+                },
+            ))
+            .unwrap();
+
+        store
+            .add(MemoryEntry::new(
+                "knowledge".to_string(),
+                vec![0.0, 1.0, 0.0],
+                MemoryType::Semantic {
+                    category: "science".to_string(),
+                },
+            ))
+            .unwrap();
+
+        let episodic_entries = store.get_by_type(&MemoryType::Episodic {
+            session_id: Uuid::nil(),
+            turn: 0,
+        });
+        assert_eq!(episodic_entries.len(), 1);
+
+        let semantic_entries = store.get_by_type(&MemoryType::Semantic {
+            category: String::new(),
+        });
+        assert_eq!(semantic_entries.len(), 1);
+    }
+
+    fn episodic(text: &str, embedding: Vec<f32>, session_id: Uuid, turn: usize) -> MemoryEntry {
+        MemoryEntry::new(text.to_string(), embedding, MemoryType::Episodic { session_id, turn })
+    }
+
+    #[test]
+    fn test_session_shards() {
+        let mut store = VectorStore::new(3);
+        let (a, b) = (Uuid::new_v4(), Uuid::new_v4());
+
+        store.add(episodic("a0", vec![1.0, 0.0, 0.0], a, 0)).unwrap();
+        store.add(episodic("a1", vec![0.0, 1.0, 0.0], a, 1)).unwrap();
+        store.add(episodic("b0", vec![1.0, 0.0, 0.0], b, 0)).unwrap();
+
+        let hits = store.search_session(&[1.0, 0.0, 0.0], &b, 5);
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].1.text, "b0");
+
+        // удаляется только одна сессия
+        assert_eq!(store.clear_session(&a), 2);
+        assert_eq!(store.len(), 1);
+        assert_eq!(store.get_session(&b).len(), 1);
+        assert!(store.get_session(&a).is_empty());
+    }
+
+    #[test]
+    fn test_recent_and_frequent_entries_rank_first() {
+        use crate::utils::clock::MockClock;
+        use std::sync::Arc;
+
+        let mut store = VectorStore::new(3);
+        let session = Uuid::new_v4();
+        let now: DateTime<Utc> = "2024-06-01T00:00:00Z".parse().unwrap();
+        let mut close = episodic("close", vec![1.0, 0.1, 0.0], session, 0);
+        close.timestamp = now - chrono::Duration::days(365);
+        let mut recalled = episodic("recalled", vec![1.0, 0.3, 0.0], session, 1);
+        recalled.timestamp = close.timestamp;
+        let recalled_id = recalled.id;
+        store.add_batch(vec![close, recalled]).unwrap();
+        let episodic_type = MemoryType::Episodic { session_id: Uuid::nil(), turn: 0 };
+
+        // without a boost only the similarity counts
+        let hits = store.search_by_type(&[1.0, 0.0, 0.0], &episodic_type, 2);
+        assert_eq!(hits[0].1.text, "close");
+
+        let memory_type = MemoryType::Episodic { session_id: session, turn: 1 };
+        for days in [3, 2, 1] {
+            assert!(store.record_access(&memory_type, recalled_id, now - chrono::Duration::days(days)));
+        }
+        assert!(!store.record_access(&memory_type, Uuid::new_v4(), now));
+        let boost = RankingBoost { recency: 0.05, half_life_days: 7.0, frequency: 0.05 };
+        store.set_ranking_boost(boost, Arc::new(MockClock::new(now)));
+        let hits = store.search_by_type(&[1.0, 0.0, 0.0], &episodic_type, 2);
+        assert_eq!(hits[0].1.text, "recalled");
+        assert_eq!(hits[0].1.access_count, 3);
+        // a year-old, never recalled entry gets next to nothing
+        assert!(boost.boost(hits[1].1, now) < 1e-3);
+    }
+
+    #[test]
+    fn test_lazy_shards_round_trip() {
+        let dir = std::env::temp_dir().join(format!("ziggurat-shards-{}", Uuid::new_v4()));
+        let session = Uuid::new_v4();
+
+        let mut store = VectorStore::new(3);
+        store.add(episodic("hello", vec![1.0, 0.0, 0.0], session, 0)).unwrap();
+        store
+            .add(MemoryEntry::new(
+                "rust".to_string(),
+                vec![0.0, 1.0, 0.0],
+                MemoryType::Semantic { category: "facts".to_string() },
+            ))
+            .unwrap();
+        assert_eq!(store.save_shards(&dir).unwrap(), 2);
+
+        let mut reopened = VectorStore::open_sharded(&dir).unwrap().unwrap();
+        let stats = reopened.stats();
+        assert_eq!((stats.total_entries, stats.loaded_shards), (2, 0));
+
+        let hits = reopened.search_session(&[1.0, 0.0, 0.0], &session, 1);
+        assert_eq!(hits[0].1.text, "hello");
+        assert_eq!(reopened.stats().loaded_shards, 1);
+
+        // only shards of removed sessions are deleted, other files in the directory stay
+        std::fs::write(dir.join("documents.json"), "[]").unwrap();
+        reopened.clear_session(&session);
+        assert_eq!(reopened.save_shards(&dir).unwrap(), 1);
+        assert!(!dir.join(ShardKey::Session(session).file_name()).exists());
+        assert!(dir.join("documents.json").exists() && dir.join(SHARD_INDEX_FILE).exists());
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_unreadable_shard_is_not_overwritten() {
+        let dir = std::env::temp_dir().join(format!("ziggurat-shards-{}", Uuid::new_v4()));
+        let session = Uuid::new_v4();
+        let mut store = VectorStore::new(3);
+        store.add(episodic("hello", vec![1.0, 0.0, 0.0], session, 0)).unwrap();
+        store.save_shards(&dir).unwrap();
+
+        let shard_path = dir.join(ShardKey::Session(session).file_name());
+        std::fs::write(&shard_path, "[{\"truncated").unwrap();
+        let mut reopened = VectorStore::open_sharded(&dir).unwrap().unwrap();
+        assert!(reopened.search_session(&[1.0, 0.0, 0.0], &session, 1).is_empty());
+        assert_eq!(reopened.stats().loaded_shards, 0);
+
+        reopened.save_shards(&dir).unwrap();
+        assert_eq!(std::fs::read_to_string(&shard_path).unwrap(), "[{\"truncated");
+        let index: ShardIndex = serde_json::from_str(&std::fs::read_to_string(dir.join(SHARD_INDEX_FILE)).unwrap()).unwrap();
+        assert_eq!(index.shards, vec![(ShardKey::Session(session), 1)]);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,221 @@
+//! 🧩 Кластеры концептов
+//!
+//! k-means по эмбеддингам (косинусная близость) показывает, из каких тем состоит
+//! семантическая память: у каждого кластера есть подпись из тегов или частых
+//! слов и самые типичные концепты. Помогает увидеть дубликаты и мусор, когда
+//! концептов становятся сотни.
+
+use std::cmp::Reverse;
+use std::collections::HashMap;
+
+use super::concept::Concept;
+use crate::totems::retrieval::vector_store::cosine_similarity;
+
+/// Больше кластеров на экране не читается
+pub const MAX_CLUSTERS: usize = 12;
+const MAX_ITERATIONS: usize = 25;
+const LABEL_WORDS: usize = 3;
+const MIN_LABEL_WORD_CHARS: usize = 3;
+/// Слова, которые ничего не говорят о теме кластера
+const STOP_WORDS: &[&str] = &[
+    "the", "and", "has", "have", "for", "with", "his", "her", "its", "are", "was", "not", "user",
+    "likes", "это", "что", "как", "для", "его", "она", "они", "пользователь", "пользователя",
+];
+
+/// Кластер концептов
+#[derive(Debug, Clone)]
+pub struct ConceptCluster {
+    /// Теги или частые слова участников
+    pub label: String,
+    /// Участники, от самого типичного (ближайшего к центроиду)
+    pub members: Vec<uuid::Uuid>,
+    /// Средняя близость участников к центроиду
+    pub cohesion: f32,
+}
+
+/// Число кластеров по умолчанию: ~sqrt(n/2)
+pub fn default_k(n: usize) -> usize {
+    ((n as f32 / 2.0).sqrt().round() as usize).clamp(1, MAX_CLUSTERS).min(n.max(1))
+}
+
+/// Разбивает концепты на `k` кластеров (по умолчанию [`default_k`]).
+/// Концепты без эмбеддинга пропускаются; порядок кластеров - по размеру
+pub fn cluster_concepts(concepts: &[&Concept], k: Option<usize>) -> Vec<ConceptCluster> {
+    let mut concepts: Vec<&Concept> = concepts.iter().copied().filter(|c| !c.embedding.is_empty()).collect();
+    if concepts.is_empty() {
+        return Vec::new();
+    }
+    // детерминированный старт: первый центроид - самый уверенный концепт
+    concepts.sort_by(|a, b| {
+        b.confidence
+            .partial_cmp(&a.confidence)
+            .unwrap_or(std::cmp::Ordering::Equal)
+            .then_with(|| a.id.cmp(&b.id))
+    });
+
+    let vectors: Vec<&[f32]> = concepts.iter().map(|c| c.embedding.as_slice()).collect();
+    let k = k.unwrap_or_else(|| default_k(vectors.len())).clamp(1, vectors.len());
+    let (assignment, centroids) = kmeans(&vectors, k);
+
+    let mut clusters: Vec<ConceptCluster> = (0..k)
+        .filter_map(|cluster| {
+            let mut members: Vec<(f32, &Concept)> = concepts
+                .iter()
+                .zip(&assignment)
+                .filter(|(_, &a)| a == cluster)
+                .map(|(c, _)| (cosine_similarity(&c.embedding, &centroids[cluster]), *c))
+                .collect();
+            if members.is_empty() {
+                return None;
+            }
+            members.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(std::cmp::Ordering::Equal));
+            let cohesion = members.iter().map(|(s, _)| s).sum::<f32>() / members.len() as f32;
+            let member_concepts: Vec<&Concept> = members.iter().map(|(_, c)| *c).collect();
+            Some(ConceptCluster {
+                label: label(&member_concepts, &concepts),
+                members: member_concepts.iter().map(|c| c.id).collect(),
+                cohesion,
+            })
+        })
+        .collect();
+    clusters.sort_by_key(|c| Reverse(c.members.len()));
+    clusters
+}
+
+/// k-means с детерминированной инициализацией farthest-first.
+/// Возвращает номер кластера для каждого вектора и центроиды
+fn kmeans(vectors: &[&[f32]], k: usize) -> (Vec<usize>, Vec<Vec<f32>>) {
+    let mut centroids: Vec<Vec<f32>> = vec![vectors[0].to_vec()];
+    while centroids.len() < k {
+        let farthest = vectors
+            .iter()
+            .map(|v| {
+                centroids
+                    .iter()
+                    .map(|c| cosine_similarity(v, c))
+                    .fold(f32::MIN, f32::max)
+            })
+            .enumerate()
+            .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal))
+            .map_or(0, |(i, _)| i);
+        centroids.push(vectors[farthest].to_vec());
+    }
+
+    let mut assignment = vec![usize::MAX; vectors.len()];
+    for _ in 0..MAX_ITERATIONS {
+        let mut changed = false;
+        for (i, v) in vectors.iter().enumerate() {
+            let nearest = centroids
+                .iter()
+                .map(|c| cosine_similarity(v, c))
+                .enumerate()
+                .max_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal))
+                .map_or(0, |(c, _)| c);
+            if assignment[i] != nearest {
+                assignment[i] = nearest;
+                changed = true;
+            }
+        }
+        if !changed {
+            break;
+        }
+
+        for (cluster, centroid) in centroids.iter_mut().enumerate() {
+            let mut sum = vec![0.0f32; centroid.len()];
+            let mut count = 0;
+            for (v, _) in vectors.iter().zip(&assignment).filter(|(_, &a)| a == cluster) {
+                for (s, x) in sum.iter_mut().zip(v.iter()) {
+                    *s += x;
+                }
+                count += 1;
+            }
+            // пустой кластер сохраняет прежний центроид
+            if count > 0 {
+                *centroid = sum;
+            }
+        }
+    }
+    (assignment, centroids)
+}
+
+/// Подпись: частые теги кластера, иначе слова, характерные для него больше, чем для всей памяти
+fn label(members: &[&Concept], all: &[&Concept]) -> String {
+    let mut tags: HashMap<&str, usize> = HashMap::new();
+    for tag in members.iter().flat_map(|c| &c.tags) {
+        *tags.entry(tag.as_str()).or_default() += 1;
+    }
+    let mut tags: Vec<(&str, usize)> = tags.into_iter().filter(|(_, n)| n * 2 >= members.len()).collect();
+    if !tags.is_empty() {
+        tags.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+        return tags.iter().take(LABEL_WORDS).map(|(t, _)| format!("#{}", t)).collect::<Vec<_>>().join(" ");
+    }
+
+    let counts = |concepts: &[&Concept]| {
+        let mut counts: HashMap<String, usize> = HashMap::new();
+        for concept in concepts {
+            let mut words: Vec<String> = concept
+                .text
+                .split(|c: char| !c.is_alphanumeric())
+                .filter(|w| w.chars().count() >= MIN_LABEL_WORD_CHARS)
+                .map(str::to_lowercase)
+                .filter(|w| !STOP_WORDS.contains(&w.as_str()))
+                .collect();
+            words.sort();
+            words.dedup();
+            for word in words {
+                *counts.entry(word).or_default() += 1;
+            }
+        }
+        counts
+    };
+    let global = counts(all);
+    let mut words: Vec<(String, f32)> = counts(members)
+        .into_iter()
+        .map(|(word, n)| {
+            let share_here = n as f32 / members.len() as f32;
+            let share_all = global.get(&word).copied().unwrap_or(n) as f32 / all.len() as f32;
+            (word, share_here * share_here / share_all)
+        })
+        .collect();
+    words.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal).then_with(|| a.0.cmp(&b.0)));
+
+    if words.is_empty() {
+        return "(no label)".to_string();
+    }
+    words.iter().take(LABEL_WORDS).map(|(w, _)| w.as_str()).collect::<Vec<_>>().join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::totems::semantic::concept::ConceptCategory;
+
+    fn concept(text: &str, embedding: [f32; 3], tags: &[&str]) -> Concept {
+        let mut c = Concept::new(text.to_string(), ConceptCategory::Facts, "test".to_string());
+        c.embedding = embedding.to_vec();
+        c.tags = tags.iter().map(|t| t.to_string()).collect();
+        c
+    }
+
+    #[test]
+    fn test_clusters_group_by_embedding() {
+        let concepts = [
+            concept("Writes Rust at work", [1.0, 0.1, 0.0], &["work"]),
+            concept("Reviews Rust pull requests", [0.9, 0.2, 0.0], &["work"]),
+            concept("Has a dog called Rex", [0.0, 0.1, 1.0], &[]),
+            concept("Walks the dog every morning", [0.1, 0.0, 0.9], &[]),
+            concept("Feeds the dog twice a day", [0.0, 0.2, 0.95], &[]),
+        ];
+        let refs: Vec<&Concept> = concepts.iter().collect();
+
+        let clusters = cluster_concepts(&refs, Some(2));
+        assert_eq!(clusters.len(), 2);
+        assert_eq!(clusters[0].members.len(), 3);
+        assert!(clusters[0].label.starts_with("dog,"), "{}", clusters[0].label);
+        assert_eq!(clusters[1].label, "#work");
+        assert!(clusters.iter().all(|c| c.cohesion > 0.9));
+
+        assert_eq!(default_k(0), 1);
+        assert_eq!(default_k(200), 10);
+    }
+}
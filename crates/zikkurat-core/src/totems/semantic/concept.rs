@@ -9,6 +9,9 @@ use std::collections::{HashMap, HashSet, VecDeque};
 use std::hash::{Hash, Hasher};
 use uuid::Uuid;
 
+use super::provenance::Evidence;
+use crate::totems::language::Language;
+
 /// Категории концептов в семантической памяти
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum ConceptCategory {
@@ -83,12 +86,39 @@ impl Hash for ConceptCategory {
     }
 }
 
+/// Происхождение знания
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum KnowledgeSource {
+    /// Извлечено из диалога
+    #[default]
+    Dialogue,
+    /// Добавлено пользователем вручную
+    Manual,
+    /// Поставляется вместе с архетипом, не затухает
+    Predefined,
+    /// Исправлено пользователем явно (`*actually ...*`, `/correct`)
+    #[serde(rename = "user_correction")]
+    UserCorrection,
+}
+
+impl std::fmt::Display for KnowledgeSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            KnowledgeSource::Dialogue => write!(f, "dialogue"),
+            KnowledgeSource::Manual => write!(f, "manual"),
+            KnowledgeSource::Predefined => write!(f, "predefined"),
+            KnowledgeSource::UserCorrection => write!(f, "user_correction"),
+        }
+    }
+}
+
 /// Конфигурация временного затухания для категорий концептов
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct DecayConfig {
     /// Период затухания в днях
     pub period_days: u32,
-    /// Коэффициент затухания за период (0.0 - 1.0)
+    /// Коэффициент затухания за период (0.0 - 1.0); 1.0 - концепт не затухает
     pub decay_rate: f32,
     /// Минимальная уверенность (ниже - концепт удаляется)
     pub min_confidence: f32,
@@ -104,13 +134,20 @@ impl Default for DecayConfig {
     }
 }
 
+impl DecayConfig {
+    /// Уверенность со временем слабеет
+    pub fn decays(&self) -> bool {
+        self.period_days > 0 && self.decay_rate < 1.0
+    }
+}
+
 impl ConceptCategory {
-    /// Получить конфигурацию затухания для категории
+    /// Затухание категории по умолчанию; [`super::retention::RetentionConfig`] может его поправить
     pub fn get_decay_config(&self) -> DecayConfig {
         match self {
             ConceptCategory::Facts => DecayConfig {
                 period_days: 30,
-                decay_rate: 0.95, // медленное затухание
+                decay_rate: 1.0, // факты не затухают
                 min_confidence: 0.05,
             },
             ConceptCategory::Rules => DecayConfig {
@@ -182,15 +219,22 @@ impl Triple {
         self
     }
 
+    /// Creation time from the caller's clock
+    pub fn at(mut self, now: DateTime<Utc>) -> Self {
+        self.created_at = now;
+        self.updated_at = now;
+        self
+    }
+
     /// Add metadata
     pub fn with_metadata(mut self, key: String, value: String) -> Self {
         self.metadata.insert(key, value);
         self
     }
 
-    /// Get effective confidence with temporal decay
-    pub fn get_effective_confidence(&self) -> f32 {
-        let days_old = (Utc::now() - self.updated_at).num_days() as f32;
+    /// Get effective confidence with temporal decay as of `now`
+    pub fn get_effective_confidence(&self, now: DateTime<Utc>) -> f32 {
+        let days_old = (now - self.updated_at).num_days() as f32;
         let decay_factor = (-days_old / 90.0).exp(); // 90-day half-life
         self.confidence * decay_factor
     }
@@ -222,8 +266,24 @@ impl KnowledgeGraph {
 
     /// Add a triple to the graph
     pub fn add_triple(&mut self, triple: Triple) -> Uuid {
-        let triple_id = triple.subject; // Use subject as ID for now
-        let uuid = triple_id;
+        // The same fact seen again only refreshes the existing edge
+        let existing_id = self.subject_index.get(&triple.subject).and_then(|ids| {
+            ids.iter().copied().find(|id| {
+                self.triples
+                    .get(id)
+                    .is_some_and(|t| t.predicate == triple.predicate && t.object == triple.object)
+            })
+        });
+        if let Some(existing_id) = existing_id {
+            if let Some(existing) = self.triples.get_mut(&existing_id) {
+                existing.confidence = existing.confidence.max(triple.confidence);
+                existing.updated_at = triple.updated_at;
+            }
+            return existing_id;
+        }
+
+        // Each edge gets its own id so a subject can have many relations
+        let uuid = Uuid::new_v4();
 
         // Index by subject
         self.subject_index
@@ -247,6 +307,59 @@ impl KnowledgeGraph {
         uuid
     }
 
+    /// Remove every triple the concept takes part in; returns how many were removed
+    pub fn remove_concept(&mut self, concept_id: &Uuid) -> usize {
+        let mut ids: Vec<Uuid> = self.subject_index.remove(concept_id).unwrap_or_default();
+        ids.extend(self.object_index.remove(concept_id).unwrap_or_default());
+        ids.sort();
+        ids.dedup();
+
+        let mut removed = 0;
+        for id in &ids {
+            let Some(triple) = self.triples.remove(id) else {
+                continue;
+            };
+            removed += 1;
+            for index in [&mut self.subject_index, &mut self.object_index] {
+                for key in [triple.subject, triple.object] {
+                    if let Some(list) = index.get_mut(&key) {
+                        list.retain(|t| t != id);
+                    }
+                }
+            }
+            if let Some(list) = self.predicate_index.get_mut(&triple.predicate) {
+                list.retain(|t| t != id);
+            }
+        }
+        removed
+    }
+
+    /// Move every triple of `from` over to `to` (a concept merged into another);
+    /// edges between the two are dropped. Returns how many triples were moved
+    pub fn redirect(&mut self, from: &Uuid, to: &Uuid) -> usize {
+        let mut ids: Vec<Uuid> = self.subject_index.get(from).cloned().unwrap_or_default();
+        ids.extend(self.object_index.get(from).cloned().unwrap_or_default());
+        ids.sort();
+        ids.dedup();
+        let triples: Vec<Triple> = ids.iter().filter_map(|id| self.triples.get(id).cloned()).collect();
+        self.remove_concept(from);
+
+        let mut moved = 0;
+        for mut triple in triples {
+            if triple.subject == *from {
+                triple.subject = *to;
+            }
+            if triple.object == *from {
+                triple.object = *to;
+            }
+            if triple.subject != triple.object {
+                self.add_triple(triple);
+                moved += 1;
+            }
+        }
+        moved
+    }
+
     /// Find triples by subject
     pub fn find_by_subject(&self, subject_id: &Uuid) -> Vec<&Triple> {
         if let Some(triple_ids) = self.subject_index.get(subject_id) {
@@ -283,8 +396,8 @@ impl KnowledgeGraph {
         }
     }
 
-    /// Find all related concepts (both directions)
-    pub fn find_related_concepts(&self, concept_id: &Uuid) -> Vec<(Uuid, &str, f32)> {
+    /// Find all related concepts (both directions) with confidence as of `now`
+    pub fn find_related_concepts(&self, concept_id: &Uuid, now: DateTime<Utc>) -> Vec<(Uuid, &str, f32)> {
         let mut related = Vec::new();
 
         // Outgoing relationships (as subject)
@@ -292,7 +405,7 @@ impl KnowledgeGraph {
             related.push((
                 triple.object,
                 triple.predicate.as_str(),
-                triple.get_effective_confidence(),
+                triple.get_effective_confidence(now),
             ));
         }
 
@@ -301,7 +414,7 @@ impl KnowledgeGraph {
             related.push((
                 triple.subject,
                 triple.predicate.as_str(),
-                triple.get_effective_confidence(),
+                triple.get_effective_confidence(now),
             ));
         }
 
@@ -329,8 +442,9 @@ impl KnowledgeGraph {
             }
 
             // Find related concepts
-            let related = self.find_related_concepts(current);
-            for (next_id, _, _) in related {
+            let related = self.find_by_subject(current).into_iter().map(|t| t.object)
+                .chain(self.find_by_object(current).into_iter().map(|t| t.subject));
+            for next_id in related {
                 if !visited.contains(&next_id) {
                     visited.insert(next_id);
                     let mut new_path = current_path.clone();
@@ -392,6 +506,50 @@ pub struct GraphStats {
     pub avg_degree: f32,
 }
 
+/// Приводит тег к каноничному виду: lowercase, без пробелов по краям и `#`
+pub fn normalize_tag(tag: &str) -> String {
+    tag.trim().trim_start_matches('#').trim().to_lowercase()
+}
+
+/// Фильтр по тегам для поиска и инъекции в промпт
+#[derive(Debug, Clone, Default)]
+pub struct TagFilter {
+    /// Концепт должен иметь хотя бы один из тегов (пусто - без ограничения)
+    pub include: Vec<String>,
+    /// Концепты с любым из этих тегов исключаются
+    pub exclude: Vec<String>,
+}
+
+impl TagFilter {
+    /// Фильтр, исключающий указанные теги
+    pub fn excluding(tags: &[String]) -> Self {
+        Self {
+            include: Vec::new(),
+            exclude: tags.iter().map(|t| normalize_tag(t)).collect(),
+        }
+    }
+
+    /// Фильтр, требующий один из указанных тегов
+    pub fn including(tags: &[String]) -> Self {
+        Self {
+            include: tags.iter().map(|t| normalize_tag(t)).collect(),
+            exclude: Vec::new(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.include.is_empty() && self.exclude.is_empty()
+    }
+
+    /// Проверяет, проходит ли концепт фильтр
+    pub fn matches(&self, concept: &Concept) -> bool {
+        if self.exclude.iter().any(|t| concept.has_tag(t)) {
+            return false;
+        }
+        self.include.is_empty() || self.include.iter().any(|t| concept.has_tag(t))
+    }
+}
+
 /// Единица семантической памяти - концепт
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Concept {
@@ -419,9 +577,70 @@ pub struct Concept {
     /// Связанные концепты (IDs) для быстрого доступа
     #[serde(skip)]
     pub related_concepts: Vec<Uuid>,
+    /// Произвольные теги (work, family, health...) поверх категорий
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Происхождение знания
+    #[serde(default)]
+    pub knowledge_source: KnowledgeSource,
+    /// Версия текста: растёт, когда концепт переписывается новым фактом
+    #[serde(default = "default_concept_version")]
+    pub version: u32,
+    /// Прежние формулировки (старые версии)
+    #[serde(default)]
+    pub previous_texts: Vec<String>,
+    /// Язык, на котором пользователь сообщил факт (текст концепта экстрактор
+    /// обычно пишет по-английски)
+    #[serde(default)]
+    pub language: Option<Language>,
+    /// Исходная реплика пользователя, из которой извлечён концепт
+    #[serde(default)]
+    pub utterance: Option<String>,
+    /// Реплики, которыми пользователь сообщал и подтверждал этот факт
+    #[serde(default)]
+    pub evidence: Vec<Evidence>,
+    /// Когда концепт последний раз попал в ответ или был сказан снова (`None` - ни разу)
+    #[serde(default)]
+    pub last_used: Option<DateTime<Utc>>,
+}
+
+fn default_concept_version() -> u32 {
+    1
+}
+
+/// Текст концепта в каноничной форме: регистр, пробелы и финальная точка не важны
+pub fn normalize_concept_text(text: &str) -> String {
+    text.to_lowercase()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .trim_end_matches(['.', '!', ';'])
+        .to_string()
 }
 
 impl Concept {
+    /// Стабильный ID по пользователю и нормализованному тексту (UUID v8, FNV-1a 128).
+    /// Повторная экстракция того же факта даёт тот же ID
+    pub fn content_id(user_id: &str, text: &str) -> Uuid {
+        const FNV_OFFSET: u128 = 0x6c62272e07bb014262b821756295c58d;
+        const FNV_PRIME: u128 = 0x0000000001000000000000000000013b;
+
+        let key = format!("{}\u{1f}{}", user_id, normalize_concept_text(text));
+        let hash = key.bytes().fold(FNV_OFFSET, |hash, byte| {
+            (hash ^ byte as u128).wrapping_mul(FNV_PRIME)
+        });
+        uuid::Builder::from_custom_bytes(hash.to_be_bytes()).into_uuid()
+    }
+
+    /// Переписывает текст новой версией, сохраняя ID и прежнюю формулировку
+    pub fn revise(&mut self, text: String, embedding: Vec<f32>, now: DateTime<Utc>) {
+        let previous = std::mem::replace(&mut self.text, text);
+        self.previous_texts.push(previous);
+        self.version += 1;
+        self.embedding = embedding;
+        self.updated_at = now;
+    }
+
     /// Создает новый концепт
     pub fn new(text: String, category: ConceptCategory, source: String) -> Self {
         let now = Utc::now();
@@ -437,6 +656,14 @@ impl Concept {
             updated_at: now,
             usage_count: 0,
             related_concepts: Vec::new(),
+            tags: Vec::new(),
+            knowledge_source: KnowledgeSource::default(),
+            version: 1,
+            previous_texts: Vec::new(),
+            language: None,
+            utterance: None,
+            evidence: Vec::new(),
+            last_used: None,
         }
     }
 
@@ -446,33 +673,114 @@ impl Concept {
         self
     }
 
+    /// Время создания по часам вызывающего менеджера
+    pub fn at(mut self, now: DateTime<Utc>) -> Self {
+        self.created_at = now;
+        self.updated_at = now;
+        self
+    }
+
     /// Добавляет метаданные
     pub fn with_metadata(mut self, key: String, value: String) -> Self {
         self.metadata.insert(key, value);
         self
     }
 
+    /// Запоминает исходную реплику и её язык
+    pub fn with_utterance(mut self, utterance: &str) -> Self {
+        self.set_utterance(utterance);
+        self
+    }
+
+    /// Как [`Concept::with_utterance`]; пустая реплика ничего не меняет
+    pub fn set_utterance(&mut self, utterance: &str) {
+        let utterance = utterance.trim();
+        if utterance.is_empty() {
+            return;
+        }
+        self.language = Language::detect(utterance);
+        self.utterance = Some(utterance.to_string());
+    }
+
+    /// Язык канонического текста концепта
+    pub fn text_language(&self) -> Option<Language> {
+        Language::detect(&self.text)
+    }
+
+    /// Задаёт происхождение знания
+    pub fn with_knowledge_source(mut self, source: KnowledgeSource) -> Self {
+        self.knowledge_source = source;
+        self
+    }
+
+    /// Добавляет теги
+    pub fn with_tags(mut self, tags: &[String]) -> Self {
+        for tag in tags {
+            self.add_tag(tag, self.updated_at);
+        }
+        self
+    }
+
+    /// Добавляет тег (нормализуется в lowercase), возвращает false если уже был
+    pub fn add_tag(&mut self, tag: &str, now: DateTime<Utc>) -> bool {
+        let tag = normalize_tag(tag);
+        if tag.is_empty() || self.tags.contains(&tag) {
+            return false;
+        }
+        self.tags.push(tag);
+        self.updated_at = now;
+        true
+    }
+
+    /// Удаляет тег, возвращает false если его не было
+    pub fn remove_tag(&mut self, tag: &str) -> bool {
+        let tag = normalize_tag(tag);
+        let before = self.tags.len();
+        self.tags.retain(|t| *t != tag);
+        before != self.tags.len()
+    }
+
+    /// Проверяет наличие тега
+    pub fn has_tag(&self, tag: &str) -> bool {
+        let tag = normalize_tag(tag);
+        self.tags.contains(&tag)
+    }
+
     /// Проверяет валидность концепта
     pub fn is_valid(&self) -> bool {
         !self.text.trim().is_empty()
     }
 
     /// Обновляет счетчик использования
-    pub fn increment_usage(&mut self) {
+    pub fn increment_usage(&mut self, now: DateTime<Utc>) {
         self.usage_count += 1;
-        self.updated_at = Utc::now();
+        self.updated_at = now;
+        self.last_used = Some(now);
+    }
+
+    /// Последнее использование, а без него - создание: по нему вытесняются концепты
+    /// сверх предела категории (см. [`super::retention`])
+    pub fn last_used_at(&self) -> DateTime<Utc> {
+        self.last_used.unwrap_or(self.created_at)
     }
 
     /// Обновляет уверенность
-    pub fn update_confidence(&mut self, delta: f32) {
+    pub fn update_confidence(&mut self, delta: f32, now: DateTime<Utc>) {
         self.confidence = (self.confidence + delta).clamp(0.0, 1.0);
-        self.updated_at = Utc::now();
+        self.updated_at = now;
     }
 
-    /// Применить временное затухание к уверенности концепта
-    pub fn apply_temporal_decay(&mut self) -> bool {
-        let config = self.category.get_decay_config();
-        let now = Utc::now();
+    /// Применить временное затухание к уверенности концепта на момент `now`
+    pub fn apply_temporal_decay(&mut self, now: DateTime<Utc>) -> bool {
+        self.apply_decay_with(&self.category.get_decay_config(), now)
+    }
+
+    /// Затухание по заданной конфигурации (политика хранения категории).
+    /// `false` - уверенность упала ниже минимума, концепт нужно удалить
+    pub fn apply_decay_with(&mut self, config: &DecayConfig, now: DateTime<Utc>) -> bool {
+        if self.knowledge_source == KnowledgeSource::Predefined || !config.decays() {
+            return true; // знания архетипа и категории без затухания не слабеют
+        }
         let days_since_update = (now - self.updated_at).num_days() as u32;
 
         if days_since_update < config.period_days {
@@ -492,10 +800,16 @@ impl Concept {
         true // концепт остается актуальным
     }
 
-    /// Получить актуальную уверенность с учетом затухания (без изменения)
-    pub fn get_effective_confidence(&self) -> f32 {
-        let config = self.category.get_decay_config();
-        let now = Utc::now();
+    /// Получить актуальную уверенность с учетом затухания на момент `now` (без изменения)
+    pub fn get_effective_confidence(&self, now: DateTime<Utc>) -> f32 {
+        self.effective_confidence_with(&self.category.get_decay_config(), now)
+    }
+
+    /// Актуальная уверенность по заданной конфигурации затухания
+    pub fn effective_confidence_with(&self, config: &DecayConfig, now: DateTime<Utc>) -> f32 {
+        if !config.decays() {
+            return self.confidence;
+        }
         let days_since_update = (now - self.updated_at).num_days() as u32;
 
         if days_since_update < config.period_days {
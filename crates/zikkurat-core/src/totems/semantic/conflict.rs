@@ -0,0 +1,143 @@
+//! ⚖️ Конфликты среди найденных концептов
+//!
+//! Поиск может поднять два взаимоисключающих факта: «favorite car is
+//! Lamborghini» и «favorite car is Porsche». Вместо того чтобы молча отдать
+//! модели оба (или случайно один), CLI спрашивает пользователя, какой из них
+//! актуален, и записывает ответ как исправление. Конфликт - это отрицание
+//! того же утверждения или один «слот» с одним значением (любимое, имя, где
+//! живёт/работает) и разными значениями.
+
+use std::collections::HashSet;
+
+use uuid::Uuid;
+
+use super::concept::{normalize_concept_text, Concept};
+use super::manager::remove_negation;
+
+/// Связки, после которых в «любимом» и имени идёт значение
+const COPULAS: &[&str] = &[" is ", " are ", " - ", " — ", ": ", " это "];
+/// Слоты с единственным значением, задаваемые связкой
+const SINGLE_VALUED: &[&str] = &["favorite", "favourite", "name", "любим", "зовут", "имя"];
+/// Слоты с единственным значением, задаваемые глаголом (значение идёт сразу после)
+const SINGLE_VALUED_VERBS: &[&str] = &[
+    "lives in ",
+    "is from ",
+    "works at ",
+    "works as ",
+    "живёт в ",
+    "живет в ",
+    "работает в ",
+    "работает ",
+];
+
+/// Два концепта, которые не могут быть верны одновременно
+#[derive(Debug, Clone)]
+pub struct ConceptConflict {
+    pub first: Concept,
+    pub second: Concept,
+}
+
+impl ConceptConflict {
+    /// Ключ пары без учёта порядка
+    pub fn key(&self) -> (Uuid, Uuid) {
+        let (a, b) = (self.first.id, self.second.id);
+        if a < b {
+            (a, b)
+        } else {
+            (b, a)
+        }
+    }
+}
+
+/// Слот и значение факта с единственным значением: `user's favorite car` - `porsche`
+fn single_valued_slot(text: &str) -> Option<(String, String)> {
+    let text = normalize_concept_text(text);
+    for copula in COPULAS {
+        if let Some((slot, value)) = text.split_once(copula) {
+            if SINGLE_VALUED.iter().any(|w| slot.contains(w)) && !value.trim().is_empty() {
+                return Some((slot.trim().to_string(), value.trim().to_string()));
+            }
+        }
+    }
+    SINGLE_VALUED_VERBS.iter().find_map(|verb| {
+        let at = text.find(verb)? + verb.len();
+        let value = text[at..].trim();
+        (!value.is_empty()).then(|| (text[..at].trim().to_string(), value.to_string()))
+    })
+}
+
+/// Одно и то же утверждение, но одно из двух - с отрицанием
+fn negates(a: &str, b: &str) -> bool {
+    let (a, b) = (normalize_concept_text(a), normalize_concept_text(b));
+    let (base_a, base_b) = (remove_negation(&a), remove_negation(&b));
+    if (base_a != a) == (base_b != b) {
+        return false;
+    }
+    // likes / like: после снятия отрицания глагол теряет окончание
+    let stems = |text: &str| -> HashSet<String> {
+        text.split_whitespace()
+            .map(|w| w.strip_suffix('s').filter(|s| s.len() > 2).unwrap_or(w).to_string())
+            .collect()
+    };
+    stems(&base_a) == stems(&base_b)
+}
+
+/// Противоречат ли два концепта друг другу
+pub fn conflicting(a: &Concept, b: &Concept) -> bool {
+    if negates(&a.text, &b.text) {
+        return true;
+    }
+    match (single_valued_slot(&a.text), single_valued_slot(&b.text)) {
+        (Some((slot_a, value_a)), Some((slot_b, value_b))) => slot_a == slot_b && value_a != value_b,
+        _ => false,
+    }
+}
+
+/// Пары противоречащих концептов среди найденных; каждый концепт - не больше чем в одной паре
+pub fn find_conflicts(concepts: &[&Concept]) -> Vec<ConceptConflict> {
+    let mut used = HashSet::new();
+    let mut conflicts = Vec::new();
+    for (i, a) in concepts.iter().enumerate() {
+        for b in &concepts[i + 1..] {
+            if used.contains(&a.id) || used.contains(&b.id) || !conflicting(a, b) {
+                continue;
+            }
+            used.insert(a.id);
+            used.insert(b.id);
+            conflicts.push(ConceptConflict {
+                first: (*a).clone(),
+                second: (*b).clone(),
+            });
+        }
+    }
+    conflicts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::totems::semantic::ConceptCategory;
+
+    fn concept(text: &str) -> Concept {
+        Concept::new(text.to_string(), ConceptCategory::Preferences, "s1".to_string())
+    }
+
+    #[test]
+    fn test_find_conflicts() {
+        let lamborghini = concept("User's favorite car is Lamborghini");
+        let porsche = concept("User's favorite car is Porsche");
+        let coffee = concept("User likes coffee");
+        let tea = concept("User likes tea");
+        let conflicts = find_conflicts(&[&lamborghini, &coffee, &porsche, &tea]);
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!((conflicts[0].first.id, conflicts[0].second.id), (lamborghini.id, porsche.id));
+
+        assert!(conflicting(&concept("User lives in Kazan"), &concept("User lives in Moscow")));
+        assert!(conflicting(&concept("Любимая машина - Porsche"), &concept("Любимая машина — Volvo")));
+        assert!(conflicting(&coffee, &concept("User doesn't like coffee")));
+        assert!(conflicting(&concept("Пользователь любит кофе"), &concept("Пользователь не любит кофе")));
+        assert!(!conflicting(&coffee, &concept("User doesn't like mornings")));
+        assert!(!conflicting(&concept("User lives in Kazan"), &concept("User lives in Kazan.")));
+        assert!(!conflicting(&concept("Rust is fast"), &concept("Rust is safe")));
+    }
+}
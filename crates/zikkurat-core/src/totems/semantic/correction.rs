@@ -0,0 +1,94 @@
+//! ✏️ Исправления пользователя
+//!
+//! Пользователь может поправить память прямо в сообщении - `*actually I prefer tea*`,
+//! `*на самом деле я живу в Казани*` - или командой `/correct <старое> -> <новое>`.
+//! Исправление сразу переписывает подходящий концепт новой версией с источником
+//! [`KnowledgeSource::UserCorrection`](super::KnowledgeSource::UserCorrection), не дожидаясь,
+//! пока эвристика противоречий заметит новый факт.
+
+use anyhow::Result;
+
+/// Слова, с которых начинается исправление внутри `*...*`
+const INLINE_MARKERS: &[&str] = &[
+    "actually",
+    "correction",
+    "on second thought",
+    "на самом деле",
+    "вообще-то",
+    "поправка",
+    "точнее",
+];
+
+/// Что исправить: `old` - ID концепта, его текст или часть текста; без него
+/// исправляется самый похожий на новый текст концепт
+#[derive(Debug, Clone, PartialEq)]
+pub struct CorrectionRequest {
+    pub old: Option<String>,
+    pub new: String,
+}
+
+/// Исправление `*actually ...*` в сообщении пользователя
+pub fn parse_inline(text: &str) -> Option<CorrectionRequest> {
+    let mut parts = text.split('*');
+    parts.next(); // текст до первой звёздочки
+    while let (Some(inner), Some(_)) = (parts.next(), parts.next()) {
+        let trimmed = inner.trim();
+        let lower = trimmed.to_lowercase();
+        let Some(marker) = INLINE_MARKERS.iter().find(|m| lower.starts_with(*m)) else {
+            continue;
+        };
+        // маркер - ASCII или кириллица, срез по длине в байтах lowercase совпадает с исходным
+        let rest = trimmed[marker.len()..].trim_start_matches([',', ':', ' ', '-', '—']).trim();
+        if !rest.is_empty() {
+            return Some(CorrectionRequest { old: None, new: capitalize(rest) });
+        }
+    }
+    None
+}
+
+/// Аргументы `/correct <old> -> <new>` (разделитель `->` или `→`)
+pub fn parse_command(args: &str) -> Result<CorrectionRequest> {
+    let (old, new) = args
+        .split_once("->")
+        .or_else(|| args.split_once('→'))
+        .ok_or_else(|| anyhow::anyhow!("Usage: /correct <old text or concept id> -> <new text>"))?;
+    let (old, new) = (old.trim(), new.trim());
+    anyhow::ensure!(!new.is_empty(), "The corrected text is empty");
+    Ok(CorrectionRequest {
+        old: (!old.is_empty()).then(|| old.to_string()),
+        new: capitalize(new),
+    })
+}
+
+fn capitalize(text: &str) -> String {
+    let mut chars = text.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_corrections() {
+        assert_eq!(
+            parse_inline("Sorry, *actually I prefer tea* - coffee keeps me up"),
+            Some(CorrectionRequest { old: None, new: "I prefer tea".to_string() })
+        );
+        assert_eq!(
+            parse_inline("*На самом деле, я живу в Казани*").unwrap().new,
+            "Я живу в Казани"
+        );
+        assert_eq!(parse_inline("I *really* like tea"), None);
+        assert_eq!(parse_inline("*actually*"), None);
+
+        let cmd = parse_command("likes coffee -> User prefers tea").unwrap();
+        assert_eq!(cmd.old.as_deref(), Some("likes coffee"));
+        assert_eq!(cmd.new, "User prefers tea");
+        assert_eq!(parse_command(" → lives in Kazan").unwrap().old, None);
+        assert!(parse_command("no arrow here").is_err());
+    }
+}
@@ -0,0 +1,155 @@
+//! 🧾 Что изменилось в памяти за ход
+//!
+//! Снимок семантической памяти до экстракции и после неё сравнивается, и
+//! пользователь видит короткий список: новые концепты, изменившаяся уверенность
+//! или формулировка, удалённые концепты и новые связи графа. Так обучение
+//! системы видно без DEBUG-логов.
+
+use std::collections::HashMap;
+use uuid::Uuid;
+
+use crate::totems::semantic::ConceptCategory;
+
+/// Изменения уверенности меньше этого не показываются
+const MIN_CONFIDENCE_CHANGE: f32 = 0.005;
+/// Длина текста концепта в строке диффа
+const MAX_TEXT_CHARS: usize = 80;
+
+/// Концепт в снимке
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConceptState {
+    pub text: String,
+    pub category: ConceptCategory,
+    pub confidence: f32,
+}
+
+/// Концепты и связи графа на момент снимка
+#[derive(Debug, Clone, Default)]
+pub struct SemanticSnapshot {
+    pub concepts: HashMap<Uuid, ConceptState>,
+    /// ID тройки -> «субъект -предикат-> объект»
+    pub triples: HashMap<Uuid, String>,
+}
+
+/// Разница двух снимков
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SemanticDiff {
+    pub added: Vec<ConceptState>,
+    /// (до, после)
+    pub updated: Vec<(ConceptState, ConceptState)>,
+    pub removed: Vec<ConceptState>,
+    pub triples_added: Vec<String>,
+}
+
+impl SemanticDiff {
+    pub fn between(before: &SemanticSnapshot, after: &SemanticSnapshot) -> Self {
+        let mut diff = SemanticDiff::default();
+        for (id, now) in &after.concepts {
+            match before.concepts.get(id) {
+                None => diff.added.push(now.clone()),
+                Some(was) => {
+                    let changed = was.text != now.text
+                        || was.category != now.category
+                        || (was.confidence - now.confidence).abs() >= MIN_CONFIDENCE_CHANGE;
+                    if changed {
+                        diff.updated.push((was.clone(), now.clone()));
+                    }
+                }
+            }
+        }
+        diff.removed = before
+            .concepts
+            .iter()
+            .filter(|(id, _)| !after.concepts.contains_key(id))
+            .map(|(_, was)| was.clone())
+            .collect();
+        diff.triples_added = after
+            .triples
+            .iter()
+            .filter(|(id, _)| !before.triples.contains_key(id))
+            .map(|(_, label)| label.clone())
+            .collect();
+
+        diff.added.sort_by(|a, b| a.text.cmp(&b.text));
+        diff.updated.sort_by(|a, b| a.1.text.cmp(&b.1.text));
+        diff.removed.sort_by(|a, b| a.text.cmp(&b.text));
+        diff.triples_added.sort();
+        diff
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.updated.is_empty() && self.removed.is_empty() && self.triples_added.is_empty()
+    }
+
+    /// Строки вида `+ [preferences 0.80] ...`, `~`, `-`
+    pub fn lines(&self) -> Vec<String> {
+        let mut lines = Vec::new();
+        for c in &self.added {
+            lines.push(format!("+ [{} {:.2}] {}", c.category, c.confidence, short(&c.text)));
+        }
+        for (was, now) in &self.updated {
+            let mut line = format!("~ [{}] {}", now.category, short(&now.text));
+            if (was.confidence - now.confidence).abs() >= MIN_CONFIDENCE_CHANGE {
+                line.push_str(&format!(": {:.2} → {:.2}", was.confidence, now.confidence));
+            }
+            if was.text != now.text {
+                line.push_str(&format!(" (was: {})", short(&was.text)));
+            }
+            lines.push(line);
+        }
+        for c in &self.removed {
+            lines.push(format!("- [{}] {}", c.category, short(&c.text)));
+        }
+        for triple in &self.triples_added {
+            lines.push(format!("+ graph: {}", triple));
+        }
+        lines
+    }
+}
+
+fn short(text: &str) -> String {
+    if text.chars().count() <= MAX_TEXT_CHARS {
+        return text.to_string();
+    }
+    let cut: String = text.chars().take(MAX_TEXT_CHARS).collect();
+    format!("{}...", cut)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn state(text: &str, confidence: f32) -> ConceptState {
+        ConceptState {
+            text: text.to_string(),
+            category: ConceptCategory::Preferences,
+            confidence,
+        }
+    }
+
+    #[test]
+    fn test_semantic_diff() {
+        let (tea, rust, cats) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        let mut before = SemanticSnapshot::default();
+        before.concepts.insert(tea, state("The user loves tea", 0.7));
+        before.concepts.insert(cats, state("The user has a cat", 0.9));
+
+        let mut after = before.clone();
+        after.concepts.get_mut(&tea).unwrap().confidence = 0.85;
+        after.concepts.get_mut(&cats).unwrap().confidence = 0.902;
+        after.concepts.insert(rust, state("The user writes Rust", 0.8));
+        after.triples.insert(Uuid::new_v4(), "user -likes-> tea".to_string());
+
+        let diff = SemanticDiff::between(&before, &after);
+        assert_eq!(
+            diff.lines(),
+            [
+                "+ [preferences 0.80] The user writes Rust",
+                "~ [preferences] The user loves tea: 0.70 → 0.85",
+                "+ graph: user -likes-> tea",
+            ]
+        );
+        assert!(SemanticDiff::between(&after, &after).is_empty());
+        assert_eq!(SemanticDiff::between(&after, &before).removed, [state("The user writes Rust", 0.8)]);
+    }
+}
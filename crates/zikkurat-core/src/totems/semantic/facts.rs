@@ -0,0 +1,179 @@
+//! 📌 Файл фактов
+//!
+//! Пользователь ведёт простой `facts.md` / `facts.yaml` («Мою собаку зовут Рекс»,
+//! «Я работаю в ночные смены»). Строки загружаются в семантическую память как
+//! предопределённые знания с высокой уверенностью и пересинхронизируются при
+//! изменении файла: новые строки добавляются, удалённые - забываются.
+//!
+//! Формат - список строк: `- факт`, `* факт`, `1. факт` или просто текст.
+//! Заголовок (`## preferences`) или ключ YAML (`preferences:`) с именем категории
+//! задаёт категорию для строк под ним; `#`-комментарии в YAML пропускаются.
+
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use super::concept::ConceptCategory;
+
+/// Где искать файл фактов, если он не указан явно
+pub const DEFAULT_FACTS_FILES: &[&str] = &["facts.md", "facts.yaml", "facts.yml"];
+/// Уверенность фактов, записанных самим пользователем
+pub const FACTS_CONFIDENCE: f32 = 0.95;
+/// Префикс `Concept::source` у концептов из файла фактов
+pub const FACTS_SOURCE_PREFIX: &str = "facts:";
+
+/// Строка файла фактов
+#[derive(Debug, Clone, PartialEq)]
+pub struct FactEntry {
+    pub text: String,
+    pub category: ConceptCategory,
+}
+
+/// Итог синхронизации
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FactsSync {
+    pub added: usize,
+    pub removed: usize,
+    pub unchanged: usize,
+}
+
+/// Разбирает markdown- или YAML-список фактов
+pub fn parse_facts(content: &str) -> Vec<FactEntry> {
+    let mut category = ConceptCategory::Facts;
+    let mut facts = Vec::new();
+
+    for line in content.lines() {
+        let line = line.trim();
+        if line.is_empty() || line == "---" {
+            continue;
+        }
+
+        // заголовок markdown или комментарий YAML
+        if let Some(heading) = line.strip_prefix('#') {
+            if let Ok(c) = heading.trim_start_matches('#').trim().parse() {
+                category = c;
+            }
+            continue;
+        }
+        // ключ YAML без значения: "preferences:"
+        if let Some(key) = line.strip_suffix(':') {
+            if !key.contains(' ') {
+                if let Ok(c) = key.parse() {
+                    category = c;
+                }
+                continue;
+            }
+        }
+
+        let text = strip_quotes(strip_bullet(line));
+        if !text.is_empty() {
+            facts.push(FactEntry {
+                text: text.to_string(),
+                category: category.clone(),
+            });
+        }
+    }
+    facts
+}
+
+fn strip_bullet(line: &str) -> &str {
+    if let Some(rest) = line.strip_prefix("- ").or_else(|| line.strip_prefix("* ")) {
+        return rest.trim();
+    }
+    let digits = line.chars().take_while(|c| c.is_ascii_digit()).count();
+    if digits > 0 {
+        if let Some(rest) = line[digits..].strip_prefix(". ") {
+            return rest.trim();
+        }
+    }
+    line
+}
+
+fn strip_quotes(text: &str) -> &str {
+    for quote in ['"', '\''] {
+        if text.len() >= 2 && text.starts_with(quote) && text.ends_with(quote) {
+            return &text[1..text.len() - 1];
+        }
+    }
+    text
+}
+
+/// Файл фактов и время его последнего изменения
+#[derive(Debug)]
+pub struct FactsFile {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+}
+
+impl FactsFile {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            modified: None,
+        }
+    }
+
+    /// Первый из [`DEFAULT_FACTS_FILES`], существующий в `dir`
+    pub fn find(dir: &Path) -> Option<Self> {
+        DEFAULT_FACTS_FILES
+            .iter()
+            .map(|name| dir.join(name))
+            .find(|path| path.is_file())
+            .map(Self::new)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// `Concept::source` концептов из этого файла
+    pub fn source(&self) -> String {
+        let name = self.path.file_name().map_or_else(
+            || self.path.display().to_string(),
+            |n| n.to_string_lossy().into_owned(),
+        );
+        format!("{}{}", FACTS_SOURCE_PREFIX, name)
+    }
+
+    /// Изменился ли файл с прошлого [`FactsFile::read`] (удалённый файл - тоже изменение)
+    pub fn changed(&self) -> bool {
+        let modified = std::fs::metadata(&self.path).and_then(|m| m.modified()).ok();
+        modified != self.modified
+    }
+
+    /// Читает факты; отсутствующий файл - пустой список (все факты забываются)
+    pub fn read(&mut self) -> Result<Vec<FactEntry>> {
+        self.modified = std::fs::metadata(&self.path).and_then(|m| m.modified()).ok();
+        if self.modified.is_none() {
+            return Ok(Vec::new());
+        }
+        let content = std::fs::read_to_string(&self.path)
+            .with_context(|| format!("Failed to read facts file {:?}", self.path))?;
+        Ok(parse_facts(&content))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_markdown_and_yaml() {
+        let md = "# My facts\n\n- My dog is called Rex\n* I work night shifts\n\n## Preferences\n1. I drink tea without sugar\n";
+        let facts = parse_facts(md);
+        assert_eq!(facts.len(), 3);
+        assert_eq!(facts[0].text, "My dog is called Rex");
+        assert_eq!(facts[0].category, ConceptCategory::Facts);
+        assert_eq!(facts[2].category, ConceptCategory::Preferences);
+
+        let yaml = "---\n# comment\nfacts:\n  - \"My dog is called Rex\"\ngoals:\n  - 'Run a marathon'\n";
+        let facts = parse_facts(yaml);
+        assert_eq!(
+            facts,
+            [
+                FactEntry { text: "My dog is called Rex".into(), category: ConceptCategory::Facts },
+                FactEntry { text: "Run a marathon".into(), category: ConceptCategory::Goals },
+            ]
+        );
+    }
+}
@@ -0,0 +1,193 @@
+//! 🧭 Обход графа знаний
+//!
+//! [`KnowledgeGraph`] хранит тройки, а [`GraphQuery`] по ним ходит: окрестность
+//! концепта на N шагов и кратчайший путь между двумя концептами, оба с фильтром
+//! по предикатам. Связи проходятся в обе стороны (`чай <-likes- user` тоже
+//! соседство), но в ответе остаются в своём направлении.
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, VecDeque};
+use uuid::Uuid;
+
+use super::concept::{KnowledgeGraph, Triple};
+
+/// Шагов в окрестности по умолчанию
+pub const DEFAULT_HOPS: usize = 2;
+/// Дальше путь между концептами не ищется
+pub const MAX_PATH_HOPS: usize = 6;
+
+/// Запрос к графу: окрестность `from` или путь от `from` до `to`
+#[derive(Debug, Clone, PartialEq)]
+pub struct GraphQuery {
+    pub from: Uuid,
+    /// Искать кратчайший путь до этого концепта
+    pub to: Option<Uuid>,
+    /// Проходить только по этим предикатам (пусто - по любым)
+    pub predicates: Vec<String>,
+    pub max_hops: usize,
+}
+
+impl GraphQuery {
+    /// Всё, что связано с `from` не дальше `hops` шагов
+    pub fn neighborhood(from: Uuid, hops: usize) -> Self {
+        Self {
+            from,
+            to: None,
+            predicates: Vec::new(),
+            max_hops: hops,
+        }
+    }
+
+    /// Кратчайшая цепочка связей от `from` до `to`
+    pub fn path(from: Uuid, to: Uuid) -> Self {
+        Self {
+            from,
+            to: Some(to),
+            predicates: Vec::new(),
+            max_hops: MAX_PATH_HOPS,
+        }
+    }
+
+    pub fn with_predicates<S: AsRef<str>>(mut self, predicates: impl IntoIterator<Item = S>) -> Self {
+        self.predicates = predicates.into_iter().map(|p| p.as_ref().trim().to_lowercase()).collect();
+        self
+    }
+
+    fn allows(&self, triple: &Triple) -> bool {
+        self.predicates.is_empty() || self.predicates.iter().any(|p| triple.predicate.eq_ignore_ascii_case(p))
+    }
+
+    /// Выполняет запрос; уверенность связей - с затуханием на момент `now`
+    pub fn run(&self, graph: &KnowledgeGraph, now: DateTime<Utc>) -> Subgraph {
+        // концепт -> (шагов от начала, связь, по которой пришли)
+        let mut reached: HashMap<Uuid, (usize, Option<&Triple>)> = HashMap::from([(self.from, (0, None))]);
+        let mut order = vec![self.from];
+        let mut edges = Vec::new();
+        let mut queue = VecDeque::from([self.from]);
+
+        while let Some(current) = queue.pop_front() {
+            let depth = reached[&current].0;
+            if Some(current) == self.to || depth >= self.max_hops {
+                continue;
+            }
+            let outgoing = graph.find_by_subject(&current).into_iter().map(|t| (t, t.object));
+            let incoming = graph.find_by_object(&current).into_iter().map(|t| (t, t.subject));
+            for (triple, next) in outgoing.chain(incoming).filter(|(t, _)| self.allows(t)) {
+                if let Entry::Vacant(slot) = reached.entry(next) {
+                    slot.insert((depth + 1, Some(triple)));
+                    order.push(next);
+                    queue.push_back(next);
+                    edges.push(GraphEdge::new(triple, now));
+                } else if self.to.is_none() && reached[&next].0 >= depth {
+                    // ребро внутри окрестности; петли и повторы через обратный индекс не дублируются
+                    let edge = GraphEdge::new(triple, now);
+                    if !edges.contains(&edge) {
+                        edges.push(edge);
+                    }
+                }
+            }
+        }
+
+        let Some(to) = self.to else {
+            return Subgraph {
+                nodes: order.into_iter().map(|id| (id, reached[&id].0)).collect(),
+                edges,
+            };
+        };
+        // путь собирается от цели назад по связям, которыми до неё дошли
+        let mut path = Vec::new();
+        let mut current = to;
+        while let Some(&(_, Some(triple))) = reached.get(&current) {
+            path.push(GraphEdge::new(triple, now));
+            current = if triple.object == current { triple.subject } else { triple.object };
+        }
+        if current != self.from || !reached.contains_key(&to) {
+            return Subgraph::default();
+        }
+        path.reverse();
+        let mut nodes = vec![(self.from, 0)];
+        let mut at = self.from;
+        for (i, edge) in path.iter().enumerate() {
+            at = if edge.subject == at { edge.object } else { edge.subject };
+            nodes.push((at, i + 1));
+        }
+        Subgraph { nodes, edges: path }
+    }
+}
+
+/// Связь в ответе
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct GraphEdge {
+    pub subject: Uuid,
+    pub predicate: String,
+    pub object: Uuid,
+    /// С затуханием
+    pub confidence: f32,
+}
+
+impl GraphEdge {
+    fn new(triple: &Triple, now: DateTime<Utc>) -> Self {
+        Self {
+            subject: triple.subject,
+            predicate: triple.predicate.clone(),
+            object: triple.object,
+            confidence: triple.get_effective_confidence(now),
+        }
+    }
+}
+
+/// Ответ на [`GraphQuery`]: для пути - его концепты и связи по порядку,
+/// пустой, если пути нет
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Subgraph {
+    /// Концепт и число шагов до него, в порядке обхода
+    pub nodes: Vec<(Uuid, usize)>,
+    pub edges: Vec<GraphEdge>,
+}
+
+impl Subgraph {
+    pub fn is_empty(&self) -> bool {
+        self.edges.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_neighborhood_and_shortest_path() {
+        let [user, tea, drink, water, rust] = [(); 5].map(|_| Uuid::new_v4());
+        let mut graph = KnowledgeGraph::new();
+        let now = Utc::now();
+        for (s, p, o) in [
+            (user, "likes", tea),
+            (tea, "is_a", drink),
+            (water, "is_a", drink),
+            (user, "likes", rust),
+            (user, "knows", rust),
+        ] {
+            graph.add_triple(Triple::new(s, p.to_string(), o).at(now));
+        }
+
+        let around = GraphQuery::neighborhood(tea, 1).run(&graph, now);
+        assert_eq!(around.nodes, vec![(tea, 0), (drink, 1), (user, 1)]);
+        assert_eq!(around.edges.len(), 2);
+        let far = GraphQuery::neighborhood(tea, 2).run(&graph, now);
+        assert_eq!(far.nodes.len(), 5);
+        assert_eq!(far.edges.len(), 5);
+        let likes = GraphQuery::neighborhood(user, 3).with_predicates(["Likes"]).run(&graph, now);
+        assert_eq!(likes.nodes.len(), 3);
+        assert!(likes.edges.iter().all(|e| e.predicate == "likes"));
+
+        // user -likes-> tea -is_a-> drink <-is_a- water
+        let path = GraphQuery::path(user, water).run(&graph, now);
+        let nodes: Vec<Uuid> = path.nodes.iter().map(|(id, _)| *id).collect();
+        assert_eq!(nodes, vec![user, tea, drink, water]);
+        assert_eq!(path.edges[2].subject, water);
+        assert!(GraphQuery::path(user, water).with_predicates(["likes"]).run(&graph, now).is_empty());
+        assert!(GraphQuery::path(rust, Uuid::new_v4()).run(&graph, now).is_empty());
+    }
+}
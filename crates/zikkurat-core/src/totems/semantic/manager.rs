@@ -0,0 +1,2067 @@
+//! 🧠 Менеджер семантической памяти
+//!
+//! Управляет концептами: добавление, поиск, объединение
+//! Извлечение концептов выполняется отдельно через SemanticExtractor
+
+use anyhow::Result;
+use regex::Regex;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+use super::clusters::{cluster_concepts, ConceptCluster};
+use super::concept::{
+    normalize_concept_text, normalize_tag, CategoryDecayStats, Concept, ConceptCategory, DecayStats, GraphStats,
+    KnowledgeGraph, KnowledgeSource, TagFilter, Triple,
+};
+use super::conflict::{find_conflicts, ConceptConflict};
+use super::correction::CorrectionRequest;
+use super::diff::{ConceptState, SemanticSnapshot};
+use super::facts::{FactEntry, FactsSync, FACTS_CONFIDENCE};
+use super::graph_query::{GraphQuery, Subgraph};
+use super::persistence::{SemanticPersistenceManager, KNOWLEDGE_GRAPH_FILE};
+use super::provenance::{add_evidence, AuditAction, AuditEntry, AuditLog, Evidence, TurnRef};
+use super::retention::RetentionConfig;
+use super::reasoning::{same_stem, GraphAnswer, RelationalQuery, USER_ALIASES};
+use super::sensitive::{PendingConcept, SensitiveDecision, SensitivePolicy, SENSITIVE_POLICY_FILE};
+use crate::priests::embeddings::{AsyncEmbedder, Embedder};
+use crate::totems::retrieval::vector_store::cosine_similarity;
+use crate::totems::user::UserId;
+use crate::utils::clock::{self, SharedClock};
+
+pub(crate) fn remove_negation(text: &str) -> String {
+    let mut result = text.to_string();
+    let negations = [
+        "don't ",
+        "don't ",
+        "doesn't ",
+        "didn't ",
+        "not ",
+        "n't ",
+        "не ",
+        "нельзя ",
+        "не люблю ",
+        "не нравится ",
+    ];
+    for neg in &negations {
+        result = result.replace(neg, "");
+    }
+    result.trim().to_string()
+}
+
+fn is_contradiction(text1: &str, text2: &str) -> bool {
+    let t1 = text1.to_lowercase();
+    let t2 = text2.to_lowercase();
+
+    let t1_neg = t1.contains("n't")
+        || t1.contains("not ")
+        || t1.contains("не ")
+        || t1.contains("нельзя")
+        || t1.contains("не люблю")
+        || t1.contains("не нравится");
+    let t2_neg = t2.contains("n't")
+        || t2.contains("not ")
+        || t2.contains("не ")
+        || t2.contains("нельзя")
+        || t2.contains("не люблю")
+        || t2.contains("не нравится");
+
+    if t1_neg != t2_neg {
+        let base1 = remove_negation(&t1);
+        let base2 = remove_negation(&t2);
+
+        let check_words = [
+            "love",
+            "loves",
+            "loved",
+            "люблю",
+            "любит",
+            "любил",
+            "like",
+            "likes",
+            "liked",
+            "нравится",
+            "нравилось",
+            "понравилось",
+            "prefer",
+            "prefers",
+            "preferred",
+            "предпочитаю",
+            "предпочитает",
+            "предпочитал",
+            "hate",
+            "hates",
+            "hated",
+            "ненавижу",
+            "ненавидит",
+            "ненавидел",
+            "enjoy",
+            "enjoys",
+            "enjoyed",
+        ];
+
+        let has_match1 = check_words.iter().any(|w| base1.contains(*w));
+        let has_match2 = check_words.iter().any(|w| base2.contains(*w));
+        if has_match1 && has_match2 {
+            return true;
+        }
+    }
+
+    false
+}
+
+pub type ExtractionResult = Vec<(String, String, f32, Vec<String>)>; // (text, category, confidence, tags)
+
+/// Ключевые слова для автоподсказки тегов, когда экстрактор их не вернул
+const TAG_KEYWORDS: &[(&str, &[&str])] = &[
+    ("work", &["work", "job", "office", "boss", "colleague", "career", "работ", "офис", "начальник", "коллег", "карьер"]),
+    ("family", &["family", "mother", "father", "dad", "wife", "husband", "daughter", "sister", "brother", "семь", "мама", "папа", "жена", "муж", "сын", "дочь", "сестр", "брат"]),
+    ("health", &["health", "doctor", "sick", "allergy", "allergic", "diet", "medicine", "здоров", "врач", "болею", "болезн", "аллерг", "диет", "лекарств"]),
+    ("food", &["pizza", "sushi", "coffee", "food", "пицц", "суши", "кофе", "еда"]),
+    ("hobby", &["hobby", "game", "music", "sport", "travel", "хобби", "игр", "музык", "спорт", "путешеств"]),
+    ("tech", &["rust", "python", "code", "programming", "linux", "docker", "код", "программ"]),
+];
+
+/// Предлагает теги для текста концепта по ключевым словам
+pub fn suggest_tags(text: &str) -> Vec<String> {
+    let lower = text.to_lowercase();
+    TAG_KEYWORDS
+        .iter()
+        .filter(|(_, keywords)| keywords.iter().any(|k| lower.contains(k)))
+        .map(|(tag, _)| tag.to_string())
+        .collect()
+}
+
+/// Минимальное сходство, при котором исправление без `old` переписывает концепт, а не создаёт новый
+const CORRECTION_MATCH_THRESHOLD: f32 = 0.6;
+/// Уверенность в том, что пользователь исправил сам
+const CORRECTION_CONFIDENCE: f32 = 0.95;
+/// Слова, по которым новый концепт из исправления считается предпочтением
+const PREFERENCE_WORDS: &[&str] = &["prefer", "like", "love", "предпочита", "люблю", "нравится"];
+
+/// Шаблоны отношений вида "X likes Y" -> (X, likes, Y)
+const THIRD_PERSON_RELATIONS: &[(&str, &str)] = &[
+    (r#"(.+)\s+is\s+a\s+([a-z]+)"#, "is_a"),
+    (r#"(.+)\s+likes\s+(.+)"#, "likes"),
+    (r#"(.+)\s+wants\s+(.+)"#, "wants"),
+    (r#"(.+)\s+has\s+(.+)"#, "has"),
+    (r#"(.+)\s+—\s+это\s+(.+)"#, "is_a"),
+    (r#"(.+)\s+любит\s+(.+)"#, "likes"),
+    (r#"(.+)\s+хочет\s+(.+)"#, "wants"),
+];
+/// Шаблоны от первого лица -> (i, likes, Y): применяются только к словам пользователя
+const FIRST_PERSON_RELATIONS: &[(&str, &str)] = &[
+    (r#"(?i)\b(i)\s+(?:like|love)\s+(.+)"#, "likes"),
+    (r#"(?i)\b(i)\s+want\s+(.+)"#, "wants"),
+    (r#"(?i)(я)\s+люблю\s+(.+)"#, "likes"),
+    (r#"(?i)(я)\s+хочу\s+(.+)"#, "wants"),
+];
+
+/// Результат исправления: новая версия концепта и текст, который она заменила
+#[derive(Debug, Clone)]
+pub struct Correction {
+    pub concept: Concept,
+    pub replaced: Option<String>,
+}
+
+pub trait ConceptExtractor: Send + Sync {
+    fn extract(
+        &mut self,
+        user_query: &str,
+        assistant_response: &str,
+        session_id: &str,
+    ) -> Result<ExtractionResult>;
+}
+
+pub struct SemanticMemoryManager {
+    concepts: HashMap<uuid::Uuid, Concept>,
+    embedder: Arc<dyn Embedder>,
+    persistence: SemanticPersistenceManager,
+    category_index: HashMap<ConceptCategory, Vec<uuid::Uuid>>,
+    extractor: Option<Arc<std::sync::Mutex<dyn ConceptExtractor>>>,
+    knowledge_graph: KnowledgeGraph,
+    sensitive: SensitivePolicy,
+    /// Чувствительные концепты, ждущие подтверждения
+    pending_sensitive: Vec<PendingConcept>,
+    /// Чьи это знания: входит в ID концепта
+    user: UserId,
+    /// [`Concept::content_id`] текущей и прежних формулировок -> ID концепта
+    content_index: HashMap<uuid::Uuid, uuid::Uuid>,
+    /// Время для затухания и отметок изменений
+    clock: SharedClock,
+    /// Конфликты, которые пользователь оставил как есть (до конца запуска)
+    dismissed_conflicts: HashSet<(uuid::Uuid, uuid::Uuid)>,
+    /// Журнал изменений концептов
+    audit: AuditLog,
+    /// Затухание и пределы по категориям (см. [`super::retention`])
+    retention: RetentionConfig,
+}
+
+/// Пользователь по умолчанию (однопользовательский CLI)
+pub const DEFAULT_USER_ID: &str = UserId::DEFAULT;
+
+impl SemanticMemoryManager {
+    /// Загружает концепты из хранилища и пересчитывает их эмбеддинги
+    pub async fn open(
+        embedder: Arc<dyn Embedder>,
+        persistence: SemanticPersistenceManager,
+    ) -> Result<Self> {
+        let mut manager = Self {
+            concepts: HashMap::new(),
+            embedder,
+            category_index: HashMap::new(),
+            extractor: None,
+            knowledge_graph: KnowledgeGraph::new(),
+            sensitive: SensitivePolicy::default(),
+            pending_sensitive: Vec::new(),
+            user: UserId::default(),
+            content_index: HashMap::new(),
+            clock: clock::default_clock(),
+            dismissed_conflicts: HashSet::new(),
+            audit: persistence.audit_log(),
+            retention: RetentionConfig::default(),
+            persistence,
+        };
+
+        let policy_path = manager.persistence.storage_path().with_file_name(SENSITIVE_POLICY_FILE);
+        manager.sensitive = SensitivePolicy::load(&policy_path)?;
+
+        if let Some(loaded) = manager.persistence.load().await? {
+            let texts = loaded.iter().map(|c| c.text.clone()).collect();
+            let embeddings = manager.embedder.embed_batch_async(texts).await?;
+            for (mut concept, embedding) in loaded.into_iter().zip(embeddings) {
+                manager.index_concept(&concept.id, &concept.category);
+                manager.index_content(&concept);
+                concept.embedding = embedding;
+                manager.concepts.insert(concept.id, concept);
+            }
+        }
+
+        Ok(manager)
+    }
+
+    /// Синхронная версия [`SemanticMemoryManager::open`]
+    pub fn new(
+        embedder: Arc<dyn Embedder>,
+        persistence: SemanticPersistenceManager,
+    ) -> Result<Self> {
+        crate::utils::block_on(Self::open(embedder, persistence))
+    }
+
+    pub fn with_extractor(
+        embedder: Arc<dyn Embedder>,
+        persistence: SemanticPersistenceManager,
+        extractor: Arc<std::sync::Mutex<dyn ConceptExtractor>>,
+    ) -> Result<Self> {
+        let mut manager = Self::new(embedder, persistence)?;
+        manager.extractor = Some(extractor);
+        Ok(manager)
+    }
+
+    pub fn set_extractor(&mut self, extractor: Arc<std::sync::Mutex<dyn ConceptExtractor>>) {
+        self.extractor = Some(extractor);
+    }
+
+    /// Подменяет часы (тесты и сценарии поведения)
+    pub fn set_clock(&mut self, clock: SharedClock) {
+        self.clock = clock;
+    }
+
+    pub fn clock(&self) -> &SharedClock {
+        &self.clock
+    }
+
+    /// Политики хранения по категориям; применяются при следующем затухании
+    pub fn set_retention(&mut self, retention: RetentionConfig) {
+        self.retention = retention;
+    }
+
+    pub fn retention(&self) -> &RetentionConfig {
+        &self.retention
+    }
+
+    /// Отмечает концепты, попавшие в ответ: давно не использованные первыми
+    /// вытесняются сверх предела категории
+    pub fn mark_used(&mut self, ids: &[uuid::Uuid]) {
+        let now = self.clock.now();
+        for id in ids {
+            if let Some(concept) = self.concepts.get_mut(id) {
+                concept.usage_count = concept.usage_count.saturating_add(1);
+                concept.last_used = Some(now);
+            }
+        }
+    }
+
+    pub async fn with_concepts(
+        embedder: Arc<dyn Embedder>,
+        persistence: SemanticPersistenceManager,
+        concepts: Vec<Concept>,
+    ) -> Result<Self> {
+        let mut manager = Self {
+            concepts: HashMap::new(),
+            embedder,
+            category_index: HashMap::new(),
+            extractor: None,
+            knowledge_graph: KnowledgeGraph::new(),
+            sensitive: SensitivePolicy::default(),
+            pending_sensitive: Vec::new(),
+            user: UserId::default(),
+            content_index: HashMap::new(),
+            clock: clock::default_clock(),
+            dismissed_conflicts: HashSet::new(),
+            audit: persistence.audit_log(),
+            retention: RetentionConfig::default(),
+            persistence,
+        };
+
+        let texts = concepts.iter().map(|c| c.text.clone()).collect();
+        let embeddings = manager.embedder.embed_batch_async(texts).await?;
+        for (mut concept, embedding) in concepts.into_iter().zip(embeddings) {
+            concept.embedding = embedding;
+            manager.concepts.insert(concept.id, concept.clone());
+            manager.index_concept(&concept.id, &concept.category);
+            manager.index_content(&concept);
+        }
+
+        Ok(manager)
+    }
+
+    fn index_concept(&mut self, id: &uuid::Uuid, category: &ConceptCategory) {
+        self.category_index
+            .entry(category.clone())
+            .or_insert_with(Vec::new)
+            .push(*id);
+    }
+
+    /// Индексирует текущую и прежние формулировки концепта
+    fn index_content(&mut self, concept: &Concept) {
+        for text in std::iter::once(&concept.text).chain(&concept.previous_texts) {
+            self.content_index
+                .insert(Concept::content_id(self.user.as_str(), text), concept.id);
+        }
+    }
+
+    /// Меняет пользователя, к которому относятся новые концепты
+    pub fn set_user(&mut self, user: UserId) {
+        self.user = user;
+        let concepts: Vec<Concept> = self.concepts.values().cloned().collect();
+        self.content_index.clear();
+        for concept in &concepts {
+            self.index_content(concept);
+        }
+    }
+
+    /// Пользователь, чьи это знания
+    pub fn user(&self) -> &UserId {
+        &self.user
+    }
+
+    /// ID концепта с этим текстом (в т.ч. по прежней формулировке)
+    pub fn find_by_content(&self, text: &str) -> Option<uuid::Uuid> {
+        self.content_index
+            .get(&Concept::content_id(self.user.as_str(), text))
+            .copied()
+    }
+
+    /// Добавляет концепт. Идемпотентно: тот же текст (с точностью до регистра и пробелов)
+    /// возвращает уже сохранённый концепт, противоречащий факт с большей уверенностью
+    /// становится новой версией существующего
+    #[tracing::instrument(name = "semantic_add", skip(self, text, source))]
+    pub async fn add_concept(
+        &mut self,
+        text: String,
+        category: ConceptCategory,
+        source: String,
+        confidence: Option<f32>,
+    ) -> Result<Concept> {
+        Ok(self.upsert_concept(text, category, source, confidence).await?.0)
+    }
+
+    /// [`SemanticMemoryManager::add_concept`] и что стало с концептом: новая реплика
+    /// подтверждает его, если только не была отклонена как противоречие
+    async fn upsert_concept(
+        &mut self,
+        text: String,
+        category: ConceptCategory,
+        source: String,
+        confidence: Option<f32>,
+    ) -> Result<(Concept, AuditAction)> {
+        let cleaned_text = text
+            .trim()
+            .replace("  ", " ")
+            .replace(" .", ".")
+            .replace(" ,", ",");
+
+        // Same fact seen again (re-extraction): no new concept, no new UUID
+        let content_id = Concept::content_id(self.user.as_str(), &cleaned_text);
+        if let Some(existing) = self
+            .content_index
+            .get(&content_id)
+            .copied()
+            .and_then(|id| self.concepts.get_mut(&id))
+        {
+            // an outdated formulation must not raise the confidence of the current one
+            let is_current = normalize_concept_text(&existing.text) == normalize_concept_text(&cleaned_text);
+            let before = existing.confidence;
+            existing.last_used = Some(self.clock.now());
+            if let Some(new_conf) = confidence {
+                if is_current && new_conf > existing.confidence {
+                    existing.confidence = new_conf;
+                    existing.updated_at = self.clock.now();
+                }
+            }
+            let mut entry = AuditEntry::new(AuditAction::Reinforced, existing, &source, self.clock.now())
+                .before(&existing.text, before);
+            if !is_current {
+                entry = entry.with_detail(cleaned_text);
+            }
+            let existing = existing.clone();
+            self.audit.append(&[entry])?;
+            return Ok((existing, AuditAction::Reinforced));
+        }
+
+        let embedding = self.embedder.embed_async(&cleaned_text).await?;
+
+        let normalized_text = cleaned_text.to_lowercase();
+
+        // Check for contradictions
+        let contradicted = self
+            .concepts
+            .values()
+            .find(|existing| is_contradiction(&normalized_text, &existing.text.to_lowercase()))
+            .map(|existing| existing.id);
+        if let Some(id) = contradicted {
+            let new_conf = confidence.unwrap_or(0.5);
+            let now = self.clock.now();
+            let existing = self.concepts.get_mut(&id).expect("contradicted concept exists");
+            if new_conf > existing.confidence {
+                // The new fact replaces the old one as its next version
+                let (old_text, old_conf) = (existing.text.clone(), existing.confidence);
+                existing.revise(cleaned_text, embedding, now);
+                existing.confidence = new_conf;
+                existing.last_used = Some(now);
+                let revised = existing.clone();
+                self.content_index.insert(content_id, id);
+                let entry = AuditEntry::new(AuditAction::Revised, &revised, &source, now).before(&old_text, old_conf);
+                self.audit.append(&[entry])?;
+                return Ok((revised, AuditAction::Revised));
+            }
+            // Keep existing, return it
+            let existing = existing.clone();
+            let entry = AuditEntry::new(AuditAction::Contradicted, &existing, &source, now)
+                .with_detail(format!("{} ({:.2})", cleaned_text, new_conf));
+            self.audit.append(&[entry])?;
+            return Ok((existing, AuditAction::Contradicted));
+        }
+
+        // Check for duplicates using similarity
+        let duplicate = self
+            .concepts
+            .values()
+            .find(|existing| cosine_similarity(&embedding, &existing.embedding) > 0.95)
+            .map(|existing| existing.id);
+        if let Some(id) = duplicate {
+            // Merge concepts - keep higher confidence, remember the wording
+            self.content_index.insert(content_id, id);
+            let existing = self.concepts.get_mut(&id).expect("duplicate concept exists");
+            let before = existing.confidence;
+            existing.last_used = Some(self.clock.now());
+            if let Some(new_conf) = confidence {
+                if new_conf > existing.confidence {
+                    existing.confidence = new_conf;
+                    existing.updated_at = self.clock.now();
+                }
+            }
+            let existing = existing.clone();
+            let entry = AuditEntry::new(AuditAction::Merged, &existing, &source, self.clock.now())
+                .before(&existing.text, before)
+                .with_detail(cleaned_text);
+            self.audit.append(&[entry])?;
+            return Ok((existing, AuditAction::Merged));
+        }
+
+        // Create new concept
+        let mut concept = Concept::new(cleaned_text, category.clone(), source).at(self.clock.now());
+        concept.id = content_id;
+        if let Some(conf) = confidence {
+            concept = concept.with_confidence(conf);
+        }
+        concept.embedding = embedding.clone();
+        self.index_concept(&concept.id, &category);
+        self.index_content(&concept);
+        self.concepts.insert(concept.id, concept.clone());
+        let entry = AuditEntry::new(AuditAction::Created, &concept, &concept.source, self.clock.now());
+        self.audit.append(&[entry])?;
+        Ok((concept, AuditAction::Created))
+    }
+
+    pub async fn search(
+        &self,
+        query: &str,
+        top_k: usize,
+        category: Option<ConceptCategory>,
+    ) -> Vec<(f32, &Concept)> {
+        self.search_with_tags(query, top_k, category, &TagFilter::default())
+            .await
+    }
+
+    /// Поиск с фильтрацией по тегам (include/exclude)
+    #[tracing::instrument(name = "semantic_search", skip(self, query, tags), fields(concepts = self.concepts.len()))]
+    pub async fn search_with_tags(
+        &self,
+        query: &str,
+        top_k: usize,
+        category: Option<ConceptCategory>,
+        tags: &TagFilter,
+    ) -> Vec<(f32, &Concept)> {
+        let query_embedding = match self.embedder.embed_async(query).await {
+            Ok(embedding) => embedding,
+            Err(_) => return Vec::new(),
+        };
+
+        let mut candidates = self
+            .concepts
+            .values()
+            .filter(|c| {
+                if let Some(cat) = &category {
+                    c.category == *cat
+                } else {
+                    true
+                }
+            })
+            .filter(|c| tags.matches(c))
+            .collect::<Vec<_>>();
+
+        candidates.sort_by(|a, b| {
+            let sim_a = cosine_similarity(&query_embedding, &a.embedding);
+            let sim_b = cosine_similarity(&query_embedding, &b.embedding);
+            sim_b
+                .partial_cmp(&sim_a)
+                .unwrap_or(std::cmp::Ordering::Equal)
+        });
+
+        candidates
+            .into_iter()
+            .take(top_k)
+            .map(|c| {
+                let sim = cosine_similarity(&query_embedding, &c.embedding);
+                (sim, c)
+            })
+            .collect()
+    }
+
+    pub async fn search_by_text(&self, query: &str, top_k: usize) -> Vec<(f32, &Concept)> {
+        self.search(query, top_k, None).await
+    }
+
+    pub async fn search_by_category(
+        &self,
+        query: &str,
+        category: ConceptCategory,
+        top_k: usize,
+    ) -> Vec<(f32, &Concept)> {
+        self.search(query, top_k, Some(category)).await
+    }
+
+    pub fn get_concepts_by_category(&self, category: &ConceptCategory) -> Vec<&Concept> {
+        if let Some(ids) = self.category_index.get(category) {
+            ids.iter().filter_map(|id| self.concepts.get(id)).collect()
+        } else {
+            Vec::new()
+        }
+    }
+
+    pub fn count(&self) -> usize {
+        self.concepts.len()
+    }
+
+    /// Концепты и связи графа сейчас, для диффа памяти за ход
+    pub fn snapshot(&self) -> SemanticSnapshot {
+        let concepts = self
+            .concepts
+            .iter()
+            .map(|(id, c)| {
+                let state = ConceptState {
+                    text: c.text.clone(),
+                    category: c.category.clone(),
+                    confidence: c.confidence,
+                };
+                (*id, state)
+            })
+            .collect();
+        let text = |id: &uuid::Uuid| self.concepts.get(id).map_or_else(|| id.to_string(), |c| c.text.clone());
+        let triples = self
+            .knowledge_graph
+            .triples
+            .iter()
+            .map(|(id, t)| (*id, format!("{} -{}-> {}", text(&t.subject), t.predicate, text(&t.object))))
+            .collect();
+        SemanticSnapshot { concepts, triples }
+    }
+
+    /// Get concept by ID
+    pub fn get_concept(&self, id: &uuid::Uuid) -> Option<&Concept> {
+        self.concepts.get(id)
+    }
+
+    /// Находит концепт по полному ID или его префиксу (как показывает `/semantic list`)
+    pub fn resolve_id(&self, id_or_prefix: &str) -> Result<uuid::Uuid> {
+        if let Ok(id) = uuid::Uuid::parse_str(id_or_prefix) {
+            if self.concepts.contains_key(&id) {
+                return Ok(id);
+            }
+        }
+
+        let prefix = id_or_prefix.to_lowercase();
+        let matches: Vec<uuid::Uuid> = self
+            .concepts
+            .keys()
+            .filter(|id| id.to_string().starts_with(&prefix))
+            .copied()
+            .collect();
+
+        match matches.len() {
+            0 => anyhow::bail!("Concept not found: {}", id_or_prefix),
+            1 => Ok(matches[0]),
+            n => anyhow::bail!("Ambiguous concept id '{}': {} matches", id_or_prefix, n),
+        }
+    }
+
+    // ============ Tags ============
+
+    /// Добавить тег концепту
+    pub fn add_tag(&mut self, id: &uuid::Uuid, tag: &str) -> Result<bool> {
+        let concept = self
+            .concepts
+            .get_mut(id)
+            .ok_or_else(|| anyhow::anyhow!("Concept not found: {}", id))?;
+        Ok(concept.add_tag(tag, self.clock.now()))
+    }
+
+    /// Снять тег с концепта
+    pub fn remove_tag(&mut self, id: &uuid::Uuid, tag: &str) -> Result<bool> {
+        let concept = self
+            .concepts
+            .get_mut(id)
+            .ok_or_else(|| anyhow::anyhow!("Concept not found: {}", id))?;
+        Ok(concept.remove_tag(tag))
+    }
+
+    /// Концепты с указанным тегом
+    pub fn get_concepts_by_tag(&self, tag: &str) -> Vec<&Concept> {
+        self.concepts.values().filter(|c| c.has_tag(tag)).collect()
+    }
+
+    /// Все теги с количеством концептов, по убыванию
+    pub fn tag_counts(&self) -> Vec<(String, usize)> {
+        let mut counts: HashMap<String, usize> = HashMap::new();
+        for concept in self.concepts.values() {
+            for tag in &concept.tags {
+                *counts.entry(tag.clone()).or_insert(0) += 1;
+            }
+        }
+        let mut counts: Vec<_> = counts.into_iter().collect();
+        counts.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        counts
+    }
+
+    #[tracing::instrument(name = "semantic_extract", skip_all)]
+    pub async fn extract_from_dialogue(
+        &mut self,
+        user_query: &str,
+        assistant_response: &str,
+        origin: &TurnRef,
+    ) -> Result<usize> {
+        // Экстрактор гоняет LLM синхронно - уводим его в blocking-пул
+        let raw_results = if let Some(extractor) = &self.extractor {
+            let extractor = extractor.clone();
+            let (user_query, assistant_response, session_id) = (
+                user_query.to_string(),
+                assistant_response.to_string(),
+                origin.session_id.clone(),
+            );
+            let run = move || {
+                let mut extractor = extractor.lock().unwrap();
+                extractor.extract(&user_query, &assistant_response, &session_id)
+            };
+            #[cfg(feature = "runtime")]
+            let extracted = tokio::task::spawn_blocking(run).await??;
+            #[cfg(not(feature = "runtime"))]
+            let extracted = run()?;
+            extracted
+        } else {
+            Vec::new()
+        };
+
+        let parsed = self
+            .parse_extraction(raw_results, origin, user_query, assistant_response)
+            .await?;
+        Ok(parsed.len())
+    }
+
+    async fn parse_extraction(
+        &mut self,
+        results: ExtractionResult,
+        origin: &TurnRef,
+        user_query: &str,
+        assistant_response: &str,
+    ) -> Result<Vec<Concept>> {
+        let mut extracted = Vec::new();
+
+        for (text, category_str, confidence, tags) in results {
+            if text.trim().is_empty() {
+                continue;
+            }
+
+            let category: ConceptCategory =
+                category_str.parse().unwrap_or(ConceptCategory::General);
+
+            match self.sensitive.decide(&text) {
+                SensitiveDecision::Store => {}
+                SensitiveDecision::Skip => continue,
+                SensitiveDecision::Ask(kind) => {
+                    self.pending_sensitive.push(PendingConcept {
+                        text: text.trim().to_string(),
+                        category,
+                        confidence,
+                        tags,
+                        source: origin.session_id.clone(),
+                        utterance: user_query.to_string(),
+                        turn: origin.turn,
+                        kind,
+                    });
+                    continue;
+                }
+            }
+
+            if let Ok((concept, outcome)) = self
+                .upsert_concept(
+                    text.trim().to_string(),
+                    category.clone(),
+                    origin.session_id.clone(),
+                    Some(confidence),
+                )
+                .await
+            {
+                let tags = if tags.is_empty() {
+                    suggest_tags(&text)
+                } else {
+                    tags.iter().map(|t| normalize_tag(t)).collect()
+                };
+                for tag in &tags {
+                    self.add_tag(&concept.id, tag)?;
+                }
+                let supports = outcome != AuditAction::Contradicted;
+                extracted.push(self.record_utterance(&concept.id, user_query, origin, supports).unwrap_or(concept));
+            }
+        }
+
+        // Extract relations from the dialogue. "I like..." is a user fact only in the
+        // user's own words: the assistant's "I love..." is about the assistant
+        let dialogue_text = format!("{} {}", user_query, assistant_response);
+        self.extract_relations(&dialogue_text, THIRD_PERSON_RELATIONS, &origin.session_id)
+            .await?;
+        self.extract_relations(user_query, FIRST_PERSON_RELATIONS, &origin.session_id)
+            .await?;
+
+        Ok(extracted)
+    }
+
+    /// Забирает чувствительные концепты, ожидающие подтверждения
+    pub fn take_pending_sensitive(&mut self) -> Vec<PendingConcept> {
+        std::mem::take(&mut self.pending_sensitive)
+    }
+
+    /// Применяет ответ пользователя: запоминает его и сохраняет концепт при согласии
+    pub async fn resolve_sensitive(
+        &mut self,
+        pending: PendingConcept,
+        store: bool,
+    ) -> Result<Option<Concept>> {
+        self.sensitive.remember(&pending.text, store);
+        self.sensitive.save()?;
+        if !store {
+            return Ok(None);
+        }
+
+        let origin = TurnRef { session_id: pending.source.clone(), turn: pending.turn };
+        let (concept, outcome) = self
+            .upsert_concept(pending.text.clone(), pending.category, pending.source, Some(pending.confidence))
+            .await?;
+        let tags = if pending.tags.is_empty() {
+            suggest_tags(&pending.text)
+        } else {
+            pending.tags.iter().map(|t| normalize_tag(t)).collect()
+        };
+        for tag in &tags {
+            self.add_tag(&concept.id, tag)?;
+        }
+        let supports = outcome != AuditAction::Contradicted;
+        Ok(Some(self.record_utterance(&concept.id, &pending.utterance, &origin, supports).unwrap_or(concept)))
+    }
+
+    /// Запоминает реплику, из которой впервые извлечён концепт; повторное
+    /// извлечение того же факта первоисточник не перезаписывает. Реплика,
+    /// подтверждающая концепт (`supports`), добавляется к его свидетельствам
+    fn record_utterance(&mut self, id: &uuid::Uuid, utterance: &str, origin: &TurnRef, supports: bool) -> Option<Concept> {
+        let now = self.clock.now();
+        let concept = self.concepts.get_mut(id)?;
+        if concept.utterance.is_none() {
+            concept.set_utterance(utterance);
+        }
+        if supports {
+            add_evidence(concept, Evidence::new(origin, utterance, now));
+        }
+        Some(concept.clone())
+    }
+
+    pub fn sensitive_policy(&self) -> &SensitivePolicy {
+        &self.sensitive
+    }
+
+    /// Политика по категориям (`/sensitive`), после изменения - [`SensitivePolicy::save`]
+    pub fn sensitive_policy_mut(&mut self) -> &mut SensitivePolicy {
+        &mut self.sensitive
+    }
+
+    pub fn find_similar_text(&self, text: &str, threshold: f32) -> Vec<&Concept> {
+        let target = text.to_lowercase();
+        self.concepts
+            .values()
+            .filter(|c| {
+                let c_text = c.text.to_lowercase();
+                let similarity = text_similarity(&target, &c_text);
+                similarity > threshold
+            })
+            .collect()
+    }
+
+    /// Применить временное затухание ко всем концептам
+    pub fn apply_temporal_decay(&mut self) -> Result<usize> {
+        let mut concepts_to_remove = Vec::new();
+        let mut updated_count: usize = 0;
+        let mut entries = Vec::new();
+        let now = self.clock.now();
+
+        for (id, concept) in &mut self.concepts {
+            let before = concept.confidence;
+            let keep = concept.apply_decay_with(&self.retention.policy(&concept.category).decay, now);
+            let action = if keep { AuditAction::Decayed } else { AuditAction::Removed };
+            if !keep || concept.confidence != before {
+                entries.push(AuditEntry::new(action, concept, "decay", now).before(&concept.text, before));
+            }
+            if !keep {
+                concepts_to_remove.push(*id);
+            } else {
+                updated_count += 1;
+            }
+        }
+
+        // Удаляем концепты с низкой уверенностью
+        for id in concepts_to_remove {
+            if let Some(concept) = self.concepts.remove(&id) {
+                // Удаляем из категорийного индекса
+                if let Some(index) = self.category_index.get_mut(&concept.category) {
+                    index.retain(|&x| x != id);
+                }
+            }
+        }
+        self.audit.append(&entries)?;
+
+        // Сверх предела категории уходят давно не использованные, вместе со связями в графе
+        let evicted = self.retention.over_limit(self.concepts.values());
+        for id in &evicted {
+            let Some(concept) = self.concepts.get(id) else {
+                continue;
+            };
+            let limit = self.retention.policy(&concept.category).max_count;
+            let detail = format!("over the {} limit of {}", concept.category, limit);
+            if self.remove_with_reason(id, "retention", Some(&detail))?.is_some() {
+                updated_count = updated_count.saturating_sub(1);
+            }
+        }
+
+        // Сохраняем изменения
+        if !self.concepts.is_empty() {
+            let concepts: Vec<Concept> = self.concepts.values().cloned().collect();
+            crate::utils::block_on(self.persistence.save(&concepts))?;
+        }
+        if !evicted.is_empty() {
+            crate::utils::block_on(self.save_graph())?;
+        }
+
+        Ok(updated_count)
+    }
+
+    /// Сливает концепты одной категории, чьи эмбеддинги ближе `threshold`. Остаётся
+    /// более уверенный (при равенстве - старший): он забирает свидетельства, теги и
+    /// связи в графе слитого. Возвращает, сколько концептов слито
+    pub fn merge_similar(&mut self, threshold: f32) -> Result<usize> {
+        let mut ids: Vec<uuid::Uuid> = self.concepts.keys().copied().collect();
+        ids.sort_by(|a, b| {
+            let (a, b) = (&self.concepts[a], &self.concepts[b]);
+            b.confidence
+                .total_cmp(&a.confidence)
+                .then(a.created_at.cmp(&b.created_at))
+                .then(a.id.cmp(&b.id))
+        });
+
+        let mut merged = 0;
+        for (i, keep_id) in ids.iter().enumerate() {
+            let Some(keep) = self.concepts.get(keep_id) else {
+                continue;
+            };
+            let similar: Vec<uuid::Uuid> = ids[i + 1..]
+                .iter()
+                .filter(|id| {
+                    self.concepts.get(id).is_some_and(|other| {
+                        other.category == keep.category
+                            && !keep.embedding.is_empty()
+                            && other.embedding.len() == keep.embedding.len()
+                            && cosine_similarity(&keep.embedding, &other.embedding) > threshold
+                    })
+                })
+                .copied()
+                .collect();
+            for other_id in similar {
+                self.absorb(keep_id, &other_id)?;
+                merged += 1;
+            }
+        }
+        Ok(merged)
+    }
+
+    /// Переносит концепт `other_id` в `keep_id` и убирает его
+    fn absorb(&mut self, keep_id: &uuid::Uuid, other_id: &uuid::Uuid) -> Result<()> {
+        let keep_text = self.concepts[keep_id].text.clone();
+        let wordings: Vec<uuid::Uuid> = self
+            .content_index
+            .iter()
+            .filter(|(_, target)| *target == other_id)
+            .map(|(content_id, _)| *content_id)
+            .collect();
+        self.knowledge_graph.redirect(other_id, keep_id);
+        let Some(other) = self.remove_with_reason(other_id, "merge", Some(&keep_text))? else {
+            return Ok(());
+        };
+        // формулировки слитого концепта теперь подтверждают оставшийся
+        for content_id in wordings {
+            self.content_index.insert(content_id, *keep_id);
+        }
+
+        let now = self.clock.now();
+        let keep = self.concepts.get_mut(keep_id).expect("merge target exists");
+        let before = keep.confidence;
+        keep.confidence = keep.confidence.max(other.confidence);
+        keep.updated_at = keep.updated_at.max(other.updated_at);
+        keep.usage_count = keep.usage_count.saturating_add(other.usage_count);
+        for tag in other.tags {
+            if !keep.tags.contains(&tag) {
+                keep.tags.push(tag);
+            }
+        }
+        for evidence in other.evidence {
+            add_evidence(keep, evidence);
+        }
+        let entry = AuditEntry::new(AuditAction::Merged, keep, "merge", now)
+            .before(&keep.text, before)
+            .with_detail(other.text);
+        self.audit.append(&[entry])?;
+        Ok(())
+    }
+
+    /// Получить концепты с учетом временного затухания (без фактического применения)
+    pub fn get_concepts_with_decay(&self, top_k: usize) -> Vec<(f32, &Concept)> {
+        let now = self.clock.now();
+        let mut concepts_with_decay: Vec<(f32, &Concept)> = self
+            .concepts
+            .values()
+            .map(|concept| {
+                let decay = self.retention.policy(&concept.category).decay;
+                (concept.effective_confidence_with(&decay, now), concept)
+            })
+            .filter(|(confidence, _)| *confidence > 0.01) // фильтруем очень низкую уверенность
+            .collect();
+
+        // Сортируем по эффективной уверенности
+        concepts_with_decay.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap());
+
+        concepts_with_decay.truncate(top_k);
+        concepts_with_decay
+    }
+
+    /// Статистика по затуханию концептов
+    pub fn get_decay_stats(&self) -> DecayStats {
+        let mut total_concepts = 0;
+        let mut decayed_concepts = 0;
+        let mut low_confidence_concepts = 0;
+        let mut category_stats: HashMap<ConceptCategory, CategoryDecayStats> = HashMap::new();
+        let now = self.clock.now();
+
+        for concept in self.concepts.values() {
+            total_concepts += 1;
+            let effective_confidence =
+                concept.effective_confidence_with(&self.retention.policy(&concept.category).decay, now);
+
+            if effective_confidence < concept.confidence * 0.9 {
+                decayed_concepts += 1;
+            }
+
+            if effective_confidence < 0.3 {
+                low_confidence_concepts += 1;
+            }
+
+            let cat_stats = category_stats
+                .entry(concept.category.clone())
+                .or_insert_with(CategoryDecayStats::default);
+            cat_stats.total += 1;
+            cat_stats.avg_confidence += effective_confidence;
+
+            if effective_confidence < 0.3 {
+                cat_stats.low_confidence += 1;
+            }
+        }
+
+        // Вычисляем средние значения
+        for cat_stats in category_stats.values_mut() {
+            if cat_stats.total > 0 {
+                cat_stats.avg_confidence /= cat_stats.total as f32;
+            }
+        }
+
+        DecayStats {
+            total_concepts,
+            decayed_concepts,
+            low_confidence_concepts,
+            category_stats,
+        }
+    }
+
+    // ============ Knowledge Graph Methods ============
+
+    /// Добавить связь (triple) между концептами
+    pub fn add_relation(
+        &mut self,
+        subject_id: &uuid::Uuid,
+        predicate: &str,
+        object_id: &uuid::Uuid,
+        confidence: Option<f32>,
+    ) -> Result<uuid::Uuid> {
+        // Проверяем существование концептов
+        if !self.concepts.contains_key(subject_id) {
+            anyhow::bail!("Subject concept not found: {}", subject_id);
+        }
+        if !self.concepts.contains_key(object_id) {
+            anyhow::bail!("Object concept not found: {}", object_id);
+        }
+
+        let mut triple = Triple::new(*subject_id, predicate.to_string(), *object_id).at(self.clock.now());
+        if let Some(conf) = confidence {
+            triple = triple.with_confidence(conf);
+        }
+
+        let triple_id = self.knowledge_graph.add_triple(triple);
+
+        // Обновляем related_concepts в концептах
+        if let Some(subject_concept) = self.concepts.get_mut(subject_id) {
+            if !subject_concept.related_concepts.contains(object_id) {
+                subject_concept.related_concepts.push(*object_id);
+            }
+        }
+        if let Some(object_concept) = self.concepts.get_mut(object_id) {
+            if !object_concept.related_concepts.contains(subject_id) {
+                object_concept.related_concepts.push(*subject_id);
+            }
+        }
+
+        Ok(triple_id)
+    }
+
+    /// Найти связи от концепта
+    pub fn find_outgoing_relations(&self, concept_id: &uuid::Uuid) -> Vec<&Triple> {
+        self.knowledge_graph.find_by_subject(concept_id)
+    }
+
+    /// Найти связи к концепту
+    pub fn find_incoming_relations(&self, concept_id: &uuid::Uuid) -> Vec<&Triple> {
+        self.knowledge_graph.find_by_object(concept_id)
+    }
+
+    /// Найти все связанные концепты
+    pub fn find_related_concepts(&self, concept_id: &uuid::Uuid) -> Vec<(uuid::Uuid, &str, f32)> {
+        self.knowledge_graph.find_related_concepts(concept_id, self.clock.now())
+    }
+
+    /// Окрестность концепта или путь между концептами по графу знаний
+    pub fn query_graph(&self, query: &GraphQuery) -> Subgraph {
+        query.run(&self.knowledge_graph, self.clock.now())
+    }
+
+    /// Отвечает на реляционный вопрос обходом графа: user -predicate-> X [X is_a class]
+    pub fn answer_relational(&self, query: &RelationalQuery) -> Option<GraphAnswer> {
+        let user_ids: Vec<uuid::Uuid> = self
+            .concepts
+            .values()
+            .filter(|c| USER_ALIASES.contains(&c.text.trim().to_lowercase().as_str()))
+            .map(|c| c.id)
+            .collect();
+
+        let mut items: Vec<(String, f32)> = Vec::new();
+        let now = self.clock.now();
+        for user_id in &user_ids {
+            for triple in self.knowledge_graph.find_by_subject(user_id) {
+                if triple.predicate != query.predicate {
+                    continue;
+                }
+                let Some(object) = self.concepts.get(&triple.object) else {
+                    continue;
+                };
+                if let Some(class) = &query.class {
+                    if !self.is_instance_of(object, class) {
+                        continue;
+                    }
+                }
+                let confidence = triple.get_effective_confidence(now);
+                match items.iter_mut().find(|(text, _)| *text == object.text) {
+                    Some(existing) => existing.1 = existing.1.max(confidence),
+                    None => items.push((object.text.clone(), confidence)),
+                }
+            }
+        }
+
+        if items.is_empty() {
+            return None;
+        }
+        items.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+        Some(GraphAnswer {
+            query: query.clone(),
+            items,
+        })
+    }
+
+    /// Концепт относится к классу: есть связь is_a к нему или совпадающий тег
+    fn is_instance_of(&self, concept: &Concept, class: &str) -> bool {
+        if concept.tags.iter().any(|t| same_stem(t, class)) {
+            return true;
+        }
+        self.knowledge_graph
+            .find_by_subject(&concept.id)
+            .into_iter()
+            .filter(|t| t.predicate == "is_a")
+            .filter_map(|t| self.concepts.get(&t.object))
+            .any(|c| same_stem(&c.text, class))
+    }
+
+    /// Автоматическое извлечение отношений из текста пользователя (`I like...` - о нём)
+    pub async fn extract_relations_from_text(
+        &mut self,
+        text: &str,
+        source_session: &str,
+    ) -> Result<usize> {
+        let third = self.extract_relations(text, THIRD_PERSON_RELATIONS, source_session).await?;
+        let first = self.extract_relations(text, FIRST_PERSON_RELATIONS, source_session).await?;
+        Ok(third + first)
+    }
+
+    /// Отношения по шаблонам `patterns`
+    async fn extract_relations(
+        &mut self,
+        text: &str,
+        patterns: &[(&str, &str)],
+        source_session: &str,
+    ) -> Result<usize> {
+        let mut relations_added = 0;
+
+        // Паттерны жадные - применяем их к отдельным предложениям
+        let sentences: Vec<&str> = text
+            .split(['.', '!', '?', ';', '\n'])
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .collect();
+
+        for &(pattern, predicate) in patterns {
+            if let Ok(re) = Regex::new(pattern) {
+                for caps in sentences.iter().flat_map(|s| re.captures_iter(s)) {
+                    if let (Some(subject_match), Some(object_match)) = (caps.get(1), caps.get(2)) {
+                        let subject_text = subject_match.as_str().trim().to_lowercase();
+                        let object_text = object_match.as_str().trim().to_lowercase();
+
+                        // Находим или создаем концепты
+                        let subject_id = self
+                            .find_or_create_concept(&subject_text, source_session)
+                            .await?;
+                        let object_id = self
+                            .find_or_create_concept(&object_text, source_session)
+                            .await?;
+
+                        // Добавляем связь
+                        if let Ok(_) =
+                            self.add_relation(&subject_id, predicate, &object_id, Some(0.7))
+                        {
+                            relations_added += 1;
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok(relations_added)
+    }
+
+    /// Найти или создать концепт
+    async fn find_or_create_concept(&mut self, text: &str, source: &str) -> Result<uuid::Uuid> {
+        // Ищем существующий концепт
+        if let Some(id) = self.find_by_content(text) {
+            return Ok(id);
+        }
+
+        // Создаем новый концепт
+        let mut concept = Concept::new(
+            text.to_string(),
+            ConceptCategory::General,
+            source.to_string(),
+        )
+        .at(self.clock.now());
+        concept.id = Concept::content_id(self.user.as_str(), text);
+        let concept_id = concept.id;
+        self.add_concept_internal(concept).await?;
+        Ok(concept_id)
+    }
+
+    /// Внутренний метод добавления концепта без сохранения
+    async fn add_concept_internal(&mut self, concept: Concept) -> Result<()> {
+        let id = concept.id;
+        self.index_concept(&id, &concept.category);
+        self.index_content(&concept);
+        let mut concept_with_embedding = concept;
+        concept_with_embedding.embedding = self
+            .embedder
+            .embed_async(&concept_with_embedding.text)
+            .await?;
+        let entry = AuditEntry::new(
+            AuditAction::Created,
+            &concept_with_embedding,
+            &concept_with_embedding.source,
+            self.clock.now(),
+        );
+        self.concepts.insert(id, concept_with_embedding);
+        self.audit.append(&[entry])?;
+        Ok(())
+    }
+
+    /// Добавляет предопределённые концепты (сиды архетипа), пропуская уже известные по тексту
+    pub async fn seed_concepts(&mut self, seeds: Vec<Concept>) -> Result<usize> {
+        let mut added = 0;
+        for mut seed in seeds {
+            let text = seed.text.trim().to_lowercase();
+            if text.is_empty() || self.concepts.values().any(|c| c.text.to_lowercase() == text) {
+                continue;
+            }
+            seed.id = Concept::content_id(self.user.as_str(), &seed.text);
+            self.add_concept_internal(seed).await?;
+            added += 1;
+        }
+        Ok(added)
+    }
+
+    /// Удаляет концепт вместе с его связями в графе
+    pub fn remove_concept(&mut self, id: &uuid::Uuid) -> Option<Concept> {
+        self.remove_with_reason(id, "manual", None).ok().flatten()
+    }
+
+    /// Удаляет концепт и записывает в журнал, кто его убрал (`source`) и чем заменил
+    fn remove_with_reason(&mut self, id: &uuid::Uuid, source: &str, detail: Option<&str>) -> Result<Option<Concept>> {
+        let Some(concept) = self.concepts.remove(id) else {
+            return Ok(None);
+        };
+        if let Some(ids) = self.category_index.get_mut(&concept.category) {
+            ids.retain(|i| i != id);
+        }
+        self.content_index.retain(|_, target| target != id);
+        self.knowledge_graph.remove_concept(id);
+        let mut entry = AuditEntry::new(AuditAction::Removed, &concept, source, self.clock.now());
+        if let Some(detail) = detail {
+            entry = entry.with_detail(detail);
+        }
+        self.audit.append(&[entry])?;
+        Ok(Some(concept))
+    }
+
+    /// Журнал изменений концепта по порядку: откуда у него нынешний текст и уверенность
+    pub fn history(&self, id: &uuid::Uuid) -> Result<Vec<AuditEntry>> {
+        self.audit.history(id)
+    }
+
+    /// Применяет исправление пользователя: подходящий концепт переписывается новой
+    /// версией с источником [`KnowledgeSource::UserCorrection`], старый текст остаётся
+    /// в `previous_texts`. Без `old` исправляется самый похожий концепт, а если похожих
+    /// нет - исправление сохраняется как новый концепт
+    pub async fn apply_correction(&mut self, request: &CorrectionRequest, source: &str) -> Result<Correction> {
+        let text = request.new.trim().to_string();
+        anyhow::ensure!(!text.is_empty(), "The corrected text is empty");
+        let embedding = self.embedder.embed_async(&text).await?;
+
+        let target = match &request.old {
+            Some(old) => Some(self.find_correction_target(old)?),
+            None => self
+                .concepts
+                .values()
+                .map(|c| (cosine_similarity(&embedding, &c.embedding), c.id))
+                .filter(|(sim, _)| *sim >= CORRECTION_MATCH_THRESHOLD)
+                .max_by(|a, b| a.0.total_cmp(&b.0))
+                .map(|(_, id)| id),
+        };
+
+        let Some(target) = target else {
+            let lower = text.to_lowercase();
+            let category = if PREFERENCE_WORDS.iter().any(|w| lower.contains(w)) {
+                ConceptCategory::Preferences
+            } else {
+                ConceptCategory::Facts
+            };
+            let mut concept = Concept::new(text.clone(), category.clone(), source.to_string())
+                .at(self.clock.now())
+                .with_confidence(CORRECTION_CONFIDENCE)
+                .with_knowledge_source(KnowledgeSource::UserCorrection)
+                .with_metadata("corrected_at".to_string(), self.clock.now().to_rfc3339());
+            concept.id = Concept::content_id(self.user.as_str(), &text);
+            concept.embedding = embedding;
+            self.index_concept(&concept.id, &category);
+            self.index_content(&concept);
+            self.concepts.insert(concept.id, concept.clone());
+            let entry = AuditEntry::new(AuditAction::Corrected, &concept, source, self.clock.now());
+            self.audit.append(&[entry])?;
+            return Ok(Correction { concept, replaced: None });
+        };
+
+        let replaced = self.concepts.get(&target).map(|c| c.text.clone());
+        let content_id = Concept::content_id(self.user.as_str(), &text);
+        // Новый текст уже известен как другой концепт: устаревший убираем, известный подтверждаем
+        let id = match self.find_by_content(&text) {
+            Some(existing) if existing != target => {
+                self.remove_with_reason(&target, source, Some(&format!("corrected to {}", existing)))?;
+                existing
+            }
+            _ => {
+                let concept = self.concepts.get_mut(&target).expect("correction target exists");
+                if normalize_concept_text(&concept.text) != normalize_concept_text(&text) {
+                    concept.revise(text, embedding, self.clock.now());
+                }
+                self.content_index.insert(content_id, target);
+                target
+            }
+        };
+
+        let concept = self.concepts.get_mut(&id).expect("corrected concept exists");
+        let before = (concept.confidence, replaced.clone().unwrap_or_else(|| concept.text.clone()));
+        concept.knowledge_source = KnowledgeSource::UserCorrection;
+        concept.confidence = concept.confidence.max(CORRECTION_CONFIDENCE);
+        concept.updated_at = self.clock.now();
+        concept
+            .metadata
+            .insert("corrected_at".to_string(), concept.updated_at.to_rfc3339());
+        let concept = concept.clone();
+        let entry = AuditEntry::new(AuditAction::Corrected, &concept, source, concept.updated_at).before(&before.1, before.0);
+        self.audit.append(&[entry])?;
+        Ok(Correction { concept, replaced })
+    }
+
+    /// Концепт, который имеет в виду `/correct <old> -> ...`: ID или его префикс,
+    /// точный текст (в т.ч. прежний) или единственный концепт, содержащий `old`
+    fn find_correction_target(&self, old: &str) -> Result<uuid::Uuid> {
+        if let Ok(id) = self.resolve_id(old) {
+            return Ok(id);
+        }
+        if let Some(id) = self.find_by_content(old) {
+            return Ok(id);
+        }
+        let needle = old.to_lowercase();
+        let matches: Vec<&Concept> = self
+            .concepts
+            .values()
+            .filter(|c| c.text.to_lowercase().contains(&needle))
+            .collect();
+        match matches.len() {
+            0 => anyhow::bail!("No concept matches '{}'", old),
+            1 => Ok(matches[0].id),
+            n => anyhow::bail!("'{}' matches {} concepts, use the concept id from /semantic list", old, n),
+        }
+    }
+
+    /// Противоречащие пары среди концептов, найденных по запросу для KNOWLEDGE;
+    /// пары, которые пользователь оставил как есть, не возвращаются
+    pub async fn retrieved_conflicts(&self, query: &str, top_k: usize, tags: &TagFilter) -> Vec<ConceptConflict> {
+        let found: Vec<&Concept> = self
+            .search_with_tags(query, top_k, None, tags)
+            .await
+            .into_iter()
+            .map(|(_, concept)| concept)
+            .collect();
+        find_conflicts(&found)
+            .into_iter()
+            .filter(|conflict| !self.dismissed_conflicts.contains(&conflict.key()))
+            .collect()
+    }
+
+    /// Ответ пользователя на конфликт: `keep` остаётся, второй концепт снимается
+    /// исправлением. Без `keep` (верны оба) пара больше не предлагается
+    pub async fn resolve_conflict(
+        &mut self,
+        conflict: &ConceptConflict,
+        keep: Option<&Concept>,
+        source: &str,
+    ) -> Result<Option<Correction>> {
+        self.dismissed_conflicts.insert(conflict.key());
+        let Some(keep) = keep else {
+            return Ok(None);
+        };
+        let outdated = if keep.id == conflict.first.id { &conflict.second } else { &conflict.first };
+        let request = CorrectionRequest {
+            old: Some(outdated.id.to_string()),
+            new: keep.text.clone(),
+        };
+        self.apply_correction(&request, source).await.map(Some)
+    }
+
+    /// Приводит концепты с источником `source` к списку фактов: новые добавляются как
+    /// предопределённые, пропавшие из списка удаляются. Такой же текст, уже известный из
+    /// диалога, не дублируется - ему только поднимается уверенность
+    pub async fn sync_facts(&mut self, source: &str, facts: Vec<FactEntry>) -> Result<FactsSync> {
+        let mut sync = FactsSync::default();
+        let wanted: HashSet<uuid::Uuid> = facts
+            .iter()
+            .map(|f| Concept::content_id(self.user.as_str(), &f.text))
+            .collect();
+
+        let stale: Vec<uuid::Uuid> = self
+            .concepts
+            .values()
+            .filter(|c| c.source == source && !wanted.contains(&Concept::content_id(self.user.as_str(), &c.text)))
+            .map(|c| c.id)
+            .collect();
+        for id in &stale {
+            self.remove_with_reason(id, source, Some("no longer in the facts file"))?;
+        }
+        sync.removed = stale.len();
+
+        for fact in facts {
+            if let Some(id) = self.find_by_content(&fact.text) {
+                if let Some(existing) = self.concepts.get_mut(&id).filter(|c| c.confidence < FACTS_CONFIDENCE) {
+                    let before = existing.confidence;
+                    existing.confidence = FACTS_CONFIDENCE;
+                    let entry = AuditEntry::new(AuditAction::Reinforced, existing, source, self.clock.now())
+                        .before(&existing.text, before);
+                    self.audit.append(&[entry])?;
+                }
+                sync.unchanged += 1;
+                continue;
+            }
+            let concept = Concept::new(fact.text.clone(), fact.category, source.to_string())
+                .at(self.clock.now())
+                .with_confidence(FACTS_CONFIDENCE)
+                .with_knowledge_source(KnowledgeSource::Predefined);
+            let concept = Concept {
+                id: Concept::content_id(self.user.as_str(), &fact.text),
+                tags: suggest_tags(&fact.text),
+                ..concept
+            };
+            self.add_concept_internal(concept).await?;
+            sync.added += 1;
+        }
+        Ok(sync)
+    }
+
+    /// Кластеры концептов по эмбеддингам (`k` по умолчанию зависит от их числа)
+    pub fn clusters(&self, k: Option<usize>) -> Vec<ConceptCluster> {
+        let concepts: Vec<&Concept> = self.concepts.values().collect();
+        cluster_concepts(&concepts, k)
+    }
+
+    /// Получить статистику графа
+    pub fn get_graph_stats(&self) -> GraphStats {
+        self.knowledge_graph.get_stats()
+    }
+
+    /// Сохранить все данные (концепты и граф)
+    pub async fn save(&self) -> Result<()> {
+        // Save concepts
+        let concepts: Vec<&Concept> = self.concepts.values().collect();
+        let owned: Vec<Concept> = concepts.into_iter().cloned().collect();
+        self.persistence.save(&owned).await?;
+        // Save knowledge graph
+        self.save_graph().await?;
+        Ok(())
+    }
+
+    /// Сохранить граф
+    pub async fn save_graph(&self) -> Result<()> {
+        if self.persistence.is_read_only() {
+            return Ok(());
+        }
+        // Сохраняем граф в отдельный файл рядом с концептами
+        let graph_path = self.graph_path();
+        crate::utils::fs::create_dir_all(graph_path.parent().unwrap()).await?;
+        let json = serde_json::to_string_pretty(&self.knowledge_graph)?;
+        crate::utils::fs::write(&graph_path, self.persistence.seal(json)?).await?;
+        Ok(())
+    }
+
+    fn graph_path(&self) -> std::path::PathBuf {
+        self.persistence.storage_path().with_file_name(KNOWLEDGE_GRAPH_FILE)
+    }
+
+    /// Загрузить граф
+    pub async fn load_graph(&mut self) -> Result<()> {
+        let graph_path = self.graph_path();
+        if graph_path.exists() {
+            let json = self.persistence.unseal(crate::utils::fs::read(&graph_path).await?)?;
+            self.knowledge_graph = serde_json::from_str(&json)?;
+        }
+        Ok(())
+    }
+}
+
+// ============ Sync facade (CLI) ============
+
+impl SemanticMemoryManager {
+    /// Синхронная версия [`SemanticMemoryManager::add_concept`]
+    pub fn add_concept_blocking(
+        &mut self,
+        text: String,
+        category: ConceptCategory,
+        source: String,
+        confidence: Option<f32>,
+    ) -> Result<Concept> {
+        crate::utils::block_on(self.add_concept(text, category, source, confidence))
+    }
+
+    /// Синхронная версия [`SemanticMemoryManager::search_by_text`]
+    pub fn search_by_text_blocking(&self, query: &str, top_k: usize) -> Vec<(f32, &Concept)> {
+        crate::utils::block_on(self.search_by_text(query, top_k))
+    }
+
+    /// Синхронная версия [`SemanticMemoryManager::search_with_tags`]
+    pub fn search_with_tags_blocking(
+        &self,
+        query: &str,
+        top_k: usize,
+        category: Option<ConceptCategory>,
+        tags: &TagFilter,
+    ) -> Vec<(f32, &Concept)> {
+        crate::utils::block_on(self.search_with_tags(query, top_k, category, tags))
+    }
+
+    /// Синхронная версия [`SemanticMemoryManager::search_by_category`]
+    pub fn search_by_category_blocking(
+        &self,
+        query: &str,
+        category: ConceptCategory,
+        top_k: usize,
+    ) -> Vec<(f32, &Concept)> {
+        crate::utils::block_on(self.search_by_category(query, category, top_k))
+    }
+
+    /// Синхронная версия [`SemanticMemoryManager::extract_from_dialogue`]
+    pub fn extract_from_dialogue_blocking(
+        &mut self,
+        user_query: &str,
+        assistant_response: &str,
+        origin: &TurnRef,
+    ) -> Result<usize> {
+        crate::utils::block_on(self.extract_from_dialogue(
+            user_query,
+            assistant_response,
+            origin,
+        ))
+    }
+
+    /// Синхронная версия [`SemanticMemoryManager::resolve_sensitive`]
+    pub fn resolve_sensitive_blocking(
+        &mut self,
+        pending: PendingConcept,
+        store: bool,
+    ) -> Result<Option<Concept>> {
+        crate::utils::block_on(self.resolve_sensitive(pending, store))
+    }
+
+    /// Синхронная версия [`SemanticMemoryManager::extract_relations_from_text`]
+    pub fn extract_relations_from_text_blocking(
+        &mut self,
+        text: &str,
+        source_session: &str,
+    ) -> Result<usize> {
+        crate::utils::block_on(self.extract_relations_from_text(text, source_session))
+    }
+
+    /// Синхронная версия [`SemanticMemoryManager::seed_concepts`]
+    pub fn seed_concepts_blocking(&mut self, seeds: Vec<Concept>) -> Result<usize> {
+        crate::utils::block_on(self.seed_concepts(seeds))
+    }
+
+    /// Синхронная версия [`SemanticMemoryManager::apply_correction`]
+    pub fn apply_correction_blocking(&mut self, request: &CorrectionRequest, source: &str) -> Result<Correction> {
+        crate::utils::block_on(self.apply_correction(request, source))
+    }
+
+    /// Синхронная версия [`SemanticMemoryManager::retrieved_conflicts`]
+    pub fn retrieved_conflicts_blocking(&self, query: &str, top_k: usize, tags: &TagFilter) -> Vec<ConceptConflict> {
+        crate::utils::block_on(self.retrieved_conflicts(query, top_k, tags))
+    }
+
+    /// Синхронная версия [`SemanticMemoryManager::resolve_conflict`]
+    pub fn resolve_conflict_blocking(
+        &mut self,
+        conflict: &ConceptConflict,
+        keep: Option<&Concept>,
+        source: &str,
+    ) -> Result<Option<Correction>> {
+        crate::utils::block_on(self.resolve_conflict(conflict, keep, source))
+    }
+
+    /// Синхронная версия [`SemanticMemoryManager::sync_facts`]
+    pub fn sync_facts_blocking(&mut self, source: &str, facts: Vec<FactEntry>) -> Result<FactsSync> {
+        crate::utils::block_on(self.sync_facts(source, facts))
+    }
+
+    /// Синхронная версия [`SemanticMemoryManager::save`]
+    pub fn save_blocking(&self) -> Result<()> {
+        crate::utils::block_on(self.save())
+    }
+
+    /// Синхронная версия [`SemanticMemoryManager::save_graph`]
+    pub fn save_graph_blocking(&self) -> Result<()> {
+        crate::utils::block_on(self.save_graph())
+    }
+
+    /// Синхронная версия [`SemanticMemoryManager::load_graph`]
+    pub fn load_graph_blocking(&mut self) -> Result<()> {
+        crate::utils::block_on(self.load_graph())
+    }
+}
+
+fn truncate_text(text: &str, max_chars: usize) -> String {
+    let char_count = text.chars().count();
+    if char_count <= max_chars {
+        return text.to_string();
+    }
+    let byte_pos = text
+        .char_indices()
+        .nth(max_chars)
+        .map(|(p, _)| p)
+        .unwrap_or(text.len());
+    format!("{}...", &text[..byte_pos])
+}
+
+fn text_similarity(a: &str, b: &str) -> f32 {
+    let a_words: HashSet<&str> = a.split_whitespace().collect();
+    let b_words: HashSet<&str> = b.split_whitespace().collect();
+
+    if a_words.is_empty() && b_words.is_empty() {
+        return 1.0;
+    }
+    if a_words.is_empty() || b_words.is_empty() {
+        return 0.0;
+    }
+
+    let intersection: HashSet<_> = a_words.intersection(&b_words).collect();
+    let union: HashSet<_> = a_words.union(&b_words).collect();
+
+    if union.is_empty() {
+        return 0.0;
+    }
+
+    intersection.len() as f32 / union.len() as f32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::MockEmbedder;
+    use crate::totems::semantic::concept::KnowledgeSource;
+
+    #[test]
+    fn test_concept_creation() {
+        let concept = Concept::new(
+            "User likes coffee".to_string(),
+            ConceptCategory::Preferences,
+            "test".to_string(),
+        );
+        assert!(concept.is_valid());
+        assert_eq!(concept.confidence, 0.5);
+    }
+
+    #[test]
+    fn test_concept_with_confidence() {
+        let concept = Concept::new(
+            "Test fact".to_string(),
+            ConceptCategory::Facts,
+            "test".to_string(),
+        )
+        .with_confidence(0.9);
+        assert_eq!(concept.confidence, 0.9);
+    }
+
+    #[test]
+    fn test_concept_tags() {
+        let mut concept = Concept::new(
+            "User is allergic to nuts".to_string(),
+            ConceptCategory::Facts,
+            "test".to_string(),
+        )
+        .with_tags(&["Health".to_string(), "#food".to_string()]);
+        assert_eq!(concept.tags, vec!["health", "food"]);
+        assert!(!concept.add_tag("HEALTH", chrono::Utc::now()));
+        assert!(concept.remove_tag("food"));
+        assert!(concept.has_tag("health"));
+
+        assert!(!TagFilter::excluding(&["health".to_string()]).matches(&concept));
+        assert!(TagFilter::including(&["health".to_string()]).matches(&concept));
+        assert!(TagFilter::default().matches(&concept));
+    }
+
+    #[test]
+    fn test_suggest_tags() {
+        assert_eq!(suggest_tags("My boss at work is strict"), vec!["work"]);
+        assert!(suggest_tags("я люблю пиццу").contains(&"food".to_string()));
+        assert!(suggest_tags("The sky is blue").is_empty());
+    }
+
+    #[test]
+    fn test_seed_concepts_skips_existing() {
+        let dir = std::env::temp_dir().join(format!("ziggurat-seed-test-{}", std::process::id()));
+        let persistence = SemanticPersistenceManager::new(Some(&dir)).unwrap();
+        let existing = Concept::new(
+            "User prefers Rust examples".to_string(),
+            ConceptCategory::Preferences,
+            "session".to_string(),
+        );
+        let mut manager = crate::utils::block_on(SemanticMemoryManager::with_concepts(
+            Arc::new(MockEmbedder::default()),
+            persistence,
+            vec![existing],
+        ))
+        .unwrap();
+
+        let seed = |text: &str| {
+            Concept::new(text.to_string(), ConceptCategory::Rules, "archetype:programmer".to_string())
+                .with_knowledge_source(KnowledgeSource::Predefined)
+        };
+        let added = manager
+            .seed_concepts_blocking(vec![seed("user prefers rust examples"), seed("Show code in fenced blocks")])
+            .unwrap();
+
+        assert_eq!(added, 1);
+        assert_eq!(manager.count(), 2);
+        let seeded = manager.get_concepts_by_category(&ConceptCategory::Rules);
+        assert_eq!(seeded[0].knowledge_source, KnowledgeSource::Predefined);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_sync_facts_follows_file() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("ziggurat-facts-test-{}", std::process::id()));
+        let persistence = SemanticPersistenceManager::new(Some(&dir))?;
+        let dialogue = Concept::new("I work night shifts".to_string(), ConceptCategory::Facts, "s1".to_string())
+            .with_confidence(0.6);
+        let dialogue = Concept {
+            id: Concept::content_id(DEFAULT_USER_ID, &dialogue.text),
+            ..dialogue
+        };
+        let mut manager = crate::utils::block_on(SemanticMemoryManager::with_concepts(
+            Arc::new(MockEmbedder::default()),
+            persistence,
+            vec![dialogue.clone()],
+        ))?;
+
+        let facts = |content: &str| crate::totems::semantic::facts::parse_facts(content);
+        let sync = manager.sync_facts_blocking("facts:facts.md", facts("- My dog is called Rex\n- I work night shifts"))?;
+        assert_eq!((sync.added, sync.removed, sync.unchanged), (1, 0, 1));
+        assert_eq!(manager.get_concept(&dialogue.id).unwrap().confidence, FACTS_CONFIDENCE);
+        let rex = manager.find_by_content("my dog is called rex").unwrap();
+        assert_eq!(manager.get_concept(&rex).unwrap().knowledge_source, KnowledgeSource::Predefined);
+
+        // the line is gone: the fact is forgotten, the dialogue concept stays
+        let sync = manager.sync_facts_blocking("facts:facts.md", facts("- I work night shifts"))?;
+        assert_eq!((sync.added, sync.removed), (0, 1));
+        assert!(manager.find_by_content("My dog is called Rex").is_none());
+        assert_eq!(manager.count(), 1);
+        let _ = std::fs::remove_dir_all(&dir);
+        Ok(())
+    }
+
+    #[test]
+    fn test_answer_relational_by_graph() {
+        let dir = std::env::temp_dir().join(format!("ziggurat-graph-test-{}", std::process::id()));
+        let persistence = SemanticPersistenceManager::new(Some(&dir)).unwrap();
+        let mut manager = crate::utils::block_on(SemanticMemoryManager::with_concepts(
+            Arc::new(MockEmbedder::default()),
+            persistence,
+            Vec::new(),
+        ))
+        .unwrap();
+
+        let added = manager
+            .extract_relations_from_text_blocking("I like pizza. pizza is a food", "test")
+            .unwrap();
+        assert!(added >= 2);
+
+        let query = RelationalQuery::parse("what foods do I like?").unwrap();
+        let answer = manager.answer_relational(&query).unwrap();
+        assert_eq!(answer.items.len(), 1);
+        assert!(answer.items[0].0.starts_with("pizza"));
+
+        let query = RelationalQuery::parse("what movies do I like?").unwrap();
+        assert!(manager.answer_relational(&query).is_none());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_assistant_first_person_is_not_a_user_fact() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("ziggurat-first-person-test-{}", uuid::Uuid::new_v4()));
+        let persistence = SemanticPersistenceManager::new(Some(&dir))?;
+        let mut manager = crate::utils::block_on(SemanticMemoryManager::with_concepts(
+            Arc::new(MockEmbedder::default()),
+            persistence,
+            Vec::new(),
+        ))?;
+        let origin = TurnRef::new("s1", 1);
+        let likes = RelationalQuery::parse("what do I like?").unwrap();
+
+        manager.extract_from_dialogue_blocking("What should I cook?", "I love risotto. Я люблю ризотто.", &origin)?;
+        assert!(manager.answer_relational(&likes).is_none());
+
+        manager.extract_from_dialogue_blocking("I love pizza", "Pizza is great. Tom likes pasta.", &origin)?;
+        let answer = manager.answer_relational(&likes).unwrap();
+        assert_eq!(answer.items.len(), 1);
+        assert!(answer.items[0].0.starts_with("pizza"));
+        // third-person statements still come from either side
+        assert!(manager.find_by_content("tom").is_some());
+
+        std::fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_reextraction_is_idempotent() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("ziggurat-ids-test-{}", std::process::id()));
+        let persistence = SemanticPersistenceManager::new(Some(&dir))?;
+        let mut manager = crate::utils::block_on(SemanticMemoryManager::with_concepts(
+            Arc::new(MockEmbedder::default()),
+            persistence,
+            Vec::new(),
+        ))?;
+
+        let add = |m: &mut SemanticMemoryManager, text: &str, conf: f32| {
+            m.add_concept_blocking(text.to_string(), ConceptCategory::Preferences, "s1".to_string(), Some(conf))
+        };
+
+        let first = add(&mut manager, "User likes coffee", 0.6)?;
+        assert_eq!(first.id, Concept::content_id(DEFAULT_USER_ID, "user likes coffee."));
+        assert_eq!(add(&mut manager, " user likes  COFFEE ", 0.6)?.id, first.id);
+        assert_eq!(manager.count(), 1);
+
+        // a contradicting fact with more confidence becomes the next version, same ID
+        let revised = add(&mut manager, "User does not like coffee", 0.9)?;
+        assert_eq!((revised.id, revised.version), (first.id, 2));
+        assert_eq!(revised.previous_texts, vec!["User likes coffee"]);
+
+        // re-extracting the old dialogue finds the concept by its previous wording
+        let again = add(&mut manager, "User likes coffee", 0.95)?;
+        assert_eq!((again.id, again.text.as_str()), (first.id, "User does not like coffee"));
+        assert_eq!(again.confidence, 0.9);
+        assert_eq!(manager.count(), 1);
+
+        // identity is per user
+        assert_ne!(Concept::content_id("alice", "User likes coffee"), first.id);
+        let _ = std::fs::remove_dir_all(&dir);
+        Ok(())
+    }
+
+    #[test]
+    fn test_extraction_keeps_utterance() -> Result<()> {
+        use crate::totems::language::Language;
+
+        let dir = std::env::temp_dir().join(format!("ziggurat-utterance-test-{}", std::process::id()));
+        let persistence = SemanticPersistenceManager::new(Some(&dir))?;
+        let mut manager = crate::utils::block_on(SemanticMemoryManager::with_concepts(
+            Arc::new(MockEmbedder::default()),
+            persistence,
+            Vec::new(),
+        ))?;
+
+        let mut extract = |utterance: &str| {
+            let results = vec![("User lives in Kazan".to_string(), "facts".to_string(), 0.8, Vec::new())];
+            crate::utils::block_on(manager.parse_extraction(results, &TurnRef::session("s1"), utterance, "Понятно"))
+        };
+        let concept = extract("Я живу в Казани")?.remove(0);
+        assert_eq!(concept.utterance.as_deref(), Some("Я живу в Казани"));
+        assert_eq!((concept.language, concept.text_language()), (Some(Language::Ru), Some(Language::En)));
+        // re-extraction does not overwrite where the fact came from
+        assert_eq!(extract("I live in Kazan")?[0].utterance.as_deref(), Some("Я живу в Казани"));
+
+        manager.save_blocking()?;
+        let loaded = crate::utils::block_on(SemanticPersistenceManager::new(Some(&dir))?.load())?.unwrap();
+        let _ = std::fs::remove_dir_all(&dir);
+        assert_eq!(loaded[0].language, Some(Language::Ru));
+        assert_eq!(loaded[0].utterance.as_deref(), Some("Я живу в Казани"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_provenance_and_history() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("ziggurat-provenance-test-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let persistence = SemanticPersistenceManager::new(Some(&dir))?;
+        let mut manager = crate::utils::block_on(SemanticMemoryManager::with_concepts(
+            Arc::new(MockEmbedder::default()),
+            persistence,
+            Vec::new(),
+        ))?;
+
+        let mut extract = |text: &str, confidence: f32, utterance: &str, turn: usize| {
+            let results = vec![(text.to_string(), "preferences".to_string(), confidence, Vec::new())];
+            crate::utils::block_on(manager.parse_extraction(results, &TurnRef::new("s1", turn), utterance, "Ок"))
+        };
+        extract("User likes coffee", 0.7, "Я люблю кофе", 1)?;
+        extract("User likes coffee", 0.9, "Обожаю кофе", 2)?;
+        // a weaker contradiction is logged, but is not evidence for the concept
+        extract("User does not like coffee", 0.4, "Кофе не люблю", 3)?;
+
+        let id = manager.find_by_content("User likes coffee").unwrap();
+        let evidence: Vec<(Option<usize>, String)> = manager.get_concept(&id).unwrap()
+            .evidence
+            .iter()
+            .map(|e| (e.turn, e.raw_text.clone()))
+            .collect();
+        assert_eq!(evidence, [(Some(1), "Я люблю кофе".to_string()), (Some(2), "Обожаю кофе".to_string())]);
+
+        let request = CorrectionRequest { old: Some(id.to_string()), new: "User likes tea".to_string() };
+        manager.apply_correction_blocking(&request, "s2")?;
+        let history = manager.history(&id)?;
+        let actions: Vec<AuditAction> = history.iter().map(|e| e.action).collect();
+        use AuditAction::*;
+        assert_eq!(actions, [Created, Reinforced, Contradicted, Corrected]);
+        assert_eq!(history[1].previous_confidence, Some(0.7));
+        assert!(history[2].describe().contains("User does not like coffee"));
+        assert_eq!(history[3].previous_text.as_deref(), Some("User likes coffee"));
+
+        // the log and the evidence survive a restart
+        manager.save_blocking()?;
+        let reopened = SemanticMemoryManager::new(Arc::new(MockEmbedder::default()), SemanticPersistenceManager::new(Some(&dir))?)?;
+        assert_eq!(reopened.history(&id)?.len(), 4);
+        assert_eq!(reopened.get_concept(&id).unwrap().evidence.len(), 2);
+        let _ = std::fs::remove_dir_all(&dir);
+        Ok(())
+    }
+
+    #[test]
+    fn test_merge_similar() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("ziggurat-merge-test-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let concept = |text: &str, category: ConceptCategory, confidence: f32| {
+            Concept::new(text.to_string(), category, "s1".to_string()).with_confidence(confidence)
+        };
+        // MockEmbedder: the same words in any order or case are identical vectors
+        let mut manager = crate::utils::block_on(SemanticMemoryManager::with_concepts(
+            Arc::new(MockEmbedder::default()),
+            SemanticPersistenceManager::new(Some(&dir))?,
+            vec![
+                concept("User likes green tea", ConceptCategory::Preferences, 0.9),
+                concept("Green tea - user likes", ConceptCategory::Preferences, 0.6),
+                concept("User has a green cat", ConceptCategory::Facts, 0.8),
+                concept("User likes coffee", ConceptCategory::Preferences, 0.7),
+            ],
+        ))?;
+        let id = |m: &SemanticMemoryManager, text: &str| m.find_by_content(text).unwrap();
+        let (likes, loves, cat) =
+            (id(&manager, "User likes green tea"), id(&manager, "Green tea - user likes"), id(&manager, "User has a green cat"));
+        manager.add_relation(&loves, "drinks_with", &cat, None)?;
+
+        assert_eq!(manager.merge_similar(0.99999)?, 1);
+        assert_eq!(manager.count(), 3);
+        assert!(manager.get_concept(&loves).is_none());
+        // the wording and the relation now belong to the survivor
+        assert_eq!(id(&manager, "Green tea - user likes"), likes);
+        assert_eq!(manager.find_outgoing_relations(&likes).len(), 1);
+        let actions: Vec<AuditAction> = manager.history(&likes)?.iter().map(|e| e.action).collect();
+        assert_eq!(actions, [AuditAction::Merged]);
+        assert_eq!(manager.merge_similar(0.99999)?, 0);
+        let _ = std::fs::remove_dir_all(&dir);
+        Ok(())
+    }
+
+    #[test]
+    fn test_apply_correction() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("ziggurat-correct-test-{}", std::process::id()));
+        let persistence = SemanticPersistenceManager::new(Some(&dir))?;
+        let coffee = Concept::new("User likes coffee".to_string(), ConceptCategory::Preferences, "s1".to_string());
+        let city = Concept::new("User lives in Moscow".to_string(), ConceptCategory::Facts, "s1".to_string());
+        let (coffee_id, city_id) = (coffee.id, city.id);
+        let mut manager = crate::utils::block_on(SemanticMemoryManager::with_concepts(
+            Arc::new(MockEmbedder::default()),
+            persistence,
+            vec![coffee, city],
+        ))?;
+
+        let request = |old: Option<&str>, new: &str| CorrectionRequest {
+            old: old.map(str::to_string),
+            new: new.to_string(),
+        };
+        let corrected = manager.apply_correction_blocking(&request(Some("coffee"), "User prefers tea"), "s2")?;
+        assert_eq!((corrected.concept.id, corrected.concept.version), (coffee_id, 2));
+        assert_eq!(corrected.replaced.as_deref(), Some("User likes coffee"));
+        assert_eq!(corrected.concept.knowledge_source, KnowledgeSource::UserCorrection);
+        assert!(corrected.concept.confidence >= CORRECTION_CONFIDENCE);
+        assert_eq!(manager.find_by_content("user prefers tea"), Some(coffee_id));
+
+        // "user" is in both concepts
+        assert!(manager.apply_correction_blocking(&request(Some("user"), "x"), "s2").is_err());
+        assert!(manager.apply_correction_blocking(&request(Some("cats"), "x"), "s2").is_err());
+
+        // correcting to an already known fact keeps only that concept
+        manager.apply_correction_blocking(&request(Some("Moscow"), "user prefers tea"), "s2")?;
+        assert!(manager.get_concept(&city_id).is_none());
+        assert_eq!(manager.count(), 1);
+        let _ = std::fs::remove_dir_all(&dir);
+        Ok(())
+    }
+
+    #[test]
+    fn test_decay_follows_clock() -> Result<()> {
+        use crate::utils::clock::{Clock, MockClock};
+
+        let dir = std::env::temp_dir().join(format!("ziggurat-clock-test-{}", std::process::id()));
+        let persistence = SemanticPersistenceManager::new(Some(&dir))?;
+        let mut manager = crate::utils::block_on(SemanticMemoryManager::with_concepts(
+            Arc::new(MockEmbedder::default()),
+            persistence,
+            Vec::new(),
+        ))?;
+        let mock = Arc::new(MockClock::starting_now());
+        manager.set_clock(mock.clone());
+
+        let concept = manager.add_concept_blocking(
+            "User likes coffee".to_string(),
+            ConceptCategory::General,
+            "s1".to_string(),
+            Some(0.5),
+        )?;
+        assert_eq!(concept.created_at, mock.now());
+
+        mock.advance(chrono::Duration::days(40));
+        assert!(manager.get_concepts_with_decay(10)[0].0 < 0.5);
+        manager.apply_temporal_decay()?;
+        assert_eq!(manager.count(), 1);
+
+        mock.advance(chrono::Duration::days(800));
+        manager.apply_temporal_decay()?;
+        assert_eq!(manager.count(), 0);
+        let _ = std::fs::remove_dir_all(&dir);
+        Ok(())
+    }
+
+    #[test]
+    fn test_category_display() {
+        assert_eq!(ConceptCategory::Facts.to_string(), "facts");
+        assert_eq!(ConceptCategory::Preferences.to_string(), "preferences");
+    }
+}
@@ -0,0 +1,58 @@
+//! 📚 Семантическая память
+//!
+//! Модуль для хранения и управления концептами: факты, правила, предпочтения, навыки
+//!
+//! # Пример использования
+//!
+//! ```rust,ignore
+//! use zikkurat_core::totems::semantic::{SemanticMemoryManager, ConceptCategory};
+//!
+//! // Создание менеджера
+//! let mut manager = SemanticMemoryManager::open(embedder, persistence).await?;
+//!
+//! // Добавление концепта
+//! manager.add_concept(
+//!     "Пользователь предпочитает тёмную тему".to_string(),
+//!     ConceptCategory::Preferences,
+//!     "session-123".to_string(),
+//!     Some(0.9),
+//! ).await?;
+//!
+//! // Поиск
+//! let results = manager.search_by_text("тема", 5).await;
+//!
+//! // Из синхронного кода (CLI) - через фасад `*_blocking`
+//! let results = manager.search_by_text_blocking("тема", 5);
+//! ```
+
+pub mod clusters;
+pub mod concept;
+pub mod conflict;
+pub mod correction;
+pub mod diff;
+pub mod facts;
+pub mod graph_query;
+pub mod manager;
+pub mod persistence;
+pub mod perspective;
+pub mod provenance;
+pub mod reasoning;
+pub mod retention;
+pub mod sensitive;
+pub mod utterance;
+
+pub use clusters::ConceptCluster;
+pub use concept::{
+    CategoryDecayStats, Concept, ConceptCategory, DecayConfig, DecayStats, GraphStats,
+    KnowledgeGraph, KnowledgeSource, TagFilter, Triple,
+};
+pub use conflict::ConceptConflict;
+pub use diff::{SemanticDiff, SemanticSnapshot};
+pub use facts::{FactEntry, FactsFile, FactsSync};
+pub use graph_query::{GraphEdge, GraphQuery, Subgraph};
+pub use manager::{suggest_tags, ConceptExtractor, Correction, ExtractionResult, SemanticMemoryManager};
+pub use provenance::{AuditAction, AuditEntry, AuditLog, Evidence, TurnRef};
+pub use reasoning::{GraphAnswer, RelationalQuery};
+pub use retention::{RetentionConfig, RetentionOverrides, RetentionPolicy};
+pub use sensitive::{PendingConcept, SensitiveAction, SensitiveKind, SensitivePolicy};
+pub use utterance::{is_self_disclosure, UtteranceKind};
@@ -0,0 +1,331 @@
+//! 💾 Persistence Layer для семантической памяти
+//!
+//! Сохраняет и загружает концепты в/из JSON файла
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use uuid::Uuid;
+
+use super::concept::Concept;
+use super::concept::ConceptCategory;
+use super::concept::KnowledgeSource;
+use super::provenance::{AuditLog, Evidence};
+use super::sensitive::SENSITIVE_POLICY_FILE;
+use crate::totems::language::Language;
+use crate::utils::crypto::{self, MemoryKey};
+use crate::utils::lock::io_guard;
+
+const SEMANTIC_MEMORY_FILE: &str = "semantic_memory.json";
+/// Граф знаний лежит рядом с концептами
+pub const KNOWLEDGE_GRAPH_FILE: &str = "knowledge_graph.json";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SemanticStorage {
+    pub version: String,
+    pub created_at: DateTime<Utc>,
+    pub last_saved_at: DateTime<Utc>,
+    pub total_concepts: usize,
+    pub concepts: Vec<SerializedConcept>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SerializedConcept {
+    pub id: String,
+    pub text: String,
+    pub category: String,
+    pub confidence: f32,
+    pub source: String,
+    pub metadata: serde_json::Value,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub usage_count: u32,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub knowledge_source: KnowledgeSource,
+    #[serde(default = "default_version")]
+    pub version: u32,
+    #[serde(default)]
+    pub previous_texts: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub language: Option<Language>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub utterance: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub evidence: Vec<Evidence>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_used: Option<DateTime<Utc>>,
+}
+
+fn default_version() -> u32 {
+    1
+}
+
+pub struct SemanticPersistenceManager {
+    storage_path: PathBuf,
+    /// Каталог принадлежит другому процессу: читаем, но не пишем (см. [`crate::utils::lock`])
+    read_only: bool,
+    /// Ключ шифрования концептов и графа знаний; `None` - пишутся открытыми
+    key: Option<MemoryKey>,
+}
+
+impl SemanticPersistenceManager {
+    pub fn new(base_path: Option<&PathBuf>) -> Result<Self> {
+        let storage_path = base_path
+            .clone()
+            .unwrap_or(&PathBuf::from("memory_data"))
+            .join(SEMANTIC_MEMORY_FILE);
+
+        if let Some(parent) = storage_path.parent() {
+            if !parent.exists() {
+                fs::create_dir_all(parent)
+                    .with_context(|| format!("Failed to create directory: {:?}", parent))?;
+            }
+        }
+
+        Ok(Self {
+            storage_path,
+            read_only: false,
+            key: crypto::installed(),
+        })
+    }
+
+    /// Ключ шифрования вместо поставленного [`crypto::install`]
+    pub fn with_encryption(mut self, key: Option<MemoryKey>) -> Self {
+        self.key = key;
+        self
+    }
+
+    /// Содержимое файла хранилища для записи на диск (с ключом - зашифрованное)
+    pub fn seal(&self, content: impl Into<Vec<u8>>) -> Result<Vec<u8>> {
+        crypto::seal(self.key.as_ref(), content)
+    }
+
+    /// Текст прочитанного файла хранилища, расшифрованный при необходимости
+    pub fn unseal(&self, content: Vec<u8>) -> Result<String> {
+        crypto::unseal_string(self.key.as_ref(), content)
+    }
+
+    /// Только чтение: сохранения пропускаются, изменения живут до конца процесса
+    pub fn with_read_only(mut self, read_only: bool) -> Self {
+        self.read_only = read_only;
+        self
+    }
+
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    fn storage_dir(&self) -> &Path {
+        self.storage_path.parent().unwrap_or(Path::new("."))
+    }
+
+    /// Журнал изменений концептов этого хранилища
+    pub fn audit_log(&self) -> AuditLog {
+        AuditLog::new(self.storage_dir(), self.read_only).with_encryption(self.key.clone())
+    }
+
+    pub async fn save(&self, concepts: &[Concept]) -> Result<()> {
+        if self.read_only {
+            return Ok(());
+        }
+        let serialized_concepts: Vec<SerializedConcept> =
+            concepts.iter().map(|c| self.serialize_concept(c)).collect();
+
+        let storage = SemanticStorage {
+            version: "1.0".to_string(),
+            created_at: Utc::now(),
+            last_saved_at: Utc::now(),
+            total_concepts: concepts.len(),
+            concepts: serialized_concepts,
+        };
+
+        let content = serde_json::to_string_pretty(&storage)
+            .context("Failed to serialize semantic memory")?;
+
+        let _guard = io_guard(self.storage_dir(), true)?;
+        crate::utils::fs::write(&self.storage_path, self.seal(content)?)
+            .await
+            .with_context(|| {
+                format!("Failed to write semantic memory to {:?}", self.storage_path)
+            })?;
+
+        eprintln!(
+            "DEBUG: Saved {} semantic concepts to {:?}",
+            concepts.len(),
+            self.storage_path
+        );
+
+        Ok(())
+    }
+
+    pub async fn load(&self) -> Result<Option<Vec<Concept>>> {
+        if !self.storage_path.exists() {
+            eprintln!(
+                "DEBUG: No semantic memory file found at {:?}",
+                self.storage_path
+            );
+            return Ok(None);
+        }
+
+        let _guard = io_guard(self.storage_dir(), false)?;
+        let content = crate::utils::fs::read(&self.storage_path)
+            .await
+            .with_context(|| {
+                format!(
+                    "Failed to read semantic memory from {:?}",
+                    self.storage_path
+                )
+            })?;
+        let content = self.unseal(content).context("Failed to decrypt semantic memory")?;
+
+        let storage: SemanticStorage =
+            serde_json::from_str(&content).context("Failed to deserialize semantic memory")?;
+
+        eprintln!(
+            "DEBUG: Loaded {} semantic concepts from {:?}",
+            storage.total_concepts, self.storage_path
+        );
+
+        let concepts: Vec<Concept> = storage
+            .concepts
+            .into_iter()
+            .filter_map(|c| self.deserialize_concept(c).ok())
+            .collect();
+
+        Ok(Some(concepts))
+    }
+
+    /// Концепты в формате файла, без пересчёта в [`Concept`] (для экспорта)
+    pub fn load_serialized(&self) -> Result<Vec<SerializedConcept>> {
+        if !self.storage_path.exists() {
+            return Ok(Vec::new());
+        }
+        let _guard = io_guard(self.storage_dir(), false)?;
+        let content = fs::read(&self.storage_path)
+            .with_context(|| format!("Failed to read semantic memory from {:?}", self.storage_path))?;
+        let content = self.unseal(content).context("Failed to decrypt semantic memory")?;
+        let storage: SemanticStorage =
+            serde_json::from_str(&content).context("Failed to deserialize semantic memory")?;
+        Ok(storage.concepts)
+    }
+
+    /// Записывает концепты в формате файла, без пересчёта из [`Concept`] (для импорта)
+    pub fn save_serialized(&self, concepts: Vec<SerializedConcept>) -> Result<()> {
+        anyhow::ensure!(!self.read_only, "Semantic memory is opened read-only");
+        let storage = SemanticStorage {
+            version: "1.0".to_string(),
+            created_at: Utc::now(),
+            last_saved_at: Utc::now(),
+            total_concepts: concepts.len(),
+            concepts,
+        };
+        let content = serde_json::to_string_pretty(&storage).context("Failed to serialize semantic memory")?;
+        let _guard = io_guard(self.storage_dir(), true)?;
+        fs::write(&self.storage_path, self.seal(content)?)
+            .with_context(|| format!("Failed to write semantic memory to {:?}", self.storage_path))
+    }
+
+    /// Переписывает концепты, граф знаний, политику чувствительных фактов и журнал изменений
+    /// зашифрованными ключом (`encrypt`) или открытыми: миграция существующего хранилища. Возвращает число переписанных файлов
+    pub fn reseal(&self, encrypt: bool) -> Result<usize> {
+        anyhow::ensure!(!self.read_only, "Semantic memory is opened read-only");
+        let Some(key) = &self.key else {
+            anyhow::bail!("No memory key: set {} or store one in the OS keyring", crypto::KEY_ENV);
+        };
+        let _guard = io_guard(self.storage_dir(), true)?;
+        let mut rewritten = 0;
+        for path in [
+            self.storage_path.clone(),
+            self.storage_path.with_file_name(KNOWLEDGE_GRAPH_FILE),
+            self.storage_path.with_file_name(SENSITIVE_POLICY_FILE),
+        ] {
+            if crypto::reseal_file(key, &path, encrypt).with_context(|| format!("Failed to rewrite {:?}", path))? {
+                rewritten += 1;
+            }
+        }
+        let audit = self.audit_log();
+        if crypto::reseal_lines(key, audit.path(), encrypt)
+            .with_context(|| format!("Failed to rewrite {:?}", audit.path()))?
+        {
+            rewritten += 1;
+        }
+        Ok(rewritten)
+    }
+
+    pub fn storage_path(&self) -> &PathBuf {
+        &self.storage_path
+    }
+
+    fn serialize_concept(&self, concept: &Concept) -> SerializedConcept {
+        let category = concept.category.to_string();
+        let metadata = serde_json::to_value(&concept.metadata).unwrap_or(serde_json::Value::Null);
+
+        SerializedConcept {
+            id: concept.id.to_string(),
+            text: concept.text.clone(),
+            category,
+            confidence: concept.confidence,
+            source: concept.source.clone(),
+            metadata,
+            created_at: concept.created_at,
+            updated_at: concept.updated_at,
+            usage_count: concept.usage_count,
+            tags: concept.tags.clone(),
+            knowledge_source: concept.knowledge_source,
+            version: concept.version,
+            previous_texts: concept.previous_texts.clone(),
+            language: concept.language,
+            utterance: concept.utterance.clone(),
+            evidence: concept.evidence.clone(),
+            last_used: concept.last_used,
+        }
+    }
+
+    fn deserialize_concept(&self, serialized: SerializedConcept) -> Result<Concept> {
+        let id: Uuid = Uuid::parse_str(&serialized.id)
+            .with_context(|| format!("Invalid concept UUID: {}", serialized.id))?;
+
+        let category: ConceptCategory = serialized
+            .category
+            .parse()
+            .unwrap_or(ConceptCategory::General);
+
+        let metadata: HashMap<String, String> = match serialized.metadata {
+            serde_json::Value::Object(map) => map
+                .into_iter()
+                .filter_map(|(k, v)| match v {
+                    serde_json::Value::String(s) => Some((k, s)),
+                    _ => None,
+                })
+                .collect(),
+            _ => HashMap::new(),
+        };
+
+        Ok(Concept {
+            id,
+            text: serialized.text,
+            category,
+            confidence: serialized.confidence,
+            source: serialized.source,
+            embedding: Vec::new(),
+            metadata,
+            created_at: serialized.created_at,
+            updated_at: serialized.updated_at,
+            usage_count: serialized.usage_count,
+            related_concepts: Vec::new(),
+            tags: serialized.tags,
+            knowledge_source: serialized.knowledge_source,
+            version: serialized.version,
+            previous_texts: serialized.previous_texts,
+            language: serialized.language,
+            utterance: serialized.utterance,
+            evidence: serialized.evidence,
+            last_used: serialized.last_used,
+        })
+    }
+}
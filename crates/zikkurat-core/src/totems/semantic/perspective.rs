@@ -0,0 +1,342 @@
+//! 🪞 Факты о пользователе от третьего лица
+//!
+//! Концепты часто сохраняются словами пользователя («I love pizza», «Я люблю
+//! пиццу»). В промпте такие строки модель принимает за собственные предпочтения,
+//! поэтому перед вставкой в USER PROFILE они переписываются от третьего лица:
+//! «The user loves pizza», «Пользователь любит пиццу». Переписывание словарное и
+//! консервативное: если русский глагол не удаётся поставить в третье лицо, факт
+//! остаётся дословным, но с явной пометкой, что это слова пользователя.
+//!
+//! Уверенность концепта тоже видна модели: факт ниже порога [`ConfidencePhrasing`]
+//! идёт с пометкой «Probably» или «Maybe (unconfirmed)», иначе догадка
+//! экстрактора звучит в ответе так же твёрдо, как прямое заявление пользователя.
+
+/// Пояснение под секцией, где есть неуверенные факты
+pub const HEDGE_NOTE: &str = "Facts marked \"Probably\" or \"Maybe (unconfirmed)\" are guesses: \
+mention them tentatively or ask the user, never state them as certain.";
+
+/// Насколько твёрдо факт подаётся в промпте
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Certainty {
+    /// Пользователь сказал это прямо
+    Definite,
+    Probable,
+    Tentative,
+}
+
+impl Certainty {
+    fn prefix(&self) -> &'static str {
+        match self {
+            Certainty::Definite => "",
+            Certainty::Probable => "Probably: ",
+            Certainty::Tentative => "Maybe (unconfirmed): ",
+        }
+    }
+}
+
+/// Пороги уверенности концепта для формулировки в промпте
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ConfidencePhrasing {
+    /// Не ниже - факт подаётся как есть
+    pub certain: f32,
+    /// Ниже - факт помечается как неподтверждённый
+    pub tentative: f32,
+}
+
+impl Default for ConfidencePhrasing {
+    fn default() -> Self {
+        Self {
+            certain: 0.85,
+            tentative: 0.6,
+        }
+    }
+}
+
+impl ConfidencePhrasing {
+    pub fn certainty(&self, confidence: f32) -> Certainty {
+        if confidence >= self.certain {
+            Certainty::Definite
+        } else if confidence >= self.tentative {
+            Certainty::Probable
+        } else {
+            Certainty::Tentative
+        }
+    }
+
+    /// Факт от третьего лица с пометкой по уверенности
+    pub fn phrase(&self, text: &str, confidence: f32) -> String {
+        format!("{}{}", self.certainty(confidence).prefix(), third_person(text))
+    }
+
+    /// В тексте есть факты с пометкой - под ним нужен [`HEDGE_NOTE`]
+    pub fn is_hedged(text: &str) -> bool {
+        [Certainty::Probable, Certainty::Tentative]
+            .iter()
+            .any(|c| text.contains(c.prefix()))
+    }
+}
+
+/// Модальные глаголы и отрицания, которые не меняются в третьем лице
+const EN_MODALS: &[&str] = &[
+    "can", "could", "will", "would", "shall", "should", "may", "might", "must", "can't", "cannot",
+    "won't", "wouldn't", "couldn't", "shouldn't", "didn't",
+];
+/// Наречия между подлежащим и глаголом: «I really love»
+const EN_ADVERBS: &[&str] = &[
+    "also", "really", "usually", "often", "never", "always", "still", "just", "sometimes", "mostly",
+    "only", "even", "already", "rarely", "totally", "definitely", "actually",
+];
+const RU_ADVERBS: &[&str] = &[
+    "очень", "тоже", "также", "всегда", "часто", "обычно", "никогда", "не", "уже", "ещё", "еще",
+    "сейчас", "давно", "постоянно", "иногда", "редко", "просто", "правда", "реально", "сильно",
+    "больше", "меньше", "так", "все", "всё",
+];
+/// Глаголы, которые не ставятся в третье лицо по окончанию
+const RU_IRREGULAR: &[(&str, &str)] = &[
+    ("хочу", "хочет"), ("могу", "может"), ("живу", "живет"), ("иду", "идет"), ("пишу", "пишет"),
+    ("хожу", "ходит"), ("вожу", "водит"), ("ношу", "носит"), ("прошу", "просит"), ("плачу", "платит"),
+    ("вижу", "видит"), ("сижу", "сидит"), ("ненавижу", "ненавидит"), ("лечу", "летит"), ("учу", "учит"),
+    ("пеку", "печет"), ("жду", "ждет"), ("беру", "берет"), ("веду", "ведет"), ("еду", "едет"),
+    ("ем", "ест"), ("пью", "пьет"), ("бегу", "бежит"), ("кладу", "кладет"), ("несу", "несет"),
+    ("зову", "зовет"), ("ищу", "ищет"), ("стою", "стоит"), ("боюсь", "боится"), ("ложусь", "ложится"),
+    ("сажусь", "садится"), ("готовлюсь", "готовится"), ("сплю", "спит"), ("шучу", "шутит"),
+];
+const RU_POSSESSIVE_NOMINATIVE: &[&str] = &["мой", "моя", "моё", "мое", "мои"];
+const RU_POSSESSIVE: &[(&str, &str)] = &[
+    ("мой", "свой"), ("моя", "своя"), ("моё", "своё"), ("мое", "свое"), ("мои", "свои"),
+    ("моего", "своего"), ("моей", "своей"), ("моему", "своему"), ("моим", "своим"), ("моих", "своих"),
+    ("мою", "свою"), ("моём", "своём"), ("моем", "своем"), ("моими", "своими"),
+];
+
+/// Переписывает факт от первого лица в третье; текст без местоимений первого лица
+/// возвращается как есть
+pub fn third_person(text: &str) -> String {
+    let mut words = tokenize(text);
+    let mut unsure = false;
+    let mut changed = false;
+    let mut at_start = true;
+
+    let mut i = 0;
+    while i < words.len() {
+        let Segment::Word(ref word) = words[i] else {
+            if let Segment::Other(ref sep) = words[i] {
+                if sep.contains(['.', '!', '?', ':', ';']) {
+                    at_start = true;
+                }
+            }
+            i += 1;
+            continue;
+        };
+        let lower = word.to_lowercase().replace('’', "'");
+        let replacement = match lower.as_str() {
+            "i" | "i'm" | "i've" | "i'll" | "i'd" => {
+                let subject = if at_start { "The user" } else { "they" };
+                match lower.as_str() {
+                    "i'm" => Some(format!("{} {}", subject, if at_start { "is" } else { "are" })),
+                    "i've" => Some(format!("{} {}", subject, if at_start { "has" } else { "have" })),
+                    "i'll" => Some(format!("{} will", subject)),
+                    "i'd" => Some(format!("{} would", subject)),
+                    _ => {
+                        if let Some(j) = next_verb(&words, i, EN_ADVERBS) {
+                            let verb = en_verb(words[j].text(), at_start);
+                            words[j] = Segment::Word(verb);
+                        }
+                        Some(subject.to_string())
+                    }
+                }
+            }
+            "my" => Some(if at_start { "The user's" } else { "their" }.to_string()),
+            "me" => Some("them".to_string()),
+            "mine" => Some("theirs".to_string()),
+            "myself" => Some("themselves".to_string()),
+            "я" => {
+                if let Some(j) = next_verb(&words, i, RU_ADVERBS) {
+                    match ru_verb(words[j].text()) {
+                        Some(verb) => words[j] = Segment::Word(verb),
+                        None => unsure = true,
+                    }
+                }
+                Some("пользователь".to_string())
+            }
+            "меня" => Some("пользователя".to_string()),
+            "мне" => Some("пользователю".to_string()),
+            "мной" => Some("пользователем".to_string()),
+            w if at_start && RU_POSSESSIVE_NOMINATIVE.contains(&w) => Some("у пользователя".to_string()),
+            w => RU_POSSESSIVE
+                .iter()
+                .find(|(first, _)| *first == w)
+                .map(|(_, reflexive)| reflexive.to_string()),
+        };
+
+        if let Some(replacement) = replacement {
+            changed = true;
+            words[i] = Segment::Word(if at_start { capitalize(&replacement) } else { replacement });
+        }
+        at_start = false;
+        i += 1;
+    }
+
+    if !changed {
+        return text.to_string();
+    }
+    if unsure {
+        return format!("Пользователь о себе: «{}»", text.trim());
+    }
+    words.iter().map(Segment::text).collect()
+}
+
+enum Segment {
+    Word(String),
+    Other(String),
+}
+
+impl Segment {
+    fn text(&self) -> &str {
+        match self {
+            Segment::Word(s) | Segment::Other(s) => s,
+        }
+    }
+}
+
+fn tokenize(text: &str) -> Vec<Segment> {
+    let mut segments = Vec::new();
+    let mut current = String::new();
+    let mut in_word = false;
+    for c in text.chars() {
+        let is_word = c.is_alphanumeric() || ((c == '\'' || c == '’') && in_word);
+        if is_word != in_word && !current.is_empty() {
+            let text = std::mem::take(&mut current);
+            segments.push(if in_word { Segment::Word(text) } else { Segment::Other(text) });
+        }
+        in_word = is_word;
+        current.push(c);
+    }
+    if !current.is_empty() {
+        segments.push(if in_word { Segment::Word(current) } else { Segment::Other(current) });
+    }
+    segments
+}
+
+/// Индекс глагола после подлежащего `i`: пропускает наречия, останавливается на пунктуации
+fn next_verb(words: &[Segment], i: usize, adverbs: &[&str]) -> Option<usize> {
+    let mut j = i + 1;
+    while j < words.len() {
+        match &words[j] {
+            Segment::Other(sep) if sep.trim().is_empty() => {}
+            Segment::Other(_) => return None,
+            Segment::Word(w) if adverbs.contains(&w.to_lowercase().as_str()) => {}
+            Segment::Word(_) => return Some(j),
+        }
+        j += 1;
+    }
+    None
+}
+
+/// Форма глагола после «The user» (`singular`) или «they»
+fn en_verb(verb: &str, singular: bool) -> String {
+    let lower = verb.to_lowercase().replace('’', "'");
+    if !singular {
+        return match lower.as_str() {
+            "am" => "are".to_string(),
+            "was" => "were".to_string(),
+            _ => verb.to_string(),
+        };
+    }
+    let irregular = match lower.as_str() {
+        "am" => Some("is"),
+        "have" => Some("has"),
+        "haven't" => Some("hasn't"),
+        "do" => Some("does"),
+        "don't" => Some("doesn't"),
+        "go" => Some("goes"),
+        _ => None,
+    };
+    if let Some(form) = irregular {
+        return form.to_string();
+    }
+    if EN_MODALS.contains(&lower.as_str()) || lower.ends_with("ed") || lower == "was" || lower.contains('\'') {
+        return verb.to_string();
+    }
+    let consonant_y = lower.ends_with('y')
+        && lower
+            .chars()
+            .rev()
+            .nth(1)
+            .is_some_and(|c| !"aeiou".contains(c));
+    if consonant_y {
+        format!("{}ies", &verb[..verb.len() - 1])
+    } else if ["s", "sh", "ch", "x", "z", "o"].iter().any(|end| lower.ends_with(end)) {
+        format!("{}es", verb)
+    } else {
+        format!("{}s", verb)
+    }
+}
+
+/// Третье лицо русского глагола настоящего времени. Прошедшее время и не-глаголы
+/// не меняются; `None` - глагол первого лица, который не удалось изменить
+fn ru_verb(verb: &str) -> Option<String> {
+    let lower = verb.to_lowercase();
+    if let Some((_, form)) = RU_IRREGULAR.iter().find(|(first, _)| *first == lower) {
+        return Some(form.to_string());
+    }
+    if let Some(base) = lower.strip_suffix("сь") {
+        if base.ends_with('ю') || base.ends_with('у') {
+            return ru_verb(base).map(|form| format!("{}ся", form));
+        }
+    }
+
+    let Some(stem) = lower.strip_suffix('ю') else {
+        // «-у» без словаря не угадать (пишу → пишет, но прошу → просит)
+        return if lower.ends_with('у') { None } else { Some(verb.to_string()) };
+    };
+    let last = stem.chars().last()?;
+    if "аеёиоуыэюя".contains(last) {
+        return Some(format!("{}ет", stem));
+    }
+    // люблю → любит, готовлю → готовит
+    let stem = match stem.strip_suffix('л') {
+        Some(s) if s.ends_with(['б', 'п', 'в', 'м', 'ф']) => s,
+        _ => stem,
+    };
+    Some(format!("{}ит", stem))
+}
+
+fn capitalize(text: &str) -> String {
+    let mut chars = text.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_third_person() {
+        assert_eq!(third_person("I love pizza"), "The user loves pizza");
+        assert_eq!(third_person("I'm a backend developer"), "The user is a backend developer");
+        assert_eq!(third_person("I really don't like tea"), "The user really doesn't like tea");
+        assert_eq!(
+            third_person("My dog is called Rex and I walk him every day"),
+            "The user's dog is called Rex and they walk him every day"
+        );
+        assert_eq!(third_person("User prefers dark theme"), "User prefers dark theme");
+
+        assert_eq!(third_person("Я люблю пиццу"), "Пользователь любит пиццу");
+        assert_eq!(third_person("Я очень увлекаюсь шахматами"), "Пользователь очень увлекается шахматами");
+        assert_eq!(third_person("Я живу в Казани"), "Пользователь живет в Казани");
+        assert_eq!(third_person("Мне нравится Rust"), "Пользователю нравится Rust");
+        assert_eq!(third_person("Моя собака Рекс"), "У пользователя собака Рекс");
+        assert_eq!(third_person("Я был в Японии"), "Пользователь был в Японии");
+        assert_eq!(third_person("Я вяжу свитера"), "Пользователь о себе: «Я вяжу свитера»");
+
+        let phrasing = ConfidencePhrasing::default();
+        assert_eq!(phrasing.phrase("I love pizza", 0.95), "The user loves pizza");
+        assert_eq!(phrasing.phrase("I love pizza", 0.7), "Probably: The user loves pizza");
+        assert_eq!(phrasing.phrase("Я люблю пиццу", 0.4), "Maybe (unconfirmed): Пользователь любит пиццу");
+        assert!(ConfidencePhrasing::is_hedged("- Probably: The user loves pizza"));
+        assert!(!ConfidencePhrasing::is_hedged("- The user loves pizza"));
+    }
+}
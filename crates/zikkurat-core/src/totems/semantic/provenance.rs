@@ -0,0 +1,267 @@
+//! 🧾 Происхождение концептов и журнал изменений
+//!
+//! Концепт живёт дольше реплики, из которой он извлечён: его подтверждают,
+//! сливают с похожими, переписывают противоречащим фактом, исправляют и гасят
+//! затуханием. Каждое подтверждение оставляет у концепта [`Evidence`] - сессию,
+//! ход и слова пользователя, - а каждое изменение текста или уверенности
+//! дописывается строкой в журнал [`AuditLog`] (`semantic_audit.jsonl` рядом с
+//! концептами). Журнал только растёт: по нему `/semantic history <id>`
+//! объясняет, откуда у концепта нынешний текст и уверенность.
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use uuid::Uuid;
+
+use super::concept::Concept;
+use crate::utils::crypto::{self, MemoryKey};
+
+/// Журнал изменений лежит рядом с концептами
+pub const AUDIT_LOG_FILE: &str = "semantic_audit.jsonl";
+/// Свидетельств у концепта не больше: первое остаётся всегда, вытесняются старые из остальных
+pub const MAX_EVIDENCE: usize = 32;
+
+/// Ход диалога, из которого пришёл факт
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct TurnRef {
+    pub session_id: String,
+    /// Номер хода в сессии с 1, если известен
+    pub turn: Option<usize>,
+}
+
+impl TurnRef {
+    pub fn new(session_id: impl Into<String>, turn: usize) -> Self {
+        Self { session_id: session_id.into(), turn: Some(turn) }
+    }
+
+    /// Сессия без номера хода
+    pub fn session(session_id: impl Into<String>) -> Self {
+        Self { session_id: session_id.into(), turn: None }
+    }
+}
+
+/// Одно подтверждение концепта: где и какими словами пользователь это сказал
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Evidence {
+    pub session_id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub turn: Option<usize>,
+    pub raw_text: String,
+    pub timestamp: DateTime<Utc>,
+}
+
+impl Evidence {
+    pub fn new(origin: &TurnRef, raw_text: &str, timestamp: DateTime<Utc>) -> Self {
+        Self {
+            session_id: origin.session_id.clone(),
+            turn: origin.turn,
+            raw_text: raw_text.trim().to_string(),
+            timestamp,
+        }
+    }
+
+    /// Та же реплика того же хода
+    fn same_as(&self, other: &Evidence) -> bool {
+        self.session_id == other.session_id && self.turn == other.turn && self.raw_text == other.raw_text
+    }
+}
+
+/// Добавляет свидетельство к концепту. Повтор той же реплики не добавляется. Возвращает,
+/// добавлено ли
+pub fn add_evidence(concept: &mut Concept, evidence: Evidence) -> bool {
+    if evidence.raw_text.is_empty() || concept.evidence.iter().any(|e| e.same_as(&evidence)) {
+        return false;
+    }
+    concept.evidence.push(evidence);
+    if concept.evidence.len() > MAX_EVIDENCE {
+        concept.evidence.remove(1);
+    }
+    true
+}
+
+/// Что случилось с концептом
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditAction {
+    /// Новый концепт
+    Created,
+    /// Тот же факт сказан снова
+    Reinforced,
+    /// Похожий факт слит с концептом
+    Merged,
+    /// Противоречащий факт с большей уверенностью стал новой версией
+    Revised,
+    /// Противоречащий факт с меньшей уверенностью отклонён
+    Contradicted,
+    /// Исправление пользователя
+    Corrected,
+    /// Уверенность снижена затуханием
+    Decayed,
+    Removed,
+}
+
+impl AuditAction {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            AuditAction::Created => "created",
+            AuditAction::Reinforced => "reinforced",
+            AuditAction::Merged => "merged",
+            AuditAction::Revised => "revised",
+            AuditAction::Contradicted => "contradicted",
+            AuditAction::Corrected => "corrected",
+            AuditAction::Decayed => "decayed",
+            AuditAction::Removed => "removed",
+        }
+    }
+}
+
+/// Строка журнала: состояние концепта после изменения и то, что было до него
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AuditEntry {
+    pub at: DateTime<Utc>,
+    pub concept_id: Uuid,
+    pub action: AuditAction,
+    pub text: String,
+    pub confidence: f32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub previous_text: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub previous_confidence: Option<f32>,
+    /// Откуда изменение: сессия, `decay`, `correction`, файл фактов...
+    pub source: String,
+    /// Текст, который вызвал изменение, если он отличается от концепта
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+}
+
+impl AuditEntry {
+    /// Запись о концепте в его нынешнем состоянии
+    pub fn new(action: AuditAction, concept: &Concept, source: &str, at: DateTime<Utc>) -> Self {
+        Self {
+            at,
+            concept_id: concept.id,
+            action,
+            text: concept.text.clone(),
+            confidence: concept.confidence,
+            previous_text: None,
+            previous_confidence: None,
+            source: source.to_string(),
+            detail: None,
+        }
+    }
+
+    /// Текст и уверенность до изменения; неизменившиеся не записываются
+    pub fn before(mut self, text: &str, confidence: f32) -> Self {
+        self.previous_text = (text != self.text).then(|| text.to_string());
+        self.previous_confidence = (confidence != self.confidence).then_some(confidence);
+        self
+    }
+
+    pub fn with_detail(mut self, detail: impl Into<String>) -> Self {
+        self.detail = Some(detail.into());
+        self
+    }
+
+    /// Строка для `/semantic history`
+    pub fn describe(&self) -> String {
+        let mut line = format!(
+            "{} {:<12} {:.2}",
+            self.at.format("%Y-%m-%d %H:%M"),
+            self.action.as_str(),
+            self.confidence
+        );
+        if let Some(previous) = self.previous_confidence {
+            line.push_str(&format!(" (was {:.2})", previous));
+        }
+        line.push_str(&format!(" [{}] {}", self.source, self.text));
+        if let Some(ref previous) = self.previous_text {
+            line.push_str(&format!(" (was: {})", previous));
+        }
+        if let Some(ref detail) = self.detail {
+            line.push_str(&format!(" <- {}", detail));
+        }
+        line
+    }
+}
+
+/// Журнал изменений концептов: только дописывается
+pub struct AuditLog {
+    path: PathBuf,
+    /// Каталог принадлежит другому процессу: записи живут в памяти до конца запуска
+    read_only: bool,
+    unsaved: Vec<AuditEntry>,
+    /// С ключом строки журнала пишутся зашифрованными (см. [`crypto::seal_line`])
+    key: Option<MemoryKey>,
+}
+
+impl AuditLog {
+    /// Журнал в каталоге `dir`
+    pub fn new(dir: &Path, read_only: bool) -> Self {
+        Self {
+            path: dir.join(AUDIT_LOG_FILE),
+            read_only,
+            unsaved: Vec::new(),
+            key: crypto::installed(),
+        }
+    }
+
+    /// Ключ шифрования вместо поставленного [`crypto::install`]
+    pub fn with_encryption(mut self, key: Option<MemoryKey>) -> Self {
+        self.key = key;
+        self
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Дописывает записи в конец файла
+    pub fn append(&mut self, entries: &[AuditEntry]) -> Result<()> {
+        if entries.is_empty() {
+            return Ok(());
+        }
+        if self.read_only {
+            self.unsaved.extend_from_slice(entries);
+            return Ok(());
+        }
+        let mut lines = String::new();
+        for entry in entries {
+            lines.push_str(&crypto::seal_line(self.key.as_ref(), &serde_json::to_string(entry)?)?);
+            lines.push('\n');
+        }
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir).with_context(|| format!("Failed to create directory: {:?}", dir))?;
+        }
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .with_context(|| format!("Failed to open audit log {:?}", self.path))?;
+        file.write_all(lines.as_bytes())
+            .with_context(|| format!("Failed to write audit log {:?}", self.path))?;
+        Ok(())
+    }
+
+    /// Все записи по порядку; повреждённые строки пропускаются, зашифрованные без ключа - ошибка
+    pub fn entries(&self) -> Result<Vec<AuditEntry>> {
+        let mut entries = Vec::new();
+        if self.path.exists() {
+            let content = std::fs::read_to_string(&self.path)
+                .with_context(|| format!("Failed to read audit log {:?}", self.path))?;
+            for line in content.lines() {
+                let line = crypto::unseal_line(self.key.as_ref(), line)
+                    .with_context(|| format!("Failed to decrypt audit log {:?}", self.path))?;
+                entries.extend(serde_json::from_str(&line).ok());
+            }
+        }
+        entries.extend(self.unsaved.iter().cloned());
+        Ok(entries)
+    }
+
+    /// Записи об одном концепте по порядку
+    pub fn history(&self, id: &Uuid) -> Result<Vec<AuditEntry>> {
+        Ok(self.entries()?.into_iter().filter(|e| e.concept_id == *id).collect())
+    }
+}
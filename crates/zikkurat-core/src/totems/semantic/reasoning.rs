@@ -0,0 +1,130 @@
+//! 🕸️ Рассуждения по графу знаний
+//!
+//! Отвечает на реляционные вопросы о пользователе ("what foods do I like?")
+//! обходом графа: user -likes-> X, где X is_a food (или X помечен тегом food).
+
+use regex::Regex;
+
+/// Тексты концептов, обозначающих самого пользователя
+pub const USER_ALIASES: &[&str] = &["user", "i", "me", "я", "пользователь"];
+
+/// Реляционный вопрос: предикат и (опционально) класс объектов
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RelationalQuery {
+    pub predicate: &'static str,
+    pub class: Option<String>,
+}
+
+impl RelationalQuery {
+    /// Распознаёт вопросы вида "what foods do I like?", "что я люблю?", "какую еду я люблю?"
+    pub fn parse(question: &str) -> Option<Self> {
+        let patterns = [
+            r"(?i)\b(?:what|which)\s+(?:(\w+)\s+)?(?:do|did)\s+i\s+(like|love|want|have|own)\b",
+            r"(?i)(?:^|\s)(?:что|какие|какую|какой|какое)\s+(?:(\w+)\s+)?я\s+(люблю|хочу|имею)",
+        ];
+
+        for pattern in patterns {
+            let re = Regex::new(pattern).ok()?;
+            if let Some(caps) = re.captures(question) {
+                let predicate = match caps.get(2)?.as_str().to_lowercase().as_str() {
+                    "like" | "love" | "люблю" => "likes",
+                    "want" | "хочу" => "wants",
+                    _ => "has",
+                };
+                let class = caps.get(1).map(|m| m.as_str().to_lowercase());
+                return Some(Self { predicate, class });
+            }
+        }
+        None
+    }
+}
+
+/// Ответ, собранный из графа
+#[derive(Debug, Clone)]
+pub struct GraphAnswer {
+    pub query: RelationalQuery,
+    /// (текст объекта, уверенность связи), по убыванию уверенности
+    pub items: Vec<(String, f32)>,
+}
+
+impl GraphAnswer {
+    /// Факты для промпта перефразирования
+    pub fn facts(&self) -> Vec<String> {
+        self.items
+            .iter()
+            .map(|(item, conf)| format!("user {} {} (confidence {:.2})", self.query.predicate, item, conf))
+            .collect()
+    }
+
+    /// Шаблонный ответ без LLM
+    pub fn compose(&self) -> String {
+        let items: Vec<&str> = self.items.iter().map(|(item, _)| item.as_str()).collect();
+        let verb = match self.query.predicate {
+            "likes" => "like",
+            "wants" => "want",
+            _ => "have",
+        };
+        match &self.query.class {
+            Some(class) => format!("You {} these {}: {}.", verb, class, items.join(", ")),
+            None => format!("You {}: {}.", verb, items.join(", ")),
+        }
+    }
+}
+
+/// Окончания, которыми могут различаться формы одного слова
+const INFLECTIONS: &[&str] = &[
+    "", "s", "es", "а", "я", "у", "ю", "ы", "и", "е", "о", "ой", "ей", "ам", "ям", "ами", "ями", "ах", "ях", "ов", "ев",
+    "ом", "ем",
+];
+
+/// Грубое сравнение по основе слова: "foods" ~ "food", "еду" ~ "еда", но не "cat" ~ "car" -
+/// слова совпадают, если после отбрасывания окончаний из [`INFLECTIONS`] остаётся общая основа
+pub fn same_stem(a: &str, b: &str) -> bool {
+    let (a, b) = (a.trim().to_lowercase(), b.trim().to_lowercase());
+    if a == b {
+        return true;
+    }
+    if a.chars().count() < 3 || b.chars().count() < 3 {
+        return false;
+    }
+    let stems = |w: &str| -> Vec<String> {
+        INFLECTIONS
+            .iter()
+            .filter_map(|ending| w.strip_suffix(ending))
+            .filter(|stem| stem.chars().count() >= 2)
+            .map(str::to_string)
+            .collect()
+    };
+    let b_stems = stems(&b);
+    stems(&a).iter().any(|stem| b_stems.contains(stem))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_relational_query() {
+        let q = RelationalQuery::parse("What foods do I like?").unwrap();
+        assert_eq!(q.predicate, "likes");
+        assert_eq!(q.class.as_deref(), Some("foods"));
+
+        let q = RelationalQuery::parse("what do I want").unwrap();
+        assert_eq!(q, RelationalQuery { predicate: "wants", class: None });
+
+        let q = RelationalQuery::parse("Какую еду я люблю?").unwrap();
+        assert_eq!(q.class.as_deref(), Some("еду"));
+        assert!(RelationalQuery::parse("How do I like my coffee made?").is_none());
+    }
+
+    #[test]
+    fn test_same_stem() {
+        assert!(same_stem("foods", "food"));
+        assert!(same_stem("еду", "еда"));
+        assert!(same_stem("fruits", "fruit"));
+        assert!(!same_stem("food", "movie"));
+        assert!(!same_stem("cat", "car"));
+        assert!(!same_stem("кот", "кит"));
+        assert!(same_stem("cats", "cat"));
+    }
+}
@@ -0,0 +1,190 @@
+//! ⏳ Сроки хранения концептов по категориям
+//!
+//! Раньше все концепты затухали по одной зашитой таблице. Теперь у каждой
+//! категории своя [`RetentionPolicy`]: как быстро слабеет уверенность
+//! (`decay_rate` 1.0 - не слабеет вовсе, так по умолчанию хранятся факты) и сколько
+//! концептов категория держит (`max_count`). Сверх предела вытесняются давно не
+//! использованные - по [`Concept::last_used_at`]. Политики применяет
+//! [`super::SemanticMemoryManager::apply_temporal_decay`], а с ним и работа `decay`
+//! обслуживания по расписанию; поправки задаёт раздел `retention` в `--system-config`.
+
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use super::concept::{Concept, ConceptCategory, DecayConfig, KnowledgeSource};
+
+/// Предел концептов категории «общее» по умолчанию
+pub const DEFAULT_GENERAL_MAX_COUNT: usize = 1000;
+
+/// Как долго и сколько хранятся концепты одной категории
+#[derive(Debug, Clone, PartialEq)]
+pub struct RetentionPolicy {
+    pub decay: DecayConfig,
+    /// Больше стольких концептов категория не держит (0 - без предела)
+    pub max_count: usize,
+}
+
+impl RetentionPolicy {
+    /// Политика категории по умолчанию
+    pub fn for_category(category: &ConceptCategory) -> Self {
+        let max_count = match category {
+            ConceptCategory::General => DEFAULT_GENERAL_MAX_COUNT,
+            _ => 0,
+        };
+        Self {
+            decay: category.get_decay_config(),
+            max_count,
+        }
+    }
+}
+
+/// Поправки одной категории: заданные поля заменяют политику по умолчанию
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RetentionOverrides {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub period_days: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub decay_rate: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_confidence: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_count: Option<usize>,
+}
+
+impl RetentionOverrides {
+    /// Политика с поправками
+    pub fn apply(&self, base: &RetentionPolicy) -> RetentionPolicy {
+        RetentionPolicy {
+            decay: DecayConfig {
+                period_days: self.period_days.unwrap_or(base.decay.period_days),
+                decay_rate: self.decay_rate.unwrap_or(base.decay.decay_rate),
+                min_confidence: self.min_confidence.unwrap_or(base.decay.min_confidence),
+            },
+            max_count: self.max_count.unwrap_or(base.max_count),
+        }
+    }
+}
+
+/// Поправки по категориям:
+/// `{"preferences": {"period_days": 60}, "general": {"period_days": 7, "decay_rate": 0.8, "max_count": 300}}`
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RetentionConfig {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub facts: Option<RetentionOverrides>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rules: Option<RetentionOverrides>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub preferences: Option<RetentionOverrides>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub skills: Option<RetentionOverrides>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub goals: Option<RetentionOverrides>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub general: Option<RetentionOverrides>,
+}
+
+impl RetentionConfig {
+    /// Политика категории с поправками из настроек
+    pub fn policy(&self, category: &ConceptCategory) -> RetentionPolicy {
+        let overrides = match category {
+            ConceptCategory::Facts => &self.facts,
+            ConceptCategory::Rules => &self.rules,
+            ConceptCategory::Preferences => &self.preferences,
+            ConceptCategory::Skills => &self.skills,
+            ConceptCategory::Goals => &self.goals,
+            ConceptCategory::General => &self.general,
+        };
+        let base = RetentionPolicy::for_category(category);
+        match overrides {
+            Some(overrides) => overrides.apply(&base),
+            None => base,
+        }
+    }
+
+    /// Концепты сверх `max_count` своей категории, давно не использованные первыми
+    /// (при равенстве - менее уверенные). Знания архетипа не вытесняются и в предел не считаются
+    pub fn over_limit<'a>(&self, concepts: impl IntoIterator<Item = &'a Concept>) -> Vec<Uuid> {
+        let mut by_category: Vec<(ConceptCategory, Vec<&Concept>)> = Vec::new();
+        for concept in concepts {
+            if concept.knowledge_source == KnowledgeSource::Predefined {
+                continue;
+            }
+            match by_category.iter_mut().find(|(category, _)| *category == concept.category) {
+                Some((_, group)) => group.push(concept),
+                None => by_category.push((concept.category.clone(), vec![concept])),
+            }
+        }
+
+        let mut evicted = Vec::new();
+        for (category, mut group) in by_category {
+            let max_count = self.policy(&category).max_count;
+            if max_count == 0 || group.len() <= max_count {
+                continue;
+            }
+            group.sort_by(|a, b| {
+                b.last_used_at()
+                    .cmp(&a.last_used_at())
+                    .then(b.confidence.total_cmp(&a.confidence))
+                    .then(a.id.cmp(&b.id))
+            });
+            evicted.extend(group[max_count..].iter().map(|c| c.id));
+        }
+        evicted
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::MockEmbedder;
+    use crate::totems::semantic::persistence::SemanticPersistenceManager;
+    use crate::totems::semantic::SemanticMemoryManager;
+    use crate::utils::clock::{Clock, MockClock};
+    use anyhow::Result;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_facts_stay_and_general_is_capped_by_recency() -> Result<()> {
+        let config: RetentionConfig =
+            serde_json::from_str(r#"{"general": {"period_days": 7, "decay_rate": 0.5, "max_count": 2}}"#)?;
+        assert_eq!(config.policy(&ConceptCategory::General).decay.min_confidence, 0.05);
+        assert_eq!(config.policy(&ConceptCategory::Rules), RetentionPolicy::for_category(&ConceptCategory::Rules));
+
+        let dir = std::env::temp_dir().join(format!("ziggurat-retention-test-{}", Uuid::new_v4()));
+        let persistence = SemanticPersistenceManager::new(Some(&dir))?;
+        let mut manager = SemanticMemoryManager::new(Arc::new(MockEmbedder::default()), persistence)?;
+        let clock = Arc::new(MockClock::starting_now());
+        manager.set_clock(clock.clone());
+        manager.set_retention(config);
+
+        let fact = "User was born in Kazan".to_string();
+        manager.add_concept_blocking(fact, ConceptCategory::Facts, "s1".to_string(), Some(0.5))?;
+        let mut general = Vec::new();
+        for text in ["Weather talk about rain", "Jazz records collection", "Mountain hiking trip"] {
+            clock.advance(chrono::Duration::hours(1));
+            general.push(manager.add_concept_blocking(text.to_string(), ConceptCategory::General, "s1".to_string(), Some(0.9))?.id);
+        }
+        // the oldest one was just used, so the middle one is the least recently used
+        clock.advance(chrono::Duration::hours(1));
+        manager.mark_used(&general[..1]);
+
+        manager.apply_temporal_decay()?;
+        assert_eq!(manager.count(), 3);
+        assert!(manager.get_concept(&general[1]).is_none());
+        assert!(manager.get_concept(&general[0]).is_some_and(|c| c.last_used == Some(clock.now())));
+
+        // general fades within weeks, the fact stays as it was
+        clock.advance(chrono::Duration::days(60));
+        manager.apply_temporal_decay()?;
+        let left: Vec<(ConceptCategory, f32)> = manager
+            .get_concepts_with_decay(10)
+            .into_iter()
+            .map(|(confidence, c)| (c.category.clone(), confidence))
+            .collect();
+        assert_eq!(left, [(ConceptCategory::Facts, 0.5)]);
+        let _ = std::fs::remove_dir_all(&dir);
+        Ok(())
+    }
+}
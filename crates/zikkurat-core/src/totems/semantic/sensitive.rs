@@ -0,0 +1,282 @@
+//! 🔒 Чувствительные концепты
+//!
+//! Факты о здоровье, финансах и личных отношениях не сохраняются молча:
+//! экстракция откладывает их, пока пользователь не подтвердит. Политика
+//! задаётся по категориям, а ответы запоминаются, чтобы не спрашивать дважды.
+//! Запоминается хеш текста, а не сам текст: отклонённый факт не должен попасть
+//! на диск и так. С ключом памяти файл политики к тому же шифруется.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use super::concept::{Concept, ConceptCategory};
+use crate::utils::crypto::{self, MemoryKey};
+
+/// Файл политики рядом с semantic_memory.json
+pub const SENSITIVE_POLICY_FILE: &str = "sensitive_policy.json";
+
+/// Начала слов; фразы (с пробелом или дефисом) ищутся подстрокой
+const HEALTH_MARKERS: &[&str] = &[
+    "диабет", "болезн", "болею", "болит", "диагноз", "депресс", "тревожн", "астм", "аллерг",
+    "лекарств", "таблетк", "беремен", "психиатр", "психотерап", "онколог", "инсульт", "инфаркт",
+    "вич", "diabet", "disease", "illness", "diagnos", "depress", "anxiety", "asthma", "allerg",
+    "medication", "pregnan", "psychiatr", "therap", "cancer", "hiv", "disorder",
+];
+
+const FINANCIAL_MARKERS: &[&str] = &[
+    "зарплат", "зарабатыва", "доход", "долги", "долгов", "задолженн", "кредит", "ипотек",
+    "сбережен", "банковск", "налог", "salary", "earn", "income", "debt", "loan", "mortgage",
+    "savings", "taxes", "bank account", "credit card",
+];
+
+const RELATIONSHIP_MARKERS: &[&str] = &[
+    "развод", "развел", "развёл", "расстал", "изменя", "встречаюсь", "моя девушка", "мой парень",
+    "бывшая жена", "бывший муж", "divorc", "broke up", "dating", "girlfriend", "boyfriend",
+    "cheat", "ex-wife", "ex-husband",
+];
+
+/// Вид чувствительных данных
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SensitiveKind {
+    Health,
+    Financial,
+    Relationship,
+}
+
+impl SensitiveKind {
+    pub const ALL: [SensitiveKind; 3] = [
+        SensitiveKind::Health,
+        SensitiveKind::Financial,
+        SensitiveKind::Relationship,
+    ];
+
+    /// Определяет вид чувствительных данных в тексте концепта
+    pub fn detect(text: &str) -> Option<Self> {
+        let lower = text.to_lowercase();
+        let words: Vec<&str> = lower
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|w| !w.is_empty())
+            .collect();
+        let matches = |markers: &[&str]| {
+            markers.iter().any(|m| {
+                if m.contains(|c: char| !c.is_alphanumeric()) {
+                    lower.contains(m)
+                } else {
+                    words.iter().any(|w| w.starts_with(m))
+                }
+            })
+        };
+
+        Self::ALL.into_iter().find(|kind| {
+            matches(match kind {
+                SensitiveKind::Health => HEALTH_MARKERS,
+                SensitiveKind::Financial => FINANCIAL_MARKERS,
+                SensitiveKind::Relationship => RELATIONSHIP_MARKERS,
+            })
+        })
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SensitiveKind::Health => "health",
+            SensitiveKind::Financial => "financial",
+            SensitiveKind::Relationship => "relationship",
+        }
+    }
+}
+
+impl std::str::FromStr for SensitiveKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|kind| kind.as_str() == s.to_lowercase())
+            .with_context(|| format!("Unknown sensitive category '{}' (health, financial, relationship)", s))
+    }
+}
+
+/// Что делать с концептом этого вида
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SensitiveAction {
+    /// Спросить пользователя
+    #[default]
+    Ask,
+    Store,
+    Skip,
+}
+
+impl std::str::FromStr for SensitiveAction {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "ask" => Ok(SensitiveAction::Ask),
+            "store" => Ok(SensitiveAction::Store),
+            "skip" => Ok(SensitiveAction::Skip),
+            other => anyhow::bail!("Unknown action '{}' (ask, store, skip)", other),
+        }
+    }
+}
+
+/// Решение по конкретному концепту
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SensitiveDecision {
+    Store,
+    Skip,
+    Ask(SensitiveKind),
+}
+
+/// Концепт, ожидающий подтверждения пользователя
+#[derive(Debug, Clone)]
+pub struct PendingConcept {
+    pub text: String,
+    pub category: ConceptCategory,
+    pub confidence: f32,
+    pub tags: Vec<String>,
+    pub source: String,
+    /// Реплика пользователя, из которой извлечён концепт
+    pub utterance: String,
+    /// Ход сессии `source`, если известен
+    pub turn: Option<usize>,
+    pub kind: SensitiveKind,
+}
+
+/// Политика по категориям и запомненные ответы
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SensitivePolicy {
+    #[serde(default)]
+    actions: HashMap<SensitiveKind, SensitiveAction>,
+    /// [`decision_key`] текста -> сохранять ли
+    #[serde(default)]
+    decisions: HashMap<String, bool>,
+    #[serde(skip)]
+    path: Option<PathBuf>,
+    #[serde(skip)]
+    key: Option<MemoryKey>,
+}
+
+impl SensitivePolicy {
+    /// Загружает политику из `path` (по умолчанию всё `ask`, если файла нет).
+    /// Ответы, записанные прежде открытым текстом, переводятся в хеши
+    pub fn load(path: &Path) -> Result<Self> {
+        let key = crypto::installed();
+        let mut policy: Self = if path.exists() {
+            let content = crypto::unseal_string(key.as_ref(), std::fs::read(path)?)
+                .with_context(|| format!("Failed to decrypt {:?}", path))?;
+            serde_json::from_str(&content).with_context(|| format!("Failed to parse {:?}", path))?
+        } else {
+            Self::default()
+        };
+        policy.decisions = std::mem::take(&mut policy.decisions)
+            .into_iter()
+            .map(|(text, store)| match uuid::Uuid::parse_str(&text) {
+                Ok(_) => (text, store),
+                Err(_) => (decision_key(&text), store),
+            })
+            .collect();
+        policy.path = Some(path.to_path_buf());
+        policy.key = key;
+        Ok(policy)
+    }
+
+    /// Сохраняет политику туда, откуда загрузили (без пути - ничего не делает)
+    pub fn save(&self) -> Result<()> {
+        if let Some(ref path) = self.path {
+            std::fs::write(path, crypto::seal(self.key.as_ref(), serde_json::to_string_pretty(self)?)?)
+                .with_context(|| format!("Failed to write {:?}", path))?;
+        }
+        Ok(())
+    }
+
+    pub fn action(&self, kind: SensitiveKind) -> SensitiveAction {
+        self.actions.get(&kind).copied().unwrap_or_default()
+    }
+
+    pub fn set_action(&mut self, kind: SensitiveKind, action: SensitiveAction) {
+        self.actions.insert(kind, action);
+    }
+
+    pub fn decide(&self, text: &str) -> SensitiveDecision {
+        let Some(kind) = SensitiveKind::detect(text) else {
+            return SensitiveDecision::Store;
+        };
+        match self.decisions.get(&decision_key(text)) {
+            Some(true) => return SensitiveDecision::Store,
+            Some(false) => return SensitiveDecision::Skip,
+            None => {}
+        }
+        match self.action(kind) {
+            SensitiveAction::Ask => SensitiveDecision::Ask(kind),
+            SensitiveAction::Store => SensitiveDecision::Store,
+            SensitiveAction::Skip => SensitiveDecision::Skip,
+        }
+    }
+
+    /// Запоминает ответ пользователя на этот текст
+    pub fn remember(&mut self, text: &str, store: bool) {
+        self.decisions.insert(decision_key(text), store);
+    }
+}
+
+/// Ключ запомненного ответа: хеш нормализованного текста, сам текст не хранится
+fn decision_key(text: &str) -> String {
+    let normalized = text.to_lowercase().split_whitespace().collect::<Vec<_>>().join(" ");
+    Concept::content_id(SENSITIVE_POLICY_FILE, &normalized).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect_sensitive() {
+        assert_eq!(SensitiveKind::detect("У пользователя диабет 2 типа"), Some(SensitiveKind::Health));
+        assert_eq!(SensitiveKind::detect("User has a mortgage"), Some(SensitiveKind::Financial));
+        assert_eq!(SensitiveKind::detect("Недавно развелся"), Some(SensitiveKind::Relationship));
+        assert_eq!(SensitiveKind::detect("Пользователь любит Rust"), None);
+        // "hiv" only at a word start, not inside "archive"
+        assert_eq!(SensitiveKind::detect("Keeps an archive of photos"), None);
+    }
+
+    #[test]
+    fn test_policy_decisions() {
+        let mut policy = SensitivePolicy::default();
+        assert_eq!(
+            policy.decide("User has diabetes"),
+            SensitiveDecision::Ask(SensitiveKind::Health)
+        );
+        assert_eq!(policy.decide("User likes pizza"), SensitiveDecision::Store);
+
+        policy.remember("User has  diabetes", false);
+        assert_eq!(policy.decide("user has diabetes"), SensitiveDecision::Skip);
+
+        policy.set_action(SensitiveKind::Financial, SensitiveAction::Store);
+        assert_eq!(policy.decide("User pays a loan"), SensitiveDecision::Store);
+    }
+
+    #[test]
+    fn test_decisions_stored_without_text() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("ziggurat-sensitive-test-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir)?;
+        let path = dir.join(SENSITIVE_POLICY_FILE);
+
+        let mut policy = SensitivePolicy::load(&path)?;
+        policy.remember("User has diabetes", false);
+        policy.save()?;
+        let on_disk = std::fs::read_to_string(&path)?;
+        assert!(!on_disk.to_lowercase().contains("diabetes"), "{}", on_disk);
+        assert_eq!(SensitivePolicy::load(&path)?.decide("user has  diabetes"), SensitiveDecision::Skip);
+
+        // answers written as text by older versions still apply
+        std::fs::write(&path, r#"{"decisions": {"user has a mortgage": true}}"#)?;
+        assert_eq!(SensitivePolicy::load(&path)?.decide("User has a mortgage"), SensitiveDecision::Store);
+
+        std::fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,202 @@
+//! ❓ Утверждение, вопрос или гипотеза
+//!
+//! Экстракция фактов запускалась на любой реплике с «я »/«i »: из «я прав?» или
+//! «если бы я жил в Париже...» получались выдуманные факты. Теперь реплика сначала
+//! классифицируется правилами (знак вопроса, вопросительные слова, «бы», «what if»),
+//! а если правила не уверены - одним коротким запросом к LLM. Факты извлекаются
+//! только из утверждений о себе.
+
+use anyhow::Result;
+
+use crate::totems::episodic::LlmPipeline;
+
+/// Слова первого лица
+const SELF_WORDS: &[&str] = &[
+    "я", "мой", "моя", "моё", "мое", "мои", "моего", "моей", "меня", "мне", "мной", "i", "i'm", "im",
+    "i've", "i'd", "i'll", "my", "me", "mine", "myself",
+];
+/// Глаголы первого лица: по-русски местоимение часто опускают («Люблю суши»)
+const SELF_VERBS: &[&str] = &["люблю", "предпочитаю", "работаю", "живу", "учусь", "занимаюсь", "ненавижу"];
+/// Глаголы, с которыми реплика - точно рассказ о себе
+const DISCLOSURE_VERBS: &[&str] =
+    &["нравится", "нравятся", "зовут", "love", "like", "prefer", "work", "live", "study", "hate", "enjoy"];
+/// Вопрос, если предложение начинается с этого слова и не кончается точкой
+const QUESTION_STARTS: &[&str] = &[
+    "кто", "что", "где", "куда", "когда", "почему", "зачем", "как", "какой", "какая", "какое",
+    "какие", "сколько", "разве", "неужели", "what", "who", "where", "when", "why", "how", "which",
+    "am", "is", "are", "do", "does", "did", "can", "could", "should", "will", "have", "has",
+];
+/// Частица «ли» в любом месте: «прав ли я»
+const QUESTION_WORDS: &[&str] = &["ли"];
+/// Гипотеза: слова и фразы целиком
+const HYPOTHETICAL_MARKERS: &[&str] = &[
+    "если бы", "я бы", "бы я", "представь", "представьте", "допустим", "предположим", "вдруг",
+    "what if", "if i", "imagine", "suppose", "hypothetically", "would i",
+];
+/// Правила не уверены: просьба подтвердить, размышление вслух
+const AMBIGUOUS_MARKERS: &[&str] =
+    &["интересно", "скажи", "не знаю", "кажется", "wonder", "tell me", "not sure", "i guess", "maybe"];
+
+/// Вид реплики пользователя
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UtteranceKind {
+    Statement,
+    Question,
+    Hypothetical,
+}
+
+impl UtteranceKind {
+    /// Разбирает ответ LLM: первое узнаваемое слово
+    pub fn from_label(answer: &str) -> Option<Self> {
+        let answer = answer.to_uppercase();
+        answer.split(|c: char| !c.is_alphabetic()).find_map(|word| match word {
+            "STATEMENT" => Some(Self::Statement),
+            "QUESTION" => Some(Self::Question),
+            "HYPOTHETICAL" => Some(Self::Hypothetical),
+            _ => None,
+        })
+    }
+}
+
+/// Слова в нижнем регистре, апостроф - часть слова
+fn words(text: &str) -> Vec<String> {
+    text.to_lowercase()
+        .replace('’', "'")
+        .split(|c: char| !(c.is_alphanumeric() || c == '\''))
+        .filter(|w| !w.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// Пользователь говорит о себе в первом лице
+pub fn mentions_self(text: &str) -> bool {
+    words(text)
+        .iter()
+        .any(|w| SELF_WORDS.contains(&w.as_str()) || SELF_VERBS.contains(&w.as_str()))
+}
+
+/// Предложения вместе с завершающим знаком
+fn sentences(text: &str) -> Vec<&str> {
+    let mut sentences = Vec::new();
+    let mut start = 0;
+    for (i, c) in text.char_indices() {
+        if matches!(c, '.' | '!' | '?' | '…') {
+            let end = i + c.len_utf8();
+            sentences.push(text[start..end].trim());
+            start = end;
+        }
+    }
+    sentences.push(text[start..].trim());
+    sentences.retain(|s| s.chars().any(char::is_alphanumeric));
+    sentences
+}
+
+/// Слово или фраза из `markers` встречается целиком
+fn has_marker(words: &[String], markers: &[&str]) -> bool {
+    let joined = format!(" {} ", words.join(" "));
+    markers.iter().any(|m| joined.contains(&format!(" {} ", m)))
+}
+
+/// Одно предложение по правилам; `None`, если правила не уверены
+fn classify_sentence(sentence: &str) -> Option<UtteranceKind> {
+    let words = words(sentence);
+    let first = words.first().map(String::as_str).unwrap_or_default();
+    let ending = sentence.trim_end().chars().last().unwrap_or_default();
+
+    if has_marker(&words, HYPOTHETICAL_MARKERS) {
+        return Some(UtteranceKind::Hypothetical);
+    }
+    if ending == '?'
+        || (QUESTION_STARTS.contains(&first) && !matches!(ending, '.' | '!'))
+        || words.iter().any(|w| QUESTION_WORDS.contains(&w.as_str()))
+    {
+        return Some(UtteranceKind::Question);
+    }
+    if has_marker(&words, AMBIGUOUS_MARKERS) {
+        return None;
+    }
+    let about_self = SELF_WORDS.contains(&first)
+        || words
+            .iter()
+            .any(|w| SELF_VERBS.contains(&w.as_str()) || DISCLOSURE_VERBS.contains(&w.as_str()));
+    if about_self || matches!(ending, '.' | '!') {
+        return Some(UtteranceKind::Statement);
+    }
+    None
+}
+
+/// Реплика по правилам: хотя бы одно утверждение делает её утверждением
+/// («Я люблю суши. А ты?»). `None`, если правила не уверены
+pub fn classify_rules(text: &str) -> Option<UtteranceKind> {
+    let kinds: Vec<Option<UtteranceKind>> = sentences(text).into_iter().map(classify_sentence).collect();
+    [UtteranceKind::Statement, UtteranceKind::Hypothetical, UtteranceKind::Question]
+        .into_iter()
+        .find(|kind| kinds.contains(&Some(*kind)))
+        .filter(|kind| *kind == UtteranceKind::Statement || !kinds.contains(&None))
+}
+
+/// Промпт для LLM, когда правила не уверены
+pub fn classify_prompt(text: &str) -> String {
+    format!(
+        "<s>[INST] Classify the user's message with one word:\n\
+         STATEMENT - the user tells something about themselves\n\
+         QUESTION - the user asks something\n\
+         HYPOTHETICAL - the user imagines or supposes something\n\
+         \n\
+         Message: {}\n\
+         \n\
+         Answer with one word.[/INST]",
+        text
+    )
+}
+
+/// Вид реплики: правила, затем LLM. Без LLM или при непонятном ответе
+/// неуверенная реплика считается утверждением, как до классификатора
+pub fn classify(text: &str, llm: Option<&dyn LlmPipeline>) -> Result<UtteranceKind> {
+    if let Some(kind) = classify_rules(text) {
+        return Ok(kind);
+    }
+    let Some(llm) = llm else {
+        return Ok(UtteranceKind::Statement);
+    };
+    let answer = llm.generate(&classify_prompt(text), 8)?;
+    Ok(UtteranceKind::from_label(&answer).unwrap_or(UtteranceKind::Statement))
+}
+
+/// Из реплики стоит извлекать факты: пользователь утверждает что-то о себе
+pub fn is_self_disclosure(text: &str, llm: Option<&dyn LlmPipeline>) -> Result<bool> {
+    Ok(mentions_self(text) && classify(text, llm)? == UtteranceKind::Statement)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::MockLlmPipeline;
+
+    #[test]
+    fn test_classify_utterance() -> Result<()> {
+        use UtteranceKind::*;
+
+        assert_eq!(classify_rules("Я люблю суши"), Some(Statement));
+        assert_eq!(classify_rules("My name is Anna."), Some(Statement));
+        assert_eq!(classify_rules("am I right?"), Some(Question));
+        assert_eq!(classify_rules("Прав ли я"), Some(Question));
+        assert_eq!(classify_rules("What do I like"), Some(Question));
+        assert_eq!(classify_rules("Если бы я жил в Париже, я бы ел круассаны"), Some(Hypothetical));
+        assert_eq!(classify_rules("What if I moved to Berlin?"), Some(Hypothetical));
+        assert_eq!(classify_rules("Я люблю суши. А ты?"), Some(Statement));
+        assert_eq!(classify_rules("интересно, я вообще нормально сплю"), None);
+
+        assert!(!mentions_self("hi there, what is Rust?"));
+        assert!(is_self_disclosure("Я работаю врачом", None)?);
+        assert!(!is_self_disclosure("am I right?", None)?);
+        assert!(!is_self_disclosure("Сортировка слиянием - это что?", None)?);
+
+        // правила не уверены - решает LLM
+        let unsure = "интересно, я вообще нормально сплю";
+        assert!(!is_self_disclosure(unsure, Some(&MockLlmPipeline::new("QUESTION")))?);
+        assert!(is_self_disclosure(unsure, Some(&MockLlmPipeline::new("Statement.")))?);
+        assert!(is_self_disclosure(unsure, Some(&MockLlmPipeline::new("Echo: ...")))?);
+        Ok(())
+    }
+}
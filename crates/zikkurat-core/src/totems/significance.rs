@@ -0,0 +1,87 @@
+//! 🔇 Значимость реплик
+//!
+//! Короткие подтверждения («ok», «спасибо», «lol», 👍) ничего не сообщают, но
+//! раньше становились записями векторного индекса и всплывали при поиске
+//! воспоминаний. Такие ходы остаются в истории сессии (контекст разговора не
+//! рвётся), но не индексируются и не проходят экстракцию концептов.
+
+/// Ключ метаданных хода-подтверждения
+pub const ACK_KEY: &str = "ack";
+/// Реплики длиннее этого (в символах) всегда значимы
+pub const DEFAULT_ACK_MAX_CHARS: usize = 40;
+
+/// Слова, из которых состоят подтверждения
+const ACK_WORDS: &[&str] = &[
+    "ok", "okay", "okey", "k", "kk", "thanks", "thank", "thx", "ty", "you", "so", "much", "lol", "lmao",
+    "cool", "nice", "great", "good", "got", "it", "i", "see", "sure", "yes", "yep", "yeah", "no", "nope",
+    "alright", "fine", "wow", "oh", "ah", "hmm", "mm", "right", "понял", "поняла", "понятно", "ясно", "ок",
+    "окей", "спасибо", "спс", "пасиб", "благодарю", "большое", "огромное", "ага", "угу", "ну", "да", "нет",
+    "ладно", "хорошо", "хор", "отлично", "круто", "класс", "супер", "лол", "ого", "вау", "ясн", "норм",
+];
+
+/// Фильтр реплик, которые не стоит помнить
+#[derive(Debug, Clone)]
+pub struct SignificanceFilter {
+    /// 0 - фильтр выключен
+    max_chars: usize,
+    /// Дополнительные слова подтверждений (`--ack-words`)
+    extra_words: Vec<String>,
+}
+
+impl Default for SignificanceFilter {
+    fn default() -> Self {
+        Self::new(DEFAULT_ACK_MAX_CHARS, Vec::new())
+    }
+}
+
+impl SignificanceFilter {
+    pub fn new(max_chars: usize, extra_words: Vec<String>) -> Self {
+        Self {
+            max_chars,
+            extra_words: extra_words.into_iter().map(|w| w.trim().to_lowercase()).collect(),
+        }
+    }
+
+    /// Всё считается значимым
+    pub fn disabled() -> Self {
+        Self::new(0, Vec::new())
+    }
+
+    /// Реплика - только подтверждение: короткая и без слов кроме «ok», «спасибо»,
+    /// смеха и эмодзи
+    pub fn is_ack(&self, text: &str) -> bool {
+        let text = text.trim();
+        if self.max_chars == 0 || text.chars().count() > self.max_chars {
+            return false;
+        }
+        let lower = text.to_lowercase().replace('ё', "е");
+        lower
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|w| !w.is_empty())
+            .all(|w| ACK_WORDS.contains(&w) || self.extra_words.iter().any(|e| e == w) || is_laughter(w))
+    }
+}
+
+/// «haha», «ахаха», «хех»
+fn is_laughter(word: &str) -> bool {
+    word.chars().count() >= 2 && word.chars().all(|c| matches!(c, 'h' | 'a' | 'e' | 'х' | 'а' | 'е'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_ack() {
+        let filter = SignificanceFilter::default();
+        for ack in ["ok", "Спасибо!", "спасибо большое)", "lol", "👍", "Thank you so much!", "ахаха", "Ага, понятно"] {
+            assert!(filter.is_ack(ack), "{}", ack);
+        }
+        for message in ["ok, and what about lifetimes?", "Спасибо, я из Казани", "Меня зовут Ира", "2+2?"] {
+            assert!(!filter.is_ack(message), "{}", message);
+        }
+        assert!(!SignificanceFilter::new(0, Vec::new()).is_ack("ok"));
+        assert!(SignificanceFilter::new(40, vec!["Merci".to_string()]).is_ack("merci!"));
+        assert!(!SignificanceFilter::new(3, Vec::new()).is_ack("thanks"));
+    }
+}
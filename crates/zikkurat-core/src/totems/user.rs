@@ -0,0 +1,139 @@
+//! 👤 Пользователь памяти
+//!
+//! Вся память была неявно одного пользователя. [`UserId`] называет, с кем
+//! говорит персона: у каждого своя эпизодическая и семантическая память, свой
+//! контекст сессии и своя линия отношений в нарративе персоны. Пользователь по
+//! умолчанию остаётся в прежней раскладке каталога данных, остальные живут в
+//! `users/<id>/` - существующая память никуда не переезжает.
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// Подкаталог каталога данных с памятью пользователей, кроме пользователя по умолчанию
+pub const USERS_DIR: &str = "users";
+/// Идентификатор становится именем каталога, поэтому короткий
+const MAX_USER_ID_CHARS: usize = 64;
+
+/// Идентификатор пользователя: буквы, цифры, `-`, `_` и `.`
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct UserId(String);
+
+impl UserId {
+    /// Пользователь однопользовательского CLI и всей памяти, записанной до `--user`
+    pub const DEFAULT: &'static str = "default";
+
+    pub fn new(id: &str) -> Result<Self> {
+        let id = id.trim();
+        anyhow::ensure!(
+            !id.is_empty() && id.chars().count() <= MAX_USER_ID_CHARS,
+            "User id must be 1 to {} characters",
+            MAX_USER_ID_CHARS
+        );
+        anyhow::ensure!(
+            !id.starts_with('.') && id.chars().all(|c| c.is_alphanumeric() || matches!(c, '-' | '_' | '.')),
+            "User id '{}' may contain only letters, digits, '-', '_' and '.'",
+            id
+        );
+        Ok(Self(id.to_string()))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    pub fn is_default(&self) -> bool {
+        self.0 == Self::DEFAULT
+    }
+
+    /// Каталог памяти пользователя в каталоге данных `base`
+    pub fn data_dir(&self, base: &Path) -> PathBuf {
+        if self.is_default() {
+            base.to_path_buf()
+        } else {
+            base.join(USERS_DIR).join(&self.0)
+        }
+    }
+
+    /// Пользователи с памятью в `base`; пользователь по умолчанию есть всегда
+    pub fn list(base: &Path) -> Vec<UserId> {
+        let mut users = vec![UserId::default()];
+        if let Ok(entries) = std::fs::read_dir(base.join(USERS_DIR)) {
+            let mut named: Vec<UserId> = entries
+                .filter_map(|e| e.ok())
+                .filter(|e| e.path().is_dir())
+                .filter_map(|e| UserId::new(&e.file_name().to_string_lossy()).ok())
+                .filter(|u| !u.is_default())
+                .collect();
+            named.sort_by(|a, b| a.0.cmp(&b.0));
+            users.extend(named);
+        }
+        users
+    }
+}
+
+impl Default for UserId {
+    fn default() -> Self {
+        Self(Self::DEFAULT.to_string())
+    }
+}
+
+impl fmt::Display for UserId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl FromStr for UserId {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Self::new(s)
+    }
+}
+
+impl TryFrom<String> for UserId {
+    type Error = anyhow::Error;
+
+    fn try_from(s: String) -> Result<Self> {
+        Self::new(&s)
+    }
+}
+
+impl From<UserId> for String {
+    fn from(user: UserId) -> String {
+        user.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_user_data_dirs() -> Result<()> {
+        let base = std::env::temp_dir().join(format!("zikkurat-users-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&base);
+
+        let default = UserId::default();
+        let anna: UserId = "anna".parse()?;
+        assert_eq!(default.data_dir(&base), base);
+        assert_eq!(anna.data_dir(&base), base.join("users/anna"));
+        assert!(UserId::new("../etc").is_err());
+        assert!(UserId::new(".hidden").is_err());
+        assert!(UserId::new("").is_err());
+        assert_eq!(UserId::new(" борис ")?.as_str(), "борис");
+
+        std::fs::create_dir_all(anna.data_dir(&base))?;
+        assert_eq!(UserId::list(&base), vec![default, anna.clone()]);
+
+        let json = serde_json::to_string(&anna)?;
+        assert_eq!(json, "\"anna\"");
+        assert!(serde_json::from_str::<UserId>("\"a/b\"").is_err());
+        let _ = std::fs::remove_dir_all(&base);
+        Ok(())
+    }
+}
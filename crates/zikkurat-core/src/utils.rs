@@ -0,0 +1,86 @@
+#[cfg(feature = "runtime")]
+use std::sync::OnceLock;
+
+pub mod background;
+#[cfg(feature = "inference")]
+pub mod data_dir;
+#[cfg(feature = "inference")]
+pub mod download;
+pub mod clock;
+pub mod crypto;
+pub mod llm_json;
+pub mod lock;
+pub mod relative_time;
+
+// === ASYNC RUNTIME ===
+
+#[cfg(feature = "runtime")]
+static RUNTIME: OnceLock<tokio::runtime::Runtime> = OnceLock::new();
+
+/// Общий tokio runtime для синхронного фасада памяти
+#[cfg(feature = "runtime")]
+pub fn runtime() -> &'static tokio::runtime::Runtime {
+    RUNTIME.get_or_init(|| {
+        tokio::runtime::Builder::new_multi_thread()
+            .thread_name("ziggurat-memory")
+            .enable_all()
+            .build()
+            .expect("failed to build tokio runtime")
+    })
+}
+
+/// Синхронно дожидается future: из CLI через общий runtime,
+/// внутри multi-thread runtime - через block_in_place, не блокируя воркеры
+#[cfg(feature = "runtime")]
+pub fn block_on<F: std::future::Future>(future: F) -> F::Output {
+    match tokio::runtime::Handle::try_current() {
+        Ok(handle) => tokio::task::block_in_place(|| handle.block_on(future)),
+        Err(_) => runtime().block_on(future),
+    }
+}
+
+/// Без tokio future памяти никогда не ждут (IO и эмбеддинги синхронные внутри),
+/// поэтому достаточно опросить future до готовности
+#[cfg(not(feature = "runtime"))]
+pub fn block_on<F: std::future::Future>(future: F) -> F::Output {
+    use std::task::{Context, Poll, Waker};
+
+    let mut future = std::pin::pin!(future);
+    let mut cx = Context::from_waker(Waker::noop());
+    loop {
+        if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+            return output;
+        }
+        std::thread::yield_now();
+    }
+}
+
+// === FILE IO ===
+
+/// Файловый IO памяти: tokio::fs при наличии runtime, иначе std::fs за async-фасадом
+#[cfg(feature = "runtime")]
+pub mod fs {
+    pub use tokio::fs::{create_dir_all, read, read_to_string, write};
+}
+
+#[cfg(not(feature = "runtime"))]
+pub mod fs {
+    use std::io;
+    use std::path::Path;
+
+    pub async fn read(path: impl AsRef<Path>) -> io::Result<Vec<u8>> {
+        std::fs::read(path)
+    }
+
+    pub async fn read_to_string(path: impl AsRef<Path>) -> io::Result<String> {
+        std::fs::read_to_string(path)
+    }
+
+    pub async fn write(path: impl AsRef<Path>, contents: impl AsRef<[u8]>) -> io::Result<()> {
+        std::fs::write(path, contents)
+    }
+
+    pub async fn create_dir_all(path: impl AsRef<Path>) -> io::Result<()> {
+        std::fs::create_dir_all(path)
+    }
+}
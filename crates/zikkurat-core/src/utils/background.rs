@@ -0,0 +1,61 @@
+//! ⏳ Загрузка в фоне
+//!
+//! Веса модели, эмбеддер и сохранённая память при старте друг от друга не
+//! зависят. Каждая загрузка идёт в своём потоке, а результат забирается там,
+//! где он впервые нужен: пока ждут одну часть, остальные уже готовятся.
+
+use anyhow::{anyhow, Result};
+use std::thread::JoinHandle;
+
+/// Результат, который ещё считается в своём потоке
+pub struct Pending<T> {
+    name: String,
+    handle: JoinHandle<Result<T>>,
+}
+
+impl<T: Send + 'static> Pending<T> {
+    /// Запускает `load` в потоке `ziggurat-<name>`
+    pub fn spawn(name: &str, load: impl FnOnce() -> Result<T> + Send + 'static) -> Result<Self> {
+        let handle = std::thread::Builder::new()
+            .name(format!("ziggurat-{}", name))
+            .spawn(load)?;
+        Ok(Self {
+            name: name.to_string(),
+            handle,
+        })
+    }
+
+    /// Готов ли результат (`wait` вернётся сразу)
+    pub fn is_ready(&self) -> bool {
+        self.handle.is_finished()
+    }
+
+    /// Дожидается результата; паника потока становится ошибкой
+    pub fn wait(self) -> Result<T> {
+        self.handle
+            .join()
+            .map_err(|_| anyhow!("Background loading of {} panicked", self.name))?
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc;
+
+    #[test]
+    fn test_pending_waits_for_result() -> Result<()> {
+        let (release, gate) = mpsc::channel::<()>();
+        let pending = Pending::spawn("test", move || {
+            gate.recv()?;
+            Ok(42)
+        })?;
+        assert!(!pending.is_ready());
+        release.send(())?;
+        assert_eq!(pending.wait()?, 42);
+
+        let failed: Pending<()> = Pending::spawn("broken", || anyhow::bail!("no weights"))?;
+        assert_eq!(failed.wait().unwrap_err().to_string(), "no weights");
+        Ok(())
+    }
+}
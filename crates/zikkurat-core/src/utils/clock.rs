@@ -0,0 +1,105 @@
+//! 🕰️ Часы памяти
+//!
+//! Затухание концептов, срок жизни контекста сессии, «3 weeks ago» у
+//! воспоминаний и эволюция персоны зависят от текущего времени. Менеджеры
+//! памяти и персона получают его через [`Clock`]: в работе это
+//! [`SystemClock`], в тестах, сценариях поведения и профиле `--ci` -
+//! [`MockClock`], который двигается только по команде, поэтому проверки времени
+//! детерминированы.
+
+use chrono::{DateTime, Duration, Utc};
+use parking_lot::Mutex;
+use std::sync::{Arc, OnceLock};
+
+/// Источник текущего времени
+pub trait Clock: Send + Sync {
+    fn now(&self) -> DateTime<Utc>;
+
+    /// Секунды Unix, в которых хранят время контекст сессии и эволюция
+    fn unix_now(&self) -> u64 {
+        self.now().timestamp().max(0) as u64
+    }
+}
+
+/// Часы, разделяемые менеджерами памяти и персоной
+pub type SharedClock = Arc<dyn Clock>;
+
+/// Системное время
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+}
+
+/// Системные часы
+pub fn system() -> SharedClock {
+    Arc::new(SystemClock)
+}
+
+/// Часы, поставленные [`install`]
+static INSTALLED: OnceLock<SharedClock> = OnceLock::new();
+
+/// Ставит часы для всех менеджеров и персон, создаваемых дальше в процессе
+/// (профиль `--ci`). Ставятся один раз; `false`, если уже стоят
+pub fn install(clock: SharedClock) -> bool {
+    INSTALLED.set(clock).is_ok()
+}
+
+/// Часы для полей по умолчанию: поставленные [`install`] или системные
+pub fn default_clock() -> SharedClock {
+    INSTALLED.get().cloned().unwrap_or_else(system)
+}
+
+/// Управляемые часы: стоят, пока их не сдвинут
+#[derive(Debug)]
+pub struct MockClock {
+    now: Mutex<DateTime<Utc>>,
+}
+
+impl MockClock {
+    pub fn new(start: DateTime<Utc>) -> Self {
+        Self { now: Mutex::new(start) }
+    }
+
+    /// Начинает с текущего системного времени
+    pub fn starting_now() -> Self {
+        Self::new(Utc::now())
+    }
+
+    /// Сдвигает часы (назад - для отрицательного `by`)
+    pub fn advance(&self, by: Duration) {
+        *self.now.lock() += by;
+    }
+
+    pub fn set(&self, to: DateTime<Utc>) {
+        *self.now.lock() = to;
+    }
+}
+
+impl Clock for MockClock {
+    fn now(&self) -> DateTime<Utc> {
+        *self.now.lock()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mock_clock() {
+        let start = DateTime::parse_from_rfc3339("2024-03-01T12:00:00Z").unwrap().with_timezone(&Utc);
+        let mock = Arc::new(MockClock::new(start));
+        let clock: SharedClock = mock.clone();
+
+        assert_eq!(clock.now(), start);
+        mock.advance(Duration::days(30));
+        assert_eq!(clock.now(), start + Duration::days(30));
+        assert_eq!(clock.unix_now(), (start + Duration::days(30)).timestamp() as u64);
+        mock.set(start);
+        assert_eq!(clock.now(), start);
+    }
+}
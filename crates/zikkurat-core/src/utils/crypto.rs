@@ -0,0 +1,354 @@
+//! 🔐 Шифрование памяти на диске
+//!
+//! `sessions.json`, `*.bin`, `summaries.json`, архив сессий, файлы семантической
+//! памяти с журналом изменений и контексты персон хранят разговоры пользователя.
+//! С ключом памяти они пишутся зашифрованными AES-256-GCM:
+//! `MAGIC | nonce (12 байт) | шифротекст с тегом`, nonce свой у каждой записи.
+//! Построчный журнал шифруется по строке ([`seal_line`]), чтобы дописываться
+//! без перезаписи.
+//! Чтение прозрачное: зашифрованный файл расшифровывается, открытый читается как
+//! есть - так старые хранилища грузятся и шифруются при следующем сохранении
+//! или командой `encrypt-memory`.
+//!
+//! Ключ берётся из `ZIGGURAT_MEMORY_KEY` (64 hex-символа - сам ключ, иначе
+//! парольная фраза, растянутая PBKDF2), иначе из системного хранилища ключей:
+//! `secret-tool` (libsecret) на Linux, `security` (Keychain) на macOS. CLI ставит
+//! найденный ключ через [`install`], менеджеры памяти берут его при создании.
+
+use anyhow::Result;
+use std::sync::OnceLock;
+
+/// Переменная окружения с ключом или парольной фразой
+pub const KEY_ENV: &str = "ZIGGURAT_MEMORY_KEY";
+/// Запись в системном хранилище ключей: сервис и учётная запись
+pub const KEYRING_SERVICE: &str = "zikkurat-mind";
+pub const KEYRING_ACCOUNT: &str = "memory-key";
+
+/// Начало зашифрованного файла
+const MAGIC: &[u8; 8] = b"ZIGENC01";
+#[cfg(feature = "encryption")]
+const NONCE_LEN: usize = 12;
+/// Соль PBKDF2 для парольной фразы: хранить её негде, поэтому она общая
+#[cfg(feature = "encryption")]
+const PASSPHRASE_SALT: &[u8] = b"zikkurat-mind/memory-key/v1";
+#[cfg(feature = "encryption")]
+const PASSPHRASE_ITERATIONS: u32 = 200_000;
+
+/// Откуда взят ключ
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeySource {
+    Env,
+    Keyring,
+}
+
+impl std::fmt::Display for KeySource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            KeySource::Env => write!(f, "{}", KEY_ENV),
+            KeySource::Keyring => write!(f, "OS keyring ({}/{})", KEYRING_SERVICE, KEYRING_ACCOUNT),
+        }
+    }
+}
+
+/// Зашифрованный файл памяти не читается. Начинать с пустой памятью в этом случае
+/// нельзя - следующее сохранение затрёт зашифрованные данные
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DecryptError {
+    /// Ключа памяти нет
+    NoKey,
+    /// Ключ другой или файл повреждён
+    WrongKey,
+}
+
+impl std::fmt::Display for DecryptError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DecryptError::NoKey => write!(
+                f,
+                "Memory is encrypted: set {} or store the key in the OS keyring ({}/{})",
+                KEY_ENV, KEYRING_SERVICE, KEYRING_ACCOUNT
+            ),
+            DecryptError::WrongKey => write!(f, "Wrong memory key, or the file is damaged"),
+        }
+    }
+}
+
+impl std::error::Error for DecryptError {}
+
+/// 256-битный ключ AES-GCM
+#[derive(Clone, PartialEq, Eq)]
+pub struct MemoryKey([u8; 32]);
+
+impl std::fmt::Debug for MemoryKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("MemoryKey(..)")
+    }
+}
+
+impl MemoryKey {
+    /// 64 hex-символа - ключ как есть, любая другая строка - парольная фраза
+    pub fn from_secret(secret: &str) -> Result<Self> {
+        let secret = secret.trim();
+        anyhow::ensure!(!secret.is_empty(), "The memory key is empty");
+        if secret.len() == 64 && secret.chars().all(|c| c.is_ascii_hexdigit()) {
+            let mut key = [0u8; 32];
+            for (i, byte) in key.iter_mut().enumerate() {
+                *byte = u8::from_str_radix(&secret[i * 2..i * 2 + 2], 16)?;
+            }
+            return Ok(Self(key));
+        }
+        Self::from_passphrase(secret)
+    }
+
+    #[cfg(feature = "encryption")]
+    fn from_passphrase(passphrase: &str) -> Result<Self> {
+        let mut key = [0u8; 32];
+        ring::pbkdf2::derive(
+            ring::pbkdf2::PBKDF2_HMAC_SHA256,
+            std::num::NonZeroU32::new(PASSPHRASE_ITERATIONS).expect("non-zero"),
+            PASSPHRASE_SALT,
+            passphrase.as_bytes(),
+            &mut key,
+        );
+        Ok(Self(key))
+    }
+
+    #[cfg(not(feature = "encryption"))]
+    fn from_passphrase(_passphrase: &str) -> Result<Self> {
+        anyhow::bail!("Built without the encryption feature: only a 64-hex-digit key is accepted")
+    }
+
+    /// Ключ из `ZIGGURAT_MEMORY_KEY` или системного хранилища; `None` - шифрование выключено
+    pub fn resolve() -> Result<Option<(Self, KeySource)>> {
+        if let Some(secret) = std::env::var(KEY_ENV).ok().filter(|s| !s.trim().is_empty()) {
+            return Ok(Some((Self::from_secret(&secret)?, KeySource::Env)));
+        }
+        match keyring_secret() {
+            Some(secret) => Ok(Some((Self::from_secret(&secret)?, KeySource::Keyring))),
+            None => Ok(None),
+        }
+    }
+
+    /// Шифрует данные для записи на диск
+    #[cfg(feature = "encryption")]
+    pub fn seal(&self, plaintext: &[u8]) -> Result<Vec<u8>> {
+        use ring::aead::{Aad, LessSafeKey, Nonce, UnboundKey, AES_256_GCM};
+        use ring::rand::{SecureRandom, SystemRandom};
+
+        let key = LessSafeKey::new(UnboundKey::new(&AES_256_GCM, &self.0).map_err(|_| anyhow::anyhow!("Bad key"))?);
+        let mut nonce = [0u8; NONCE_LEN];
+        SystemRandom::new()
+            .fill(&mut nonce)
+            .map_err(|_| anyhow::anyhow!("No randomness for the nonce"))?;
+        let mut sealed = plaintext.to_vec();
+        key.seal_in_place_append_tag(Nonce::assume_unique_for_key(nonce), Aad::from(MAGIC), &mut sealed)
+            .map_err(|_| anyhow::anyhow!("Encryption failed"))?;
+        Ok([&MAGIC[..], &nonce, &sealed].concat())
+    }
+
+    #[cfg(not(feature = "encryption"))]
+    pub fn seal(&self, _plaintext: &[u8]) -> Result<Vec<u8>> {
+        anyhow::bail!("Built without the encryption feature")
+    }
+
+    /// Расшифровывает то, что записал [`MemoryKey::seal`]
+    #[cfg(feature = "encryption")]
+    pub fn open(&self, data: &[u8]) -> Result<Vec<u8>> {
+        use ring::aead::{Aad, LessSafeKey, Nonce, UnboundKey, AES_256_GCM};
+
+        anyhow::ensure!(is_sealed(data) && data.len() >= MAGIC.len() + NONCE_LEN, "Not an encrypted memory file");
+        let (nonce, sealed) = data[MAGIC.len()..].split_at(NONCE_LEN);
+        let key = LessSafeKey::new(UnboundKey::new(&AES_256_GCM, &self.0).map_err(|_| anyhow::anyhow!("Bad key"))?);
+        let mut buffer = sealed.to_vec();
+        let nonce = Nonce::try_assume_unique_for_key(nonce).map_err(|_| anyhow::anyhow!("Bad nonce"))?;
+        let plaintext = key
+            .open_in_place(nonce, Aad::from(MAGIC), &mut buffer)
+            .map_err(|_| DecryptError::WrongKey)?;
+        Ok(plaintext.to_vec())
+    }
+
+    #[cfg(not(feature = "encryption"))]
+    pub fn open(&self, _data: &[u8]) -> Result<Vec<u8>> {
+        anyhow::bail!("Built without the encryption feature")
+    }
+}
+
+/// Данные записаны [`MemoryKey::seal`]
+pub fn is_sealed(data: &[u8]) -> bool {
+    data.starts_with(MAGIC)
+}
+
+/// Прочитанный файл памяти: зашифрованный расшифровывается, открытый возвращается как есть
+pub fn unseal(key: Option<&MemoryKey>, data: Vec<u8>) -> Result<Vec<u8>> {
+    if !is_sealed(&data) {
+        return Ok(data);
+    }
+    match key {
+        Some(key) => key.open(&data),
+        None => Err(DecryptError::NoKey.into()),
+    }
+}
+
+/// Файл памяти для записи: с ключом - зашифрованный
+pub fn seal(key: Option<&MemoryKey>, data: impl Into<Vec<u8>>) -> Result<Vec<u8>> {
+    let data = data.into();
+    match key {
+        Some(key) => key.seal(&data),
+        None => Ok(data),
+    }
+}
+
+/// [`unseal`] для текстовых файлов
+pub fn unseal_string(key: Option<&MemoryKey>, data: Vec<u8>) -> Result<String> {
+    Ok(String::from_utf8(unseal(key, data)?)?)
+}
+
+/// Переписывает файл памяти зашифрованным (`encrypt`) или открытым: миграция хранилища.
+/// `false` - файла нет или он уже такой
+pub fn reseal_file(key: &MemoryKey, path: &std::path::Path, encrypt: bool) -> Result<bool> {
+    if !path.exists() {
+        return Ok(false);
+    }
+    let data = std::fs::read(path)?;
+    if is_sealed(&data) == encrypt {
+        return Ok(false);
+    }
+    let data = if encrypt { key.seal(&data)? } else { key.open(&data)? };
+    std::fs::write(path, data)?;
+    Ok(true)
+}
+
+/// Строка построчного журнала для записи: с ключом - зашифрованная и в hex, так что
+/// журнал по-прежнему дописывается строками, без перезаписи файла
+pub fn seal_line(key: Option<&MemoryKey>, line: &str) -> Result<String> {
+    match key {
+        Some(key) => Ok(to_hex(&key.seal(line.as_bytes())?)),
+        None => Ok(line.to_string()),
+    }
+}
+
+/// Прочитанная строка журнала: зашифрованная расшифровывается, открытая возвращается как есть
+pub fn unseal_line(key: Option<&MemoryKey>, line: &str) -> Result<String> {
+    if !is_sealed_line(line) {
+        return Ok(line.to_string());
+    }
+    let data = from_hex(line).ok_or(DecryptError::WrongKey)?;
+    unseal_string(key, data)
+}
+
+/// Строка записана [`seal_line`]
+pub fn is_sealed_line(line: &str) -> bool {
+    line.starts_with(&to_hex(MAGIC))
+}
+
+/// [`reseal_file`] для построчного журнала: каждая строка переписывается отдельно.
+/// `false` - файла нет или все строки уже такие
+pub fn reseal_lines(key: &MemoryKey, path: &std::path::Path, encrypt: bool) -> Result<bool> {
+    if !path.exists() {
+        return Ok(false);
+    }
+    let content = std::fs::read_to_string(path)?;
+    let lines: Vec<&str> = content.lines().filter(|l| !l.trim().is_empty()).collect();
+    if lines.iter().all(|l| is_sealed_line(l) == encrypt) {
+        return Ok(false);
+    }
+    let mut rewritten = String::new();
+    for line in lines {
+        let line = unseal_line(Some(key), line)?;
+        rewritten.push_str(&seal_line(encrypt.then_some(key), &line)?);
+        rewritten.push('\n');
+    }
+    std::fs::write(path, rewritten)?;
+    Ok(true)
+}
+
+fn to_hex(data: &[u8]) -> String {
+    data.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn from_hex(text: &str) -> Option<Vec<u8>> {
+    if !text.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..text.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(text.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+/// Ключ, поставленный [`install`]
+static INSTALLED: OnceLock<MemoryKey> = OnceLock::new();
+
+/// Ставит ключ для всех менеджеров памяти, создаваемых дальше в процессе.
+/// Ставится один раз; `false`, если уже стоит
+pub fn install(key: MemoryKey) -> bool {
+    INSTALLED.set(key).is_ok()
+}
+
+/// Ключ для новых менеджеров памяти: поставленный [`install`] или никакого
+pub fn installed() -> Option<MemoryKey> {
+    INSTALLED.get().cloned()
+}
+
+/// Секрет из системного хранилища ключей, если там есть запись и утилита
+fn keyring_secret() -> Option<String> {
+    let mut command = if cfg!(target_os = "macos") {
+        let mut command = std::process::Command::new("security");
+        command.args(["find-generic-password", "-s", KEYRING_SERVICE, "-a", KEYRING_ACCOUNT, "-w"]);
+        command
+    } else if cfg!(target_os = "linux") {
+        let mut command = std::process::Command::new("secret-tool");
+        command.args(["lookup", "service", KEYRING_SERVICE, "account", KEYRING_ACCOUNT]);
+        command
+    } else {
+        return None;
+    };
+    let output = command.stderr(std::process::Stdio::null()).output().ok()?;
+    let secret = String::from_utf8(output.stdout).ok()?;
+    (output.status.success() && !secret.trim().is_empty()).then(|| secret.trim().to_string())
+}
+
+#[cfg(all(test, feature = "encryption"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_seal_open_and_plaintext_passthrough() -> Result<()> {
+        let key = MemoryKey::from_secret("correct horse battery staple")?;
+        let hex = MemoryKey::from_secret(&"ab".repeat(32))?;
+        assert_eq!(hex, MemoryKey([0xab; 32]));
+
+        let sealed = seal(Some(&key), "{\"sessions\": []}")?;
+        assert!(is_sealed(&sealed) && !sealed.windows(8).any(|w| w == b"sessions"));
+        assert_ne!(sealed, seal(Some(&key), "{\"sessions\": []}")?, "the nonce is fresh each time");
+        assert_eq!(unseal_string(Some(&key), sealed.clone())?, "{\"sessions\": []}");
+
+        assert!(unseal(Some(&hex), sealed.clone()).unwrap_err().is::<DecryptError>());
+        assert!(unseal(None, sealed).unwrap_err().to_string().contains(KEY_ENV));
+        // stores written before encryption still load
+        assert_eq!(unseal(Some(&key), b"[1, 2]".to_vec())?, b"[1, 2]");
+        Ok(())
+    }
+
+    #[test]
+    fn test_sealed_lines() -> Result<()> {
+        let key = MemoryKey::from_secret("correct horse battery staple")?;
+        let line = seal_line(Some(&key), "{\"text\": \"Tom likes tea\"}")?;
+        assert!(is_sealed_line(&line) && !line.contains('\n') && !line.contains("Tom"));
+        assert_eq!(unseal_line(Some(&key), &line)?, "{\"text\": \"Tom likes tea\"}");
+        assert_eq!(unseal_line(None, "{\"text\": \"plain\"}")?, "{\"text\": \"plain\"}");
+        assert!(unseal_line(None, &line).unwrap_err().is::<DecryptError>());
+
+        // a log with plaintext lines appended before encryption is rewritten line by line
+        let path = std::env::temp_dir().join(format!("ziggurat-crypto-lines-test-{}.jsonl", std::process::id()));
+        std::fs::write(&path, format!("{{\"n\": 1}}\n{}\n", seal_line(Some(&key), "{\"n\": 2}")?))?;
+        assert!(reseal_lines(&key, &path, true)?);
+        assert!(!reseal_lines(&key, &path, true)?);
+        let content = std::fs::read_to_string(&path)?;
+        assert!(content.lines().all(is_sealed_line));
+        assert!(reseal_lines(&key, &path, false)?);
+        assert_eq!(std::fs::read_to_string(&path)?, "{\"n\": 1}\n{\"n\": 2}\n");
+        std::fs::remove_file(&path)?;
+        Ok(())
+    }
+}
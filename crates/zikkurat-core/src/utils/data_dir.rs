@@ -0,0 +1,155 @@
+//! 📂 Каталог данных
+//!
+//! Раньше `memory_data` искался рядом с `Cargo.toml` над исполняемым файлом, и у
+//! установленного бинарника память оказывалась в случайном месте. Теперь каталог
+//! выбирается так: `--data-dir`, иначе стандартный каталог данных платформы
+//! (`~/.local/share/zikkurat-mind` по XDG). Старый `memory_data` в checkout
+//! продолжает работать, пока его не перенесут командой `migrate-data`.
+
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Подкаталог приложения в каталоге данных платформы
+pub const APP_DIR: &str = "zikkurat-mind";
+/// Каталог памяти в прежней раскладке (рядом с `Cargo.toml`)
+pub const LEGACY_DIR: &str = "memory_data";
+
+/// Откуда взят каталог данных
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DataDirSource {
+    /// `--data-dir`
+    Flag,
+    /// Прежний `memory_data`, ещё не перенесённый
+    Legacy,
+    /// Каталог данных платформы
+    Platform,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DataDir {
+    pub path: PathBuf,
+    pub source: DataDirSource,
+}
+
+/// Каталог данных платформы, если он у неё есть
+pub fn platform_dir() -> Option<PathBuf> {
+    dirs::data_dir().map(|dir| dir.join(APP_DIR))
+}
+
+/// Выбирает каталог данных; `legacy` - путь прежнего `memory_data`
+pub fn resolve(flag: Option<&Path>, legacy: &Path) -> DataDir {
+    resolve_with(flag, legacy, platform_dir())
+}
+
+fn resolve_with(flag: Option<&Path>, legacy: &Path, platform: Option<PathBuf>) -> DataDir {
+    if let Some(path) = flag {
+        return DataDir {
+            path: path.to_path_buf(),
+            source: DataDirSource::Flag,
+        };
+    }
+    match platform {
+        // непереехавшая память остаётся на месте, пока её не перенесут
+        Some(path) if path.exists() || !legacy.exists() => DataDir {
+            path,
+            source: DataDirSource::Platform,
+        },
+        _ => DataDir {
+            path: legacy.to_path_buf(),
+            source: DataDirSource::Legacy,
+        },
+    }
+}
+
+/// Число файлов и их суммарный размер - для сверки до и после переноса
+pub fn tree_size(dir: &Path) -> Result<(usize, u64)> {
+    let mut total = (0, 0);
+    for entry in fs::read_dir(dir).with_context(|| format!("Failed to read {:?}", dir))? {
+        let entry = entry?;
+        let meta = entry.metadata()?;
+        if meta.is_dir() {
+            let (files, bytes) = tree_size(&entry.path())?;
+            total.0 += files;
+            total.1 += bytes;
+        } else {
+            total.0 += 1;
+            total.1 += meta.len();
+        }
+    }
+    Ok(total)
+}
+
+/// Переносит каталог целиком: rename, а между файловыми системами - копия и удаление
+/// исходного. `to` не должен существовать или должен быть пустым
+pub fn move_dir(from: &Path, to: &Path) -> Result<()> {
+    if to.exists() {
+        let empty = fs::read_dir(to)?.next().is_none();
+        anyhow::ensure!(empty, "{:?} already exists and is not empty", to);
+        fs::remove_dir(to)?;
+    }
+    if let Some(parent) = to.parent() {
+        fs::create_dir_all(parent).with_context(|| format!("Failed to create {:?}", parent))?;
+    }
+    if fs::rename(from, to).is_ok() {
+        return Ok(());
+    }
+
+    if let Err(e) = copy_dir(from, to) {
+        let _ = fs::remove_dir_all(to);
+        return Err(e.context(format!("Failed to copy {:?} to {:?}", from, to)));
+    }
+    let copied = tree_size(to)?;
+    let source = tree_size(from)?;
+    if copied != source {
+        let _ = fs::remove_dir_all(to);
+        anyhow::bail!("Copy of {:?} is incomplete: {:?} vs {:?} (files, bytes)", from, copied, source);
+    }
+    fs::remove_dir_all(from).with_context(|| format!("Copied, but failed to remove {:?}", from))
+}
+
+fn copy_dir(from: &Path, to: &Path) -> Result<()> {
+    fs::create_dir_all(to)?;
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        let target = to.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            copy_dir(&entry.path(), &target)?;
+        } else {
+            fs::copy(entry.path(), &target)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_and_move() -> Result<()> {
+        let root = std::env::temp_dir().join(format!("ziggurat-data-dir-{}", std::process::id()));
+        let legacy = root.join("checkout/memory_data");
+        let platform = root.join("share/zikkurat-mind");
+        let flag = root.join("custom");
+
+        let resolve = |flag: Option<&Path>| resolve_with(flag, &legacy, Some(platform.clone()));
+        assert_eq!(resolve(None).source, DataDirSource::Platform);
+        assert_eq!(resolve(Some(&flag)).path, flag);
+
+        fs::create_dir_all(legacy.join("semantic"))?;
+        fs::write(legacy.join("semantic/semantic_memory.json"), "[]")?;
+        assert_eq!(resolve(None), DataDir { path: legacy.clone(), source: DataDirSource::Legacy });
+
+        move_dir(&legacy, &platform)?;
+        assert!(!legacy.exists());
+        assert_eq!(tree_size(&platform)?, (1, 2));
+        assert_eq!(resolve(None).source, DataDirSource::Platform);
+
+        fs::create_dir_all(&legacy)?;
+        fs::write(root.join("custom"), "x")?;
+        assert!(move_dir(&legacy, &root.join("custom")).is_err());
+        let _ = fs::remove_dir_all(&root);
+        Ok(())
+    }
+}
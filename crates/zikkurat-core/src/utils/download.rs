@@ -0,0 +1,229 @@
+//! ⬇️ Загрузка моделей с HF Hub
+//!
+//! Все пути загрузки моделей идут через [`ModelDownloader`]: оборванный файл
+//! докачивается с места обрыва (`.part` в кэше hf-hub), сетевые ошибки повторяются
+//! с паузой, прогресс показывается полосой, а скачанный файл сверяется с
+//! контрольной суммой. Для закрытых сетей huggingface.co заменяется зеркалом
+//! (`--hf-mirror` или переменная `HF_ENDPOINT`).
+//!
+//! Контрольная сумма берётся из имени блоба в кэше: для LFS-файлов (веса) это
+//! sha256 содержимого, для обычных (json) - git sha1 блоба.
+
+use anyhow::{Context, Result};
+use hf_hub::api::sync::{ApiBuilder, ApiRepo};
+use hf_hub::{Repo, RepoType};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Параметры загрузки
+#[derive(Debug, Clone, PartialEq)]
+pub struct DownloadConfig {
+    /// Адрес зеркала вместо `https://huggingface.co`
+    pub mirror: Option<String>,
+    /// Сколько раз повторять загрузку после сетевой ошибки
+    pub retries: usize,
+    pub progress: bool,
+}
+
+impl Default for DownloadConfig {
+    fn default() -> Self {
+        Self {
+            mirror: None,
+            retries: 5,
+            progress: true,
+        }
+    }
+}
+
+/// Результат сверки файла с контрольной суммой
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Checksum {
+    Verified,
+    /// Имя блоба не похоже на хэш (например, файл скопирован в кэш вручную)
+    Unknown,
+    Mismatch { expected: String, actual: String },
+}
+
+/// Файлы одного репозитория модели
+pub struct ModelDownloader {
+    repo: ApiRepo,
+    model_id: String,
+    endpoint: String,
+    retries: usize,
+}
+
+impl ModelDownloader {
+    pub fn new(model_id: &str, revision: String, config: &DownloadConfig) -> Result<Self> {
+        // --hf-mirror важнее HF_ENDPOINT; кэш - из HF_HOME
+        let endpoint = config
+            .mirror
+            .clone()
+            .or_else(|| std::env::var("HF_ENDPOINT").ok())
+            .unwrap_or_else(|| "https://huggingface.co".to_string())
+            .trim_end_matches('/')
+            .to_string();
+        let api = ApiBuilder::from_env()
+            .with_endpoint(endpoint.clone())
+            .with_retries(config.retries)
+            .with_progress(config.progress)
+            .build()?;
+        let repo = api.repo(Repo::with_revision(model_id.to_string(), RepoType::Model, revision));
+        Ok(Self {
+            repo,
+            model_id: model_id.to_string(),
+            endpoint,
+            retries: config.retries,
+        })
+    }
+
+    /// Файл из кэша или с хаба, проверенный по контрольной сумме.
+    /// Испорченный файл удаляется и скачивается ещё раз
+    pub fn get(&self, filename: &str) -> Result<PathBuf> {
+        let path = self.fetch(filename)?;
+        match verify_blob(&path)? {
+            Checksum::Verified | Checksum::Unknown => return Ok(path),
+            Checksum::Mismatch { expected, actual } => {
+                eprintln!(
+                    "⚠️  {} is corrupted (sha {} instead of {}), downloading again",
+                    filename, actual, expected
+                );
+                discard(&path)?;
+            }
+        }
+
+        let path = self.fetch(filename)?;
+        match verify_blob(&path)? {
+            Checksum::Mismatch { expected, actual } => {
+                discard(&path)?;
+                anyhow::bail!("Checksum mismatch for {}: expected {}, got {}", filename, expected, actual)
+            }
+            _ => Ok(path),
+        }
+    }
+
+    /// Веса из индекса `model.safetensors.index.json`
+    pub fn safetensors(&self, index_file: &str) -> Result<Vec<PathBuf>> {
+        let index_path = self.get(index_file)?;
+        let index: serde_json::Value = serde_json::from_reader(std::fs::File::open(&index_path)?)?;
+        let weight_map = index
+            .get("weight_map")
+            .and_then(|v| v.as_object())
+            .ok_or_else(|| anyhow::anyhow!("no 'weight_map' object in {}", index_file))?;
+
+        let mut files: Vec<&str> = weight_map.values().filter_map(|v| v.as_str()).collect();
+        files.sort_unstable();
+        files.dedup();
+        files.into_iter().map(|f| self.get(f)).collect()
+    }
+
+    /// `repo.get` с повторами: hf-hub сам докачивает оборванное тело файла, но не
+    /// повторяет запрос метаданных, который тоже падает на плохой сети
+    fn fetch(&self, filename: &str) -> Result<PathBuf> {
+        let mut attempt = 0;
+        loop {
+            match self.repo.get(filename) {
+                Ok(path) => return Ok(path),
+                Err(e) if attempt < self.retries => {
+                    attempt += 1;
+                    let wait = Duration::from_millis((500u64 << attempt.min(5)).min(10_000));
+                    eprintln!(
+                        "⚠️  Download of {} failed ({}), retry {}/{} in {:.1}s",
+                        filename,
+                        e,
+                        attempt,
+                        self.retries,
+                        wait.as_secs_f32()
+                    );
+                    std::thread::sleep(wait);
+                }
+                Err(e) => {
+                    return Err(e).with_context(|| {
+                        format!(
+                            "Failed to download {} of {} from {} (set --hf-mirror or HF_ENDPOINT behind a restricted network)",
+                            filename, self.model_id, self.endpoint
+                        )
+                    })
+                }
+            }
+        }
+    }
+}
+
+/// Сверяет файл из кэша hf-hub с хэшем в имени его блоба
+pub fn verify_blob(path: &Path) -> Result<Checksum> {
+    let blob = std::fs::canonicalize(path)?;
+    let expected = blob
+        .file_name()
+        .and_then(|n| n.to_str())
+        .unwrap_or_default()
+        .to_lowercase();
+    if !expected.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Ok(Checksum::Unknown);
+    }
+
+    let mut file = std::fs::File::open(&blob)?;
+    let mut context = match expected.len() {
+        64 => ring::digest::Context::new(&ring::digest::SHA256),
+        40 => {
+            let mut context = ring::digest::Context::new(&ring::digest::SHA1_FOR_LEGACY_USE_ONLY);
+            context.update(format!("blob {}\0", file.metadata()?.len()).as_bytes());
+            context
+        }
+        _ => return Ok(Checksum::Unknown),
+    };
+    let mut buffer = vec![0u8; 1 << 20];
+    loop {
+        let n = file.read(&mut buffer)?;
+        if n == 0 {
+            break;
+        }
+        context.update(&buffer[..n]);
+    }
+
+    let actual: String = context.finish().as_ref().iter().map(|b| format!("{:02x}", b)).collect();
+    Ok(if actual == expected {
+        Checksum::Verified
+    } else {
+        Checksum::Mismatch { expected, actual }
+    })
+}
+
+/// Удаляет блоб и ссылку на него, чтобы следующий `get` скачал файл заново
+fn discard(path: &Path) -> Result<()> {
+    let blob = std::fs::canonicalize(path)?;
+    std::fs::remove_file(&blob)?;
+    if path != blob {
+        std::fs::remove_file(path)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_verify_blob() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("ziggurat-blob-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir)?;
+        let write = |name: &str| -> Result<PathBuf> {
+            let path = dir.join(name);
+            std::fs::write(&path, "hello")?;
+            Ok(path)
+        };
+
+        let lfs = write("2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824")?;
+        let git = write("b6fc4c620b67d95f953a5c1c1230aaab5db5a1b0")?;
+        let broken = write("0000000000000000000000000000000000000000")?;
+        let manual = write("config.json")?;
+        let results = [verify_blob(&lfs)?, verify_blob(&git)?, verify_blob(&broken)?, verify_blob(&manual)?];
+        let _ = std::fs::remove_dir_all(&dir);
+
+        assert_eq!(results[0], Checksum::Verified);
+        assert_eq!(results[1], Checksum::Verified);
+        assert!(matches!(results[2], Checksum::Mismatch { ref actual, .. } if actual.starts_with("b6fc4c")));
+        assert_eq!(results[3], Checksum::Unknown);
+        Ok(())
+    }
+}
@@ -0,0 +1,192 @@
+//! JSON из ответов LLM
+//!
+//! Модель оборачивает JSON в ```json, дописывает пояснения до и после, оставляет
+//! висячие запятые и обрывается на лимите токенов. Здесь ищется первое значение
+//! (объект или массив), которое удаётся разобрать: скобки сопоставляются с учётом
+//! строк, висячие запятые убираются, оборванный хвост отрезается до последнего
+//! целого элемента и закрывается.
+
+use anyhow::Result;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+
+/// Сколько символов ответа показывать в ошибке
+const PREVIEW_CHARS: usize = 120;
+
+/// Первое значение JSON в ответе, которое десериализуется в `T`
+pub fn parse<T: DeserializeOwned>(text: &str) -> Result<T> {
+    let mut mismatch = None;
+    for (value, _) in values(text) {
+        match serde_json::from_value(value) {
+            Ok(parsed) => return Ok(parsed),
+            Err(e) => {
+                mismatch.get_or_insert(e);
+            }
+        }
+    }
+    Err(error::<T>(text, mismatch))
+}
+
+/// Список `T`: массив JSON, либо объекты подряд без обрамляющих скобок
+pub fn parse_list<T: DeserializeOwned>(text: &str) -> Result<Vec<T>> {
+    let mut mismatch = None;
+    let mut items = Vec::new();
+    let mut taken_until = 0;
+
+    for (value, (start, end)) in values(text) {
+        if value.is_array() {
+            match serde_json::from_value::<Vec<T>>(value.clone()) {
+                Ok(list) if items.is_empty() => return Ok(list),
+                Ok(_) => continue,
+                Err(e) => {
+                    mismatch.get_or_insert(e);
+                }
+            }
+        }
+        if start < taken_until {
+            continue;
+        }
+        match serde_json::from_value::<T>(value) {
+            Ok(item) => {
+                items.push(item);
+                taken_until = end;
+            }
+            Err(e) => {
+                mismatch.get_or_insert(e);
+            }
+        }
+    }
+
+    if items.is_empty() {
+        return Err(error::<Vec<T>>(text, mismatch));
+    }
+    Ok(items)
+}
+
+/// Все значения, которые удалось разобрать, с их границами в `text`, по порядку начала
+fn values(text: &str) -> impl Iterator<Item = (Value, (usize, usize))> + '_ {
+    text.char_indices()
+        .filter(|&(_, c)| c == '{' || c == '[')
+        .filter_map(move |(start, _)| {
+            let (json, end) = balanced(text, start)?;
+            let value = serde_json::from_str(&json).ok()?;
+            Some((value, (start, end)))
+        })
+}
+
+/// Сбалансированный фрагмент от `start`: исправленный текст и байтовый конец в `text`
+fn balanced(text: &str, start: usize) -> Option<(String, usize)> {
+    let mut closers: Vec<char> = Vec::new();
+    let mut out = String::new();
+    let mut in_string = false;
+    let mut escaped = false;
+    // последняя точка, после которой оборванное значение можно закрыть
+    let mut safe: Option<(usize, Vec<char>)> = None;
+
+    for (offset, c) in text[start..].char_indices() {
+        if in_string {
+            out.push(c);
+            if escaped {
+                escaped = false;
+            } else if c == '\\' {
+                escaped = true;
+            } else if c == '"' {
+                in_string = false;
+                if closers.last() == Some(&']') {
+                    safe = Some((out.len(), closers.clone()));
+                }
+            }
+            continue;
+        }
+
+        match c {
+            '"' => {
+                in_string = true;
+                out.push(c);
+            }
+            '{' => {
+                closers.push('}');
+                out.push(c);
+            }
+            '[' => {
+                closers.push(']');
+                out.push(c);
+            }
+            '}' | ']' => {
+                if closers.pop() != Some(c) {
+                    return None;
+                }
+                drop_trailing_comma(&mut out);
+                out.push(c);
+                if closers.is_empty() {
+                    return Some((out, start + offset + c.len_utf8()));
+                }
+                safe = Some((out.len(), closers.clone()));
+            }
+            _ => out.push(c),
+        }
+    }
+
+    // ответ оборвался: закрываем после последнего целого элемента
+    let (len, open) = safe?;
+    out.truncate(len);
+    drop_trailing_comma(&mut out);
+    out.extend(open.iter().rev());
+    Some((out, text.len()))
+}
+
+fn drop_trailing_comma(out: &mut String) {
+    out.truncate(out.trim_end().len());
+    if out.ends_with(',') {
+        out.pop();
+    }
+}
+
+fn error<T>(text: &str, mismatch: Option<serde_json::Error>) -> anyhow::Error {
+    let preview: String = text.trim().chars().take(PREVIEW_CHARS).collect();
+    let expected = std::any::type_name::<T>();
+    match mismatch {
+        Some(e) => anyhow::anyhow!("LLM output has JSON, but not {}: {} (output: {:?})", expected, e, preview),
+        None => anyhow::anyhow!("No JSON value in LLM output (expected {}): {:?}", expected, preview),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Fact {
+        text: String,
+        confidence: f32,
+    }
+
+    #[test]
+    fn test_parse_noisy_output() {
+        let fenced = "Sure! Here you go:\n```json\n[\"rust\", \"память\",]\n```\nHope it helps [1].";
+        assert_eq!(parse::<Vec<String>>(fenced).unwrap(), ["rust", "память"]);
+
+        // скобки внутри строк не сбивают сопоставление
+        let nested = r#"{"text": "use [brackets] and {braces}", "confidence": 0.9, }"#;
+        assert_eq!(
+            parse::<Fact>(nested).unwrap(),
+            Fact { text: "use [brackets] and {braces}".into(), confidence: 0.9 }
+        );
+
+        // оборвано на лимите токенов
+        let truncated = r#"[{"text": "likes tea", "confidence": 0.8}, {"text": "lives in Ka"#;
+        assert_eq!(parse_list::<Fact>(truncated).unwrap().len(), 1);
+        assert_eq!(parse::<Vec<String>>(r#"["a", "b", "c"#).unwrap(), ["a", "b"]);
+
+        // объекты подряд без массива
+        let loose = r#"{"text": "a", "confidence": 0.5}
+{"text": "b", "confidence": 0.6}"#;
+        assert_eq!(parse_list::<Fact>(loose).unwrap().len(), 2);
+
+        let err = parse::<Fact>("no json here").unwrap_err().to_string();
+        assert!(err.contains("No JSON value"), "{}", err);
+        let err = parse::<Fact>(r#"{"name": 1}"#).unwrap_err().to_string();
+        assert!(err.contains("missing field `text`"), "{}", err);
+    }
+}
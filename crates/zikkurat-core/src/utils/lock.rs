@@ -0,0 +1,159 @@
+//! 🔒 Блокировка каталога памяти между процессами
+//!
+//! Два экземпляра на одной `memory_data` молча перетирают файлы друг друга. Первый
+//! процесс берёт эксклюзивную advisory-блокировку `.lock` в каталоге памяти на всё
+//! время работы, второй получает понятную ошибку - или запускается с `--read-only`
+//! и только читает. Каждое сохранение и загрузка файлов памяти идут под
+//! `.io.lock` каталога ([`io_guard`]), чтобы читатель не застал файл недописанным.
+
+use anyhow::{Context, Result};
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Seek, Write};
+use std::path::{Path, PathBuf};
+
+/// Блокировка владельца каталога
+pub const LOCK_FILE: &str = ".lock";
+/// Блокировка на время одной записи или чтения
+const IO_LOCK_FILE: &str = ".io.lock";
+
+/// Право на запись в каталог памяти; отпускается при drop (или при завершении процесса)
+#[derive(Debug)]
+pub struct MemoryLock {
+    file: File,
+    path: PathBuf,
+}
+
+impl MemoryLock {
+    /// Берёт блокировку без ожидания. Если каталог занят, ошибка называет процесс-владельца
+    pub fn acquire(dir: &Path) -> Result<Self> {
+        std::fs::create_dir_all(dir).with_context(|| format!("Failed to create {:?}", dir))?;
+        let path = dir.join(LOCK_FILE);
+        // без truncate: до получения блокировки файл принадлежит владельцу
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&path)
+            .with_context(|| format!("Failed to open lock file {:?}", path))?;
+
+        if let Err(e) = lock(&file, true, false) {
+            if e.kind() != io::ErrorKind::WouldBlock && !is_contended(&e) {
+                return Err(e).with_context(|| format!("Failed to lock {:?}", path));
+            }
+            let mut holder = String::new();
+            let _ = file.read_to_string(&mut holder);
+            let holder = holder.trim();
+            anyhow::bail!(
+                "Memory directory {:?} is in use by another instance ({}). \
+                 Stop it, or start this one with --read-only to share the memory without writing",
+                dir,
+                if holder.is_empty() { "unknown process" } else { holder }
+            );
+        }
+
+        file.set_len(0)?;
+        file.rewind()?;
+        writeln!(file, "pid {} since {}", std::process::id(), chrono::Utc::now().format("%Y-%m-%d %H:%M:%S UTC"))?;
+        file.flush()?;
+        Ok(Self { file, path })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for MemoryLock {
+    fn drop(&mut self) {
+        let _ = self.file.set_len(0);
+        let _ = unlock(&self.file);
+    }
+}
+
+/// Блокировка `.io.lock`, снимается при drop
+#[derive(Debug)]
+pub struct IoGuard(File);
+
+impl Drop for IoGuard {
+    fn drop(&mut self) {
+        let _ = unlock(&self.0);
+    }
+}
+
+/// Ждёт блокировку каталога на одну операцию: `exclusive` для записи, общую для чтения
+pub fn io_guard(dir: &Path, exclusive: bool) -> Result<IoGuard> {
+    let path = dir.join(IO_LOCK_FILE);
+    let file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(&path)
+        .with_context(|| format!("Failed to open lock file {:?}", path))?;
+    lock(&file, exclusive, true).with_context(|| format!("Failed to lock {:?}", path))?;
+    Ok(IoGuard(file))
+}
+
+// методы через FileExt::, а не file.lock_*(): у std::fs::File есть одноимённые
+#[cfg(not(target_arch = "wasm32"))]
+fn lock(file: &File, exclusive: bool, wait: bool) -> io::Result<()> {
+    match (exclusive, wait) {
+        (true, true) => fs2::FileExt::lock_exclusive(file),
+        (true, false) => fs2::FileExt::try_lock_exclusive(file),
+        (false, true) => fs2::FileExt::lock_shared(file),
+        (false, false) => fs2::FileExt::try_lock_shared(file),
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn unlock(file: &File) -> io::Result<()> {
+    fs2::FileExt::unlock(file)
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn is_contended(e: &io::Error) -> bool {
+    e.raw_os_error() == fs2::lock_contended_error().raw_os_error()
+}
+
+// в wasm32 память открывает один процесс
+#[cfg(target_arch = "wasm32")]
+fn lock(_file: &File, _exclusive: bool, _wait: bool) -> io::Result<()> {
+    Ok(())
+}
+
+#[cfg(target_arch = "wasm32")]
+fn unlock(_file: &File) -> io::Result<()> {
+    Ok(())
+}
+
+#[cfg(target_arch = "wasm32")]
+fn is_contended(_e: &io::Error) -> bool {
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_memory_lock_is_exclusive() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("ziggurat-lock-test-{}", uuid::Uuid::new_v4()));
+
+        let lock = MemoryLock::acquire(&dir)?;
+        let err = MemoryLock::acquire(&dir).unwrap_err().to_string();
+        assert!(err.contains("--read-only"), "{}", err);
+        assert!(err.contains(&format!("pid {}", std::process::id())), "{}", err);
+
+        // читатели делят .io.lock между собой
+        let reader = io_guard(&dir, false)?;
+        let second_reader = io_guard(&dir, false)?;
+        drop((reader, second_reader));
+        drop(io_guard(&dir, true)?);
+
+        drop(lock);
+        assert!(MemoryLock::acquire(&dir).is_ok());
+        let _ = std::fs::remove_dir_all(&dir);
+        Ok(())
+    }
+}
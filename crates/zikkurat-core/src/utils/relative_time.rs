@@ -0,0 +1,84 @@
+//! Относительное время для промпта
+//!
+//! Воспоминания без дат модель называет «недавними», даже если им полгода.
+//! Рядом с эпизодами и концептами в промпт идёт «3 weeks ago»; «сегодня» и
+//! «вчера» считаются по календарю пользователя (его смещению от UTC).
+
+use anyhow::Result;
+use chrono::{DateTime, FixedOffset, Utc};
+
+/// «3 weeks ago» для момента `then` относительно `now` в часовом поясе `offset`
+pub fn humanize(then: DateTime<Utc>, now: DateTime<Utc>, offset: FixedOffset) -> String {
+    let elapsed = now.signed_duration_since(then);
+    if elapsed.num_seconds() < 60 {
+        return "just now".to_string();
+    }
+    if elapsed.num_minutes() < 60 {
+        return ago(elapsed.num_minutes(), "minute");
+    }
+
+    let days = (now.with_timezone(&offset).date_naive() - then.with_timezone(&offset).date_naive()).num_days();
+    match days {
+        0 => ago(elapsed.num_hours(), "hour"),
+        1 => "yesterday".to_string(),
+        2..=6 => ago(days, "day"),
+        7..=34 => ago(days / 7, "week"),
+        35..=364 => ago((days / 30).max(1), "month"),
+        _ => ago(days / 365, "year"),
+    }
+}
+
+fn ago(n: i64, unit: &str) -> String {
+    match n {
+        1 => format!("{} {} ago", if unit == "hour" { "an" } else { "a" }, unit),
+        n => format!("{} {}s ago", n, unit),
+    }
+}
+
+/// Смещение от UTC: `+03:00`, `-0530`, `+3` или `UTC`
+pub fn parse_utc_offset(s: &str) -> Result<FixedOffset> {
+    let s = s.trim();
+    if s.eq_ignore_ascii_case("utc") || s.eq_ignore_ascii_case("z") {
+        return Ok(FixedOffset::east_opt(0).expect("zero offset"));
+    }
+    let (sign, rest) = match s.as_bytes().first() {
+        Some(b'+') => (1, &s[1..]),
+        Some(b'-') => (-1, &s[1..]),
+        _ => anyhow::bail!("Invalid UTC offset '{}' (expected e.g. +03:00 or UTC)", s),
+    };
+    let digits: String = rest.chars().filter(|c| *c != ':').collect();
+    let (hours, minutes) = match digits.len() {
+        1 | 2 => (digits.as_str(), "0"),
+        4 => digits.split_at(2),
+        _ => anyhow::bail!("Invalid UTC offset '{}' (expected e.g. +03:00 or UTC)", s),
+    };
+    let seconds = hours.parse::<i32>()? * 3600 + minutes.parse::<i32>()? * 60;
+    FixedOffset::east_opt(sign * seconds).ok_or_else(|| anyhow::anyhow!("UTC offset '{}' is out of range", s))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Duration, TimeZone};
+
+    #[test]
+    fn test_humanize() {
+        let utc = FixedOffset::east_opt(0).unwrap();
+        let now = Utc.with_ymd_and_hms(2024, 6, 1, 10, 0, 0).unwrap();
+        let at = |d: Duration| humanize(now - d, now, utc);
+        assert_eq!(at(Duration::seconds(20)), "just now");
+        assert_eq!(at(Duration::minutes(5)), "5 minutes ago");
+        assert_eq!(at(Duration::hours(1)), "an hour ago");
+        assert_eq!(at(Duration::hours(11)), "yesterday");
+        assert_eq!(at(Duration::days(3)), "3 days ago");
+        assert_eq!(at(Duration::days(21)), "3 weeks ago");
+        assert_eq!(at(Duration::days(40)), "a month ago");
+        assert_eq!(at(Duration::days(800)), "2 years ago");
+
+        // 23:00 UTC накануне - это уже сегодня в UTC+3
+        let msk = parse_utc_offset("+03:00").unwrap();
+        assert_eq!(humanize(now - Duration::hours(11), now, msk), "11 hours ago");
+        assert_eq!(parse_utc_offset("-0530").unwrap().local_minus_utc(), -(5 * 3600 + 30 * 60));
+        assert!(parse_utc_offset("Moscow").is_err());
+    }
+}
@@ -0,0 +1,319 @@
+//! Chat templates of instruction-tuned models
+//!
+//! Prompts used to be written in Mistral's `<s>[INST] ... [/INST]` format
+//! everywhere. Fine-tunes of the same architecture ship other formats
+//! (OpenHermes speaks ChatML, Llama-3 and Gemma derivatives keep their
+//! headers), and a model prompted in the wrong one answers poorly or never
+//! emits its stop token. [`ChatTemplate`] renders a list of [`ChatMessage`]s
+//! in the model's own format. It is picked with `--chat-template` or detected
+//! from `tokenizer_config.json` and the tokenizer vocabulary.
+
+use anyhow::{Context, Result};
+use std::path::Path;
+use std::str::FromStr;
+
+/// Who says a message
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChatRole {
+    System,
+    User,
+    Assistant,
+}
+
+/// One message of a conversation
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChatMessage {
+    pub role: ChatRole,
+    pub content: String,
+}
+
+impl ChatMessage {
+    pub fn system(content: impl Into<String>) -> Self {
+        Self { role: ChatRole::System, content: content.into() }
+    }
+
+    pub fn user(content: impl Into<String>) -> Self {
+        Self { role: ChatRole::User, content: content.into() }
+    }
+
+    pub fn assistant(content: impl Into<String>) -> Self {
+        Self { role: ChatRole::Assistant, content: content.into() }
+    }
+}
+
+/// Prompt format of the loaded model
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ChatTemplate {
+    /// `<s>[INST] ... [/INST]`
+    #[default]
+    Mistral,
+    /// `<|start_header_id|>user<|end_header_id|> ... <|eot_id|>`
+    Llama3,
+    /// `<|im_start|>user ... <|im_end|>`
+    ChatMl,
+    /// `<start_of_turn>user ... <end_of_turn>`
+    Gemma,
+}
+
+impl ChatTemplate {
+    pub const ALL: [ChatTemplate; 4] =
+        [ChatTemplate::Mistral, ChatTemplate::Llama3, ChatTemplate::ChatMl, ChatTemplate::Gemma];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ChatTemplate::Mistral => "mistral",
+            ChatTemplate::Llama3 => "llama3",
+            ChatTemplate::ChatMl => "chatml",
+            ChatTemplate::Gemma => "gemma",
+        }
+    }
+
+    /// Token that ends the assistant's turn
+    pub fn stop_token(self) -> &'static str {
+        match self {
+            ChatTemplate::Mistral => "</s>",
+            ChatTemplate::Llama3 => "<|eot_id|>",
+            ChatTemplate::ChatMl => "<|im_end|>",
+            ChatTemplate::Gemma => "<end_of_turn>",
+        }
+    }
+
+    /// Markers around a user turn
+    fn user_turn(self) -> (&'static str, &'static str) {
+        match self {
+            ChatTemplate::Mistral => ("[INST]", "[/INST]"),
+            ChatTemplate::Llama3 => ("<|start_header_id|>user<|end_header_id|>", "<|eot_id|>"),
+            ChatTemplate::ChatMl => ("<|im_start|>user", "<|im_end|>"),
+            ChatTemplate::Gemma => ("<start_of_turn>user", "<end_of_turn>"),
+        }
+    }
+
+    /// Renders the conversation. Unless it ends with the assistant, the prompt
+    /// ends where the assistant's answer starts
+    pub fn render(self, messages: &[ChatMessage]) -> String {
+        let mut prompt = String::new();
+        let generation = messages.last().is_none_or(|m| m.role != ChatRole::Assistant);
+        match self {
+            ChatTemplate::Mistral | ChatTemplate::Gemma => {
+                // neither has a system role: it goes in front of the first user turn
+                let mut system: Option<&str> = None;
+                let (user, end_user, assistant, end_assistant) = if self == ChatTemplate::Mistral {
+                    ("[INST] ", " [/INST]", "", "</s>")
+                } else {
+                    ("<start_of_turn>user\n", "<end_of_turn>\n", "<start_of_turn>model\n", "<end_of_turn>\n")
+                };
+                prompt.push_str(if self == ChatTemplate::Mistral { "<s>" } else { "<bos>" });
+                for message in messages {
+                    match message.role {
+                        ChatRole::System => system = Some(&message.content),
+                        ChatRole::User => {
+                            prompt.push_str(user);
+                            if let Some(system) = system.take() {
+                                prompt.push_str(system);
+                                prompt.push_str("\n\n");
+                            }
+                            prompt.push_str(&message.content);
+                            prompt.push_str(end_user);
+                        }
+                        ChatRole::Assistant => {
+                            prompt.push_str(assistant);
+                            prompt.push_str(&message.content);
+                            prompt.push_str(end_assistant);
+                        }
+                    }
+                }
+                if generation && self == ChatTemplate::Gemma {
+                    prompt.push_str("<start_of_turn>model\n");
+                }
+            }
+            ChatTemplate::Llama3 => {
+                prompt.push_str("<|begin_of_text|>");
+                for message in messages {
+                    prompt.push_str(&format!(
+                        "<|start_header_id|>{}<|end_header_id|>\n\n{}<|eot_id|>",
+                        role_name(message.role),
+                        message.content
+                    ));
+                }
+                if generation {
+                    prompt.push_str("<|start_header_id|>assistant<|end_header_id|>\n\n");
+                }
+            }
+            ChatTemplate::ChatMl => {
+                for message in messages {
+                    prompt.push_str(&format!("<|im_start|>{}\n{}<|im_end|>\n", role_name(message.role), message.content));
+                }
+                if generation {
+                    prompt.push_str("<|im_start|>assistant\n");
+                }
+            }
+        }
+        prompt
+    }
+
+    /// Prompt with a single user message
+    pub fn instruction(self, text: &str) -> String {
+        self.render(&[ChatMessage::user(text)])
+    }
+
+    /// Re-renders a prompt written in the Mistral format, as the memory
+    /// modules of `zikkurat-core` write theirs
+    pub fn adapt(self, prompt: &str) -> String {
+        if self == ChatTemplate::Mistral {
+            return prompt.to_string();
+        }
+        self.render(&parse_mistral(prompt))
+    }
+
+    /// Text of the last user turn in any of the templates, the whole prompt
+    /// without one
+    pub fn last_user_turn(prompt: &str) -> &str {
+        let last = Self::ALL
+            .into_iter()
+            .filter_map(|template| {
+                let (open, close) = template.user_turn();
+                prompt.rfind(open).map(|start| (start + open.len(), close))
+            })
+            .max_by_key(|(start, _)| *start);
+        let Some((start, close)) = last else {
+            return prompt.trim();
+        };
+        let rest = &prompt[start..];
+        rest.find(close).map_or(rest, |end| &rest[..end]).trim()
+    }
+
+    /// Template whose markers appear in a Jinja `chat_template`
+    pub fn detect(chat_template: &str) -> Option<Self> {
+        [
+            ("<|start_header_id|>", ChatTemplate::Llama3),
+            ("<|im_start|>", ChatTemplate::ChatMl),
+            ("<start_of_turn>", ChatTemplate::Gemma),
+            ("[INST]", ChatTemplate::Mistral),
+        ]
+        .into_iter()
+        .find_map(|(marker, template)| chat_template.contains(marker).then_some(template))
+    }
+
+    /// Template from the `chat_template` of a `tokenizer_config.json`: either
+    /// a string or a list of named templates
+    pub fn from_tokenizer_config(path: &Path) -> Result<Option<Self>> {
+        let content = std::fs::read_to_string(path).with_context(|| format!("Failed to read {:?}", path))?;
+        let config: serde_json::Value =
+            serde_json::from_str(&content).with_context(|| format!("Failed to parse {:?}", path))?;
+        let detected = match config.get("chat_template") {
+            Some(serde_json::Value::String(template)) => Self::detect(template),
+            Some(serde_json::Value::Array(templates)) => templates
+                .iter()
+                .filter_map(|t| t.get("template").and_then(|t| t.as_str()))
+                .find_map(Self::detect),
+            _ => None,
+        };
+        Ok(detected)
+    }
+
+    /// Template from the special tokens of the vocabulary
+    pub fn from_vocab(has_token: impl Fn(&str) -> bool) -> Option<Self> {
+        [
+            ("<|eot_id|>", ChatTemplate::Llama3),
+            ("<|im_start|>", ChatTemplate::ChatMl),
+            ("<start_of_turn>", ChatTemplate::Gemma),
+        ]
+        .into_iter()
+        .find_map(|(token, template)| has_token(token).then_some(template))
+    }
+}
+
+impl std::fmt::Display for ChatTemplate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for ChatTemplate {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "mistral" => Ok(ChatTemplate::Mistral),
+            "llama3" | "llama-3" => Ok(ChatTemplate::Llama3),
+            "chatml" => Ok(ChatTemplate::ChatMl),
+            "gemma" => Ok(ChatTemplate::Gemma),
+            _ => anyhow::bail!("Unknown chat template '{}': expected mistral, llama3, chatml or gemma", s),
+        }
+    }
+}
+
+fn role_name(role: ChatRole) -> &'static str {
+    match role {
+        ChatRole::System => "system",
+        ChatRole::User => "user",
+        ChatRole::Assistant => "assistant",
+    }
+}
+
+/// Messages of a Mistral prompt: `[INST]` blocks are user turns, the text
+/// between them is the assistant's. A prompt without `[INST]` is one user turn
+fn parse_mistral(prompt: &str) -> Vec<ChatMessage> {
+    let mut messages = Vec::new();
+    let mut rest = prompt.trim();
+    while let Some(start) = rest.find("[INST]") {
+        let answer = clean_turn(&rest[..start]);
+        if !answer.is_empty() && !messages.is_empty() {
+            messages.push(ChatMessage::assistant(answer));
+        }
+        rest = &rest[start + "[INST]".len()..];
+        let end = rest.find("[/INST]").unwrap_or(rest.len());
+        messages.push(ChatMessage::user(rest[..end].trim()));
+        rest = rest.get(end + "[/INST]".len()..).unwrap_or_default();
+    }
+    let tail = clean_turn(rest);
+    if messages.is_empty() {
+        messages.push(ChatMessage::user(tail));
+    } else if !tail.is_empty() {
+        messages.push(ChatMessage::assistant(tail));
+    }
+    messages
+}
+
+fn clean_turn(text: &str) -> String {
+    text.replace("<s>", "").replace("</s>", "").trim().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chat_templates() -> Result<()> {
+        let messages = [ChatMessage::system("You are Kai."), ChatMessage::user("hello")];
+        assert_eq!(ChatTemplate::Mistral.render(&messages), "<s>[INST] You are Kai.\n\nhello [/INST]");
+        assert_eq!(
+            ChatTemplate::ChatMl.render(&messages),
+            "<|im_start|>system\nYou are Kai.<|im_end|>\n<|im_start|>user\nhello<|im_end|>\n<|im_start|>assistant\n"
+        );
+        assert_eq!(
+            ChatTemplate::Llama3.instruction("hello"),
+            "<|begin_of_text|><|start_header_id|>user<|end_header_id|>\n\nhello<|eot_id|><|start_header_id|>assistant<|end_header_id|>\n\n"
+        );
+        assert_eq!(
+            ChatTemplate::Gemma.render(&messages),
+            "<bos><start_of_turn>user\nYou are Kai.\n\nhello<end_of_turn>\n<start_of_turn>model\n"
+        );
+
+        // memory prompts written for Mistral are re-rendered, answers included
+        let adapted = ChatTemplate::ChatMl.adapt("<s>[INST] hi [/INST]Hello!</s>[INST] Rate it [/INST]");
+        assert_eq!(
+            adapted,
+            "<|im_start|>user\nhi<|im_end|>\n<|im_start|>assistant\nHello!<|im_end|>\n<|im_start|>user\nRate it<|im_end|>\n<|im_start|>assistant\n"
+        );
+        assert_eq!(ChatTemplate::last_user_turn(&adapted), "Rate it");
+        assert_eq!(ChatTemplate::last_user_turn("[INST] one [/INST]"), "one");
+
+        assert_eq!(ChatTemplate::detect("{% if %}<|im_start|>{{ role }}"), Some(ChatTemplate::ChatMl));
+        assert_eq!(ChatTemplate::from_vocab(|t| t == "<|eot_id|>"), Some(ChatTemplate::Llama3));
+        assert_eq!(ChatTemplate::from_vocab(|_| false), None);
+        assert_eq!("llama-3".parse::<ChatTemplate>()?, ChatTemplate::Llama3);
+        assert!("alpaca".parse::<ChatTemplate>().is_err());
+        Ok(())
+    }
+}
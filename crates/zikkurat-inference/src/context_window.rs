@@ -0,0 +1,135 @@
+//! Context window of the loaded model
+//!
+//! Taken from the model's own `config.json` (max positions, sliding window,
+//! rope theta) instead of Mistral-7B defaults. Prompt and answer budgets are
+//! derived from it for every request.
+
+use candle_transformers::models::mistral::Config;
+
+/// Shortest answer still worth generating when the prompt is long
+const MIN_ANSWER_TOKENS: usize = 64;
+/// An answer may take at most this share of the window
+const MAX_ANSWER_SHARE: f64 = 0.5;
+
+/// Attention span of the model
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ContextWindow {
+    /// Rotary embeddings are precomputed up to this position
+    pub max_positions: usize,
+    /// Tokens further back than this are masked out
+    pub sliding_window: Option<usize>,
+    pub rope_theta: f64,
+    /// `--max-context`: long-context models (Mistral-Nemo, 128k+) are cut to
+    /// what fits in memory, the KV cache grows with every position
+    pub limit: Option<usize>,
+}
+
+impl ContextWindow {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            max_positions: config.max_position_embeddings,
+            sliding_window: config.sliding_window,
+            rope_theta: config.rope_theta,
+            limit: None,
+        }
+    }
+
+    /// Caps the window at `max_tokens` (0 = the model's full window)
+    pub fn with_limit(self, max_tokens: usize) -> Self {
+        let limit = (max_tokens > 0 && max_tokens < self.full_tokens()).then_some(max_tokens);
+        Self { limit, ..self }
+    }
+
+    /// Shrinks the config to the capped window: rotary tables are precomputed
+    /// for `max_position_embeddings`, a 1M-position table is wasted memory
+    pub fn apply_to(&self, config: &mut Config) {
+        config.max_position_embeddings = config.max_position_embeddings.min(self.tokens());
+    }
+
+    fn full_tokens(&self) -> usize {
+        match self.sliding_window {
+            Some(window) => window.min(self.max_positions),
+            None => self.max_positions,
+        }
+    }
+
+    /// Tokens the model sees at once: prompt + answer must fit here
+    pub fn tokens(&self) -> usize {
+        let full = self.full_tokens();
+        self.limit.map_or(full, |limit| limit.min(full))
+    }
+
+    /// Caps the requested answer length to its share of the window
+    pub fn answer_budget(&self, requested: usize) -> usize {
+        let cap = (self.tokens() as f64 * MAX_ANSWER_SHARE) as usize;
+        requested.min(cap).max(1)
+    }
+
+    /// Tokens left for the prompt when `answer` tokens are reserved
+    pub fn prompt_budget(&self, answer: usize) -> usize {
+        self.tokens().saturating_sub(answer)
+    }
+
+    /// Answer length that fits after `prompt_tokens`, `None` if the prompt leaves no room
+    pub fn fit_answer(&self, prompt_tokens: usize, requested: usize) -> Option<usize> {
+        let room = self.tokens().saturating_sub(prompt_tokens);
+        (room > 0 && room >= MIN_ANSWER_TOKENS.min(requested)).then(|| requested.min(room))
+    }
+}
+
+impl std::fmt::Display for ContextWindow {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} tokens", self.tokens())?;
+        if let Some(window) = self.sliding_window {
+            write!(f, " (sliding window {}, max positions {})", window, self.max_positions)?;
+        } else if self.limit.is_some() {
+            write!(f, " (limited by --max-context, model supports {})", self.max_positions)?;
+        }
+        write!(f, ", rope θ={}", self.rope_theta)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_budgets_follow_config() {
+        // Mistral-7B v0.1: 32k positions but a 4k sliding window
+        let v01 = ContextWindow::from_config(&Config::config_7b_v0_1(false));
+        assert_eq!(v01.tokens(), 4096);
+        assert_eq!(v01.answer_budget(4000), 2048);
+        assert_eq!(v01.prompt_budget(512), 3584);
+
+        // v0.2 dropped the sliding window
+        let v02 = ContextWindow {
+            sliding_window: None,
+            rope_theta: 1e6,
+            ..v01
+        };
+        assert_eq!(v02.tokens(), 32768);
+
+        assert_eq!(v01.fit_answer(1000, 512), Some(512));
+        assert_eq!(v01.fit_answer(4000, 512), Some(96));
+        assert_eq!(v01.fit_answer(4090, 512), None);
+    }
+
+    #[test]
+    fn test_long_context_limit() {
+        // Mistral-Nemo: 1M positions, no sliding window, explicit head_dim
+        let mut nemo = Config::config_7b_v0_1(false);
+        nemo.max_position_embeddings = 1_024_000;
+        nemo.sliding_window = None;
+        nemo.head_dim = Some(128);
+
+        let window = ContextWindow::from_config(&nemo).with_limit(131_072);
+        assert_eq!(window.tokens(), 131_072);
+        assert_eq!(window.prompt_budget(1024), 130_048);
+        window.apply_to(&mut nemo);
+        assert_eq!(nemo.max_position_embeddings, 131_072);
+
+        // a limit above the model's window changes nothing
+        let v01 = ContextWindow::from_config(&Config::config_7b_v0_1(false)).with_limit(32_768);
+        assert_eq!((v01.tokens(), v01.limit), (4096, None));
+    }
+}
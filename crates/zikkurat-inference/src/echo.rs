@@ -0,0 +1,129 @@
+//! Rule-based echo model for `--smoke-test`
+//!
+//! Stands in for Mistral so CI and new contributors can run the whole chat and
+//! memory flow in seconds, without downloading the weights. Words are tokens,
+//! and the answer is built from the prompt by a few fixed rules: the user's
+//! message is echoed back, the first line of injected memory is quoted,
+//! extraction prompts that want JSON get an empty list, and scoring prompts
+//! that want a single number get a neutral 0.5.
+
+use anyhow::Result;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+use crate::chat_template::ChatTemplate;
+use crate::context_window::ContextWindow;
+use zikkurat_core::totems::episodic::LlmPipeline;
+
+/// Window of the echo model; long enough for any prompt the tests build
+const ECHO_WINDOW_TOKENS: usize = 8192;
+/// Stands in for BOS when special tokens are requested
+const BOS_TOKEN: u32 = 1;
+
+/// Markers that start the user's message in the chat prompts
+const USER_MARKERS: &[&str] = &["User's question:", "User:"];
+/// Markers of prompts that want a single number, e.g. the session's emotional state
+const NUMBER_MARKERS: &[&str] = &["Верни только число", "Return only a number"];
+/// Markers of injected memory
+const MEMORY_MARKERS: &[&str] = &["PAST MEMORY:", "KNOWLEDGE:", "Current conversation:"];
+
+/// Deterministic model without weights
+#[derive(Debug, Clone, Copy, Default)]
+pub struct EchoModel;
+
+impl EchoModel {
+    pub fn window() -> ContextWindow {
+        ContextWindow {
+            max_positions: ECHO_WINDOW_TOKENS,
+            sliding_window: None,
+            rope_theta: 10_000.0,
+            limit: None,
+        }
+    }
+
+    /// One id per whitespace-separated word
+    pub fn encode(&self, text: &str, add_special_tokens: bool) -> Vec<u32> {
+        let words = text.split_whitespace().map(|word| {
+            let mut hasher = DefaultHasher::new();
+            word.hash(&mut hasher);
+            hasher.finish() as u32
+        });
+        add_special_tokens.then_some(BOS_TOKEN).into_iter().chain(words).collect()
+    }
+
+    /// Answer to `prompt`, at most `max_tokens` words
+    pub fn reply(&self, prompt: &str, max_tokens: usize) -> String {
+        let instruction = instruction(prompt);
+        if instruction.contains("JSON") {
+            return "[]".to_string();
+        }
+        if NUMBER_MARKERS.iter().any(|marker| instruction.contains(marker)) {
+            return "0.5".to_string();
+        }
+
+        let message = USER_MARKERS
+            .iter()
+            .find_map(|marker| instruction.rsplit_once(marker).map(|(_, rest)| rest))
+            .map(|rest| rest.split("\n\n").next().unwrap_or_default())
+            .unwrap_or_else(|| instruction.lines().find(|l| !l.trim().is_empty()).unwrap_or_default());
+        let mut reply = format!("Echo: {}", message.split_whitespace().collect::<Vec<_>>().join(" "));
+
+        let remembered = MEMORY_MARKERS.iter().find_map(|marker| {
+            let (_, rest) = instruction.split_once(marker)?;
+            // skip blank lines, section titles and the ═══ frames
+            rest.lines()
+                .map(str::trim)
+                .find(|l| !l.is_empty() && !l.ends_with(':') && !l.starts_with('═'))
+        });
+        if let Some(line) = remembered {
+            reply.push_str("\nRemembered: ");
+            reply.push_str(line);
+        }
+
+        let words: Vec<&str> = reply.split(' ').collect();
+        if words.len() > max_tokens {
+            words[..max_tokens].join(" ")
+        } else {
+            reply
+        }
+    }
+}
+
+impl LlmPipeline for EchoModel {
+    fn generate(&self, prompt: &str, max_tokens: usize) -> Result<String> {
+        Ok(self.reply(prompt, max_tokens))
+    }
+}
+
+/// Text of the last user turn, in whatever chat template the prompt is written
+fn instruction(prompt: &str) -> &str {
+    ChatTemplate::last_user_turn(prompt)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_echo_reply() {
+        let echo = EchoModel;
+        assert_eq!(echo.reply("<s>[INST] How do I sort a Vec? [/INST]", 100), "Echo: How do I sort a Vec?");
+        assert_eq!(
+            echo.reply("<s>[INST] You are Kai.\n\nUser: hello\nthere\n\nRespond naturally.[/INST]", 100),
+            "Echo: hello there"
+        );
+        assert_eq!(
+            echo.reply(
+                "<s>[INST] PAST MEMORY:\nKNOWLEDGE:\n[preferences 0.91] The user loves sushi\n\nUser's question: what do I love?\n\nYour confident answer:[/INST]",
+                100
+            ),
+            "Echo: what do I love?\nRemembered: [preferences 0.91] The user loves sushi"
+        );
+        assert_eq!(echo.reply("[INST] Output format: Only JSON. User message: I love tea [/INST]", 100), "[]");
+        assert_eq!(echo.reply("[INST] Mood of the dialogue. Верни только число от 0.0 до 1.0. [/INST]", 100), "0.5");
+        assert_eq!(echo.reply("[INST] one two three four [/INST]", 3), "Echo: one two");
+
+        assert_eq!(echo.encode("hello  world", true).len(), 3);
+        assert_eq!(echo.encode("hello", false), echo.encode("hello", false));
+    }
+}
@@ -0,0 +1,17 @@
+//! ZIGGURAT MIND - генерация на candle
+//!
+//! Конвейер Mistral ([`pipeline::UnifiedPipeline`]), шаблоны чата, LoRA-адаптеры, окно контекста,
+//! кэш токенов и KV-кэш префикса промпта, сторож генерации и детектор повторов.
+//! Память и персоны берутся из `zikkurat-core`.
+
+pub mod chat_template;
+pub mod context_window;
+pub mod echo;
+pub mod loop_guard;
+pub mod lora;
+pub mod pipeline;
+pub mod prefix_cache;
+pub mod prompt_tokens;
+pub mod sampling;
+pub mod tokenizer;
+pub mod watchdog;
@@ -0,0 +1,182 @@
+//! Repetition loop guard
+//!
+//! Repeat penalty works on single tokens, and the model still sometimes loops a
+//! whole phrase for hundreds of tokens. The guard watches the generated tokens for
+//! an n-gram that keeps coming back within a window. The first time it sees one, the
+//! looping tokens get a much stronger penalty; if the loop goes on anyway, generation
+//! stops and the answer is cut back to one pass of the loop, at a sentence boundary.
+
+/// N-gram length that counts as a repeated phrase
+pub const DEFAULT_LOOP_NGRAM: usize = 8;
+/// Generated tokens searched for earlier copies of the last n-gram
+const WINDOW: usize = 256;
+/// Copies of the n-gram in the window that make a loop
+const MAX_REPEATS: usize = 4;
+/// Penalty on the looping tokens relative to `--repeat-penalty` (at least 1.1)
+const LOOP_PENALTY_BOOST: f32 = 1.5;
+
+/// What the generation loop should do after a token
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LoopAction {
+    Continue,
+    /// A loop started: the looping tokens are penalized from now on
+    Penalize,
+    /// The loop survived the penalty: keep only the first `keep` generated tokens
+    Stop { keep: usize },
+}
+
+/// How the last generation ended up with respect to loops
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LoopOutcome {
+    /// A loop was detected at this token and broken by the penalty
+    Penalized { at: usize },
+    /// Generation was stopped at this token, the answer was cut to `kept` tokens
+    Stopped { at: usize, kept: usize },
+}
+
+impl std::fmt::Display for LoopOutcome {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LoopOutcome::Penalized { at } => write!(f, "repetition loop at token {}, penalty raised", at),
+            LoopOutcome::Stopped { at, kept } => {
+                write!(f, "repetition loop stopped at token {}, answer cut to {} tokens", at, kept)
+            }
+        }
+    }
+}
+
+/// Loop detection settings; an n-gram of 0 disables the guard
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LoopGuard {
+    ngram: usize,
+}
+
+impl Default for LoopGuard {
+    fn default() -> Self {
+        Self::new(DEFAULT_LOOP_NGRAM)
+    }
+}
+
+impl LoopGuard {
+    pub fn new(ngram: usize) -> Self {
+        Self { ngram }
+    }
+
+    /// Starts watching one generation
+    pub fn start(&self) -> LoopWatch {
+        LoopWatch {
+            ngram: self.ngram,
+            penalized_at: None,
+            penalized: Vec::new(),
+        }
+    }
+}
+
+/// One watched generation
+#[derive(Debug)]
+pub struct LoopWatch {
+    ngram: usize,
+    /// Output length when the loop was first seen
+    penalized_at: Option<usize>,
+    /// Tokens of the looping phrase
+    penalized: Vec<u32>,
+}
+
+impl LoopWatch {
+    /// Checks the generated tokens after a new one was appended
+    pub fn observe(&mut self, output: &[u32]) -> LoopAction {
+        let n = self.ngram;
+        if n == 0 || output.len() < n * MAX_REPEATS {
+            return LoopAction::Continue;
+        }
+        let start = output.len().saturating_sub(WINDOW);
+        let tail = &output[output.len() - n..];
+        let copies: Vec<usize> = output[start..]
+            .windows(n)
+            .enumerate()
+            .filter(|(_, w)| *w == tail)
+            .map(|(i, _)| start + i)
+            .collect();
+        if copies.len() < MAX_REPEATS {
+            return LoopAction::Continue;
+        }
+
+        let period = copies[1] - copies[0];
+        let loop_start = loop_start(output, copies[0], period, start);
+        match self.penalized_at {
+            None => {
+                self.penalized_at = Some(output.len());
+                self.penalized = output[loop_start..loop_start + period].to_vec();
+                LoopAction::Penalize
+            }
+            // the penalty had a full n-gram to break the loop and did not
+            Some(at) if output.len() >= at + n => LoopAction::Stop {
+                keep: loop_start + period,
+            },
+            Some(_) => LoopAction::Continue,
+        }
+    }
+
+    /// Tokens to penalize on top of the usual repeat penalty
+    pub fn penalized_tokens(&self) -> &[u32] {
+        &self.penalized
+    }
+
+    /// Penalty for [`Self::penalized_tokens`]
+    pub fn penalty(&self, repeat_penalty: f32) -> f32 {
+        repeat_penalty.max(1.1) * LOOP_PENALTY_BOOST
+    }
+}
+
+/// Where the periodic part of the output begins: the first copy of the n-gram is usually
+/// somewhere inside the first pass of the phrase
+fn loop_start(output: &[u32], first_copy: usize, period: usize, floor: usize) -> usize {
+    let mut start = first_copy;
+    while start > floor && output[start - 1] == output[start - 1 + period] {
+        start -= 1;
+    }
+    start
+}
+
+/// Cuts the text after its last sentence end; text without one is kept whole
+pub fn cut_at_sentence(text: &str) -> &str {
+    match text.rfind(['.', '!', '?', '…', '\n']) {
+        Some(end) if end > 0 => {
+            let end = end + text[end..].chars().next().map_or(1, char::len_utf8);
+            text[..end].trim_end()
+        }
+        _ => text.trim_end(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_loop_is_penalized_then_stopped() {
+        let mut watch = LoopGuard::new(3).start();
+        let mut output: Vec<u32> = vec![1, 2, 3];
+        let mut actions = Vec::new();
+        // intro, then the phrase 10 11 12 13 forever
+        for token in [10, 11, 12, 13].iter().cycle().take(40) {
+            output.push(*token);
+            actions.push(watch.observe(&output));
+        }
+        // the fourth copy of 10 11 12 completes at the 18th token
+        assert_eq!(actions.iter().position(|a| *a == LoopAction::Penalize), Some(14));
+        assert_eq!(watch.penalized_tokens(), &[10, 11, 12, 13]);
+        assert_eq!(actions.iter().find(|a| matches!(a, LoopAction::Stop { .. })), Some(&LoopAction::Stop { keep: 7 }));
+
+        // varied text and a disabled guard never trigger
+        let mut watch = LoopGuard::new(3).start();
+        let varied: Vec<u32> = (0..300).collect();
+        assert!((1..=varied.len()).all(|i| watch.observe(&varied[..i]) == LoopAction::Continue));
+        let mut off = LoopGuard::new(0).start();
+        assert_eq!(off.observe(&[1; 100]), LoopAction::Continue);
+
+        assert_eq!(cut_at_sentence("It works. It wor"), "It works.");
+        assert_eq!(cut_at_sentence("Готово! Теперь"), "Готово!");
+        assert_eq!(cut_at_sentence("no boundary "), "no boundary");
+    }
+}
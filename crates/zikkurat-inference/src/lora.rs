@@ -0,0 +1,207 @@
+//! LoRA adapters on top of the base model
+//!
+//! A PEFT-style adapter (`adapter_config.json` + `adapter_model.safetensors`)
+//! is merged into the base weights while the model is being built:
+//! `W' = W + scale * (alpha / r) * B·A`. QLoRA adapters are plain LoRA
+//! weights as well and merge the same way into the full-precision base.
+
+use anyhow::{Context, Result};
+use candle_core::{DType, Device, Shape, Tensor};
+use candle_nn::var_builder::SimpleBackend;
+use candle_nn::VarBuilder;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+const ADAPTER_CONFIG_FILE: &str = "adapter_config.json";
+const ADAPTER_WEIGHTS_FILE: &str = "adapter_model.safetensors";
+
+#[derive(Debug, Deserialize)]
+struct AdapterConfigFile {
+    r: Option<usize>,
+    lora_alpha: Option<f64>,
+}
+
+/// Loaded LoRA adapter: (A, B) pairs keyed by the base weight name
+pub struct LoraAdapter {
+    pub path: PathBuf,
+    /// `scale * lora_alpha / r`
+    pub scaling: f64,
+    deltas: HashMap<String, (Tensor, Tensor)>,
+}
+
+impl LoraAdapter {
+    /// Loads an adapter directory. `scale` multiplies the adapter strength (1.0 = as trained)
+    pub fn load(dir: &Path, scale: f64) -> Result<Self> {
+        let config: AdapterConfigFile = serde_json::from_str(
+            &std::fs::read_to_string(dir.join(ADAPTER_CONFIG_FILE))
+                .with_context(|| format!("Missing {} in {}", ADAPTER_CONFIG_FILE, dir.display()))?,
+        )?;
+        let tensors = candle_core::safetensors::load(dir.join(ADAPTER_WEIGHTS_FILE), &Device::Cpu)
+            .with_context(|| format!("Failed to read {} in {}", ADAPTER_WEIGHTS_FILE, dir.display()))?;
+
+        let mut adapter = Self::from_tensors(tensors, config.r, config.lora_alpha, scale)?;
+        adapter.path = dir.to_path_buf();
+        Ok(adapter)
+    }
+
+    fn from_tensors(
+        tensors: HashMap<String, Tensor>,
+        r: Option<usize>,
+        lora_alpha: Option<f64>,
+        scale: f64,
+    ) -> Result<Self> {
+        let mut a_parts = HashMap::new();
+        let mut b_parts = HashMap::new();
+        for (name, tensor) in tensors {
+            match lora_target(&name) {
+                Some((target, true)) => a_parts.insert(target, tensor),
+                Some((target, false)) => b_parts.insert(target, tensor),
+                None => None,
+            };
+        }
+
+        let mut deltas = HashMap::new();
+        for (target, a) in a_parts {
+            let b = b_parts
+                .remove(&target)
+                .with_context(|| format!("Adapter has lora_A but no lora_B for {}", target))?;
+            deltas.insert(target, (a, b));
+        }
+        anyhow::ensure!(!deltas.is_empty(), "Adapter contains no LoRA weights");
+
+        // rank from the config, or from the shape of any lora_A ([r, in])
+        let rank = match r {
+            Some(r) => r,
+            None => deltas.values().next().map(|(a, _)| a.dims()[0]).unwrap_or(1),
+        };
+        let alpha = lora_alpha.unwrap_or(rank as f64);
+
+        Ok(Self {
+            path: PathBuf::new(),
+            scaling: scale * alpha / rank as f64,
+            deltas,
+        })
+    }
+
+    /// Number of base weights the adapter modifies
+    pub fn target_count(&self) -> usize {
+        self.deltas.len()
+    }
+}
+
+/// Maps a PEFT tensor name to the base weight name and whether it is lora_A.
+/// `base_model.model.model.layers.0.self_attn.q_proj.lora_A.weight`
+/// → `model.layers.0.self_attn.q_proj.weight`
+fn lora_target(name: &str) -> Option<(String, bool)> {
+    let name = name.strip_prefix("base_model.model.").unwrap_or(name);
+    for (marker, is_a) in [(".lora_A.", true), (".lora_B.", false)] {
+        if let Some(pos) = name.find(marker) {
+            return Some((format!("{}.weight", &name[..pos]), is_a));
+        }
+    }
+    None
+}
+
+/// Weight backend that adds the LoRA delta to every targeted base weight
+struct LoraBackend {
+    base: Box<dyn SimpleBackend>,
+    adapter: LoraAdapter,
+}
+
+impl LoraBackend {
+    fn merge(&self, name: &str, weight: Tensor, dtype: DType, dev: &Device) -> candle_core::Result<Tensor> {
+        let Some((a, b)) = self.adapter.deltas.get(name) else {
+            return Ok(weight);
+        };
+        let a = a.to_device(dev)?.to_dtype(DType::F32)?;
+        let b = b.to_device(dev)?.to_dtype(DType::F32)?;
+        let delta = b.matmul(&a)?.affine(self.adapter.scaling, 0.)?;
+        (weight.to_dtype(DType::F32)? + delta)?.to_dtype(dtype)
+    }
+}
+
+impl SimpleBackend for LoraBackend {
+    fn get(
+        &self,
+        s: Shape,
+        name: &str,
+        h: candle_nn::Init,
+        dtype: DType,
+        dev: &Device,
+    ) -> candle_core::Result<Tensor> {
+        let weight = self.base.get(s, name, h, dtype, dev)?;
+        self.merge(name, weight, dtype, dev)
+    }
+
+    fn get_unchecked(&self, name: &str, dtype: DType, dev: &Device) -> candle_core::Result<Tensor> {
+        let weight = self.base.get_unchecked(name, dtype, dev)?;
+        self.merge(name, weight, dtype, dev)
+    }
+
+    fn contains_tensor(&self, name: &str) -> bool {
+        self.base.contains_tensor(name)
+    }
+}
+
+/// VarBuilder over the base safetensors, with the adapter merged in if given
+pub fn var_builder_with_adapter(
+    filenames: &[PathBuf],
+    dtype: DType,
+    device: &Device,
+    adapter: Option<LoraAdapter>,
+) -> Result<VarBuilder<'static>> {
+    let base = unsafe { candle_core::safetensors::MmapedSafetensors::multi(filenames)? };
+    let backend: Box<dyn SimpleBackend> = match adapter {
+        Some(adapter) => Box::new(LoraBackend {
+            base: Box::new(base),
+            adapter,
+        }),
+        None => Box::new(base),
+    };
+    Ok(VarBuilder::from_backend(backend, dtype, device.clone()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lora_merge() -> Result<()> {
+        assert_eq!(
+            lora_target("base_model.model.model.layers.3.self_attn.q_proj.lora_B.default.weight"),
+            Some(("model.layers.3.self_attn.q_proj.weight".to_string(), false))
+        );
+
+        let dev = Device::Cpu;
+        let name = "model.layers.0.self_attn.q_proj.weight";
+        let base: HashMap<String, Tensor> =
+            [(name.to_string(), Tensor::zeros((2, 2), DType::F32, &dev)?)].into();
+        let adapter = LoraAdapter::from_tensors(
+            [
+                (
+                    "base_model.model.model.layers.0.self_attn.q_proj.lora_A.weight".to_string(),
+                    Tensor::new(&[[1f32, 2.]], &dev)?,
+                ),
+                (
+                    "base_model.model.model.layers.0.self_attn.q_proj.lora_B.weight".to_string(),
+                    Tensor::new(&[[1f32], [3.]], &dev)?,
+                ),
+            ]
+            .into(),
+            Some(1),
+            Some(2.0),
+            0.5,
+        )?;
+        assert_eq!(adapter.scaling, 1.0);
+
+        let vb = VarBuilder::from_backend(
+            Box::new(LoraBackend { base: Box::new(base), adapter }),
+            DType::F32,
+            dev,
+        );
+        let merged = vb.get((2, 2), name)?.to_vec2::<f32>()?;
+        assert_eq!(merged, vec![vec![1., 2.], vec![3., 6.]]);
+        Ok(())
+    }
+}
@@ -0,0 +1,563 @@
+//! Generation pipeline
+//!
+//! [`UnifiedPipeline`] owns the model (or the `--smoke-test` echo stand-in) and
+//! runs one request: prompt encoding with the paragraph token cache, chunked
+//! prefill that resumes from the kept prompt prefix, sampling with the repeat penalty and the loop guard, and the
+//! watchdog's wall-clock limit. With a limit the generation runs on a worker thread:
+//! a forward pass that hangs leaves the pipeline without a model
+//! ([`UnifiedPipeline::needs_reload`]) instead of blocking the caller.
+
+use anyhow::{bail, Error as E, Result};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::time::Instant;
+use candle_core::{DType, Device, Tensor};
+use candle_transformers::generation::{LogitsProcessor, Sampling};
+use candle_transformers::models::mistral::Model as Mistral;
+use tokenizers::Tokenizer;
+
+use zikkurat_core::demiurge::AdapterConfig;
+use zikkurat_core::logos::metrics;
+use zikkurat_core::logos::profiling::{self, Stage};
+use zikkurat_core::totems::episodic::SamplingRecord;
+
+use crate::chat_template::ChatTemplate;
+use crate::context_window::ContextWindow;
+use crate::echo::EchoModel;
+use crate::loop_guard::{self, LoopAction, LoopGuard, LoopOutcome};
+use crate::prefix_cache::{Prefill, PrefixCache};
+use crate::prompt_tokens::PromptTokenCache;
+use crate::watchdog::Watchdog;
+
+/// Prompt tokens per forward pass during prefill. Within the window every cached
+/// position is visible, so chunking does not change what a token attends to
+pub const PREFILL_CHUNK: usize = 2048;
+
+/// What produces the tokens: the real model or the `--smoke-test` stand-in
+pub enum Backend {
+    Mistral { model: Mistral, tokenizer: Box<Tokenizer> },
+    Echo(EchoModel),
+    /// The model is held by a generation that never returned
+    Stalled,
+}
+
+impl Backend {
+    fn encode(&self, text: &str, add_special_tokens: bool) -> Result<Vec<u32>> {
+        match self {
+            Backend::Mistral { tokenizer, .. } => {
+                Ok(tokenizer.encode(text, add_special_tokens).map_err(E::msg)?.get_ids().to_vec())
+            }
+            Backend::Echo(echo) => Ok(echo.encode(text, add_special_tokens)),
+            Backend::Stalled => bail!(STALLED),
+        }
+    }
+
+    fn forward(&mut self, input: &Tensor, start_pos: usize) -> Result<Tensor> {
+        match self {
+            Backend::Mistral { model, .. } => Ok(model.forward(input, start_pos)?),
+            Backend::Echo(_) => anyhow::bail!("The echo model has no weights to run"),
+            Backend::Stalled => bail!(STALLED),
+        }
+    }
+}
+
+const STALLED: &str = "The model stopped responding and must be reloaded";
+
+/// What the generation worker sends back
+enum Progress {
+    Text(String),
+    Done(Box<UnifiedPipeline>, Result<String>),
+}
+
+pub struct UnifiedPipeline {
+    backend: Backend,
+    pub device: Device,
+    repeat_penalty: f32,
+    repeat_last_n: usize,
+    temperature: f64,
+    top_k: Option<usize>,
+    top_p: Option<f64>,
+    pub last_generated_tokens: usize,
+    last_prompt_tokens: usize,
+    /// Context window from the model's config.json
+    pub window: ContextWindow,
+    /// LoRA adapter merged into the weights
+    pub adapter: Option<AdapterConfig>,
+    /// Wall-clock limit for one `run`
+    pub watchdog: Watchdog,
+    /// The last `run` was aborted by the watchdog
+    last_timed_out: bool,
+    /// Phrase repetition detection in `run`
+    pub loop_guard: LoopGuard,
+    /// What the loop guard did in the last `run`
+    last_loop: Option<LoopOutcome>,
+    /// Token ids of prompt paragraphs that repeat between turns
+    pub prompt_tokens: PromptTokenCache,
+    /// Prompt format of the model; its stop token ends generation
+    pub chat_template: ChatTemplate,
+    /// Model state after the prompt prefix shared between turns
+    pub prefix_cache: PrefixCache<Mistral>,
+}
+
+impl UnifiedPipeline {
+    /// Очищает KV кэш между запросами
+    pub fn clear_cache(&mut self) {
+        if let Backend::Mistral { model, .. } = &mut self.backend {
+            model.clear_kv_cache();
+        }
+    }
+
+    // Sampling knobs mirror the CLI flags one to one; the rest is set on the fields after construction
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        backend: Backend,
+        device: Device,
+        window: ContextWindow,
+        temperature: Option<f64>,
+        top_p: Option<f64>,
+        top_k: Option<usize>,
+        repeat_penalty: f32,
+        repeat_last_n: usize,
+    ) -> Self {
+        let temperature = temperature.unwrap_or(0.);
+
+        Self {
+            backend,
+            device,
+            repeat_penalty,
+            repeat_last_n,
+            temperature,
+            top_k,
+            top_p,
+            last_generated_tokens: 0,
+            last_prompt_tokens: 0,
+            window,
+            adapter: None,
+            watchdog: Watchdog::default(),
+            last_timed_out: false,
+            loop_guard: LoopGuard::default(),
+            last_loop: None,
+            prompt_tokens: PromptTokenCache::default(),
+            chat_template: ChatTemplate::default(),
+            prefix_cache: PrefixCache::default(),
+        }
+    }
+
+    /// Состояние сэмплирования последнего вызова `run` для сохранения в Turn
+    pub fn sampling_record(&self, seed: u64, max_tokens: usize, prompt: &str) -> SamplingRecord {
+        SamplingRecord {
+            seed,
+            temperature: self.temperature,
+            top_p: self.top_p,
+            top_k: self.top_k,
+            repeat_penalty: self.repeat_penalty,
+            repeat_last_n: self.repeat_last_n,
+            max_tokens,
+            prompt_tokens: self.last_prompt_tokens,
+            generated_tokens: self.last_generated_tokens,
+            prompt: prompt.to_string(),
+        }
+    }
+
+    /// Восстанавливает параметры сэмплирования из записи хода (для `--replay`)
+    pub fn apply_sampling(&mut self, record: &SamplingRecord) {
+        self.temperature = record.temperature;
+        self.top_p = record.top_p;
+        self.top_k = record.top_k;
+        self.repeat_penalty = record.repeat_penalty;
+        self.repeat_last_n = record.repeat_last_n;
+    }
+
+    /// Update temperature for generation
+    pub fn set_temperature(&mut self, temp: f64) {
+        self.temperature = temp;
+    }
+
+    /// Get current temperature
+    pub fn get_temperature(&self) -> f64 {
+        self.temperature
+    }
+
+    /// Число токенов текста (без BOS)
+    pub fn count_tokens(&self, text: &str) -> Result<usize> {
+        Ok(self.backend.encode(text, false)?.len())
+    }
+
+    /// Токены промпта с BOS; абзацы, не изменившиеся с прошлых ходов, берутся из кэша
+    pub fn encode_prompt(&self, prompt: &str) -> Result<Vec<u32>> {
+        self.prompt_tokens.encode(prompt, |text, special| self.backend.encode(text, special))
+    }
+
+    /// Прогоняет промпт с позиции `start` через модель кусками по `PREFILL_CHUNK` токенов:
+    /// маска внимания на длинный промпт (Nemo, 128k) целиком не помещается в память.
+    /// Логиты последнего токена
+    fn prefill(&mut self, tokens: &[u32], start: usize) -> Result<Tensor> {
+        let mut logits = None;
+        for (i, chunk) in tokens[start..].chunks(PREFILL_CHUNK).enumerate() {
+            let input = Tensor::new(chunk, &self.device)?.unsqueeze(0)?;
+            logits = Some(self.backend.forward(&input, start + i * PREFILL_CHUNK)?);
+        }
+        logits.ok_or_else(|| anyhow::anyhow!("Empty prompt"))
+    }
+
+    /// Prefills the prompt into a KV cache that starts either empty or from the kept
+    /// prefix state; the state at a newly found prefix boundary is kept for later turns
+    fn prefill_prompt(&mut self, tokens: &[u32]) -> Result<Tensor> {
+        let Backend::Mistral { model, .. } = &mut self.backend else {
+            anyhow::bail!("The echo model has no weights to run");
+        };
+        // whatever the previous call left in the KV cache is stale
+        let start = match self.prefix_cache.plan(tokens) {
+            Prefill::Resume { from } => {
+                *model = self.prefix_cache.state().expect("resume is planned only with a kept state").clone();
+                from
+            }
+            Prefill::Split { at } => {
+                model.clear_kv_cache();
+                self.prefill(&tokens[..at], 0)?;
+                if let Backend::Mistral { model, .. } = &self.backend {
+                    self.prefix_cache.store(&tokens[..at], model.clone());
+                }
+                at
+            }
+            Prefill::Whole => {
+                model.clear_kv_cache();
+                0
+            }
+        };
+        tracing::Span::current().record("prefix_tokens", start);
+        self.prefill(tokens, start)
+    }
+
+    /// The last generation hung: the model is lost until the caller loads it again
+    pub fn needs_reload(&self) -> bool {
+        matches!(self.backend, Backend::Stalled)
+    }
+
+    pub fn run(&mut self, prompt: &str, sample_len: usize, seed: u64) -> Result<String> {
+        self.generate_watched(prompt, sample_len, seed, None)
+    }
+
+    /// `run` that passes the answer to `on_text` piece by piece as it is generated.
+    /// The pieces are a preview: a loop cut at the end trims the returned answer
+    pub fn run_streaming(
+        &mut self,
+        prompt: &str,
+        sample_len: usize,
+        seed: u64,
+        on_text: &mut dyn FnMut(&str),
+    ) -> Result<String> {
+        self.generate_watched(prompt, sample_len, seed, Some(on_text))
+    }
+
+    /// `generate` under the watchdog: on a worker thread that owns the pipeline, so
+    /// the caller stops waiting when the limit passes even if a step never returns.
+    /// The pipeline left behind keeps the settings but has no model
+    fn generate_watched(
+        &mut self,
+        prompt: &str,
+        sample_len: usize,
+        seed: u64,
+        mut on_text: Option<&mut dyn FnMut(&str)>,
+    ) -> Result<String> {
+        if self.needs_reload() {
+            bail!(STALLED);
+        }
+        let Some(give_up) = self.watchdog.give_up_after() else {
+            return self.generate(prompt, sample_len, seed, on_text);
+        };
+
+        let stalled = self.stalled();
+        let mut worker = std::mem::replace(self, stalled);
+        let (tx, rx) = mpsc::channel();
+        let (prompt, stream, span) = (prompt.to_string(), on_text.is_some(), tracing::Span::current());
+        std::thread::spawn(move || {
+            let _span = span.entered();
+            let text_tx = tx.clone();
+            let mut forward = |text: &str| {
+                let _ = text_tx.send(Progress::Text(text.to_string()));
+            };
+            let on_text = stream.then_some(&mut forward as &mut dyn FnMut(&str));
+            let result = worker.generate(&prompt, sample_len, seed, on_text);
+            let _ = tx.send(Progress::Done(Box::new(worker), result));
+        });
+
+        let deadline = Instant::now() + give_up;
+        loop {
+            match rx.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+                Ok(Progress::Text(text)) => {
+                    if let Some(on_text) = on_text.as_mut() {
+                        on_text(&text);
+                    }
+                }
+                Ok(Progress::Done(worker, result)) => {
+                    *self = *worker;
+                    return result;
+                }
+                Err(RecvTimeoutError::Timeout) => {
+                    self.last_timed_out = true;
+                    metrics::record_timeout();
+                    println!(
+                        "\n⏱️  Generation did not return within {}s (limit {}s): the model is not responding and will be reloaded",
+                        give_up.as_secs(),
+                        self.watchdog.limit().map_or(0, |l| l.as_secs()),
+                    );
+                    bail!("Generation timed out after {}s", give_up.as_secs());
+                }
+                Err(RecvTimeoutError::Disconnected) => bail!("Generation worker panicked, the model must be reloaded"),
+            }
+        }
+    }
+
+    /// Settings of this pipeline without the model and the caches, left in place while
+    /// a worker generates
+    fn stalled(&self) -> Self {
+        let mut pipeline = Self::new(
+            Backend::Stalled,
+            self.device.clone(),
+            self.window,
+            Some(self.temperature),
+            self.top_p,
+            self.top_k,
+            self.repeat_penalty,
+            self.repeat_last_n,
+        );
+        pipeline.adapter = self.adapter.clone();
+        pipeline.watchdog = self.watchdog;
+        pipeline.loop_guard = self.loop_guard;
+        pipeline.chat_template = self.chat_template;
+        pipeline
+    }
+
+    #[tracing::instrument(
+        name = "pipeline_run",
+        skip(self, prompt, on_text),
+        fields(prompt_tokens, prefix_tokens, generated_tokens, tokens_per_second)
+    )]
+    fn generate(
+        &mut self,
+        prompt: &str,
+        sample_len: usize,
+        seed: u64,
+        mut on_text: Option<&mut dyn FnMut(&str)>,
+    ) -> Result<String> {
+        let tokenize_timer = profiling::time(Stage::Tokenize);
+        let mut tokens = self.encode_prompt(prompt)?;
+        drop(tokenize_timer);
+        self.last_prompt_tokens = tokens.len();
+        tracing::Span::current().record("prompt_tokens", tokens.len());
+
+        // Prompt + answer must fit the window, positions past it don't exist in the model
+        let Some(sample_len) = self.window.fit_answer(tokens.len(), sample_len) else {
+            anyhow::bail!(
+                "Prompt of {} tokens does not fit the context window of {}",
+                tokens.len(),
+                self.window
+            );
+        };
+
+        let eos_token = match &self.backend {
+            Backend::Mistral { tokenizer, .. } => {
+                let vocab = tokenizer.get_vocab(false);
+                vocab
+                    .get(self.chat_template.stop_token())
+                    .or_else(|| vocab.get("</s>"))
+                    .copied()
+                    .unwrap_or(2)
+            }
+            Backend::Echo(echo) => {
+                let echo = *echo;
+                return Ok(self.run_echo(echo, prompt, sample_len, on_text));
+            }
+            Backend::Stalled => bail!(STALLED),
+        };
+
+        let mut generated_tokens = 0usize;
+
+        let temperature = self.temperature;
+        let sampling = if temperature <= 0. {
+            Sampling::ArgMax
+        } else {
+            match (self.top_k, self.top_p) {
+                (None, None) => Sampling::All { temperature },
+                (Some(k), None) => Sampling::TopK { k, temperature },
+                (None, Some(p)) => Sampling::TopP { p, temperature },
+                (Some(k), Some(p)) => Sampling::TopKThenTopP { k, p, temperature },
+            }
+        };
+        // Fresh RNG per call: the output depends only on (prompt, params, seed)
+        let mut logits_processor = LogitsProcessor::from_sampling(seed, sampling);
+
+        let start_gen = std::time::Instant::now();
+        let mut output_tokens = Vec::new();
+        // Bytes of the decoded answer already passed to `on_text`
+        let mut streamed = 0usize;
+        let watch = self.watchdog.start();
+        self.last_timed_out = false;
+        let mut loop_watch = self.loop_guard.start();
+        self.last_loop = None;
+
+        for index in 0..sample_len {
+            if watch.expired() {
+                self.last_timed_out = true;
+                break;
+            }
+
+            let forward_timer = profiling::time(Stage::Forward);
+            let logits = if index == 0 {
+                self.prefill_prompt(&tokens)?
+            } else {
+                let start_pos = tokens.len() - 1;
+                let input = Tensor::new(&tokens[start_pos..], &self.device)?.unsqueeze(0)?;
+                self.backend.forward(&input, start_pos)?
+            };
+            let logits = logits.squeeze(0)?.squeeze(0)?.to_dtype(DType::F32)?;
+            drop(forward_timer);
+
+            let sampling_timer = profiling::time(Stage::Sampling);
+            let logits = if self.repeat_penalty == 1. {
+                logits
+            } else {
+                let start_at = tokens.len().saturating_sub(self.repeat_last_n);
+                candle_transformers::utils::apply_repeat_penalty(
+                    &logits,
+                    self.repeat_penalty,
+                    &tokens[start_at..],
+                )?
+            };
+            let logits = if loop_watch.penalized_tokens().is_empty() {
+                logits
+            } else {
+                candle_transformers::utils::apply_repeat_penalty(
+                    &logits,
+                    loop_watch.penalty(self.repeat_penalty),
+                    loop_watch.penalized_tokens(),
+                )?
+            };
+
+            let next_token = logits_processor.sample(&logits)?;
+            drop(sampling_timer);
+            tokens.push(next_token);
+            output_tokens.push(next_token);
+            generated_tokens += 1;
+
+            if next_token == eos_token {
+                break;
+            }
+            if let (Some(on_text), Backend::Mistral { tokenizer, .. }) = (on_text.as_mut(), &self.backend) {
+                // A token can end mid-character: wait until the piece decodes to whole chars
+                let text = tokenizer.decode(&output_tokens, true).map_err(E::msg)?;
+                match text.get(streamed..) {
+                    Some(piece) if !piece.is_empty() && !piece.ends_with('\u{FFFD}') => {
+                        on_text(piece);
+                        streamed = text.len();
+                    }
+                    _ => {}
+                }
+            }
+            match loop_watch.observe(&output_tokens) {
+                LoopAction::Continue => {}
+                LoopAction::Penalize => {
+                    self.last_loop = Some(LoopOutcome::Penalized { at: generated_tokens });
+                }
+                LoopAction::Stop { keep } => {
+                    output_tokens.truncate(keep);
+                    self.last_loop = Some(LoopOutcome::Stopped {
+                        at: generated_tokens,
+                        kept: keep,
+                    });
+                    break;
+                }
+            }
+        }
+
+        let dt = start_gen.elapsed();
+        if self.last_timed_out {
+            // KV cache holds a half-finished sequence: the next request starts clean
+            self.clear_cache();
+            metrics::record_timeout();
+            println!(
+                "\n⏱️  Generation aborted by the watchdog after {:.1}s (limit {}s): {generated_tokens} tokens kept",
+                dt.as_secs_f64(),
+                self.watchdog.limit().map_or(0, |l| l.as_secs()),
+            );
+        } else {
+            println!(
+                "\n{generated_tokens} tokens generated ({:.2} token/s)",
+                generated_tokens as f64 / dt.as_secs_f64(),
+            );
+        }
+        if let Some(outcome) = self.last_loop {
+            metrics::record_loop(matches!(outcome, LoopOutcome::Stopped { .. }));
+            println!("🔁 {}", outcome);
+        }
+        self.last_generated_tokens = generated_tokens;
+        let span = tracing::Span::current();
+        span.record("generated_tokens", generated_tokens);
+        span.record("tokens_per_second", generated_tokens as f64 / dt.as_secs_f64());
+
+        let _detokenize_timer = profiling::time(Stage::Detokenize);
+        let text = match &self.backend {
+            Backend::Mistral { tokenizer, .. } => tokenizer.decode(&output_tokens, true).map_err(E::msg)?,
+            Backend::Echo(_) | Backend::Stalled => unreachable!("only the model gets here"),
+        };
+        // A loop cut mid-phrase ends at the last full sentence before the cut
+        match self.last_loop {
+            Some(LoopOutcome::Stopped { .. }) if !loop_guard::cut_at_sentence(&text).is_empty() => {
+                Ok(loop_guard::cut_at_sentence(&text).to_string())
+            }
+            _ => Ok(text),
+        }
+    }
+
+    /// `run` for `--smoke-test`: the answer comes from the echo rules at once
+    fn run_echo(
+        &mut self,
+        echo: EchoModel,
+        prompt: &str,
+        sample_len: usize,
+        on_text: Option<&mut dyn FnMut(&str)>,
+    ) -> String {
+        let answer = echo.reply(prompt, sample_len);
+        if let Some(on_text) = on_text {
+            answer.split_inclusive(' ').for_each(on_text);
+        }
+        self.last_generated_tokens = echo.encode(&answer, false).len();
+        self.last_timed_out = false;
+        println!("\n{} tokens generated (echo model)", self.last_generated_tokens);
+        answer
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_watched_generation_returns_the_pipeline() {
+        let mut pipeline = UnifiedPipeline::new(
+            Backend::Echo(EchoModel),
+            Device::Cpu,
+            EchoModel::window(),
+            None,
+            None,
+            None,
+            1.1,
+            64,
+        );
+        pipeline.watchdog = Watchdog::new(Duration::from_secs(30));
+
+        let mut pieces = Vec::new();
+        let answer = pipeline
+            .run_streaming("[INST] Привет [/INST]", 32, 0, &mut |text| pieces.push(text.to_string()))
+            .unwrap();
+        assert!(!pipeline.needs_reload());
+        assert_eq!(pieces.concat(), answer);
+
+        // the stand-in left behind by a hung worker refuses to generate
+        let mut stalled = pipeline.stalled();
+        assert!(stalled.needs_reload());
+        assert_eq!(stalled.watchdog, pipeline.watchdog);
+        assert!(stalled.run("[INST] Привет [/INST]", 32, 0).is_err());
+    }
+}
@@ -0,0 +1,156 @@
+//! Prompt prefix KV cache
+//!
+//! The persona prefix, style constraints and most of the memory sections are
+//! the same from turn to turn, yet every `run` used to clear the KV cache and
+//! prefill the whole prompt again. [`PrefixCache`] keeps the model state right
+//! after the prefix shared by recent prompts; a prompt that starts with those
+//! tokens resumes from it and prefills only the rest.
+//!
+//! The model's KV cache only grows, so a kept state can't be cut back to a
+//! shorter prefix. The boundary is guessed instead: the first prompt is
+//! prefilled whole, the next one up to where it stops matching the previous
+//! prompt, and the state at that point is kept. A prompt that diverges inside
+//! the kept prefix (persona switch, edited system prompt) sets a new boundary
+//! the same way. For candle's Mistral the state is a clone of the model: the
+//! weights are shared, only the prefix's keys and values are its own.
+
+/// Shorter shared prefixes are prefilled whole: the saving would not pay for the kept state
+pub const DEFAULT_MIN_PREFIX_TOKENS: usize = 64;
+
+/// How a prompt goes through the model
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Prefill {
+    /// Restore the kept state and prefill from this position
+    Resume { from: usize },
+    /// Prefill up to `at`, keep the state there, then prefill the rest
+    Split { at: usize },
+    /// Prefill from an empty cache
+    Whole,
+}
+
+/// Counters for `/stats perf`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PrefixStats {
+    /// Prompts resumed from the kept state
+    pub hits: usize,
+    /// Prompts prefilled from position 0
+    pub misses: usize,
+    /// Prompt tokens not prefilled thanks to the kept state
+    pub reused_tokens: usize,
+    /// Length of the kept prefix
+    pub prefix_tokens: usize,
+}
+
+/// Model state `S` after a prompt prefix
+pub struct PrefixCache<S> {
+    /// 0 turns the cache off
+    min_tokens: usize,
+    /// Tokens the kept state has processed
+    tokens: Vec<u32>,
+    state: Option<S>,
+    /// Prompt of the previous run, to find where the next one diverges
+    last_prompt: Vec<u32>,
+    stats: PrefixStats,
+}
+
+impl<S> Default for PrefixCache<S> {
+    fn default() -> Self {
+        Self::new(DEFAULT_MIN_PREFIX_TOKENS)
+    }
+}
+
+impl<S> PrefixCache<S> {
+    pub fn new(min_tokens: usize) -> Self {
+        Self {
+            min_tokens,
+            tokens: Vec::new(),
+            state: None,
+            last_prompt: Vec::new(),
+            stats: PrefixStats::default(),
+        }
+    }
+
+    /// Decides how `prompt` is prefilled. At least its last token is always left
+    /// to prefill: its logits start the answer
+    pub fn plan(&mut self, prompt: &[u32]) -> Prefill {
+        let previous = std::mem::replace(&mut self.last_prompt, prompt.to_vec());
+        if self.min_tokens == 0 || prompt.is_empty() {
+            return Prefill::Whole;
+        }
+        if self.state.is_some() && self.tokens.len() < prompt.len() && prompt.starts_with(&self.tokens) {
+            self.stats.hits += 1;
+            self.stats.reused_tokens += self.tokens.len();
+            return Prefill::Resume { from: self.tokens.len() };
+        }
+
+        self.stats.misses += 1;
+        let shared = previous
+            .iter()
+            .zip(prompt)
+            .take_while(|(a, b)| a == b)
+            .count()
+            .min(prompt.len() - 1);
+        if shared >= self.min_tokens {
+            Prefill::Split { at: shared }
+        } else {
+            Prefill::Whole
+        }
+    }
+
+    /// State to restore for [`Prefill::Resume`]
+    pub fn state(&self) -> Option<&S> {
+        self.state.as_ref()
+    }
+
+    /// Keeps the state after `tokens`, replacing the previous one
+    pub fn store(&mut self, tokens: &[u32], state: S) {
+        self.tokens = tokens.to_vec();
+        self.state = Some(state);
+    }
+
+    /// Drops the kept state; the next prompts find a new boundary
+    pub fn clear(&mut self) {
+        self.tokens.clear();
+        self.state = None;
+        self.last_prompt.clear();
+    }
+
+    pub fn stats(&self) -> PrefixStats {
+        PrefixStats {
+            prefix_tokens: self.tokens.len(),
+            ..self.stats
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_prefix_found_kept_and_replaced() {
+        let mut cache: PrefixCache<&str> = PrefixCache::new(3);
+        let persona = [1, 2, 3, 4];
+        let prompt = |tail: &[u32]| [&persona[..], tail].concat();
+
+        assert_eq!(cache.plan(&prompt(&[10, 11])), Prefill::Whole);
+        // the second prompt shows what stays the same
+        assert_eq!(cache.plan(&prompt(&[20])), Prefill::Split { at: 4 });
+        cache.store(&persona, "persona");
+        assert_eq!(cache.plan(&prompt(&[30, 31, 32])), Prefill::Resume { from: 4 });
+        assert_eq!(cache.state(), Some(&"persona"));
+        // the whole prompt is the prefix: the last token still goes through the model
+        assert_eq!(cache.plan(&persona), Prefill::Split { at: 3 });
+        cache.store(&persona[..3], "short");
+
+        // another persona: too little in common, then a new boundary
+        assert_eq!(cache.plan(&[9, 8, 7, 6, 5]), Prefill::Whole);
+        assert_eq!(cache.plan(&[9, 8, 7, 6, 1]), Prefill::Split { at: 4 });
+        let stats = cache.stats();
+        assert_eq!((stats.hits, stats.misses, stats.reused_tokens, stats.prefix_tokens), (1, 5, 4, 3));
+
+        let mut off: PrefixCache<&str> = PrefixCache::new(0);
+        off.plan(&persona);
+        assert_eq!(off.plan(&persona), Prefill::Whole);
+    }
+}
@@ -0,0 +1,238 @@
+//! Pre-tokenized prompt assembly
+//!
+//! Between turns only the user's message and part of the memory change; the
+//! persona prefix, style constraints and most of the KNOWLEDGE section stay the
+//! same. The prompt is split into paragraphs (at blank lines), each paragraph's
+//! token ids are cached by content hash, and the prompt is assembled by
+//! concatenating them. Unchanged leading paragraphs give identical leading ids,
+//! so a prefix KV cache can reuse them by comparing ids.
+//!
+//! The Mistral tokenizer puts a `▁` in front of every encoded text. A paragraph
+//! is therefore encoded after a `\n` marker whose tokens are then cut off, which
+//! gives the ids it has inside the whole prompt. The first assembled prompt is
+//! checked against a full encode; if a tokenizer does not compose this way, the
+//! cache turns itself off and every prompt is encoded whole.
+
+use anyhow::Result;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, VecDeque};
+use std::hash::{Hash, Hasher};
+
+/// Paragraphs kept; older ones are evicted first
+const DEFAULT_CAPACITY: usize = 256;
+const MARKER: &str = "\n";
+const SEPARATOR: &str = "\n\n";
+
+/// Cached paragraphs oldest first and the result of the compose check, for a warm start
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PromptTokenSnapshot {
+    pub entries: Vec<(u64, Vec<u32>)>,
+    pub composes: Option<bool>,
+}
+
+/// Token ids of prompt paragraphs, keyed by content hash
+pub struct PromptTokenCache {
+    state: Mutex<CacheState>,
+}
+
+#[derive(Default)]
+struct CacheState {
+    entries: HashMap<u64, Vec<u32>>,
+    order: VecDeque<u64>,
+    capacity: usize,
+    /// `None` until the first prompt is checked against a full encode
+    composes: Option<bool>,
+    hits: usize,
+    misses: usize,
+}
+
+impl Default for PromptTokenCache {
+    fn default() -> Self {
+        Self::new(DEFAULT_CAPACITY)
+    }
+}
+
+impl PromptTokenCache {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            state: Mutex::new(CacheState {
+                capacity,
+                ..Default::default()
+            }),
+        }
+    }
+
+    /// Token ids of `prompt` with special tokens, as `encode(prompt, true)` would give.
+    /// `encode(text, add_special_tokens)` is the tokenizer
+    pub fn encode(&self, prompt: &str, encode: impl Fn(&str, bool) -> Result<Vec<u32>>) -> Result<Vec<u32>> {
+        let mut state = self.state.lock();
+        if state.composes == Some(false) || state.capacity == 0 {
+            return encode(prompt, true);
+        }
+
+        let marker = encode(MARKER, false)?;
+        let mut ids = Vec::new();
+        for (i, paragraph) in paragraphs(prompt).enumerate() {
+            let first = i == 0;
+            let key = key(first, paragraph);
+            if let Some(cached) = state.entries.get(&key) {
+                ids.extend_from_slice(cached);
+                state.hits += 1;
+                continue;
+            }
+
+            let tokens = if first {
+                encode(paragraph, true)?
+            } else {
+                let tokens = encode(&format!("{}{}", MARKER, paragraph), false)?;
+                match tokens.strip_prefix(marker.as_slice()) {
+                    Some(rest) => rest.to_vec(),
+                    None => {
+                        state.composes = Some(false);
+                        return encode(prompt, true);
+                    }
+                }
+            };
+            ids.extend_from_slice(&tokens);
+            state.misses += 1;
+            state.insert(key, tokens);
+        }
+
+        if state.composes.is_none() {
+            let whole = encode(prompt, true)?;
+            let composes = whole == ids;
+            state.composes = Some(composes);
+            if !composes {
+                state.entries.clear();
+                state.order.clear();
+                return Ok(whole);
+            }
+        }
+        Ok(ids)
+    }
+
+    /// (hits, misses) by paragraph
+    pub fn stats(&self) -> (usize, usize) {
+        let state = self.state.lock();
+        (state.hits, state.misses)
+    }
+
+    pub fn snapshot(&self) -> PromptTokenSnapshot {
+        let state = self.state.lock();
+        let entries = state
+            .order
+            .iter()
+            .filter_map(|key| state.entries.get(key).map(|ids| (*key, ids.clone())))
+            .collect();
+        PromptTokenSnapshot {
+            entries,
+            composes: state.composes,
+        }
+    }
+
+    /// Loads paragraphs from a previous run; a tokenizer found not to compose stays off
+    pub fn restore(&self, snapshot: PromptTokenSnapshot) {
+        let mut state = self.state.lock();
+        state.composes = snapshot.composes;
+        if snapshot.composes == Some(false) {
+            return;
+        }
+        for (key, ids) in snapshot.entries {
+            state.insert(key, ids);
+        }
+    }
+}
+
+impl CacheState {
+    fn insert(&mut self, key: u64, tokens: Vec<u32>) {
+        if self.entries.insert(key, tokens).is_none() {
+            self.order.push_back(key);
+        }
+        while self.order.len() > self.capacity {
+            if let Some(oldest) = self.order.pop_front() {
+                self.entries.remove(&oldest);
+            }
+        }
+    }
+}
+
+fn key(first: bool, paragraph: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    first.hash(&mut hasher);
+    paragraph.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Paragraphs with their trailing blank line, so that they concatenate back to `prompt`
+fn paragraphs(prompt: &str) -> impl Iterator<Item = &str> {
+    let mut rest = prompt;
+    std::iter::from_fn(move || {
+        if rest.is_empty() {
+            return None;
+        }
+        let end = rest.find(SEPARATOR).map_or(rest.len(), |i| i + SEPARATOR.len());
+        let (paragraph, tail) = rest.split_at(end);
+        rest = tail;
+        Some(paragraph)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    /// Char-level tokenizer with a BOS (1) and a dummy prefix (0), like SentencePiece
+    fn encode(calls: &Cell<usize>) -> impl Fn(&str, bool) -> Result<Vec<u32>> + '_ {
+        move |text, special| {
+            calls.set(calls.get() + text.chars().count());
+            let bos = special.then_some(1);
+            Ok(bos.into_iter().chain([0]).chain(text.chars().map(|c| c as u32)).collect())
+        }
+    }
+
+    #[test]
+    fn test_assembly_matches_full_encode() {
+        let calls = Cell::new(0);
+        let cache = PromptTokenCache::default();
+        let prompt = "<s>[INST] You are Ada.\n\nKNOWLEDGE:\nuser loves rust\n\nUser: hi [/INST]";
+        let full = encode(&calls)(prompt, true).unwrap();
+
+        assert_eq!(cache.encode(prompt, encode(&calls)).unwrap(), full);
+        assert_eq!(cache.stats(), (0, 3));
+
+        // only the last paragraph is tokenized again
+        let next = prompt.replace("hi", "and go?");
+        calls.set(0);
+        assert_eq!(cache.encode(&next, encode(&calls)).unwrap(), encode(&Cell::new(0))(&next, true).unwrap());
+        assert_eq!(cache.stats(), (2, 4));
+        assert_eq!(calls.get(), MARKER.len() + MARKER.len() + "User: and go? [/INST]".len());
+
+        // a tokenizer that does not compose turns the cache off
+        let merging = |text: &str, special: bool| -> Result<Vec<u32>> {
+            Ok(special.then_some(1).into_iter().chain([text.len() as u32]).collect())
+        };
+        let cache = PromptTokenCache::default();
+        assert_eq!(cache.encode(prompt, merging).unwrap(), merging(prompt, true).unwrap());
+        assert_eq!(cache.encode(prompt, merging).unwrap(), merging(prompt, true).unwrap());
+    }
+
+    #[test]
+    fn test_snapshot_restore() {
+        let calls = Cell::new(0);
+        let cache = PromptTokenCache::default();
+        let prompt = "<s>[INST] You are Ada.\n\nUser: hi [/INST]";
+        let full = cache.encode(prompt, encode(&calls)).unwrap();
+
+        // a restarted process encodes the same prompt without the tokenizer
+        let restored = PromptTokenCache::default();
+        restored.restore(cache.snapshot());
+        assert_eq!(restored.snapshot(), cache.snapshot());
+        calls.set(0);
+        assert_eq!(restored.encode(prompt, encode(&calls)).unwrap(), full);
+        assert_eq!(restored.stats(), (2, 0));
+        assert_eq!(calls.get(), MARKER.len());
+    }
+}
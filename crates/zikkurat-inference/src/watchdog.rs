@@ -0,0 +1,82 @@
+//! Generation watchdog
+//!
+//! A forward pass occasionally hangs (driver issues) and takes the REPL with it.
+//! The generation loop checks its [`WatchGuard`] before every step and stops once
+//! the wall-clock limit is exceeded. A step that never returns cannot be stopped, so
+//! the pipeline runs the generation on a worker thread and gives up waiting after
+//! [`Watchdog::give_up_after`]; the stuck worker keeps the model, which is reloaded.
+
+use std::time::{Duration, Instant};
+
+/// A step that returns within this time after the limit is merely slow, not stuck
+const STUCK_GRACE: Duration = Duration::from_secs(5);
+
+/// Wall-clock limit for one generation, `None` = no limit
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Watchdog {
+    limit: Option<Duration>,
+}
+
+impl Watchdog {
+    /// Zero disables the watchdog
+    pub fn new(limit: Duration) -> Self {
+        Self {
+            limit: (!limit.is_zero()).then_some(limit),
+        }
+    }
+
+    pub fn limit(&self) -> Option<Duration> {
+        self.limit
+    }
+
+    /// How long to wait for a generation before calling the model stuck
+    pub fn give_up_after(&self) -> Option<Duration> {
+        self.limit.map(|limit| limit + STUCK_GRACE)
+    }
+
+    /// Starts watching one generation
+    pub fn start(&self) -> WatchGuard {
+        WatchGuard {
+            started: Instant::now(),
+            limit: self.limit,
+        }
+    }
+}
+
+/// One watched generation
+#[derive(Debug)]
+pub struct WatchGuard {
+    started: Instant,
+    limit: Option<Duration>,
+}
+
+impl WatchGuard {
+    pub fn elapsed(&self) -> Duration {
+        self.started.elapsed()
+    }
+
+    /// The generation ran past the limit and must stop
+    pub fn expired(&self) -> bool {
+        self.limit.is_some_and(|limit| self.elapsed() >= limit)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_watchdog_expires() {
+        let off = Watchdog::new(Duration::ZERO);
+        assert_eq!(off.limit(), None);
+        assert_eq!(off.give_up_after(), None);
+        assert!(!off.start().expired());
+
+        let watchdog = Watchdog::new(Duration::from_millis(20));
+        assert_eq!(watchdog.give_up_after(), Some(Duration::from_millis(20) + STUCK_GRACE));
+        let guard = watchdog.start();
+        assert!(!guard.expired());
+        std::thread::sleep(Duration::from_millis(30));
+        assert!(guard.expired());
+    }
+}
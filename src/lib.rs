@@ -0,0 +1,10 @@
+//! ZIGGURAT MIND
+//!
+//! Прежний единый крейт, теперь разделённый на `zikkurat-core` (память, персоны, поиск,
+//! хранение), `zikkurat-inference` (пайплайны candle) и `zikkurat-cli` (бинарники).
+//! Реэкспортирует их под старыми путями: `zikkurat_mind::totems::...` продолжает работать.
+
+pub use zikkurat_core::{demiurge, logos, priests, totems, utils};
+
+#[cfg(feature = "inference")]
+pub use zikkurat_inference as inference;
@@ -1 +0,0 @@
-// Placeholder for inference wrapper (Phase 1)
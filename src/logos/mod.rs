@@ -1,3 +0,0 @@
-pub mod inference;
-pub mod sampling;
-pub mod tokenizer;
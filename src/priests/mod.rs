@@ -1,3 +0,0 @@
-pub mod device;
-pub mod dummy_embeddings;
-pub mod embeddings;
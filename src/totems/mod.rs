@@ -1,5 +0,0 @@
-#![allow(dead_code)]
-
-pub mod episodic;
-pub mod retrieval;
-pub mod semantic;
@@ -1,5 +0,0 @@
-#![allow(dead_code)]
-
-pub mod vector_store;
-
-pub use vector_store::{MemoryEntry, MemoryType, VectorStore};